@@ -232,8 +232,17 @@ def create_schema(conn: sqlite3.Connection):
             INSERT INTO files_fts(rowid, name, path) VALUES (new.id, new.name, new.path);
         END;
 
+        -- Schema v3: recency index
+        CREATE INDEX IF NOT EXISTS idx_files_snapshot_mtime ON files(snapshot_id, mtime DESC);
+
+        -- Schema v4: optional content hash for rename detection
+        ALTER TABLE files ADD COLUMN content_hash TEXT;
+
+        -- Schema v5: derived cleanup flags (large/duplicate/stale bitmask)
+        ALTER TABLE files ADD COLUMN file_flags INTEGER NOT NULL DEFAULT 0;
+
         -- Set schema version to match Rust code
-        PRAGMA user_version = 2;
+        PRAGMA user_version = 5;
     """)
     conn.commit()
 
@@ -0,0 +1,16 @@
+use crate::error::{AmberError, Result};
+use crate::services::dashboard::{self, Overview};
+use crate::state::AppState;
+use tauri::State;
+
+/// Totals, last backups and overdue schedules across every job, for the
+/// dashboard. Opens every connected destination's index, so it runs off the
+/// async runtime.
+#[tauri::command]
+pub async fn get_overview(state: State<'_, AppState>) -> Result<Overview> {
+    let jobs = state.store.load_jobs()?;
+    let local = state.index_service.clone();
+    tokio::task::spawn_blocking(move || dashboard::overview(&jobs, &local, chrono::Utc::now()))
+        .await
+        .map_err(|e| AmberError::Index(format!("Overview task failed: {}", e)))
+}
@@ -1,5 +1,6 @@
 use crate::error::Result;
-use crate::services::file_service::FileEntry;
+use crate::services::cancel_token;
+use crate::services::file_service::{DirListOptions, FileEntry, FilePreview};
 use crate::state::AppState;
 use crate::types::snapshot::file_type;
 use serde::{Deserialize, Serialize};
@@ -11,8 +12,11 @@ pub struct DirEntry {
     pub name: String,
     pub path: String,
     pub is_directory: bool,
+    pub is_symlink: bool,
     pub size: u64,
     pub modified: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
 }
 
 impl From<FileEntry> for DirEntry {
@@ -21,16 +25,25 @@ impl From<FileEntry> for DirEntry {
             name: e.name,
             path: e.path,
             is_directory: e.is_dir,
+            is_symlink: e.is_symlink,
             size: e.size,
             modified: e.modified,
+            error: e.error,
         }
     }
 }
 
+/// List a directory with metadata inline; `options` sorts/filters server-side
 #[tauri::command]
-pub async fn read_dir(state: State<'_, AppState>, path: String) -> Result<Vec<DirEntry>> {
+pub async fn read_dir(
+    state: State<'_, AppState>,
+    path: String,
+    options: Option<DirListOptions>,
+) -> Result<Vec<DirEntry>> {
     let validated_path = state.validate_path(&path)?;
-    let entries = state.file_service.scan_directory(&validated_path)?;
+    let entries = state
+        .file_service
+        .list_directory(&validated_path, &options.unwrap_or_default())?;
     Ok(entries.into_iter().map(DirEntry::from).collect())
 }
 
@@ -39,7 +52,7 @@ pub async fn read_file_preview(
     state: State<'_, AppState>,
     file_path: String,
     max_lines: Option<usize>,
-) -> Result<String> {
+) -> Result<FilePreview> {
     let validated_path = state.validate_path(&file_path)?;
     state
         .file_service
@@ -64,21 +77,52 @@ pub async fn show_item_in_folder(state: State<'_, AppState>, path: String) -> Re
     state.file_service.show_in_folder(&validated_path)
 }
 
+/// Size and free space of the filesystem holding a path, plus where it is
+/// mounted and whether it is a network share
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiskStats {
+    pub total_bytes: u64,
+    pub available_bytes: u64,
+    /// None when the mount table couldn't be read
+    pub mount_point: Option<String>,
+    pub fs_type: Option<String>,
+    /// SMB, NFS, AFP and similar shares, where hardlinks and symlinks depend
+    /// on the server
+    pub is_network: bool,
+}
+
 #[tauri::command]
-pub async fn get_disk_stats(state: State<'_, AppState>, path: String) -> Result<String> {
-    use std::process::Command;
+pub async fn get_disk_stats(state: State<'_, AppState>, path: String) -> Result<DiskStats> {
+    use crate::utils::platform;
 
     let validated_path = state.validate_path(&path)?;
-    let output = Command::new("df")
-        .args(["-h", "--", &validated_path])
-        .output()?;
-    if !output.status.success() {
-        return Err(crate::error::AmberError::Io(std::io::Error::other(
-            String::from_utf8_lossy(&output.stderr).to_string(),
-        )));
-    }
+    let (total_bytes, available_bytes) = parse_df_output(&validated_path)?;
+    let mount = platform::mount_info(std::path::Path::new(&validated_path));
 
-    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    Ok(DiskStats {
+        total_bytes,
+        available_bytes,
+        is_network: mount
+            .as_ref()
+            .is_some_and(|m| platform::is_network_fs(&m.fs_type)),
+        mount_point: mount
+            .as_ref()
+            .map(|m| m.mount_point.to_string_lossy().to_string()),
+        fs_type: mount.map(|m| m.fs_type),
+    })
+}
+
+/// Whether TimeMachine snapshots at `path` can share unchanged files through
+/// hardlinks (probed once per destination). The path may not exist yet.
+#[tauri::command]
+pub async fn check_hardlink_support(state: State<'_, AppState>, path: String) -> Result<bool> {
+    let validated_path = state.validate_path_for_create(&path)?;
+    tokio::task::spawn_blocking(move || {
+        crate::services::hardlink_probe::supports_hardlinks(std::path::Path::new(&validated_path))
+    })
+    .await
+    .map_err(|e| crate::error::AmberError::Filesystem(format!("Hardlink probe failed: {}", e)))?
 }
 
 /// Get volume info for any path (returns stats of the containing filesystem)
@@ -185,7 +229,8 @@ fn parse_df_output(path: &str) -> std::io::Result<(u64, u64)> {
     Ok((total_kb * 1024, available_kb * 1024))
 }
 
-/// Search files in a volume by pattern (fuzzy filename match)
+/// Search files in a volume by pattern (fuzzy filename match). Cancelled
+/// through `volume-search:<volume path>`.
 #[tauri::command]
 pub async fn search_volume(
     state: State<'_, AppState>,
@@ -199,6 +244,8 @@ pub async fn search_volume(
     use std::sync::Mutex;
 
     let validated_path = state.validate_path(&volume_path)?;
+    let op_id = cancel_token::operation_id(cancel_token::VOLUME_SEARCH, &validated_path);
+    let cancel = state.operations.register(&op_id);
     let limit = limit.unwrap_or(50);
     let pattern_lower = pattern.to_lowercase();
     let count = AtomicUsize::new(0);
@@ -211,50 +258,59 @@ pub async fn search_volume(
         .max_depth(5)
         .parallelism(jwalk::Parallelism::RayonNewPool(4));
 
-    walker.into_iter().par_bridge().for_each(|entry| {
-        if count.load(Ordering::Relaxed) >= limit {
-            return;
-        }
+    // Cancelling stops the walk itself, not just the matching
+    let walked = walker
+        .into_iter()
+        .par_bridge()
+        .try_for_each(|entry| -> Result<()> {
+            cancel.check()?;
+            if count.load(Ordering::Relaxed) >= limit {
+                return Ok(());
+            }
 
-        if let Ok(entry) = entry {
-            let name = entry.file_name().to_string_lossy().to_string();
-            let name_lower = name.to_lowercase();
-
-            // Fuzzy match: check if pattern chars appear in order
-            if fuzzy_match(&pattern_lower, &name_lower) {
-                if let Ok(metadata) = entry.metadata() {
-                    let modified = metadata
-                        .modified()
-                        .ok()
-                        .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
-                        .map(|d| d.as_secs() as i64)
-                        .unwrap_or(0);
-
-                    let path_str = entry.path().to_string_lossy().to_string();
-                    let node = crate::types::snapshot::FileNode {
-                        id: path_str.clone(),
-                        name,
-                        node_type: if metadata.is_dir() {
-                            file_type::DIR.to_string()
-                        } else {
-                            file_type::FILE.to_string()
-                        },
-                        size: metadata.len(),
-                        modified,
-                        children: None,
-                        path: path_str,
-                    };
-
-                    let mut results_guard = results.lock().unwrap();
-                    if results_guard.len() < limit {
-                        results_guard.push(node);
-                        count.fetch_add(1, Ordering::Relaxed);
+            if let Ok(entry) = entry {
+                let name = entry.file_name().to_string_lossy().to_string();
+                let name_lower = name.to_lowercase();
+
+                // Fuzzy match: check if pattern chars appear in order
+                if fuzzy_match(&pattern_lower, &name_lower) {
+                    if let Ok(metadata) = entry.metadata() {
+                        let modified = metadata
+                            .modified()
+                            .ok()
+                            .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+                            .map(|d| d.as_secs() as i64)
+                            .unwrap_or(0);
+
+                        let path_str = entry.path().to_string_lossy().to_string();
+                        let node = crate::types::snapshot::FileNode {
+                            id: path_str.clone(),
+                            name,
+                            node_type: if metadata.is_dir() {
+                                file_type::DIR.to_string()
+                            } else {
+                                file_type::FILE.to_string()
+                            },
+                            size: metadata.len(),
+                            modified,
+                            children: None,
+                            path: path_str,
+                            link_count: None,
+                        };
+
+                        let mut results_guard = results.lock().unwrap();
+                        if results_guard.len() < limit {
+                            results_guard.push(node);
+                            count.fetch_add(1, Ordering::Relaxed);
+                        }
                     }
                 }
             }
-        }
-    });
+            Ok(())
+        });
+    state.operations.unregister(&op_id, &cancel);
 
+    walked?;
     Ok(results.into_inner().unwrap())
 }
 
@@ -460,6 +516,7 @@ pub async fn import_backup_as_job(backup_path: String) -> Result<crate::types::j
         name: manifest.job_name,
         source_path: manifest.source_path,
         dest_path: backup_path,
+        dest_subfolder: None,
         mode: crate::types::job::SyncMode::TimeMachine,
         status: crate::types::job::JobStatus::Idle,
         destination_type: Some(crate::types::job::DestinationType::Local),
@@ -469,6 +526,9 @@ pub async fn import_backup_as_job(backup_path: String) -> Result<crate::types::j
         ssh_config: None,
         cloud_config: None,
         last_run: None,
+        enabled: true,
+        auto_index: true,
+        env: Default::default(),
         snapshots: None,
     };
 
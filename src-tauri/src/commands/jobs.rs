@@ -1,10 +1,17 @@
 use crate::error::Result;
 use crate::services::cache_service;
+use crate::services::exclude_preview::{self, ExcludePreview};
+use crate::services::job_rename;
+use crate::services::job_validation::{self, JobValidation};
+use crate::services::keychain_service::KeychainService;
 use crate::services::manifest_service;
+use crate::services::ssh_check::{self, SshOutput, SshTestResult};
+use crate::services::{data_dir, ssh_askpass};
 use crate::state::AppState;
-use crate::types::job::SyncJob;
-use crate::types::manifest::ManifestSnapshot;
-use crate::utils::validation::validate_job_id;
+use crate::types::job::{DestinationType, SshConfig, SyncJob};
+use crate::types::manifest::{IndexDrift, ManifestSnapshot, RunConfig, SnapshotChanges};
+use crate::utils::validation::{validate_job_id, validate_rsync_env};
+use crate::utils::VolumeInfo;
 use futures::future::join_all;
 use serde::{Deserialize, Serialize};
 use std::path::Path;
@@ -19,6 +26,14 @@ pub struct SnapshotInfo {
     pub size_bytes: u64,
     pub file_count: u64,
     pub changes_count: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub changes: Option<SnapshotChanges>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub index_drift: Option<IndexDrift>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source_volume: Option<VolumeInfo>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub run_config: Option<RunConfig>,
     pub status: String,
     pub duration: Option<u64>,
     pub path: Option<String>,
@@ -33,6 +48,10 @@ impl SnapshotInfo {
             size_bytes: s.total_size,
             file_count: s.file_count,
             changes_count: s.changes_count.unwrap_or(0),
+            changes: s.changes,
+            index_drift: s.index_drift,
+            source_volume: s.source_volume,
+            run_config: s.run_config,
             status: s.status.as_str().to_string(),
             duration: s.duration_ms,
             path: Some(path),
@@ -183,9 +202,18 @@ pub async fn get_jobs_with_status(state: State<'_, AppState>) -> Result<Vec<JobW
     Ok(results)
 }
 
+/// Save a job. With `validate`, a dry run of the job is started in the
+/// background once it is saved and its result arrives as a `job-validation`
+/// event; the save itself doesn't wait for it.
 #[tauri::command]
-pub async fn save_job(state: State<'_, AppState>, job: SyncJob) -> Result<()> {
+pub async fn save_job(
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+    job: SyncJob,
+    validate: Option<bool>,
+) -> Result<()> {
     validate_job_id(&job.id)?;
+    validate_rsync_env(&job.env)?;
 
     // Save to local store first
     state.store.save_job(job.clone())?;
@@ -214,9 +242,70 @@ pub async fn save_job(state: State<'_, AppState>, job: SyncJob) -> Result<()> {
         }
     }
 
+    if validate.unwrap_or(false) && job.destination_type != Some(DestinationType::Cloud) {
+        tauri::async_runtime::spawn(async move {
+            use tauri::Emitter;
+            match run_job_validation(job).await {
+                Ok(validation) => {
+                    let _ = app.emit("job-validation", validation);
+                }
+                Err(e) => log::warn!("Job validation failed to run: {}", e),
+            }
+        });
+    }
+
     Ok(())
 }
 
+/// Dry-run `job` the way its backups run rsync (askpass, allowlisted
+/// environment, priority), off the async runtime
+async fn run_job_validation(job: SyncJob) -> Result<JobValidation> {
+    tokio::task::spawn_blocking(move || {
+        let service = super::rsync::get_rsync_service();
+        job_validation::validate_job(&job, service, |args| service.run_to_completion(&job, args))
+    })
+    .await
+    .map_err(|e| crate::error::AmberError::Rsync(format!("Validation task failed: {}", e)))?
+}
+
+/// Check a saved job works: source and destination reachable, excludes
+/// sane, and an `rsync -n` over the top level of the source gets through
+#[tauri::command]
+pub async fn validate_job(state: State<'_, AppState>, job_id: String) -> Result<JobValidation> {
+    validate_job_id(&job_id)?;
+    let job = state
+        .store
+        .get_job(&job_id)?
+        .ok_or_else(|| crate::error::AmberError::job_not_found(job_id.clone()))?;
+    run_job_validation(job).await
+}
+
+/// Pause (`enabled = false`) or resume a job's schedule without touching the
+/// schedule itself or the job's history
+#[tauri::command]
+pub async fn set_job_enabled(
+    state: State<'_, AppState>,
+    job_id: String,
+    enabled: bool,
+) -> Result<SyncJob> {
+    validate_job_id(&job_id)?;
+    let mut job = state
+        .store
+        .get_job(&job_id)?
+        .ok_or_else(|| crate::error::AmberError::job_not_found(job_id.clone()))?;
+    job.enabled = enabled;
+
+    state.store.save_job(job.clone())?;
+    if let Err(e) = state.store.write_job_to_destination(&job) {
+        log::warn!("Failed to write job config to destination: {}", e);
+    }
+    if let Err(e) = state.scheduler.set_job_enabled(&job_id, enabled).await {
+        log::warn!("Failed to update scheduler after set_job_enabled: {}", e);
+    }
+
+    Ok(job)
+}
+
 #[tauri::command]
 pub async fn delete_job(state: State<'_, AppState>, job_id: String) -> Result<()> {
     validate_job_id(&job_id)?;
@@ -246,6 +335,103 @@ pub async fn delete_job(state: State<'_, AppState>, job_id: String) -> Result<()
     Ok(())
 }
 
+/// Change a job's id, carrying its indexed snapshots and manifest along.
+/// Refused while the job is running or if a job already has `new_id`.
+#[tauri::command]
+pub async fn rename_job_id(
+    state: State<'_, AppState>,
+    old_id: String,
+    new_id: String,
+) -> Result<SyncJob> {
+    validate_job_id(&old_id)?;
+    if crate::commands::rsync::get_rsync_service().is_job_running(&old_id)
+        || crate::commands::rclone::get_rclone_service().is_job_running(&old_id)
+    {
+        return Err(crate::error::AmberError::JobAlreadyRunning(old_id));
+    }
+
+    let job =
+        job_rename::rename_job_id(&state.store, &state.index_service, &old_id, &new_id).await?;
+
+    match state.store.load_jobs() {
+        Ok(jobs) => {
+            if let Err(e) = state.scheduler.update_jobs(jobs).await {
+                log::warn!("Failed to update scheduler after rename_job_id: {}", e);
+            }
+        }
+        Err(e) => {
+            log::warn!("Failed to reload jobs for scheduler update: {}", e);
+        }
+    }
+
+    Ok(job)
+}
+
+/// Dry-run exclude patterns against the top level of a source before saving
+#[tauri::command]
+pub async fn preview_excludes(
+    state: State<'_, AppState>,
+    source_path: String,
+    patterns: Vec<String>,
+) -> Result<ExcludePreview> {
+    let validated_path = state.validate_path(&source_path)?;
+    exclude_preview::preview_excludes(&validated_path, &patterns)
+}
+
+/// Askpass environment for ssh commands run outside a backup. A keychain
+/// passphrase for the identity reaches ssh the way it does for backups:
+/// through SSH_ASKPASS, never argv. Empty means ssh should run in batch mode.
+pub(crate) fn keychain_askpass_env(job: &SyncJob) -> Vec<(String, String)> {
+    let helper_dir = if data_dir::is_initialized() {
+        data_dir::get().clone()
+    } else {
+        data_dir::default_data_dir()
+    };
+    let keychain = KeychainService::new();
+    ssh_askpass::env_for_job(job, &helper_dir, |key| keychain.get_ssh_passphrase(key))
+        .unwrap_or_else(|e| {
+            log::warn!("[ssh_check] SSH passphrase handoff unavailable: {}", e);
+            Vec::new()
+        })
+}
+
+/// Run ssh with `args` and the given extra environment, capturing its output
+pub(crate) fn run_ssh(args: &[String], env: Vec<(String, String)>) -> std::io::Result<SshOutput> {
+    let output = std::process::Command::new("ssh")
+        .args(args)
+        .envs(env)
+        .stdin(std::process::Stdio::null())
+        .output()?;
+    Ok(SshOutput {
+        exit_code: output.status.code(),
+        stdout: String::from_utf8_lossy(&output.stdout).to_string(),
+        stderr: String::from_utf8_lossy(&output.stderr).to_string(),
+    })
+}
+
+/// "Test Connection" for an SSH destination before the job is saved: logs in
+/// with `ssh_config` and checks the folder in `remote` is writable
+#[tauri::command]
+pub async fn test_ssh_destination(ssh_config: SshConfig, remote: String) -> Result<SshTestResult> {
+    let job = SyncJob {
+        ssh_config: Some(SshConfig {
+            enabled: true,
+            ..ssh_config.clone()
+        }),
+        ..Default::default()
+    };
+    let askpass = keychain_askpass_env(&job);
+
+    tokio::task::spawn_blocking(move || {
+        let batch_mode = askpass.is_empty();
+        ssh_check::test_destination(&ssh_config, &remote, batch_mode, |args| {
+            run_ssh(args, askpass)
+        })
+    })
+    .await
+    .map_err(|e| crate::error::AmberError::Rsync(format!("SSH test task failed: {}", e)))?
+}
+
 /// Delete backup data from the destination path
 /// This removes the entire backup directory including all snapshots
 #[tauri::command]
@@ -1,8 +1,10 @@
 // Command modules - Tauri IPC handlers
+pub mod dashboard;
 pub mod filesystem;
 pub mod jobs;
 pub mod manifest;
 pub mod migration;
+pub mod operations;
 pub mod preferences;
 pub mod rclone;
 pub mod rsync;
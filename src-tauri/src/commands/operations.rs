@@ -0,0 +1,10 @@
+use crate::error::Result;
+use crate::state::AppState;
+use tauri::State;
+
+/// Cancel the long operation registered under `op_id` (`"<kind>:<key>"`,
+/// e.g. `restore:<job id>`); returns false if none is running
+#[tauri::command]
+pub async fn cancel_operation(state: State<'_, AppState>, op_id: String) -> Result<bool> {
+    Ok(state.operations.cancel(&op_id))
+}
@@ -1,4 +1,9 @@
 use crate::error::Result;
+use crate::services::index_service::FileFlagThresholds;
+use crate::services::{
+    hardlink_probe, index_service, index_warmup, logging, parallel_restore, process_priority,
+    snapshot_commit, volume_gate, walk_pool,
+};
 use crate::state::AppState;
 use crate::types::preferences::AppPreferences;
 use tauri::State;
@@ -14,6 +19,29 @@ pub async fn set_preferences(
     preferences: AppPreferences,
 ) -> Result<AppPreferences> {
     state.store.save_preferences(&preferences)?;
+    walk_pool::configure(preferences.index_threads, preferences.background_priority)?;
+    process_priority::configure(preferences.background_priority);
+    hardlink_probe::configure(preferences.require_hardlinks);
+    index_warmup::configure(preferences.warmup_snapshot_count);
+    parallel_restore::configure(preferences.restore_workers);
+    index_service::configure_diacritic_folding(preferences.search_fold_diacritics);
+    index_service::configure_normalized_storage(preferences.normalized_index_storage);
+    index_service::configure_unicode_normalization(!preferences.preserve_raw_path_names);
+    index_service::configure_auto_compaction(preferences.compact_after_deletions);
+    index_service::configure_max_index_files(preferences.max_index_files);
+    index_service::configure_flag_thresholds(FileFlagThresholds::from_preferences(
+        preferences.large_file_mb,
+        preferences.stale_after_days,
+    ));
+    volume_gate::configure(preferences.serialize_index_with_backups);
+    snapshot_commit::configure_verification(preferences.verify_index_after_backup);
+    state
+        .file_service
+        .set_max_read_bytes(preferences.max_preview_size_mb.saturating_mul(1024 * 1024));
+    state
+        .index_service
+        .set_diacritic_folding(preferences.search_fold_diacritics)?;
+    logging::set_level(logging::parse_level(&preferences.log_level));
     Ok(preferences)
 }
 
@@ -1,11 +1,19 @@
 use crate::error::{AmberError, Result};
-use crate::services::rclone_service::{RcloneRemote, RcloneService, RcloneStatus};
+use crate::services::rclone_service::{
+    parse_stats_line, RcloneRemote, RcloneService, RcloneStatus, DEFAULT_SYNC_ATTEMPTS,
+};
+use crate::services::rsync_service::split_output;
 use crate::types::job::SyncJob;
+use std::io::{BufReader, Read};
 use std::sync::OnceLock;
+use std::time::Duration;
+
+/// Pause before re-running a sync that hit a temporary error
+const RETRY_BACKOFF: Duration = Duration::from_secs(30);
 
 static RCLONE_SERVICE: OnceLock<RcloneService> = OnceLock::new();
 
-fn get_rclone_service() -> &'static RcloneService {
+pub(crate) fn get_rclone_service() -> &'static RcloneService {
     RCLONE_SERVICE.get_or_init(RcloneService::new)
 }
 
@@ -23,38 +31,58 @@ pub async fn list_rclone_remotes() -> Result<Vec<RcloneRemote>> {
     service.list_remotes()
 }
 
-/// Run an rclone sync job for cloud backup
+/// Run an rclone sync job for cloud backup, re-running it after temporary
+/// failures up to the job's `sync_attempts`
 #[tauri::command]
 pub async fn run_rclone(job: SyncJob) -> Result<()> {
     let cloud_config = job
         .cloud_config
-        .as_ref()
+        .clone()
         .ok_or_else(|| AmberError::Rclone("Job has no cloud configuration".to_string()))?;
 
     let service = get_rclone_service();
-    let mut child = service.spawn_sync(
-        &job.id,
-        &job.source_path,
-        &cloud_config.remote_name,
-        cloud_config.remote_path.as_deref(),
-        cloud_config.bandwidth.as_deref(),
-        cloud_config.encrypt,
-    )?;
-
-    // Wait for completion
-    let status = child.wait()?;
-
-    // Mark completed
-    service.mark_completed(&job.id);
+    let attempts = cloud_config.sync_attempts.unwrap_or(DEFAULT_SYNC_ATTEMPTS);
+    tokio::task::spawn_blocking(move || {
+        service.sync_with_retries(&job.id, attempts, RETRY_BACKOFF, |_| {
+            let mut child = service.spawn_sync(&job.id, &job.source_path, &cloud_config)?;
+            let readers = [
+                child
+                    .stdout
+                    .take()
+                    .map(|out| watch_output(service, &job.id, out)),
+                child
+                    .stderr
+                    .take()
+                    .map(|err| watch_output(service, &job.id, err)),
+            ];
+            let status = child.wait();
+            for reader in readers.into_iter().flatten() {
+                let _ = reader.join();
+            }
+            service.mark_completed(&job.id);
+            Ok(status?.code())
+        })
+    })
+    .await
+    .map_err(|e| AmberError::Rclone(format!("rclone task failed: {}", e)))?
+}
 
-    if status.success() {
-        Ok(())
-    } else {
-        Err(AmberError::Rclone(format!(
-            "rclone exited with code {:?}",
-            status.code()
-        )))
-    }
+/// Read one of rclone's output streams to the end, recording its stats lines
+/// as the job's progress. Draining both pipes also keeps rclone from blocking
+/// on a full one.
+fn watch_output<R: Read + Send + 'static>(
+    service: &'static RcloneService,
+    job_id: &str,
+    output: R,
+) -> std::thread::JoinHandle<()> {
+    let job_id = job_id.to_string();
+    std::thread::spawn(move || {
+        for line in split_output(BufReader::new(output)) {
+            if let Some(progress) = parse_stats_line(&line) {
+                service.runs().record_progress(&job_id, progress);
+            }
+        }
+    })
 }
 
 /// Kill a running rclone sync job
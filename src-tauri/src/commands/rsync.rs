@@ -1,25 +1,22 @@
 #![allow(clippy::lines_filter_map_ok)]
 
 use crate::error::Result;
-use crate::services::index_service::IndexService;
-use crate::services::manifest_service;
-use crate::services::rsync_service::RsyncService;
+use crate::services::backup_runner::RunProgress;
+use crate::services::rsync_service::{
+    parse_output_line, parse_progress_line, protocol_mismatch, split_output, RsyncOutputLine,
+    RsyncProgress, RsyncService, RsyncStatus,
+};
+use crate::services::{
+    delete_guard, hardlink_probe, manifest_service, snapshot_commit, volume_gate, xattr_check,
+};
 use crate::types::job::{SyncJob, SyncMode};
 use crate::types::manifest::{ManifestSnapshot, ManifestSnapshotStatus};
 use crate::utils::validation::validate_job_id;
-use regex::Regex;
 use serde::Serialize;
+use std::collections::VecDeque;
 use std::io::{BufRead, BufReader};
 use std::sync::atomic::{AtomicBool, AtomicI64, Ordering};
-use std::sync::{Arc, OnceLock};
-
-/// Compiled regex for rsync progress line parsing (compiled once, reused)
-fn progress_regex() -> &'static Regex {
-    static RE: OnceLock<Regex> = OnceLock::new();
-    RE.get_or_init(|| {
-        Regex::new(r"^\s*([\d,]+)\s+(\d+)%\s+([\d.]+[KMG]?B/s)\s+(\d+:\d+:\d+)").unwrap()
-    })
-}
+use std::sync::{Arc, Mutex, OnceLock};
 use tauri::{Emitter, Manager};
 use tokio::time::{timeout, Duration};
 use walkdir::WalkDir;
@@ -34,10 +31,19 @@ fn rebuild_tray(app: &tauri::AppHandle) {
 
 static RSYNC_SERVICE: OnceLock<RsyncService> = OnceLock::new();
 
+/// stderr lines kept from a backup run to explain its failure
+const STDERR_TAIL_LINES: usize = 50;
+
 pub(crate) fn get_rsync_service() -> &'static RsyncService {
     RSYNC_SERVICE.get_or_init(RsyncService::new)
 }
 
+/// Check if rsync is installed; errors with install instructions if not
+#[tauri::command]
+pub async fn check_rsync() -> Result<RsyncStatus> {
+    get_rsync_service().check_installation()
+}
+
 // Event payloads for frontend
 #[derive(Clone, Serialize)]
 #[serde(rename_all = "camelCase")]
@@ -55,6 +61,8 @@ struct RsyncProgressPayload {
     speed: String,
     eta: String,
     current_file: Option<String>,
+    /// The same update as numbers, for drawing a progress bar
+    progress: Option<RsyncProgress>,
 }
 
 #[derive(Clone, Serialize)]
@@ -71,19 +79,6 @@ struct RsyncStartedPayload {
     job_id: String,
 }
 
-/// Parse rsync progress line like:
-/// "         16,384 100%    4.00MB/s    0:00:00 (xfr#2, to-chk=5/10)"
-fn parse_rsync_progress(line: &str) -> Option<(String, u8, String, String)> {
-    let caps = progress_regex().captures(line)?;
-
-    let transferred = caps.get(1)?.as_str().to_string();
-    let percentage: u8 = caps.get(2)?.as_str().parse().ok()?;
-    let speed = caps.get(3)?.as_str().to_string();
-    let eta = caps.get(4)?.as_str().to_string();
-
-    Some((transferred, percentage, speed, eta))
-}
-
 /// Calculate file count and total size for a directory
 async fn calculate_snapshot_stats(path: std::path::PathBuf) -> (u64, u64) {
     tokio::task::spawn_blocking(move || {
@@ -112,6 +107,7 @@ fn spawn_rsync_process(
     app: &tauri::AppHandle,
 ) -> Result<std::process::Child> {
     log::info!(
+        job_id = job.id.as_str(), operation = "backup";
         "[run_rsync] Command invoked for job '{}' (id: {})",
         job.name,
         job.id
@@ -132,6 +128,28 @@ fn spawn_rsync_process(
         },
     );
 
+    if let Some(warning) = hardlink_probe::check_destination(job)? {
+        log::warn!("[run_rsync] {}", warning);
+        let _ = app.emit(
+            "rsync-log",
+            RsyncLogPayload {
+                job_id: job.id.clone(),
+                message: format!("Warning: {}", warning),
+            },
+        );
+    }
+
+    if let Some(warning) = xattr_check::check_destination(job) {
+        log::warn!("[run_rsync] {}", warning);
+        let _ = app.emit(
+            "rsync-log",
+            RsyncLogPayload {
+                job_id: job.id.clone(),
+                message: format!("Warning: {}", warning),
+            },
+        );
+    }
+
     let child = service.spawn_rsync(job)?;
 
     // Emit the actual command being run
@@ -152,6 +170,7 @@ fn setup_output_streams(
     job: &SyncJob,
     app: &tauri::AppHandle,
     last_activity: Arc<AtomicI64>,
+    stderr_tail: Arc<Mutex<VecDeque<String>>>,
 ) -> (
     Option<std::thread::JoinHandle<()>>,
     Option<std::thread::JoinHandle<()>>,
@@ -162,6 +181,7 @@ fn setup_output_streams(
 
     let job_id = job.id.clone();
     let app_handle = app.clone();
+    let verbosity = job.config.verbosity;
 
     // Spawn thread to read stdout and emit events
     let stdout_handle = stdout.map(|stdout| {
@@ -169,10 +189,9 @@ fn setup_output_streams(
         let app = app_handle.clone();
         let last_activity = last_activity.clone();
         std::thread::spawn(move || {
-            let reader = BufReader::new(stdout);
             let mut current_file: Option<String> = None;
 
-            for line in reader.lines().flatten() {
+            for line in split_output(BufReader::new(stdout)) {
                 // Skip empty lines
                 if line.trim().is_empty() {
                     continue;
@@ -180,39 +199,52 @@ fn setup_output_streams(
 
                 last_activity.store(chrono::Utc::now().timestamp_millis(), Ordering::Relaxed);
 
-                // Try to parse as progress line
-                if let Some((transferred, percentage, speed, eta)) = parse_rsync_progress(&line) {
-                    let _ = app.emit(
-                        "rsync-progress",
-                        RsyncProgressPayload {
-                            job_id: job_id.clone(),
-                            transferred,
-                            percentage,
-                            speed,
-                            eta,
-                            current_file: current_file.clone(),
-                        },
-                    );
-                } else {
-                    // Non-progress line (file name or info)
-                    // Update current file if it looks like a filename
-                    if !line.starts_with("sending")
-                        && !line.starts_with("receiving")
-                        && !line.starts_with("total")
-                        && !line.contains("files to consider")
-                    {
-                        current_file = Some(line.clone());
+                let message = match parse_output_line(&line, verbosity) {
+                    RsyncOutputLine::Progress {
+                        transferred,
+                        percentage,
+                        speed,
+                        eta,
+                    } => {
+                        let _ = app.emit(
+                            "rsync-progress",
+                            RsyncProgressPayload {
+                                job_id: job_id.clone(),
+                                transferred: transferred.clone(),
+                                percentage,
+                                speed: speed.clone(),
+                                eta: eta.clone(),
+                                current_file: current_file.clone(),
+                                progress: parse_progress_line(&line),
+                            },
+                        );
+                        get_rsync_service().runs().record_progress(
+                            &job_id,
+                            RunProgress {
+                                transferred,
+                                percentage,
+                                speed,
+                                eta: Some(eta),
+                                current_file: current_file.clone(),
+                            },
+                        );
+                        continue;
                     }
-
-                    // Emit as log
-                    let _ = app.emit(
-                        "rsync-log",
-                        RsyncLogPayload {
-                            job_id: job_id.clone(),
-                            message: line,
-                        },
-                    );
-                }
+                    RsyncOutputLine::File(file) => {
+                        current_file = Some(file.clone());
+                        file
+                    }
+                    RsyncOutputLine::Info(info) => info,
+                };
+
+                // Emit as log
+                let _ = app.emit(
+                    "rsync-log",
+                    RsyncLogPayload {
+                        job_id: job_id.clone(),
+                        message,
+                    },
+                );
             }
         })
     });
@@ -227,6 +259,12 @@ fn setup_output_streams(
             for line in reader.lines().flatten() {
                 if !line.trim().is_empty() {
                     last_activity.store(chrono::Utc::now().timestamp_millis(), Ordering::Relaxed);
+                    if let Ok(mut tail) = stderr_tail.lock() {
+                        if tail.len() == STDERR_TAIL_LINES {
+                            tail.pop_front();
+                        }
+                        tail.push_back(line.clone());
+                    }
                     let _ = app.emit(
                         "rsync-log",
                         RsyncLogPayload {
@@ -267,6 +305,10 @@ async fn handle_backup_success(
         if let Some(info) = backup_info {
             let end_time = chrono::Utc::now().timestamp_millis();
             let duration_ms = end_time.saturating_sub(info.start_time) as u64;
+            log::info!(
+                job_id = job.id.as_str(), operation = "backup", duration_ms = duration_ms;
+                "Backup completed: {}", info.folder_name
+            );
 
             // Calculate snapshot stats
             let (file_count, total_size) =
@@ -279,10 +321,16 @@ async fn handle_backup_success(
                 total_size,
                 ManifestSnapshotStatus::Complete,
                 Some(duration_ms),
-            );
+            )
+            .with_source_volume(&job.source_path)
+            .with_run_config(job);
 
-            // Get or create manifest and add snapshot
+            // Index the snapshot on the destination drive (TIM-127, stored at
+            // <dest>/.amber-meta/index.db for portability) unless the job
+            // indexes on request, and add it to the manifest; a crash between
+            // the two is repaired on next open
             let dest_path = job.dest_path.clone();
+            let snapshot_path_str = info.snapshot_path.to_string_lossy().to_string();
             match manifest_service::get_or_create_manifest(
                 &dest_path,
                 &job.id,
@@ -292,10 +340,13 @@ async fn handle_backup_success(
             .await
             {
                 Ok(_) => {
-                    if let Err(e) =
-                        manifest_service::add_snapshot_to_manifest(&dest_path, snapshot).await
+                    // Another job may still be writing to the same drive
+                    volume_gate::defer_while_backing_up(&dest_path).await;
+                    match snapshot_commit::record_snapshot(job, snapshot, &snapshot_path_str).await
                     {
-                        log::warn!("Failed to add snapshot to manifest: {}", e);
+                        Ok(Some(_)) => log::info!("Snapshot indexed successfully on destination"),
+                        Ok(None) => log::info!("Snapshot recorded on destination, not indexed"),
+                        Err(e) => log::warn!("Failed to record snapshot on destination: {}", e),
                     }
                 }
                 Err(e) => {
@@ -309,25 +360,6 @@ async fn handle_backup_success(
             {
                 log::warn!("Failed to update latest symlink: {}", e);
             }
-
-            // TIM-127: Index snapshot on destination drive
-            // Store index at <dest>/.amber-meta/index.db for portability
-            let snapshot_path_str = info.snapshot_path.to_string_lossy().to_string();
-            let timestamp = chrono::Utc::now().timestamp_millis();
-
-            log::info!("Indexing snapshot on destination: {}", dest_path);
-            match IndexService::for_destination(&dest_path) {
-                Ok(index) => {
-                    if let Err(e) = index.index_snapshot(&job.id, timestamp, &snapshot_path_str) {
-                        log::warn!("Failed to index snapshot on destination: {}", e);
-                    } else {
-                        log::info!("Snapshot indexed successfully on destination");
-                    }
-                }
-                Err(e) => {
-                    log::warn!("Failed to open destination index: {}", e);
-                }
-            }
         }
     }
 
@@ -358,19 +390,30 @@ async fn handle_backup_failure(
     status: std::process::ExitStatus,
     backup_info: Option<crate::services::rsync_service::BackupInfo>,
     stalled: bool,
+    stderr: &str,
     app: &tauri::AppHandle,
 ) -> Result<()> {
     // Clean up backup info even on failure
     service.clear_backup_info(&job.id);
 
+    let mismatch = if stalled {
+        None
+    } else {
+        protocol_mismatch(stderr, service.version())
+    };
     let error_msg = if stalled {
         format!(
             "Backup stalled after {} seconds",
             job.config.stall_timeout_seconds
         )
+    } else if let Some(ref mismatch) = mismatch {
+        mismatch.to_string()
+    } else if status.code() == Some(delete_guard::EXIT_DELETE_LIMIT) {
+        "rsync stopped at the job's delete limit; check the source before overriding it".to_string()
     } else {
         format!("rsync exited with code {:?}", status.code())
     };
+    log::error!(job_id = job.id.as_str(), operation = "backup"; "Backup failed: {}", error_msg);
 
     if job.mode == SyncMode::TimeMachine {
         if let Some(info) = backup_info {
@@ -383,7 +426,8 @@ async fn handle_backup_failure(
                 0,
                 0,
                 ManifestSnapshotStatus::Failed,
-            );
+            )
+            .with_run_config(job);
             snapshot.duration_ms = Some(duration_ms);
 
             let dest_path = job.dest_path.clone();
@@ -423,11 +467,18 @@ async fn handle_backup_failure(
         },
     );
 
-    Err(crate::error::AmberError::Rsync(error_msg))
+    Err(mismatch.unwrap_or_else(|| crate::error::AmberError::Rsync(error_msg)))
 }
 
+/// Back up `job`. A job with a delete threshold is dry-run first and refused
+/// if it would delete more; `allow_mass_delete` skips that check and rsync's
+/// `--max-delete` for this run.
 #[tauri::command]
-pub async fn run_rsync(app: tauri::AppHandle, job: SyncJob) -> Result<()> {
+pub async fn run_rsync(
+    app: tauri::AppHandle,
+    mut job: SyncJob,
+    allow_mass_delete: Option<bool>,
+) -> Result<()> {
     let service = get_rsync_service();
 
     validate_job_id(&job.id)?;
@@ -436,12 +487,34 @@ pub async fn run_rsync(app: tauri::AppHandle, job: SyncJob) -> Result<()> {
         return Err(crate::error::AmberError::JobAlreadyRunning(job.id));
     }
 
+    if allow_mass_delete.unwrap_or(false) {
+        if job.config.max_delete.take().is_some() {
+            log::warn!(
+                job_id = job.id.as_str(), operation = "backup";
+                "Delete threshold overridden for this run"
+            );
+        }
+    } else if delete_guard::threshold(&job).is_some() {
+        let guarded = job.clone();
+        tokio::task::spawn_blocking(move || {
+            delete_guard::check(service, &guarded, |args| {
+                service.run_to_completion(&guarded, args)
+            })
+        })
+        .await
+        .map_err(|e| {
+            crate::error::AmberError::Rsync(format!("Deletion dry run task failed: {}", e))
+        })??;
+    }
+
     let last_activity = Arc::new(AtomicI64::new(chrono::Utc::now().timestamp_millis()));
     let completed = Arc::new(AtomicBool::new(false));
     let stall_killed = Arc::new(AtomicBool::new(false));
 
     // Spawn rsync process
     let mut child = spawn_rsync_process(service, &job, &app)?;
+    // Index operations on this volume may wait for the backup to finish
+    let volume_guard = volume_gate::begin_backup(&job.dest_path);
 
     // Rebuild tray to show running state
     #[cfg(desktop)]
@@ -459,8 +532,14 @@ pub async fn run_rsync(app: tauri::AppHandle, job: SyncJob) -> Result<()> {
     let backup_info = service.get_backup_info(&job.id);
 
     // Set up output stream handlers
-    let (stdout_handle, stderr_handle) =
-        setup_output_streams(&mut child, &job, &app, last_activity.clone());
+    let stderr_tail = Arc::new(Mutex::new(VecDeque::new()));
+    let (stdout_handle, stderr_handle) = setup_output_streams(
+        &mut child,
+        &job,
+        &app,
+        last_activity.clone(),
+        stderr_tail.clone(),
+    );
 
     let stall_timeout = job.config.stall_timeout_seconds;
     let job_id = job.id.clone();
@@ -598,6 +677,7 @@ pub async fn run_rsync(app: tauri::AppHandle, job: SyncJob) -> Result<()> {
     // Mark completed
     service.mark_completed(&job.id);
     completed.store(true, Ordering::Relaxed);
+    drop(volume_guard);
 
     if let Some(handle) = stall_handle {
         let _ = handle.await;
@@ -607,12 +687,17 @@ pub async fn run_rsync(app: tauri::AppHandle, job: SyncJob) -> Result<()> {
     if status.success() {
         handle_backup_success(service, &job, backup_info, &app).await
     } else {
+        let stderr = stderr_tail
+            .lock()
+            .map(|tail| Vec::from(tail.clone()).join("\n"))
+            .unwrap_or_default();
         handle_backup_failure(
             service,
             &job,
             status,
             backup_info,
             stall_killed.load(Ordering::Relaxed),
+            &stderr,
             &app,
         )
         .await
@@ -1,11 +1,27 @@
 use crate::error::{AmberError, Result};
-use crate::services::index_service::IndexService;
+use crate::services::cancel_token;
+use crate::services::diagnostics::{self, BenchmarkResult};
+use crate::services::dir_diff::{self, DirectoryDiff};
+use crate::services::index_backfill::{self, BackfillProgress, BackfillReport};
+use crate::services::index_service::{
+    DeletedFiles, DiffCategory, DiffEntry, DiffPage, DiffPageRequest, FileFlag, IndexService,
+    SortBy, DEFAULT_SIZE_BUCKETS,
+};
+use crate::services::index_warmup;
+use crate::services::maintenance::{self, CompactionResult};
 use crate::services::manifest_service;
+use crate::services::parallel_restore::{self, ConflictStrategy, RestoreProgress};
+use crate::services::retention::{self, PrunedSnapshot, RetentionPolicy};
+use crate::services::snapshot_export::{self, ArchiveFormat, ExportProgress, ExportedArchive};
+use crate::services::snapshot_open;
+use crate::services::source_diff::{self, SourceComparison};
+use crate::services::volume_gate;
 use crate::state::AppState;
-use crate::types::snapshot::{FileNode, SnapshotMetadata};
+use crate::types::snapshot::{FileCategory, FileNode, SnapshotMetadata};
 use crate::utils::validation::validate_job_id;
+use crate::utils::{is_ssh_remote, parse_ssh_remote, path_bytes};
 use serde::Serialize;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use tauri::State;
 
 #[derive(Debug, Serialize)]
@@ -27,6 +43,13 @@ impl<'a> IndexHandle<'a> {
             IndexHandle::Destination(index) => f(index),
         }
     }
+
+    fn get(&self) -> &IndexService {
+        match self {
+            IndexHandle::Local(index) => index,
+            IndexHandle::Destination(index) => index,
+        }
+    }
 }
 
 fn resolve_index<'a>(
@@ -104,15 +127,10 @@ pub async fn get_snapshot_tree(
 ) -> Result<Vec<FileNode>> {
     ensure_job_id(&job_id)?;
     let index = resolve_index(&state, &job_id, true)?;
-    if index.with(|idx| idx.is_indexed(&job_id, timestamp))? {
-        return index.with(|idx| idx.get_directory_contents(&job_id, timestamp, ""));
-    }
-
-    // Fall back to filesystem scan (legacy behavior)
     let validated_snapshot = state.validate_path(&snapshot_path)?;
     state
         .snapshot_service
-        .get_snapshot_tree(&job_id, timestamp, &validated_snapshot)
+        .get_snapshot_tree(Some(index.get()), &job_id, timestamp, &validated_snapshot)
         .await
 }
 
@@ -125,12 +143,23 @@ pub async fn get_indexed_directory(
     parent_path: String,
 ) -> Result<Vec<FileNode>> {
     ensure_job_id(&job_id)?;
+    // Snapshot roots warmed up when the drive was connected skip opening the index
+    if parent_path.is_empty() {
+        if let Some(job) = state.store.get_job(&job_id)? {
+            return index_warmup::root_contents(&job.dest_path, &job_id, timestamp, || {
+                resolve_index(&state, &job_id, true)?
+                    .with(|idx| idx.get_directory_contents(&job_id, timestamp, ""))
+            });
+        }
+    }
     let index = resolve_index(&state, &job_id, true)?;
     index.with(|idx| idx.get_directory_contents(&job_id, timestamp, &parent_path))
 }
 
-/// Get directory contents from index with pagination (for large directories)
+/// Get directory contents from index with pagination (for large directories);
+/// `type_filter` lists only files of one category, plus the folders
 #[tauri::command]
+#[allow(clippy::too_many_arguments)]
 pub async fn get_indexed_directory_paginated(
     state: State<'_, AppState>,
     job_id: String,
@@ -138,26 +167,145 @@ pub async fn get_indexed_directory_paginated(
     parent_path: String,
     limit: Option<usize>,
     offset: Option<usize>,
+    modified_after: Option<i64>,
+    type_filter: Option<FileCategory>,
+) -> Result<crate::services::index_service::DirectoryContents> {
+    ensure_job_id(&job_id)?;
+    let index = resolve_index(&state, &job_id, true)?;
+    index.with(|idx| {
+        idx.get_directory_contents_paginated(
+            &job_id,
+            timestamp,
+            &parent_path,
+            limit,
+            offset,
+            modified_after,
+            type_filter,
+        )
+    })
+}
+
+/// One page of a directory from the index in the given order, sorted and
+/// paged by SQL; `total_count` covers the whole directory
+#[tauri::command]
+pub async fn get_indexed_directory_paged(
+    state: State<'_, AppState>,
+    job_id: String,
+    timestamp: i64,
+    parent_path: String,
+    sort: Option<SortBy>,
+    offset: Option<usize>,
+    limit: Option<usize>,
 ) -> Result<crate::services::index_service::DirectoryContents> {
     ensure_job_id(&job_id)?;
     let index = resolve_index(&state, &job_id, true)?;
     index.with(|idx| {
-        idx.get_directory_contents_paginated(&job_id, timestamp, &parent_path, limit, offset)
+        idx.get_directory_contents_paged(
+            &job_id,
+            timestamp,
+            &parent_path,
+            sort.unwrap_or_default(),
+            offset.unwrap_or(0),
+            limit.unwrap_or(500),
+        )
     })
 }
 
+/// A directory's descendants down to `depth` levels, nested, for the
+/// browser to expand incrementally (bounded in node count)
+#[tauri::command]
+pub async fn get_subtree(
+    state: State<'_, AppState>,
+    job_id: String,
+    timestamp: i64,
+    parent_path: String,
+    depth: usize,
+    max_nodes: Option<usize>,
+) -> Result<crate::services::index_service::Subtree> {
+    ensure_job_id(&job_id)?;
+    let index = resolve_index(&state, &job_id, true)?;
+    index.with(|idx| idx.get_subtree(&job_id, timestamp, &parent_path, depth, max_nodes))
+}
+
 /// Index a snapshot after backup completes
+///
+/// `force_replace` allows replacing a different folder already indexed at
+/// the same timestamp; without it that collision is an error. `hash` also
+/// stores content hashes so comparisons catch same-size edits; it reads
+/// every file, so it is off by default. Runs as `index:<job id>:<timestamp>`,
+/// so `cancel_operation` can stop it; without either flag the next call picks
+/// up where a cancelled one stopped.
 #[tauri::command]
 pub async fn index_snapshot(
     state: State<'_, AppState>,
     job_id: String,
     timestamp: i64,
     snapshot_path: String,
+    force_replace: Option<bool>,
+    hash: Option<bool>,
 ) -> Result<crate::services::index_service::IndexedSnapshot> {
     ensure_job_id(&job_id)?;
     let index = resolve_index(&state, &job_id, false)?;
     let validated_snapshot = state.validate_path(&snapshot_path)?;
-    index.with(|idx| idx.index_snapshot(&job_id, timestamp, &validated_snapshot))
+    volume_gate::defer_while_backing_up(&validated_snapshot).await;
+
+    let op_id =
+        cancel_token::operation_id(cancel_token::INDEX, &format!("{}:{}", job_id, timestamp));
+    let cancel = state.operations.register(&op_id);
+    let indexed = index.with(|idx| {
+        idx.index_snapshot_cancellable(
+            &job_id,
+            timestamp,
+            &validated_snapshot,
+            force_replace.unwrap_or(false),
+            hash.unwrap_or(false),
+            &cancel,
+        )
+    });
+    state.operations.unregister(&op_id, &cancel);
+    let indexed = indexed?;
+    state
+        .snapshot_service
+        .invalidate_cache(&job_id, timestamp)
+        .await;
+    Ok(indexed)
+}
+
+/// Record the job's source as it is now (names, sizes, mtimes) as a
+/// metadata-only snapshot, without running a backup. It can be compared
+/// against other snapshots but not restored from. Local sources only.
+#[tauri::command]
+pub async fn pin_source_state(
+    state: State<'_, AppState>,
+    job_id: String,
+) -> Result<crate::services::index_service::IndexedSnapshot> {
+    ensure_job_id(&job_id)?;
+    let job = state
+        .store
+        .get_job(&job_id)?
+        .ok_or_else(|| AmberError::job_not_found(job_id.clone()))?;
+    if parse_ssh_remote(&job.source_path).is_some() {
+        return Err(AmberError::ValidationError(
+            "Only local sources can be pinned".to_string(),
+        ));
+    }
+    let source = state.validate_path(&job.source_path)?;
+
+    let timestamp = chrono::Utc::now().timestamp_millis();
+    let index = resolve_index(&state, &job_id, false)?;
+    index.with(|idx| idx.index_metadata_snapshot(&job_id, timestamp, &source))
+}
+
+/// Groups of files in a snapshot that are hard links of each other
+#[tauri::command]
+pub async fn get_hardlink_groups(
+    state: State<'_, AppState>,
+    job_id: String,
+    timestamp: i64,
+) -> Result<Vec<crate::services::index_service::HardlinkGroup>> {
+    ensure_job_id(&job_id)?;
+    let index = resolve_index(&state, &job_id, true)?;
+    index.with(|idx| idx.get_hardlink_groups(&job_id, timestamp))
 }
 
 /// Check if a snapshot is indexed
@@ -172,7 +320,8 @@ pub async fn is_snapshot_indexed(
     index.with(|idx| idx.is_indexed(&job_id, timestamp))
 }
 
-/// Search files in a snapshot
+/// Search files in a snapshot; `type_filter` keeps only files of one
+/// category, plus matching folders
 #[tauri::command]
 pub async fn search_snapshot_files(
     state: State<'_, AppState>,
@@ -180,10 +329,55 @@ pub async fn search_snapshot_files(
     timestamp: i64,
     pattern: String,
     limit: Option<usize>,
+    modified_after: Option<i64>,
+    type_filter: Option<FileCategory>,
 ) -> Result<Vec<FileNode>> {
     ensure_job_id(&job_id)?;
     let index = resolve_index(&state, &job_id, true)?;
-    index.with(|idx| idx.search_files(&job_id, timestamp, &pattern, limit.unwrap_or(100)))
+    index.with(|idx| {
+        idx.search_files_modified_after(
+            &job_id,
+            timestamp,
+            &pattern,
+            limit.unwrap_or(100),
+            modified_after,
+            type_filter,
+        )
+    })
+}
+
+/// Every snapshot of a job that holds `path` (relative to the snapshot
+/// root), newest first, with the size and mtime it had in each
+#[tauri::command]
+pub async fn find_path_in_snapshots(
+    state: State<'_, AppState>,
+    job_id: String,
+    path: String,
+) -> Result<Vec<crate::services::index_service::PathOccurrence>> {
+    ensure_job_id(&job_id)?;
+    let index = resolve_index(&state, &job_id, true)?;
+    index.with(|idx| idx.find_path_in_snapshots(&job_id, &path))
+}
+
+/// Search files in every snapshot of a job, including files deleted since
+#[tauri::command]
+pub async fn search_files_job(
+    state: State<'_, AppState>,
+    job_id: String,
+    pattern: String,
+    limit: Option<usize>,
+    collapse: Option<bool>,
+) -> Result<Vec<crate::services::index_service::JobSearchResult>> {
+    ensure_job_id(&job_id)?;
+    let index = resolve_index(&state, &job_id, true)?;
+    index.with(|idx| {
+        idx.search_files_job(
+            &job_id,
+            &pattern,
+            limit.unwrap_or(100),
+            collapse.unwrap_or(false),
+        )
+    })
 }
 
 /// Search files globally across all snapshots using FTS5
@@ -205,6 +399,41 @@ pub async fn search_files_global(
     index.with(|idx| idx.search_files_global(&pattern, job_id.as_deref(), limit.unwrap_or(50)))
 }
 
+/// Whether global search folds diacritics for this job's index (or the local
+/// index when no job is given)
+#[tauri::command]
+pub async fn get_search_diacritic_folding(
+    state: State<'_, AppState>,
+    job_id: Option<String>,
+) -> Result<bool> {
+    let index = match &job_id {
+        Some(id) => {
+            ensure_job_id(id)?;
+            resolve_index(&state, id, true)?
+        }
+        None => IndexHandle::Local(&state.index_service),
+    };
+    index.with(|idx| idx.uses_diacritic_folding())
+}
+
+/// Re-tokenize every file of the job's index into its search index, in the
+/// diacritic folding mode of the preferences. Cancelled through
+/// `fts-rebuild:<job id>`; a cancelled rebuild keeps the old search index.
+#[tauri::command]
+pub async fn rebuild_search_index(state: State<'_, AppState>, job_id: String) -> Result<()> {
+    ensure_job_id(&job_id)?;
+    let index = resolve_index(&state, &job_id, true)?;
+    let op_id = cancel_token::operation_id(cancel_token::FTS_REBUILD, &job_id);
+    let cancel = state.operations.register(&op_id);
+    // Switching the folding mode re-tokenizes everything already
+    let result = index.with(|idx| match idx.apply_configured_diacritic_folding()? {
+        true => Ok(()),
+        false => idx.rebuild_fts(&cancel),
+    });
+    state.operations.unregister(&op_id, &cancel);
+    result
+}
+
 /// Get snapshot statistics from index
 #[tauri::command]
 pub async fn get_snapshot_stats(
@@ -230,6 +459,46 @@ pub async fn get_file_type_stats(
     index.with(|idx| idx.get_file_type_stats(&job_id, timestamp, limit.unwrap_or(20)))
 }
 
+/// Count and size of a snapshot's files per size range. `buckets` are the
+/// bounds between ranges in bytes (default <1K, 1K–1M, 1M–100M, >=100M).
+#[tauri::command]
+pub async fn get_size_histogram(
+    state: State<'_, AppState>,
+    job_id: String,
+    timestamp: i64,
+    buckets: Option<Vec<i64>>,
+) -> Result<Vec<crate::services::index_service::SizeBucket>> {
+    ensure_job_id(&job_id)?;
+    let index = resolve_index(&state, &job_id, true)?;
+    let bounds = buckets.unwrap_or_else(|| DEFAULT_SIZE_BUCKETS.to_vec());
+    index.with(|idx| idx.get_size_histogram(&job_id, timestamp, &bounds))
+}
+
+/// Get count and size of one extension across every snapshot of a job
+#[tauri::command]
+pub async fn get_extension_growth(
+    state: State<'_, AppState>,
+    job_id: String,
+    extension: String,
+) -> Result<Vec<crate::services::index_service::ExtensionGrowthPoint>> {
+    ensure_job_id(&job_id)?;
+    let index = resolve_index(&state, &job_id, true)?;
+    index.with(|idx| idx.get_extension_growth(&job_id, &extension))
+}
+
+/// Breadcrumbs for a browsed directory, root first
+#[tauri::command]
+pub async fn get_breadcrumbs(
+    state: State<'_, AppState>,
+    job_id: String,
+    timestamp: i64,
+    parent_path: String,
+) -> Result<Vec<crate::services::index_service::Crumb>> {
+    ensure_job_id(&job_id)?;
+    let index = resolve_index(&state, &job_id, true)?;
+    index.with(|idx| idx.resolve_breadcrumbs(&job_id, timestamp, &parent_path))
+}
+
 /// Get largest files in a snapshot (for analytics)
 #[tauri::command]
 pub async fn get_largest_files(
@@ -243,6 +512,33 @@ pub async fn get_largest_files(
     index.with(|idx| idx.get_largest_files(&job_id, timestamp, limit.unwrap_or(10)))
 }
 
+/// Get the most recently modified files in a snapshot
+#[tauri::command]
+pub async fn get_recent_files(
+    state: State<'_, AppState>,
+    job_id: String,
+    timestamp: i64,
+    limit: Option<usize>,
+) -> Result<Vec<FileNode>> {
+    ensure_job_id(&job_id)?;
+    let index = resolve_index(&state, &job_id, true)?;
+    index.with(|idx| idx.get_recent_files(&job_id, timestamp, limit.unwrap_or(50)))
+}
+
+/// Get files carrying a derived cleanup flag ("large", "duplicate", "stale")
+#[tauri::command]
+pub async fn get_flagged_files(
+    state: State<'_, AppState>,
+    job_id: String,
+    timestamp: i64,
+    flag: FileFlag,
+    limit: Option<usize>,
+) -> Result<Vec<FileNode>> {
+    ensure_job_id(&job_id)?;
+    let index = resolve_index(&state, &job_id, true)?;
+    index.with(|idx| idx.get_flagged_files(&job_id, timestamp, flag, limit.unwrap_or(100)))
+}
+
 /// Delete a snapshot from the index
 #[tauri::command]
 pub async fn delete_snapshot_index(
@@ -263,13 +559,60 @@ pub async fn delete_job_index(state: State<'_, AppState>, job_id: String) -> Res
     index.with(|idx| idx.delete_job_snapshots(&job_id))
 }
 
+/// Total files and bytes `restore_files` will write for `paths`, read from
+/// the index so a progress bar has its denominator before anything is copied
+#[tauri::command]
+pub async fn restore_estimate(
+    state: State<'_, AppState>,
+    job_id: String,
+    timestamp: i64,
+    paths: Vec<String>,
+) -> Result<crate::services::index_service::RestoreEstimate> {
+    ensure_job_id(&job_id)?;
+    let validated = validate_restore_file_list(&paths)?;
+    let index = resolve_index(&state, &job_id, true)?;
+    index.with(|idx| idx.restore_estimate(&job_id, timestamp, &validated))
+}
+
+/// rsync arguments for `restore_files`; the NUL-separated file list goes to
+/// stdin. `--files-from` turns off the recursion `-a` implies, so `-r` is
+/// given to restore selected directories with their contents. Fifos, sockets
+/// and devices are skipped (`--no-D`): recreating them needs privileges and
+/// they hold no data.
+pub fn restore_files_args(snapshot: String, target: String) -> Vec<String> {
+    vec![
+        "-avr".to_string(),
+        "--no-D".to_string(),
+        "--progress".to_string(),
+        "--files-from=-".to_string(),
+        "--from0".to_string(),
+        "--".to_string(),
+        snapshot,
+        target,
+    ]
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct RestoreProgressPayload {
+    job_id: String,
+    #[serde(flatten)]
+    progress: RestoreProgress,
+}
+
+/// Restore selected files and folders from a snapshot. Files already at the
+/// target are handled per `conflict` (overwrite by default). With the
+/// `restoreWorkers` preference set the files are copied in parallel,
+/// emitting `restore-progress` events; otherwise rsync restores them.
 #[tauri::command]
 pub async fn restore_files(
+    app: tauri::AppHandle,
     state: State<'_, AppState>,
     job_id: String,
     snapshot_path: String,
     files: Vec<String>,
     target_path: String,
+    conflict: Option<ConflictStrategy>,
 ) -> Result<()> {
     ensure_job_id(&job_id)?;
     use std::process::Command;
@@ -282,6 +625,16 @@ pub async fn restore_files(
 
     let validated_snapshot = state.validate_path(&snapshot_path)?;
     let validated_target = state.validate_path_for_create(&target_path)?;
+    let validated_files = validate_restore_file_list(&files)?;
+    // Restoring doesn't need the index; it only gets a say if it opens. It
+    // also knows the real bytes of names listed in their `%XX` form.
+    let restore_paths: Vec<PathBuf> = match resolve_index(&state, &job_id, true) {
+        Ok(index) => index.with(|idx| {
+            idx.ensure_restorable_root(&job_id, &validated_snapshot)?;
+            idx.restore_paths(&job_id, &validated_snapshot, &validated_files)
+        })?,
+        Err(_) => validated_files.iter().map(PathBuf::from).collect(),
+    };
 
     let dest_root = std::path::Path::new(&job.dest_path)
         .canonicalize()
@@ -298,17 +651,53 @@ pub async fn restore_files(
         ));
     }
 
-    let validated_files = validate_restore_file_list(&files)?;
+    let conflict = conflict.unwrap_or_default();
+
+    let workers = parallel_restore::workers();
+    if workers > 0 {
+        use tauri::Emitter;
+
+        let op_id = cancel_token::operation_id(cancel_token::RESTORE, &job_id);
+        let cancel = state.operations.register(&op_id);
+        let worker_cancel = cancel.clone();
+        let payload_job_id = job_id.clone();
+        let result = tokio::task::spawn_blocking(move || {
+            parallel_restore::restore_parallel(
+                Path::new(&validated_snapshot),
+                &restore_paths,
+                Path::new(&validated_target),
+                workers,
+                conflict,
+                &worker_cancel,
+                |progress| {
+                    let _ = app.emit(
+                        "restore-progress",
+                        RestoreProgressPayload {
+                            job_id: payload_job_id.clone(),
+                            progress: progress.clone(),
+                        },
+                    );
+                },
+            )
+        })
+        .await
+        .map_err(|e| AmberError::Filesystem(format!("Restore task failed: {}", e)));
+        state.operations.unregister(&op_id, &cancel);
+
+        let report = result??;
+        log::info!(
+            "[restore] Restored {} files ({} skipped) with {} workers",
+            report.files_restored,
+            report.files_skipped,
+            workers
+        );
+        return Ok(());
+    }
 
-    let args = vec![
-        "-av".to_string(),
-        "--progress".to_string(),
-        "--files-from=-".to_string(),
-        "--from0".to_string(),
-        "--".to_string(),
-        validated_snapshot,
-        validated_target,
-    ];
+    let mut args = restore_files_args(validated_snapshot, validated_target);
+    if let Some(flag) = conflict.rsync_flag() {
+        args.insert(1, flag.to_string());
+    }
 
     let mut child = Command::new("rsync")
         .args(&args)
@@ -319,8 +708,11 @@ pub async fn restore_files(
 
     if let Some(mut stdin) = child.stdin.take() {
         use std::io::Write;
-        let file_list = validated_files.join("\0");
-        stdin.write_all(file_list.as_bytes())?;
+        let file_list: Vec<Vec<u8>> = restore_paths
+            .iter()
+            .map(|path| path_bytes(path).unwrap_or_else(|| path.to_string_lossy().into()))
+            .collect();
+        stdin.write_all(&file_list.join(&0u8))?;
     }
 
     let output = child.wait_with_output()?;
@@ -335,6 +727,15 @@ pub async fn restore_files(
     Ok(())
 }
 
+/// Cancel a running parallel restore; returns false if none was running
+#[tauri::command]
+pub async fn cancel_restore(state: State<'_, AppState>, job_id: String) -> Result<bool> {
+    ensure_job_id(&job_id)?;
+    Ok(state
+        .operations
+        .cancel(&cancel_token::operation_id(cancel_token::RESTORE, &job_id)))
+}
+
 #[tauri::command]
 pub async fn restore_snapshot(
     state: State<'_, AppState>,
@@ -354,6 +755,10 @@ pub async fn restore_snapshot(
 
     let validated_snapshot = state.validate_path(&snapshot_path)?;
     let validated_target = state.validate_path_for_create(&target_path)?;
+    // Restoring doesn't need the index; it only gets a say if it opens
+    if let Ok(index) = resolve_index(&state, &job_id, true) {
+        index.with(|idx| idx.ensure_restorable_root(&job_id, &validated_snapshot))?;
+    }
 
     let dest_root = std::path::Path::new(&job.dest_path)
         .canonicalize()
@@ -376,7 +781,12 @@ pub async fn restore_snapshot(
         format!("{}/", validated_snapshot)
     };
 
-    let mut args = vec!["-av".to_string(), "--progress".to_string()];
+    // Special files are skipped as in `restore_files_args`
+    let mut args = vec![
+        "-av".to_string(),
+        "--no-D".to_string(),
+        "--progress".to_string(),
+    ];
 
     // Add --delete flag for mirror mode (exact copy)
     if mirror.unwrap_or(false) {
@@ -520,6 +930,7 @@ pub async fn index_snapshot_on_destination(
     ensure_job_id(&job_id)?;
     let validated_dest = validate_destination_path(&state, &dest_path, true)?;
     let validated_snapshot = state.validate_path(&snapshot_path)?;
+    volume_gate::defer_while_backing_up(&validated_snapshot).await;
     let index = IndexService::for_destination(&validated_dest)?;
     index.index_snapshot(&job_id, timestamp, &validated_snapshot)
 }
@@ -532,11 +943,28 @@ pub async fn get_directory_from_destination(
     job_id: String,
     timestamp: i64,
     parent_path: String,
+    modified_after: Option<i64>,
+    type_filter: Option<FileCategory>,
 ) -> Result<Vec<FileNode>> {
     ensure_job_id(&job_id)?;
     let validated = validate_destination_path(&state, &dest_path, true)?;
+    if parent_path.is_empty() && modified_after.is_none() && type_filter.is_none() {
+        return index_warmup::root_contents(&validated, &job_id, timestamp, || {
+            IndexService::for_destination(&validated)?
+                .get_directory_contents(&job_id, timestamp, "")
+        });
+    }
     let index = IndexService::for_destination(&validated)?;
-    index.get_directory_contents(&job_id, timestamp, &parent_path)
+    let contents = index.get_directory_contents_paginated(
+        &job_id,
+        timestamp,
+        &parent_path,
+        None,
+        None,
+        modified_after,
+        type_filter,
+    )?;
+    Ok(contents.files)
 }
 
 /// Check if a snapshot is indexed on the destination
@@ -555,6 +983,7 @@ pub async fn is_indexed_on_destination(
 
 /// Search files in destination's index
 #[tauri::command]
+#[allow(clippy::too_many_arguments)]
 pub async fn search_files_on_destination(
     state: State<'_, AppState>,
     dest_path: String,
@@ -562,11 +991,20 @@ pub async fn search_files_on_destination(
     timestamp: i64,
     pattern: String,
     limit: Option<usize>,
+    modified_after: Option<i64>,
+    type_filter: Option<FileCategory>,
 ) -> Result<Vec<FileNode>> {
     ensure_job_id(&job_id)?;
     let validated = validate_destination_path(&state, &dest_path, true)?;
     let index = IndexService::for_destination(&validated)?;
-    index.search_files(&job_id, timestamp, &pattern, limit.unwrap_or(100))
+    index.search_files_modified_after(
+        &job_id,
+        timestamp,
+        &pattern,
+        limit.unwrap_or(100),
+        modified_after,
+        type_filter,
+    )
 }
 
 /// Get file type stats from destination's index
@@ -639,6 +1077,17 @@ pub async fn get_job_aggregate_stats(
     index.with(|idx| idx.get_job_aggregate_stats(&job_id))
 }
 
+/// Estimate the unique content stored across all of a job's snapshots
+#[tauri::command]
+pub async fn get_job_logical_footprint(
+    state: State<'_, AppState>,
+    job_id: String,
+) -> Result<crate::services::index_service::JobLogicalFootprint> {
+    ensure_job_id(&job_id)?;
+    let index = resolve_index(&state, &job_id, true)?;
+    index.with(|idx| idx.get_job_logical_footprint(&job_id))
+}
+
 /// Get aggregate statistics for a job from destination's index
 #[tauri::command]
 pub async fn get_job_aggregate_stats_on_destination(
@@ -679,17 +1128,262 @@ pub async fn get_snapshot_density_on_destination(
 }
 
 /// TIM-221: Compare two snapshots and return file differences
+/// `under_path` (relative to the snapshot root) limits the diff to one folder;
+/// `ignore_globs` drops matching paths (e.g. `**/node_modules`) from both sides;
+/// `top_contributors` also lists that many files with the largest size change
 #[tauri::command]
+#[allow(clippy::too_many_arguments)]
 pub async fn compare_snapshots(
     state: State<'_, AppState>,
     job_id: String,
     timestamp_a: i64,
     timestamp_b: i64,
+    under_path: Option<String>,
+    ignore_globs: Option<Vec<String>>,
     limit: Option<usize>,
+    top_contributors: Option<usize>,
 ) -> Result<crate::services::index_service::SnapshotDiff> {
     ensure_job_id(&job_id)?;
     let index = resolve_index(&state, &job_id, true)?;
-    index.with(|idx| idx.compare_snapshots(&job_id, timestamp_a, timestamp_b, limit))
+    let ignore_globs = ignore_globs.unwrap_or_default();
+    index.with(|idx| {
+        let mut diff = idx.compare_snapshots_under(
+            &job_id,
+            timestamp_a,
+            timestamp_b,
+            under_path.as_deref(),
+            &ignore_globs,
+            limit,
+        )?;
+        if let Some(count) = top_contributors.filter(|count| *count > 0) {
+            diff.top_contributors = idx.size_contributors(
+                &job_id,
+                timestamp_a,
+                timestamp_b,
+                under_path.as_deref(),
+                &ignore_globs,
+                count,
+            )?;
+        }
+        Ok(diff)
+    })
+}
+
+/// Page through one category (added/deleted/modified) of a snapshot diff
+#[tauri::command]
+pub async fn compare_snapshots_page(
+    state: State<'_, AppState>,
+    job_id: String,
+    timestamp_a: i64,
+    timestamp_b: i64,
+    page: DiffPageRequest,
+    under_path: Option<String>,
+    ignore_globs: Option<Vec<String>>,
+) -> Result<DiffPage> {
+    ensure_job_id(&job_id)?;
+    let index = resolve_index(&state, &job_id, true)?;
+    index.with(|idx| {
+        idx.compare_snapshots_page(
+            &job_id,
+            timestamp_a,
+            timestamp_b,
+            under_path.as_deref(),
+            &ignore_globs.unwrap_or_default(),
+            page,
+        )
+    })
+}
+
+/// Files deleted between a last-good snapshot and a later one, with the
+/// good snapshot's path so they can go straight to `restore_files`
+#[tauri::command]
+pub async fn list_deleted_between(
+    state: State<'_, AppState>,
+    job_id: String,
+    timestamp_good: i64,
+    timestamp_now: i64,
+) -> Result<DeletedFiles> {
+    ensure_job_id(&job_id)?;
+    let index = resolve_index(&state, &job_id, true)?;
+    index.with(|idx| idx.list_deleted_between(&job_id, timestamp_good, timestamp_now))
+}
+
+/// Entries sent per `snapshot-diff-batch` event
+const DIFF_STREAM_BATCH: usize = 500;
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct DiffBatchPayload {
+    job_id: String,
+    timestamp_a: i64,
+    timestamp_b: i64,
+    category: DiffCategory,
+    entries: Vec<DiffEntry>,
+}
+
+/// Compare two snapshots without collecting the whole diff: entries are
+/// emitted as `snapshot-diff-batch` events while they are read from the
+/// index. Resolves with the total number of entries once all batches are out.
+#[tauri::command]
+pub async fn compare_snapshots_stream(
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+    job_id: String,
+    timestamp_a: i64,
+    timestamp_b: i64,
+    under_path: Option<String>,
+    ignore_globs: Option<Vec<String>>,
+) -> Result<usize> {
+    use tauri::Emitter;
+
+    ensure_job_id(&job_id)?;
+    let index = resolve_index(&state, &job_id, true)?;
+
+    let emit = |category: DiffCategory, entries: Vec<DiffEntry>| {
+        let _ = app.emit(
+            "snapshot-diff-batch",
+            DiffBatchPayload {
+                job_id: job_id.clone(),
+                timestamp_a,
+                timestamp_b,
+                category,
+                entries,
+            },
+        );
+    };
+
+    let mut batch: Vec<DiffEntry> = Vec::with_capacity(DIFF_STREAM_BATCH);
+    let mut batch_category = DiffCategory::Added;
+    let delivered = index.with(|idx| {
+        idx.compare_snapshots_each(
+            &job_id,
+            timestamp_a,
+            timestamp_b,
+            under_path.as_deref(),
+            &ignore_globs.unwrap_or_default(),
+            |category, entry| {
+                if category != batch_category || batch.len() == DIFF_STREAM_BATCH {
+                    if !batch.is_empty() {
+                        emit(batch_category, std::mem::take(&mut batch));
+                    }
+                    batch_category = category;
+                }
+                batch.push(entry);
+                Ok(())
+            },
+        )
+    })?;
+    if !batch.is_empty() {
+        emit(batch_category, batch);
+    }
+    Ok(delivered)
+}
+
+/// Preview what the next backup would add, update and (with `--delete`)
+/// remove by comparing the job's source against its latest indexed snapshot
+/// by path, size and mtime, with the job's exclude patterns applied.
+/// Read-only; rsync is not run. SSH sources are listed over ssh. `None` if
+/// the job has no indexed snapshot yet.
+#[tauri::command]
+pub async fn compare_source_to_snapshot(
+    state: State<'_, AppState>,
+    job_id: String,
+) -> Result<Option<SourceComparison>> {
+    ensure_job_id(&job_id)?;
+    let job = state
+        .store
+        .get_job(&job_id)?
+        .ok_or_else(|| AmberError::job_not_found(job_id.clone()))?;
+
+    // The index is only needed for the snapshot side; let go of it before scanning
+    let latest = {
+        let index = resolve_index(&state, &job_id, true)?;
+        index.with(|idx| idx.latest_snapshot_files(&job_id))?
+    };
+    let Some((snapshot_timestamp, snapshot_files)) = latest else {
+        return Ok(None);
+    };
+
+    let exclude_patterns = job.config.exclude_patterns.clone();
+    let source = if is_ssh_remote(&job.source_path) {
+        let askpass = super::jobs::keychain_askpass_env(&job);
+        tokio::task::spawn_blocking(move || {
+            let ssh = job.ssh_config.clone().unwrap_or_default();
+            let batch_mode = askpass.is_empty();
+            source_diff::scan_remote(&ssh, &job.source_path, batch_mode, |args| {
+                super::jobs::run_ssh(args, askpass)
+            })
+        })
+        .await
+        .map_err(|e| AmberError::Rsync(format!("Source scan task failed: {}", e)))??
+    } else {
+        let root = state.validate_path(&job.source_path)?;
+        tokio::task::spawn_blocking(move || source_diff::scan_local(Path::new(&root)))
+            .await
+            .map_err(|e| AmberError::Filesystem(format!("Source scan task failed: {}", e)))??
+    };
+
+    Ok(Some(source_diff::diff_source(
+        snapshot_timestamp,
+        snapshot_files,
+        source,
+        &exclude_patterns,
+    )))
+}
+
+/// How the files under `path_b` differ from those under `path_a`, by
+/// relative path and size, read straight from disk rather than the index.
+/// Meant for checking a restore against the snapshot it came from. Each list
+/// holds at most `limit` entries; the totals count everything.
+#[tauri::command]
+pub async fn diff_directories(
+    state: State<'_, AppState>,
+    path_a: String,
+    path_b: String,
+    limit: Option<usize>,
+) -> Result<DirectoryDiff> {
+    let a = state.validate_path(&path_a)?;
+    let b = state.validate_path(&path_b)?;
+    tokio::task::spawn_blocking(move || {
+        dir_diff::diff_directories(Path::new(&a), Path::new(&b), limit)
+    })
+    .await
+    .map_err(|e| AmberError::Filesystem(format!("Directory diff task failed: {}", e)))?
+}
+
+/// Benchmark the existing index on `dest_path` for a support bundle. Works in
+/// release builds; read-only, but keeps the index busy for a moment, so it
+/// only ever runs from this explicit command.
+#[tauri::command]
+pub async fn run_index_benchmarks(
+    state: State<'_, AppState>,
+    dest_path: String,
+) -> Result<Vec<BenchmarkResult>> {
+    let dest = state.validate_path(&dest_path)?;
+    tokio::task::spawn_blocking(move || diagnostics::run_index_benchmarks(&dest))
+        .await
+        .map_err(|e| AmberError::Index(format!("Benchmark task failed: {}", e)))?
+}
+
+/// Copy a snapshot to a second destination, with its manifest entry and index
+#[tauri::command]
+pub async fn replicate_snapshot(
+    state: State<'_, AppState>,
+    job_id: String,
+    timestamp: i64,
+    src_dest: String,
+    dst_dest: String,
+) -> Result<crate::services::replication::ReplicatedSnapshot> {
+    ensure_job_id(&job_id)?;
+    let validated_src = validate_destination_path(&state, &src_dest, true)?;
+    let validated_dst = validate_destination_path(&state, &dst_dest, true)?;
+    crate::services::replication::replicate_snapshot(
+        &job_id,
+        timestamp,
+        &validated_src,
+        &validated_dst,
+    )
+    .await
 }
 
 /// Prune a snapshot: remove from manifest, delete from index, and remove folder from disk.
@@ -759,6 +1453,42 @@ pub async fn prune_snapshot(
     })
 }
 
+/// Delete the job's snapshots on `dest_path` that `policy` doesn't keep, or
+/// with `dry_run` only list them. The folder a running backup is writing is
+/// always kept.
+#[tauri::command]
+pub async fn prune_snapshots(
+    state: State<'_, AppState>,
+    job_id: String,
+    dest_path: String,
+    policy: RetentionPolicy,
+    dry_run: Option<bool>,
+) -> Result<Vec<PrunedSnapshot>> {
+    ensure_job_id(&job_id)?;
+    let validated = validate_destination_path(&state, &dest_path, true)?;
+    let active_snapshot = crate::commands::rsync::get_rsync_service()
+        .get_backup_info(&job_id)
+        .map(|info| info.snapshot_path);
+    let dry_run = dry_run.unwrap_or(false);
+
+    let mut job = state
+        .store
+        .get_job(&job_id)?
+        .ok_or_else(|| AmberError::job_not_found(job_id.clone()))?;
+    job.dest_path = validated;
+
+    let pruned = retention::prune_snapshots(&job, &policy, active_snapshot, dry_run).await?;
+    if !dry_run {
+        for snapshot in &pruned {
+            state
+                .snapshot_service
+                .invalidate_cache(&job_id, snapshot.timestamp)
+                .await;
+        }
+    }
+    Ok(pruned)
+}
+
 /// Recursively calculate directory size in bytes.
 fn dir_size(path: &Path) -> u64 {
     let mut total: u64 = 0;
@@ -776,3 +1506,209 @@ fn dir_size(path: &Path) -> u64 {
     }
     total
 }
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ExportProgressPayload {
+    job_id: String,
+    timestamp: i64,
+    #[serde(flatten)]
+    progress: ExportProgress,
+}
+
+/// Package a snapshot into a single tar or zip archive, emitting
+/// `snapshot-export-progress` events as entries are written
+#[tauri::command]
+pub async fn export_snapshot_archive(
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+    job_id: String,
+    timestamp: i64,
+    dest_path: String,
+    out_path: String,
+    format: ArchiveFormat,
+) -> Result<ExportedArchive> {
+    use tauri::Emitter;
+
+    ensure_job_id(&job_id)?;
+    let validated_dest = validate_destination_path(&state, &dest_path, true)?;
+    let validated_out = state.validate_path_for_create(&out_path)?;
+
+    let op_id = export_operation_id(&job_id, timestamp);
+    let cancel = state.operations.register(&op_id);
+    let payload_job_id = job_id.clone();
+    let result = snapshot_export::export_snapshot_archive(
+        &job_id,
+        timestamp,
+        &validated_dest,
+        &validated_out,
+        format,
+        cancel.clone(),
+        move |progress| {
+            let _ = app.emit(
+                "snapshot-export-progress",
+                ExportProgressPayload {
+                    job_id: payload_job_id.clone(),
+                    timestamp,
+                    progress: progress.clone(),
+                },
+            );
+        },
+    )
+    .await;
+    state.operations.unregister(&op_id, &cancel);
+    result
+}
+
+/// Copy one file out of a snapshot into a temp folder and return the copy's
+/// path, for the opener to launch the default app on without a restore
+#[tauri::command]
+pub async fn open_file_from_snapshot(
+    state: State<'_, AppState>,
+    job_id: String,
+    timestamp: i64,
+    path: String,
+) -> Result<String> {
+    ensure_job_id(&job_id)?;
+    let job = state
+        .store
+        .get_job(&job_id)?
+        .ok_or_else(|| AmberError::job_not_found(job_id.clone()))?;
+    let validated_dest = validate_destination_path(&state, &job.dest_path, true)?;
+    let copy = snapshot_open::open_file_from_snapshot(
+        &job_id,
+        timestamp,
+        &validated_dest,
+        &path,
+        &snapshot_open::extraction_root(),
+    )
+    .await?;
+    Ok(copy.to_string_lossy().to_string())
+}
+
+fn export_operation_id(job_id: &str, timestamp: i64) -> String {
+    cancel_token::operation_id(
+        cancel_token::SNAPSHOT_EXPORT,
+        &format!("{}:{}", job_id, timestamp),
+    )
+}
+
+/// Cancel a running snapshot export; returns false if none was running
+#[tauri::command]
+pub async fn cancel_snapshot_export(
+    state: State<'_, AppState>,
+    job_id: String,
+    timestamp: i64,
+) -> Result<bool> {
+    ensure_job_id(&job_id)?;
+    Ok(state
+        .operations
+        .cancel(&export_operation_id(&job_id, timestamp)))
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct BackfillProgressPayload {
+    job_id: String,
+    dest_path: String,
+    #[serde(flatten)]
+    progress: BackfillProgress,
+}
+
+/// Compact the index on each of `destinations`, or on every job's
+/// destination when none are given. Unplugged drives are reported as skipped.
+#[tauri::command]
+pub async fn compact_all_destinations(
+    state: State<'_, AppState>,
+    destinations: Option<Vec<String>>,
+) -> Result<Vec<CompactionResult>> {
+    let destinations = match destinations {
+        Some(paths) => {
+            let mut validated = Vec::with_capacity(paths.len());
+            for path in paths {
+                match state.validate_path(&path) {
+                    Ok(path) => validated.push(path),
+                    // Can't be resolved while unplugged; compact_all skips it
+                    Err(_) if !Path::new(&path).exists() => validated.push(path),
+                    Err(e) => return Err(e),
+                }
+            }
+            validated
+        }
+        None => state
+            .store
+            .load_jobs()?
+            .into_iter()
+            .map(|job| job.dest_path)
+            .collect(),
+    };
+
+    tokio::task::spawn_blocking(move || maintenance::compact_all(&destinations))
+        .await
+        .map_err(|e| AmberError::Index(format!("Compaction task failed: {}", e)))
+}
+
+/// Index every snapshot folder on the destination that its index is missing,
+/// emitting `index-backfill-progress` events as folders are indexed
+#[tauri::command]
+pub async fn index_all_missing(
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+    job_id: String,
+    dest_path: String,
+) -> Result<BackfillReport> {
+    use tauri::Emitter;
+
+    ensure_job_id(&job_id)?;
+    let validated_dest = validate_destination_path(&state, &dest_path, true)?;
+    let active_snapshot = crate::commands::rsync::get_rsync_service()
+        .get_backup_info(&job_id)
+        .map(|info| info.snapshot_path);
+
+    let mut job = state
+        .store
+        .get_job(&job_id)?
+        .ok_or_else(|| AmberError::job_not_found(job_id.clone()))?;
+    job.dest_path = validated_dest.clone();
+
+    volume_gate::defer_while_backing_up(&validated_dest).await;
+    let op_id = cancel_token::operation_id(cancel_token::INDEX_BACKFILL, &validated_dest);
+    let cancel = state.operations.register(&op_id);
+    let payload_job_id = job_id.clone();
+    let payload_dest = validated_dest.clone();
+    let result =
+        index_backfill::index_all_missing(&job, active_snapshot, cancel.clone(), move |progress| {
+            let _ = app.emit(
+                "index-backfill-progress",
+                BackfillProgressPayload {
+                    job_id: payload_job_id.clone(),
+                    dest_path: payload_dest.clone(),
+                    progress: progress.clone(),
+                },
+            );
+        })
+        .await;
+    state.operations.unregister(&op_id, &cancel);
+
+    let report = result?;
+    for &timestamp in &report.indexed {
+        state
+            .snapshot_service
+            .invalidate_cache(&job_id, timestamp)
+            .await;
+    }
+    Ok(report)
+}
+
+/// Cancel a running `index_all_missing`; returns false if none was running
+#[tauri::command]
+pub async fn cancel_index_all_missing(
+    state: State<'_, AppState>,
+    dest_path: String,
+) -> Result<bool> {
+    let validated_dest = validate_destination_path(&state, &dest_path, false)?;
+    Ok(state.operations.cancel(&cancel_token::operation_id(
+        cancel_token::INDEX_BACKFILL,
+        &validated_dest,
+    )))
+}
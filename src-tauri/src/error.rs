@@ -12,6 +12,13 @@ pub enum AmberError {
     #[error("Rsync failed: {0}")]
     Rsync(String),
 
+    #[error(
+        "Rsync failed: the local ({local}) and remote ({remote}) rsync could not agree on a \
+         protocol. Upgrade rsync on the older side, or set a protocol version for this job. \
+         If both are recent, check that the remote login shell prints nothing."
+    )]
+    RsyncProtocolMismatch { local: String, remote: String },
+
     // Snapshot operations
     #[error("Snapshot error: {0}")]
     Snapshot(String),
@@ -54,6 +61,14 @@ pub enum AmberError {
     #[error("Index error: {0}")]
     Index(String),
 
+    /// Another connection held the index locked for longer than SQLite
+    /// waits; the same call may well succeed a moment later
+    #[error("Index is busy: {0}")]
+    IndexBusy(String),
+
+    #[error("Too many files to index: {path} holds more than {limit}")]
+    IndexTooLarge { path: String, limit: u64 },
+
     // Serialization
     #[error("Serialization error: {0}")]
     Serialization(#[from] serde_json::Error),
@@ -86,6 +101,21 @@ pub enum AmberError {
 
     #[error("Validation error: {0}")]
     ValidationError(String),
+
+    #[error("File too large: {size} bytes exceeds the {limit} byte limit")]
+    FileTooLarge { size: u64, limit: u64 },
+
+    // Backup source pre-flight
+    #[error("Source is empty: {0}")]
+    EmptySource(String),
+
+    #[error("Source is unreadable: {0}")]
+    UnreadableSource(String),
+
+    #[error(
+        "Backup would delete {would_delete} files from the destination, over its limit of {limit}"
+    )]
+    DeleteThresholdExceeded { would_delete: u64, limit: String },
 }
 
 impl serde::Serialize for AmberError {
@@ -137,6 +167,11 @@ impl AmberError {
         AmberError::Scheduler(format!("job '{}': {}", job_id.as_ref(), reason))
     }
 
+    /// Whether the failed call is worth trying again as is
+    pub fn is_retryable(&self) -> bool {
+        matches!(self, AmberError::IndexBusy(_))
+    }
+
     /// Create a snapshot error with job context
     pub fn snapshot_for_job(job_id: impl AsRef<str>, reason: impl std::fmt::Display) -> Self {
         AmberError::Snapshot(format!("job '{}': {}", job_id.as_ref(), reason))
@@ -146,7 +181,12 @@ impl AmberError {
 // Conversion from rusqlite errors
 impl From<rusqlite::Error> for AmberError {
     fn from(err: rusqlite::Error) -> Self {
-        AmberError::Database(err.to_string())
+        match err.sqlite_error_code() {
+            Some(rusqlite::ErrorCode::DatabaseBusy | rusqlite::ErrorCode::DatabaseLocked) => {
+                AmberError::IndexBusy(err.to_string())
+            }
+            _ => AmberError::Database(err.to_string()),
+        }
     }
 }
 
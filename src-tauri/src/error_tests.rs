@@ -3,6 +3,17 @@ mod tests {
     use crate::error::{AmberError, Result};
     use std::io;
 
+    #[test]
+    fn test_protocol_mismatch_names_both_versions() {
+        let err = AmberError::RsyncProtocolMismatch {
+            local: "3.2.7".to_string(),
+            remote: "protocol 26".to_string(),
+        };
+        let message = err.to_string();
+        assert!(message.contains("local (3.2.7)"));
+        assert!(message.contains("remote (protocol 26)"));
+    }
+
     #[test]
     fn test_job_not_found_error() {
         let err = AmberError::job_not_found("test-job-123");
@@ -189,6 +200,60 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_source_preflight_errors() {
+        let err = AmberError::EmptySource("/Users/demo/Empty".to_string());
+        assert_eq!(err.to_string(), "Source is empty: /Users/demo/Empty");
+
+        let err = AmberError::UnreadableSource("/private/var: Permission denied".to_string());
+        assert_eq!(
+            err.to_string(),
+            "Source is unreadable: /private/var: Permission denied"
+        );
+    }
+
+    #[test]
+    fn test_file_too_large_error() {
+        let err = AmberError::FileTooLarge {
+            size: 30_000_000,
+            limit: 26_214_400,
+        };
+        assert_eq!(
+            err.to_string(),
+            "File too large: 30000000 bytes exceeds the 26214400 byte limit"
+        );
+    }
+
+    #[test]
+    fn test_index_too_large_error() {
+        let err = AmberError::IndexTooLarge {
+            path: "/Volumes/Backup/2024-01-01-120000".to_string(),
+            limit: 20_000_000,
+        };
+        assert_eq!(
+            err.to_string(),
+            "Too many files to index: /Volumes/Backup/2024-01-01-120000 holds more than 20000000"
+        );
+    }
+
+    #[test]
+    fn test_index_busy_is_retryable() {
+        let err =
+            AmberError::IndexBusy("Failed to query snapshots: database is locked".to_string());
+        assert!(err.is_retryable());
+        assert_eq!(
+            err.to_string(),
+            "Index is busy: Failed to query snapshots: database is locked"
+        );
+        assert!(!AmberError::Index("no such table".to_string()).is_retryable());
+
+        let busy = rusqlite::Error::SqliteFailure(
+            rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_BUSY),
+            None,
+        );
+        assert!(AmberError::from(busy).is_retryable());
+    }
+
     #[test]
     fn test_error_debug_format() {
         let err = AmberError::job_not_found("test-job");
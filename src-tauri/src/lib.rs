@@ -55,9 +55,24 @@ pub fn run() {
                             Vec::new()
                         }
                     };
+                    let log_level = app_state
+                        .store
+                        .load_preferences()
+                        .map(|p| p.log_level)
+                        .unwrap_or_default();
                     let app_handle_for_scheduler = app.handle().clone();
+                    let store_for_warmup = app_state.store.clone();
+                    let operations_for_warmup = app_state.operations.clone();
                     app.manage(app_state);
 
+                    // Self-check: jobs can't run without rsync, so say so up front
+                    match commands::rsync::get_rsync_service().check_installation() {
+                        Ok(status) => {
+                            log::info!("Found {}", status.version.as_deref().unwrap_or("rsync"))
+                        }
+                        Err(e) => log::error!("{}", e),
+                    }
+
                     tauri::async_runtime::spawn(async move {
                         scheduler.set_app_handle(app_handle_for_scheduler).await;
                         if let Err(e) = scheduler.init_with_jobs(scheduled_jobs).await {
@@ -65,13 +80,20 @@ pub fn run() {
                         }
                     });
 
-                    if cfg!(debug_assertions) {
-                        app.handle().plugin(
-                            tauri_plugin_log::Builder::default()
-                                .level(log::LevelFilter::Info)
-                                .build(),
-                        )?;
-                    }
+                    // Preload destination indexes as their drives are connected
+                    tauri::async_runtime::spawn(services::index_warmup::watch_volumes(
+                        store_for_warmup,
+                        operations_for_warmup,
+                    ));
+
+                    // Expire copies of files opened straight from snapshots
+                    tauri::async_runtime::spawn(services::snapshot_open::watch_extractions());
+
+                    // File logging is always on so release builds can be diagnosed
+                    app.handle().plugin(services::logging::plugin(
+                        services::data_dir::default_log_dir(),
+                    ))?;
+                    services::logging::set_level(services::logging::parse_level(&log_level));
 
                     // Initialize MCP plugin for Claude Code integration (dev only)
                     #[allow(unexpected_cfgs)]
@@ -141,11 +163,17 @@ pub fn run() {
             commands::jobs::get_jobs,
             commands::jobs::get_jobs_with_status,
             commands::jobs::save_job,
+            commands::jobs::set_job_enabled,
             commands::jobs::delete_job,
+            commands::jobs::rename_job_id,
             commands::jobs::delete_job_data,
+            commands::jobs::preview_excludes,
+            commands::jobs::test_ssh_destination,
+            commands::jobs::validate_job,
             // Rsync commands
             commands::rsync::run_rsync,
             commands::rsync::kill_rsync,
+            commands::rsync::check_rsync,
             // Rclone commands
             commands::rclone::check_rclone,
             commands::rclone::list_rclone_remotes,
@@ -157,16 +185,30 @@ pub fn run() {
             commands::snapshots::get_snapshot_tree,
             commands::snapshots::get_indexed_directory,
             commands::snapshots::get_indexed_directory_paginated,
+            commands::snapshots::get_indexed_directory_paged,
+            commands::snapshots::get_subtree,
             commands::snapshots::index_snapshot,
+            commands::snapshots::pin_source_state,
             commands::snapshots::is_snapshot_indexed,
             commands::snapshots::search_snapshot_files,
+            commands::snapshots::search_files_job,
+            commands::snapshots::find_path_in_snapshots,
             commands::snapshots::search_files_global,
+            commands::snapshots::get_search_diacritic_folding,
             commands::snapshots::get_snapshot_stats,
             commands::snapshots::get_file_type_stats,
+            commands::snapshots::get_extension_growth,
+            commands::snapshots::get_size_histogram,
+            commands::snapshots::get_breadcrumbs,
             commands::snapshots::get_largest_files,
+            commands::snapshots::get_recent_files,
+            commands::snapshots::get_flagged_files,
+            commands::snapshots::get_hardlink_groups,
             commands::snapshots::delete_snapshot_index,
             commands::snapshots::delete_job_index,
+            commands::snapshots::restore_estimate,
             commands::snapshots::restore_files,
+            commands::snapshots::cancel_restore,
             commands::snapshots::restore_snapshot,
             commands::snapshots::get_destination_index_path,
             commands::snapshots::destination_has_index,
@@ -181,13 +223,33 @@ pub fn run() {
             commands::snapshots::delete_snapshot_from_destination,
             commands::snapshots::list_snapshots_in_range_on_destination,
             commands::snapshots::get_job_aggregate_stats,
+            commands::snapshots::get_job_logical_footprint,
             commands::snapshots::get_job_aggregate_stats_on_destination,
             commands::snapshots::get_snapshot_density,
             commands::snapshots::get_snapshot_density_on_destination,
             // TIM-221: Snapshot comparison
             commands::snapshots::compare_snapshots,
+            commands::snapshots::compare_snapshots_page,
+            commands::snapshots::compare_snapshots_stream,
+            commands::snapshots::list_deleted_between,
+            commands::snapshots::compare_source_to_snapshot,
+            commands::snapshots::diff_directories,
+            commands::snapshots::run_index_benchmarks,
             // Snapshot pruning (delete from manifest + index + disk)
             commands::snapshots::prune_snapshot,
+            commands::snapshots::prune_snapshots,
+            commands::snapshots::replicate_snapshot,
+            commands::snapshots::export_snapshot_archive,
+            commands::snapshots::cancel_snapshot_export,
+            commands::snapshots::open_file_from_snapshot,
+            commands::snapshots::index_all_missing,
+            commands::snapshots::cancel_index_all_missing,
+            commands::snapshots::compact_all_destinations,
+            commands::snapshots::rebuild_search_index,
+            // Long operation commands
+            commands::operations::cancel_operation,
+            // Dashboard commands
+            commands::dashboard::get_overview,
             // Filesystem commands
             commands::filesystem::read_dir,
             commands::filesystem::read_file_preview,
@@ -195,6 +257,7 @@ pub fn run() {
             commands::filesystem::open_path,
             commands::filesystem::show_item_in_folder,
             commands::filesystem::get_disk_stats,
+            commands::filesystem::check_hardlink_support,
             commands::filesystem::get_volume_info,
             commands::filesystem::list_volumes,
             commands::filesystem::search_volume,
@@ -0,0 +1,218 @@
+//! Backing up files by modification time
+//!
+//! rsync can't filter on mtime. For a job with an `AgeFilter` the local
+//! source is scanned before the backup, and the files whose mtime falls in
+//! the window are written to a NUL-separated list that rsync reads with
+//! `--files-from`. Exclude patterns still apply to the listed files. SSH
+//! sources would need a scan over ssh first and are refused.
+
+use crate::error::{AmberError, Result};
+use crate::services::source_diff::{self, ScannedFile};
+use crate::types::job::{AgeFilter, SyncJob};
+use crate::utils::parse_ssh_remote;
+use std::path::{Path, PathBuf};
+
+/// Reject a window no file can fall into
+pub fn validate(filter: &AgeFilter) -> Result<()> {
+    match (filter.max_age_days, filter.min_age_days) {
+        (Some(max), Some(min)) if min > max => Err(AmberError::ValidationError(format!(
+            "Age filter keeps nothing: files must be at least {} days old but at most {}",
+            min, max
+        ))),
+        _ => Ok(()),
+    }
+}
+
+/// Paths of the scanned files `filter` keeps at `now`, sorted
+pub fn select(files: Vec<ScannedFile>, filter: &AgeFilter, now: i64) -> Vec<String> {
+    let mut kept: Vec<String> = files
+        .into_iter()
+        .filter(|f| filter.keeps(f.mtime, now))
+        .map(|f| f.path)
+        .collect();
+    kept.sort();
+    kept
+}
+
+/// Scan `job`'s source and write the files its age filter keeps to a list
+/// in `list_dir`, for `--files-from`. None when the job has no active filter.
+pub fn write_files_from(job: &SyncJob, list_dir: &Path, now: i64) -> Result<Option<PathBuf>> {
+    let Some(filter) = job.config.age_filter.as_ref().filter(|f| f.is_active()) else {
+        return Ok(None);
+    };
+    validate(filter)?;
+    if parse_ssh_remote(&job.source_path).is_some() {
+        return Err(AmberError::ValidationError(
+            "Age filters need a local source; SSH sources can't be scanned before the backup"
+                .to_string(),
+        ));
+    }
+
+    let files = source_diff::scan_local(Path::new(&job.source_path))?;
+    let total = files.len();
+    let kept = select(files, filter, now);
+    log::info!(
+        "[age_filter] Job '{}': backing up {} of {} files",
+        job.id,
+        kept.len(),
+        total
+    );
+
+    std::fs::create_dir_all(list_dir)?;
+    let list = list_dir.join(format!("{}.files-from", job.id));
+    let mut contents = Vec::new();
+    for path in &kept {
+        contents.extend_from_slice(path.as_bytes());
+        contents.push(0);
+    }
+    std::fs::write(&list, contents)?;
+    Ok(Some(list))
+}
+
+/// Add `--files-from` for `list` ahead of the source and destination, which
+/// rsync's argument lists end with
+pub fn insert_files_from(args: &mut Vec<String>, list: &Path) {
+    let at = args.len().saturating_sub(2);
+    args.splice(
+        at..at,
+        [
+            format!("--files-from={}", list.display()),
+            "--from0".to_string(),
+        ],
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::job::SyncMode;
+    use std::fs;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    const NOW: i64 = 1_750_000_000;
+    const DAY: i64 = 24 * 60 * 60;
+
+    /// A file last modified `days` before NOW
+    fn aged(root: &Path, path: &str, days: i64) {
+        let path = root.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, path.to_string_lossy().as_bytes()).unwrap();
+        let mtime = UNIX_EPOCH + Duration::from_secs((NOW - days * DAY) as u64);
+        fs::File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(mtime)
+            .unwrap();
+    }
+
+    fn job_with(source: &Path, filter: AgeFilter) -> SyncJob {
+        let mut job = SyncJob {
+            id: "age-job".to_string(),
+            source_path: source.to_string_lossy().to_string(),
+            mode: SyncMode::Mirror,
+            ..SyncJob::default()
+        };
+        job.config.age_filter = Some(filter);
+        job
+    }
+
+    fn listed(list: &Path) -> Vec<String> {
+        fs::read(list)
+            .unwrap()
+            .split(|&b| b == 0)
+            .filter(|p| !p.is_empty())
+            .map(|p| String::from_utf8(p.to_vec()).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_generated_list_matches_the_age_window() {
+        let source = tempfile::tempdir().unwrap();
+        let lists = tempfile::tempdir().unwrap();
+        aged(source.path(), "today.txt", 0);
+        aged(source.path(), "docs/last-week.txt", 6);
+        aged(source.path(), "docs/last-month.txt", 30);
+        aged(source.path(), "archive/2019/taxes.pdf", 6 * 365);
+
+        // "Only files modified in the last week"
+        let job = job_with(
+            source.path(),
+            AgeFilter {
+                max_age_days: Some(7),
+                min_age_days: None,
+            },
+        );
+        let list = write_files_from(&job, lists.path(), NOW).unwrap().unwrap();
+        assert_eq!(listed(&list), vec!["docs/last-week.txt", "today.txt"]);
+
+        // "Don't back up files older than 5 years"
+        let job = job_with(
+            source.path(),
+            AgeFilter {
+                max_age_days: Some(5 * 365),
+                min_age_days: None,
+            },
+        );
+        let list = write_files_from(&job, lists.path(), NOW).unwrap().unwrap();
+        assert_eq!(
+            listed(&list),
+            vec!["docs/last-month.txt", "docs/last-week.txt", "today.txt"]
+        );
+
+        // Settled files only: at least a week old, at most a year
+        let job = job_with(
+            source.path(),
+            AgeFilter {
+                max_age_days: Some(365),
+                min_age_days: Some(7),
+            },
+        );
+        let list = write_files_from(&job, lists.path(), NOW).unwrap().unwrap();
+        assert_eq!(listed(&list), vec!["docs/last-month.txt"]);
+    }
+
+    #[test]
+    fn test_inactive_impossible_and_remote_filters() {
+        let source = tempfile::tempdir().unwrap();
+        let lists = tempfile::tempdir().unwrap();
+
+        let job = job_with(source.path(), AgeFilter::default());
+        assert_eq!(write_files_from(&job, lists.path(), NOW).unwrap(), None);
+
+        let job = job_with(
+            source.path(),
+            AgeFilter {
+                max_age_days: Some(7),
+                min_age_days: Some(30),
+            },
+        );
+        assert!(write_files_from(&job, lists.path(), NOW).is_err());
+
+        let mut job = job_with(
+            source.path(),
+            AgeFilter {
+                max_age_days: Some(7),
+                min_age_days: None,
+            },
+        );
+        job.source_path = "me@nas:/photos".to_string();
+        assert!(write_files_from(&job, lists.path(), NOW).is_err());
+    }
+
+    #[test]
+    fn test_files_from_goes_before_source_and_dest() {
+        let mut args = vec!["-a".to_string(), "/src/".to_string(), "/dest".to_string()];
+        insert_files_from(&mut args, Path::new("/lists/job.files-from"));
+        assert_eq!(
+            args,
+            vec![
+                "-a",
+                "--files-from=/lists/job.files-from",
+                "--from0",
+                "/src/",
+                "/dest"
+            ]
+        );
+    }
+}
@@ -0,0 +1,246 @@
+//! Common interface over the rsync and rclone backends
+//!
+//! Local and SSH jobs back up with rsync, cloud jobs with rclone. Both track
+//! the process of each running job, can kill it and report its latest
+//! progress; `BackupRunner` exposes that uniformly so the scheduler and the
+//! tray don't need to know which backend a job uses.
+
+use crate::error::{AmberError, Result};
+use crate::services::rclone_service::RcloneService;
+use crate::services::rsync_service::RsyncService;
+use crate::types::job::{DestinationType, SyncJob};
+use futures::future::BoxFuture;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// The tool that backs up a job
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Backend {
+    Rsync,
+    Rclone,
+}
+
+impl Backend {
+    /// Cloud destinations go through rclone, everything else through rsync
+    pub fn for_job(job: &SyncJob) -> Self {
+        match job.destination_type {
+            Some(DestinationType::Cloud) => Backend::Rclone,
+            _ => Backend::Rsync,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Backend::Rsync => "rsync",
+            Backend::Rclone => "rclone",
+        }
+    }
+}
+
+/// Latest progress reported by a running backup
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RunProgress {
+    /// As the backend prints it, e.g. "1.23G" or "1.234 GiB"
+    pub transferred: String,
+    pub percentage: u8,
+    pub speed: String,
+    pub eta: Option<String>,
+    pub current_file: Option<String>,
+}
+
+/// Processes and progress of the jobs one backend is running
+#[derive(Debug, Default)]
+pub struct RunTracker {
+    pids: Mutex<HashMap<String, u32>>, // job_id -> pid
+    progress: Mutex<HashMap<String, RunProgress>>,
+}
+
+impl RunTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record that `job_id` is running as process `pid`
+    pub fn start(&self, job_id: &str, pid: u32) {
+        if let Ok(mut pids) = self.pids.lock() {
+            pids.insert(job_id.to_string(), pid);
+        }
+        if let Ok(mut progress) = self.progress.lock() {
+            progress.remove(job_id);
+        }
+    }
+
+    /// Forget `job_id`'s process and progress once it has exited
+    pub fn finish(&self, job_id: &str) {
+        if let Ok(mut pids) = self.pids.lock() {
+            pids.remove(job_id);
+        }
+        if let Ok(mut progress) = self.progress.lock() {
+            progress.remove(job_id);
+        }
+    }
+
+    /// Stop tracking `job_id`'s process and return its pid, to kill it
+    pub fn take_pid(&self, job_id: &str) -> Option<u32> {
+        self.pids.lock().ok()?.remove(job_id)
+    }
+
+    pub fn is_running(&self, job_id: &str) -> bool {
+        self.pids
+            .lock()
+            .map(|pids| pids.contains_key(job_id))
+            .unwrap_or(false)
+    }
+
+    pub fn record_progress(&self, job_id: &str, progress: RunProgress) {
+        if let Ok(mut all) = self.progress.lock() {
+            all.insert(job_id.to_string(), progress);
+        }
+    }
+
+    pub fn progress(&self, job_id: &str) -> Option<RunProgress> {
+        self.progress.lock().ok()?.get(job_id).cloned()
+    }
+}
+
+/// A backup backend as seen by the scheduler and the tray
+pub trait BackupRunner: Send + Sync {
+    fn backend(&self) -> Backend;
+
+    /// Where the backend tracks its running jobs
+    fn tracker(&self) -> &RunTracker;
+
+    /// Spawn a backup of `job` and wait for it to finish. rsync reports its
+    /// output through `app`'s events and can't run without it.
+    fn run(&self, job: SyncJob, app: Option<tauri::AppHandle>) -> BoxFuture<'static, Result<()>>;
+
+    /// Kill `job_id`'s backup if it is running
+    fn kill(&self, job_id: &str) -> Result<()>;
+
+    fn is_running(&self, job_id: &str) -> bool {
+        self.tracker().is_running(job_id)
+    }
+
+    fn progress(&self, job_id: &str) -> Option<RunProgress> {
+        self.tracker().progress(job_id)
+    }
+}
+
+impl BackupRunner for RsyncService {
+    fn backend(&self) -> Backend {
+        Backend::Rsync
+    }
+
+    fn tracker(&self) -> &RunTracker {
+        self.runs()
+    }
+
+    fn run(&self, job: SyncJob, app: Option<tauri::AppHandle>) -> BoxFuture<'static, Result<()>> {
+        Box::pin(async move {
+            let app = app.ok_or_else(|| {
+                AmberError::Scheduler(format!(
+                    "rsync job '{}' skipped: app handle not initialized",
+                    job.id
+                ))
+            })?;
+            crate::commands::rsync::run_rsync(app, job, None).await
+        })
+    }
+
+    fn kill(&self, job_id: &str) -> Result<()> {
+        self.kill_job(job_id)
+    }
+}
+
+impl BackupRunner for RcloneService {
+    fn backend(&self) -> Backend {
+        Backend::Rclone
+    }
+
+    fn tracker(&self) -> &RunTracker {
+        self.runs()
+    }
+
+    fn run(&self, job: SyncJob, _app: Option<tauri::AppHandle>) -> BoxFuture<'static, Result<()>> {
+        Box::pin(crate::commands::rclone::run_rclone(job))
+    }
+
+    fn kill(&self, job_id: &str) -> Result<()> {
+        self.kill_job(job_id)
+    }
+}
+
+/// The app-wide runner for `backend`
+pub fn runner(backend: Backend) -> &'static dyn BackupRunner {
+    match backend {
+        Backend::Rsync => crate::commands::rsync::get_rsync_service(),
+        Backend::Rclone => crate::commands::rclone::get_rclone_service(),
+    }
+}
+
+/// The runner that backs up `job`
+pub fn runner_for(job: &SyncJob) -> &'static dyn BackupRunner {
+    runner(Backend::for_job(job))
+}
+
+/// The runner currently running `job_id`, if any
+pub fn running(job_id: &str) -> Option<&'static dyn BackupRunner> {
+    [Backend::Rsync, Backend::Rclone]
+        .into_iter()
+        .map(runner)
+        .find(|runner| runner.is_running(job_id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn selects_rsync_for_local_jobs() {
+        let job = SyncJob {
+            destination_type: Some(DestinationType::Local),
+            ..SyncJob::default()
+        };
+        assert_eq!(Backend::for_job(&job), Backend::Rsync);
+        assert_eq!(Backend::for_job(&SyncJob::default()), Backend::Rsync);
+    }
+
+    #[test]
+    fn selects_rclone_for_cloud_jobs() {
+        let job = SyncJob {
+            destination_type: Some(DestinationType::Cloud),
+            ..SyncJob::default()
+        };
+        assert_eq!(Backend::for_job(&job), Backend::Rclone);
+    }
+
+    #[test]
+    fn tracker_follows_a_run() {
+        let tracker = RunTracker::new();
+        assert!(!tracker.is_running("job"));
+
+        tracker.start("job", 42);
+        assert!(tracker.is_running("job"));
+        assert_eq!(tracker.progress("job"), None);
+
+        let halfway = RunProgress {
+            transferred: "5M".to_string(),
+            percentage: 50,
+            ..RunProgress::default()
+        };
+        tracker.record_progress("job", halfway.clone());
+        assert_eq!(tracker.progress("job"), Some(halfway));
+
+        tracker.finish("job");
+        assert!(!tracker.is_running("job"));
+        assert_eq!(tracker.progress("job"), None);
+
+        tracker.start("job", 43);
+        assert_eq!(tracker.take_pid("job"), Some(43));
+        assert_eq!(tracker.take_pid("job"), None);
+        assert!(!tracker.is_running("job"));
+    }
+}
@@ -0,0 +1,152 @@
+//! Cancelling long-running operations
+//!
+//! Restores, snapshot indexing, backfills, index warm-ups, exports, volume
+//! searches and search index rebuilds can run for minutes. Each one registers a `CancelToken` under an
+//! operation id in the app's `CancelRegistry` and checks it as it works; the
+//! `cancel_operation` command flips the token, and the operation stops with
+//! `AmberError::Cancelled` at its next check. Ids are `"<kind>:<key>"`, e.g.
+//! `restore:<job id>`, so the UI can name an operation it started without
+//! being handed an id back.
+
+use crate::error::{AmberError, Result};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// Operation kinds, the first half of an operation id
+pub const RESTORE: &str = "restore";
+pub const INDEX: &str = "index";
+pub const INDEX_BACKFILL: &str = "index-backfill";
+pub const INDEX_WARMUP: &str = "index-warmup";
+pub const SNAPSHOT_EXPORT: &str = "snapshot-export";
+pub const VOLUME_SEARCH: &str = "volume-search";
+pub const FTS_REBUILD: &str = "fts-rebuild";
+
+/// The id an operation of `kind` on `key` is registered under
+pub fn operation_id(kind: &str, key: &str) -> String {
+    format!("{}:{}", kind, key)
+}
+
+/// A flag shared between an operation and whoever may cancel it. Clones
+/// share the flag.
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken {
+    flag: Arc<AtomicBool>,
+}
+
+impl CancelToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Ask the operation to stop
+    pub fn cancel(&self) {
+        self.flag.store(true, Ordering::SeqCst);
+    }
+
+    pub fn cancelled(&self) -> bool {
+        self.flag.load(Ordering::SeqCst)
+    }
+
+    /// `AmberError::Cancelled` once the token is cancelled
+    pub fn check(&self) -> Result<()> {
+        if self.cancelled() {
+            Err(AmberError::Cancelled)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn same_as(&self, other: &CancelToken) -> bool {
+        Arc::ptr_eq(&self.flag, &other.flag)
+    }
+}
+
+/// Tokens of the operations running now, keyed by operation id
+#[derive(Debug, Default)]
+pub struct CancelRegistry {
+    tokens: Mutex<HashMap<String, CancelToken>>,
+}
+
+impl CancelRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a starting operation and return its token. A second
+    /// operation under the same id takes the id over.
+    pub fn register(&self, op_id: &str) -> CancelToken {
+        let token = CancelToken::new();
+        if let Ok(mut tokens) = self.tokens.lock() {
+            tokens.insert(op_id.to_string(), token.clone());
+        }
+        token
+    }
+
+    /// Drop the token of a finished operation, unless the id has since been
+    /// taken over by another one
+    pub fn unregister(&self, op_id: &str, token: &CancelToken) {
+        if let Ok(mut tokens) = self.tokens.lock() {
+            if tokens.get(op_id).is_some_and(|t| t.same_as(token)) {
+                tokens.remove(op_id);
+            }
+        }
+    }
+
+    /// Cancel the operation running under `op_id`; returns false if none was
+    pub fn cancel(&self, op_id: &str) -> bool {
+        match self.tokens.lock() {
+            Ok(tokens) => match tokens.get(op_id) {
+                Some(token) => {
+                    token.cancel();
+                    true
+                }
+                None => false,
+            },
+            Err(_) => false,
+        }
+    }
+
+    pub fn is_running(&self, op_id: &str) -> bool {
+        self.tokens
+            .lock()
+            .map(|tokens| tokens.contains_key(op_id))
+            .unwrap_or(false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cancel_reaches_every_clone() {
+        let registry = CancelRegistry::new();
+        let op_id = operation_id(RESTORE, "job-1");
+        assert_eq!(op_id, "restore:job-1");
+
+        let token = registry.register(&op_id);
+        let worker = token.clone();
+        assert!(worker.check().is_ok());
+        assert!(registry.cancel(&op_id));
+        assert!(worker.cancelled());
+        assert!(matches!(worker.check(), Err(AmberError::Cancelled)));
+
+        registry.unregister(&op_id, &token);
+        assert!(!registry.is_running(&op_id));
+        assert!(!registry.cancel(&op_id));
+    }
+
+    #[test]
+    fn test_finished_operation_leaves_its_successor_registered() {
+        let registry = CancelRegistry::new();
+        let first = registry.register("restore:job-1");
+        let second = registry.register("restore:job-1");
+
+        registry.unregister("restore:job-1", &first);
+        assert!(registry.is_running("restore:job-1"));
+        assert!(registry.cancel("restore:job-1"));
+        assert!(second.cancelled());
+        assert!(!first.cancelled());
+    }
+}
@@ -0,0 +1,138 @@
+//! Storage overview across every job
+//!
+//! The dashboard shows totals that would otherwise take a handful of calls
+//! per job. `overview` makes one pass over the jobs, asking each job's index
+//! (its destination's when the drive is connected and indexed, the local
+//! one otherwise) for the same aggregate and footprint figures the per-job
+//! commands return, and checks each schedule against the job's last backup.
+//! A job whose destination index can't be read falls back to the local one,
+//! and a job neither can answer for gets a line carrying the error, so one
+//! bad drive doesn't blank the whole dashboard.
+
+use crate::error::{AmberError, Result};
+use crate::services::index_service::IndexService;
+use crate::services::job_scheduler::next_run_after;
+use crate::services::manifest_service;
+use crate::types::job::SyncJob;
+use chrono::{DateTime, TimeZone, Utc};
+use serde::Serialize;
+use std::path::Path;
+
+/// One job's line on the dashboard
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JobOverview {
+    pub job_id: String,
+    pub name: String,
+    pub snapshots: i64,
+    /// Sum of every snapshot's size
+    pub logical_bytes: i64,
+    /// Bytes with files shared between snapshots counted once
+    pub physical_bytes: i64,
+    /// Latest of the newest indexed snapshot and the job's last run
+    /// (milliseconds), None if it has never backed up
+    pub last_backup_ms: Option<i64>,
+    /// The schedule fired since the last backup without one being made
+    pub overdue: bool,
+    /// Why no index could be read for this job; its figures are then zero
+    pub error: Option<String>,
+}
+
+/// Totals across all jobs, plus each job's own line
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Overview {
+    pub total_jobs: usize,
+    pub total_snapshots: i64,
+    pub logical_bytes: i64,
+    pub physical_bytes: i64,
+    /// Most recent backup of any job (milliseconds)
+    pub last_backup_ms: Option<i64>,
+    /// Ids of the jobs that are overdue
+    pub overdue_jobs: Vec<String>,
+    pub jobs: Vec<JobOverview>,
+}
+
+/// Whether `job`'s schedule should have run between `last_backup_ms` and
+/// `now`. Paused and unscheduled jobs are never overdue, and neither is a job
+/// that has not backed up yet, as there is nothing to measure from.
+pub fn is_overdue(job: &SyncJob, last_backup_ms: Option<i64>, now: DateTime<Utc>) -> bool {
+    let Some(last) = last_backup_ms.and_then(|ms| Utc.timestamp_millis_opt(ms).single()) else {
+        return false;
+    };
+    next_run_after(job, last).is_some_and(|due| due < now)
+}
+
+/// The dashboard figures for `jobs` as of `now`. Jobs whose destination has
+/// a readable index are read from it; the rest from `local`.
+pub fn overview(jobs: &[SyncJob], local: &IndexService, now: DateTime<Utc>) -> Overview {
+    let lines: Vec<JobOverview> = jobs
+        .iter()
+        .map(|job| {
+            let from_destination = Path::new(&job.dest_path).is_dir()
+                && manifest_service::get_index_path(&job.dest_path).exists();
+            let line = if from_destination {
+                IndexService::for_destination(&job.dest_path)
+                    .and_then(|index| job_overview(job, &index, now))
+                    .or_else(|e| {
+                        log::warn!(
+                            "Dashboard falling back to the local index for job {}: {}",
+                            job.id,
+                            e
+                        );
+                        job_overview(job, local, now)
+                    })
+            } else {
+                job_overview(job, local, now)
+            };
+            line.unwrap_or_else(|e| unreadable_job(job, &e, now))
+        })
+        .collect();
+
+    Overview {
+        total_jobs: lines.len(),
+        total_snapshots: lines.iter().map(|j| j.snapshots).sum(),
+        logical_bytes: lines.iter().map(|j| j.logical_bytes).sum(),
+        physical_bytes: lines.iter().map(|j| j.physical_bytes).sum(),
+        last_backup_ms: lines.iter().filter_map(|j| j.last_backup_ms).max(),
+        overdue_jobs: lines
+            .iter()
+            .filter(|j| j.overdue)
+            .map(|j| j.job_id.clone())
+            .collect(),
+        jobs: lines,
+    }
+}
+
+fn job_overview(job: &SyncJob, index: &IndexService, now: DateTime<Utc>) -> Result<JobOverview> {
+    let stats = index.get_job_aggregate_stats(&job.id)?;
+    let footprint = index.get_job_logical_footprint(&job.id)?;
+    let last_backup_ms = stats.last_snapshot_ms.max(job.last_run);
+
+    Ok(JobOverview {
+        job_id: job.id.clone(),
+        name: job.name.clone(),
+        snapshots: stats.total_snapshots,
+        logical_bytes: stats.total_size_bytes,
+        physical_bytes: footprint.unique_size_bytes,
+        last_backup_ms,
+        overdue: is_overdue(job, last_backup_ms, now),
+        error: None,
+    })
+}
+
+/// The line of a job no index could answer for: only what the job itself
+/// records
+fn unreadable_job(job: &SyncJob, error: &AmberError, now: DateTime<Utc>) -> JobOverview {
+    log::warn!("Dashboard has no figures for job {}: {}", job.id, error);
+    JobOverview {
+        job_id: job.id.clone(),
+        name: job.name.clone(),
+        snapshots: 0,
+        logical_bytes: 0,
+        physical_bytes: 0,
+        last_backup_ms: job.last_run,
+        overdue: is_overdue(job, job.last_run, now),
+        error: Some(error.to_string()),
+    }
+}
@@ -35,6 +35,51 @@ pub fn get() -> &'static PathBuf {
         .expect("Data directory not initialized - call data_dir::init() first")
 }
 
+/// Application folder name under the platform base directories
+const APP_DIR_NAME: &str = "amber";
+
+/// Default production data directory.
+///
+/// Uses the platform base-dir APIs, so Linux honors `XDG_DATA_HOME`
+/// (`~/.local/share/amber` by default) and macOS keeps
+/// `~/Library/Application Support/amber`.
+pub fn default_data_dir() -> PathBuf {
+    if let Some(dir) = dirs::data_dir() {
+        dir.join(APP_DIR_NAME)
+    } else if let Some(home) = dirs::home_dir() {
+        home.join(".amber")
+    } else {
+        std::env::temp_dir().join(APP_DIR_NAME)
+    }
+}
+
+/// Default configuration directory (`XDG_CONFIG_HOME/amber` on Linux,
+/// `~/Library/Application Support/amber` on macOS)
+pub fn default_config_dir() -> PathBuf {
+    dirs::config_dir()
+        .map(|dir| dir.join(APP_DIR_NAME))
+        .unwrap_or_else(default_data_dir)
+}
+
+/// Default log directory.
+///
+/// Linux: `XDG_STATE_HOME/amber/logs` (`~/.local/state/amber/logs`).
+/// macOS: `~/Library/Logs/amber`. Elsewhere: `<data dir>/logs`.
+pub fn default_log_dir() -> PathBuf {
+    #[cfg(target_os = "macos")]
+    if let Some(home) = dirs::home_dir() {
+        return home.join("Library/Logs").join(APP_DIR_NAME);
+    }
+
+    dirs::state_dir()
+        .map(|dir| dir.join(APP_DIR_NAME).join("logs"))
+        .unwrap_or_else(|| default_data_dir().join("logs"))
+}
+
+/// Held by tests that set, or depend on, the `XDG_*` variables
+#[cfg(test)]
+pub(crate) static XDG_ENV_LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
 /// Check if the data directory has been initialized
 ///
 /// Useful for tests or conditional initialization.
@@ -58,4 +103,31 @@ mod tests {
         }
         assert!(is_initialized());
     }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_base_dirs_honor_xdg_overrides() {
+        let _env = XDG_ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let vars = ["XDG_DATA_HOME", "XDG_CONFIG_HOME", "XDG_STATE_HOME"];
+        let saved: Vec<_> = vars.iter().map(std::env::var_os).collect();
+
+        std::env::set_var("XDG_DATA_HOME", "/tmp/xdg-data");
+        std::env::set_var("XDG_CONFIG_HOME", "/tmp/xdg-config");
+        std::env::set_var("XDG_STATE_HOME", "/tmp/xdg-state");
+
+        let data = default_data_dir();
+        let config = default_config_dir();
+        let logs = default_log_dir();
+
+        for (var, value) in vars.iter().zip(saved) {
+            match value {
+                Some(v) => std::env::set_var(var, v),
+                None => std::env::remove_var(var),
+            }
+        }
+
+        assert_eq!(data, PathBuf::from("/tmp/xdg-data/amber"));
+        assert_eq!(config, PathBuf::from("/tmp/xdg-config/amber"));
+        assert_eq!(logs, PathBuf::from("/tmp/xdg-state/amber/logs"));
+    }
 }
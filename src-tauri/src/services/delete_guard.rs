@@ -0,0 +1,365 @@
+//! Refusing backups that would delete much of the destination
+//!
+//! A mirror with `--delete` removes whatever is gone from the source. When
+//! the wrong drive is mounted at the source path, or it comes up empty,
+//! that is most of the backup. A job with a `max_delete` threshold gets an
+//! `rsync -n` over the whole source before the backup; the deletions it
+//! lists are held against the threshold, and the backup is refused with
+//! `DeleteThresholdExceeded` until it is run with the override. Count
+//! thresholds are also passed to the backup as `--max-delete`.
+
+use crate::error::{AmberError, Result};
+use crate::services::rsync_service::{self, RsyncService};
+use crate::services::ssh_check::SshOutput;
+use crate::types::job::{DeleteThreshold, SyncJob, SyncMode};
+use serde::Serialize;
+
+/// rsync's exit status when it stopped deleting at `--max-delete`
+pub const EXIT_DELETE_LIMIT: i32 = 25;
+
+/// Exit statuses that still list every deletion: some files couldn't be
+/// read (23) or vanished while rsync looked at them (24)
+const PARTIAL_EXIT_CODES: [i32; 2] = [23, 24];
+
+/// What the dry run says the backup would do to the destination
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeletePreview {
+    /// Files and folders the backup would delete
+    pub would_delete: u64,
+    /// Files and folders in the destination before the backup, when rsync
+    /// printed the counts to work it out
+    pub dest_entries: Option<u64>,
+}
+
+/// The threshold `job`'s backup is held to, if any. Time Machine snapshots
+/// start from an empty folder and age-filtered runs only touch the listed
+/// files, so neither deletes anything worth guarding; a custom command is
+/// run as written.
+pub fn threshold(job: &SyncJob) -> Option<DeleteThreshold> {
+    let conf = &job.config;
+    let custom = conf
+        .custom_command
+        .as_deref()
+        .is_some_and(|c| !c.trim().is_empty());
+    let age_filtered = conf.age_filter.as_ref().is_some_and(|f| f.is_active());
+    if !conf.delete || custom || age_filtered || job.mode == SyncMode::TimeMachine {
+        return None;
+    }
+    conf.max_delete
+}
+
+/// rsync arguments for the dry run: the backup's own, itemized and with
+/// statistics, and without `--max-delete` so every deletion is listed
+pub fn dry_run_args(rsync: &RsyncService, job: &SyncJob, dest: &str) -> Vec<String> {
+    let mut unlimited = job.clone();
+    unlimited.config.max_delete = None;
+    let mut args = rsync.build_rsync_args(&unlimited, dest, None);
+    let at = args.len().saturating_sub(2);
+    args.splice(
+        at..at,
+        [
+            "--dry-run".to_string(),
+            "--itemize-changes".to_string(),
+            "--stats".to_string(),
+        ],
+    );
+    args
+}
+
+/// The count from a `--stats` line like "Number of files: 1,204 (reg: 1,100)"
+fn stat(stdout: &str, label: &str) -> Option<u64> {
+    stdout.lines().find_map(|line| {
+        let value = line.trim().strip_prefix(label)?.trim_start();
+        let digits: String = value
+            .split_whitespace()
+            .next()?
+            .chars()
+            .filter(char::is_ascii_digit)
+            .collect();
+        digits.parse().ok()
+    })
+}
+
+/// Read the deletions and the destination's size off the dry run's output
+pub fn parse_dry_run(stdout: &str) -> DeletePreview {
+    let itemized = stdout
+        .lines()
+        .filter(|line| {
+            // "*deleting   old/" itemized, "deleting old/" with plain -v
+            let line = line.trim_start();
+            line.strip_prefix('*')
+                .unwrap_or(line)
+                .starts_with("deleting ")
+        })
+        .count() as u64;
+    // rsync 3.1+ counts the deletions itself; older ones only list them
+    let would_delete = stat(stdout, "Number of deleted files:").unwrap_or(itemized);
+    // The destination ends up holding the source; before the backup it had
+    // that minus what is created plus what is deleted
+    let dest_entries = match (
+        stat(stdout, "Number of files:"),
+        stat(stdout, "Number of created files:"),
+    ) {
+        (Some(files), Some(created)) => Some((files + would_delete).saturating_sub(created)),
+        _ => None,
+    };
+    DeletePreview {
+        would_delete,
+        dest_entries,
+    }
+}
+
+/// Whether `preview` deletes more than `threshold` allows. A percentage
+/// can't be judged without the destination's size, so then any deletion is
+/// taken to exceed it.
+pub fn exceeds(threshold: DeleteThreshold, preview: &DeletePreview) -> bool {
+    match threshold {
+        DeleteThreshold::Count(max) => preview.would_delete > max,
+        DeleteThreshold::Percent(percent) => match preview.dest_entries {
+            Some(entries) => preview.would_delete * 100 > u64::from(percent) * entries,
+            None => preview.would_delete > 0,
+        },
+    }
+}
+
+/// Dry-run `job` through `run` and refuse it if it would delete more than
+/// its threshold allows. None when the job has no threshold to check.
+pub fn check<F>(rsync: &RsyncService, job: &SyncJob, run: F) -> Result<Option<DeletePreview>>
+where
+    F: FnOnce(&[String]) -> std::io::Result<SshOutput>,
+{
+    let Some(threshold) = threshold(job) else {
+        return Ok(None);
+    };
+    let target = rsync_service::target_base(job)?;
+    let args = dry_run_args(rsync, job, target.to_str().unwrap_or(""));
+    let output = run(&args)
+        .map_err(|e| AmberError::Rsync(format!("Failed to run the deletion dry run: {}", e)))?;
+    match output.exit_code {
+        Some(0) => {}
+        Some(code) if PARTIAL_EXIT_CODES.contains(&code) => {}
+        code => {
+            return Err(AmberError::Rsync(format!(
+                "Deletion dry run failed with code {:?}",
+                code
+            )))
+        }
+    }
+
+    let preview = parse_dry_run(&output.stdout);
+    if preview.dest_entries.is_none() && matches!(threshold, DeleteThreshold::Percent(_)) {
+        log::warn!(
+            "[delete_guard] Job '{}': rsync printed no file counts to check the {} limit against",
+            job.id,
+            threshold
+        );
+    }
+    if exceeds(threshold, &preview) {
+        log::warn!(
+            "[delete_guard] Refusing job '{}': it would delete {} files, over its limit of {}",
+            job.id,
+            preview.would_delete,
+            threshold
+        );
+        return Err(AmberError::DeleteThresholdExceeded {
+            would_delete: preview.would_delete,
+            limit: threshold.to_string(),
+        });
+    }
+    Ok(Some(preview))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    /// A mirror of a local source with a delete threshold
+    fn mirror_job(root: &Path, threshold: DeleteThreshold) -> SyncJob {
+        let mut job = SyncJob {
+            id: "delete-guard-job".to_string(),
+            source_path: root.join("source").to_string_lossy().to_string(),
+            dest_path: root.join("dest").to_string_lossy().to_string(),
+            mode: SyncMode::Mirror,
+            ..SyncJob::default()
+        };
+        job.config.delete = true;
+        job.config.max_delete = Some(threshold);
+        job
+    }
+
+    /// Dry run output deleting `deleted` of the `dest` entries the
+    /// destination holds, with no new files
+    fn dry_run(deleted: u64, dest: u64) -> String {
+        let mut out = String::new();
+        for i in 0..deleted {
+            out.push_str(&format!("*deleting   old/file-{}.txt\n", i));
+        }
+        out.push_str(&format!(
+            "\nNumber of files: {} (reg: {})\nNumber of created files: 0\nNumber of deleted files: {}\n",
+            dest - deleted,
+            dest - deleted,
+            deleted
+        ));
+        out
+    }
+
+    fn stub(stdout: String) -> impl FnOnce(&[String]) -> std::io::Result<SshOutput> {
+        move |_| {
+            Ok(SshOutput {
+                exit_code: Some(0),
+                stdout,
+                stderr: String::new(),
+            })
+        }
+    }
+
+    #[test]
+    fn test_dry_run_over_the_threshold_blocks_the_backup() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let job = mirror_job(dir.path(), DeleteThreshold::Count(100));
+        let result = check(&RsyncService::new(), &job, stub(dry_run(101, 1000)));
+        match result {
+            Err(AmberError::DeleteThresholdExceeded {
+                would_delete,
+                limit,
+            }) => {
+                assert_eq!(would_delete, 101);
+                assert_eq!(limit, "100 files");
+            }
+            other => panic!("expected the backup to be refused, got {:?}", other),
+        }
+
+        // The wrong drive: nearly everything in the destination would go
+        let job = mirror_job(dir.path(), DeleteThreshold::Percent(20));
+        let result = check(&RsyncService::new(), &job, stub(dry_run(990, 1000)));
+        assert!(matches!(
+            result,
+            Err(AmberError::DeleteThresholdExceeded {
+                would_delete: 990,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_dry_run_under_the_threshold_lets_the_backup_proceed() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut seen = Vec::new();
+
+        let job = mirror_job(dir.path(), DeleteThreshold::Percent(20));
+        let preview = check(&RsyncService::new(), &job, |args| {
+            seen = args.to_vec();
+            stub(dry_run(200, 1000))(args)
+        })
+        .unwrap();
+        assert_eq!(
+            preview,
+            Some(DeletePreview {
+                would_delete: 200,
+                dest_entries: Some(1000),
+            })
+        );
+        assert!(seen.contains(&"--dry-run".to_string()));
+        assert!(seen.contains(&"--delete".to_string()));
+        assert!(seen.contains(&"--itemize-changes".to_string()));
+        assert!(seen.ends_with(&[
+            format!("{}/", job.source_path),
+            format!("{}/source", job.dest_path)
+        ]));
+
+        // The dry run lists every deletion; only the backup gets --max-delete
+        let job = mirror_job(dir.path(), DeleteThreshold::Count(100));
+        let preview = check(&RsyncService::new(), &job, |args| {
+            seen = args.to_vec();
+            stub(dry_run(100, 1000))(args)
+        })
+        .unwrap();
+        assert_eq!(preview.unwrap().would_delete, 100);
+        assert!(!seen.iter().any(|a| a.starts_with("--max-delete")));
+    }
+
+    #[test]
+    fn test_percent_threshold_without_file_counts_blocks_the_backup() {
+        let dir = tempfile::tempdir().unwrap();
+        let job = mirror_job(dir.path(), DeleteThreshold::Percent(50));
+
+        // An older rsync: deletions itemized, but no counts to size the
+        // destination with
+        let stdout = "*deleting   old/report.pdf\n>f+++++++++ new.txt\n".to_string();
+        let result = check(&RsyncService::new(), &job, stub(stdout));
+        assert!(matches!(
+            result,
+            Err(AmberError::DeleteThresholdExceeded {
+                would_delete: 1,
+                ..
+            })
+        ));
+
+        let stdout = ">f+++++++++ new.txt\n".to_string();
+        let preview = check(&RsyncService::new(), &job, stub(stdout)).unwrap();
+        assert_eq!(
+            preview,
+            Some(DeletePreview {
+                would_delete: 0,
+                dest_entries: None,
+            })
+        );
+    }
+
+    #[test]
+    fn test_jobs_without_a_threshold_skip_the_dry_run() {
+        let dir = tempfile::tempdir().unwrap();
+        let no_dry_run = |_: &[String]| -> std::io::Result<SshOutput> { panic!("no dry run") };
+
+        let mut job = mirror_job(dir.path(), DeleteThreshold::Count(0));
+        job.config.max_delete = None;
+        assert_eq!(check(&RsyncService::new(), &job, no_dry_run).unwrap(), None);
+
+        let mut job = mirror_job(dir.path(), DeleteThreshold::Count(0));
+        job.config.delete = false;
+        assert_eq!(check(&RsyncService::new(), &job, no_dry_run).unwrap(), None);
+
+        let mut job = mirror_job(dir.path(), DeleteThreshold::Count(0));
+        job.mode = SyncMode::TimeMachine;
+        assert_eq!(check(&RsyncService::new(), &job, no_dry_run).unwrap(), None);
+    }
+
+    #[test]
+    fn test_parses_itemized_deletions_from_older_rsync() {
+        let stdout = "*deleting   old/report.pdf\n\
+                      *deleting   old/\n\
+                      >f+++++++++ new.txt\n\
+                      \n\
+                      Number of files: 1,204\n\
+                      Number of files transferred: 1\n";
+        let preview = parse_dry_run(stdout);
+        assert_eq!(preview.would_delete, 2);
+        assert_eq!(preview.dest_entries, None);
+
+        // Without the destination's size a percentage can't be judged, so
+        // only a run that deletes nothing is within it
+        assert!(exceeds(DeleteThreshold::Percent(100), &preview));
+        assert!(!exceeds(
+            DeleteThreshold::Percent(1),
+            &DeletePreview {
+                would_delete: 0,
+                dest_entries: None,
+            }
+        ));
+        assert!(exceeds(DeleteThreshold::Count(1), &preview));
+
+        let stdout = "Number of files: 1,204 (reg: 1,100, dir: 104)\n\
+                      Number of created files: 4 (reg: 4)\n\
+                      Number of deleted files: 0\n";
+        assert_eq!(
+            parse_dry_run(stdout),
+            DeletePreview {
+                would_delete: 0,
+                dest_entries: Some(1200),
+            }
+        );
+    }
+}
@@ -1,6 +1,9 @@
-//! Dev playground at `~/.amber-dev/` — one job, ~20K real files, multiple snapshots.
+//! Dev playground at `<app data>/dev-playground/` — one job, ~20K real files, multiple snapshots.
 
 use crate::error::{AmberError, Result};
+use crate::services::data_dir;
+use crate::services::diagnostics;
+pub use crate::services::diagnostics::BenchmarkResult;
 use crate::services::index_service::IndexService;
 use crate::services::manifest_service;
 use crate::services::store::Store;
@@ -30,16 +33,6 @@ pub struct SeedResult {
     pub duration_ms: u64,
 }
 
-#[derive(Debug, Clone, serde::Serialize)]
-pub struct BenchmarkResult {
-    pub operation: String,
-    pub iterations: usize,
-    pub avg_ms: f64,
-    pub min_ms: f64,
-    pub max_ms: f64,
-    pub total_ms: f64,
-}
-
 #[derive(Debug, Clone, serde::Serialize)]
 pub struct ChurnResult {
     pub added: usize,
@@ -53,10 +46,10 @@ pub struct DevSeeder {
     app_data_path: PathBuf,
 }
 
+/// Under the production data dir (XDG-aware on Linux), even when the app
+/// runs on mock-data, so the playground survives a data dir switch
 fn playground_root() -> PathBuf {
-    dirs::home_dir()
-        .expect("No home directory found")
-        .join(".amber-dev")
+    data_dir::default_data_dir().join("dev-playground")
 }
 
 impl DevSeeder {
@@ -147,21 +140,23 @@ impl DevSeeder {
             // Use a single timestamp for both manifest and index
             let ts = chrono::Utc::now().timestamp_millis();
 
-            let snapshot = ManifestSnapshot {
-                id: ts.to_string(),
-                timestamp: ts,
-                folder_name: folder.clone(),
-                file_count: snap_files,
-                total_size: snap_size,
-                status: ManifestSnapshotStatus::Complete,
-                duration_ms: Some(start.elapsed().as_millis() as u64),
-                changes_count: if snap_i > 0 { Some(350) } else { None },
-            };
-            manifest.add_snapshot(snapshot);
-
-            // Index with the SAME timestamp
+            // Index first so the entry can carry the real changes since the
+            // previous snapshot; manifest and index share the SAME timestamp
             dest_index.index_snapshot(JOB_ID, ts, &snap_dir.to_string_lossy())?;
 
+            let mut snapshot = ManifestSnapshot::from_timestamp(
+                ts,
+                folder.clone(),
+                snap_files,
+                snap_size,
+                ManifestSnapshotStatus::Complete,
+            );
+            snapshot.duration_ms = Some(start.elapsed().as_millis() as u64);
+            if let Some(changes) = dest_index.changes_since_previous(JOB_ID, ts)? {
+                snapshot = snapshot.with_changes(changes);
+            }
+            manifest.add_snapshot(snapshot);
+
             log::info!(
                 "Snapshot {}: {} files, {} bytes",
                 snap_i + 1,
@@ -225,7 +220,7 @@ impl DevSeeder {
         churn_source(source, 200, 100, 50)
     }
 
-    /// Remove dev job and delete `~/.amber-dev/`.
+    /// Remove dev job and delete the playground.
     pub fn clear(&self) -> Result<()> {
         let jobs = self.store.load_jobs()?;
         for job in &jobs {
@@ -248,32 +243,7 @@ impl DevSeeder {
             .ok_or_else(|| AmberError::Index("No dev job for benchmarks".to_string()))?;
 
         let idx = IndexService::for_destination(&job.dest_path)?;
-        let n = 100;
-
-        Ok(vec![
-            bench("list_snapshots", n, || {
-                idx.list_snapshots(JOB_ID)?;
-                Ok(())
-            })?,
-            bench("directory_contents", n, || {
-                let snaps = idx.list_snapshots(JOB_ID)?;
-                if let Some(s) = snaps.first() {
-                    idx.get_directory_contents(JOB_ID, s.timestamp, "")?;
-                }
-                Ok(())
-            })?,
-            bench("fts_search", n, || {
-                idx.search_files_global("readme", None, 50)?;
-                Ok(())
-            })?,
-            bench("snapshot_stats", n, || {
-                let snaps = idx.list_snapshots(JOB_ID)?;
-                if let Some(s) = snaps.first() {
-                    idx.get_snapshot_stats(JOB_ID, s.timestamp)?;
-                }
-                Ok(())
-            })?,
-        ])
+        diagnostics::index_benchmarks(&idx, JOB_ID, 100)
     }
 }
 
@@ -1056,32 +1026,17 @@ fn capitalize(s: &str) -> String {
     }
 }
 
-fn bench(name: &str, n: usize, mut f: impl FnMut() -> Result<()>) -> Result<BenchmarkResult> {
-    let mut times = Vec::with_capacity(n);
-    for _ in 0..n {
-        let t = Instant::now();
-        f()?;
-        times.push(t.elapsed().as_secs_f64() * 1000.0);
-    }
-    let total: f64 = times.iter().sum();
-    Ok(BenchmarkResult {
-        operation: name.to_string(),
-        iterations: n,
-        avg_ms: total / n as f64,
-        min_ms: times.iter().cloned().fold(f64::INFINITY, f64::min),
-        max_ms: times.iter().cloned().fold(f64::NEG_INFINITY, f64::max),
-        total_ms: total,
-    })
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
 
     #[test]
     fn test_playground_root() {
+        let _env = data_dir::XDG_ENV_LOCK
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
         let root = playground_root();
-        assert!(root.to_string_lossy().contains(".amber-dev"));
+        assert_eq!(root, data_dir::default_data_dir().join("dev-playground"));
     }
 
     #[test]
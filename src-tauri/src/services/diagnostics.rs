@@ -0,0 +1,140 @@
+//! Index benchmarks that are safe to run in release builds
+//!
+//! The dev tools benchmark the seeded playground only. Users reporting slow
+//! browsing on their own hardware need the same numbers for their real
+//! destination, so support can compare like with like. Nothing here writes
+//! to the index; it is only run when explicitly asked for.
+
+use crate::error::{AmberError, Result};
+use crate::services::index_service::IndexService;
+use crate::services::manifest_service;
+use std::path::Path;
+use std::time::Instant;
+
+/// Iterations per operation for `run_index_benchmarks`; enough to smooth out
+/// noise without keeping a large index busy for long
+pub const SUPPORT_BENCH_ITERATIONS: usize = 20;
+
+/// Timing stats for one benchmarked operation (milliseconds)
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct BenchmarkResult {
+    pub operation: String,
+    pub iterations: usize,
+    pub avg_ms: f64,
+    pub min_ms: f64,
+    pub max_ms: f64,
+    pub total_ms: f64,
+}
+
+/// Run `f` `n` times and summarize how long each call took
+pub fn bench(name: &str, n: usize, mut f: impl FnMut() -> Result<()>) -> Result<BenchmarkResult> {
+    let mut times = Vec::with_capacity(n);
+    for _ in 0..n {
+        let t = Instant::now();
+        f()?;
+        times.push(t.elapsed().as_secs_f64() * 1000.0);
+    }
+    let total: f64 = times.iter().sum();
+    Ok(BenchmarkResult {
+        operation: name.to_string(),
+        iterations: n,
+        avg_ms: total / n as f64,
+        min_ms: times.iter().cloned().fold(f64::INFINITY, f64::min),
+        max_ms: times.iter().cloned().fold(f64::NEG_INFINITY, f64::max),
+        total_ms: total,
+    })
+}
+
+/// The read paths the UI leans on, timed against `job_id`'s newest snapshot
+pub fn index_benchmarks(
+    idx: &IndexService,
+    job_id: &str,
+    iterations: usize,
+) -> Result<Vec<BenchmarkResult>> {
+    Ok(vec![
+        bench("list_snapshots", iterations, || {
+            idx.list_snapshots(job_id)?;
+            Ok(())
+        })?,
+        bench("directory_contents", iterations, || {
+            let snaps = idx.list_snapshots(job_id)?;
+            if let Some(s) = snaps.first() {
+                idx.get_directory_contents(job_id, s.timestamp, "")?;
+            }
+            Ok(())
+        })?,
+        bench("fts_search", iterations, || {
+            idx.search_files_global("readme", None, 50)?;
+            Ok(())
+        })?,
+        bench("snapshot_stats", iterations, || {
+            let snaps = idx.list_snapshots(job_id)?;
+            if let Some(s) = snaps.first() {
+                idx.get_snapshot_stats(job_id, s.timestamp)?;
+            }
+            Ok(())
+        })?,
+    ])
+}
+
+/// Benchmark the existing index on `dest_path` for a support bundle, using
+/// the job with the most recent snapshot. Fails rather than creating an
+/// index if the destination has none.
+pub fn run_index_benchmarks(dest_path: &str) -> Result<Vec<BenchmarkResult>> {
+    if !manifest_service::get_index_path(dest_path).exists() {
+        return Err(AmberError::NotFound(format!(
+            "No index on {}",
+            Path::new(dest_path).display()
+        )));
+    }
+    let idx = IndexService::for_destination(dest_path)?;
+    let latest = idx
+        .latest_snapshot()?
+        .ok_or_else(|| AmberError::NotFound(format!("No indexed snapshots on {}", dest_path)))?;
+
+    let results = index_benchmarks(&idx, &latest.job_id, SUPPORT_BENCH_ITERATIONS)?;
+    for result in &results {
+        log::info!(
+            "[diagnostics] Benchmark '{}': avg={:.3}ms, min={:.3}ms, max={:.3}ms ({} iterations)",
+            result.operation,
+            result.avg_ms,
+            result.min_ms,
+            result.max_ms,
+            result.iterations
+        );
+    }
+    Ok(results)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bench_stats_are_ordered() {
+        let mut calls = 0;
+        let result = bench("sleep", 5, || {
+            calls += 1;
+            std::thread::sleep(std::time::Duration::from_millis(1));
+            Ok(())
+        })
+        .unwrap();
+
+        assert_eq!(calls, 5);
+        assert_eq!(result.iterations, 5);
+        assert!(result.min_ms > 0.0);
+        assert!(result.min_ms <= result.avg_ms && result.avg_ms <= result.max_ms);
+        assert!((result.total_ms - result.avg_ms * 5.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_bench_stops_on_error() {
+        let mut calls = 0;
+        let result = bench("fails", 5, || {
+            calls += 1;
+            Err(AmberError::Index("boom".to_string()))
+        });
+        assert!(result.is_err());
+        assert_eq!(calls, 1);
+    }
+}
@@ -0,0 +1,249 @@
+//! Compare two directory trees on disk, without an index
+//!
+//! Used to verify a restore: the restored folder against the snapshot folder
+//! it came from. Both trees are walked in the same sorted order and merged as
+//! they are read, so memory stays flat however many files they hold. Only
+//! regular files are compared, by relative path and size; Amber's own
+//! `.amber-meta` folder is skipped on both sides.
+
+use crate::error::{AmberError, Result};
+use crate::services::index_service::DiffEntry;
+use crate::services::manifest_service::AMBER_META_DIR;
+use serde::Serialize;
+use std::cmp::Ordering;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+/// How a file differs between the two trees
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum DirDiffKind {
+    OnlyInA,
+    OnlyInB,
+    SizeDiffers,
+}
+
+/// Full counts of a directory diff
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DirDiffTotals {
+    pub only_in_a: u64,
+    pub only_in_b: u64,
+    pub size_differs: u64,
+    /// Files in both trees with the same size
+    pub matching: u64,
+}
+
+/// How directory B differs from directory A
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DirectoryDiff {
+    pub only_in_a: Vec<DiffEntry>,
+    pub only_in_b: Vec<DiffEntry>,
+    pub size_differs: Vec<DiffEntry>,
+    pub totals: DirDiffTotals,
+    /// Set when `limit` cut any list short; `totals` still counts everything
+    pub truncated: bool,
+}
+
+/// Regular files under `root` as (relative path, size), in the order the
+/// merge relies on: names sorted within each folder and a folder's contents
+/// right after it, which is the component-wise order `Path` compares in
+fn sorted_files(root: &Path) -> impl Iterator<Item = (PathBuf, i64)> + '_ {
+    WalkDir::new(root)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_entry(|e| e.depth() == 0 || e.file_name() != AMBER_META_DIR)
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_file())
+        .filter_map(move |entry| {
+            let size = entry.metadata().ok()?.len() as i64;
+            let relative = entry.path().strip_prefix(root).ok()?.to_path_buf();
+            Some((relative, size))
+        })
+}
+
+fn ensure_dir(root: &Path) -> Result<()> {
+    if root.is_dir() {
+        Ok(())
+    } else {
+        Err(AmberError::InvalidPath(format!(
+            "Not a directory: {}",
+            root.display()
+        )))
+    }
+}
+
+/// Walk `a` and `b` together and hand every file that differs to `on_entry`
+/// as it is found, in path order. Returns the full counts. Returning an error
+/// from `on_entry` stops the walk and is passed through.
+pub fn diff_directories_each<F>(a: &Path, b: &Path, mut on_entry: F) -> Result<DirDiffTotals>
+where
+    F: FnMut(DirDiffKind, DiffEntry) -> Result<()>,
+{
+    ensure_dir(a)?;
+    ensure_dir(b)?;
+
+    let entry = |path: &Path, size_a, size_b| DiffEntry {
+        path: path.to_string_lossy().replace('\\', "/"),
+        size_a,
+        size_b,
+    };
+
+    let mut totals = DirDiffTotals::default();
+    let mut files_a = sorted_files(a).peekable();
+    let mut files_b = sorted_files(b).peekable();
+    loop {
+        let order = match (files_a.peek(), files_b.peek()) {
+            (None, None) => break,
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (Some((path_a, _)), Some((path_b, _))) => path_a.cmp(path_b),
+        };
+
+        match order {
+            Ordering::Less => {
+                let Some((path, size)) = files_a.next() else {
+                    break;
+                };
+                totals.only_in_a += 1;
+                on_entry(DirDiffKind::OnlyInA, entry(&path, Some(size), None))?;
+            }
+            Ordering::Greater => {
+                let Some((path, size)) = files_b.next() else {
+                    break;
+                };
+                totals.only_in_b += 1;
+                on_entry(DirDiffKind::OnlyInB, entry(&path, None, Some(size)))?;
+            }
+            Ordering::Equal => {
+                let (Some((path, size_a)), Some((_, size_b))) = (files_a.next(), files_b.next())
+                else {
+                    break;
+                };
+                if size_a == size_b {
+                    totals.matching += 1;
+                } else {
+                    totals.size_differs += 1;
+                    on_entry(
+                        DirDiffKind::SizeDiffers,
+                        entry(&path, Some(size_a), Some(size_b)),
+                    )?;
+                }
+            }
+        }
+    }
+
+    Ok(totals)
+}
+
+/// Compare the files under `a` and `b`. Each list keeps at most `limit`
+/// entries (all when None), sorted by path.
+pub fn diff_directories(a: &Path, b: &Path, limit: Option<usize>) -> Result<DirectoryDiff> {
+    let limit = limit.unwrap_or(usize::MAX);
+    let mut diff = DirectoryDiff {
+        only_in_a: Vec::new(),
+        only_in_b: Vec::new(),
+        size_differs: Vec::new(),
+        totals: DirDiffTotals::default(),
+        truncated: false,
+    };
+
+    diff.totals = diff_directories_each(a, b, |kind, entry| {
+        let list = match kind {
+            DirDiffKind::OnlyInA => &mut diff.only_in_a,
+            DirDiffKind::OnlyInB => &mut diff.only_in_b,
+            DirDiffKind::SizeDiffers => &mut diff.size_differs,
+        };
+        if list.len() < limit {
+            list.push(entry);
+        } else {
+            diff.truncated = true;
+        }
+        Ok(())
+    })?;
+
+    Ok(diff)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn write(root: &Path, path: &str, content: &str) {
+        let path = root.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+
+    fn paths(entries: &[DiffEntry]) -> Vec<&str> {
+        entries.iter().map(|e| e.path.as_str()).collect()
+    }
+
+    #[test]
+    fn test_reports_the_three_categories() {
+        let a = tempfile::tempdir().unwrap();
+        let b = tempfile::tempdir().unwrap();
+
+        for root in [a.path(), b.path()] {
+            write(root, "same.txt", "same");
+            write(root, "docs/report.pdf", "report");
+            // As a plain string "docs.txt" sorts before "docs/..."; the merge must not trip
+            write(root, "docs.txt", "notes");
+        }
+        write(a.path(), "docs/grown.txt", "abc");
+        write(b.path(), "docs/grown.txt", "abcdef");
+        write(a.path(), "docs/old/gone.txt", "gone");
+        write(a.path(), "z-last.txt", "z");
+        write(b.path(), "docs/new.txt", "new");
+        write(b.path(), "extra/deep/file.bin", "0123456789");
+        // Manifests and indexes aren't part of the comparison
+        write(a.path(), ".amber-meta/index.db", "index");
+
+        let diff = diff_directories(a.path(), b.path(), None).unwrap();
+        assert_eq!(
+            paths(&diff.only_in_a),
+            vec!["docs/old/gone.txt", "z-last.txt"]
+        );
+        assert_eq!(
+            paths(&diff.only_in_b),
+            vec!["docs/new.txt", "extra/deep/file.bin"]
+        );
+        assert_eq!(paths(&diff.size_differs), vec!["docs/grown.txt"]);
+        assert_eq!(diff.size_differs[0].size_a, Some(3));
+        assert_eq!(diff.size_differs[0].size_b, Some(6));
+        assert_eq!(diff.only_in_b[1].size_b, Some(10));
+        assert_eq!(
+            diff.totals,
+            DirDiffTotals {
+                only_in_a: 2,
+                only_in_b: 2,
+                size_differs: 1,
+                matching: 3,
+            }
+        );
+        assert!(!diff.truncated);
+
+        let capped = diff_directories(a.path(), b.path(), Some(1)).unwrap();
+        assert_eq!(paths(&capped.only_in_a), vec!["docs/old/gone.txt"]);
+        assert_eq!(capped.totals, diff.totals);
+        assert!(capped.truncated);
+    }
+
+    #[test]
+    fn test_identical_trees_and_missing_roots() {
+        let a = tempfile::tempdir().unwrap();
+        let b = tempfile::tempdir().unwrap();
+        for root in [a.path(), b.path()] {
+            write(root, "photos/2024/beach.jpg", "jpeg");
+        }
+
+        let diff = diff_directories(a.path(), b.path(), None).unwrap();
+        assert!(diff.only_in_a.is_empty() && diff.only_in_b.is_empty());
+        assert!(diff.size_differs.is_empty());
+        assert_eq!(diff.totals.matching, 1);
+
+        assert!(diff_directories(a.path(), &b.path().join("missing"), None).is_err());
+    }
+}
@@ -0,0 +1,414 @@
+//! Exclude-pattern preview
+//!
+//! Runs rsync in list-only dry-run mode over the top level of a source with a
+//! job's exclude patterns, so users can see what a pattern actually drops
+//! before the first real backup. Patterns are passed exactly as
+//! `RsyncService::build_rsync_args` passes them, after `normalize_patterns`.
+
+use crate::error::{AmberError, Result};
+use globset::{GlobBuilder, GlobMatcher};
+use serde::Serialize;
+use std::collections::{BTreeSet, HashSet};
+use std::path::Path;
+use std::process::Command;
+
+/// A pattern that is almost certainly a mistake
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExcludeWarning {
+    pub pattern: String,
+    pub reason: String,
+}
+
+/// What the patterns do to the top level of the source
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExcludePreview {
+    /// Top-level entries in the source
+    pub total_entries: usize,
+    /// Top-level entries rsync would skip, sorted by name
+    pub excluded: Vec<String>,
+    pub warnings: Vec<ExcludeWarning>,
+}
+
+/// Why `pattern` is dangerous, if it is
+pub(crate) fn dangerous_pattern_reason(pattern: &str) -> Option<&'static str> {
+    match pattern {
+        "/" | "/*" | "/**" | "/***" => Some("matches the root of the source"),
+        "*" | "**" | "***" | "*/" | "**/" => Some("matches every file or directory"),
+        "." | "./" => Some("matches the source directory itself"),
+        _ if pattern.chars().all(|c| c == '*' || c == '/') => {
+            Some("matches every file or directory")
+        }
+        _ => None,
+    }
+}
+
+/// Extract the entry name from one `rsync --list-only` line
+/// (`perms size date time name`, with ` -> target` after symlink names)
+fn parse_list_only_name(line: &str) -> Option<&str> {
+    let mut rest = line.trim_start();
+    let is_symlink = rest.starts_with('l');
+    for _ in 0..4 {
+        let end = rest.find(char::is_whitespace)?;
+        rest = rest[end..].trim_start();
+    }
+    if rest.is_empty() {
+        return None;
+    }
+    if is_symlink {
+        if let Some((name, _target)) = rest.split_once(" -> ") {
+            return Some(name);
+        }
+    }
+    Some(rest)
+}
+
+fn trimmed_patterns(patterns: &[String]) -> Vec<&str> {
+    let mut seen = HashSet::new();
+    patterns
+        .iter()
+        .map(|p| p.trim())
+        .filter(|p| !p.is_empty() && seen.insert(*p))
+        .collect()
+}
+
+/// Patterns as rsync gets them: trimmed, empty ones dropped and repeats
+/// removed. The first occurrence wins, so distinct patterns keep their order.
+pub fn normalize_patterns(patterns: &[String]) -> Vec<String> {
+    trimmed_patterns(patterns)
+        .into_iter()
+        .map(str::to_string)
+        .collect()
+}
+
+/// Whether `pattern` is a name pattern: no `/` other than a trailing one, so
+/// rsync matches it against every path component
+fn is_name_pattern(pattern: &str) -> bool {
+    !pattern.trim_end_matches('/').contains('/')
+}
+
+/// The first of `broader` that already excludes everything `narrow` does.
+/// Only name patterns are considered, matched against `narrow`'s literal
+/// components: excluding a directory above the last component, or the last
+/// component itself, covers it. A trailing `/` limits a pattern to directories.
+fn covering_pattern<'a>(narrow: &str, broader: &[&'a str]) -> Option<&'a str> {
+    let narrow_dir_only = narrow.ends_with('/');
+    let components: Vec<&str> = narrow.trim_matches('/').split('/').collect();
+
+    broader.iter().copied().find(|&broad| {
+        if broad == narrow || !is_name_pattern(broad) {
+            return false;
+        }
+        let broad_dir_only = broad.ends_with('/');
+        let Ok(glob) = GlobBuilder::new(broad.trim_end_matches('/'))
+            .literal_separator(true)
+            .build()
+        else {
+            return false;
+        };
+        let matcher = glob.compile_matcher();
+
+        components.iter().enumerate().any(|(i, component)| {
+            let is_last = i == components.len() - 1;
+            let literal = !component.contains(['*', '?', '[']);
+            literal
+                && !component.is_empty()
+                && !(is_last && broad_dir_only && !narrow_dir_only)
+                && matcher.is_match(component)
+        })
+    })
+}
+
+/// Patterns that a broader pattern in the same list already covers
+pub fn subsumed_patterns<S: AsRef<str>>(patterns: &[S]) -> Vec<ExcludeWarning> {
+    let patterns: Vec<&str> = patterns.iter().map(|p| p.as_ref()).collect();
+    patterns
+        .iter()
+        .filter_map(|&narrow| {
+            covering_pattern(narrow, &patterns).map(|broad| ExcludeWarning {
+                pattern: narrow.to_string(),
+                reason: format!("already excluded by \"{}\"", broad),
+            })
+        })
+        .collect()
+}
+
+/// A job's exclude patterns matched in-process against paths relative to the
+/// source root, as `--exclude` matches them during a transfer: a pattern
+/// without an inner `/` matches any component, a leading `/` anchors it to
+/// the root, a trailing `/` limits it to directories, and excluding a
+/// directory excludes everything below it
+pub struct ExcludeMatcher {
+    /// Compiled pattern and whether it only matches directories
+    rules: Vec<(GlobMatcher, bool)>,
+}
+
+impl ExcludeMatcher {
+    /// Compile `patterns` after `normalize_patterns`. Patterns globset can't
+    /// parse are skipped with a warning.
+    pub fn new(patterns: &[String]) -> Self {
+        let rules = normalize_patterns(patterns)
+            .into_iter()
+            .filter_map(|pattern| {
+                let dir_only = pattern.ends_with('/');
+                let trimmed = pattern.trim_end_matches('/');
+                // Unanchored patterns match at any depth
+                let glob = match trimmed.strip_prefix('/') {
+                    Some(anchored) => anchored.to_string(),
+                    None => format!("**/{}", trimmed),
+                };
+                match GlobBuilder::new(&glob).literal_separator(true).build() {
+                    Ok(glob) => Some((glob.compile_matcher(), dir_only)),
+                    Err(e) => {
+                        log::warn!(
+                            "[exclude_preview] Skipping exclude pattern '{}': {}",
+                            pattern,
+                            e
+                        );
+                        None
+                    }
+                }
+            })
+            .collect();
+        Self { rules }
+    }
+
+    /// Whether the regular file at `path` (`/`-separated) is excluded, by its
+    /// own name or by a directory above it
+    pub fn excludes_file(&self, path: &str) -> bool {
+        if self.rules.is_empty() {
+            return false;
+        }
+        let mut ends: Vec<(usize, bool)> =
+            path.match_indices('/').map(|(i, _)| (i, true)).collect();
+        ends.push((path.len(), false));
+        ends.into_iter().any(|(end, is_dir)| {
+            let prefix = &path[..end];
+            self.rules
+                .iter()
+                .any(|(matcher, dir_only)| (is_dir || !dir_only) && matcher.is_match(prefix))
+        })
+    }
+}
+
+/// Dry-run `patterns` against the top level of `source_path`
+pub fn preview_excludes(source_path: &str, patterns: &[String]) -> Result<ExcludePreview> {
+    let source = Path::new(source_path);
+    if !source.is_dir() {
+        return Err(AmberError::InvalidPath(format!(
+            "Source is not a directory: {}",
+            source_path
+        )));
+    }
+
+    let patterns = trimmed_patterns(patterns);
+
+    let entries: BTreeSet<String> = std::fs::read_dir(source)?
+        .flatten()
+        .map(|e| e.file_name().to_string_lossy().to_string())
+        .collect();
+
+    // --list-only without -r implies --dirs: one level, the root shows as "."
+    let mut args = vec!["--dry-run".to_string(), "--list-only".to_string()];
+    args.extend(patterns.iter().map(|p| format!("--exclude={}", p)));
+    args.push("--".to_string());
+    args.push(format!("{}/", source_path.trim_end_matches('/')));
+
+    let output = Command::new("rsync")
+        .args(&args)
+        .output()
+        .map_err(|e| AmberError::Rsync(format!("Failed to run rsync: {}", e)))?;
+    if !output.status.success() {
+        return Err(AmberError::Rsync(format!(
+            "Exclude preview failed with code {:?}: {}",
+            output.status.code(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let listed: BTreeSet<&str> = stdout
+        .lines()
+        .filter_map(parse_list_only_name)
+        .filter(|name| *name != ".")
+        .collect();
+
+    Ok(build_preview(&entries, &listed, &patterns))
+}
+
+fn build_preview(
+    entries: &BTreeSet<String>,
+    listed: &BTreeSet<&str>,
+    patterns: &[&str],
+) -> ExcludePreview {
+    let excluded: Vec<String> = entries
+        .iter()
+        .filter(|name| !listed.contains(name.as_str()))
+        .cloned()
+        .collect();
+
+    let mut warnings: Vec<ExcludeWarning> = patterns
+        .iter()
+        .filter_map(|p| {
+            dangerous_pattern_reason(p).map(|reason| ExcludeWarning {
+                pattern: p.to_string(),
+                reason: reason.to_string(),
+            })
+        })
+        .collect();
+
+    // Catch combinations that are individually harmless but drop everything
+    if !entries.is_empty() && excluded.len() == entries.len() && warnings.is_empty() {
+        warnings.push(ExcludeWarning {
+            pattern: patterns.join(" "),
+            reason: "excludes every top-level entry".to_string(),
+        });
+    }
+    warnings.extend(subsumed_patterns(patterns));
+
+    ExcludePreview {
+        total_entries: entries.len(),
+        excluded,
+        warnings,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dangerous_patterns_are_flagged() {
+        for p in ["/", "*", "**", "/*", "*/", ".", "***/"] {
+            assert!(dangerous_pattern_reason(p).is_some(), "{} not flagged", p);
+        }
+        for p in ["*.log", "/build", "node_modules/", ".cache", "**/tmp"] {
+            assert!(dangerous_pattern_reason(p).is_none(), "{} flagged", p);
+        }
+    }
+
+    #[test]
+    fn test_normalize_collapses_duplicates_and_empties() {
+        let patterns: Vec<String> = [
+            "*.log",
+            "  node_modules/ ",
+            "",
+            "   ",
+            "/build",
+            "*.log",
+            "node_modules/",
+            "\t*.log\n",
+            ".cache",
+        ]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+
+        assert_eq!(
+            normalize_patterns(&patterns),
+            vec!["*.log", "node_modules/", "/build", ".cache"]
+        );
+        assert!(normalize_patterns(&["".to_string(), " ".to_string()]).is_empty());
+    }
+
+    #[test]
+    fn test_subsumed_patterns_name_their_broader_pattern() {
+        let warnings = subsumed_patterns(&[
+            "node_modules",
+            "node_modules/",
+            "web/node_modules/react",
+            "*.log",
+            "logs/debug.log",
+            "/error.log",
+            "build/",
+            "build",
+            "src/build/out",
+            "*.tmp",
+            "scratch/*.tmp",
+        ]);
+        let found: Vec<(&str, &str)> = warnings
+            .iter()
+            .map(|w| (w.pattern.as_str(), w.reason.as_str()))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                ("node_modules/", "already excluded by \"node_modules\""),
+                (
+                    "web/node_modules/react",
+                    "already excluded by \"node_modules\""
+                ),
+                ("logs/debug.log", "already excluded by \"*.log\""),
+                ("/error.log", "already excluded by \"*.log\""),
+                ("build/", "already excluded by \"build\""),
+                ("src/build/out", "already excluded by \"build/\""),
+            ]
+        );
+
+        // A directory-only pattern doesn't cover a file of the same name, and
+        // anchored or multi-component patterns never count as broader
+        assert_eq!(subsumed_patterns(&["cache/", "cache"]).len(), 1);
+        assert!(subsumed_patterns(&["/cache", "a/cache"]).is_empty());
+        assert!(subsumed_patterns(&["*.log", "*.log.gz"]).is_empty());
+    }
+
+    #[test]
+    fn test_exclude_matcher_follows_rsync_rules() {
+        let patterns: Vec<String> = ["*.log", "node_modules/", "/build", "docs/draft", "cache/"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        let matcher = ExcludeMatcher::new(&patterns);
+
+        for excluded in [
+            "debug.log",
+            "a/b/trace.log",
+            "web/node_modules/react/index.js",
+            "build/out.bin",
+            "docs/draft",
+            "old/docs/draft/notes.txt",
+            "cache/entry",
+        ] {
+            assert!(matcher.excludes_file(excluded), "{} kept", excluded);
+        }
+        for kept in [
+            "log.txt",
+            "src/build/out.bin",
+            "docs/drafts.txt",
+            // `cache/` only matches directories
+            "cache",
+            "notes/cache",
+        ] {
+            assert!(!matcher.excludes_file(kept), "{} excluded", kept);
+        }
+        assert!(!ExcludeMatcher::new(&[]).excludes_file("anything"));
+    }
+
+    #[test]
+    fn test_parse_list_only_names() {
+        let out = "drwxr-xr-x          4,096 2024/01/01 12:00:00 .\n\
+                   -rw-r--r--             12 2024/01/01 12:00:00 keep.txt\n\
+                   drwxr-xr-x          4,096 2024/01/01 12:00:00 a dir\n\
+                   lrwxrwxrwx              8 2024/01/01 12:00:00 link -> keep.txt\n";
+        let names: Vec<_> = out.lines().filter_map(parse_list_only_name).collect();
+        assert_eq!(names, vec![".", "keep.txt", "a dir", "link"]);
+    }
+
+    #[test]
+    fn test_build_preview_reports_unlisted_entries() {
+        let entries: BTreeSet<String> = ["build", "keep.txt", "x.log"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        let listed: BTreeSet<&str> = ["keep.txt"].into_iter().collect();
+
+        let preview = build_preview(&entries, &listed, &["*.log", "/build"]);
+        assert_eq!(preview.total_entries, 3);
+        assert_eq!(preview.excluded, vec!["build", "x.log"]);
+        assert!(preview.warnings.is_empty());
+
+        let all_gone = build_preview(&entries, &BTreeSet::new(), &["*.log", "*.txt", "b*"]);
+        assert_eq!(all_gone.warnings.len(), 1);
+    }
+}
@@ -1,27 +1,95 @@
 use crate::error::{AmberError, Result};
 use serde::{Deserialize, Serialize};
 use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
 use walkdir::WalkDir;
 
+/// Default cap on bytes `read_file_base64` and `read_file_preview` will load
+pub const DEFAULT_MAX_READ_BYTES: u64 = 25 * 1024 * 1024;
+
+/// Text of a file preview; `truncated` is set when the file has more to show
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FilePreview {
+    pub content: String,
+    pub truncated: bool,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct FileEntry {
     pub path: String,
     pub name: String,
     pub is_dir: bool,
+    #[serde(default)]
+    pub is_symlink: bool,
     pub size: u64,
     pub modified: u64,
+    /// Set when the entry exists but its metadata could not be read
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Sort key for directory listings
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum DirSortKey {
+    #[default]
+    Name,
+    Size,
+    Modified,
 }
 
-pub struct FileService;
+/// Server-side sorting and filtering for `list_directory`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DirListOptions {
+    /// Sort order; `None` keeps filesystem order
+    #[serde(default)]
+    pub sort_by: Option<DirSortKey>,
+    #[serde(default)]
+    pub descending: bool,
+    /// Group directories ahead of files (applied before `sort_by`)
+    #[serde(default)]
+    pub directories_first: bool,
+    /// Case-insensitive substring match on the entry name
+    #[serde(default)]
+    pub name_contains: Option<String>,
+}
+
+pub struct FileService {
+    max_read_bytes: AtomicU64,
+}
 
 impl FileService {
     pub fn new() -> Self {
-        Self
+        Self {
+            max_read_bytes: AtomicU64::new(DEFAULT_MAX_READ_BYTES),
+        }
+    }
+
+    /// Largest file `read_file_base64` loads and the most bytes a preview reads
+    pub fn max_read_bytes(&self) -> u64 {
+        self.max_read_bytes.load(Ordering::Relaxed)
+    }
+
+    pub fn set_max_read_bytes(&self, bytes: u64) {
+        self.max_read_bytes.store(bytes, Ordering::Relaxed);
     }
 
     /// Scans a directory and returns all entries (single level)
     pub fn scan_directory(&self, dir_path: &str) -> Result<Vec<FileEntry>> {
+        self.list_directory(dir_path, &DirListOptions::default())
+    }
+
+    /// Single-level listing with full metadata inline, so the UI needs one call
+    /// per directory. Entries whose metadata can't be read are still returned,
+    /// with `error` set.
+    pub fn list_directory(
+        &self,
+        dir_path: &str,
+        options: &DirListOptions,
+    ) -> Result<Vec<FileEntry>> {
         let path = Path::new(dir_path);
         if !path.exists() {
             return Err(AmberError::Io(std::io::Error::new(
@@ -30,35 +98,83 @@ impl FileService {
             )));
         }
 
+        let needle = options.name_contains.as_ref().map(|n| n.to_lowercase());
         let mut entries = Vec::new();
 
-        for e in WalkDir::new(path)
-            .min_depth(1)
-            .max_depth(1)
-            .into_iter()
-            .flatten()
-        {
-            if let Ok(metadata) = e.metadata() {
-                let modified = metadata
-                    .modified()
-                    .ok()
-                    .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
-                    .map(|d| d.as_secs())
-                    .unwrap_or(0);
+        for result in WalkDir::new(path).min_depth(1).max_depth(1) {
+            let entry = match result {
+                Ok(e) => match e.metadata() {
+                    Ok(metadata) => {
+                        let modified = metadata
+                            .modified()
+                            .ok()
+                            .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+                            .map(|d| d.as_secs())
+                            .unwrap_or(0);
 
-                entries.push(FileEntry {
-                    path: e.path().to_string_lossy().to_string(),
-                    name: e.file_name().to_string_lossy().to_string(),
-                    is_dir: metadata.is_dir(),
-                    size: metadata.len(),
-                    modified,
-                });
+                        FileEntry {
+                            path: e.path().to_string_lossy().to_string(),
+                            name: e.file_name().to_string_lossy().to_string(),
+                            is_dir: metadata.is_dir(),
+                            is_symlink: metadata.is_symlink(),
+                            size: metadata.len(),
+                            modified,
+                            error: None,
+                        }
+                    }
+                    Err(err) => Self::unreadable_entry(e.path(), err.to_string()),
+                },
+                Err(err) => match err.path() {
+                    Some(p) => Self::unreadable_entry(p, err.to_string()),
+                    None => continue,
+                },
+            };
+
+            if let Some(needle) = &needle {
+                if !entry.name.to_lowercase().contains(needle) {
+                    continue;
+                }
             }
+            entries.push(entry);
+        }
+
+        if let Some(key) = options.sort_by {
+            entries.sort_by(|a, b| {
+                let ord = match key {
+                    DirSortKey::Name => a.name.to_lowercase().cmp(&b.name.to_lowercase()),
+                    DirSortKey::Size => a.size.cmp(&b.size),
+                    DirSortKey::Modified => a.modified.cmp(&b.modified),
+                };
+                if options.descending {
+                    ord.reverse()
+                } else {
+                    ord
+                }
+            });
+        }
+        if options.directories_first {
+            // Stable sort keeps the order chosen above within each group
+            entries.sort_by_key(|e| !e.is_dir);
         }
 
         Ok(entries)
     }
 
+    fn unreadable_entry(path: &Path, error: String) -> FileEntry {
+        FileEntry {
+            path: path.to_string_lossy().to_string(),
+            name: path
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_default(),
+            is_dir: false,
+            is_symlink: false,
+            size: 0,
+            modified: 0,
+            error: Some(error),
+        }
+    }
+
     /// Recursively scans a directory
     pub fn scan_recursive(&self, dir_path: &str, max_depth: usize) -> Result<Vec<FileEntry>> {
         let path = Path::new(dir_path);
@@ -89,8 +205,10 @@ impl FileService {
                     path: e.path().to_string_lossy().to_string(),
                     name: e.file_name().to_string_lossy().to_string(),
                     is_dir: metadata.is_dir(),
+                    is_symlink: metadata.is_symlink(),
                     size: metadata.len(),
                     modified,
+                    error: None,
                 });
             }
         }
@@ -98,28 +216,40 @@ impl FileService {
         Ok(entries)
     }
 
-    /// Read file content as string (for preview)
-    pub fn read_file_preview(&self, file_path: &str, max_lines: usize) -> Result<String> {
+    /// Read up to `max_lines` lines of a file (for preview), reading no more
+    /// than `max_read_bytes`
+    pub fn read_file_preview(&self, file_path: &str, max_lines: usize) -> Result<FilePreview> {
         use std::fs::File;
-        use std::io::{BufRead, BufReader};
+        use std::io::{BufRead, BufReader, Read};
 
+        let limit = self.max_read_bytes();
         let file = File::open(file_path)?;
-        let reader = BufReader::new(file);
+        let size = file.metadata()?.len();
+        let mut reader = BufReader::new(file.take(limit)).lines();
         let mut lines = Vec::new();
 
-        for line in reader.lines().take(max_lines) {
+        for line in reader.by_ref().take(max_lines) {
             match line {
                 Ok(l) => lines.push(l),
                 Err(_) => break,
             }
         }
 
-        Ok(lines.join("\n"))
+        let truncated = size > limit || reader.next().is_some();
+        Ok(FilePreview {
+            content: lines.join("\n"),
+            truncated,
+        })
     }
 
-    /// Read file as base64
+    /// Read file as base64; files over `max_read_bytes` are refused unread
     pub fn read_file_base64(&self, file_path: &str) -> Result<String> {
         use base64::{engine::general_purpose::STANDARD, Engine};
+        let limit = self.max_read_bytes();
+        let size = std::fs::metadata(file_path)?.len();
+        if size > limit {
+            return Err(AmberError::FileTooLarge { size, limit });
+        }
         let bytes = std::fs::read(file_path)?;
         Ok(STANDARD.encode(bytes))
     }
@@ -154,3 +284,144 @@ impl Default for FileService {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn mixed_dir() -> TempDir {
+        let temp = TempDir::new().unwrap();
+        std::fs::write(temp.path().join("b.txt"), "hello world").unwrap();
+        std::fs::write(temp.path().join("A.txt"), "x").unwrap();
+        std::fs::create_dir(temp.path().join("folder")).unwrap();
+        #[cfg(unix)]
+        std::os::unix::fs::symlink("b.txt", temp.path().join("link")).unwrap();
+        temp
+    }
+
+    #[test]
+    fn test_list_directory_returns_full_metadata() {
+        let temp = mixed_dir();
+        let service = FileService::new();
+        let entries = service
+            .list_directory(temp.path().to_str().unwrap(), &DirListOptions::default())
+            .unwrap();
+
+        let find = |name: &str| entries.iter().find(|e| e.name == name).unwrap();
+
+        let file = find("b.txt");
+        assert!(!file.is_dir && !file.is_symlink);
+        assert_eq!(file.size, 11);
+        assert!(file.modified > 0);
+        assert!(file.error.is_none());
+
+        assert!(find("folder").is_dir);
+
+        #[cfg(unix)]
+        {
+            let link = find("link");
+            assert!(link.is_symlink);
+            assert!(!link.is_dir);
+        }
+    }
+
+    #[test]
+    fn test_list_directory_sorts_and_filters() {
+        let temp = mixed_dir();
+        let service = FileService::new();
+        let dir = temp.path().to_str().unwrap();
+
+        let by_name = service
+            .list_directory(
+                dir,
+                &DirListOptions {
+                    sort_by: Some(DirSortKey::Name),
+                    directories_first: true,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        let names: Vec<_> = by_name.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names[0], "folder");
+        assert_eq!(names[1], "A.txt");
+        assert_eq!(names[2], "b.txt");
+
+        let filtered = service
+            .list_directory(
+                dir,
+                &DirListOptions {
+                    name_contains: Some("TXT".to_string()),
+                    sort_by: Some(DirSortKey::Size),
+                    descending: true,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        let names: Vec<_> = filtered.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, vec!["b.txt", "A.txt"]);
+    }
+
+    #[test]
+    fn test_read_file_base64_within_limit() {
+        let temp = mixed_dir();
+        let service = FileService::new();
+        let path = temp.path().join("b.txt");
+        let encoded = service.read_file_base64(path.to_str().unwrap()).unwrap();
+        assert_eq!(encoded, "aGVsbG8gd29ybGQ=");
+    }
+
+    #[test]
+    fn test_read_file_base64_over_limit_is_refused() {
+        let temp = TempDir::new().unwrap();
+        // Sparse, so the test never has this much data on disk
+        let path = temp.path().join("huge.bin");
+        let file = std::fs::File::create(&path).unwrap();
+        file.set_len(4 * 1024 * 1024 * 1024).unwrap();
+
+        let service = FileService::new();
+        let err = service
+            .read_file_base64(path.to_str().unwrap())
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            AmberError::FileTooLarge {
+                size: 4294967296,
+                limit: DEFAULT_MAX_READ_BYTES,
+            }
+        ));
+
+        service.set_max_read_bytes(4);
+        let small = temp.path().join("small.txt");
+        std::fs::write(&small, "hello").unwrap();
+        let err = service
+            .read_file_base64(small.to_str().unwrap())
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            AmberError::FileTooLarge { size: 5, limit: 4 }
+        ));
+    }
+
+    #[test]
+    fn test_read_file_preview_marks_truncation() {
+        let temp = TempDir::new().unwrap();
+        let path = temp.path().join("lines.txt");
+        std::fs::write(&path, "one\ntwo\nthree\n").unwrap();
+        let path = path.to_str().unwrap();
+        let service = FileService::new();
+
+        let full = service.read_file_preview(path, 10).unwrap();
+        assert_eq!(full.content, "one\ntwo\nthree");
+        assert!(!full.truncated);
+
+        let by_lines = service.read_file_preview(path, 2).unwrap();
+        assert_eq!(by_lines.content, "one\ntwo");
+        assert!(by_lines.truncated);
+
+        service.set_max_read_bytes(6);
+        let by_bytes = service.read_file_preview(path, 10).unwrap();
+        assert_eq!(by_bytes.content, "one\ntw");
+        assert!(by_bytes.truncated);
+    }
+}
@@ -0,0 +1,146 @@
+//! Hardlink support at a backup destination
+//!
+//! TimeMachine mode shares unchanged files between snapshots through rsync's
+//! `--link-dest` hardlinks. exFAT, FAT and some SMB shares can't hardlink, and
+//! rsync then quietly copies every file into every snapshot. The probe links
+//! two temp files at the destination and checks the link count; the result is
+//! cached per destination. A TimeMachine backup to an incapable destination
+//! logs a warning, or fails when the `requireHardlinks` preference is on.
+
+use crate::error::{AmberError, Result};
+use crate::types::job::{SyncJob, SyncMode};
+use crate::utils::parse_ssh_remote;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, OnceLock};
+
+static REQUIRE_HARDLINKS: AtomicBool = AtomicBool::new(false);
+
+/// Fail TimeMachine backups to destinations without hardlinks instead of
+/// warning
+pub fn configure(require: bool) {
+    REQUIRE_HARDLINKS.store(require, Ordering::SeqCst);
+}
+
+fn cache() -> &'static Mutex<HashMap<PathBuf, bool>> {
+    static CACHE: OnceLock<Mutex<HashMap<PathBuf, bool>>> = OnceLock::new();
+    CACHE.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// Whether files in `dir` can be hardlinked: links one temp file to a second
+/// name and checks both report two links (some SMB servers "succeed" by
+/// copying). Both files are removed again. `dir` must exist.
+pub fn probe(dir: &Path) -> Result<bool> {
+    let stamp = format!(
+        "{}-{}",
+        std::process::id(),
+        chrono::Utc::now().timestamp_nanos_opt().unwrap_or(0)
+    );
+    let original = dir.join(format!(".amber-link-probe-{}", stamp));
+    let link = dir.join(format!(".amber-link-probe-{}.link", stamp));
+
+    std::fs::write(&original, b"amber")?;
+    let linked = std::fs::hard_link(&original, &link).is_ok() && link_count(&link) == Some(2);
+
+    let _ = std::fs::remove_file(&link);
+    let _ = std::fs::remove_file(&original);
+    Ok(linked)
+}
+
+#[cfg(unix)]
+fn link_count(path: &Path) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt;
+    std::fs::metadata(path).ok().map(|m| m.nlink())
+}
+
+/// Windows doesn't expose the link count on stable; trust `hard_link`
+#[cfg(not(unix))]
+fn link_count(_path: &Path) -> Option<u64> {
+    Some(2)
+}
+
+/// `probe` for `dest`, cached per destination. A destination that doesn't
+/// exist yet is probed at its nearest existing parent, where it will be
+/// created.
+pub fn supports_hardlinks(dest: &Path) -> Result<bool> {
+    let dir = dest
+        .ancestors()
+        .find(|dir| dir.is_dir())
+        .ok_or_else(|| AmberError::InvalidPath(format!("{} does not exist", dest.display())))?;
+    let key = std::fs::canonicalize(dir).unwrap_or_else(|_| dir.to_path_buf());
+
+    if let Some(known) = cache().lock().ok().and_then(|c| c.get(&key).copied()) {
+        return Ok(known);
+    }
+    let supported = probe(&key)?;
+    if let Ok(mut cache) = cache().lock() {
+        cache.insert(key, supported);
+    }
+    Ok(supported)
+}
+
+/// Pre-flight for a backup: a warning for a TimeMachine job whose local
+/// destination can't hardlink (every snapshot would be a full copy), or an
+/// error when hardlinks are required. Other modes and remote destinations
+/// pass.
+pub fn check_destination(job: &SyncJob) -> Result<Option<String>> {
+    if job.mode != SyncMode::TimeMachine || parse_ssh_remote(&job.dest_path).is_some() {
+        return Ok(None);
+    }
+    if supports_hardlinks(Path::new(&job.dest_path))? {
+        return Ok(None);
+    }
+
+    let message = format!(
+        "{} does not support hardlinks, so every TimeMachine snapshot will be a full copy",
+        job.dest_path
+    );
+    if REQUIRE_HARDLINKS.load(Ordering::SeqCst) {
+        return Err(AmberError::Filesystem(message));
+    }
+    Ok(Some(message))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_probe_detects_hardlinks_on_temp_dir() {
+        // The temp dir's filesystem (tmpfs, ext4, APFS) supports hardlinks
+        let dir = tempfile::tempdir().unwrap();
+        assert!(probe(dir.path()).unwrap());
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 0);
+
+        assert!(supports_hardlinks(&dir.path().join("not/created/yet")).unwrap());
+        assert!(probe(&dir.path().join("missing")).is_err());
+    }
+
+    #[test]
+    fn test_only_time_machine_jobs_are_checked() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut job = SyncJob {
+            dest_path: dir.path().to_string_lossy().to_string(),
+            mode: SyncMode::TimeMachine,
+            ..SyncJob::default()
+        };
+        assert_eq!(check_destination(&job).unwrap(), None);
+
+        job.mode = SyncMode::Mirror;
+        job.dest_path = "/definitely/not/here".to_string();
+        assert_eq!(check_destination(&job).unwrap(), None);
+
+        job.mode = SyncMode::TimeMachine;
+        job.dest_path = "me@nas:/backups".to_string();
+        assert_eq!(check_destination(&job).unwrap(), None);
+    }
+
+    #[test]
+    #[ignore] // Needs FAT/exFAT: AMBER_TEST_NO_HARDLINK_DIR=/media/usb cargo test -- --ignored
+    fn test_probe_detects_missing_hardlinks() {
+        let dir = std::env::var("AMBER_TEST_NO_HARDLINK_DIR")
+            .expect("set AMBER_TEST_NO_HARDLINK_DIR to a directory on FAT or exFAT");
+        assert!(!probe(Path::new(&dir)).unwrap());
+    }
+}
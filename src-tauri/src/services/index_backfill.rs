@@ -0,0 +1,217 @@
+//! Indexing every snapshot folder a destination is missing from its index
+//!
+//! After importing an orphan backup or recovering a drive, a destination can
+//! hold many snapshot folders of which few are indexed. Folders are found in
+//! the job's target base, the rendered destination subfolder backups are
+//! written into. A folder whose manifest entry is not `Complete`, or that a
+//! running backup is still writing, is skipped.
+
+use crate::error::{AmberError, Result};
+use crate::services::cancel_token::CancelToken;
+use crate::services::index_service::IndexService;
+use crate::services::manifest_service;
+use crate::services::rsync_service;
+use crate::types::job::SyncJob;
+use crate::types::manifest::ManifestSnapshotStatus;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// Progress before each folder is indexed
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BackfillProgress {
+    pub folders_done: u64,
+    pub folders_total: u64,
+    pub timestamp: i64,
+    pub folder_name: String,
+}
+
+/// A snapshot folder that was left unindexed on purpose
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SkippedFolder {
+    pub folder_name: String,
+    pub reason: String,
+}
+
+/// Outcome of [`index_all_missing`], timestamps in ascending order
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BackfillReport {
+    pub indexed: Vec<i64>,
+    pub already_indexed: Vec<i64>,
+    pub skipped: Vec<SkippedFolder>,
+}
+
+/// Timestamp for a folder name, read as UTC like the filesystem listing does
+pub(crate) fn folder_timestamp(name: &str) -> Option<i64> {
+    chrono::NaiveDateTime::parse_from_str(name, "%Y-%m-%d-%H%M%S")
+        .ok()
+        .map(|dt| dt.and_utc().timestamp_millis())
+}
+
+fn is_snapshot_folder(path: &Path) -> bool {
+    path.is_dir()
+        && path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .is_some_and(|n| rsync_service::backup_dir_pattern().is_match(n))
+}
+
+/// Snapshot folders directly in `target_base`, none if it doesn't exist yet
+pub(crate) fn find_snapshot_folders(target_base: &Path) -> Result<Vec<PathBuf>> {
+    if !target_base.is_dir() {
+        return Ok(Vec::new());
+    }
+    Ok(std::fs::read_dir(target_base)?
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| is_snapshot_folder(path))
+        .collect())
+}
+
+/// Index every snapshot folder of `job` that its destination's index lacks.
+///
+/// `active_snapshot` is the folder a running backup is writing, if any.
+/// `on_progress` runs before each folder; cancelling `cancel` stops with
+/// `AmberError::Cancelled`, keeping the snapshots indexed so far. A folder cut
+/// off mid-way keeps its committed batches and the next run resumes it.
+pub async fn index_all_missing(
+    job: &SyncJob,
+    active_snapshot: Option<PathBuf>,
+    cancel: CancelToken,
+    on_progress: impl FnMut(&BackfillProgress) + Send + 'static,
+) -> Result<BackfillReport> {
+    let job_id = job.id.as_str();
+    let dest_path = job.dest_path.as_str();
+    let target_base = rsync_service::target_base(job)?;
+    let manifest = manifest_service::read_manifest(dest_path)
+        .await
+        .map_err(|e| AmberError::Snapshot(format!("Failed to read manifest: {}", e)))?;
+    if let Some(manifest) = &manifest {
+        if manifest.job_id != job_id {
+            return Err(AmberError::snapshot_for_job(
+                job_id,
+                format!("{} belongs to job '{}'", dest_path, manifest.job_id),
+            ));
+        }
+    }
+    // Folder name -> (timestamp, status) as recorded by the backup
+    let recorded: HashMap<String, (i64, ManifestSnapshotStatus)> = manifest
+        .map(|m| {
+            m.snapshots
+                .into_iter()
+                .map(|s| (s.folder_name, (s.timestamp, s.status)))
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let job_id = job_id.to_string();
+    let dest_path = dest_path.to_string();
+    tokio::task::spawn_blocking(move || {
+        backfill(
+            &job_id,
+            &dest_path,
+            &target_base,
+            &recorded,
+            active_snapshot.as_deref(),
+            &cancel,
+            on_progress,
+        )
+    })
+    .await
+    .map_err(|e| AmberError::Index(format!("Backfill task failed: {}", e)))?
+}
+
+fn backfill(
+    job_id: &str,
+    dest_path: &str,
+    target_base: &Path,
+    recorded: &HashMap<String, (i64, ManifestSnapshotStatus)>,
+    active_snapshot: Option<&Path>,
+    cancel: &CancelToken,
+    mut on_progress: impl FnMut(&BackfillProgress),
+) -> Result<BackfillReport> {
+    let index = IndexService::for_destination(dest_path)?;
+    let active = active_snapshot.and_then(|p| p.canonicalize().ok());
+    let mut report = BackfillReport::default();
+    let mut missing = Vec::new();
+
+    for path in find_snapshot_folders(target_base)? {
+        let folder_name = path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let skip = |reason: &str| SkippedFolder {
+            folder_name: folder_name.clone(),
+            reason: reason.to_string(),
+        };
+
+        if active.is_some() && path.canonicalize().ok() == active {
+            report.skipped.push(skip("Backup in progress"));
+            continue;
+        }
+        let timestamp = match recorded.get(&folder_name) {
+            Some((_, status)) if *status != ManifestSnapshotStatus::Complete => {
+                report
+                    .skipped
+                    .push(skip(&format!("Snapshot is {}", status.as_str())));
+                continue;
+            }
+            Some((timestamp, _)) => *timestamp,
+            None => match folder_timestamp(&folder_name) {
+                Some(timestamp) => timestamp,
+                None => {
+                    report.skipped.push(skip("Not a valid snapshot date"));
+                    continue;
+                }
+            },
+        };
+
+        if index.is_indexed(job_id, timestamp)? {
+            report.already_indexed.push(timestamp);
+        } else {
+            missing.push((timestamp, folder_name, path));
+        }
+    }
+
+    missing.sort_by_key(|(timestamp, _, _)| *timestamp);
+    report.already_indexed.sort();
+    report
+        .skipped
+        .sort_by(|a, b| a.folder_name.cmp(&b.folder_name));
+
+    let mut progress = BackfillProgress {
+        folders_done: 0,
+        folders_total: missing.len() as u64,
+        timestamp: 0,
+        folder_name: String::new(),
+    };
+    for (timestamp, folder_name, path) in missing {
+        cancel.check()?;
+        progress.timestamp = timestamp;
+        progress.folder_name = folder_name;
+        on_progress(&progress);
+
+        index.index_snapshot_resumable(
+            job_id,
+            timestamp,
+            &path.to_string_lossy(),
+            cancel,
+            |_, _| {},
+        )?;
+        report.indexed.push(timestamp);
+        progress.folders_done += 1;
+    }
+
+    log::info!(
+        job_id = job_id, operation = "index";
+        "Backfilled index on {}: {} indexed, {} already indexed, {} skipped",
+        dest_path,
+        report.indexed.len(),
+        report.already_indexed.len(),
+        report.skipped.len()
+    );
+    Ok(report)
+}
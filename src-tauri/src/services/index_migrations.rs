@@ -0,0 +1,529 @@
+//! Schema migrations for the SQLite snapshot index
+//!
+//! Each migration is a version, the SQL that upgrades to it and, where the
+//! change can be undone, the SQL that takes it back out. They run in version
+//! order, one transaction each, and every applied version is recorded in
+//! `schema_migrations` next to `PRAGMA user_version`. To add a column, append
+//! a migration here and bump `LATEST_VERSION`.
+//!
+//! (Not to be confused with `migration_service`, which moves job data from
+//! jobs.json into manifests.)
+
+use crate::error::{AmberError, Result};
+use crate::services::index_service::fts_schema_sql;
+use rusqlite::{params, Connection};
+
+/// Schema version the index is migrated to on open
+pub const LATEST_VERSION: i32 = 11;
+
+/// One schema step
+#[derive(Debug, Clone)]
+pub struct Migration {
+    pub version: i32,
+    pub name: &'static str,
+    pub up: String,
+    /// None when the step can't be reverted
+    pub down: Option<String>,
+}
+
+/// A row of `schema_migrations`
+#[derive(Debug, Clone, PartialEq)]
+pub struct AppliedMigration {
+    pub version: i32,
+    pub name: String,
+    /// Unix SECONDS; NULL for versions applied before the table existed
+    pub applied_at: Option<i64>,
+}
+
+const V1_SCHEMA: &str = r#"
+    -- =================================================================
+    -- SCHEMA DOCUMENTATION
+    -- =================================================================
+    -- Timestamps:
+    --   - snapshots.timestamp: Unix MILLISECONDS
+    --   - files.mtime: Unix SECONDS (converted to ms at API boundary)
+    --   - snapshots.created_at: Unix SECONDS (default)
+    --
+    -- File types (files.file_type):
+    --   - 'file': Regular file
+    --   - 'dir': Directory
+    --   - 'symlink': Symbolic link
+    --   Note: Always lowercase, matches Rust file_type module
+    -- =================================================================
+
+    -- Snapshots table: One entry per backup snapshot
+    CREATE TABLE IF NOT EXISTS snapshots (
+        id INTEGER PRIMARY KEY,
+        job_id TEXT NOT NULL,
+        timestamp INTEGER NOT NULL,           -- Unix MILLISECONDS
+        root_path TEXT NOT NULL,
+        file_count INTEGER DEFAULT 0,
+        total_size INTEGER DEFAULT 0,         -- Bytes
+        created_at INTEGER DEFAULT (strftime('%s', 'now')),  -- Unix SECONDS
+        UNIQUE(job_id, timestamp)
+    );
+
+    -- Files table: All files/directories in each snapshot
+    CREATE TABLE IF NOT EXISTS files (
+        id INTEGER PRIMARY KEY,
+        snapshot_id INTEGER NOT NULL,
+        path TEXT NOT NULL,                   -- Relative path from snapshot root
+        name TEXT NOT NULL,                   -- Filename only
+        parent_path TEXT NOT NULL,            -- Parent directory path
+        size INTEGER NOT NULL,                -- Bytes (0 for directories)
+        mtime INTEGER NOT NULL,               -- Unix SECONDS (API multiplies by 1000)
+        inode INTEGER,                        -- Unix inode for dedup detection
+        file_type TEXT NOT NULL,              -- 'file' | 'dir' | 'symlink'
+        FOREIGN KEY (snapshot_id) REFERENCES snapshots(id) ON DELETE CASCADE
+    );
+
+    -- Indexes for fast queries
+    CREATE INDEX IF NOT EXISTS idx_snapshots_job ON snapshots(job_id);
+    CREATE INDEX IF NOT EXISTS idx_files_snapshot_parent ON files(snapshot_id, parent_path);
+    CREATE INDEX IF NOT EXISTS idx_files_path ON files(snapshot_id, path);
+    CREATE INDEX IF NOT EXISTS idx_files_name ON files(name);
+
+    -- Composite indexes for performance optimization (TIM-150K+)
+    -- Directory browsing with ORDER BY (avoids sorting in query)
+    CREATE INDEX IF NOT EXISTS idx_files_snapshot_parent_type_name
+    ON files(snapshot_id, parent_path, file_type DESC, name ASC);
+
+    -- File type statistics aggregation
+    CREATE INDEX IF NOT EXISTS idx_files_snapshot_type_size
+    ON files(snapshot_id, file_type, size);
+
+    -- Snapshots range queries for timeline views
+    CREATE INDEX IF NOT EXISTS idx_snapshots_job_timestamp
+    ON snapshots(job_id, timestamp DESC);
+
+    -- Global search join optimization
+    CREATE INDEX IF NOT EXISTS idx_snapshots_id_job_timestamp
+    ON snapshots(id, job_id, timestamp);
+"#;
+
+/// Every migration in version order. The FTS step is built with the
+/// tokenizer mode new indexes should get.
+pub fn migrations(fold_diacritics: bool) -> Vec<Migration> {
+    vec![
+        Migration {
+            version: 1,
+            name: "initial schema",
+            up: V1_SCHEMA.to_string(),
+            down: Some("DROP TABLE IF EXISTS files;\nDROP TABLE IF EXISTS snapshots;".to_string()),
+        },
+        Migration {
+            // FTS5 for instant full-text search (TIM-101), filled from existing rows
+            version: 2,
+            name: "files_fts",
+            up: format!(
+                "{}\nINSERT INTO files_fts(files_fts) VALUES('rebuild');",
+                fts_schema_sql(fold_diacritics)
+            ),
+            down: Some(
+                r#"
+                DROP TRIGGER IF EXISTS files_ai;
+                DROP TRIGGER IF EXISTS files_ad;
+                DROP TRIGGER IF EXISTS files_au;
+                DROP TABLE IF EXISTS files_fts;
+                "#
+                .to_string(),
+            ),
+        },
+        Migration {
+            // Recency view: newest files in a snapshot without a full sort
+            version: 3,
+            name: "mtime index",
+            up: "CREATE INDEX IF NOT EXISTS idx_files_snapshot_mtime \
+                 ON files(snapshot_id, mtime DESC);"
+                .to_string(),
+            down: Some("DROP INDEX IF EXISTS idx_files_snapshot_mtime;".to_string()),
+        },
+        Migration {
+            // Optional content hash (NULL when the indexer didn't hash the file),
+            // used to pair deletes with adds as renames in compare_snapshots
+            version: 4,
+            name: "content hash",
+            up: "ALTER TABLE files ADD COLUMN content_hash TEXT;".to_string(),
+            down: Some("ALTER TABLE files DROP COLUMN content_hash;".to_string()),
+        },
+        Migration {
+            // Derived cleanup flags (FileFlag bitmask); rows indexed before
+            // this version read as 0 until their snapshot is re-indexed
+            version: 5,
+            name: "file flags",
+            up: "ALTER TABLE files ADD COLUMN file_flags INTEGER NOT NULL DEFAULT 0;".to_string(),
+            down: Some("ALTER TABLE files DROP COLUMN file_flags;".to_string()),
+        },
+        Migration {
+            // Set while a snapshot is indexed but its manifest entry isn't
+            // written yet; still set on open means the run died in between
+            version: 6,
+            name: "snapshot pending flag",
+            up: "ALTER TABLE snapshots ADD COLUMN pending INTEGER NOT NULL DEFAULT 0;".to_string(),
+            down: Some("ALTER TABLE snapshots DROP COLUMN pending;".to_string()),
+        },
+        Migration {
+            // Last path committed by a resumable index that hasn't finished;
+            // NULL once the snapshot is complete
+            version: 7,
+            name: "snapshot resume marker",
+            up: "ALTER TABLE snapshots ADD COLUMN resume_after TEXT;".to_string(),
+            down: Some("ALTER TABLE snapshots DROP COLUMN resume_after;".to_string()),
+        },
+        Migration {
+            // Counters kept across opens, e.g. snapshots deleted since the
+            // last VACUUM for automatic compaction
+            version: 8,
+            name: "index metadata",
+            up: "CREATE TABLE IF NOT EXISTS index_meta (
+                     key TEXT PRIMARY KEY,
+                     value INTEGER NOT NULL
+                 );"
+            .to_string(),
+            down: Some("DROP TABLE IF EXISTS index_meta;".to_string()),
+        },
+        Migration {
+            // Snapshots that record the source's listing without copying
+            // any data; comparable, never restorable
+            version: 9,
+            name: "metadata-only snapshots",
+            up: "ALTER TABLE snapshots ADD COLUMN metadata_only INTEGER NOT NULL DEFAULT 0;"
+                .to_string(),
+            down: Some("ALTER TABLE snapshots DROP COLUMN metadata_only;".to_string()),
+        },
+        Migration {
+            // Where each symlink pointed, keyed by its path relative to the
+            // snapshot root. A table of its own so it also applies to the
+            // normalized layout, where `files` is a view.
+            version: 10,
+            name: "symlink targets",
+            up: r#"
+                CREATE TABLE IF NOT EXISTS symlink_targets (
+                    snapshot_id INTEGER NOT NULL REFERENCES snapshots(id) ON DELETE CASCADE,
+                    path TEXT NOT NULL,
+                    target TEXT NOT NULL,
+                    PRIMARY KEY (snapshot_id, path)
+                ) WITHOUT ROWID;
+            "#
+            .to_string(),
+            down: Some("DROP TABLE IF EXISTS symlink_targets;".to_string()),
+        },
+        Migration {
+            // Exact bytes of paths that aren't valid UTF-8, keyed by their
+            // `%XX` form relative to the snapshot root, so restores can find
+            // the real file. Only such paths get a row.
+            version: 11,
+            name: "raw paths",
+            up: r#"
+                CREATE TABLE IF NOT EXISTS raw_paths (
+                    snapshot_id INTEGER NOT NULL REFERENCES snapshots(id) ON DELETE CASCADE,
+                    path TEXT NOT NULL,
+                    raw BLOB NOT NULL,
+                    PRIMARY KEY (snapshot_id, path)
+                ) WITHOUT ROWID;
+            "#
+            .to_string(),
+            down: Some("DROP TABLE IF EXISTS raw_paths;".to_string()),
+        },
+    ]
+}
+
+/// Current `PRAGMA user_version`
+pub fn current_version(conn: &Connection) -> Result<i32> {
+    conn.query_row("PRAGMA user_version", [], |row| row.get(0))
+        .map_err(|e| AmberError::Index(format!("Failed to read schema version: {}", e)))
+}
+
+/// Refuse an index written by a newer Amber: it may depend on schema this
+/// build doesn't know, and migrating it would be guesswork. Older indexes
+/// are fine; `migrate_up` brings them to `latest`.
+pub fn ensure_not_newer(conn: &Connection, latest: i32) -> Result<i32> {
+    let version = current_version(conn)?;
+    if version > latest {
+        return Err(AmberError::Index(format!(
+            "Index schema v{} is newer than this version of Amber supports (v{}). \
+             Update Amber to open this destination.",
+            version, latest
+        )));
+    }
+    Ok(version)
+}
+
+/// Create `schema_migrations` if needed. Indexes migrated before the table
+/// existed get a row per version they already have, with no applied_at.
+fn ensure_version_table(conn: &Connection, registry: &[Migration]) -> Result<()> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS schema_migrations (
+             version INTEGER PRIMARY KEY,
+             name TEXT NOT NULL,
+             applied_at INTEGER               -- Unix SECONDS
+         );",
+    )
+    .map_err(|e| AmberError::Index(format!("Failed to create schema_migrations: {}", e)))?;
+
+    let version = current_version(conn)?;
+    for migration in registry.iter().filter(|m| m.version <= version) {
+        conn.execute(
+            "INSERT OR IGNORE INTO schema_migrations (version, name, applied_at)
+             VALUES (?, ?, NULL)",
+            params![migration.version, migration.name],
+        )
+        .map_err(|e| AmberError::Index(format!("Failed to record migration: {}", e)))?;
+    }
+    Ok(())
+}
+
+/// Apply every migration above the current version up to `target`.
+/// Returns how many ran.
+pub fn migrate_up(conn: &mut Connection, registry: &[Migration], target: i32) -> Result<usize> {
+    ensure_version_table(conn, registry)?;
+    let from = current_version(conn)?;
+
+    let mut applied = 0;
+    for migration in registry
+        .iter()
+        .filter(|m| m.version > from && m.version <= target)
+    {
+        let tx = conn
+            .transaction()
+            .map_err(|e| AmberError::Index(format!("Failed to start transaction: {}", e)))?;
+        tx.execute_batch(&migration.up).map_err(|e| {
+            AmberError::Index(format!(
+                "Migration v{} ({}) failed: {}",
+                migration.version, migration.name, e
+            ))
+        })?;
+        tx.execute(
+            "INSERT OR REPLACE INTO schema_migrations (version, name, applied_at)
+             VALUES (?, ?, strftime('%s', 'now'))",
+            params![migration.version, migration.name],
+        )
+        .map_err(|e| AmberError::Index(format!("Failed to record migration: {}", e)))?;
+        tx.pragma_update(None, "user_version", migration.version)
+            .map_err(|e| AmberError::Index(format!("Failed to set schema version: {}", e)))?;
+        tx.commit()
+            .map_err(|e| AmberError::Index(format!("Failed to commit migration: {}", e)))?;
+        applied += 1;
+    }
+    Ok(applied)
+}
+
+/// Revert migrations above `target`, newest first. Nothing runs if any of
+/// them has no down SQL.
+pub fn migrate_down(conn: &mut Connection, registry: &[Migration], target: i32) -> Result<usize> {
+    ensure_version_table(conn, registry)?;
+    let from = current_version(conn)?;
+
+    let steps: Vec<&Migration> = registry
+        .iter()
+        .rev()
+        .filter(|m| m.version > target && m.version <= from)
+        .collect();
+    if let Some(stuck) = steps.iter().find(|m| m.down.is_none()) {
+        return Err(AmberError::Index(format!(
+            "Migration v{} ({}) cannot be reverted",
+            stuck.version, stuck.name
+        )));
+    }
+
+    for migration in &steps {
+        let tx = conn
+            .transaction()
+            .map_err(|e| AmberError::Index(format!("Failed to start transaction: {}", e)))?;
+        tx.execute_batch(migration.down.as_deref().unwrap_or_default())
+            .map_err(|e| {
+                AmberError::Index(format!(
+                    "Reverting v{} ({}) failed: {}",
+                    migration.version, migration.name, e
+                ))
+            })?;
+        tx.execute(
+            "DELETE FROM schema_migrations WHERE version = ?",
+            [migration.version],
+        )
+        .map_err(|e| AmberError::Index(format!("Failed to record migration: {}", e)))?;
+        tx.pragma_update(None, "user_version", migration.version - 1)
+            .map_err(|e| AmberError::Index(format!("Failed to set schema version: {}", e)))?;
+        tx.commit()
+            .map_err(|e| AmberError::Index(format!("Failed to commit migration: {}", e)))?;
+    }
+    Ok(steps.len())
+}
+
+/// Rows of `schema_migrations`, oldest first
+pub fn applied_migrations(conn: &Connection) -> Result<Vec<AppliedMigration>> {
+    let mut stmt = conn
+        .prepare("SELECT version, name, applied_at FROM schema_migrations ORDER BY version")
+        .map_err(|e| AmberError::Index(format!("Failed to prepare query: {}", e)))?;
+    let rows = stmt
+        .query_map([], |row| {
+            Ok(AppliedMigration {
+                version: row.get(0)?,
+                name: row.get(1)?,
+                applied_at: row.get(2)?,
+            })
+        })
+        .map_err(|e| AmberError::Index(format!("Failed to query migrations: {}", e)))?;
+    rows.collect::<rusqlite::Result<Vec<_>>>()
+        .map_err(|e| AmberError::Index(format!("Failed to read migrations: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tables, indexes and triggers with their SQL, plus every table's columns
+    fn schema(conn: &Connection) -> Vec<String> {
+        let mut stmt = conn
+            .prepare(
+                "SELECT type, name, COALESCE(sql, '') FROM sqlite_master
+                 WHERE name NOT LIKE 'sqlite_%' AND name NOT LIKE 'files_fts_%'
+                 ORDER BY type, name",
+            )
+            .unwrap();
+        let objects: Vec<(String, String, String)> = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+            .unwrap()
+            .map(|r| r.unwrap())
+            .collect();
+
+        let mut out = Vec::new();
+        for (kind, name, sql) in objects {
+            if kind == "table" {
+                let mut cols = conn
+                    .prepare(&format!(
+                        "SELECT name, type, \"notnull\", COALESCE(dflt_value, '') \
+                         FROM pragma_table_info('{}')",
+                        name
+                    ))
+                    .unwrap();
+                let cols: Vec<String> = cols
+                    .query_map([], |row| {
+                        Ok(format!(
+                            "{} {} {} {}",
+                            row.get::<_, String>(0)?,
+                            row.get::<_, String>(1)?,
+                            row.get::<_, i64>(2)?,
+                            row.get::<_, String>(3)?
+                        ))
+                    })
+                    .unwrap()
+                    .map(|r| r.unwrap())
+                    .collect();
+                out.push(format!("table {}: {}", name, cols.join(", ")));
+            } else {
+                out.push(format!("{} {}: {}", kind, name, sql));
+            }
+        }
+        out
+    }
+
+    fn versions(conn: &Connection) -> Vec<i32> {
+        applied_migrations(conn)
+            .unwrap()
+            .iter()
+            .map(|m| m.version)
+            .collect()
+    }
+
+    #[test]
+    fn test_registry_is_ordered_and_ends_at_latest() {
+        let registry = migrations(true);
+        let listed: Vec<i32> = registry.iter().map(|m| m.version).collect();
+        assert_eq!(listed, (1..=LATEST_VERSION).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_upgrade_from_each_version_matches_fresh_schema() {
+        let registry = migrations(true);
+        let mut fresh = Connection::open_in_memory().unwrap();
+        migrate_up(&mut fresh, &registry, LATEST_VERSION).unwrap();
+        let expected = schema(&fresh);
+
+        for start in 0..LATEST_VERSION {
+            let mut conn = Connection::open_in_memory().unwrap();
+            migrate_up(&mut conn, &registry, start).unwrap();
+            assert_eq!(current_version(&conn).unwrap(), start);
+
+            let ran = migrate_up(&mut conn, &registry, LATEST_VERSION).unwrap();
+            assert_eq!(ran as i32, LATEST_VERSION - start);
+            assert_eq!(current_version(&conn).unwrap(), LATEST_VERSION);
+            assert_eq!(schema(&conn), expected, "upgrading from v{}", start);
+            assert_eq!(versions(&conn), (1..=LATEST_VERSION).collect::<Vec<_>>());
+        }
+    }
+
+    #[test]
+    fn test_downgrade_restores_earlier_schema() {
+        let registry = migrations(true);
+        for target in 0..LATEST_VERSION {
+            let mut expected = Connection::open_in_memory().unwrap();
+            migrate_up(&mut expected, &registry, target).unwrap();
+
+            let mut conn = Connection::open_in_memory().unwrap();
+            migrate_up(&mut conn, &registry, LATEST_VERSION).unwrap();
+            migrate_down(&mut conn, &registry, target).unwrap();
+
+            assert_eq!(current_version(&conn).unwrap(), target);
+            assert_eq!(schema(&conn), schema(&expected), "reverting to v{}", target);
+            assert_eq!(versions(&conn), (1..=target).collect::<Vec<_>>());
+        }
+    }
+
+    #[test]
+    fn test_irreversible_migration_blocks_downgrade() {
+        let mut registry = migrations(true);
+        registry[2].down = None;
+
+        let mut conn = Connection::open_in_memory().unwrap();
+        migrate_up(&mut conn, &registry, LATEST_VERSION).unwrap();
+        assert!(migrate_down(&mut conn, &registry, 1).is_err());
+        assert_eq!(current_version(&conn).unwrap(), LATEST_VERSION);
+
+        // Reverting only the steps above it still works
+        migrate_down(&mut conn, &registry, 3).unwrap();
+        assert_eq!(current_version(&conn).unwrap(), 3);
+    }
+
+    #[test]
+    fn test_legacy_index_gets_backfilled_history() {
+        let registry = migrations(true);
+        let mut conn = Connection::open_in_memory().unwrap();
+        // Index created by the old if-ladder: schema and pragma, no history table
+        for migration in &registry[..3] {
+            conn.execute_batch(&migration.up).unwrap();
+        }
+        conn.pragma_update(None, "user_version", 3).unwrap();
+
+        migrate_up(&mut conn, &registry, LATEST_VERSION).unwrap();
+        let history = applied_migrations(&conn).unwrap();
+        assert_eq!(history.len(), LATEST_VERSION as usize);
+        assert!(history[..3].iter().all(|m| m.applied_at.is_none()));
+        assert!(history[3..].iter().all(|m| m.applied_at.is_some()));
+    }
+
+    #[test]
+    fn test_failed_migration_leaves_previous_version() {
+        let mut registry = migrations(true);
+        registry[3].up = "ALTER TABLE no_such_table ADD COLUMN x TEXT;".to_string();
+
+        let mut conn = Connection::open_in_memory().unwrap();
+        assert!(migrate_up(&mut conn, &registry, LATEST_VERSION).is_err());
+        assert_eq!(current_version(&conn).unwrap(), 3);
+        assert_eq!(versions(&conn), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn test_newer_index_is_refused() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        migrate_up(&mut conn, &migrations(true), 2).unwrap();
+        assert_eq!(ensure_not_newer(&conn, LATEST_VERSION).unwrap(), 2);
+
+        conn.pragma_update(None, "user_version", LATEST_VERSION + 1)
+            .unwrap();
+        let err = ensure_not_newer(&conn, LATEST_VERSION).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains(&format!("v{}", LATEST_VERSION + 1)));
+    }
+}
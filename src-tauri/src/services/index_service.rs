@@ -4,25 +4,418 @@
 //! Designed to handle millions of files (full MacBook backup).
 
 use crate::error::{AmberError, Result};
-use crate::types::snapshot::FileNode;
-use crate::utils::make_relative; // TIM-123: Use centralized path utility
-use jwalk::WalkDir;
-use rayon::prelude::*;
-use rusqlite::{params, Connection, Transaction};
+use crate::services::cancel_token::CancelToken;
+use crate::services::source_diff::{self, ScannedFile, SourceComparison};
+use crate::services::walk_pool::{self, WalkPool};
+use crate::services::{index_migrations, index_warmup, manifest_service};
+use crate::types::manifest::SnapshotChanges;
+use crate::types::snapshot::{file_type, FileCategory, FileNode};
+use crate::utils::{make_relative, os_path_bytes, path_bytes, path_from_bytes, path_text}; // TIM-123: Use centralized path utility
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+use jwalk::WalkDirGeneric;
+use rusqlite::{params, Connection, OptionalExtension, Transaction};
+use std::borrow::Cow;
+use std::collections::HashMap;
 use std::path::{Path, PathBuf};
-use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, AtomicI64, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
+use unicode_normalization::{is_nfc, UnicodeNormalization};
 
 /// Database version for migrations
-const DB_VERSION: i32 = 2;
+const DB_VERSION: i32 = index_migrations::LATEST_VERSION;
 
 /// Batch size for inserts (performance tuning)
 const BATCH_SIZE: usize = 1000;
 
+/// Directory names never walked while indexing: test/app index data and the
+/// destination's `.amber-meta` (which holds the destination index.db)
+const SKIPPED_DIR_NAMES: &[&str] = &[".index-data", manifest_service::AMBER_META_DIR];
+
+/// Columns bound per row in `batch_insert_files`
+const FILE_INSERT_COLUMNS: usize = 10;
+
+/// Columns bound per row when staging files for the normalized layout
+const STAGED_INSERT_COLUMNS: usize = 8;
+
+/// Files committed per transaction by `index_snapshot_resumable`
+const RESUME_BATCH_ROWS: usize = 50_000;
+
+/// Most nodes one `get_subtree` call returns
+pub const SUBTREE_NODE_LIMIT: usize = 5_000;
+
+/// `db_path` of an index opened with `new_in_memory`
+const IN_MEMORY_PATH: &str = ":memory:";
+
+/// How long SQLite itself waits on another connection's lock before a
+/// statement fails as busy
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Tries at a read that keeps finding the database busy
+const READ_ATTEMPTS: u32 = 4;
+
+/// Wait before retrying a busy read; doubles with each retry
+const READ_RETRY_BACKOFF: Duration = Duration::from_millis(25);
+
+/// SQLITE_MAX_VARIABLE_NUMBER for the bundled SQLite (>= 3.32)
+const SQLITE_MAX_PARAMS: usize = 32_766;
+
+/// Rows per multi-row INSERT, kept under the bound-parameter limit
+const ROWS_PER_INSERT: usize = if BATCH_SIZE * FILE_INSERT_COLUMNS <= SQLITE_MAX_PARAMS {
+    BATCH_SIZE
+} else {
+    SQLITE_MAX_PARAMS / FILE_INSERT_COLUMNS
+};
+
+/// Whether newly opened indexes fold diacritics in full-text search
+/// (the `searchFoldDiacritics` preference)
+static FOLD_DIACRITICS: AtomicBool = AtomicBool::new(true);
+
+/// Set the diacritic folding mode applied to every index opened from now on.
+///
+/// Folding (`unicode61 remove_diacritics 1`) lets `cafe` find `café` and vice
+/// versa, at the cost of never telling the two apart. Without it an accented
+/// query only matches the accented spelling. New indexes are created in this
+/// mode; an existing one opened in the other mode keeps it (opening never
+/// rewrites a drive's index) and reports `diacritic_folding_outdated` until
+/// `apply_configured_diacritic_folding` rebuilds it.
+pub fn configure_diacritic_folding(fold: bool) {
+    FOLD_DIACRITICS.store(fold, Ordering::SeqCst);
+}
+
+/// Whether newly created indexes use the normalized (shared row) layout
+/// (the experimental `normalizedIndexStorage` preference)
+static NORMALIZED_STORAGE: AtomicBool = AtomicBool::new(false);
+
+/// Set the file layout given to every index created from now on.
+///
+/// Only an index without snapshots is laid out this way when opened; existing
+/// indexes keep the layout they were built with.
+pub fn configure_normalized_storage(normalized: bool) {
+    NORMALIZED_STORAGE.store(normalized, Ordering::SeqCst);
+}
+
+/// Snapshot deletions after which an index is vacuumed on its next open
+/// (the `compactAfterDeletions` preference; 0 = never)
+static COMPACT_AFTER_DELETIONS: AtomicU64 = AtomicU64::new(DEFAULT_COMPACT_AFTER_DELETIONS);
+
+/// Default for `COMPACT_AFTER_DELETIONS`
+pub const DEFAULT_COMPACT_AFTER_DELETIONS: u64 = 50;
+
+/// Most entries a walk may find before indexing gives up (the
+/// `maxIndexFiles` preference; 0 = no limit)
+static MAX_INDEX_FILES: AtomicU64 = AtomicU64::new(DEFAULT_MAX_INDEX_FILES);
+
+/// Default for `MAX_INDEX_FILES`: far beyond any real snapshot, but stops a
+/// job pointed at `/` before it fills memory and the destination
+pub const DEFAULT_MAX_INDEX_FILES: u64 = 20_000_000;
+
+/// Set how many files and folders a snapshot may hold for indexes opened
+/// from now on to index it. 0 turns the limit off.
+pub fn configure_max_index_files(max_files: u64) {
+    MAX_INDEX_FILES.store(max_files, Ordering::SeqCst);
+}
+
+/// Flag thresholds indexes opened from now on use (the `largeFileMb` and
+/// `staleAfterDays` preferences)
+static LARGE_FILE_BYTES: AtomicI64 = AtomicI64::new(DEFAULT_LARGE_FILE_MB as i64 * 1024 * 1024);
+static STALE_AFTER_SECS: AtomicI64 = AtomicI64::new(DEFAULT_STALE_AFTER_DAYS as i64 * 24 * 60 * 60);
+
+/// Default size (MB) at which a file is flagged large
+pub const DEFAULT_LARGE_FILE_MB: u64 = 100;
+
+/// Default age (days since last modified) at which a file is flagged stale
+pub const DEFAULT_STALE_AFTER_DAYS: u64 = 365;
+
+/// Set the thresholds `FileFlag`s are derived with by indexes opened from
+/// now on. Snapshots already indexed keep the flags they were given.
+pub fn configure_flag_thresholds(thresholds: FileFlagThresholds) {
+    LARGE_FILE_BYTES.store(thresholds.large_bytes, Ordering::SeqCst);
+    STALE_AFTER_SECS.store(thresholds.stale_after_secs, Ordering::SeqCst);
+}
+
+/// `index_meta` key counting snapshots deleted since the last VACUUM
+const META_DELETIONS_SINCE_COMPACT: &str = "deletions_since_compact";
+
+/// `index_meta` key counting VACUUMs run on this index
+const META_COMPACTIONS: &str = "compactions";
+
+/// Set how many snapshot deletions every index opened from now on tolerates
+/// before it compacts itself. VACUUM rewrites the whole file, so this trades
+/// reclaimed space against the time each compaction takes. 0 turns it off.
+pub fn configure_auto_compaction(after_deletions: u64) {
+    COMPACT_AFTER_DELETIONS.store(after_deletions, Ordering::SeqCst);
+}
+
+/// Whether names and paths are stored NFC-normalized (the default) rather
+/// than byte-for-byte (the `preserveRawPathNames` preference)
+static NORMALIZE_NAMES: AtomicBool = AtomicBool::new(true);
+
+/// Choose how names and paths indexed from now on are stored.
+///
+/// macOS reports names decomposed (NFD) while Linux keeps whatever bytes were
+/// written, usually composed (NFC), so the same file indexed on both would
+/// never compare or search as equal. Normalizing to NFC makes them equal, at
+/// the cost of the stored path no longer being the exact on-disk name for a
+/// file whose name wasn't NFC: APFS and HFS+ still open it under either form,
+/// a Linux filesystem may not, so the on-disk spelling of such paths is kept
+/// in `raw_paths` for restoring and opening. Preserving raw names keeps paths
+/// exact but comparisons byte-for-byte. Snapshots already indexed keep their
+/// form.
+pub fn configure_unicode_normalization(normalize: bool) {
+    NORMALIZE_NAMES.store(normalize, Ordering::SeqCst);
+}
+
+/// `s` the way names, paths and search patterns are stored and matched under
+/// the current setting; borrowed when nothing changes
+pub fn stored_form(s: &str) -> Cow<'_, str> {
+    stored_form_with(s, NORMALIZE_NAMES.load(Ordering::SeqCst))
+}
+
+/// `stored_form` with normalization on or off
+fn stored_form_with(s: &str, normalize: bool) -> Cow<'_, str> {
+    if !normalize || is_nfc(s) {
+        Cow::Borrowed(s)
+    } else {
+        Cow::Owned(s.nfc().collect())
+    }
+}
+
+/// How an index stores its file rows
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum IndexStorage {
+    /// One `files` row per entry per snapshot
+    #[default]
+    Denormalized,
+    /// Entries unchanged from an earlier snapshot share one `file_rows` row;
+    /// `files` is a view joining them back onto each snapshot
+    Normalized,
+}
+
+impl IndexStorage {
+    /// Layout new indexes get under the current preference
+    pub fn configured() -> Self {
+        if NORMALIZED_STORAGE.load(Ordering::SeqCst) {
+            IndexStorage::Normalized
+        } else {
+            IndexStorage::Denormalized
+        }
+    }
+}
+
+/// `files_fts` itself, tokenized with or without diacritic folding
+fn fts_table_sql(fold_diacritics: bool) -> String {
+    format!(
+        r#"
+        -- FTS5 virtual table for fast full-text search
+        -- Uses external content table (files) to avoid data duplication
+        CREATE VIRTUAL TABLE IF NOT EXISTS files_fts USING fts5(
+            name,
+            path,
+            content=files,
+            content_rowid=id,
+            tokenize='unicode61 remove_diacritics {}'
+        );
+        "#,
+        if fold_diacritics { 1 } else { 0 }
+    )
+}
+
+/// `files_fts` plus its sync triggers, tokenized with or without diacritic folding
+pub(crate) fn fts_schema_sql(fold_diacritics: bool) -> String {
+    format!(
+        r#"
+        {}
+
+        -- Triggers to keep FTS index in sync with files table
+        CREATE TRIGGER IF NOT EXISTS files_ai AFTER INSERT ON files BEGIN
+            INSERT INTO files_fts(rowid, name, path) VALUES (new.id, new.name, new.path);
+        END;
+
+        CREATE TRIGGER IF NOT EXISTS files_ad AFTER DELETE ON files BEGIN
+            INSERT INTO files_fts(files_fts, rowid, name, path) VALUES('delete', old.id, old.name, old.path);
+        END;
+
+        CREATE TRIGGER IF NOT EXISTS files_au AFTER UPDATE ON files BEGIN
+            INSERT INTO files_fts(files_fts, rowid, name, path) VALUES('delete', old.id, old.name, old.path);
+            INSERT INTO files_fts(rowid, name, path) VALUES (new.id, new.name, new.path);
+        END;
+        "#,
+        fts_table_sql(fold_diacritics)
+    )
+}
+
+/// Absolute path of a `file_rows` entry `r` inside snapshot `s`. Matches the
+/// walk's `root.join(relative)` for roots without `.`/`..` components.
+const SHARED_ROW_PATH_SQL: &str = "rtrim(s.root_path, '/') || '/' || \
+     CASE WHEN r.parent_path = '' THEN r.name ELSE r.parent_path || '/' || r.name END";
+
+/// `files_fts` with the normalized layout's sync triggers. FTS rows follow
+/// `snapshot_files`; snapshot rows are unlinked before the snapshot goes so
+/// the removed paths can still be rebuilt for the FTS delete.
+fn normalized_fts_schema_sql(fold_diacritics: bool) -> String {
+    format!(
+        r#"
+        {table}
+
+        CREATE TRIGGER IF NOT EXISTS snapshot_files_ai AFTER INSERT ON snapshot_files BEGIN
+            INSERT INTO files_fts(rowid, name, path)
+                SELECT id, name, path FROM files WHERE id = new.id;
+        END;
+
+        CREATE TRIGGER IF NOT EXISTS snapshot_files_ad AFTER DELETE ON snapshot_files BEGIN
+            INSERT INTO files_fts(files_fts, rowid, name, path)
+                SELECT 'delete', old.id, r.name, {path}
+                FROM file_rows r, snapshots s
+                WHERE r.id = old.file_row_id AND s.id = old.snapshot_id;
+            DELETE FROM file_rows WHERE id = old.file_row_id
+                AND NOT EXISTS (SELECT 1 FROM snapshot_files WHERE file_row_id = old.file_row_id);
+        END;
+
+        CREATE TRIGGER IF NOT EXISTS snapshots_bd_files BEFORE DELETE ON snapshots BEGIN
+            DELETE FROM snapshot_files WHERE snapshot_id = old.id;
+        END;
+        "#,
+        table = fts_table_sql(fold_diacritics),
+        path = SHARED_ROW_PATH_SQL
+    )
+}
+
+/// Replace the `files` table of an empty index with the normalized layout.
+/// Schema migrations that alter `files` can't run on an index converted this
+/// way; they need a matching step for `file_rows`.
+fn normalized_layout_sql(fold_diacritics: bool) -> String {
+    format!(
+        r#"
+        DROP TRIGGER IF EXISTS files_ai;
+        DROP TRIGGER IF EXISTS files_ad;
+        DROP TRIGGER IF EXISTS files_au;
+        DROP TABLE IF EXISTS files_fts;
+        DROP TABLE files;
+
+        -- One row per distinct entry; identical entries in later snapshots reuse it
+        CREATE TABLE file_rows (
+            id INTEGER PRIMARY KEY,
+            name TEXT NOT NULL,
+            parent_path TEXT NOT NULL,            -- Relative to the snapshot root
+            size INTEGER NOT NULL,
+            mtime INTEGER NOT NULL,               -- Unix SECONDS
+            inode INTEGER,
+            file_type TEXT NOT NULL,
+            content_hash TEXT,
+            file_flags INTEGER NOT NULL DEFAULT 0
+        );
+        CREATE UNIQUE INDEX idx_file_rows_identity ON file_rows(
+            parent_path, name, size, mtime, IFNULL(inode, -1), file_type,
+            IFNULL(content_hash, ''), file_flags
+        );
+
+        -- Which entries each snapshot holds; id doubles as the files.id / FTS rowid
+        CREATE TABLE snapshot_files (
+            id INTEGER PRIMARY KEY,
+            snapshot_id INTEGER NOT NULL,
+            file_row_id INTEGER NOT NULL,
+            FOREIGN KEY (snapshot_id) REFERENCES snapshots(id) ON DELETE CASCADE,
+            FOREIGN KEY (file_row_id) REFERENCES file_rows(id)
+        );
+        CREATE INDEX idx_snapshot_files_snapshot ON snapshot_files(snapshot_id, file_row_id);
+        CREATE INDEX idx_snapshot_files_row ON snapshot_files(file_row_id, snapshot_id);
+
+        -- Per-snapshot view with the same columns as the denormalized table
+        CREATE VIEW files AS
+            SELECT sf.id AS id,
+                   sf.snapshot_id AS snapshot_id,
+                   {path} AS path,
+                   r.name AS name,
+                   r.parent_path AS parent_path,
+                   r.size AS size,
+                   r.mtime AS mtime,
+                   r.inode AS inode,
+                   r.file_type AS file_type,
+                   r.content_hash AS content_hash,
+                   r.file_flags AS file_flags
+            FROM snapshot_files sf
+            JOIN file_rows r ON r.id = sf.file_row_id
+            JOIN snapshots s ON s.id = sf.snapshot_id;
+
+        {fts}
+        "#,
+        path = SHARED_ROW_PATH_SQL,
+        fts = normalized_fts_schema_sql(fold_diacritics)
+    )
+}
+
+/// `files.mtime` bound for a `modified_after` filter given in Unix ms. mtime
+/// is stored in whole seconds; flooring makes `mtime > cutoff` the same test
+/// as `mtime * 1000 > modified_after`.
+fn mtime_cutoff_secs(modified_after_ms: Option<i64>) -> Option<i64> {
+    modified_after_ms.map(|ms| ms.div_euclid(1000))
+}
+
+/// `files.name`'s extension in SQL: lowercase, after the last dot, '' without
+/// one. RTRIM strips every character but the dot, leaving the name up to it.
+const EXTENSION_SQL: &str = "CASE WHEN INSTR(name, '.') > 0 \
+     THEN LOWER(SUBSTR(name, LENGTH(RTRIM(name, REPLACE(name, '.', ''))) + 1)) \
+     ELSE '' END";
+
+/// SQL condition for a `type_filter`: files of `category`, and directories
+/// so the tree stays navigable; always true without a filter. The
+/// extensions are constants, so they can be inlined.
+fn category_condition(category: Option<FileCategory>) -> String {
+    let Some(category) = category else {
+        return "1".to_string();
+    };
+    let extensions: Vec<String> = category
+        .extensions()
+        .iter()
+        .map(|ext| format!("'{}'", ext))
+        .collect();
+    format!(
+        "(file_type = 'dir' OR {} IN ({}))",
+        EXTENSION_SQL,
+        extensions.join(", ")
+    )
+}
+
 /// SQLite-based snapshot index service
 pub struct IndexService {
     db_path: PathBuf,
     conn: Mutex<Connection>,
+    /// Thread budget for directory walks; `None` uses the shared pool
+    walk_pool: Option<Arc<WalkPool>>,
+    /// Thresholds for the derived `file_flags` written at index time
+    flag_thresholds: FileFlagThresholds,
+    /// File layout, fixed when the index was first created
+    storage: IndexStorage,
+    /// Files per committed batch in `index_snapshot_resumable`
+    resume_batch_rows: usize,
+    /// Snapshot deletions that make `compact_if_due` vacuum (0 = never)
+    compact_after_deletions: u64,
+    /// Entries a walk may find before it fails with `IndexTooLarge` (0 = no
+    /// limit)
+    max_index_files: u64,
+    /// Search folds diacritics differently from `configure_diacritic_folding`
+    folding_outdated: AtomicBool,
+}
+
+/// How `index_snapshot_inner` writes a snapshot
+#[derive(Debug, Clone, Copy, Default)]
+struct IndexOptions<'a> {
+    /// Replace a different folder already indexed at the same timestamp
+    force_replace: bool,
+    /// Leave the snapshot pending until `mark_snapshot_committed`
+    pending: bool,
+    /// Names, sizes and mtimes only; can't be restored from
+    metadata_only: bool,
+    /// Store a SHA-256 of every regular file
+    hash_contents: bool,
+    /// Checked during the walk and between insert chunks
+    cancel: Option<&'a CancelToken>,
+}
+
+/// `AmberError::Cancelled` once `cancel` is cancelled; never without one
+fn check_cancel(cancel: Option<&CancelToken>) -> Result<()> {
+    cancel.map_or(Ok(()), CancelToken::check)
 }
 
 /// File entry from directory walk
@@ -35,6 +428,139 @@ pub struct IndexedFile {
     pub mtime: i64,
     pub inode: Option<i64>,
     pub file_type: FileType,
+    /// Content hash, when the indexer computed one
+    pub content_hash: Option<String>,
+    /// Bitmask of `FileFlag` bits derived at index time
+    pub flags: i64,
+    /// Where a symlink points, as stored in the link (not resolved)
+    pub link_target: Option<String>,
+    /// The exact bytes of the path relative to the snapshot root, when the
+    /// stored path has escaped them (see `path_text`)
+    pub raw_path: Option<Vec<u8>>,
+}
+
+/// Derived per-file flags, stored as a bitmask in `files.file_flags`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FileFlag {
+    /// Size at or above `FileFlagThresholds::large_bytes`
+    Large,
+    /// Same content hash and size as another file in the snapshot
+    Duplicate,
+    /// Not modified for `FileFlagThresholds::stale_after_secs` before the snapshot
+    Stale,
+}
+
+impl FileFlag {
+    pub const fn bit(self) -> i64 {
+        match self {
+            FileFlag::Large => 1,
+            FileFlag::Duplicate => 1 << 1,
+            FileFlag::Stale => 1 << 2,
+        }
+    }
+}
+
+/// Thresholds used to derive `FileFlag`s while indexing
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FileFlagThresholds {
+    pub large_bytes: i64,
+    pub stale_after_secs: i64,
+}
+
+impl Default for FileFlagThresholds {
+    fn default() -> Self {
+        Self::from_preferences(DEFAULT_LARGE_FILE_MB, DEFAULT_STALE_AFTER_DAYS)
+    }
+}
+
+impl FileFlagThresholds {
+    /// Thresholds from a size in MB and an age in days, saturating
+    pub fn from_preferences(large_file_mb: u64, stale_after_days: u64) -> Self {
+        let clamp = |n: u64| i64::try_from(n).unwrap_or(i64::MAX);
+        Self {
+            large_bytes: clamp(large_file_mb.saturating_mul(1024 * 1024)),
+            stale_after_secs: clamp(stale_after_days.saturating_mul(24 * 60 * 60)),
+        }
+    }
+
+    /// The thresholds `configure_flag_thresholds` last set
+    pub fn configured() -> Self {
+        Self {
+            large_bytes: LARGE_FILE_BYTES.load(Ordering::SeqCst),
+            stale_after_secs: STALE_AFTER_SECS.load(Ordering::SeqCst),
+        }
+    }
+}
+
+/// Fill in `content_hash` with the hex SHA-256 of the contents of each
+/// regular file that doesn't have one yet, on `pool` like the walk. A file
+/// that can't be read is left without one.
+fn hash_file_contents(pool: &WalkPool, files: &mut [IndexedFile]) {
+    use rayon::prelude::*;
+
+    pool.install(|| {
+        files
+            .par_iter_mut()
+            .filter(|file| file.file_type == FileType::File && file.content_hash.is_none())
+            .for_each(|file| {
+                let _worker = pool.enter();
+                match sha256_file(Path::new(&file.path)) {
+                    Ok(hash) => file.content_hash = Some(hash),
+                    Err(e) => log::debug!("Failed to hash {}: {}", file.path, e),
+                }
+            })
+    });
+}
+
+fn sha256_file(path: &Path) -> std::io::Result<String> {
+    use sha2::{Digest, Sha256};
+
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut std::fs::File::open(path)?, &mut hasher)?;
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect())
+}
+
+/// Set `flags` on every regular file. Staleness is measured against the
+/// snapshot time (`snapshot_secs`) so re-indexing gives the same answer.
+fn compute_file_flags(
+    files: &mut [IndexedFile],
+    thresholds: &FileFlagThresholds,
+    snapshot_secs: i64,
+) {
+    let mut content_counts: HashMap<(&str, i64), usize> = HashMap::new();
+    for file in files.iter().filter(|f| f.file_type == FileType::File) {
+        if let Some(hash) = &file.content_hash {
+            *content_counts
+                .entry((hash.as_str(), file.size))
+                .or_default() += 1;
+        }
+    }
+    let duplicates: std::collections::HashSet<(String, i64)> = content_counts
+        .into_iter()
+        .filter(|(_, count)| *count > 1)
+        .map(|((hash, size), _)| (hash.to_string(), size))
+        .collect();
+
+    for file in files.iter_mut().filter(|f| f.file_type == FileType::File) {
+        let mut flags = 0;
+        if file.size >= thresholds.large_bytes {
+            flags |= FileFlag::Large.bit();
+        }
+        if snapshot_secs - file.mtime > thresholds.stale_after_secs {
+            flags |= FileFlag::Stale.bit();
+        }
+        if let Some(hash) = &file.content_hash {
+            if duplicates.contains(&(hash.clone(), file.size)) {
+                flags |= FileFlag::Duplicate.bit();
+            }
+        }
+        file.flags = flags;
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -42,6 +568,8 @@ pub enum FileType {
     File,
     Directory,
     Symlink,
+    /// Fifo, socket, or block/char device: listed, but not restored
+    Special,
 }
 
 impl FileType {
@@ -50,6 +578,7 @@ impl FileType {
             FileType::File => "file",
             FileType::Directory => "dir",
             FileType::Symlink => "symlink",
+            FileType::Special => "special",
         }
     }
 
@@ -58,6 +587,7 @@ impl FileType {
         match s {
             "dir" => FileType::Directory,
             "symlink" => FileType::Symlink,
+            "special" => FileType::Special,
             _ => FileType::File,
         }
     }
@@ -73,6 +603,51 @@ pub struct IndexedSnapshot {
     pub root_path: String,
     pub file_count: i64,
     pub total_size: i64,
+    /// Only the source's listing was recorded; there is no data to restore
+    pub metadata_only: bool,
+}
+
+/// What `reindex_snapshot_incremental` changed, by entry
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IncrementalReindex {
+    pub snapshot: IndexedSnapshot,
+    /// Entries new to the folder
+    pub added: usize,
+    /// Entries still there whose size, mtime or derived columns changed
+    pub updated: usize,
+    /// Entries gone from the folder
+    pub removed: usize,
+    /// Entries whose rows were left alone
+    pub unchanged: usize,
+}
+
+/// A `files` row as `reindex_snapshot_incremental` compares it to the walk
+struct StoredEntry {
+    id: i64,
+    size: i64,
+    mtime: i64,
+    inode: Option<i64>,
+    file_type: String,
+    content_hash: Option<String>,
+    flags: i64,
+}
+
+impl StoredEntry {
+    /// Same size, mtime and type, so the contents are taken to be unchanged
+    fn same_content(&self, file: &IndexedFile) -> bool {
+        self.size == file.size
+            && self.mtime == file.mtime
+            && self.file_type == file.file_type.as_str()
+    }
+
+    /// Every stored column matches, so the row can stay as it is
+    fn matches(&self, file: &IndexedFile) -> bool {
+        self.same_content(file)
+            && self.inode == file.inode
+            && self.content_hash == file.content_hash
+            && self.flags == file.flags
+    }
 }
 
 /// Global search result with snapshot context
@@ -85,6 +660,31 @@ pub struct GlobalSearchResult {
     pub rank: f64,
 }
 
+/// A match from searching every snapshot of one job
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JobSearchResult {
+    /// The file as it was in `snapshot_timestamp`
+    pub file: FileNode,
+    /// Path relative to the snapshot root, the same in every snapshot
+    pub rel_path: String,
+    /// Newest snapshot this match came from
+    pub snapshot_timestamp: i64,
+    /// Every snapshot holding this path, newest first. Only collapsed
+    /// results list more than `snapshot_timestamp`.
+    pub snapshot_timestamps: Vec<i64>,
+}
+
+/// One snapshot holding a given path, as the path was in it
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PathOccurrence {
+    pub timestamp: i64,
+    pub size: i64,
+    /// Unix MILLISECONDS, like `FileNode::modified`
+    pub mtime: i64,
+}
+
 /// File type statistics (aggregated by extension)
 #[derive(Debug, Clone, serde::Serialize)]
 #[serde(rename_all = "camelCase")]
@@ -94,6 +694,54 @@ pub struct FileTypeStats {
     pub total_size: i64,
 }
 
+/// Default `get_size_histogram` bucket bounds: <1K, 1K–1M, 1M–100M, >=100M
+pub const DEFAULT_SIZE_BUCKETS: [i64; 3] = [1024, 1024 * 1024, 100 * 1024 * 1024];
+
+/// Most bucket bounds a size histogram accepts
+const MAX_SIZE_BUCKETS: usize = 64;
+
+/// Files of one size range in a snapshot
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SizeBucket {
+    /// Inclusive lower bound in bytes
+    pub min_size: i64,
+    /// Exclusive upper bound in bytes; None for the last bucket
+    pub max_size: Option<i64>,
+    pub count: i64,
+    pub total_size: i64,
+}
+
+/// Count and size of one extension in one snapshot
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExtensionGrowthPoint {
+    pub timestamp: i64,
+    pub count: i64,
+    pub total_size: i64,
+}
+
+/// What restoring a selection from a snapshot will write
+#[derive(Debug, Clone, Copy, Default, PartialEq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RestoreEstimate {
+    /// Files and symlinks; directories are not counted
+    pub file_count: u64,
+    pub total_bytes: u64,
+    /// Fifos, sockets and devices in the selection, which restore skips
+    pub skipped_special: u64,
+}
+
+/// Files in one snapshot that are hard links of each other
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HardlinkGroup {
+    pub inode: i64,
+    pub size: i64,
+    /// Sorted
+    pub paths: Vec<String>,
+}
+
 /// Paginated directory contents with metadata
 #[derive(Debug, Clone, serde::Serialize)]
 #[serde(rename_all = "camelCase")]
@@ -103,6 +751,138 @@ pub struct DirectoryContents {
     pub has_more: bool,
 }
 
+/// What a directory listing is ordered by
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SortField {
+    #[default]
+    Name,
+    Size,
+    Mtime,
+}
+
+/// Order of a directory listing. Ties go by name, A to Z.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SortBy {
+    #[serde(default)]
+    pub field: SortField,
+    #[serde(default)]
+    pub descending: bool,
+    /// List folders ahead of everything else, each group in `field` order
+    #[serde(default = "default_directories_first")]
+    pub directories_first: bool,
+}
+
+fn default_directories_first() -> bool {
+    true
+}
+
+impl Default for SortBy {
+    fn default() -> Self {
+        Self {
+            field: SortField::Name,
+            descending: false,
+            directories_first: true,
+        }
+    }
+}
+
+impl SortBy {
+    /// The ORDER BY clause, built from fixed column names only
+    fn order_sql(self) -> String {
+        let direction = if self.descending { "DESC" } else { "ASC" };
+        let mut order = String::new();
+        if self.directories_first {
+            order.push_str("file_type = 'dir' DESC, ");
+        }
+        match self.field {
+            SortField::Name => order.push_str(&format!("name {}", direction)),
+            SortField::Size => order.push_str(&format!("size {}, name ASC", direction)),
+            SortField::Mtime => order.push_str(&format!("mtime {}, name ASC", direction)),
+        }
+        order
+    }
+}
+
+/// A directory's descendants a few levels deep, for incremental expansion
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Subtree {
+    /// Children of the requested directory, nested. A directory whose
+    /// `children` is None wasn't expanded (too deep, or over the node limit).
+    pub nodes: Vec<FileNode>,
+    pub node_count: usize,
+    /// The node limit stopped expansion before `depth` was reached
+    pub truncated: bool,
+}
+
+/// One breadcrumb of a browsed directory
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Crumb {
+    pub name: String,
+    /// Value to pass as `parent_path` to list this directory
+    pub parent_path: String,
+}
+
+/// `parent_path` of the entries inside directory `name`, itself in `parent`
+fn child_parent_path(parent: &str, name: &str) -> String {
+    if parent.is_empty() {
+        name.to_string()
+    } else {
+        format!("{}/{}", parent, name)
+    }
+}
+
+/// Take `dir`'s listing out of `listed` with its expanded subdirectories
+/// nested in; directories never listed get `children: None`
+fn nest_listed(listed: &mut HashMap<String, Vec<FileNode>>, dir: &str) -> Vec<FileNode> {
+    let mut nodes = listed.remove(dir).unwrap_or_default();
+    for node in &mut nodes {
+        if node.node_type == file_type::DIR {
+            let path = child_parent_path(dir, &node.name);
+            node.children = listed
+                .contains_key(&path)
+                .then(|| nest_listed(listed, &path));
+        }
+    }
+    nodes
+}
+
+/// Split a relative `parent_path` the way the index stores it: segments joined
+/// by `/`, with "" as the snapshot root. The first crumb is always the root.
+fn breadcrumbs_for(root_name: &str, parent_path: &str) -> Result<Vec<Crumb>> {
+    let mut crumbs = vec![Crumb {
+        name: root_name.to_string(),
+        parent_path: String::new(),
+    }];
+
+    let mut current = String::new();
+    for segment in parent_path.split('/') {
+        match segment {
+            "" | "." => continue,
+            ".." => {
+                return Err(AmberError::InvalidPath(format!(
+                    "Parent path must not contain '..': {}",
+                    parent_path
+                )))
+            }
+            _ => {}
+        }
+        if !current.is_empty() {
+            current.push('/');
+        }
+        current.push_str(segment);
+        crumbs.push(Crumb {
+            name: segment.to_string(),
+            parent_path: current.clone(),
+        });
+    }
+
+    Ok(crumbs)
+}
+
 /// Largest file info for analytics
 #[derive(Debug, Clone, serde::Serialize)]
 #[serde(rename_all = "camelCase")]
@@ -128,6 +908,18 @@ pub struct JobAggregateStats {
     pub last_snapshot_ms: Option<i64>,
 }
 
+/// Approximate unique content stored for a job across all of its snapshots
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JobLogicalFootprint {
+    /// Bytes after counting each shared file once
+    pub unique_size_bytes: i64,
+    /// Sum of every snapshot's size (what `JobAggregateStats` reports)
+    pub naive_size_bytes: i64,
+    /// Distinct files behind `unique_size_bytes`
+    pub unique_files: i64,
+}
+
 /// Snapshot density for calendar/timeline visualization (TIM-128)
 #[derive(Debug, Clone, serde::Serialize)]
 #[serde(rename_all = "camelCase")]
@@ -149,13 +941,58 @@ pub struct DiffEntry {
     pub size_b: Option<i64>, // size in snapshot B (None if deleted)
 }
 
+/// A file of the earlier snapshot that the later one no longer has
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeletedFile {
+    /// Relative to the snapshot root
+    pub path: String,
+    pub size: i64,
+}
+
+/// Everything deleted between a last-good snapshot and a later one, in the
+/// shape `restore_files` takes: the good snapshot's folder and the paths
+/// inside it
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeletedFiles {
+    pub snapshot_path: String,
+    /// Ordered by path
+    pub files: Vec<DeletedFile>,
+    pub total_bytes: i64,
+}
+
+/// A file that moved between snapshots (same content hash and size)
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RenamedEntry {
+    pub from: String,
+    pub to: String,
+    pub size: i64,
+}
+
+/// A symlink whose target changed between two snapshots
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SymlinkRetarget {
+    pub path: String,
+    pub target_a: String,
+    pub target_b: String,
+}
+
 /// TIM-221: Summary statistics for snapshot diff
 #[derive(Debug, Clone, serde::Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct DiffSummary {
     pub total_added: u32,
     pub total_deleted: u32,
+    /// Every modified file: size changes plus `content_changed`
     pub total_modified: u32,
+    /// Modified files that kept their size but whose content hash differs;
+    /// only found when both snapshots were indexed with hashes
+    pub content_changed: u32,
+    pub total_renamed: u32,
+    pub total_symlink_retargeted: u32,
     pub size_delta: i64,
 }
 
@@ -166,74 +1003,508 @@ pub struct SnapshotDiff {
     pub added: Vec<DiffEntry>,
     pub deleted: Vec<DiffEntry>,
     pub modified: Vec<DiffEntry>,
+    /// Deleted/added pairs with identical hash+size; only found when both
+    /// snapshots carry content hashes
+    pub renamed: Vec<RenamedEntry>,
+    /// Symlinks at the same path in both snapshots that point somewhere else
+    /// in B, e.g. `current -> v1` becoming `current -> v2`
+    pub symlink_retargeted: Vec<SymlinkRetarget>,
+    /// Set per list when `limit` cut it short; the summary has full totals
+    pub truncated: DiffTruncation,
     pub summary: DiffSummary,
+    /// Files that changed size the most, when asked for (see
+    /// `IndexService::size_contributors`); empty otherwise
+    pub top_contributors: Vec<DiffEntry>,
 }
 
-impl IndexService {
-    /// Create or open the index database at the default app data location
-    pub fn new(app_data_dir: &Path) -> Result<Self> {
-        let db_path = app_data_dir.join("index.db");
-        Self::open_at_path(db_path)
-    }
-
-    /// Open an index database at a destination drive (TIM-127)
-    /// Path: <dest_path>/.amber-meta/index.db
-    pub fn for_destination(dest_path: &str) -> Result<Self> {
-        use crate::services::manifest_service;
-        let dest_root = Path::new(dest_path);
-        if !dest_root.is_dir() {
-            return Err(AmberError::InvalidPath(format!(
-                "Destination path is not accessible: {}",
-                dest_path
-            )));
+/// Pair deleted and added files that share a content hash and size.
+/// Entries without a hash are left untouched as plain add/delete.
+fn detect_renames(
+    added: Vec<DiffRow>,
+    deleted: Vec<DiffRow>,
+) -> (Vec<DiffEntry>, Vec<DiffEntry>, Vec<RenamedEntry>) {
+    let mut added_by_content: HashMap<(String, i64), Vec<usize>> = HashMap::new();
+    for (i, (entry, hash)) in added.iter().enumerate() {
+        if let Some(hash) = hash {
+            added_by_content
+                .entry((hash.clone(), entry.size_b.unwrap_or(0)))
+                .or_default()
+                .push(i);
         }
-        let db_path = manifest_service::get_index_path(dest_path);
-        Self::open_at_path(db_path)
     }
 
-    /// Internal: open database at a specific path
-    fn open_at_path(db_path: PathBuf) -> Result<Self> {
-        // Ensure parent directory exists
-        if let Some(parent) = db_path.parent() {
-            std::fs::create_dir_all(parent).map_err(|e| {
-                AmberError::Index(format!("Failed to create index directory: {}", e))
-            })?;
+    let mut paired = vec![false; added.len()];
+    let mut renamed = Vec::new();
+    let mut still_deleted = Vec::new();
+    for (entry, hash) in deleted {
+        let size = entry.size_a.unwrap_or(0);
+        let candidate = hash.and_then(|hash| {
+            added_by_content
+                .get_mut(&(hash, size))
+                .and_then(|candidates| candidates.pop())
+        });
+        match candidate {
+            Some(i) => {
+                paired[i] = true;
+                renamed.push(RenamedEntry {
+                    from: entry.path,
+                    to: added[i].0.path.clone(),
+                    size,
+                });
+            }
+            None => still_deleted.push(entry),
         }
+    }
 
-        let conn = Connection::open(&db_path)
-            .map_err(|e| AmberError::Index(format!("Failed to open index database: {}", e)))?;
+    let still_added = added
+        .into_iter()
+        .zip(paired)
+        .filter(|(_, paired)| !paired)
+        .map(|((entry, _), _)| entry)
+        .collect();
 
-        conn.busy_timeout(Duration::from_secs(5))
-            .map_err(|e| AmberError::Index(format!("Failed to set busy timeout: {}", e)))?;
+    (still_added, still_deleted, renamed)
+}
 
-        let service = Self {
-            db_path,
-            conn: Mutex::new(conn),
-        };
+/// How far a file's size moved between the two snapshots, either way
+fn size_change(entry: &DiffEntry) -> i64 {
+    (entry.size_b.unwrap_or(0) - entry.size_a.unwrap_or(0)).abs()
+}
 
-        service.initialize_schema()?;
-        Ok(service)
-    }
+/// One of the three lists in a snapshot diff
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DiffCategory {
+    Added,
+    Deleted,
+    Modified,
+}
 
-    /// Get the path to the database file
-    pub fn get_db_path(&self) -> &Path {
-        &self.db_path
+impl DiffCategory {
+    fn label(self) -> &'static str {
+        match self {
+            DiffCategory::Added => "added",
+            DiffCategory::Deleted => "deleted",
+            DiffCategory::Modified => "modified",
+        }
     }
+}
 
-    /// Validate that the database schema matches what we expect.
-    /// Returns Ok(()) if valid, or an error describing the mismatch.
-    /// This is useful for detecting when mock data was generated with an old schema.
-    pub fn validate_schema(&self) -> Result<()> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|e| AmberError::Index(format!("Failed to acquire database lock: {}", e)))?;
+/// Which lists in a `SnapshotDiff` were cut short by the limit
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiffTruncation {
+    pub added: bool,
+    pub deleted: bool,
+    pub modified: bool,
+}
+
+/// Which slice of a diff category to fetch
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiffPageRequest {
+    pub category: DiffCategory,
+    #[serde(default)]
+    pub offset: usize,
+    pub limit: usize,
+}
+
+/// One page of a single diff category
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiffPage {
+    pub category: DiffCategory,
+    pub entries: Vec<DiffEntry>,
+    pub offset: usize,
+    /// Total entries in this category (across all pages)
+    pub total: u32,
+    pub has_more: bool,
+}
+
+/// A diff entry plus its content hash (used for rename pairing)
+type DiffRow = (DiffEntry, Option<String>);
+
+struct DiffCategoryTotals {
+    count: u32,
+    size_delta: i64,
+    /// Rows whose size is the same on both sides (hash-only modifications)
+    same_size: u32,
+}
+
+/// Rows of one diff category as (rel_path, size_a, size_b, content_hash).
+/// Binds ?1 = snapshot A id, ?2 = snapshot B id, ?3 = subtree filter.
+fn diff_category_sql(category: DiffCategory) -> String {
+    // Use relative paths (parent_path + name) for comparison since
+    // absolute paths differ between snapshots (each has a different root dir)
+    let rel = "CASE WHEN parent_path = '' THEN name ELSE parent_path || '/' || name END";
+
+    // Subtree filter on ?3: exact component prefix match (no LIKE, so
+    // `_`/`%` in folder names and case differences can't widen the match)
+    let scope = "(?3 = '' OR parent_path = ?3 \
+         OR substr(parent_path, 1, length(?3) + 1) = ?3 || '/')";
+
+    match category {
+        DiffCategory::Added => format!(
+            r#"
+            SELECT {rel} AS rel_path, NULL AS size_a, size AS size_b, content_hash
+            FROM files WHERE snapshot_id = ?2 AND file_type = 'file' AND {scope}
+              AND {rel} NOT IN (
+                SELECT {rel} FROM files WHERE snapshot_id = ?1 AND file_type = 'file' AND {scope}
+              )
+            "#
+        ),
+        DiffCategory::Deleted => format!(
+            r#"
+            SELECT {rel} AS rel_path, size AS size_a, NULL AS size_b, content_hash
+            FROM files WHERE snapshot_id = ?1 AND file_type = 'file' AND {scope}
+              AND {rel} NOT IN (
+                SELECT {rel} FROM files WHERE snapshot_id = ?2 AND file_type = 'file' AND {scope}
+              )
+            "#
+        ),
+        // A size change, or the same size with a different hash when both
+        // sides were hashed
+        DiffCategory::Modified => format!(
+            r#"
+            SELECT a.rel_path AS rel_path, a.size AS size_a, b.size AS size_b,
+                   NULL AS content_hash
+            FROM (
+                SELECT {rel} AS rel_path, size, content_hash
+                FROM files WHERE snapshot_id = ?1 AND file_type = 'file' AND {scope}
+            ) a
+            INNER JOIN (
+                SELECT {rel} AS rel_path, size, content_hash
+                FROM files WHERE snapshot_id = ?2 AND file_type = 'file' AND {scope}
+            ) b ON a.rel_path = b.rel_path
+            WHERE a.size != b.size
+               OR (a.content_hash IS NOT NULL AND b.content_hash IS NOT NULL
+                   AND a.content_hash != b.content_hash)
+            "#
+        ),
+    }
+}
+
+/// Whether SQLite gave up waiting on another connection's lock
+fn is_busy(e: &rusqlite::Error) -> bool {
+    matches!(
+        e.sqlite_error_code(),
+        Some(rusqlite::ErrorCode::DatabaseBusy | rusqlite::ErrorCode::DatabaseLocked)
+    )
+}
+
+/// `AmberError::IndexBusy` for busy/locked errors, which are worth retrying;
+/// `AmberError::Index` with `context` for anything else
+fn sql_error(context: &str, e: rusqlite::Error) -> AmberError {
+    if is_busy(&e) {
+        AmberError::IndexBusy(format!("{}: {}", context, e))
+    } else {
+        AmberError::Index(format!("{}: {}", context, e))
+    }
+}
+
+/// Looking up a snapshot's id found no row, or failed
+fn snapshot_lookup_error(e: rusqlite::Error) -> AmberError {
+    match e {
+        rusqlite::Error::QueryReturnedNoRows => {
+            AmberError::Index("Snapshot not found in index".to_string())
+        }
+        e => sql_error("Failed to look up snapshot", e),
+    }
+}
+
+/// Run `attempt` until it stops failing with `AmberError::IndexBusy`, up to
+/// `READ_ATTEMPTS` times with a doubling backoff in between
+fn retry_busy<T>(mut attempt: impl FnMut() -> Result<T>) -> Result<T> {
+    let mut backoff = READ_RETRY_BACKOFF;
+    for _ in 1..READ_ATTEMPTS {
+        match attempt() {
+            Err(AmberError::IndexBusy(e)) => {
+                log::debug!("Index busy, retrying in {:?}: {}", backoff, e);
+                std::thread::sleep(backoff);
+                backoff *= 2;
+            }
+            result => return result,
+        }
+    }
+    attempt()
+}
+
+/// A counter from `index_meta` (0 if never written)
+fn read_meta(conn: &Connection, key: &str) -> Result<u64> {
+    conn.query_row(
+        "SELECT value FROM index_meta WHERE key = ?",
+        params![key],
+        |row| row.get::<_, i64>(0),
+    )
+    .optional()
+    .map(|v| v.unwrap_or(0).max(0) as u64)
+    .map_err(|e| sql_error(&format!("Failed to read index metadata '{}'", key), e))
+}
+
+fn set_meta(conn: &Connection, key: &str, value: u64) -> Result<()> {
+    conn.execute(
+        "INSERT INTO index_meta (key, value) VALUES (?1, ?2)
+         ON CONFLICT(key) DO UPDATE SET value = excluded.value",
+        params![key, value as i64],
+    )
+    .map_err(|e| sql_error(&format!("Failed to write index metadata '{}'", key), e))?;
+    Ok(())
+}
+
+fn bump_meta(conn: &Connection, key: &str, by: u64) -> Result<()> {
+    if by == 0 {
+        return Ok(());
+    }
+    conn.execute(
+        "INSERT INTO index_meta (key, value) VALUES (?1, ?2)
+         ON CONFLICT(key) DO UPDATE SET value = value + excluded.value",
+        params![key, by as i64],
+    )
+    .map_err(|e| sql_error(&format!("Failed to write index metadata '{}'", key), e))?;
+    Ok(())
+}
+
+/// Normalize a relative subtree filter ("./photos//2024/" -> "photos/2024")
+fn normalize_subtree(path: &str) -> Result<String> {
+    let mut parts = Vec::new();
+    for component in path.split('/') {
+        match component {
+            "" | "." => continue,
+            ".." => {
+                return Err(AmberError::InvalidPath(format!(
+                    "Subtree path must not contain '..': {}",
+                    path
+                )))
+            }
+            c => parts.push(c),
+        }
+    }
+    Ok(parts.join("/"))
+}
+
+/// Compile diff ignore globs; `None` when there are none. `*` stays within
+/// one path component, `**` crosses them.
+fn build_ignore_set(globs: &[String]) -> Result<Option<GlobSet>> {
+    if globs.is_empty() {
+        return Ok(None);
+    }
+    let mut builder = GlobSetBuilder::new();
+    for glob in globs {
+        let pattern = glob.trim().trim_start_matches("./").trim_end_matches('/');
+        let compiled = GlobBuilder::new(pattern)
+            .literal_separator(true)
+            .build()
+            .map_err(|e| {
+                AmberError::ValidationError(format!("Invalid ignore glob '{}': {}", glob, e))
+            })?;
+        builder.add(compiled);
+    }
+    builder
+        .build()
+        .map(Some)
+        .map_err(|e| AmberError::ValidationError(format!("Invalid ignore globs: {}", e)))
+}
+
+/// Whether a relative path, or any folder above it, matches an ignore glob,
+/// so `cache` ignores everything under `cache/`
+fn is_ignored(ignore: &GlobSet, rel_path: &str) -> bool {
+    ignore.is_match(rel_path)
+        || rel_path
+            .match_indices('/')
+            .any(|(i, _)| ignore.is_match(&rel_path[..i]))
+}
+
+/// Whether two snapshot root paths name the same folder (ignores trailing
+/// slashes, and resolves symlinks when both still exist)
+fn same_root_path(a: &str, b: &str) -> bool {
+    let (a, b) = (Path::new(a), Path::new(b));
+    if a.components().eq(b.components()) {
+        return true;
+    }
+    matches!((a.canonicalize(), b.canonicalize()), (Ok(a), Ok(b)) if a == b)
+}
+
+/// Refuse to put `snapshot_path` at a (job_id, timestamp) already indexed for
+/// another folder, which would silently clobber that snapshot
+fn ensure_same_root(
+    job_id: &str,
+    timestamp: i64,
+    existing_root: &str,
+    snapshot_path: &str,
+) -> Result<()> {
+    if same_root_path(existing_root, snapshot_path) {
+        return Ok(());
+    }
+    Err(AmberError::Snapshot(format!(
+        "job '{}': timestamp {} is already indexed for {}, refusing to replace it with {}",
+        job_id, timestamp, existing_root, snapshot_path
+    )))
+}
+
+impl IndexService {
+    /// Create or open the index database at the default app data location
+    pub fn new(app_data_dir: &Path) -> Result<Self> {
+        Self::new_with_storage(app_data_dir, IndexStorage::configured())
+    }
+
+    /// Like `new`, laying a newly created index out as `storage` instead of
+    /// following the preference
+    pub fn new_with_storage(app_data_dir: &Path, storage: IndexStorage) -> Result<Self> {
+        let db_path = app_data_dir.join("index.db");
+        Self::open_at_path(db_path, storage)
+    }
+
+    /// Open an index database at a destination drive (TIM-127)
+    /// Path: <dest_path>/.amber-meta/index.db
+    pub fn for_destination(dest_path: &str) -> Result<Self> {
+        let dest_root = Path::new(dest_path);
+        if !dest_root.is_dir() {
+            return Err(AmberError::InvalidPath(format!(
+                "Destination path is not accessible: {}",
+                dest_path
+            )));
+        }
+        let db_path = manifest_service::get_index_path(dest_path);
+        Self::open_at_path(db_path, IndexStorage::configured())
+    }
+
+    /// Internal: open database at a specific path
+    fn open_at_path(db_path: PathBuf, storage: IndexStorage) -> Result<Self> {
+        // Ensure parent directory exists
+        if let Some(parent) = db_path.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| {
+                AmberError::Index(format!("Failed to create index directory: {}", e))
+            })?;
+        }
+
+        let conn = Connection::open(&db_path)
+            .map_err(|e| sql_error("Failed to open index database", e))?;
+        Self::from_connection(db_path, conn, storage)
+    }
+
+    /// An index that lives only in memory, with the same schema and
+    /// migrations as a file-backed one. Nothing is persisted, so it suits
+    /// tests that don't reopen the index.
+    pub fn new_in_memory() -> Result<Self> {
+        let conn = Connection::open_in_memory()
+            .map_err(|e| sql_error("Failed to open index database", e))?;
+        Self::from_connection(
+            PathBuf::from(IN_MEMORY_PATH),
+            conn,
+            IndexStorage::configured(),
+        )
+    }
+
+    fn from_connection(db_path: PathBuf, conn: Connection, storage: IndexStorage) -> Result<Self> {
+        conn.busy_timeout(BUSY_TIMEOUT)
+            .map_err(|e| sql_error("Failed to set busy timeout", e))?;
+
+        let mut service = Self {
+            db_path,
+            conn: Mutex::new(conn),
+            walk_pool: None,
+            flag_thresholds: FileFlagThresholds::configured(),
+            storage: IndexStorage::Denormalized,
+            resume_batch_rows: RESUME_BATCH_ROWS,
+            compact_after_deletions: COMPACT_AFTER_DELETIONS.load(Ordering::SeqCst),
+            max_index_files: MAX_INDEX_FILES.load(Ordering::SeqCst),
+            folding_outdated: AtomicBool::new(false),
+        };
+
+        service.initialize_schema()?;
+        service.storage = service.initialize_storage(storage)?;
+        let fold = FOLD_DIACRITICS.load(Ordering::SeqCst);
+        if service.uses_diacritic_folding()? != fold {
+            log::info!(
+                "Search index of {} {} diacritics unlike the preference; left as is until rebuilt",
+                service.db_path.display(),
+                if fold { "keeps" } else { "folds" }
+            );
+            service.folding_outdated.store(true, Ordering::SeqCst);
+        }
+        if let Err(e) = service.compact_if_due() {
+            log::warn!(
+                "Automatic compaction of {:?} failed: {}",
+                service.db_path,
+                e
+            );
+        }
+        Ok(service)
+    }
+
+    /// Walk directories on a dedicated pool instead of the shared one
+    pub fn with_walk_pool(mut self, pool: Arc<WalkPool>) -> Self {
+        self.walk_pool = Some(pool);
+        self
+    }
+
+    /// Derive `file_flags` with custom thresholds instead of the configured ones
+    pub fn with_flag_thresholds(mut self, thresholds: FileFlagThresholds) -> Self {
+        self.flag_thresholds = thresholds;
+        self
+    }
+
+    /// Commit resumable indexing in batches of `rows` files
+    pub fn with_resume_batch_rows(mut self, rows: usize) -> Self {
+        self.resume_batch_rows = rows.max(1);
+        self
+    }
+
+    /// Compact after `deletions` snapshot deletions instead of the preference
+    /// (0 = never)
+    pub fn with_compaction_threshold(mut self, deletions: u64) -> Self {
+        self.compact_after_deletions = deletions;
+        self
+    }
+
+    /// Refuse snapshots with more than `max_files` entries instead of the
+    /// preference (0 = no limit)
+    pub fn with_max_index_files(mut self, max_files: u64) -> Self {
+        self.max_index_files = max_files;
+        self
+    }
+
+    /// Get the path to the database file
+    pub fn get_db_path(&self) -> &Path {
+        &self.db_path
+    }
+
+    /// Run a read on the connection, retrying while another connection keeps
+    /// the database busy. The lock is released between attempts.
+    fn read<T>(&self, mut query: impl FnMut(&Connection) -> Result<T>) -> Result<T> {
+        retry_busy(|| {
+            let conn = self.conn.lock().map_err(|e| {
+                AmberError::Index(format!("Failed to acquire database lock: {}", e))
+            })?;
+            query(&conn)
+        })
+    }
+
+    /// Whether this index was opened with `new_in_memory`
+    pub fn is_in_memory(&self) -> bool {
+        self.db_path == Path::new(IN_MEMORY_PATH)
+    }
+
+    /// Validate that the database schema matches what we expect.
+    /// Returns Ok(()) if valid, or an error describing the mismatch.
+    /// This is useful for detecting when mock data was generated with an old schema.
+    pub fn validate_schema(&self) -> Result<()> {
+        let conn = self
+            .conn
+            .lock()
+            .map_err(|e| AmberError::Index(format!("Failed to acquire database lock: {}", e)))?;
 
         // Check user_version
         let version: i32 = conn
             .query_row("PRAGMA user_version", [], |row| row.get(0))
             .unwrap_or(0);
 
+        if version > DB_VERSION {
+            return Err(AmberError::Index(format!(
+                "Database schema v{} is newer than supported (v{})",
+                version, DB_VERSION
+            )));
+        }
         if version != DB_VERSION {
             return Err(AmberError::Index(format!(
                 "Database schema version mismatch: found v{}, expected v{}. \
@@ -250,6 +1521,8 @@ impl IndexService {
             "root_path",
             "file_count",
             "total_size",
+            "pending",
+            "resume_after",
         ];
         for col in required_snapshot_cols {
             let exists: bool = conn
@@ -279,6 +1552,8 @@ impl IndexService {
             "size",
             "mtime",
             "file_type",
+            "content_hash",
+            "file_flags",
         ];
         for col in required_file_cols {
             let exists: bool = conn
@@ -304,177 +1579,311 @@ impl IndexService {
 
     /// Initialize database schema
     fn initialize_schema(&self) -> Result<()> {
-        let conn = self
+        let mut conn = self
             .conn
             .lock()
             .map_err(|e| AmberError::Index(format!("Failed to acquire database lock: {}", e)))?;
 
-        // Enable WAL mode for better concurrent read performance
+        // An older index (e.g. a drive indexed by an earlier Amber) is migrated
+        // below; a newer one is left untouched
+        index_migrations::ensure_not_newer(&conn, DB_VERSION)?;
+
+        // Enable WAL mode for better concurrent read performance. An in-memory
+        // database has no file for a WAL or mmap, so those two are skipped.
+        if !self.is_in_memory() {
+            conn.execute_batch(
+                "PRAGMA journal_mode=WAL;
+                 PRAGMA mmap_size = 268435456;    -- 256MB memory-mapped I/O",
+            )
+            .map_err(|e| sql_error("Failed to enable WAL mode", e))?;
+        }
         // Also apply performance PRAGMA optimizations for large datasets (150K+ files)
         conn.execute_batch(
-            "PRAGMA journal_mode=WAL;
-             PRAGMA foreign_keys = ON;
+            "PRAGMA foreign_keys = ON;
              PRAGMA cache_size = -64000;      -- 64MB cache for better performance
              PRAGMA temp_store = MEMORY;      -- Store temp tables in memory
-             PRAGMA mmap_size = 268435456;    -- 256MB memory-mapped I/O
              PRAGMA synchronous = NORMAL;", // Balanced safety/performance
         )
-        .map_err(|e| AmberError::Index(format!("Failed to set database optimizations: {}", e)))?;
+        .map_err(|e| sql_error("Failed to set database optimizations", e))?;
 
-        // Check current version
-        let version: i32 = conn
-            .query_row("PRAGMA user_version", [], |row| row.get(0))
-            .unwrap_or(0);
+        let registry = index_migrations::migrations(FOLD_DIACRITICS.load(Ordering::SeqCst));
+        let applied = index_migrations::migrate_up(&mut conn, &registry, DB_VERSION)?;
 
-        if version < DB_VERSION {
-            self.run_migrations(&conn, version)?;
+        if applied > 0 {
+            // Analyze tables to update query planner statistics
+            conn.execute_batch("ANALYZE;")
+                .map_err(|e| sql_error("Failed to analyze database", e))?;
         }
 
         Ok(())
     }
 
-    /// Run database migrations
-    fn run_migrations(&self, conn: &Connection, from_version: i32) -> Result<()> {
-        if from_version < 1 {
-            // Initial schema (v1)
-            conn.execute_batch(
-                r#"
-                -- =================================================================
-                -- SCHEMA DOCUMENTATION
-                -- =================================================================
-                -- Timestamps:
-                --   - snapshots.timestamp: Unix MILLISECONDS
-                --   - files.mtime: Unix SECONDS (converted to ms at API boundary)
-                --   - snapshots.created_at: Unix SECONDS (default)
-                --
-                -- File types (files.file_type):
-                --   - 'file': Regular file
-                --   - 'dir': Directory
-                --   - 'symlink': Symbolic link
-                --   Note: Always lowercase, matches Rust file_type module
-                -- =================================================================
-
-                -- Snapshots table: One entry per backup snapshot
-                CREATE TABLE IF NOT EXISTS snapshots (
-                    id INTEGER PRIMARY KEY,
-                    job_id TEXT NOT NULL,
-                    timestamp INTEGER NOT NULL,           -- Unix MILLISECONDS
-                    root_path TEXT NOT NULL,
-                    file_count INTEGER DEFAULT 0,
-                    total_size INTEGER DEFAULT 0,         -- Bytes
-                    created_at INTEGER DEFAULT (strftime('%s', 'now')),  -- Unix SECONDS
-                    UNIQUE(job_id, timestamp)
-                );
+    /// Read the index's file layout, converting a new (snapshot-free) index
+    /// to the normalized layout when that is requested
+    fn initialize_storage(&self, requested: IndexStorage) -> Result<IndexStorage> {
+        let mut conn = self
+            .conn
+            .lock()
+            .map_err(|e| AmberError::Index(format!("Failed to acquire database lock: {}", e)))?;
 
-                -- Files table: All files/directories in each snapshot
-                CREATE TABLE IF NOT EXISTS files (
-                    id INTEGER PRIMARY KEY,
-                    snapshot_id INTEGER NOT NULL,
-                    path TEXT NOT NULL,                   -- Relative path from snapshot root
-                    name TEXT NOT NULL,                   -- Filename only
-                    parent_path TEXT NOT NULL,            -- Parent directory path
-                    size INTEGER NOT NULL,                -- Bytes (0 for directories)
-                    mtime INTEGER NOT NULL,               -- Unix SECONDS (API multiplies by 1000)
-                    inode INTEGER,                        -- Unix inode for dedup detection
-                    file_type TEXT NOT NULL,              -- 'file' | 'dir' | 'symlink'
-                    FOREIGN KEY (snapshot_id) REFERENCES snapshots(id) ON DELETE CASCADE
-                );
+        let files_kind: String = conn
+            .query_row(
+                "SELECT type FROM sqlite_master WHERE name = 'files'",
+                [],
+                |row| row.get(0),
+            )
+            .map_err(|e| sql_error("Failed to read files schema", e))?;
+        if files_kind == "view" {
+            return Ok(IndexStorage::Normalized);
+        }
+        if requested == IndexStorage::Denormalized {
+            return Ok(IndexStorage::Denormalized);
+        }
 
-                -- Indexes for fast queries
-                CREATE INDEX IF NOT EXISTS idx_snapshots_job ON snapshots(job_id);
-                CREATE INDEX IF NOT EXISTS idx_files_snapshot_parent ON files(snapshot_id, parent_path);
-                CREATE INDEX IF NOT EXISTS idx_files_path ON files(snapshot_id, path);
-                CREATE INDEX IF NOT EXISTS idx_files_name ON files(name);
+        let snapshot_count: i64 = conn
+            .query_row("SELECT COUNT(*) FROM snapshots", [], |row| row.get(0))
+            .map_err(|e| sql_error("Failed to count snapshots", e))?;
+        if snapshot_count > 0 {
+            log::info!(
+                "Keeping denormalized layout for {}: it already holds snapshots",
+                self.db_path.display()
+            );
+            return Ok(IndexStorage::Denormalized);
+        }
 
-                -- Composite indexes for performance optimization (TIM-150K+)
-                -- Directory browsing with ORDER BY (avoids sorting in query)
-                CREATE INDEX IF NOT EXISTS idx_files_snapshot_parent_type_name
-                ON files(snapshot_id, parent_path, file_type DESC, name ASC);
+        let fold = Self::fts_folds_diacritics(&conn)?;
+        let tx = conn
+            .transaction()
+            .map_err(|e| sql_error("Failed to start transaction", e))?;
+        tx.execute_batch(&normalized_layout_sql(fold))
+            .map_err(|e| sql_error("Failed to create normalized layout", e))?;
+        tx.commit()
+            .map_err(|e| sql_error("Failed to commit transaction", e))?;
 
-                -- File type statistics aggregation
-                CREATE INDEX IF NOT EXISTS idx_files_snapshot_type_size
-                ON files(snapshot_id, file_type, size);
+        log::info!(
+            "Created normalized index layout: {}",
+            self.db_path.display()
+        );
+        Ok(IndexStorage::Normalized)
+    }
 
-                -- Snapshots range queries for timeline views
-                CREATE INDEX IF NOT EXISTS idx_snapshots_job_timestamp
-                ON snapshots(job_id, timestamp DESC);
+    /// File layout of this index
+    pub fn storage(&self) -> IndexStorage {
+        self.storage
+    }
 
-                -- Global search join optimization
-                CREATE INDEX IF NOT EXISTS idx_snapshots_id_job_timestamp
-                ON snapshots(id, job_id, timestamp);
+    /// Whether this index's full-text search folds diacritics
+    pub fn uses_diacritic_folding(&self) -> Result<bool> {
+        self.read(Self::fts_folds_diacritics)
+    }
 
-                -- Update version
-                PRAGMA user_version = 1;
-                "#,
+    fn fts_folds_diacritics(conn: &Connection) -> Result<bool> {
+        let sql: String = conn
+            .query_row(
+                "SELECT sql FROM sqlite_master WHERE type = 'table' AND name = 'files_fts'",
+                [],
+                |row| row.get(0),
             )
-            .map_err(|e| AmberError::Index(format!("Migration v1 failed: {}", e)))?;
-        }
-
-        if from_version < 2 {
-            // Add FTS5 for instant full-text search (TIM-101)
-            conn.execute_batch(
-                r#"
-                -- FTS5 virtual table for fast full-text search
-                -- Uses external content table (files) to avoid data duplication
-                CREATE VIRTUAL TABLE IF NOT EXISTS files_fts USING fts5(
-                    name,
-                    path,
-                    content=files,
-                    content_rowid=id,
-                    tokenize='unicode61 remove_diacritics 1'
-                );
-
-                -- Triggers to keep FTS index in sync with files table
-                CREATE TRIGGER IF NOT EXISTS files_ai AFTER INSERT ON files BEGIN
-                    INSERT INTO files_fts(rowid, name, path) VALUES (new.id, new.name, new.path);
-                END;
+            .map_err(|e| sql_error("Failed to read FTS schema", e))?;
+        // unicode61 folds unless told otherwise
+        Ok(!sql.replace(' ', "").contains("remove_diacritics0"))
+    }
 
-                CREATE TRIGGER IF NOT EXISTS files_ad AFTER DELETE ON files BEGIN
-                    INSERT INTO files_fts(files_fts, rowid, name, path) VALUES('delete', old.id, old.name, old.path);
-                END;
+    /// Whether search folds diacritics differently from the configured mode,
+    /// as found when the index was opened
+    pub fn diacritic_folding_outdated(&self) -> bool {
+        self.folding_outdated.load(Ordering::SeqCst)
+    }
 
-                CREATE TRIGGER IF NOT EXISTS files_au AFTER UPDATE ON files BEGIN
-                    INSERT INTO files_fts(files_fts, rowid, name, path) VALUES('delete', old.id, old.name, old.path);
-                    INSERT INTO files_fts(rowid, name, path) VALUES (new.id, new.name, new.path);
-                END;
+    /// `set_diacritic_folding` to the mode set by `configure_diacritic_folding`
+    pub fn apply_configured_diacritic_folding(&self) -> Result<bool> {
+        self.set_diacritic_folding(FOLD_DIACRITICS.load(Ordering::SeqCst))
+    }
 
-                -- Update version
-                PRAGMA user_version = 2;
-                "#,
-            )
-            .map_err(|e| AmberError::Index(format!("Migration v2 (FTS5) failed: {}", e)))?;
+    /// Recreate `files_fts` with or without diacritic folding and re-tokenize
+    /// every file. Returns false when the index already used that mode.
+    pub fn set_diacritic_folding(&self, fold: bool) -> Result<bool> {
+        let mut conn = self
+            .conn
+            .lock()
+            .map_err(|e| AmberError::Index(format!("Failed to acquire database lock: {}", e)))?;
 
-            // Rebuild FTS index from existing data
-            self.rebuild_fts_index(conn)?;
+        let outdated = fold != FOLD_DIACRITICS.load(Ordering::SeqCst);
+        if Self::fts_folds_diacritics(&conn)? == fold {
+            self.folding_outdated.store(outdated, Ordering::SeqCst);
+            return Ok(false);
         }
 
-        // Analyze tables to update query planner statistics
-        conn.execute_batch("ANALYZE;")
-            .map_err(|e| AmberError::Index(format!("Failed to analyze database: {}", e)))?;
-
-        Ok(())
-    }
-
-    /// Rebuild FTS index from existing files table
-    fn rebuild_fts_index(&self, conn: &Connection) -> Result<()> {
-        // This populates the FTS index from existing data
-        conn.execute_batch(
+        let tx = conn
+            .transaction()
+            .map_err(|e| sql_error("Failed to start transaction", e))?;
+        let schema = match self.storage {
+            IndexStorage::Denormalized => fts_schema_sql(fold),
+            IndexStorage::Normalized => normalized_fts_schema_sql(fold),
+        };
+        tx.execute_batch(&format!(
             r#"
+            DROP TRIGGER IF EXISTS files_ai;
+            DROP TRIGGER IF EXISTS files_ad;
+            DROP TRIGGER IF EXISTS files_au;
+            DROP TRIGGER IF EXISTS snapshot_files_ai;
+            DROP TRIGGER IF EXISTS snapshot_files_ad;
+            DROP TRIGGER IF EXISTS snapshots_bd_files;
+            DROP TABLE IF EXISTS files_fts;
+            {}
             INSERT INTO files_fts(files_fts) VALUES('rebuild');
             "#,
-        )
-        .map_err(|e| AmberError::Index(format!("Failed to rebuild FTS index: {}", e)))?;
+            schema
+        ))
+        .map_err(|e| sql_error("Failed to rebuild FTS tokenizer", e))?;
+        tx.commit()
+            .map_err(|e| sql_error("Failed to commit transaction", e))?;
+        self.folding_outdated.store(outdated, Ordering::SeqCst);
 
-        Ok(())
+        log::info!(
+            "Rebuilt search index {} diacritic folding: {}",
+            if fold { "with" } else { "without" },
+            self.db_path.display()
+        );
+        Ok(true)
     }
 
     /// Index a snapshot directory using fast parallel walking
+    ///
+    /// Re-indexing the same folder replaces its rows. A different folder at an
+    /// already indexed (job_id, timestamp) is refused, since it would silently
+    /// clobber the other snapshot; use `index_snapshot_force_replace` for that.
     pub fn index_snapshot(
         &self,
         job_id: &str,
         timestamp: i64,
         snapshot_path: &str,
     ) -> Result<IndexedSnapshot> {
+        self.index_snapshot_inner(job_id, timestamp, snapshot_path, IndexOptions::default())
+    }
+
+    /// Index a snapshot, replacing whatever folder was indexed at (job_id, timestamp)
+    pub fn index_snapshot_force_replace(
+        &self,
+        job_id: &str,
+        timestamp: i64,
+        snapshot_path: &str,
+    ) -> Result<IndexedSnapshot> {
+        index_warmup::forget(job_id, timestamp);
+        let options = IndexOptions {
+            force_replace: true,
+            ..IndexOptions::default()
+        };
+        self.index_snapshot_inner(job_id, timestamp, snapshot_path, options)
+    }
+
+    /// Index a snapshot and store a SHA-256 of every regular file, so
+    /// `compare_snapshots` also catches edits that kept a file's size. Reads
+    /// every byte of the snapshot, so it is much slower than `index_snapshot`.
+    pub fn index_snapshot_hashed(
+        &self,
+        job_id: &str,
+        timestamp: i64,
+        snapshot_path: &str,
+        force_replace: bool,
+    ) -> Result<IndexedSnapshot> {
+        if force_replace {
+            index_warmup::forget(job_id, timestamp);
+        }
+        let options = IndexOptions {
+            force_replace,
+            hash_contents: true,
+            ..IndexOptions::default()
+        };
+        self.index_snapshot_inner(job_id, timestamp, snapshot_path, options)
+    }
+
+    /// Index a snapshot as the flags say, stopping with
+    /// `AmberError::Cancelled` once `cancel` is cancelled.
+    ///
+    /// A plain index goes through `index_snapshot_resumable`: a cancelled run
+    /// keeps the batches it committed and the next call picks up after them.
+    /// Replacing a different folder or hashing contents runs as
+    /// `index_snapshot_force_replace` / `index_snapshot_hashed` in one
+    /// transaction instead, so a cancelled run rolls back and leaves any
+    /// earlier index of the snapshot in place.
+    pub fn index_snapshot_cancellable(
+        &self,
+        job_id: &str,
+        timestamp: i64,
+        snapshot_path: &str,
+        force_replace: bool,
+        hash_contents: bool,
+        cancel: &CancelToken,
+    ) -> Result<IndexedSnapshot> {
+        if !force_replace && !hash_contents {
+            return self.index_snapshot_resumable(
+                job_id,
+                timestamp,
+                snapshot_path,
+                cancel,
+                |_, _| {},
+            );
+        }
+        if force_replace {
+            index_warmup::forget(job_id, timestamp);
+        }
+        let options = IndexOptions {
+            force_replace,
+            hash_contents,
+            cancel: Some(cancel),
+            ..IndexOptions::default()
+        };
+        self.index_snapshot_inner(job_id, timestamp, snapshot_path, options)
+    }
+
+    /// Index a snapshot whose manifest entry is still to be written. It stays
+    /// pending until `mark_snapshot_committed`; a pending snapshot found later
+    /// means the process died between the two writes.
+    pub fn index_snapshot_pending(
+        &self,
+        job_id: &str,
+        timestamp: i64,
+        snapshot_path: &str,
+    ) -> Result<IndexedSnapshot> {
+        let options = IndexOptions {
+            pending: true,
+            ..IndexOptions::default()
+        };
+        self.index_snapshot_inner(job_id, timestamp, snapshot_path, options)
+    }
+
+    /// Record the current listing of `source_path` (names, sizes, mtimes) as
+    /// a metadata-only snapshot, without rsync or copying anything. It can be
+    /// browsed and compared like any snapshot but not restored from; its root
+    /// is the source itself.
+    pub fn index_metadata_snapshot(
+        &self,
+        job_id: &str,
+        timestamp: i64,
+        source_path: &str,
+    ) -> Result<IndexedSnapshot> {
+        let options = IndexOptions {
+            metadata_only: true,
+            ..IndexOptions::default()
+        };
+        self.index_snapshot_inner(job_id, timestamp, source_path, options)
+    }
+
+    fn index_snapshot_inner(
+        &self,
+        job_id: &str,
+        timestamp: i64,
+        snapshot_path: &str,
+        options: IndexOptions,
+    ) -> Result<IndexedSnapshot> {
+        let IndexOptions {
+            force_replace,
+            pending,
+            metadata_only,
+            hash_contents,
+            cancel,
+        } = options;
         let root_path = Path::new(snapshot_path);
         if !root_path.exists() {
             return Err(AmberError::Index(format!(
@@ -482,9 +1891,15 @@ impl IndexService {
                 snapshot_path
             )));
         }
+        self.ensure_not_index_dir(root_path)?;
 
         // Collect files using jwalk (parallel directory walking)
-        let files: Vec<IndexedFile> = self.walk_directory(snapshot_path)?;
+        let mut files: Vec<IndexedFile> = self.walk_directory(snapshot_path, cancel)?;
+        if hash_contents {
+            hash_file_contents(&self.walk_pool()?, &mut files);
+            check_cancel(cancel)?;
+        }
+        compute_file_flags(&mut files, &self.flag_thresholds, timestamp / 1000);
 
         // Calculate stats
         let file_count = files
@@ -501,29 +1916,402 @@ impl IndexService {
 
         let tx = conn
             .transaction()
-            .map_err(|e| AmberError::Index(format!("Failed to start transaction: {}", e)))?;
+            .map_err(|e| sql_error("Failed to start transaction", e))?;
 
-        // Delete existing snapshot if re-indexing
-        tx.execute(
-            "DELETE FROM snapshots WHERE job_id = ? AND timestamp = ?",
-            params![job_id, timestamp],
-        )
-        .map_err(|e| AmberError::Index(format!("Failed to delete existing snapshot: {}", e)))?;
+        if !force_replace {
+            let existing_root: Option<String> = tx
+                .query_row(
+                    "SELECT root_path FROM snapshots WHERE job_id = ? AND timestamp = ?",
+                    params![job_id, timestamp],
+                    |row| row.get(0),
+                )
+                .optional()
+                .map_err(|e| sql_error("Failed to query existing snapshot", e))?;
+            if let Some(existing_root) = existing_root {
+                ensure_same_root(job_id, timestamp, &existing_root, snapshot_path)?;
+            }
+        }
+
+        // Delete existing snapshot if re-indexing
+        tx.execute(
+            "DELETE FROM snapshots WHERE job_id = ? AND timestamp = ?",
+            params![job_id, timestamp],
+        )
+        .map_err(|e| sql_error("Failed to delete existing snapshot", e))?;
 
         // Insert snapshot
         tx.execute(
-            "INSERT INTO snapshots (job_id, timestamp, root_path, file_count, total_size) VALUES (?, ?, ?, ?, ?)",
-            params![job_id, timestamp, snapshot_path, file_count, total_size],
+            "INSERT INTO snapshots
+                 (job_id, timestamp, root_path, file_count, total_size, pending, metadata_only)
+             VALUES (?, ?, ?, ?, ?, ?, ?)",
+            params![
+                job_id,
+                timestamp,
+                snapshot_path,
+                file_count,
+                total_size,
+                pending,
+                metadata_only
+            ],
         )
-        .map_err(|e| AmberError::Index(format!("Failed to insert snapshot: {}", e)))?;
+        .map_err(|e| sql_error("Failed to insert snapshot", e))?;
 
         let snapshot_id = tx.last_insert_rowid();
 
         // Batch insert files
-        self.batch_insert_files(&tx, snapshot_id, &files)?;
+        match self.storage {
+            IndexStorage::Denormalized => {
+                self.batch_insert_files(&tx, snapshot_id, &files, cancel)?
+            }
+            IndexStorage::Normalized => {
+                self.batch_insert_shared_files(&tx, snapshot_id, &files, cancel)?
+            }
+        }
+        Self::insert_symlink_targets(&tx, snapshot_id, &files)?;
+        Self::insert_raw_paths(&tx, snapshot_id, &files)?;
+
+        tx.commit()
+            .map_err(|e| sql_error("Failed to commit transaction", e))?;
+
+        Ok(IndexedSnapshot {
+            id: snapshot_id,
+            job_id: job_id.to_string(),
+            timestamp,
+            root_path: snapshot_path.to_string(),
+            file_count,
+            total_size,
+            metadata_only,
+        })
+    }
+
+    /// Bring an indexed snapshot up to date with its folder, writing only the
+    /// entries that changed. Stored rows are matched to the walk by path; an
+    /// entry with the same size, mtime and type keeps its row (and its content
+    /// hash, if the snapshot was indexed with hashes), so `files` and the FTS
+    /// index only see the added, changed and removed entries, in one
+    /// transaction. Changed rows are updated in place and keep their ids;
+    /// the normalized layout relinks them instead. A snapshot that isn't
+    /// indexed yet is indexed in full, and a partial one is completed.
+    pub fn reindex_snapshot_incremental(
+        &self,
+        job_id: &str,
+        timestamp: i64,
+        snapshot_path: &str,
+    ) -> Result<IncrementalReindex> {
+        let root_path = Path::new(snapshot_path);
+        if !root_path.exists() {
+            return Err(AmberError::Index(format!(
+                "Snapshot path does not exist: {}",
+                snapshot_path
+            )));
+        }
+        self.ensure_not_index_dir(root_path)?;
+
+        let existing: Option<(i64, String, bool)> = self.read(|conn| {
+            conn.query_row(
+                "SELECT id, root_path, metadata_only FROM snapshots
+                 WHERE job_id = ? AND timestamp = ?",
+                params![job_id, timestamp],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )
+            .optional()
+            .map_err(|e| sql_error("Failed to query existing snapshot", e))
+        })?;
+        // Walk the stored root, so unchanged rows' paths come out identical
+        let root = match &existing {
+            Some((_, root, _)) => {
+                ensure_same_root(job_id, timestamp, root, snapshot_path)?;
+                root.clone()
+            }
+            None => snapshot_path.to_string(),
+        };
+
+        let mut files: Vec<IndexedFile> = self.walk_directory(&root, None)?;
+        let file_count = files
+            .iter()
+            .filter(|f| f.file_type == FileType::File)
+            .count() as i64;
+        let total_size: i64 = files.iter().map(|f| f.size).sum();
+
+        let mut conn = self
+            .conn
+            .lock()
+            .map_err(|e| AmberError::Index(format!("Failed to acquire database lock: {}", e)))?;
+        let tx = conn
+            .transaction()
+            .map_err(|e| sql_error("Failed to start transaction", e))?;
+
+        let (snapshot_id, metadata_only) = match existing {
+            Some((id, _, metadata_only)) => {
+                let updated = tx
+                    .execute(
+                        "UPDATE snapshots SET file_count = ?, total_size = ?, resume_after = NULL
+                         WHERE id = ?",
+                        params![file_count, total_size, id],
+                    )
+                    .map_err(|e| sql_error("Failed to update snapshot", e))?;
+                if updated == 0 {
+                    return Err(AmberError::Index(
+                        "Snapshot was removed from the index while re-indexing".to_string(),
+                    ));
+                }
+                (id, metadata_only)
+            }
+            None => {
+                tx.execute(
+                    "INSERT INTO snapshots (job_id, timestamp, root_path, file_count, total_size)
+                     VALUES (?, ?, ?, ?, ?)",
+                    params![job_id, timestamp, root, file_count, total_size],
+                )
+                .map_err(|e| sql_error("Failed to insert snapshot", e))?;
+                (tx.last_insert_rowid(), false)
+            }
+        };
+
+        let mut stored: HashMap<String, StoredEntry> = HashMap::new();
+        {
+            let mut stmt = tx
+                .prepare(
+                    "SELECT id, parent_path, name, size, mtime, inode, file_type, content_hash,
+                            file_flags
+                     FROM files WHERE snapshot_id = ?",
+                )
+                .map_err(|e| sql_error("Failed to prepare query", e))?;
+            let rows = stmt
+                .query_map(params![snapshot_id], |row| {
+                    let parent_path: String = row.get(1)?;
+                    let name: String = row.get(2)?;
+                    Ok((
+                        child_parent_path(&parent_path, &name),
+                        StoredEntry {
+                            id: row.get(0)?,
+                            size: row.get(3)?,
+                            mtime: row.get(4)?,
+                            inode: row.get(5)?,
+                            file_type: row.get(6)?,
+                            content_hash: row.get(7)?,
+                            flags: row.get(8)?,
+                        },
+                    ))
+                })
+                .map_err(|e| sql_error("Failed to read indexed files", e))?;
+            for row in rows {
+                let (rel_path, entry) =
+                    row.map_err(|e| sql_error("Failed to read indexed file", e))?;
+                stored.insert(rel_path, entry);
+            }
+        }
+
+        // Hashes of unchanged files carry over; only changed ones are read
+        let hashed = stored.values().any(|entry| entry.content_hash.is_some());
+        for file in files.iter_mut() {
+            let rel_path = child_parent_path(&file.parent_path, &file.name);
+            if let Some(entry) = stored.get(&rel_path) {
+                if entry.same_content(file) {
+                    file.content_hash = entry.content_hash.clone();
+                }
+            }
+        }
+        if hashed {
+            hash_file_contents(&self.walk_pool()?, &mut files);
+        }
+        compute_file_flags(&mut files, &self.flag_thresholds, timestamp / 1000);
+
+        let mut added = 0;
+        let mut unchanged = 0;
+        let mut changed: Vec<(i64, &IndexedFile)> = Vec::new();
+        let mut inserts: Vec<IndexedFile> = Vec::new();
+        for file in &files {
+            match stored.remove(&child_parent_path(&file.parent_path, &file.name)) {
+                None => {
+                    added += 1;
+                    inserts.push(file.clone());
+                }
+                Some(entry) if entry.matches(file) => unchanged += 1,
+                Some(entry) => changed.push((entry.id, file)),
+            }
+        }
+        let removed: Vec<i64> = stored.into_values().map(|entry| entry.id).collect();
+
+        // `files` is a view over `snapshot_files` in the normalized layout;
+        // removing a link there also drops its FTS row and any orphaned entry
+        let delete_sql = match self.storage {
+            IndexStorage::Denormalized => "DELETE FROM files WHERE id = ?",
+            IndexStorage::Normalized => "DELETE FROM snapshot_files WHERE id = ?",
+        };
+        let mut delete = tx
+            .prepare_cached(delete_sql)
+            .map_err(|e| sql_error("Failed to prepare delete", e))?;
+        for id in &removed {
+            delete
+                .execute(params![id])
+                .map_err(|e| sql_error("Failed to delete file", e))?;
+        }
+
+        match self.storage {
+            IndexStorage::Denormalized => {
+                let mut update = tx
+                    .prepare_cached(
+                        "UPDATE files SET size = ?, mtime = ?, inode = ?, file_type = ?,
+                                          content_hash = ?, file_flags = ?
+                         WHERE id = ?",
+                    )
+                    .map_err(|e| sql_error("Failed to prepare update", e))?;
+                for (id, file) in &changed {
+                    update
+                        .execute(params![
+                            file.size,
+                            file.mtime,
+                            file.inode,
+                            file.file_type.as_str(),
+                            file.content_hash,
+                            file.flags,
+                            id
+                        ])
+                        .map_err(|e| sql_error("Failed to update file", e))?;
+                }
+                self.batch_insert_files(&tx, snapshot_id, &inserts, None)?;
+            }
+            IndexStorage::Normalized => {
+                for (id, file) in &changed {
+                    delete
+                        .execute(params![id])
+                        .map_err(|e| sql_error("Failed to delete file", e))?;
+                    inserts.push((*file).clone());
+                }
+                self.batch_insert_shared_files(&tx, snapshot_id, &inserts, None)?;
+            }
+        }
+        drop(delete);
+
+        // Few enough to rewrite whole
+        tx.execute(
+            "DELETE FROM symlink_targets WHERE snapshot_id = ?",
+            params![snapshot_id],
+        )
+        .map_err(|e| sql_error("Failed to clear symlink targets", e))?;
+        tx.execute(
+            "DELETE FROM raw_paths WHERE snapshot_id = ?",
+            params![snapshot_id],
+        )
+        .map_err(|e| sql_error("Failed to clear raw paths", e))?;
+        Self::insert_symlink_targets(&tx, snapshot_id, &files)?;
+        Self::insert_raw_paths(&tx, snapshot_id, &files)?;
 
         tx.commit()
-            .map_err(|e| AmberError::Index(format!("Failed to commit transaction: {}", e)))?;
+            .map_err(|e| sql_error("Failed to commit transaction", e))?;
+        index_warmup::forget(job_id, timestamp);
+
+        Ok(IncrementalReindex {
+            snapshot: IndexedSnapshot {
+                id: snapshot_id,
+                job_id: job_id.to_string(),
+                timestamp,
+                root_path: root,
+                file_count,
+                total_size,
+                metadata_only,
+            },
+            added,
+            updated: changed.len(),
+            removed: removed.len(),
+            unchanged,
+        })
+    }
+
+    /// Index a snapshot in batches that are committed as they go, so a
+    /// cancelled run can pick up where it stopped. Files go in path order and
+    /// until the last batch lands the snapshot is partial: its `resume_after`
+    /// marker holds the last path inserted, and it is left out of listings and
+    /// `is_indexed`. Calling this again for the same folder skips everything
+    /// up to the marker and clears it once the rest is in.
+    ///
+    /// `cancel` is checked before each batch and stops the run with
+    /// `AmberError::Cancelled`. `on_batch` gets (entries inserted, total)
+    /// after each commit.
+    pub fn index_snapshot_resumable(
+        &self,
+        job_id: &str,
+        timestamp: i64,
+        snapshot_path: &str,
+        cancel: &CancelToken,
+        mut on_batch: impl FnMut(u64, u64),
+    ) -> Result<IndexedSnapshot> {
+        let root_path = Path::new(snapshot_path);
+        if !root_path.exists() {
+            return Err(AmberError::Index(format!(
+                "Snapshot path does not exist: {}",
+                snapshot_path
+            )));
+        }
+        self.ensure_not_index_dir(root_path)?;
+
+        let mut files: Vec<IndexedFile> = self.walk_directory(snapshot_path, Some(cancel))?;
+        compute_file_flags(&mut files, &self.flag_thresholds, timestamp / 1000);
+        files.sort_by(|a, b| a.path.cmp(&b.path));
+
+        let file_count = files
+            .iter()
+            .filter(|f| f.file_type == FileType::File)
+            .count() as i64;
+        let total_size: i64 = files.iter().map(|f| f.size).sum();
+
+        let (snapshot_id, resume_after) =
+            self.begin_resumable(job_id, timestamp, snapshot_path, file_count, total_size)?;
+        let start = resume_after.map_or(0, |last| files.partition_point(|f| f.path <= last));
+        if start > 0 {
+            log::info!(
+                "Resuming index of {} after {} of {} entries",
+                snapshot_path,
+                start,
+                files.len()
+            );
+        }
+
+        let total = files.len() as u64;
+        let mut done = start as u64;
+        for batch in files[start..].chunks(self.resume_batch_rows) {
+            cancel.check()?;
+
+            let mut conn = self.conn.lock().map_err(|e| {
+                AmberError::Index(format!("Failed to acquire database lock: {}", e))
+            })?;
+            let tx = conn
+                .transaction()
+                .map_err(|e| sql_error("Failed to start transaction", e))?;
+            match self.storage {
+                IndexStorage::Denormalized => {
+                    self.batch_insert_files(&tx, snapshot_id, batch, None)?
+                }
+                IndexStorage::Normalized => {
+                    self.batch_insert_shared_files(&tx, snapshot_id, batch, None)?
+                }
+            }
+            Self::insert_symlink_targets(&tx, snapshot_id, batch)?;
+            Self::insert_raw_paths(&tx, snapshot_id, batch)?;
+            if let Some(last) = batch.last() {
+                tx.execute(
+                    "UPDATE snapshots SET resume_after = ? WHERE id = ?",
+                    params![last.path, snapshot_id],
+                )
+                .map_err(|e| sql_error("Failed to record progress", e))?;
+            }
+            tx.commit()
+                .map_err(|e| sql_error("Failed to commit transaction", e))?;
+
+            done += batch.len() as u64;
+            on_batch(done, total);
+        }
+
+        let conn = self
+            .conn
+            .lock()
+            .map_err(|e| AmberError::Index(format!("Failed to acquire database lock: {}", e)))?;
+        conn.execute(
+            "UPDATE snapshots SET resume_after = NULL WHERE id = ?",
+            params![snapshot_id],
+        )
+        .map_err(|e| sql_error("Failed to finish snapshot", e))?;
 
         Ok(IndexedSnapshot {
             id: snapshot_id,
@@ -532,42 +2320,207 @@ impl IndexService {
             root_path: snapshot_path.to_string(),
             file_count,
             total_size,
+            metadata_only: false,
         })
     }
 
+    /// Snapshot row for a resumable index, plus the marker to resume after.
+    /// A partial row for the same folder is picked up; anything else at
+    /// (job_id, timestamp) for that folder is replaced by a fresh partial row.
+    fn begin_resumable(
+        &self,
+        job_id: &str,
+        timestamp: i64,
+        snapshot_path: &str,
+        file_count: i64,
+        total_size: i64,
+    ) -> Result<(i64, Option<String>)> {
+        let mut conn = self
+            .conn
+            .lock()
+            .map_err(|e| AmberError::Index(format!("Failed to acquire database lock: {}", e)))?;
+        let tx = conn
+            .transaction()
+            .map_err(|e| sql_error("Failed to start transaction", e))?;
+
+        let existing: Option<(i64, String, Option<String>)> = tx
+            .query_row(
+                "SELECT id, root_path, resume_after FROM snapshots WHERE job_id = ? AND timestamp = ?",
+                params![job_id, timestamp],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )
+            .optional()
+            .map_err(|e| sql_error("Failed to query existing snapshot", e))?;
+
+        let resumed = match existing {
+            Some((id, root, marker)) => {
+                ensure_same_root(job_id, timestamp, &root, snapshot_path)?;
+                marker.map(|marker| (id, marker))
+            }
+            None => None,
+        };
+
+        let begun = match resumed {
+            Some((id, marker)) => {
+                tx.execute(
+                    "UPDATE snapshots SET file_count = ?, total_size = ? WHERE id = ?",
+                    params![file_count, total_size, id],
+                )
+                .map_err(|e| sql_error("Failed to update snapshot", e))?;
+                (id, Some(marker))
+            }
+            None => {
+                tx.execute(
+                    "DELETE FROM snapshots WHERE job_id = ? AND timestamp = ?",
+                    params![job_id, timestamp],
+                )
+                .map_err(|e| sql_error("Failed to delete existing snapshot", e))?;
+                // An empty marker: partial, nothing inserted yet
+                tx.execute(
+                    "INSERT INTO snapshots (job_id, timestamp, root_path, file_count, total_size,
+                                            resume_after)
+                     VALUES (?, ?, ?, ?, ?, '')",
+                    params![job_id, timestamp, snapshot_path, file_count, total_size],
+                )
+                .map_err(|e| sql_error("Failed to insert snapshot", e))?;
+                (tx.last_insert_rowid(), None)
+            }
+        };
+
+        tx.commit()
+            .map_err(|e| sql_error("Failed to commit transaction", e))?;
+        Ok(begun)
+    }
+
+    /// Directory holding the open database
+    fn index_dir(&self) -> Option<PathBuf> {
+        if self.is_in_memory() {
+            return None;
+        }
+        self.db_path.parent().and_then(|p| p.canonicalize().ok())
+    }
+
+    /// Refuse to index the directory that holds the open database, or any
+    /// folder above it: the snapshot would take in index.db and its WAL
+    /// mid-write. Walks that aren't indexed (source listings) skip the index
+    /// dir in `walk_directory` instead.
+    fn ensure_not_index_dir(&self, root: &Path) -> Result<()> {
+        let (Some(index_dir), Ok(root)) = (self.index_dir(), root.canonicalize()) else {
+            return Ok(());
+        };
+        if index_dir.starts_with(&root) {
+            return Err(AmberError::InvalidPath(format!(
+                "Refusing to index {}: it contains the open index database",
+                root.display()
+            )));
+        }
+        Ok(())
+    }
+
+    /// The pool walks and hashing run on: this index's own, or the shared one
+    fn walk_pool(&self) -> Result<Arc<WalkPool>> {
+        match &self.walk_pool {
+            Some(pool) => Ok(pool.clone()),
+            None => walk_pool::shared(),
+        }
+    }
+
     /// Walk directory using jwalk for parallel performance
-    fn walk_directory(&self, root_path: &str) -> Result<Vec<IndexedFile>> {
+    ///
+    /// Directory reads and metadata lookups run on the walk pool, so the
+    /// number of threads touching the disk never exceeds its budget. The walk
+    /// stops with `IndexTooLarge` as soon as it finds more entries than
+    /// `max_index_files`, and with `Cancelled` once `cancel` is cancelled,
+    /// before anything is written.
+    fn walk_directory(
+        &self,
+        root_path: &str,
+        cancel: Option<&CancelToken>,
+    ) -> Result<Vec<IndexedFile>> {
         let root = Path::new(root_path);
-
-        let entries: Vec<IndexedFile> = WalkDir::new(root)
+        let pool = self.walk_pool()?;
+        let worker_pool = pool.clone();
+        let walk_cancel = cancel.cloned();
+
+        // The index dir may sit anywhere under the root (not just under a known
+        // name); map it onto the uncanonicalized root so jwalk paths compare equal
+        let index_dir = self.index_dir().and_then(|dir| {
+            let canonical_root = root.canonicalize().ok()?;
+            dir.strip_prefix(&canonical_root)
+                .ok()
+                .map(|rel| root.join(rel))
+        });
+
+        let walk = WalkDirGeneric::<((), Option<std::fs::Metadata>)>::new(root)
             .skip_hidden(false)
-            .parallelism(jwalk::Parallelism::RayonNewPool(num_cpus::get()))
+            .parallelism(pool.parallelism())
+            .process_read_dir(move |depth, _path, _state, children| {
+                // The root entry (depth None) is handled on the calling thread
+                let _worker = depth.map(|_| worker_pool.enter());
+                // Once cancelled, stop descending; the caller bails after the walk
+                if walk_cancel.as_ref().is_some_and(CancelToken::cancelled) {
+                    children.clear();
+                    return;
+                }
+                children.retain(|child| {
+                    let Ok(entry) = child else { return true };
+                    if !entry.file_type().is_dir() {
+                        return true;
+                    }
+                    let skipped_name = entry
+                        .file_name()
+                        .to_str()
+                        .is_some_and(|name| SKIPPED_DIR_NAMES.contains(&name));
+                    !skipped_name && index_dir.as_deref() != Some(entry.path().as_path())
+                });
+                for entry in children.iter_mut().flatten() {
+                    entry.client_state = entry.metadata().ok();
+                }
+            })
             .into_iter()
             .filter_map(|entry| entry.ok())
             .filter(|entry| entry.path() != root)
-            .par_bridge()
             .filter_map(|entry| {
                 let path = entry.path();
-                let metadata = entry.metadata().ok()?;
-
-                let path_str = path.to_string_lossy().to_string();
+                let metadata = entry.client_state.as_ref()?;
+
+                // Only the part below the root is normalized; the root
+                // itself is stored as given (see `stored_form`)
+                let path_str = match path.strip_prefix(root) {
+                    Ok(rel) => root
+                        .join(stored_form(&path_text(rel)).as_ref())
+                        .to_string_lossy()
+                        .to_string(),
+                    Err(_) => path_text(&path).into_owned(),
+                };
                 let name = path
                     .file_name()
-                    .map(|n| n.to_string_lossy().to_string())
+                    .map(|n| stored_form(&path_text(Path::new(n))).into_owned())
                     .unwrap_or_default();
+                // The on-disk spelling wherever the stored one differs from
+                // it: escaped, or NFC-normalized (a Linux filesystem won't
+                // open the NFC form of a decomposed name)
+                let raw_path = path.strip_prefix(root).ok().and_then(|rel| {
+                    path_bytes(rel).or_else(|| {
+                        matches!(stored_form(&path_text(rel)), Cow::Owned(_))
+                            .then(|| os_path_bytes(rel))
+                    })
+                });
 
                 // TIM-123: Use centralized make_relative utility
                 let parent_path = path
                     .parent()
-                    .map(|p| make_relative(p, root))
+                    .map(|p| stored_form(&make_relative(p, root)).into_owned())
                     .unwrap_or_default();
 
                 let file_type = if metadata.is_dir() {
                     FileType::Directory
                 } else if metadata.is_symlink() {
                     FileType::Symlink
-                } else {
+                } else if metadata.is_file() {
                     FileType::File
+                } else {
+                    FileType::Special
                 };
 
                 let mtime = metadata
@@ -586,51 +2539,237 @@ impl IndexService {
                 #[cfg(not(unix))]
                 let inode = None;
 
+                let link_target = (file_type == FileType::Symlink)
+                    .then(|| std::fs::read_link(&path).ok())
+                    .flatten()
+                    .map(|target| target.to_string_lossy().to_string());
+
                 Some(IndexedFile {
                     path: path_str,
                     name,
                     parent_path,
-                    size: metadata.len() as i64,
+                    // A device's length is not content restore could write
+                    size: if file_type == FileType::Special {
+                        0
+                    } else {
+                        metadata.len() as i64
+                    },
                     mtime,
                     inode,
                     file_type,
+                    content_hash: None,
+                    flags: 0,
+                    link_target,
+                    raw_path,
                 })
-            })
-            .collect();
+            });
+
+        let mut entries = Vec::new();
+        for entry in walk {
+            entries.push(entry);
+            if self.max_index_files > 0 && entries.len() as u64 > self.max_index_files {
+                log::warn!(
+                    "Stopped indexing {}: more than {} entries",
+                    root_path,
+                    self.max_index_files
+                );
+                return Err(AmberError::IndexTooLarge {
+                    path: root_path.to_string(),
+                    limit: self.max_index_files,
+                });
+            }
+        }
+        check_cancel(cancel)?;
 
         Ok(entries)
     }
 
     /// Batch insert files for performance
+    ///
+    /// Uses one multi-row `INSERT ... VALUES (...), (...)` per chunk instead of
+    /// a statement per file. The FTS triggers are row-level, so they still fire
+    /// once per inserted file.
     fn batch_insert_files(
         &self,
         tx: &Transaction,
         snapshot_id: i64,
         files: &[IndexedFile],
+        cancel: Option<&CancelToken>,
+    ) -> Result<()> {
+        for chunk in files.chunks(ROWS_PER_INSERT) {
+            check_cancel(cancel)?;
+            // Every full chunk reuses the same cached statement; only the tail differs
+            let row = format!("({})", ["?"; FILE_INSERT_COLUMNS].join(", "));
+            let placeholders = vec![row.as_str(); chunk.len()].join(", ");
+            let sql = format!(
+                "INSERT INTO files (snapshot_id, path, name, parent_path, size, mtime, inode, file_type,
+                                    content_hash, file_flags)
+                 VALUES {}",
+                placeholders
+            );
+            let mut stmt = tx
+                .prepare_cached(&sql)
+                .map_err(|e| sql_error("Failed to prepare insert statement", e))?;
+
+            let file_types: Vec<&str> = chunk.iter().map(|f| f.file_type.as_str()).collect();
+            let mut values: Vec<&dyn rusqlite::ToSql> =
+                Vec::with_capacity(chunk.len() * FILE_INSERT_COLUMNS);
+            for (file, file_type) in chunk.iter().zip(&file_types) {
+                values.push(&snapshot_id);
+                values.push(&file.path);
+                values.push(&file.name);
+                values.push(&file.parent_path);
+                values.push(&file.size);
+                values.push(&file.mtime);
+                values.push(&file.inode);
+                values.push(file_type);
+                values.push(&file.content_hash);
+                values.push(&file.flags);
+            }
+
+            stmt.execute(values.as_slice())
+                .map_err(|e| sql_error("Failed to insert files", e))?;
+        }
+
+        Ok(())
+    }
+
+    /// Normalized counterpart of `batch_insert_files`
+    ///
+    /// Entries are staged in a temp table, added to `file_rows` unless an
+    /// identical row (every column but the id) already exists, and linked to
+    /// the snapshot in walk order. An entry unchanged since an earlier
+    /// snapshot therefore costs one `snapshot_files` row instead of a full
+    /// `files` row.
+    fn batch_insert_shared_files(
+        &self,
+        tx: &Transaction,
+        snapshot_id: i64,
+        files: &[IndexedFile],
+        cancel: Option<&CancelToken>,
+    ) -> Result<()> {
+        tx.execute_batch(
+            "CREATE TEMP TABLE IF NOT EXISTS staged_files (
+                 name TEXT, parent_path TEXT, size INTEGER, mtime INTEGER, inode INTEGER,
+                 file_type TEXT, content_hash TEXT, file_flags INTEGER
+             );
+             DELETE FROM temp.staged_files;",
+        )
+        .map_err(|e| sql_error("Failed to stage files", e))?;
+
+        // Fewer columns than a `files` row, so ROWS_PER_INSERT stays in bounds
+        for chunk in files.chunks(ROWS_PER_INSERT) {
+            check_cancel(cancel)?;
+            let row = format!("({})", ["?"; STAGED_INSERT_COLUMNS].join(", "));
+            let placeholders = vec![row.as_str(); chunk.len()].join(", ");
+            let sql = format!(
+                "INSERT INTO temp.staged_files (name, parent_path, size, mtime, inode, file_type,
+                                                content_hash, file_flags)
+                 VALUES {}",
+                placeholders
+            );
+            let mut stmt = tx
+                .prepare_cached(&sql)
+                .map_err(|e| sql_error("Failed to prepare insert statement", e))?;
+
+            let file_types: Vec<&str> = chunk.iter().map(|f| f.file_type.as_str()).collect();
+            let mut values: Vec<&dyn rusqlite::ToSql> =
+                Vec::with_capacity(chunk.len() * STAGED_INSERT_COLUMNS);
+            for (file, file_type) in chunk.iter().zip(&file_types) {
+                values.push(&file.name);
+                values.push(&file.parent_path);
+                values.push(&file.size);
+                values.push(&file.mtime);
+                values.push(&file.inode);
+                values.push(file_type);
+                values.push(&file.content_hash);
+                values.push(&file.flags);
+            }
+
+            stmt.execute(values.as_slice())
+                .map_err(|e| sql_error("Failed to stage files", e))?;
+        }
+
+        tx.execute(
+            "INSERT OR IGNORE INTO file_rows (name, parent_path, size, mtime, inode, file_type,
+                                              content_hash, file_flags)
+             SELECT name, parent_path, size, mtime, inode, file_type, content_hash, file_flags
+             FROM temp.staged_files ORDER BY rowid",
+            [],
+        )
+        .map_err(|e| sql_error("Failed to insert file rows", e))?;
+
+        tx.execute(
+            "INSERT INTO snapshot_files (snapshot_id, file_row_id)
+             SELECT ?1, r.id
+             FROM temp.staged_files s
+             JOIN file_rows r
+               ON r.parent_path = s.parent_path AND r.name = s.name
+              AND r.size = s.size AND r.mtime = s.mtime
+              AND IFNULL(r.inode, -1) = IFNULL(s.inode, -1)
+              AND r.file_type = s.file_type
+              AND IFNULL(r.content_hash, '') = IFNULL(s.content_hash, '')
+              AND r.file_flags = s.file_flags
+             ORDER BY s.rowid",
+            params![snapshot_id],
+        )
+        .map_err(|e| sql_error("Failed to link snapshot files", e))?;
+
+        tx.execute("DELETE FROM temp.staged_files", [])
+            .map_err(|e| sql_error("Failed to clear staged files", e))?;
+        Ok(())
+    }
+
+    /// Record where the symlinks among `files` point, keyed by their path
+    /// relative to the snapshot root. Kept outside `files` so both storage
+    /// layouts share it.
+    fn insert_symlink_targets(
+        tx: &Transaction,
+        snapshot_id: i64,
+        files: &[IndexedFile],
     ) -> Result<()> {
         let mut stmt = tx
-            .prepare(
-                "INSERT INTO files (snapshot_id, path, name, parent_path, size, mtime, inode, file_type)
-                 VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+            .prepare_cached(
+                "INSERT OR REPLACE INTO symlink_targets (snapshot_id, path, target)
+                 VALUES (?1, ?2, ?3)",
             )
-            .map_err(|e| AmberError::Index(format!("Failed to prepare insert statement: {}", e)))?;
-
-        for chunk in files.chunks(BATCH_SIZE) {
-            for file in chunk {
-                stmt.execute(params![
-                    snapshot_id,
-                    file.path,
-                    file.name,
-                    file.parent_path,
-                    file.size,
-                    file.mtime,
-                    file.inode,
-                    file.file_type.as_str(),
-                ])
-                .map_err(|e| AmberError::Index(format!("Failed to insert file: {}", e)))?;
-            }
+            .map_err(|e| sql_error("Failed to prepare symlink target insert", e))?;
+        for file in files {
+            let Some(target) = &file.link_target else {
+                continue;
+            };
+            let rel_path = if file.parent_path.is_empty() {
+                file.name.clone()
+            } else {
+                format!("{}/{}", file.parent_path, file.name)
+            };
+            stmt.execute(params![snapshot_id, rel_path, target])
+                .map_err(|e| sql_error("Failed to insert symlink target", e))?;
         }
+        Ok(())
+    }
 
+    /// Record the exact bytes of the paths among `files` that `path_text`
+    /// escaped or `stored_form` normalized, keyed by their stored path
+    /// relative to the snapshot root
+    fn insert_raw_paths(tx: &Transaction, snapshot_id: i64, files: &[IndexedFile]) -> Result<()> {
+        let mut stmt = tx
+            .prepare_cached(
+                "INSERT OR REPLACE INTO raw_paths (snapshot_id, path, raw) VALUES (?1, ?2, ?3)",
+            )
+            .map_err(|e| sql_error("Failed to prepare raw path insert", e))?;
+        for file in files {
+            let Some(raw) = &file.raw_path else {
+                continue;
+            };
+            let rel_path = if file.parent_path.is_empty() {
+                file.name.clone()
+            } else {
+                format!("{}/{}", file.parent_path, file.name)
+            };
+            stmt.execute(params![snapshot_id, rel_path, raw])
+                .map_err(|e| sql_error("Failed to insert raw path", e))?;
+        }
         Ok(())
     }
 
@@ -643,12 +2782,115 @@ impl IndexService {
         parent_path: &str,
     ) -> Result<Vec<FileNode>> {
         // Call paginated version with no limits
-        let contents =
-            self.get_directory_contents_paginated(job_id, timestamp, parent_path, None, None)?;
+        let contents = self.get_directory_contents_paginated(
+            job_id,
+            timestamp,
+            parent_path,
+            None,
+            None,
+            None,
+            None,
+        )?;
         Ok(contents.files)
     }
 
-    /// Get files in a directory with pagination support
+    /// How many files of the snapshot share each of `inodes`, for those
+    /// shared by more than one
+    fn hardlink_counts(
+        conn: &Connection,
+        snapshot_id: i64,
+        inodes: &[i64],
+    ) -> Result<HashMap<i64, u32>> {
+        let mut counts = HashMap::new();
+        if inodes.is_empty() {
+            return Ok(counts);
+        }
+
+        // One page of a listing, so well under SQLite's variable limit
+        let placeholders = vec!["?"; inodes.len()].join(", ");
+        let mut stmt = conn
+            .prepare(&format!(
+                "SELECT inode, COUNT(*) FROM files
+                 WHERE snapshot_id = ? AND file_type = 'file' AND inode IN ({})
+                 GROUP BY inode HAVING COUNT(*) > 1",
+                placeholders
+            ))
+            .map_err(|e| sql_error("Failed to prepare query", e))?;
+
+        let mut values: Vec<&dyn rusqlite::ToSql> = vec![&snapshot_id];
+        values.extend(inodes.iter().map(|i| i as &dyn rusqlite::ToSql));
+        let rows = stmt
+            .query_map(values.as_slice(), |row| {
+                Ok((row.get::<_, i64>(0)?, row.get::<_, u32>(1)?))
+            })
+            .map_err(|e| sql_error("Failed to count hard links", e))?;
+        for row in rows {
+            let (inode, count) = row.map_err(|e| sql_error("Failed to count hard links", e))?;
+            counts.insert(inode, count);
+        }
+        Ok(counts)
+    }
+
+    /// Files of a snapshot that share an inode with another file in it, i.e.
+    /// hard links, largest first. Inodes are only unique per filesystem, so a
+    /// snapshot spanning mounts (`cross_filesystems`) can group unrelated files.
+    pub fn get_hardlink_groups(&self, job_id: &str, timestamp: i64) -> Result<Vec<HardlinkGroup>> {
+        self.read(|conn| {
+            let snapshot_id: i64 = conn
+                .query_row(
+                    "SELECT id FROM snapshots WHERE job_id = ? AND timestamp = ?",
+                    params![job_id, timestamp],
+                    |row| row.get(0),
+                )
+                .map_err(snapshot_lookup_error)?;
+
+            let mut stmt = conn
+                .prepare(
+                    "SELECT f.inode, f.size, f.path
+                 FROM files f
+                 JOIN (
+                     SELECT inode FROM files
+                     WHERE snapshot_id = ?1 AND file_type = 'file' AND inode IS NOT NULL
+                     GROUP BY inode HAVING COUNT(*) > 1
+                 ) linked ON f.inode = linked.inode
+                 WHERE f.snapshot_id = ?1 AND f.file_type = 'file'
+                 ORDER BY f.size DESC, f.inode, f.path",
+                )
+                .map_err(|e| sql_error("Failed to prepare query", e))?;
+
+            let rows = stmt
+                .query_map(params![snapshot_id], |row| {
+                    Ok((
+                        row.get::<_, i64>(0)?,
+                        row.get::<_, i64>(1)?,
+                        row.get::<_, String>(2)?,
+                    ))
+                })
+                .map_err(|e| sql_error("Failed to query hard links", e))?;
+
+            let mut groups: Vec<HardlinkGroup> = Vec::new();
+            for row in rows {
+                let (inode, size, path) =
+                    row.map_err(|e| sql_error("Failed to query hard links", e))?;
+                match groups.last_mut() {
+                    Some(group) if group.inode == inode => group.paths.push(path),
+                    _ => groups.push(HardlinkGroup {
+                        inode,
+                        size,
+                        paths: vec![path],
+                    }),
+                }
+            }
+            Ok(groups)
+        })
+    }
+
+    /// Get files in a directory with pagination support.
+    ///
+    /// `modified_after` (Unix ms) drops files not modified after it and
+    /// `type_filter` files outside the category; directories are always
+    /// listed so the tree stays navigable.
+    #[allow(clippy::too_many_arguments)]
     pub fn get_directory_contents_paginated(
         &self,
         job_id: &str,
@@ -656,113 +2898,346 @@ impl IndexService {
         parent_path: &str,
         limit: Option<usize>,
         offset: Option<usize>,
+        modified_after: Option<i64>,
+        type_filter: Option<FileCategory>,
     ) -> Result<DirectoryContents> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|e| AmberError::Index(format!("Failed to acquire database lock: {}", e)))?;
-
-        // Get snapshot ID
-        let snapshot_id: i64 = conn
-            .query_row(
-                "SELECT id FROM snapshots WHERE job_id = ? AND timestamp = ?",
-                params![job_id, timestamp],
-                |row| row.get(0),
-            )
-            .map_err(|_| AmberError::Index("Snapshot not found in index".to_string()))?;
+        self.directory_page(
+            job_id,
+            timestamp,
+            parent_path,
+            "file_type DESC, name ASC",
+            limit,
+            offset,
+            modified_after,
+            type_filter,
+        )
+    }
 
-        // Get total count for pagination metadata
-        let total_count: i64 = conn
-            .query_row(
-                "SELECT COUNT(*) FROM files WHERE snapshot_id = ? AND parent_path = ?",
-                params![snapshot_id, parent_path],
-                |row| row.get(0),
-            )
-            .map_err(|e| AmberError::Index(format!("Failed to count files: {}", e)))?;
+    /// One page of a directory in `sort` order, with the directory's total
+    /// entry count for the scrollbar. Sorting and paging happen in SQL, so
+    /// a page of a huge directory costs about the same as a small one.
+    pub fn get_directory_contents_paged(
+        &self,
+        job_id: &str,
+        timestamp: i64,
+        parent_path: &str,
+        sort: SortBy,
+        offset: usize,
+        limit: usize,
+    ) -> Result<DirectoryContents> {
+        self.directory_page(
+            job_id,
+            timestamp,
+            parent_path,
+            &sort.order_sql(),
+            Some(limit),
+            Some(offset),
+            None,
+            None,
+        )
+    }
+
+    /// `order_by` is spliced into the query and must be trusted SQL
+    #[allow(clippy::too_many_arguments)]
+    fn directory_page(
+        &self,
+        job_id: &str,
+        timestamp: i64,
+        parent_path: &str,
+        order_by: &str,
+        limit: Option<usize>,
+        offset: Option<usize>,
+        modified_after: Option<i64>,
+        type_filter: Option<FileCategory>,
+    ) -> Result<DirectoryContents> {
+        self.read(|conn| {
+            // Get snapshot ID
+            let snapshot_id: i64 = conn
+                .query_row(
+                    "SELECT id FROM snapshots WHERE job_id = ? AND timestamp = ?",
+                    params![job_id, timestamp],
+                    |row| row.get(0),
+                )
+                .map_err(snapshot_lookup_error)?;
 
-        // Get files in directory with pagination
-        let limit_val = limit.unwrap_or(500);
-        let offset_val = offset.unwrap_or(0);
+            let mtime_cutoff = mtime_cutoff_secs(modified_after);
+            let in_category = category_condition(type_filter);
 
-        let mut stmt = conn
-            .prepare(
-                "SELECT path, name, size, mtime, file_type
+            // Get total count for pagination metadata
+            let total_count: i64 = conn
+                .query_row(
+                    &format!(
+                        "SELECT COUNT(*) FROM files
+                 WHERE snapshot_id = ?1 AND parent_path = ?2
+                   AND (?3 IS NULL OR file_type = 'dir' OR mtime > ?3) AND {}",
+                        in_category
+                    ),
+                    params![snapshot_id, parent_path, mtime_cutoff],
+                    |row| row.get(0),
+                )
+                .map_err(|e| sql_error("Failed to count files", e))?;
+
+            // Get files in directory with pagination
+            let limit_val = limit.unwrap_or(500);
+            let offset_val = offset.unwrap_or(0);
+
+            let mut stmt = conn
+                .prepare(&format!(
+                    "SELECT path, name, size, mtime, file_type, inode
                  FROM files
-                 WHERE snapshot_id = ? AND parent_path = ?
-                 ORDER BY file_type DESC, name ASC
-                 LIMIT ? OFFSET ?",
-            )
-            .map_err(|e| AmberError::Index(format!("Failed to prepare query: {}", e)))?;
+                 WHERE snapshot_id = ?1 AND parent_path = ?2
+                   AND (?5 IS NULL OR file_type = 'dir' OR mtime > ?5) AND {}
+                 ORDER BY {}
+                 LIMIT ?3 OFFSET ?4",
+                    in_category, order_by
+                ))
+                .map_err(|e| sql_error("Failed to prepare query", e))?;
+
+            let files = stmt
+                .query_map(
+                    params![
+                        snapshot_id,
+                        parent_path,
+                        limit_val as i64,
+                        offset_val as i64,
+                        mtime_cutoff
+                    ],
+                    |row| {
+                        let kind = row.get::<_, String>(4)?;
+                        let inode = if kind == file_type::FILE {
+                            row.get::<_, Option<i64>>(5)?
+                        } else {
+                            None
+                        };
+                        Ok((
+                            FileNode::from_db_row(
+                                row.get(0)?,
+                                row.get(1)?,
+                                row.get(2)?,
+                                row.get(3)?,
+                                &kind,
+                            ),
+                            inode,
+                        ))
+                    },
+                )
+                .map_err(|e| sql_error("Failed to query files", e))?;
+
+            let rows: Vec<(FileNode, Option<i64>)> = files
+                .collect::<rusqlite::Result<_>>()
+                .map_err(|e| sql_error("Failed to query files", e))?;
+            let inodes: Vec<i64> = rows.iter().filter_map(|(_, inode)| *inode).collect();
+            let link_counts = Self::hardlink_counts(&conn, snapshot_id, &inodes)?;
+            let result: Vec<FileNode> = rows
+                .into_iter()
+                .map(|(mut file, inode)| {
+                    file.link_count = inode.and_then(|i| link_counts.get(&i).copied());
+                    file
+                })
+                .collect();
 
-        let files = stmt
-            .query_map(
-                params![
-                    snapshot_id,
-                    parent_path,
-                    limit_val as i64,
-                    offset_val as i64
-                ],
-                |row| {
-                    Ok(FileNode::from_db_row(
-                        row.get(0)?,
-                        row.get(1)?,
-                        row.get(2)?,
-                        row.get(3)?,
-                        &row.get::<_, String>(4)?,
-                    ))
-                },
-            )
-            .map_err(|e| AmberError::Index(format!("Failed to query files: {}", e)))?;
+            let has_more = offset_val + result.len() < total_count as usize;
 
-        let mut result = Vec::new();
-        for f in files.flatten() {
-            result.push(f);
-        }
+            Ok(DirectoryContents {
+                files: result,
+                total_count: total_count as usize,
+                has_more,
+            })
+        })
+    }
+
+    /// Descendants of `parent_path` down to `depth` levels, nested, so the UI
+    /// can prefetch a few levels and expand the rest lazily. Depth 1 is the
+    /// same listing as `get_directory_contents`. Levels are filled breadth
+    /// first; once `max_nodes` (at most `SUBTREE_NODE_LIMIT`) would be
+    /// exceeded, the remaining directories are left unexpanded.
+    pub fn get_subtree(
+        &self,
+        job_id: &str,
+        timestamp: i64,
+        parent_path: &str,
+        depth: usize,
+        max_nodes: Option<usize>,
+    ) -> Result<Subtree> {
+        self.read(|conn| {
+            let snapshot_id: i64 = conn
+                .query_row(
+                    "SELECT id FROM snapshots WHERE job_id = ? AND timestamp = ?",
+                    params![job_id, timestamp],
+                    |row| row.get(0),
+                )
+                .map_err(snapshot_lookup_error)?;
+
+            let mut stmt = conn
+                .prepare(
+                    "SELECT path, name, size, mtime, file_type, inode
+                 FROM files
+                 WHERE snapshot_id = ?1 AND parent_path = ?2
+                 ORDER BY file_type DESC, name ASC
+                 LIMIT ?3",
+                )
+                .map_err(|e| sql_error("Failed to prepare query", e))?;
+
+            let mut remaining = max_nodes
+                .unwrap_or(SUBTREE_NODE_LIMIT)
+                .min(SUBTREE_NODE_LIMIT);
+            let mut truncated = false;
+            // Children of every expanded directory, keyed by its parent_path form
+            let mut listed: HashMap<String, Vec<FileNode>> = HashMap::new();
+            let mut frontier = vec![parent_path.to_string()];
+
+            'levels: for _ in 0..depth.max(1) {
+                let mut next = Vec::new();
+                for dir in frontier {
+                    // One extra row tells whether the directory fits
+                    let rows = stmt
+                        .query_map(params![snapshot_id, dir, (remaining + 1) as i64], |row| {
+                            let kind = row.get::<_, String>(4)?;
+                            let inode = if kind == file_type::FILE {
+                                row.get::<_, Option<i64>>(5)?
+                            } else {
+                                None
+                            };
+                            Ok((
+                                FileNode::from_db_row(
+                                    row.get(0)?,
+                                    row.get(1)?,
+                                    row.get(2)?,
+                                    row.get(3)?,
+                                    &kind,
+                                ),
+                                inode,
+                            ))
+                        })
+                        .map_err(|e| sql_error("Failed to query files", e))?;
+                    let mut rows: Vec<(FileNode, Option<i64>)> = rows
+                        .collect::<rusqlite::Result<_>>()
+                        .map_err(|e| sql_error("Failed to query files", e))?;
+
+                    if rows.len() > remaining {
+                        truncated = true;
+                        // The requested directory itself still gets a partial listing
+                        if dir != parent_path {
+                            break 'levels;
+                        }
+                        rows.truncate(remaining);
+                    }
+                    remaining -= rows.len();
+
+                    let inodes: Vec<i64> = rows.iter().filter_map(|(_, inode)| *inode).collect();
+                    let link_counts = Self::hardlink_counts(conn, snapshot_id, &inodes)?;
+                    let children: Vec<FileNode> = rows
+                        .into_iter()
+                        .map(|(mut file, inode)| {
+                            file.link_count = inode.and_then(|i| link_counts.get(&i).copied());
+                            file
+                        })
+                        .collect();
+                    next.extend(
+                        children
+                            .iter()
+                            .filter(|c| c.node_type == file_type::DIR)
+                            .map(|c| child_parent_path(&dir, &c.name)),
+                    );
+                    listed.insert(dir, children);
+                    if truncated {
+                        break 'levels;
+                    }
+                }
+                frontier = next;
+            }
+
+            let node_count = listed.values().map(Vec::len).sum();
+            Ok(Subtree {
+                nodes: nest_listed(&mut listed, parent_path),
+                node_count,
+                truncated,
+            })
+        })
+    }
 
-        let has_more = offset_val + result.len() < total_count as usize;
+    /// Breadcrumbs from the snapshot root down to `parent_path`. The root crumb
+    /// is named after the snapshot folder.
+    pub fn resolve_breadcrumbs(
+        &self,
+        job_id: &str,
+        timestamp: i64,
+        parent_path: &str,
+    ) -> Result<Vec<Crumb>> {
+        self.read(|conn| {
+            let root_path: String = conn
+                .query_row(
+                    "SELECT root_path FROM snapshots WHERE job_id = ? AND timestamp = ?",
+                    params![job_id, timestamp],
+                    |row| row.get(0),
+                )
+                .map_err(snapshot_lookup_error)?;
 
-        Ok(DirectoryContents {
-            files: result,
-            total_count: total_count as usize,
-            has_more,
+            let root_name = Path::new(&root_path)
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or(root_path);
+            breadcrumbs_for(&root_name, parent_path)
         })
     }
 
     /// List all indexed snapshots for a job
     pub fn list_snapshots(&self, job_id: &str) -> Result<Vec<IndexedSnapshot>> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|e| AmberError::Index(format!("Failed to acquire database lock: {}", e)))?;
-
-        let mut stmt = conn
-            .prepare(
-                "SELECT id, job_id, timestamp, root_path, file_count, total_size
+        self.read(|conn| {
+            let mut stmt = conn
+                .prepare(
+                    "SELECT id, job_id, timestamp, root_path, file_count, total_size, metadata_only
                  FROM snapshots
-                 WHERE job_id = ?
+                 WHERE job_id = ? AND resume_after IS NULL
                  ORDER BY timestamp DESC",
-            )
-            .map_err(|e| AmberError::Index(format!("Failed to prepare query: {}", e)))?;
-
-        let snapshots = stmt
-            .query_map(params![job_id], |row| {
-                Ok(IndexedSnapshot {
-                    id: row.get(0)?,
-                    job_id: row.get(1)?,
-                    timestamp: row.get(2)?,
-                    root_path: row.get(3)?,
-                    file_count: row.get(4)?,
-                    total_size: row.get(5)?,
+                )
+                .map_err(|e| sql_error("Failed to prepare query", e))?;
+
+            let snapshots = stmt
+                .query_map(params![job_id], |row| {
+                    Ok(IndexedSnapshot {
+                        id: row.get(0)?,
+                        job_id: row.get(1)?,
+                        timestamp: row.get(2)?,
+                        root_path: row.get(3)?,
+                        file_count: row.get(4)?,
+                        total_size: row.get(5)?,
+                        metadata_only: row.get(6)?,
+                    })
                 })
-            })
-            .map_err(|e| AmberError::Index(format!("Failed to query snapshots: {}", e)))?;
+                .map_err(|e| sql_error("Failed to query snapshots", e))?;
 
-        let mut result = Vec::new();
-        for s in snapshots.flatten() {
-            result.push(s);
-        }
+            snapshots
+                .collect::<rusqlite::Result<Vec<_>>>()
+                .map_err(|e| sql_error("Failed to query snapshots", e))
+        })
+    }
 
-        Ok(result)
+    /// The most recent complete snapshot of any job in this index
+    pub fn latest_snapshot(&self) -> Result<Option<IndexedSnapshot>> {
+        self.read(|conn| {
+            conn.query_row(
+                "SELECT id, job_id, timestamp, root_path, file_count, total_size, metadata_only
+             FROM snapshots
+             WHERE resume_after IS NULL
+             ORDER BY timestamp DESC
+             LIMIT 1",
+                [],
+                |row| {
+                    Ok(IndexedSnapshot {
+                        id: row.get(0)?,
+                        job_id: row.get(1)?,
+                        timestamp: row.get(2)?,
+                        root_path: row.get(3)?,
+                        file_count: row.get(4)?,
+                        total_size: row.get(5)?,
+                        metadata_only: row.get(6)?,
+                    })
+                },
+            )
+            .optional()
+            .map_err(|e| sql_error("Failed to query latest snapshot", e))
+        })
     }
 
     /// List snapshots within a date range (for filtering UI)
@@ -773,106 +3248,142 @@ impl IndexService {
         start_ms: i64,
         end_ms: i64,
     ) -> Result<Vec<IndexedSnapshot>> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|e| AmberError::Index(format!("Failed to acquire database lock: {}", e)))?;
-
-        let mut stmt = conn
-            .prepare(
-                "SELECT id, job_id, timestamp, root_path, file_count, total_size
+        self.read(|conn| {
+            let mut stmt = conn
+                .prepare(
+                    "SELECT id, job_id, timestamp, root_path, file_count, total_size, metadata_only
                  FROM snapshots
                  WHERE job_id = ? AND timestamp >= ? AND timestamp <= ?
+                   AND resume_after IS NULL
                  ORDER BY timestamp DESC",
-            )
-            .map_err(|e| AmberError::Index(format!("Failed to prepare query: {}", e)))?;
-
-        let snapshots = stmt
-            .query_map(params![job_id, start_ms, end_ms], |row| {
-                Ok(IndexedSnapshot {
-                    id: row.get(0)?,
-                    job_id: row.get(1)?,
-                    timestamp: row.get(2)?,
-                    root_path: row.get(3)?,
-                    file_count: row.get(4)?,
-                    total_size: row.get(5)?,
+                )
+                .map_err(|e| sql_error("Failed to prepare query", e))?;
+
+            let snapshots = stmt
+                .query_map(params![job_id, start_ms, end_ms], |row| {
+                    Ok(IndexedSnapshot {
+                        id: row.get(0)?,
+                        job_id: row.get(1)?,
+                        timestamp: row.get(2)?,
+                        root_path: row.get(3)?,
+                        file_count: row.get(4)?,
+                        total_size: row.get(5)?,
+                        metadata_only: row.get(6)?,
+                    })
                 })
-            })
-            .map_err(|e| AmberError::Index(format!("Failed to query snapshots: {}", e)))?;
-
-        let mut result = Vec::new();
-        for s in snapshots.flatten() {
-            result.push(s);
-        }
+                .map_err(|e| sql_error("Failed to query snapshots", e))?;
 
-        Ok(result)
+            snapshots
+                .collect::<rusqlite::Result<Vec<_>>>()
+                .map_err(|e| sql_error("Failed to query snapshots", e))
+        })
     }
 
     /// Get aggregate statistics for all snapshots of a job (TIM-127)
     pub fn get_job_aggregate_stats(&self, job_id: &str) -> Result<JobAggregateStats> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|e| AmberError::Index(format!("Failed to acquire database lock: {}", e)))?;
-
-        // Get aggregate stats from snapshots table in a single query
-        let result = conn
-            .query_row(
-                "SELECT
+        self.read(|conn| {
+            // Get aggregate stats from snapshots table in a single query
+            let result = conn
+                .query_row(
+                    "SELECT
                     COUNT(*) as total_snapshots,
                     COALESCE(SUM(total_size), 0) as total_size,
                     COALESCE(SUM(file_count), 0) as total_files,
                     MIN(timestamp) as first_snapshot,
                     MAX(timestamp) as last_snapshot
                  FROM snapshots
-                 WHERE job_id = ?",
-                params![job_id],
-                |row| {
-                    let total_snapshots: i64 = row.get(0)?;
-                    Ok(JobAggregateStats {
-                        total_snapshots,
-                        total_size_bytes: row.get(1)?,
-                        total_files: row.get(2)?,
-                        // Only set timestamps if there are snapshots
-                        first_snapshot_ms: if total_snapshots > 0 {
-                            row.get(3)?
-                        } else {
-                            None
-                        },
-                        last_snapshot_ms: if total_snapshots > 0 {
-                            row.get(4)?
-                        } else {
-                            None
-                        },
-                    })
-                },
-            )
-            .map_err(|e| AmberError::Index(format!("Failed to query aggregate stats: {}", e)))?;
+                 WHERE job_id = ? AND resume_after IS NULL",
+                    params![job_id],
+                    |row| {
+                        let total_snapshots: i64 = row.get(0)?;
+                        Ok(JobAggregateStats {
+                            total_snapshots,
+                            total_size_bytes: row.get(1)?,
+                            total_files: row.get(2)?,
+                            // Only set timestamps if there are snapshots
+                            first_snapshot_ms: if total_snapshots > 0 {
+                                row.get(3)?
+                            } else {
+                                None
+                            },
+                            last_snapshot_ms: if total_snapshots > 0 {
+                                row.get(4)?
+                            } else {
+                                None
+                            },
+                        })
+                    },
+                )
+                .map_err(|e| sql_error("Failed to query aggregate stats", e))?;
 
-        Ok(result)
+            Ok(result)
+        })
+    }
+
+    /// Estimate how much distinct file content a job's snapshots hold.
+    ///
+    /// Files with an inode are counted once per inode, which is exact for
+    /// `--link-dest` snapshots on one volume. Files without one (non-Unix
+    /// indexes) are counted once per distinct (relative path, size), so a
+    /// file that moved, or was edited without changing size, is misjudged.
+    /// Only regular files count; directory and symlink entries are ignored.
+    pub fn get_job_logical_footprint(&self, job_id: &str) -> Result<JobLogicalFootprint> {
+        self.read(|conn| {
+            let (unique_size_bytes, unique_files) = conn
+                .query_row(
+                    r#"
+                SELECT COALESCE(SUM(size), 0), COUNT(*) FROM (
+                    SELECT MAX(f.size) AS size
+                    FROM files f
+                    JOIN snapshots s ON f.snapshot_id = s.id
+                    WHERE s.job_id = ?1 AND f.file_type = 'file' AND f.inode IS NOT NULL
+                    GROUP BY f.inode
+                    UNION ALL
+                    SELECT f.size AS size
+                    FROM files f
+                    JOIN snapshots s ON f.snapshot_id = s.id
+                    WHERE s.job_id = ?1 AND f.file_type = 'file' AND f.inode IS NULL
+                    GROUP BY f.parent_path, f.name, f.size
+                )
+                "#,
+                    params![job_id],
+                    |row| Ok((row.get(0)?, row.get(1)?)),
+                )
+                .map_err(|e| sql_error("Failed to query logical footprint", e))?;
+
+            let naive_size_bytes: i64 = conn
+                .query_row(
+                    "SELECT COALESCE(SUM(total_size), 0) FROM snapshots WHERE job_id = ?",
+                    params![job_id],
+                    |row| row.get(0),
+                )
+                .map_err(|e| sql_error("Failed to query snapshot sizes", e))?;
+
+            Ok(JobLogicalFootprint {
+                unique_size_bytes,
+                naive_size_bytes,
+                unique_files,
+            })
+        })
     }
 
     /// Get snapshot density grouped by period (TIM-128: for calendar/timeline visualization)
     /// Period can be: "day", "week", "month", "year"
     pub fn get_snapshot_density(&self, job_id: &str, period: &str) -> Result<Vec<SnapshotDensity>> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|e| AmberError::Index(format!("Failed to acquire database lock: {}", e)))?;
-
-        // Validate period input (security: prevent SQL injection via match arm)
-        let period_code = match period {
-            "day" => 1,
-            "week" => 2,
-            "month" => 3,
-            "year" => 4,
-            _ => 3, // Default to month
-        };
-
-        // Use CASE expression instead of format! to avoid SQL injection risk
-        // Even though the input is validated, this pattern is safer and prevents
-        // future refactoring from introducing vulnerabilities
-        let query = r#"
+        self.read(|conn| {
+            // Validate period input (security: prevent SQL injection via match arm)
+            let period_code = match period {
+                "day" => 1,
+                "week" => 2,
+                "month" => 3,
+                "year" => 4,
+                _ => 3, // Default to month
+            };
+
+            // Use CASE expression instead of format! to avoid SQL injection risk
+            // Even though the input is validated, this pattern is safer and prevents
+            // future refactoring from introducing vulnerabilities
+            let query = r#"
             SELECT
                 CASE ?1
                     WHEN 1 THEN strftime('%Y-%m-%d', timestamp / 1000, 'unixepoch')
@@ -888,26 +3399,24 @@ impl IndexService {
              ORDER BY period DESC
         "#;
 
-        let mut stmt = conn
-            .prepare(query)
-            .map_err(|e| AmberError::Index(format!("Failed to prepare query: {}", e)))?;
-
-        let density = stmt
-            .query_map(params![period_code, job_id], |row| {
-                Ok(SnapshotDensity {
-                    period: row.get(0)?,
-                    count: row.get(1)?,
-                    total_size: row.get(2)?,
-                })
-            })
-            .map_err(|e| AmberError::Index(format!("Failed to query density: {}", e)))?;
+            let mut stmt = conn
+                .prepare(query)
+                .map_err(|e| sql_error("Failed to prepare query", e))?;
 
-        let mut result = Vec::new();
-        for entry in density.flatten() {
-            result.push(entry);
-        }
+            let density = stmt
+                .query_map(params![period_code, job_id], |row| {
+                    Ok(SnapshotDensity {
+                        period: row.get(0)?,
+                        count: row.get(1)?,
+                        total_size: row.get(2)?,
+                    })
+                })
+                .map_err(|e| sql_error("Failed to query density", e))?;
 
-        Ok(result)
+            density
+                .collect::<rusqlite::Result<Vec<_>>>()
+                .map_err(|e| sql_error("Failed to query density", e))
+        })
     }
 
     /// TIM-221: Compare two snapshots and return the differences
@@ -919,195 +3428,776 @@ impl IndexService {
         timestamp_b: i64,
         limit: Option<usize>,
     ) -> Result<SnapshotDiff> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|e| AmberError::Index(format!("Failed to acquire database lock: {}", e)))?;
+        self.compare_snapshots_under(job_id, timestamp_a, timestamp_b, None, &[], limit)
+    }
 
-        let limit_val = limit.unwrap_or(5000) as i64;
+    /// Compare two snapshots, only looking at files beneath `under_path`
+    /// (relative to the snapshot root). `None` or "" compares everything.
+    /// Files matching `ignore_globs` (relative to the snapshot root), or inside
+    /// a folder that does, are left out of both sides before diffing.
+    ///
+    /// `limit` caps each list; the summary still carries the full per-category
+    /// totals and `truncated` says which lists were cut short. Renames are
+    /// paired within the returned lists only.
+    pub fn compare_snapshots_under(
+        &self,
+        job_id: &str,
+        timestamp_a: i64,
+        timestamp_b: i64,
+        under_path: Option<&str>,
+        ignore_globs: &[String],
+        limit: Option<usize>,
+    ) -> Result<SnapshotDiff> {
+        let subtree = normalize_subtree(under_path.unwrap_or(""))?;
+        let ignore = build_ignore_set(ignore_globs)?;
+        let ignore = ignore.as_ref();
+        let limit = limit.unwrap_or(5000);
+
+        self.read(|conn| {
+            let ids = Self::diff_snapshot_ids(conn, job_id, timestamp_a, timestamp_b)?;
+
+            let (added_rows, added_total) = Self::query_diff_category(
+                conn,
+                ids,
+                &subtree,
+                ignore,
+                DiffCategory::Added,
+                0,
+                limit,
+            )?;
+            let (deleted_rows, deleted_total) = Self::query_diff_category(
+                conn,
+                ids,
+                &subtree,
+                ignore,
+                DiffCategory::Deleted,
+                0,
+                limit,
+            )?;
+            let (modified_rows, modified_total) = Self::query_diff_category(
+                conn,
+                ids,
+                &subtree,
+                ignore,
+                DiffCategory::Modified,
+                0,
+                limit,
+            )?;
+
+            let truncated = DiffTruncation {
+                added: added_rows.len() < added_total.count as usize,
+                deleted: deleted_rows.len() < deleted_total.count as usize,
+                modified: modified_rows.len() < modified_total.count as usize,
+            };
+
+            // Moves keep their hash+size, so they net out of the size delta
+            let (added, deleted, renamed) = detect_renames(added_rows, deleted_rows);
+            let modified: Vec<DiffEntry> = modified_rows.into_iter().map(|(e, _)| e).collect();
+            let symlink_retargeted: Vec<SymlinkRetarget> =
+                Self::query_symlink_retargets(conn, ids, &subtree)?
+                    .into_iter()
+                    .filter(|r| ignore.map_or(true, |set| !is_ignored(set, &r.path)))
+                    .collect();
+
+            let summary = DiffSummary {
+                total_added: added_total.count - renamed.len() as u32,
+                total_deleted: deleted_total.count - renamed.len() as u32,
+                total_modified: modified_total.count,
+                content_changed: modified_total.same_size,
+                total_renamed: renamed.len() as u32,
+                total_symlink_retargeted: symlink_retargeted.len() as u32,
+                size_delta: added_total.size_delta
+                    + deleted_total.size_delta
+                    + modified_total.size_delta,
+            };
+
+            Ok(SnapshotDiff {
+                added,
+                deleted,
+                modified,
+                renamed,
+                symlink_retargeted,
+                truncated,
+                summary,
+                top_contributors: Vec::new(),
+            })
+        })
+    }
 
-        // Get snapshot IDs for both timestamps
-        let snapshot_id_a: i64 = conn
-            .query_row(
-                "SELECT id FROM snapshots WHERE job_id = ? AND timestamp = ?",
-                params![job_id, timestamp_a],
-                |row| row.get(0),
-            )
-            .map_err(|_| {
-                AmberError::Index(format!(
-                    "Snapshot A not found: job_id={}, timestamp={}",
-                    job_id, timestamp_a
-                ))
-            })?;
+    /// The `count` files behind most of a diff's size delta: added, deleted,
+    /// grown and shrunk files ordered by how much their size changed, largest
+    /// first. Scoped like `compare_snapshots_under`. Renames aren't paired, so
+    /// a moved file shows up as a deletion and an addition.
+    pub fn size_contributors(
+        &self,
+        job_id: &str,
+        timestamp_a: i64,
+        timestamp_b: i64,
+        under_path: Option<&str>,
+        ignore_globs: &[String],
+        count: usize,
+    ) -> Result<Vec<DiffEntry>> {
+        let subtree = normalize_subtree(under_path.unwrap_or(""))?;
+        let ignore = build_ignore_set(ignore_globs)?;
+
+        self.read(|conn| {
+            let ids = Self::diff_snapshot_ids(conn, job_id, timestamp_a, timestamp_b)?;
+
+            // Globs can't be expressed in SQL, so rank the filtered rows here
+            if let Some(ignore) = ignore {
+                let mut entries = Vec::new();
+                for category in [
+                    DiffCategory::Added,
+                    DiffCategory::Deleted,
+                    DiffCategory::Modified,
+                ] {
+                    Self::for_each_diff_row(
+                        conn,
+                        ids,
+                        &subtree,
+                        category,
+                        0,
+                        None,
+                        |(entry, _)| {
+                            if !is_ignored(&ignore, &entry.path) {
+                                entries.push(entry);
+                            }
+                            Ok(())
+                        },
+                    )?;
+                }
+                entries.sort_by(|a, b| {
+                    size_change(b)
+                        .cmp(&size_change(a))
+                        .then_with(|| a.path.cmp(&b.path))
+                });
+                entries.truncate(count);
+                return Ok(entries);
+            }
 
-        let snapshot_id_b: i64 = conn
-            .query_row(
-                "SELECT id FROM snapshots WHERE job_id = ? AND timestamp = ?",
-                params![job_id, timestamp_b],
-                |row| row.get(0),
-            )
-            .map_err(|_| {
-                AmberError::Index(format!(
-                    "Snapshot B not found: job_id={}, timestamp={}",
-                    job_id, timestamp_b
+            let mut stmt = conn
+                .prepare(&format!(
+                    "SELECT rel_path, size_a, size_b FROM ({} UNION ALL {} UNION ALL {})
+                 ORDER BY ABS(COALESCE(size_b, 0) - COALESCE(size_a, 0)) DESC, rel_path
+                 LIMIT ?4",
+                    diff_category_sql(DiffCategory::Added),
+                    diff_category_sql(DiffCategory::Deleted),
+                    diff_category_sql(DiffCategory::Modified)
                 ))
-            })?;
+                .map_err(|e| sql_error("Failed to prepare contributors query", e))?;
+
+            let (snapshot_id_a, snapshot_id_b) = ids;
+            let entries = stmt
+                .query_map(
+                    params![snapshot_id_a, snapshot_id_b, subtree, count as i64],
+                    |row| {
+                        Ok(DiffEntry {
+                            path: row.get(0)?,
+                            size_a: row.get(1)?,
+                            size_b: row.get(2)?,
+                        })
+                    },
+                )
+                .map_err(|e| sql_error("Failed to query contributors", e))?
+                .collect::<rusqlite::Result<_>>()
+                .map_err(|e| sql_error("Failed to query contributors", e))?;
 
-        // Use relative paths (parent_path + name) for comparison since
-        // absolute paths differ between snapshots (each has a different root dir)
-        let rel_path_expr =
-            "CASE WHEN parent_path = '' THEN name ELSE parent_path || '/' || name END";
+            Ok(entries)
+        })
+    }
 
-        // Query for added files (in B but not in A)
-        let added_query = format!(
-            r#"
-            WITH rel_b AS (
-                SELECT {rel} AS rel_path, size
-                FROM files WHERE snapshot_id = ?1 AND file_type = 'file'
-            )
-            SELECT rel_path, size FROM rel_b
-            WHERE rel_path NOT IN (
-                SELECT {rel} FROM files WHERE snapshot_id = ?2 AND file_type = 'file'
-            )
-            LIMIT ?3
-            "#,
-            rel = rel_path_expr,
-        );
+    /// Streaming variant of `compare_snapshots_under`: hands every added,
+    /// deleted and modified entry to `on_entry` as rows are read (all added
+    /// first, then deleted, then modified, each ordered by path), so diffs of
+    /// millions of files never sit in memory at once. There is no limit and no
+    /// rename pairing; a moved file arrives as one deletion and one addition.
+    /// Returning an error from `on_entry` stops the scan and is passed through.
+    /// Returns the number of entries delivered.
+    pub fn compare_snapshots_each<F>(
+        &self,
+        job_id: &str,
+        timestamp_a: i64,
+        timestamp_b: i64,
+        under_path: Option<&str>,
+        ignore_globs: &[String],
+        mut on_entry: F,
+    ) -> Result<usize>
+    where
+        F: FnMut(DiffCategory, DiffEntry) -> Result<()>,
+    {
+        let subtree = normalize_subtree(under_path.unwrap_or(""))?;
+        let ignore = build_ignore_set(ignore_globs)?;
+
+        self.read(|conn| {
+            let ids = Self::diff_snapshot_ids(conn, job_id, timestamp_a, timestamp_b)?;
+
+            let mut delivered = 0;
+            for category in [
+                DiffCategory::Added,
+                DiffCategory::Deleted,
+                DiffCategory::Modified,
+            ] {
+                Self::for_each_diff_row(conn, ids, &subtree, category, 0, None, |(entry, _)| {
+                    if ignore
+                        .as_ref()
+                        .is_some_and(|set| is_ignored(set, &entry.path))
+                    {
+                        return Ok(());
+                    }
+                    on_entry(category, entry)?;
+                    delivered += 1;
+                    Ok(())
+                })?;
+            }
 
-        let mut added_stmt = conn
-            .prepare(&added_query)
-            .map_err(|e| AmberError::Index(format!("Failed to prepare added query: {}", e)))?;
+            Ok(delivered)
+        })
+    }
 
-        let added_rows = added_stmt
-            .query_map(params![snapshot_id_b, snapshot_id_a, limit_val], |row| {
-                Ok(DiffEntry {
-                    path: row.get(0)?,
-                    size_a: None,
-                    size_b: Some(row.get(1)?),
-                })
+    /// Regular files in the snapshot at `timestamp_good` that are gone from
+    /// the one at `timestamp_now`, for restoring after an accidental
+    /// deletion. Only the deleted side of the diff is queried, with no limit
+    /// and no rename pairing: a file that was moved is listed too.
+    pub fn list_deleted_between(
+        &self,
+        job_id: &str,
+        timestamp_good: i64,
+        timestamp_now: i64,
+    ) -> Result<DeletedFiles> {
+        self.read(|conn| {
+            let ids = Self::diff_snapshot_ids(conn, job_id, timestamp_good, timestamp_now)?;
+            let snapshot_path: String = conn
+                .query_row(
+                    "SELECT root_path FROM snapshots WHERE id = ?",
+                    params![ids.0],
+                    |row| row.get(0),
+                )
+                .map_err(snapshot_lookup_error)?;
+
+            let mut files = Vec::new();
+            let mut total_bytes = 0;
+            Self::for_each_diff_row(
+                conn,
+                ids,
+                "",
+                DiffCategory::Deleted,
+                0,
+                None,
+                |(entry, _)| {
+                    let size = entry.size_a.unwrap_or(0);
+                    total_bytes += size;
+                    files.push(DeletedFile {
+                        path: entry.path,
+                        size,
+                    });
+                    Ok(())
+                },
+            )?;
+
+            Ok(DeletedFiles {
+                snapshot_path,
+                files,
+                total_bytes,
             })
-            .map_err(|e| AmberError::Index(format!("Failed to query added files: {}", e)))?;
+        })
+    }
 
-        let mut added: Vec<DiffEntry> = Vec::new();
-        let mut size_added: i64 = 0;
-        for entry in added_rows.flatten() {
-            size_added += entry.size_b.unwrap_or(0);
-            added.push(entry);
-        }
+    /// Page through one category of a snapshot diff (for lazy-loading lists).
+    /// Entries are ordered by path; no rename pairing is applied. Pass the
+    /// same `ignore_globs` as the `compare_snapshots_under` call being paged.
+    pub fn compare_snapshots_page(
+        &self,
+        job_id: &str,
+        timestamp_a: i64,
+        timestamp_b: i64,
+        under_path: Option<&str>,
+        ignore_globs: &[String],
+        page: DiffPageRequest,
+    ) -> Result<DiffPage> {
+        let subtree = normalize_subtree(under_path.unwrap_or(""))?;
+        let ignore = build_ignore_set(ignore_globs)?;
+
+        self.read(|conn| {
+            let ids = Self::diff_snapshot_ids(conn, job_id, timestamp_a, timestamp_b)?;
+            let (rows, totals) = Self::query_diff_category(
+                conn,
+                ids,
+                &subtree,
+                ignore.as_ref(),
+                page.category,
+                page.offset,
+                page.limit,
+            )?;
+
+            Ok(DiffPage {
+                category: page.category,
+                offset: page.offset,
+                has_more: page.offset + rows.len() < totals.count as usize,
+                total: totals.count,
+                entries: rows.into_iter().map(|(e, _)| e).collect(),
+            })
+        })
+    }
 
-        // Query for deleted files (in A but not in B)
-        let deleted_query = format!(
-            r#"
-            WITH rel_a AS (
-                SELECT {rel} AS rel_path, size
-                FROM files WHERE snapshot_id = ?1 AND file_type = 'file'
-            )
-            SELECT rel_path, size FROM rel_a
-            WHERE rel_path NOT IN (
-                SELECT {rel} FROM files WHERE snapshot_id = ?2 AND file_type = 'file'
+    /// Regular files of a snapshot with their paths relative to its root, for
+    /// comparing against a live source (see `source_diff`)
+    pub fn snapshot_files(&self, job_id: &str, timestamp: i64) -> Result<Vec<ScannedFile>> {
+        let snapshot_id: i64 = self.read(|conn| {
+            conn.query_row(
+                "SELECT id FROM snapshots WHERE job_id = ? AND timestamp = ?",
+                params![job_id, timestamp],
+                |row| row.get(0),
             )
-            LIMIT ?3
-            "#,
-            rel = rel_path_expr,
-        );
-
-        let mut deleted_stmt = conn
-            .prepare(&deleted_query)
-            .map_err(|e| AmberError::Index(format!("Failed to prepare deleted query: {}", e)))?;
+            .map_err(snapshot_lookup_error)
+        })?;
+        self.regular_files_of(snapshot_id)
+    }
 
-        let deleted_rows = deleted_stmt
-            .query_map(params![snapshot_id_a, snapshot_id_b, limit_val], |row| {
-                Ok(DiffEntry {
-                    path: row.get(0)?,
-                    size_a: Some(row.get(1)?),
-                    size_b: None,
+    fn regular_files_of(&self, snapshot_id: i64) -> Result<Vec<ScannedFile>> {
+        self.read(|conn| {
+            let mut stmt = conn
+                .prepare(
+                    "SELECT CASE WHEN parent_path = '' THEN name
+                             ELSE parent_path || '/' || name END, size, mtime
+                 FROM files WHERE snapshot_id = ? AND file_type = 'file'",
+                )
+                .map_err(|e| sql_error("Failed to prepare query", e))?;
+            let rows = stmt
+                .query_map(params![snapshot_id], |row| {
+                    Ok(ScannedFile {
+                        path: row.get(0)?,
+                        size: row.get(1)?,
+                        mtime: row.get(2)?,
+                    })
                 })
-            })
-            .map_err(|e| AmberError::Index(format!("Failed to query deleted files: {}", e)))?;
-
-        let mut deleted: Vec<DiffEntry> = Vec::new();
-        let mut size_deleted: i64 = 0;
-        for entry in deleted_rows.flatten() {
-            size_deleted += entry.size_a.unwrap_or(0);
-            deleted.push(entry);
-        }
+                .map_err(|e| sql_error("Failed to query files", e))?;
 
-        // Query for modified files (in both but different size)
-        let modified_query = format!(
-            r#"
-            WITH rel_a AS (
-                SELECT {rel} AS rel_path, size
-                FROM files WHERE snapshot_id = ?1 AND file_type = 'file'
-            ),
-            rel_b AS (
-                SELECT {rel} AS rel_path, size
-                FROM files WHERE snapshot_id = ?2 AND file_type = 'file'
-            )
-            SELECT a.rel_path, a.size, b.size
-            FROM rel_a a
-            INNER JOIN rel_b b ON a.rel_path = b.rel_path
-            WHERE a.size != b.size
-            LIMIT ?3
-            "#,
-            rel = rel_path_expr,
-        );
+            rows.collect::<rusqlite::Result<Vec<_>>>()
+                .map_err(|e| sql_error("Failed to query files", e))
+        })
+    }
 
-        let mut modified_stmt = conn
-            .prepare(&modified_query)
-            .map_err(|e| AmberError::Index(format!("Failed to prepare modified query: {}", e)))?;
+    /// Regular files of the job's latest indexed snapshot and its timestamp,
+    /// skipping metadata-only pins. `None` if the job has no indexed snapshot.
+    pub fn latest_snapshot_files(&self, job_id: &str) -> Result<Option<(i64, Vec<ScannedFile>)>> {
+        let Some(latest) = self
+            .list_snapshots(job_id)?
+            .into_iter()
+            .find(|s| !s.metadata_only)
+        else {
+            return Ok(None);
+        };
+        Ok(Some((latest.timestamp, self.regular_files_of(latest.id)?)))
+    }
+
+    /// Compare the live local `source_path` against the job's latest indexed
+    /// snapshot without running rsync (see `source_diff::diff_source`). Paths
+    /// are relative to the source root. `None` if the job has no indexed
+    /// snapshot.
+    pub fn compare_source_to_snapshot(
+        &self,
+        job_id: &str,
+        source_path: &str,
+        exclude_patterns: &[String],
+    ) -> Result<Option<SourceComparison>> {
+        let Some((timestamp, snapshot)) = self.latest_snapshot_files(job_id)? else {
+            return Ok(None);
+        };
+        let source = source_diff::scan_local(Path::new(source_path))?;
+        Ok(Some(source_diff::diff_source(
+            timestamp,
+            snapshot,
+            source,
+            exclude_patterns,
+        )))
+    }
+
+    /// Count the files added, modified and deleted since the job's previous
+    /// indexed snapshot. `None` for the job's first snapshot. Renames are not
+    /// paired, so a moved file counts as one deletion and one addition.
+    pub fn changes_since_previous(
+        &self,
+        job_id: &str,
+        timestamp: i64,
+    ) -> Result<Option<SnapshotChanges>> {
+        self.read(|conn| {
+            let previous: Option<i64> = conn
+                .query_row(
+                    "SELECT MAX(timestamp) FROM snapshots WHERE job_id = ? AND timestamp < ?",
+                    params![job_id, timestamp],
+                    |row| row.get(0),
+                )
+                .map_err(|e| sql_error("Failed to find previous snapshot", e))?;
+            let Some(previous) = previous else {
+                return Ok(None);
+            };
+
+            let ids = Self::diff_snapshot_ids(conn, job_id, previous, timestamp)?;
+            let count = |category| {
+                Self::diff_category_totals(conn, ids, "", category).map(|t| t.count as u64)
+            };
+            Ok(Some(SnapshotChanges {
+                added: count(DiffCategory::Added)?,
+                modified: count(DiffCategory::Modified)?,
+                deleted: count(DiffCategory::Deleted)?,
+            }))
+        })
+    }
+
+    /// Resolve (snapshot A id, snapshot B id) for a comparison
+    fn diff_snapshot_ids(
+        conn: &Connection,
+        job_id: &str,
+        timestamp_a: i64,
+        timestamp_b: i64,
+    ) -> Result<(i64, i64)> {
+        let lookup = |timestamp: i64, label: &str| {
+            conn.query_row(
+                "SELECT id FROM snapshots WHERE job_id = ? AND timestamp = ?",
+                params![job_id, timestamp],
+                |row| row.get::<_, i64>(0),
+            )
+            .map_err(|e| match e {
+                rusqlite::Error::QueryReturnedNoRows => AmberError::Index(format!(
+                    "Snapshot {} not found: job_id={}, timestamp={}",
+                    label, job_id, timestamp
+                )),
+                e => sql_error("Failed to look up snapshot", e),
+            })
+        };
+        Ok((lookup(timestamp_a, "A")?, lookup(timestamp_b, "B")?))
+    }
 
-        let modified_rows = modified_stmt
-            .query_map(params![snapshot_id_a, snapshot_id_b, limit_val], |row| {
-                Ok(DiffEntry {
+    /// Symlinks present in both snapshots under `subtree` whose targets
+    /// differ, by path. Snapshots indexed before targets were recorded have
+    /// none, so they never report a retarget.
+    fn query_symlink_retargets(
+        conn: &Connection,
+        (snapshot_id_a, snapshot_id_b): (i64, i64),
+        subtree: &str,
+    ) -> Result<Vec<SymlinkRetarget>> {
+        let mut stmt = conn
+            .prepare(
+                "SELECT a.path, a.target, b.target
+                 FROM symlink_targets a
+                 JOIN symlink_targets b ON b.snapshot_id = ?2 AND b.path = a.path
+                 WHERE a.snapshot_id = ?1 AND a.target != b.target
+                   AND (?3 = '' OR substr(a.path, 1, length(?3) + 1) = ?3 || '/')
+                 ORDER BY a.path",
+            )
+            .map_err(|e| sql_error("Failed to prepare symlink target query", e))?;
+        let rows = stmt
+            .query_map(params![snapshot_id_a, snapshot_id_b, subtree], |row| {
+                Ok(SymlinkRetarget {
                     path: row.get(0)?,
-                    size_a: Some(row.get(1)?),
-                    size_b: Some(row.get(2)?),
+                    target_a: row.get(1)?,
+                    target_b: row.get(2)?,
                 })
             })
-            .map_err(|e| AmberError::Index(format!("Failed to query modified files: {}", e)))?;
-
-        let mut modified: Vec<DiffEntry> = Vec::new();
-        let mut size_modified_delta: i64 = 0;
-        for entry in modified_rows.flatten() {
-            let delta = entry.size_b.unwrap_or(0) - entry.size_a.unwrap_or(0);
-            size_modified_delta += delta;
-            modified.push(entry);
-        }
+            .map_err(|e| sql_error("Failed to query symlink targets", e))?;
+        rows.collect::<rusqlite::Result<Vec<_>>>()
+            .map_err(|e| sql_error("Failed to query symlink targets", e))
+    }
 
-        // Calculate summary statistics
-        let size_delta = size_added - size_deleted + size_modified_delta;
+    /// One page of a diff category plus the category's full count and size delta
+    fn query_diff_category(
+        conn: &Connection,
+        ids: (i64, i64),
+        subtree: &str,
+        ignore: Option<&GlobSet>,
+        category: DiffCategory,
+        offset: usize,
+        limit: usize,
+    ) -> Result<(Vec<DiffRow>, DiffCategoryTotals)> {
+        let Some(ignore) = ignore else {
+            let totals = Self::diff_category_totals(conn, ids, subtree, category)?;
+            let rows = Self::diff_category_rows(conn, ids, subtree, category, offset, Some(limit))?;
+            return Ok((rows, totals));
+        };
 
-        let summary = DiffSummary {
-            total_added: added.len() as u32,
-            total_deleted: deleted.len() as u32,
-            total_modified: modified.len() as u32,
-            size_delta,
+        // Globs can't be expressed in SQL, so filter the whole category here
+        let rows: Vec<DiffRow> = Self::diff_category_rows(conn, ids, subtree, category, 0, None)?
+            .into_iter()
+            .filter(|(entry, _)| !is_ignored(ignore, &entry.path))
+            .collect();
+        let totals = DiffCategoryTotals {
+            count: rows.len() as u32,
+            size_delta: rows
+                .iter()
+                .map(|(e, _)| e.size_b.unwrap_or(0) - e.size_a.unwrap_or(0))
+                .sum(),
+            same_size: rows
+                .iter()
+                .filter(|(e, _)| e.size_a.is_some() && e.size_a == e.size_b)
+                .count() as u32,
         };
+        let page = rows.into_iter().skip(offset).take(limit).collect();
+        Ok((page, totals))
+    }
 
-        Ok(SnapshotDiff {
-            added,
-            deleted,
-            modified,
-            summary,
-        })
+    fn diff_category_totals(
+        conn: &Connection,
+        (snapshot_id_a, snapshot_id_b): (i64, i64),
+        subtree: &str,
+        category: DiffCategory,
+    ) -> Result<DiffCategoryTotals> {
+        conn.query_row(
+            &format!(
+                "SELECT COUNT(*), COALESCE(SUM(COALESCE(size_b, 0) - COALESCE(size_a, 0)), 0),
+                        COALESCE(SUM(size_a = size_b), 0)
+                 FROM ({})",
+                diff_category_sql(category)
+            ),
+            params![snapshot_id_a, snapshot_id_b, subtree],
+            |row| {
+                Ok(DiffCategoryTotals {
+                    count: row.get(0)?,
+                    size_delta: row.get(1)?,
+                    same_size: row.get(2)?,
+                })
+            },
+        )
+        .map_err(|e| sql_error(&format!("Failed to count {} files", category.label()), e))
+    }
+
+    /// Rows of a diff category ordered by path; `limit: None` returns them all
+    fn diff_category_rows(
+        conn: &Connection,
+        ids: (i64, i64),
+        subtree: &str,
+        category: DiffCategory,
+        offset: usize,
+        limit: Option<usize>,
+    ) -> Result<Vec<DiffRow>> {
+        let mut rows = Vec::new();
+        Self::for_each_diff_row(conn, ids, subtree, category, offset, limit, |row| {
+            rows.push(row);
+            Ok(())
+        })?;
+        Ok(rows)
     }
 
-    /// Check if a snapshot is indexed
+    /// Hand each row of a diff category to `on_row` as it is read, ordered by
+    /// path. Stops at the first error from `on_row`.
+    fn for_each_diff_row(
+        conn: &Connection,
+        (snapshot_id_a, snapshot_id_b): (i64, i64),
+        subtree: &str,
+        category: DiffCategory,
+        offset: usize,
+        limit: Option<usize>,
+        mut on_row: impl FnMut(DiffRow) -> Result<()>,
+    ) -> Result<()> {
+        let label = category.label();
+        let mut stmt = conn
+            .prepare(&format!(
+                "SELECT * FROM ({}) ORDER BY rel_path LIMIT ?4 OFFSET ?5",
+                diff_category_sql(category)
+            ))
+            .map_err(|e| sql_error(&format!("Failed to prepare {} query", label), e))?;
+
+        // A negative LIMIT means no limit in SQLite
+        let limit = limit.map_or(-1, |l| l as i64);
+        let rows = stmt
+            .query_map(
+                params![snapshot_id_a, snapshot_id_b, subtree, limit, offset as i64],
+                |row| {
+                    Ok((
+                        DiffEntry {
+                            path: row.get(0)?,
+                            size_a: row.get(1)?,
+                            size_b: row.get(2)?,
+                        },
+                        row.get::<_, Option<String>>(3)?,
+                    ))
+                },
+            )
+            .map_err(|e| sql_error(&format!("Failed to query {} files", label), e))?;
+
+        for row in rows {
+            let row = row.map_err(|e| sql_error(&format!("Failed to query {} files", label), e))?;
+            on_row(row)?;
+        }
+        Ok(())
+    }
+
+    /// Check if a snapshot is indexed (a partial resumable index doesn't count)
     pub fn is_indexed(&self, job_id: &str, timestamp: i64) -> Result<bool> {
+        self.read(|conn| {
+            let count: i64 = conn
+                .query_row(
+                    "SELECT COUNT(*) FROM snapshots
+                 WHERE job_id = ? AND timestamp = ? AND resume_after IS NULL",
+                    params![job_id, timestamp],
+                    |row| row.get(0),
+                )
+                .or_else(|e| {
+                    if is_busy(&e) {
+                        Err(sql_error("Failed to check snapshot", e))
+                    } else {
+                        Ok(0)
+                    }
+                })?;
+
+            Ok(count > 0)
+        })
+    }
+
+    /// Clear the pending flag once the snapshot's manifest entry is written.
+    /// Returns false if no such snapshot is indexed.
+    pub fn mark_snapshot_committed(&self, job_id: &str, timestamp: i64) -> Result<bool> {
         let conn = self
             .conn
             .lock()
             .map_err(|e| AmberError::Index(format!("Failed to acquire database lock: {}", e)))?;
 
-        let count: i64 = conn
-            .query_row(
-                "SELECT COUNT(*) FROM snapshots WHERE job_id = ? AND timestamp = ?",
+        let updated = conn
+            .execute(
+                "UPDATE snapshots SET pending = 0 WHERE job_id = ? AND timestamp = ?",
                 params![job_id, timestamp],
-                |row| row.get(0),
             )
-            .unwrap_or(0);
+            .map_err(|e| sql_error("Failed to commit snapshot", e))?;
+
+        Ok(updated > 0)
+    }
+
+    /// Snapshots (of any job) still waiting for their manifest entry
+    pub fn list_pending_snapshots(&self) -> Result<Vec<IndexedSnapshot>> {
+        self.read(|conn| {
+            let mut stmt = conn
+                .prepare(
+                    "SELECT id, job_id, timestamp, root_path, file_count, total_size, metadata_only
+                 FROM snapshots
+                 WHERE pending != 0
+                 ORDER BY timestamp",
+                )
+                .map_err(|e| sql_error("Failed to prepare query", e))?;
+
+            let snapshots = stmt
+                .query_map([], |row| {
+                    Ok(IndexedSnapshot {
+                        id: row.get(0)?,
+                        job_id: row.get(1)?,
+                        timestamp: row.get(2)?,
+                        root_path: row.get(3)?,
+                        file_count: row.get(4)?,
+                        total_size: row.get(5)?,
+                        metadata_only: row.get(6)?,
+                    })
+                })
+                .map_err(|e| sql_error("Failed to query snapshots", e))?;
+
+            snapshots
+                .collect::<rusqlite::Result<Vec<_>>>()
+                .map_err(|e| sql_error("Failed to read snapshots", e))
+        })
+    }
+
+    /// Refuse to restore from a metadata-only snapshot: it records names and
+    /// sizes but holds no file data. Snapshots the index doesn't know pass.
+    pub fn ensure_restorable(&self, job_id: &str, timestamp: i64) -> Result<()> {
+        self.refuse_metadata_only(
+            "SELECT metadata_only FROM snapshots WHERE job_id = ? AND timestamp = ?",
+            params![job_id, timestamp],
+        )
+    }
+
+    /// `ensure_restorable` for a snapshot given by its root folder
+    pub fn ensure_restorable_root(&self, job_id: &str, root_path: &str) -> Result<()> {
+        self.refuse_metadata_only(
+            "SELECT metadata_only FROM snapshots WHERE job_id = ? AND root_path = ?
+             ORDER BY metadata_only DESC LIMIT 1",
+            params![job_id, root_path.trim_end_matches('/')],
+        )
+    }
+
+    /// Where the stored relative `paths` of the snapshot at `root_path` are
+    /// on disk, relative to its root: the exact bytes for paths that weren't
+    /// valid UTF-8 or were stored NFC-normalized, the path as given for the
+    /// rest (and for every path of a snapshot the index doesn't know)
+    pub fn restore_paths(
+        &self,
+        job_id: &str,
+        root_path: &str,
+        paths: &[String],
+    ) -> Result<Vec<PathBuf>> {
+        self.read(|conn| {
+            let snapshot_id: Option<i64> = conn
+                .query_row(
+                    "SELECT id FROM snapshots WHERE job_id = ? AND root_path = ? LIMIT 1",
+                    params![job_id, root_path.trim_end_matches('/')],
+                    |row| row.get(0),
+                )
+                .optional()
+                .map_err(|e| sql_error("Failed to look up snapshot", e))?;
+            Self::on_disk_paths(conn, snapshot_id, paths)
+        })
+    }
 
-        Ok(count > 0)
+    /// `restore_paths` for the snapshot at `timestamp`
+    pub fn restore_paths_at(
+        &self,
+        job_id: &str,
+        timestamp: i64,
+        paths: &[String],
+    ) -> Result<Vec<PathBuf>> {
+        self.read(|conn| {
+            let snapshot_id: Option<i64> = conn
+                .query_row(
+                    "SELECT id FROM snapshots WHERE job_id = ? AND timestamp = ?",
+                    params![job_id, timestamp],
+                    |row| row.get(0),
+                )
+                .optional()
+                .map_err(|e| sql_error("Failed to look up snapshot", e))?;
+            Self::on_disk_paths(conn, snapshot_id, paths)
+        })
+    }
+
+    fn on_disk_paths(
+        conn: &Connection,
+        snapshot_id: Option<i64>,
+        paths: &[String],
+    ) -> Result<Vec<PathBuf>> {
+        let Some(snapshot_id) = snapshot_id else {
+            return Ok(paths.iter().map(PathBuf::from).collect());
+        };
+
+        let mut stmt = conn
+            .prepare_cached("SELECT raw FROM raw_paths WHERE snapshot_id = ? AND path = ?")
+            .map_err(|e| sql_error("Failed to prepare raw path query", e))?;
+        paths
+            .iter()
+            .map(|path| {
+                let raw: Option<Vec<u8>> = stmt
+                    .query_row(params![snapshot_id, path.trim_matches('/')], |row| {
+                        row.get(0)
+                    })
+                    .optional()
+                    .map_err(|e| sql_error("Failed to look up raw path", e))?;
+                Ok(raw.map_or_else(|| PathBuf::from(path), |raw| path_from_bytes(&raw)))
+            })
+            .collect()
+    }
+
+    fn refuse_metadata_only(&self, sql: &str, params: impl rusqlite::Params) -> Result<()> {
+        let conn = self
+            .conn
+            .lock()
+            .map_err(|e| AmberError::Index(format!("Failed to acquire database lock: {}", e)))?;
+
+        let metadata_only: Option<bool> = conn
+            .query_row(sql, params, |row| row.get(0))
+            .optional()
+            .map_err(|e| sql_error("Failed to query snapshot", e))?;
+        if metadata_only == Some(true) {
+            return Err(AmberError::Snapshot(
+                "This is a metadata-only snapshot: it records the file listing but holds no \
+                 file data, so nothing can be restored from it"
+                    .to_string(),
+            ));
+        }
+        Ok(())
     }
 
     /// Delete a snapshot from the index
@@ -1117,11 +4207,14 @@ impl IndexService {
             .lock()
             .map_err(|e| AmberError::Index(format!("Failed to acquire database lock: {}", e)))?;
 
-        conn.execute(
-            "DELETE FROM snapshots WHERE job_id = ? AND timestamp = ?",
-            params![job_id, timestamp],
-        )
-        .map_err(|e| AmberError::Index(format!("Failed to delete snapshot: {}", e)))?;
+        let deleted = conn
+            .execute(
+                "DELETE FROM snapshots WHERE job_id = ? AND timestamp = ?",
+                params![job_id, timestamp],
+            )
+            .map_err(|e| sql_error("Failed to delete snapshot", e))?;
+        bump_meta(&conn, META_DELETIONS_SINCE_COMPACT, deleted as u64)?;
+        index_warmup::forget(job_id, timestamp);
 
         Ok(())
     }
@@ -1133,69 +4226,246 @@ impl IndexService {
             .lock()
             .map_err(|e| AmberError::Index(format!("Failed to acquire database lock: {}", e)))?;
 
-        conn.execute("DELETE FROM snapshots WHERE job_id = ?", params![job_id])
-            .map_err(|e| AmberError::Index(format!("Failed to delete job snapshots: {}", e)))?;
+        let deleted = conn
+            .execute("DELETE FROM snapshots WHERE job_id = ?", params![job_id])
+            .map_err(|e| sql_error("Failed to delete job snapshots", e))?;
+        bump_meta(&conn, META_DELETIONS_SINCE_COMPACT, deleted as u64)?;
+        index_warmup::forget_job(job_id);
 
         Ok(())
     }
 
-    /// Search files by name pattern
-    pub fn search_files(
-        &self,
-        job_id: &str,
-        timestamp: i64,
-        pattern: &str,
-        limit: usize,
-    ) -> Result<Vec<FileNode>> {
-        let conn = self
+    /// Move every snapshot of `old_id` to `new_id` in one transaction.
+    /// Refused if the index already holds snapshots of `new_id`. Returns how
+    /// many snapshots moved.
+    pub fn rename_job(&self, old_id: &str, new_id: &str) -> Result<usize> {
+        let mut conn = self
             .conn
             .lock()
             .map_err(|e| AmberError::Index(format!("Failed to acquire database lock: {}", e)))?;
 
-        // Get snapshot ID
-        let snapshot_id: i64 = conn
+        let tx = conn
+            .transaction()
+            .map_err(|e| sql_error("Failed to start transaction", e))?;
+        let taken: i64 = tx
             .query_row(
-                "SELECT id FROM snapshots WHERE job_id = ? AND timestamp = ?",
-                params![job_id, timestamp],
+                "SELECT COUNT(*) FROM snapshots WHERE job_id = ?",
+                params![new_id],
                 |row| row.get(0),
             )
-            .map_err(|_| AmberError::Index("Snapshot not found in index".to_string()))?;
+            .map_err(|e| sql_error("Failed to count snapshots", e))?;
+        if taken > 0 {
+            return Err(AmberError::ValidationError(format!(
+                "{} already holds {} snapshots of job '{}'",
+                self.db_path.display(),
+                taken,
+                new_id
+            )));
+        }
+        let renamed = tx
+            .execute(
+                "UPDATE snapshots SET job_id = ?2 WHERE job_id = ?1",
+                params![old_id, new_id],
+            )
+            .map_err(|e| sql_error("Failed to rename job snapshots", e))?;
+        tx.commit()
+            .map_err(|e| sql_error("Failed to commit transaction", e))?;
+        index_warmup::forget_job(old_id);
 
-        // Escape LIKE special characters in user input
-        let escaped = pattern
-            .replace('\\', "\\\\")
-            .replace('%', "\\%")
-            .replace('_', "\\_");
-        let search_pattern = format!("%{}%", escaped);
+        Ok(renamed)
+    }
 
-        let mut stmt = conn
-            .prepare(
-                "SELECT path, name, size, mtime, file_type
+    /// Search files by name pattern
+    pub fn search_files(
+        &self,
+        job_id: &str,
+        timestamp: i64,
+        pattern: &str,
+        limit: usize,
+    ) -> Result<Vec<FileNode>> {
+        self.search_files_modified_after(job_id, timestamp, pattern, limit, None, None)
+    }
+
+    /// `search_files` restricted like `get_directory_contents_paginated`:
+    /// files must be modified after `modified_after` (Unix ms) and belong to
+    /// `type_filter`, directories always match
+    pub fn search_files_modified_after(
+        &self,
+        job_id: &str,
+        timestamp: i64,
+        pattern: &str,
+        limit: usize,
+        modified_after: Option<i64>,
+        type_filter: Option<FileCategory>,
+    ) -> Result<Vec<FileNode>> {
+        self.read(|conn| {
+            // Get snapshot ID
+            let snapshot_id: i64 = conn
+                .query_row(
+                    "SELECT id FROM snapshots WHERE job_id = ? AND timestamp = ?",
+                    params![job_id, timestamp],
+                    |row| row.get(0),
+                )
+                .map_err(snapshot_lookup_error)?;
+
+            // Escape LIKE special characters in user input
+            let pattern = stored_form(pattern);
+            let escaped = pattern
+                .replace('\\', "\\\\")
+                .replace('%', "\\%")
+                .replace('_', "\\_");
+            let search_pattern = format!("%{}%", escaped);
+
+            let mut stmt = conn
+                .prepare(&format!(
+                    "SELECT path, name, size, mtime, file_type
                  FROM files
-                 WHERE snapshot_id = ? AND name LIKE ? ESCAPE '\\'
+                 WHERE snapshot_id = ?1 AND name LIKE ?2 ESCAPE '\\'
+                   AND (?4 IS NULL OR file_type = 'dir' OR mtime > ?4) AND {}
                  ORDER BY name ASC
-                 LIMIT ?",
-            )
-            .map_err(|e| AmberError::Index(format!("Failed to prepare query: {}", e)))?;
-
-        let files = stmt
-            .query_map(params![snapshot_id, search_pattern, limit as i64], |row| {
-                Ok(FileNode::from_db_row(
-                    row.get(0)?,
-                    row.get(1)?,
-                    row.get(2)?,
-                    row.get(3)?,
-                    &row.get::<_, String>(4)?,
+                 LIMIT ?3",
+                    category_condition(type_filter)
                 ))
-            })
-            .map_err(|e| AmberError::Index(format!("Failed to search files: {}", e)))?;
+                .map_err(|e| sql_error("Failed to prepare query", e))?;
+
+            let mtime_cutoff = mtime_cutoff_secs(modified_after);
+            let files = stmt
+                .query_map(
+                    params![snapshot_id, search_pattern, limit as i64, mtime_cutoff],
+                    |row| {
+                        Ok(FileNode::from_db_row(
+                            row.get(0)?,
+                            row.get(1)?,
+                            row.get(2)?,
+                            row.get(3)?,
+                            &row.get::<_, String>(4)?,
+                        ))
+                    },
+                )
+                .map_err(|e| sql_error("Failed to search files", e))?;
 
-        let mut result = Vec::new();
-        for f in files.flatten() {
-            result.push(f);
+            files
+                .collect::<rusqlite::Result<Vec<_>>>()
+                .map_err(|e| sql_error("Failed to search files", e))
+        })
+    }
+
+    /// Search files by name pattern in every snapshot of a job, so files
+    /// deleted since are still found. Results are ordered by path, then
+    /// newest snapshot first. With `collapse`, each path is returned once
+    /// with all its snapshots and `limit` counts paths instead of matches.
+    pub fn search_files_job(
+        &self,
+        job_id: &str,
+        pattern: &str,
+        limit: usize,
+        collapse: bool,
+    ) -> Result<Vec<JobSearchResult>> {
+        self.read(|conn| {
+            // Escape LIKE special characters in user input
+            let pattern = stored_form(pattern);
+            let escaped = pattern
+                .replace('\\', "\\\\")
+                .replace('%', "\\%")
+                .replace('_', "\\_");
+            let search_pattern = format!("%{}%", escaped);
+
+            // Collapsing reads until `limit` distinct paths have been seen
+            let row_limit = if collapse { -1 } else { limit as i64 };
+
+            let mut stmt = conn
+                .prepare(
+                    "SELECT f.path, f.name, f.size, f.mtime, f.file_type, s.timestamp,
+                        CASE WHEN f.parent_path = '' THEN f.name
+                             ELSE f.parent_path || '/' || f.name END AS rel_path
+                 FROM files f
+                 JOIN snapshots s ON f.snapshot_id = s.id
+                 WHERE s.job_id = ?1 AND f.name LIKE ?2 ESCAPE '\\'
+                 ORDER BY rel_path ASC, s.timestamp DESC
+                 LIMIT ?3",
+                )
+                .map_err(|e| sql_error("Failed to prepare query", e))?;
+
+            let rows = stmt
+                .query_map(params![job_id, search_pattern, row_limit], |row| {
+                    Ok((
+                        FileNode::from_db_row(
+                            row.get(0)?,
+                            row.get(1)?,
+                            row.get(2)?,
+                            row.get(3)?,
+                            &row.get::<_, String>(4)?,
+                        ),
+                        row.get::<_, i64>(5)?,
+                        row.get::<_, String>(6)?,
+                    ))
+                })
+                .map_err(|e| sql_error("Failed to search files", e))?;
+
+            let mut result: Vec<JobSearchResult> = Vec::new();
+            for row in rows {
+                let (file, timestamp, rel_path) =
+                    row.map_err(|e| sql_error("Failed to search files", e))?;
+                if collapse {
+                    if let Some(last) = result.last_mut().filter(|r| r.rel_path == rel_path) {
+                        last.snapshot_timestamps.push(timestamp);
+                        continue;
+                    }
+                    if result.len() == limit {
+                        break;
+                    }
+                }
+                result.push(JobSearchResult {
+                    file,
+                    rel_path,
+                    snapshot_timestamp: timestamp,
+                    snapshot_timestamps: vec![timestamp],
+                });
+            }
+
+            Ok(result)
+        })
+    }
+
+    /// Every complete snapshot of `job_id` that holds `path` (relative to the
+    /// snapshot root), newest first, with its size and mtime there. Backs
+    /// the "this file exists in N backups" picker for targeted restores.
+    pub fn find_path_in_snapshots(&self, job_id: &str, path: &str) -> Result<Vec<PathOccurrence>> {
+        let path = normalize_subtree(path)?;
+        let path = stored_form(&path);
+        let (parent_path, name) = match path.rsplit_once('/') {
+            Some((parent, name)) => (parent, name),
+            None => ("", path.as_ref()),
+        };
+        if name.is_empty() {
+            return Err(AmberError::InvalidPath(
+                "A path inside the snapshot is required".to_string(),
+            ));
         }
 
-        Ok(result)
+        self.read(|conn| {
+            let mut stmt = conn
+                .prepare(
+                    "SELECT s.timestamp, f.size, f.mtime
+                     FROM files f
+                     JOIN snapshots s ON f.snapshot_id = s.id
+                     WHERE s.job_id = ?1 AND s.resume_after IS NULL
+                       AND f.parent_path = ?2 AND f.name = ?3
+                     ORDER BY s.timestamp DESC",
+                )
+                .map_err(|e| sql_error("Failed to prepare query", e))?;
+            let rows = stmt
+                .query_map(params![job_id, parent_path, name], |row| {
+                    Ok(PathOccurrence {
+                        timestamp: row.get(0)?,
+                        size: row.get(1)?,
+                        mtime: row.get::<_, i64>(2)? * 1000,
+                    })
+                })
+                .map_err(|e| sql_error("Failed to find path in snapshots", e))?;
+            rows.collect::<rusqlite::Result<Vec<_>>>()
+                .map_err(|e| sql_error("Failed to find path in snapshots", e))
+        })
     }
 
     /// Search files globally across all snapshots using FTS5
@@ -1206,23 +4476,45 @@ impl IndexService {
         job_id: Option<&str>,
         limit: usize,
     ) -> Result<Vec<GlobalSearchResult>> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|e| AmberError::Index(format!("Failed to acquire database lock: {}", e)))?;
-
-        // Build the FTS5 query - support prefix matching with *
-        let fts_pattern = if pattern.contains('*') || pattern.contains('"') {
-            // User provided explicit FTS syntax
-            pattern.to_string()
-        } else {
-            // Add prefix matching for better UX (e.g., "read" matches "readme")
-            format!("{}*", pattern)
-        };
+        let mut result = Vec::new();
+        self.search_files_global_each(pattern, job_id, Some(limit), |item| {
+            result.push(item);
+            Ok(())
+        })?;
+        Ok(result)
+    }
 
-        // Query with optional job_id filter
-        let query = if job_id.is_some() {
-            r#"
+    /// Streaming variant of `search_files_global`: hands each match to
+    /// `on_result` as rows are read, so exports of millions of matches never
+    /// hold them all in memory. `limit: None` streams every match. Returning
+    /// an error from `on_result` stops the scan and is passed through.
+    /// Returns the number of results delivered.
+    pub fn search_files_global_each<F>(
+        &self,
+        pattern: &str,
+        job_id: Option<&str>,
+        limit: Option<usize>,
+        mut on_result: F,
+    ) -> Result<usize>
+    where
+        F: FnMut(GlobalSearchResult) -> Result<()>,
+    {
+        self.read(|conn| {
+            // Build the FTS5 query - support prefix matching with *
+            let pattern = stored_form(pattern);
+            let fts_pattern = if pattern.contains('*') || pattern.contains('"') {
+                // User provided explicit FTS syntax
+                pattern.to_string()
+            } else {
+                // Add prefix matching for better UX (e.g., "read" matches "readme")
+                format!("{}*", pattern)
+            };
+
+            // SQLite treats a negative LIMIT as "no limit"
+            let limit_val = limit.map(|l| l as i64).unwrap_or(-1);
+
+            // Query with optional job_id filter (?2 = NULL matches every job)
+            let query = r#"
             SELECT
                 f.path, f.name, f.size, f.mtime, f.file_type,
                 s.job_id, s.timestamp,
@@ -1231,54 +4523,30 @@ impl IndexService {
             JOIN files f ON fts.rowid = f.id
             JOIN snapshots s ON f.snapshot_id = s.id
             WHERE files_fts MATCH ?1
-              AND s.job_id = ?2
+              AND (?2 IS NULL OR s.job_id = ?2)
             ORDER BY rank
             LIMIT ?3
-            "#
-        } else {
-            r#"
-            SELECT
-                f.path, f.name, f.size, f.mtime, f.file_type,
-                s.job_id, s.timestamp,
-                bm25(files_fts, 10.0, 1.0) as rank
-            FROM files_fts fts
-            JOIN files f ON fts.rowid = f.id
-            JOIN snapshots s ON f.snapshot_id = s.id
-            WHERE files_fts MATCH ?1
-            ORDER BY rank
-            LIMIT ?2
-            "#
-        };
-
-        let mut stmt = conn
-            .prepare(query)
-            .map_err(|e| AmberError::Index(format!("Failed to prepare FTS query: {}", e)))?;
-
-        let mut result = Vec::new();
+            "#;
 
-        if let Some(jid) = job_id {
-            let rows = stmt
-                .query_map(params![fts_pattern, jid, limit as i64], |row| {
-                    Self::map_global_search_row(row)
-                })
-                .map_err(|e| AmberError::Index(format!("FTS search failed: {}", e)))?;
+            let mut stmt = conn
+                .prepare(query)
+                .map_err(|e| sql_error("Failed to prepare FTS query", e))?;
 
-            for item in rows.flatten() {
-                result.push(item);
-            }
-        } else {
             let rows = stmt
-                .query_map(params![fts_pattern, limit as i64], |row| {
+                .query_map(params![fts_pattern, job_id, limit_val], |row| {
                     Self::map_global_search_row(row)
                 })
-                .map_err(|e| AmberError::Index(format!("FTS search failed: {}", e)))?;
+                .map_err(|e| sql_error("FTS search failed", e))?;
 
-            for item in rows.flatten() {
-                result.push(item);
+            let mut delivered = 0;
+            for row in rows {
+                let item = row.map_err(|e| sql_error("FTS search failed", e))?;
+                on_result(item)?;
+                delivered += 1;
             }
-        }
 
-        Ok(result)
+            Ok(delivered)
+        })
     }
 
     /// Helper to map a row to GlobalSearchResult
@@ -1303,47 +4571,109 @@ impl IndexService {
         })
     }
 
-    /// Get snapshot statistics
-    pub fn get_snapshot_stats(&self, job_id: &str, timestamp: i64) -> Result<(i64, i64)> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|e| AmberError::Index(format!("Failed to acquire database lock: {}", e)))?;
-
-        conn.query_row(
-            "SELECT file_count, total_size FROM snapshots WHERE job_id = ? AND timestamp = ?",
-            params![job_id, timestamp],
-            |row| Ok((row.get(0)?, row.get(1)?)),
-        )
-        .map_err(|_| AmberError::Index("Snapshot not found".to_string()))
-    }
-
-    /// Get file type statistics for a snapshot (aggregated by extension)
-    pub fn get_file_type_stats(
+    /// Count what restoring `paths` (relative to the snapshot root) would
+    /// write, from the index alone. Directories include everything below
+    /// them; a path inside another selected one is only counted once.
+    pub fn restore_estimate(
         &self,
         job_id: &str,
         timestamp: i64,
-        limit: usize,
-    ) -> Result<Vec<FileTypeStats>> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|e| AmberError::Index(format!("Failed to acquire database lock: {}", e)))?;
-
-        // Get snapshot ID
-        let snapshot_id: i64 = conn
-            .query_row(
-                "SELECT id FROM snapshots WHERE job_id = ? AND timestamp = ?",
+        paths: &[String],
+    ) -> Result<RestoreEstimate> {
+        self.ensure_restorable(job_id, timestamp)?;
+        self.read(|conn| {
+            let snapshot_id: i64 = conn
+                .query_row(
+                    "SELECT id FROM snapshots WHERE job_id = ? AND timestamp = ?",
+                    params![job_id, timestamp],
+                    |row| row.get(0),
+                )
+                .map_err(snapshot_lookup_error)?;
+
+            // Drop selections nested in another one so no file counts twice
+            let mut selected: Vec<&str> = paths
+                .iter()
+                .map(|p| p.trim_matches('/'))
+                .filter(|p| !p.is_empty())
+                .collect();
+            selected.sort_unstable();
+            selected.dedup();
+            let mut roots: Vec<&str> = Vec::with_capacity(selected.len());
+            for path in selected {
+                let nested = roots.last().is_some_and(|root| {
+                    path.strip_prefix(root)
+                        .is_some_and(|rest| rest.starts_with('/'))
+                });
+                if !nested {
+                    roots.push(path);
+                }
+            }
+
+            // The entry itself, or anything below it (component prefix, no LIKE)
+            let mut stmt = conn
+                .prepare(
+                    "SELECT COUNT(*) FILTER (WHERE file_type != 'special'),
+                        COALESCE(SUM(size) FILTER (WHERE file_type != 'special'), 0),
+                        COUNT(*) FILTER (WHERE file_type = 'special')
+                 FROM files
+                 WHERE snapshot_id = ?1 AND file_type != 'dir'
+                   AND ((CASE WHEN parent_path = '' THEN name
+                              ELSE parent_path || '/' || name END) = ?2
+                        OR parent_path = ?2
+                        OR substr(parent_path, 1, length(?2) + 1) = ?2 || '/')",
+                )
+                .map_err(|e| sql_error("Failed to prepare query", e))?;
+
+            let mut estimate = RestoreEstimate::default();
+            for root in roots {
+                let (count, bytes, special): (i64, i64, i64) = stmt
+                    .query_row(params![snapshot_id, root], |row| {
+                        Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+                    })
+                    .map_err(|e| sql_error("Failed to estimate restore", e))?;
+                estimate.file_count += count as u64;
+                estimate.total_bytes += bytes as u64;
+                estimate.skipped_special += special as u64;
+            }
+
+            Ok(estimate)
+        })
+    }
+
+    /// Get snapshot statistics
+    pub fn get_snapshot_stats(&self, job_id: &str, timestamp: i64) -> Result<(i64, i64)> {
+        self.read(|conn| {
+            conn.query_row(
+                "SELECT file_count, total_size FROM snapshots WHERE job_id = ? AND timestamp = ?",
                 params![job_id, timestamp],
-                |row| row.get(0),
+                |row| Ok((row.get(0)?, row.get(1)?)),
             )
-            .map_err(|_| AmberError::Index("Snapshot not found in index".to_string()))?;
+            .map_err(snapshot_lookup_error)
+        })
+    }
 
-        // Query file extensions with aggregated stats
-        // Extract extension using SUBSTR and INSTR, group by it
-        let mut stmt = conn
-            .prepare(
-                r#"
+    /// Get file type statistics for a snapshot (aggregated by extension)
+    pub fn get_file_type_stats(
+        &self,
+        job_id: &str,
+        timestamp: i64,
+        limit: usize,
+    ) -> Result<Vec<FileTypeStats>> {
+        self.read(|conn| {
+            // Get snapshot ID
+            let snapshot_id: i64 = conn
+                .query_row(
+                    "SELECT id FROM snapshots WHERE job_id = ? AND timestamp = ?",
+                    params![job_id, timestamp],
+                    |row| row.get(0),
+                )
+                .map_err(snapshot_lookup_error)?;
+
+            // Query file extensions with aggregated stats
+            // Extract extension using SUBSTR and INSTR, group by it
+            let mut stmt = conn
+                .prepare(
+                    r#"
                 SELECT
                     CASE
                         WHEN INSTR(name, '.') > 0
@@ -1358,25 +4688,147 @@ impl IndexService {
                 ORDER BY total_size DESC
                 LIMIT ?
                 "#,
-            )
-            .map_err(|e| AmberError::Index(format!("Failed to prepare query: {}", e)))?;
-
-        let stats = stmt
-            .query_map(params![snapshot_id, limit as i64], |row| {
-                Ok(FileTypeStats {
-                    extension: row.get(0)?,
-                    count: row.get(1)?,
-                    total_size: row.get(2)?,
+                )
+                .map_err(|e| sql_error("Failed to prepare query", e))?;
+
+            let stats = stmt
+                .query_map(params![snapshot_id, limit as i64], |row| {
+                    Ok(FileTypeStats {
+                        extension: row.get(0)?,
+                        count: row.get(1)?,
+                        total_size: row.get(2)?,
+                    })
                 })
-            })
-            .map_err(|e| AmberError::Index(format!("Failed to query file types: {}", e)))?;
+                .map_err(|e| sql_error("Failed to query file types", e))?;
 
-        let mut result = Vec::new();
-        for s in stats.flatten() {
-            result.push(s);
+            stats
+                .collect::<rusqlite::Result<Vec<_>>>()
+                .map_err(|e| sql_error("Failed to query file types", e))
+        })
+    }
+
+    /// Count and total size of a snapshot's files per size range. `bounds` are
+    /// the ascending sizes where one bucket ends and the next begins, so n
+    /// bounds give n + 1 buckets; every bucket is returned, empty ones with
+    /// zero counts.
+    pub fn get_size_histogram(
+        &self,
+        job_id: &str,
+        timestamp: i64,
+        bounds: &[i64],
+    ) -> Result<Vec<SizeBucket>> {
+        if bounds.len() > MAX_SIZE_BUCKETS {
+            return Err(AmberError::ValidationError(format!(
+                "At most {} bucket bounds are allowed",
+                MAX_SIZE_BUCKETS
+            )));
+        }
+        if bounds.first().is_some_and(|first| *first <= 0)
+            || bounds.windows(2).any(|pair| pair[0] >= pair[1])
+        {
+            return Err(AmberError::ValidationError(
+                "Bucket bounds must be positive and strictly ascending".to_string(),
+            ));
         }
 
-        Ok(result)
+        self.read(|conn| {
+            let snapshot_id: i64 = conn
+                .query_row(
+                    "SELECT id FROM snapshots WHERE job_id = ? AND timestamp = ?",
+                    params![job_id, timestamp],
+                    |row| row.get(0),
+                )
+                .map_err(snapshot_lookup_error)?;
+
+            // Bounds are validated integers, so they can be inlined into the CASE
+            let arms: String = bounds
+                .iter()
+                .enumerate()
+                .map(|(i, bound)| format!("WHEN size < {} THEN {} ", bound, i))
+                .collect();
+            let mut stmt = conn
+                .prepare(&format!(
+                    "SELECT CASE {}ELSE {} END AS bucket, COUNT(*), COALESCE(SUM(size), 0)
+                 FROM files
+                 WHERE snapshot_id = ? AND file_type = 'file'
+                 GROUP BY bucket",
+                    arms,
+                    bounds.len()
+                ))
+                .map_err(|e| sql_error("Failed to prepare query", e))?;
+
+            let mut buckets: Vec<SizeBucket> = (0..=bounds.len())
+                .map(|i| SizeBucket {
+                    min_size: if i == 0 { 0 } else { bounds[i - 1] },
+                    max_size: bounds.get(i).copied(),
+                    count: 0,
+                    total_size: 0,
+                })
+                .collect();
+
+            let rows = stmt
+                .query_map(params![snapshot_id], |row| {
+                    Ok((row.get::<_, i64>(0)?, row.get(1)?, row.get(2)?))
+                })
+                .map_err(|e| sql_error("Failed to query size histogram", e))?;
+            for row in rows {
+                let (bucket, count, total_size) =
+                    row.map_err(|e| sql_error("Failed to query size histogram", e))?;
+                if let Some(bucket) = buckets.get_mut(bucket as usize) {
+                    bucket.count = count;
+                    bucket.total_size = total_size;
+                }
+            }
+
+            Ok(buckets)
+        })
+    }
+
+    /// Count and size of files with `extension` in every snapshot of a job,
+    /// oldest first. Extensions are matched the way `get_file_type_stats`
+    /// groups them; snapshots without a match report zero.
+    pub fn get_extension_growth(
+        &self,
+        job_id: &str,
+        extension: &str,
+    ) -> Result<Vec<ExtensionGrowthPoint>> {
+        self.read(|conn| {
+            let extension = extension.trim().trim_start_matches('.').to_lowercase();
+
+            let mut stmt = conn
+                .prepare(
+                    r#"
+                SELECT
+                    s.timestamp,
+                    COUNT(f.id) as count,
+                    COALESCE(SUM(f.size), 0) as total_size
+                FROM snapshots s
+                LEFT JOIN files f
+                    ON f.snapshot_id = s.id
+                    AND f.file_type = 'file'
+                    AND INSTR(f.name, '.') > 0
+                    AND LOWER(SUBSTR(f.name, INSTR(f.name, '.') + 1)) = ?2
+                WHERE s.job_id = ?1
+                GROUP BY s.id
+                ORDER BY s.timestamp ASC
+                "#,
+                )
+                .map_err(|e| sql_error("Failed to prepare query", e))?;
+
+            let points = stmt
+                .query_map(params![job_id, extension], |row| {
+                    Ok(ExtensionGrowthPoint {
+                        timestamp: row.get(0)?,
+                        count: row.get(1)?,
+                        total_size: row.get(2)?,
+                    })
+                })
+                .map_err(|e| sql_error("Failed to query extension growth", e))?;
+
+            points
+                .collect::<rusqlite::Result<Vec<_>>>()
+                .map_err(|e| sql_error("Failed to query extension growth", e))
+        })
     }
 
     /// Get largest files in a snapshot (for analytics)
@@ -1386,49 +4838,136 @@ impl IndexService {
         timestamp: i64,
         limit: usize,
     ) -> Result<Vec<LargestFile>> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|e| AmberError::Index(format!("Failed to acquire database lock: {}", e)))?;
-
-        // Get snapshot ID
-        let snapshot_id: i64 = conn
-            .query_row(
-                "SELECT id FROM snapshots WHERE job_id = ? AND timestamp = ?",
-                params![job_id, timestamp],
-                |row| row.get(0),
-            )
-            .map_err(|_| AmberError::Index("Snapshot not found in index".to_string()))?;
+        self.read(|conn| {
+            // Get snapshot ID
+            let snapshot_id: i64 = conn
+                .query_row(
+                    "SELECT id FROM snapshots WHERE job_id = ? AND timestamp = ?",
+                    params![job_id, timestamp],
+                    |row| row.get(0),
+                )
+                .map_err(snapshot_lookup_error)?;
 
-        // Query largest files
-        let mut stmt = conn
-            .prepare(
-                r#"
+            // Query largest files
+            let mut stmt = conn
+                .prepare(
+                    r#"
                 SELECT name, size, path
                 FROM files
                 WHERE snapshot_id = ? AND file_type = 'file'
                 ORDER BY size DESC
                 LIMIT ?
                 "#,
-            )
-            .map_err(|e| AmberError::Index(format!("Failed to prepare query: {}", e)))?;
-
-        let files = stmt
-            .query_map(params![snapshot_id, limit as i64], |row| {
-                Ok(LargestFile {
-                    name: row.get(0)?,
-                    size: row.get(1)?,
-                    path: row.get(2)?,
+                )
+                .map_err(|e| sql_error("Failed to prepare query", e))?;
+
+            let files = stmt
+                .query_map(params![snapshot_id, limit as i64], |row| {
+                    Ok(LargestFile {
+                        name: row.get(0)?,
+                        size: row.get(1)?,
+                        path: row.get(2)?,
+                    })
                 })
-            })
-            .map_err(|e| AmberError::Index(format!("Failed to query largest files: {}", e)))?;
+                .map_err(|e| sql_error("Failed to query largest files", e))?;
 
-        let mut result = Vec::new();
-        for f in files.flatten() {
-            result.push(f);
-        }
+            files
+                .collect::<rusqlite::Result<Vec<_>>>()
+                .map_err(|e| sql_error("Failed to query largest files", e))
+        })
+    }
 
-        Ok(result)
+    /// Most recently modified files in a snapshot, newest first
+    pub fn get_recent_files(
+        &self,
+        job_id: &str,
+        timestamp: i64,
+        limit: usize,
+    ) -> Result<Vec<FileNode>> {
+        self.read(|conn| {
+            let snapshot_id: i64 = conn
+                .query_row(
+                    "SELECT id FROM snapshots WHERE job_id = ? AND timestamp = ?",
+                    params![job_id, timestamp],
+                    |row| row.get(0),
+                )
+                .map_err(snapshot_lookup_error)?;
+
+            let mut stmt = conn
+                .prepare(
+                    r#"
+                SELECT path, name, size, mtime, file_type
+                FROM files
+                WHERE snapshot_id = ? AND file_type = 'file'
+                ORDER BY mtime DESC, name ASC
+                LIMIT ?
+                "#,
+                )
+                .map_err(|e| sql_error("Failed to prepare query", e))?;
+
+            let files = stmt
+                .query_map(params![snapshot_id, limit as i64], |row| {
+                    Ok(FileNode::from_db_row(
+                        row.get(0)?,
+                        row.get(1)?,
+                        row.get(2)?,
+                        row.get(3)?,
+                        &row.get::<_, String>(4)?,
+                    ))
+                })
+                .map_err(|e| sql_error("Failed to query recent files", e))?;
+
+            files
+                .collect::<rusqlite::Result<Vec<_>>>()
+                .map_err(|e| sql_error("Failed to query recent files", e))
+        })
+    }
+
+    /// Files in a snapshot carrying `flag`, largest first
+    pub fn get_flagged_files(
+        &self,
+        job_id: &str,
+        timestamp: i64,
+        flag: FileFlag,
+        limit: usize,
+    ) -> Result<Vec<FileNode>> {
+        self.read(|conn| {
+            let snapshot_id: i64 = conn
+                .query_row(
+                    "SELECT id FROM snapshots WHERE job_id = ? AND timestamp = ?",
+                    params![job_id, timestamp],
+                    |row| row.get(0),
+                )
+                .map_err(snapshot_lookup_error)?;
+
+            let mut stmt = conn
+                .prepare(
+                    r#"
+                SELECT path, name, size, mtime, file_type
+                FROM files
+                WHERE snapshot_id = ? AND (file_flags & ?) != 0
+                ORDER BY size DESC, name ASC
+                LIMIT ?
+                "#,
+                )
+                .map_err(|e| sql_error("Failed to prepare query", e))?;
+
+            let files = stmt
+                .query_map(params![snapshot_id, flag.bit(), limit as i64], |row| {
+                    Ok(FileNode::from_db_row(
+                        row.get(0)?,
+                        row.get(1)?,
+                        row.get(2)?,
+                        row.get(3)?,
+                        &row.get::<_, String>(4)?,
+                    ))
+                })
+                .map_err(|e| sql_error("Failed to query flagged files", e))?;
+
+            files
+                .collect::<rusqlite::Result<Vec<_>>>()
+                .map_err(|e| sql_error("Failed to query flagged files", e))
+        })
     }
 
     /// Get database path (for debugging)
@@ -1444,17 +4983,122 @@ impl IndexService {
             .map_err(|e| AmberError::Index(format!("Failed to acquire database lock: {}", e)))?;
 
         conn.execute("VACUUM", [])
-            .map_err(|e| AmberError::Index(format!("Failed to vacuum database: {}", e)))?;
+            .map_err(|e| sql_error("Failed to vacuum database", e))?;
+        set_meta(&conn, META_DELETIONS_SINCE_COMPACT, 0)?;
+        bump_meta(&conn, META_COMPACTIONS, 1)?;
+
+        Ok(())
+    }
+
+    /// Merge the search index's segments into one, which deletions and many
+    /// small indexing runs leave fragmented
+    pub fn optimize_fts(&self) -> Result<()> {
+        let conn = self
+            .conn
+            .lock()
+            .map_err(|e| AmberError::Index(format!("Failed to acquire database lock: {}", e)))?;
+
+        conn.execute("INSERT INTO files_fts(files_fts) VALUES('optimize')", [])
+            .map_err(|e| sql_error("Failed to optimize search index", e))?;
+
+        Ok(())
+    }
+
+    /// Re-tokenize every file into the search index, e.g. after it was found
+    /// out of step with the files table. Runs as one statement, so a run
+    /// cancelled through `cancel` is rolled back and leaves the old search
+    /// index in place.
+    pub fn rebuild_fts(&self, cancel: &CancelToken) -> Result<()> {
+        cancel.check()?;
+        let conn = self
+            .conn
+            .lock()
+            .map_err(|e| AmberError::Index(format!("Failed to acquire database lock: {}", e)))?;
+
+        // SQLite only stops a running statement when interrupted from outside
+        let done = Arc::new(AtomicBool::new(false));
+        let watcher = {
+            let done = done.clone();
+            let cancel = cancel.clone();
+            let interrupt = conn.get_interrupt_handle();
+            std::thread::spawn(move || {
+                while !done.load(Ordering::SeqCst) {
+                    if cancel.cancelled() {
+                        interrupt.interrupt();
+                        return;
+                    }
+                    std::thread::sleep(Duration::from_millis(20));
+                }
+            })
+        };
+        let result = conn.execute("INSERT INTO files_fts(files_fts) VALUES('rebuild')", []);
+        done.store(true, Ordering::SeqCst);
+        let _ = watcher.join();
+
+        match result {
+            Ok(_) => {
+                log::info!("Rebuilt search index of {}", self.db_path.display());
+                Ok(())
+            }
+            Err(_) if cancel.cancelled() => Err(AmberError::Cancelled),
+            Err(e) => Err(sql_error("Failed to rebuild search index", e)),
+        }
+    }
+
+    /// Write the WAL back into the database file and truncate it
+    pub fn checkpoint(&self) -> Result<()> {
+        let conn = self
+            .conn
+            .lock()
+            .map_err(|e| AmberError::Index(format!("Failed to acquire database lock: {}", e)))?;
+
+        conn.query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |_| Ok(()))
+            .map_err(|e| sql_error("Failed to checkpoint database", e))?;
 
         Ok(())
     }
 
+    /// Compact if the snapshots deleted since the last VACUUM have reached
+    /// the threshold. Runs on every open; returns whether it compacted.
+    pub fn compact_if_due(&self) -> Result<bool> {
+        if self.compact_after_deletions == 0
+            || self.deletions_since_compact()? < self.compact_after_deletions
+        {
+            return Ok(false);
+        }
+        log::info!(
+            "Compacting {:?} after {} snapshot deletions",
+            self.db_path,
+            self.compact_after_deletions
+        );
+        self.compact()?;
+        Ok(true)
+    }
+
+    /// Snapshots deleted since the last VACUUM
+    pub fn deletions_since_compact(&self) -> Result<u64> {
+        self.read(|conn| read_meta(conn, META_DELETIONS_SINCE_COMPACT))
+    }
+
+    /// Number of times this index has been compacted
+    pub fn compaction_count(&self) -> Result<u64> {
+        self.read(|conn| read_meta(conn, META_COMPACTIONS))
+    }
+
     /// Reconnect to the database (dev only)
     /// Used after replacing the database file to get a fresh connection
     #[cfg(debug_assertions)]
     pub fn reconnect(&self) -> Result<()> {
+        if self.is_in_memory() {
+            return Err(AmberError::Index(
+                "An in-memory index has no database file to reconnect to".to_string(),
+            ));
+        }
         let new_conn = Connection::open(&self.db_path)
-            .map_err(|e| AmberError::Index(format!("Failed to reconnect to database: {}", e)))?;
+            .map_err(|e| sql_error("Failed to reconnect to database", e))?;
+        new_conn
+            .busy_timeout(BUSY_TIMEOUT)
+            .map_err(|e| sql_error("Failed to set busy timeout", e))?;
 
         let mut conn = self
             .conn
@@ -1466,52 +5110,464 @@ impl IndexService {
         Ok(())
     }
 
-    /// Get connection for dev stats (dev only)
-    #[cfg(debug_assertions)]
-    pub fn get_connection_for_stats(&self) -> Result<std::sync::MutexGuard<'_, Connection>> {
-        self.conn
-            .lock()
-            .map_err(|e| AmberError::Index(format!("Failed to acquire database lock: {}", e)))
+    /// Get connection for dev stats (dev only)
+    #[cfg(debug_assertions)]
+    pub fn get_connection_for_stats(&self) -> Result<std::sync::MutexGuard<'_, Connection>> {
+        self.conn
+            .lock()
+            .map_err(|e| AmberError::Index(format!("Failed to acquire database lock: {}", e)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    /// In-memory index plus a temp dir for snapshot folders
+    fn create_test_service() -> (IndexService, TempDir) {
+        let temp_dir = TempDir::new().unwrap();
+        let service = IndexService::new_in_memory().unwrap();
+        (service, temp_dir)
+    }
+
+    #[test]
+    fn test_create_database() {
+        let temp_dir = TempDir::new().unwrap();
+        let service = IndexService::new(temp_dir.path()).unwrap();
+        assert!(service.db_path().exists());
+        assert!(!service.is_in_memory());
+    }
+
+    #[test]
+    fn test_in_memory_index_matches_file_backed() {
+        let temp_dir = TempDir::new().unwrap();
+        let first = temp_dir.path().join("first");
+        let second = temp_dir.path().join("second");
+        for (dir, files) in [
+            (&first, vec![("notes.txt", "a"), ("docs/report.pdf", "bb")]),
+            (
+                &second,
+                vec![("notes.txt", "a!"), ("docs/summary.md", "ccc")],
+            ),
+        ] {
+            for (name, content) in files {
+                let path = dir.join(name);
+                std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+                std::fs::write(path, content).unwrap();
+            }
+        }
+
+        let outcome = |service: &IndexService| {
+            let a = service
+                .index_snapshot("job1", 1000, first.to_str().unwrap())
+                .unwrap();
+            let b = service
+                .index_snapshot("job1", 2000, second.to_str().unwrap())
+                .unwrap();
+            let mut found: Vec<(i64, String)> = service
+                .search_files_global("report OR summary", None, 10)
+                .unwrap()
+                .into_iter()
+                .map(|r| (r.snapshot_timestamp, r.file.name))
+                .collect();
+            found.sort();
+            let diff = service.compare_snapshots("job1", 1000, 2000, None).unwrap();
+            let paths =
+                |entries: &[DiffEntry]| entries.iter().map(|e| e.path.clone()).collect::<Vec<_>>();
+            (
+                (a.file_count, a.total_size, b.file_count, b.total_size),
+                found,
+                paths(&diff.added),
+                paths(&diff.deleted),
+                paths(&diff.modified),
+            )
+        };
+
+        let memory = IndexService::new_in_memory().unwrap();
+        assert!(memory.is_in_memory());
+        let on_disk = IndexService::new(&temp_dir.path().join("db")).unwrap();
+
+        let expected = outcome(&on_disk);
+        assert_eq!(outcome(&memory), expected);
+        assert_eq!(expected.2, vec!["docs/summary.md"]);
+        assert_eq!(expected.3, vec!["docs/report.pdf"]);
+        assert_eq!(expected.4, vec!["notes.txt"]);
+    }
+
+    #[test]
+    fn test_index_snapshot() {
+        let (service, temp_dir) = create_test_service();
+
+        // Create test directory structure
+        let snapshot_dir = temp_dir.path().join("snapshot");
+        std::fs::create_dir_all(&snapshot_dir).unwrap();
+        std::fs::write(snapshot_dir.join("file1.txt"), "hello").unwrap();
+        std::fs::write(snapshot_dir.join("file2.txt"), "world").unwrap();
+        std::fs::create_dir_all(snapshot_dir.join("subdir")).unwrap();
+        std::fs::write(snapshot_dir.join("subdir/nested.txt"), "nested").unwrap();
+
+        let result = service
+            .index_snapshot("job1", 1700000000000, snapshot_dir.to_str().unwrap())
+            .unwrap();
+
+        assert_eq!(result.job_id, "job1");
+        assert_eq!(result.timestamp, 1700000000000);
+        assert_eq!(result.file_count, 3); // 3 files
+        assert!(result.total_size > 0);
+    }
+
+    /// Synthetic files spanning several insert chunks plus a partial tail
+    fn synthetic_files(count: usize) -> Vec<IndexedFile> {
+        (0..count)
+            .map(|i| IndexedFile {
+                path: format!("/snap/dir{}/file{}.txt", i % 7, i),
+                name: format!("file{}.txt", i),
+                parent_path: format!("dir{}", i % 7),
+                size: i as i64 * 3,
+                mtime: 1_700_000_000 + i as i64,
+                inode: if i % 5 == 0 { None } else { Some(i as i64) },
+                file_type: if i % 11 == 0 {
+                    FileType::Directory
+                } else {
+                    FileType::File
+                },
+                content_hash: (i % 3 == 0).then(|| format!("h{}", i % 13)),
+                flags: (i % 8) as i64,
+                link_target: None,
+                raw_path: None,
+            })
+            .collect()
+    }
+
+    /// Reference implementation: one INSERT per file
+    fn insert_files_per_row(tx: &Transaction, snapshot_id: i64, files: &[IndexedFile]) {
+        let mut stmt = tx
+            .prepare(
+                "INSERT INTO files (snapshot_id, path, name, parent_path, size, mtime, inode, file_type,
+                                    content_hash, file_flags)
+                 VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+            )
+            .unwrap();
+        for file in files {
+            stmt.execute(params![
+                snapshot_id,
+                file.path,
+                file.name,
+                file.parent_path,
+                file.size,
+                file.mtime,
+                file.inode,
+                file.file_type.as_str(),
+                file.content_hash,
+                file.flags,
+            ])
+            .unwrap();
+        }
+    }
+
+    fn insert_snapshot_row(tx: &Transaction, timestamp: i64) -> i64 {
+        tx.execute(
+            "INSERT INTO snapshots (job_id, timestamp, root_path) VALUES ('job1', ?, '/snap')",
+            params![timestamp],
+        )
+        .unwrap();
+        tx.last_insert_rowid()
+    }
+
+    type FileRow = (
+        String,
+        String,
+        String,
+        i64,
+        i64,
+        Option<i64>,
+        String,
+        Option<String>,
+        i64,
+    );
+
+    fn file_rows(conn: &Connection, snapshot_id: i64) -> Vec<FileRow> {
+        let mut stmt = conn
+            .prepare(
+                "SELECT path, name, parent_path, size, mtime, inode, file_type, content_hash, file_flags
+                 FROM files WHERE snapshot_id = ? ORDER BY path",
+            )
+            .unwrap();
+        stmt.query_map(params![snapshot_id], |row| {
+            Ok((
+                row.get(0)?,
+                row.get(1)?,
+                row.get(2)?,
+                row.get(3)?,
+                row.get(4)?,
+                row.get(5)?,
+                row.get(6)?,
+                row.get(7)?,
+                row.get(8)?,
+            ))
+        })
+        .unwrap()
+        .collect::<std::result::Result<_, _>>()
+        .unwrap()
+    }
+
+    fn fts_matches(conn: &Connection, snapshot_id: i64, query: &str) -> i64 {
+        conn.query_row(
+            "SELECT COUNT(*) FROM files_fts fts JOIN files f ON f.id = fts.rowid
+             WHERE files_fts MATCH ?1 AND f.snapshot_id = ?2",
+            params![query, snapshot_id],
+            |row| row.get(0),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_batch_insert_matches_per_row_insert() {
+        let (service, _temp_dir) = create_test_service();
+        let files = synthetic_files(ROWS_PER_INSERT * 2 + 37);
+
+        let mut conn = service.conn.lock().unwrap();
+        let tx = conn.transaction().unwrap();
+        let per_row_id = insert_snapshot_row(&tx, 1);
+        insert_files_per_row(&tx, per_row_id, &files);
+        let batched_id = insert_snapshot_row(&tx, 2);
+        service
+            .batch_insert_files(&tx, batched_id, &files, None)
+            .unwrap();
+        tx.commit().unwrap();
+
+        let expected = file_rows(&conn, per_row_id);
+        assert_eq!(expected.len(), files.len());
+        assert_eq!(file_rows(&conn, batched_id), expected);
+
+        // FTS triggers fire per row for multi-row inserts too
+        for query in ["file1*", "dir3", "\"file2000.txt\""] {
+            assert_eq!(
+                fts_matches(&conn, batched_id, query),
+                fts_matches(&conn, per_row_id, query),
+                "FTS mismatch for {}",
+                query
+            );
+        }
+        assert_eq!(fts_matches(&conn, batched_id, "txt"), files.len() as i64);
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use tempfile::TempDir;
+    #[test]
+    #[ignore] // Timing comparison: cargo test --release -- --ignored --nocapture
+    fn bench_batch_insert_vs_per_row() {
+        let files = synthetic_files(200_000);
+
+        let (per_row, _dir_a) = create_test_service();
+        let mut conn = per_row.conn.lock().unwrap();
+        let tx = conn.transaction().unwrap();
+        let id = insert_snapshot_row(&tx, 1);
+        let start = std::time::Instant::now();
+        insert_files_per_row(&tx, id, &files);
+        tx.commit().unwrap();
+        let per_row_elapsed = start.elapsed();
+        drop(conn);
+
+        let (batched, _dir_b) = create_test_service();
+        let mut conn = batched.conn.lock().unwrap();
+        let tx = conn.transaction().unwrap();
+        let id = insert_snapshot_row(&tx, 1);
+        let start = std::time::Instant::now();
+        batched.batch_insert_files(&tx, id, &files, None).unwrap();
+        tx.commit().unwrap();
+        let batched_elapsed = start.elapsed();
+
+        println!(
+            "{} files: per-row {:?}, multi-row {:?} ({:.2}x)",
+            files.len(),
+            per_row_elapsed,
+            batched_elapsed,
+            per_row_elapsed.as_secs_f64() / batched_elapsed.as_secs_f64()
+        );
+        assert!(batched_elapsed < per_row_elapsed);
+    }
 
-    fn create_test_service() -> (IndexService, TempDir) {
+    #[test]
+    fn test_concurrent_indexes_share_thread_budget() {
+        // Below the CPU count, so an unbounded walk would show up as excess
+        // (a two-CPU machine still gets two, the least that can overlap)
+        let budget = num_cpus::get().saturating_sub(1).max(2);
+        let pool = Arc::new(WalkPool::new(budget).unwrap());
         let temp_dir = TempDir::new().unwrap();
-        let service = IndexService::new(temp_dir.path()).unwrap();
-        (service, temp_dir)
+
+        // Wide tree so both walks have plenty of directories to fan out on
+        let snapshot_dir = temp_dir.path().join("snapshot");
+        for d in 0..200 {
+            let dir = snapshot_dir.join(format!("dir{}", d));
+            std::fs::create_dir_all(&dir).unwrap();
+            for f in 0..10 {
+                std::fs::write(dir.join(format!("file{}.txt", f)), "data").unwrap();
+            }
+        }
+
+        let start = Arc::new(std::sync::Barrier::new(2));
+        let handles: Vec<_> = (0..2)
+            .map(|i| {
+                let db_dir = temp_dir.path().join(format!("db{}", i));
+                let service = IndexService::new(&db_dir)
+                    .unwrap()
+                    .with_walk_pool(pool.clone());
+                let path = snapshot_dir.to_string_lossy().to_string();
+                let start = start.clone();
+                std::thread::spawn(move || {
+                    start.wait();
+                    service.index_snapshot_hashed("job1", 1700000000000, &path, false)
+                })
+            })
+            .collect();
+
+        for handle in handles {
+            let result = handle.join().unwrap().unwrap();
+            assert_eq!(result.file_count, 2000);
+        }
+
+        let peak = pool.peak_threads();
+        // Whether the walks actually overlapped is up to the scheduler; only
+        // the ceiling is guaranteed
+        assert!(peak <= budget, "peak {} exceeded budget {}", peak, budget);
     }
 
     #[test]
-    fn test_create_database() {
-        let (service, _temp_dir) = create_test_service();
-        assert!(service.db_path().exists());
+    fn test_get_recent_files_newest_first() {
+        let (service, temp_dir) = create_test_service();
+
+        let snapshot_dir = temp_dir.path().join("snapshot");
+        std::fs::create_dir_all(snapshot_dir.join("sub")).unwrap();
+        let fixtures = [
+            ("old.txt", 1_600_000_000),
+            ("sub/newest.txt", 1_700_000_300),
+            ("middle.txt", 1_700_000_000),
+            ("sub/newer.txt", 1_700_000_200),
+        ];
+        for (name, mtime) in fixtures {
+            let path = snapshot_dir.join(name);
+            std::fs::write(&path, name).unwrap();
+            let time = std::time::UNIX_EPOCH + Duration::from_secs(mtime);
+            std::fs::File::options()
+                .write(true)
+                .open(&path)
+                .unwrap()
+                .set_modified(time)
+                .unwrap();
+        }
+
+        service
+            .index_snapshot("job1", 1700000000000, snapshot_dir.to_str().unwrap())
+            .unwrap();
+
+        let recent = service.get_recent_files("job1", 1700000000000, 3).unwrap();
+        let names: Vec<_> = recent.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(names, vec!["newest.txt", "newer.txt", "middle.txt"]);
+        assert_eq!(recent[0].modified, 1_700_000_300 * 1000);
     }
 
     #[test]
-    fn test_index_snapshot() {
-        let (service, temp_dir) = create_test_service();
+    fn test_index_sets_large_and_stale_flags() {
+        let temp_dir = TempDir::new().unwrap();
+        let service = IndexService::new(temp_dir.path())
+            .unwrap()
+            .with_flag_thresholds(FileFlagThresholds {
+                large_bytes: 10,
+                stale_after_secs: 24 * 60 * 60,
+            });
 
-        // Create test directory structure
         let snapshot_dir = temp_dir.path().join("snapshot");
         std::fs::create_dir_all(&snapshot_dir).unwrap();
-        std::fs::write(snapshot_dir.join("file1.txt"), "hello").unwrap();
-        std::fs::write(snapshot_dir.join("file2.txt"), "world").unwrap();
-        std::fs::create_dir_all(snapshot_dir.join("subdir")).unwrap();
-        std::fs::write(snapshot_dir.join("subdir/nested.txt"), "nested").unwrap();
+        std::fs::write(snapshot_dir.join("big.bin"), [0u8; 20]).unwrap();
+        std::fs::write(snapshot_dir.join("edge.bin"), [0u8; 10]).unwrap();
+        std::fs::write(snapshot_dir.join("small.txt"), "hi").unwrap();
+        let old = std::fs::File::options()
+            .create(true)
+            .truncate(true)
+            .write(true)
+            .open(snapshot_dir.join("old.txt"))
+            .unwrap();
+        let two_days = std::time::Duration::from_secs(2 * 24 * 60 * 60);
+        old.set_modified(std::time::SystemTime::now() - two_days)
+            .unwrap();
 
-        let result = service
-            .index_snapshot("job1", 1700000000000, snapshot_dir.to_str().unwrap())
+        let ts = chrono::Utc::now().timestamp_millis();
+        service
+            .index_snapshot("job1", ts, snapshot_dir.to_str().unwrap())
             .unwrap();
 
-        assert_eq!(result.job_id, "job1");
-        assert_eq!(result.timestamp, 1700000000000);
-        assert_eq!(result.file_count, 3); // 3 files
-        assert!(result.total_size > 0);
+        let names = |flag| -> Vec<String> {
+            service
+                .get_flagged_files("job1", ts, flag, 100)
+                .unwrap()
+                .into_iter()
+                .map(|f| f.name)
+                .collect()
+        };
+        assert_eq!(names(FileFlag::Large), vec!["big.bin", "edge.bin"]);
+        assert_eq!(names(FileFlag::Stale), vec!["old.txt"]);
+        assert!(names(FileFlag::Duplicate).is_empty()); // no hashes yet
+    }
+
+    #[test]
+    fn test_flag_thresholds_from_preferences() {
+        assert_eq!(
+            FileFlagThresholds::from_preferences(1, 2),
+            FileFlagThresholds {
+                large_bytes: 1024 * 1024,
+                stale_after_secs: 2 * 24 * 60 * 60,
+            }
+        );
+        assert_eq!(
+            FileFlagThresholds::default(),
+            FileFlagThresholds {
+                large_bytes: 100 * 1024 * 1024,
+                stale_after_secs: 365 * 24 * 60 * 60,
+            }
+        );
+        assert_eq!(
+            FileFlagThresholds::from_preferences(u64::MAX, u64::MAX),
+            FileFlagThresholds {
+                large_bytes: i64::MAX,
+                stale_after_secs: i64::MAX,
+            }
+        );
+    }
+
+    #[test]
+    fn test_compute_file_flags_marks_duplicates() {
+        let file = |name: &str, size: i64, hash: Option<&str>| IndexedFile {
+            path: format!("/snap/{}", name),
+            name: name.to_string(),
+            parent_path: String::new(),
+            size,
+            mtime: 1_700_000_000,
+            inode: None,
+            file_type: FileType::File,
+            content_hash: hash.map(str::to_string),
+            flags: 0,
+            link_target: None,
+            raw_path: None,
+        };
+        let mut files = vec![
+            file("a.jpg", 5, Some("h1")),
+            file("copy-of-a.jpg", 5, Some("h1")),
+            file("b.jpg", 5, Some("h2")),
+            file("same-hash-other-size.jpg", 6, Some("h1")),
+            file("unhashed.jpg", 5, None),
+        ];
+
+        compute_file_flags(&mut files, &FileFlagThresholds::default(), 1_700_000_000);
+
+        let dup: Vec<_> = files
+            .iter()
+            .filter(|f| f.flags & FileFlag::Duplicate.bit() != 0)
+            .map(|f| f.name.as_str())
+            .collect();
+        assert_eq!(dup, vec!["a.jpg", "copy-of-a.jpg"]);
+        assert!(files
+            .iter()
+            .all(|f| f.flags & !FileFlag::Duplicate.bit() == 0));
     }
 
     #[test]
@@ -1642,6 +5698,50 @@ mod tests {
         assert_eq!(stats.last_snapshot_ms, Some(ts2));
     }
 
+    #[test]
+    fn test_job_logical_footprint_counts_shared_files_once() {
+        let (service, temp_dir) = create_test_service();
+        let snap = |name: &str| {
+            let dir = temp_dir.path().join(name);
+            std::fs::create_dir_all(&dir).unwrap();
+            dir
+        };
+        let link_or_copy = |from: &Path, to: &Path| {
+            #[cfg(unix)]
+            std::fs::hard_link(from, to).unwrap();
+            #[cfg(not(unix))]
+            std::fs::copy(from, to).unwrap();
+        };
+
+        // s1: a(100) b(200); s2: links to a, b plus c(50); s3: link a, b rewritten (300)
+        let s1 = snap("s1");
+        std::fs::write(s1.join("a.bin"), vec![0u8; 100]).unwrap();
+        std::fs::write(s1.join("b.bin"), vec![0u8; 200]).unwrap();
+        let s2 = snap("s2");
+        link_or_copy(&s1.join("a.bin"), &s2.join("a.bin"));
+        link_or_copy(&s1.join("b.bin"), &s2.join("b.bin"));
+        std::fs::write(s2.join("c.bin"), vec![0u8; 50]).unwrap();
+        let s3 = snap("s3");
+        link_or_copy(&s1.join("a.bin"), &s3.join("a.bin"));
+        link_or_copy(&s2.join("c.bin"), &s3.join("c.bin"));
+        std::fs::write(s3.join("b.bin"), vec![0u8; 300]).unwrap();
+
+        for (i, dir) in [&s1, &s2, &s3].iter().enumerate() {
+            service
+                .index_snapshot("job1", 1700000000000 + i as i64, dir.to_str().unwrap())
+                .unwrap();
+        }
+
+        let footprint = service.get_job_logical_footprint("job1").unwrap();
+        assert_eq!(footprint.naive_size_bytes, 300 + 350 + 450);
+        assert_eq!(footprint.unique_size_bytes, 100 + 200 + 50 + 300);
+        assert_eq!(footprint.unique_files, 4);
+
+        let empty = service.get_job_logical_footprint("nonexistent").unwrap();
+        assert_eq!(empty.unique_size_bytes, 0);
+        assert_eq!(empty.naive_size_bytes, 0);
+    }
+
     #[test]
     fn test_get_snapshot_density() {
         let (service, temp_dir) = create_test_service();
@@ -1695,6 +5795,117 @@ mod tests {
         assert!(empty.is_empty());
     }
 
+    #[test]
+    fn test_get_extension_growth() {
+        let (service, temp_dir) = create_test_service();
+
+        // Each snapshot adds two more photos; the text file never changes
+        let timestamps = [1704067200000_i64, 1706745600000_i64, 1709251200000_i64];
+        for (i, ts) in timestamps.iter().enumerate() {
+            let snapshot = temp_dir.path().join(format!("snapshot{}", i));
+            std::fs::create_dir_all(snapshot.join("photos")).unwrap();
+            std::fs::write(snapshot.join("notes.txt"), "notes").unwrap();
+            for n in 0..(i + 1) * 2 {
+                std::fs::write(
+                    snapshot.join("photos").join(format!("img{}.JPG", n)),
+                    "0123456789",
+                )
+                .unwrap();
+            }
+            service
+                .index_snapshot("job1", *ts, snapshot.to_str().unwrap())
+                .unwrap();
+        }
+
+        let growth = service.get_extension_growth("job1", ".jpg").unwrap();
+        let series: Vec<_> = growth
+            .iter()
+            .map(|p| (p.timestamp, p.count, p.total_size))
+            .collect();
+        assert_eq!(
+            series,
+            vec![
+                (timestamps[0], 2, 20),
+                (timestamps[1], 4, 40),
+                (timestamps[2], 6, 60),
+            ]
+        );
+
+        // Unknown extensions still produce one zero point per snapshot
+        let none = service.get_extension_growth("job1", "png").unwrap();
+        assert_eq!(none.len(), 3);
+        assert!(none.iter().all(|p| p.count == 0 && p.total_size == 0));
+
+        assert!(service
+            .get_extension_growth("nonexistent", "jpg")
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_get_size_histogram() {
+        let (service, temp_dir) = create_test_service();
+
+        let snapshot = temp_dir.path().join("snapshot");
+        std::fs::create_dir_all(snapshot.join("nested")).unwrap();
+        for (name, size) in [
+            ("empty", 0),
+            ("tiny", 5),
+            ("nested/edge", 9),
+            ("ten", 10),
+            ("nested/fifty", 50),
+            ("hundred", 100),
+            ("nested/big", 2000),
+        ] {
+            std::fs::write(snapshot.join(name), vec![b'x'; size]).unwrap();
+        }
+        service
+            .index_snapshot("job1", 1704067200000, snapshot.to_str().unwrap())
+            .unwrap();
+
+        let histogram = service
+            .get_size_histogram("job1", 1704067200000, &[10, 100, 1000, 5000])
+            .unwrap();
+        let buckets: Vec<_> = histogram
+            .iter()
+            .map(|b| (b.min_size, b.max_size, b.count, b.total_size))
+            .collect();
+        assert_eq!(
+            buckets,
+            vec![
+                (0, Some(10), 3, 14),
+                (10, Some(100), 2, 60),
+                (100, Some(1000), 1, 100),
+                (1000, Some(5000), 1, 2000),
+                (5000, None, 0, 0),
+            ]
+        );
+
+        // Default bounds: everything but the 2000-byte file is under 1K
+        let default = service
+            .get_size_histogram("job1", 1704067200000, &DEFAULT_SIZE_BUCKETS)
+            .unwrap();
+        let counts: Vec<_> = default.iter().map(|b| b.count).collect();
+        assert_eq!(counts, vec![6, 1, 0, 0]);
+
+        // No bounds: one bucket holding everything
+        let all = service
+            .get_size_histogram("job1", 1704067200000, &[])
+            .unwrap();
+        assert_eq!(all.len(), 1);
+        assert_eq!((all[0].count, all[0].total_size), (7, 2174));
+
+        assert!(service
+            .get_size_histogram("job1", 1704067200000, &[100, 10])
+            .is_err());
+        assert!(service
+            .get_size_histogram("job1", 1704067200000, &[0, 10])
+            .is_err());
+        assert!(service
+            .get_size_histogram("nonexistent", 1704067200000, &[10])
+            .is_err());
+    }
+
     #[test]
     fn test_delete_snapshot() {
         let (service, temp_dir) = create_test_service();
@@ -1756,6 +5967,82 @@ mod tests {
         assert_eq!(total_size, 11); // 5 + 6 bytes
     }
 
+    #[test]
+    fn test_accented_search_follows_folding_mode() {
+        let (service, temp_dir) = create_test_service();
+        let snapshot = temp_dir.path().join("snapshot");
+        std::fs::create_dir_all(&snapshot).unwrap();
+        std::fs::write(snapshot.join("café.txt"), "accented").unwrap();
+        std::fs::write(snapshot.join("cafe.txt"), "plain").unwrap();
+        service
+            .index_snapshot("job1", 1700000000000, snapshot.to_str().unwrap())
+            .unwrap();
+
+        let names = |query: &str| {
+            let mut names: Vec<String> = service
+                .search_files_global(query, None, 10)
+                .unwrap()
+                .into_iter()
+                .map(|r| r.file.name)
+                .collect();
+            names.sort();
+            names
+        };
+
+        // Default: folded, both spellings match either query
+        assert!(service.uses_diacritic_folding().unwrap());
+        assert_eq!(names("café"), vec!["cafe.txt", "café.txt"]);
+        assert_eq!(names("cafe"), vec!["cafe.txt", "café.txt"]);
+
+        // Exact: rebuilt in place, accents now distinguish the files
+        assert!(service.set_diacritic_folding(false).unwrap());
+        assert!(!service.set_diacritic_folding(false).unwrap());
+        assert!(!service.uses_diacritic_folding().unwrap());
+        assert_eq!(names("café"), vec!["café.txt"]);
+        assert_eq!(names("cafe"), vec!["cafe.txt"]);
+
+        // Triggers were recreated too: new rows are searchable
+        std::fs::write(snapshot.join("décor.txt"), "new").unwrap();
+        service
+            .index_snapshot("job1", 1700000000000, snapshot.to_str().unwrap())
+            .unwrap();
+        assert_eq!(names("décor"), vec!["décor.txt"]);
+        assert!(names("decor").is_empty());
+
+        assert!(service.set_diacritic_folding(true).unwrap());
+        assert_eq!(names("decor"), vec!["décor.txt"]);
+    }
+
+    #[test]
+    fn test_open_reports_folding_mismatch_without_rebuilding() {
+        let temp_dir = TempDir::new().unwrap();
+        let service = IndexService::new(temp_dir.path()).unwrap();
+        assert!(!service.diacritic_folding_outdated());
+        assert!(service.set_diacritic_folding(false).unwrap());
+        assert!(service.diacritic_folding_outdated());
+        drop(service);
+
+        // Reopened under the default (folding) preference: left as it was
+        let service = IndexService::new(temp_dir.path()).unwrap();
+        assert!(!service.uses_diacritic_folding().unwrap());
+        assert!(service.diacritic_folding_outdated());
+
+        assert!(service.apply_configured_diacritic_folding().unwrap());
+        assert!(service.uses_diacritic_folding().unwrap());
+        assert!(!service.diacritic_folding_outdated());
+    }
+
+    #[test]
+    fn test_stored_form_is_nfc_unless_raw_names_are_kept() {
+        let nfd = "Cafe\u{301}.txt";
+        let nfc = "Caf\u{e9}.txt";
+        assert_eq!(stored_form_with(nfd, true), nfc);
+        assert!(matches!(stored_form_with(nfc, true), Cow::Borrowed(_)));
+
+        assert_eq!(stored_form_with(nfd, false), nfd);
+        assert!(matches!(stored_form_with(nfd, false), Cow::Borrowed(_)));
+    }
+
     #[test]
     fn test_search_files_global_fts5() {
         let (service, temp_dir) = create_test_service();
@@ -1806,6 +6093,55 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_search_files_global_each_matches_collected() {
+        let (service, temp_dir) = create_test_service();
+
+        for (job, ts) in [("job1", 1700000000000_i64), ("job2", 1700000001000)] {
+            let dir = temp_dir.path().join(job);
+            std::fs::create_dir_all(dir.join("docs")).unwrap();
+            for i in 0..25 {
+                std::fs::write(dir.join(format!("docs/report{}.txt", i)), "r").unwrap();
+            }
+            std::fs::write(dir.join("notes.md"), "n").unwrap();
+            service
+                .index_snapshot(job, ts, dir.to_str().unwrap())
+                .unwrap();
+        }
+
+        for job_id in [None, Some("job2")] {
+            let collected = service.search_files_global("report", job_id, 1000).unwrap();
+
+            let mut streamed = Vec::new();
+            let delivered = service
+                .search_files_global_each("report", job_id, None, |r| {
+                    streamed.push((r.file.path, r.job_id, r.snapshot_timestamp));
+                    Ok(())
+                })
+                .unwrap();
+
+            let collected: Vec<_> = collected
+                .into_iter()
+                .map(|r| (r.file.path, r.job_id, r.snapshot_timestamp))
+                .collect();
+            assert_eq!(delivered, collected.len());
+            assert_eq!(streamed, collected);
+        }
+
+        // A callback error stops the scan early
+        let mut seen = 0;
+        let err = service.search_files_global_each("report", None, None, |_| {
+            seen += 1;
+            if seen == 3 {
+                Err(AmberError::Index("stop".into()))
+            } else {
+                Ok(())
+            }
+        });
+        assert!(err.is_err());
+        assert_eq!(seen, 3);
+    }
+
     #[test]
     fn test_compare_snapshots() {
         let (service, temp_dir) = create_test_service();
@@ -1884,4 +6220,202 @@ mod tests {
         assert_eq!(diff.summary.total_modified, 1);
         assert!(diff.modified.iter().any(|e| e.path == "docs/readme.md"));
     }
+
+    #[test]
+    fn test_busy_reads_are_retried_until_the_lock_is_released() {
+        let dir = TempDir::new().unwrap();
+        let db = dir.path().join("contended.db");
+        let holder = Connection::open(&db).unwrap();
+        holder
+            .execute_batch("CREATE TABLE t (x INTEGER); INSERT INTO t VALUES (1);")
+            .unwrap();
+        let reader = Connection::open(&db).unwrap();
+        reader.busy_timeout(Duration::ZERO).unwrap();
+        let query = |conn: &Connection| {
+            conn.query_row("SELECT x FROM t", [], |row| row.get::<_, i64>(0))
+                .map_err(|e| sql_error("Failed to read", e))
+        };
+
+        // Rollback journal: an exclusive transaction keeps every reader out
+        holder.execute_batch("BEGIN EXCLUSIVE").unwrap();
+        assert!(matches!(query(&reader), Err(AmberError::IndexBusy(_))));
+
+        let release = std::thread::spawn(move || {
+            std::thread::sleep(READ_RETRY_BACKOFF);
+            holder.execute_batch("COMMIT").unwrap();
+        });
+        let mut attempts = 0;
+        let value = retry_busy(|| {
+            attempts += 1;
+            query(&reader)
+        })
+        .unwrap();
+        release.join().unwrap();
+
+        assert_eq!(value, 1);
+        assert!(attempts > 1, "succeeded without contention");
+    }
+
+    #[test]
+    fn test_compare_snapshots_reports_move_as_rename() {
+        let (service, temp_dir) = create_test_service();
+
+        let snap_a = temp_dir.path().join("snap_a");
+        std::fs::create_dir_all(snap_a.join("inbox")).unwrap();
+        std::fs::write(snap_a.join("inbox/report.pdf"), "quarterly numbers").unwrap();
+        std::fs::write(snap_a.join("notes.txt"), "unhashed").unwrap();
+
+        let snap_b = temp_dir.path().join("snap_b");
+        std::fs::create_dir_all(snap_b.join("archive/2024")).unwrap();
+        std::fs::write(snap_b.join("archive/2024/report.pdf"), "quarterly numbers").unwrap();
+        std::fs::write(snap_b.join("notes-moved.txt"), "unhashed").unwrap();
+
+        let ts_a = 1700000000000_i64;
+        let ts_b = 1700000001000_i64;
+        service
+            .index_snapshot("job1", ts_a, snap_a.to_str().unwrap())
+            .unwrap();
+        service
+            .index_snapshot("job1", ts_b, snap_b.to_str().unwrap())
+            .unwrap();
+
+        // Hash only the report; notes.txt has no hash and must stay add/delete
+        service
+            .conn
+            .lock()
+            .unwrap()
+            .execute(
+                "UPDATE files SET content_hash = 'abc123' WHERE name = 'report.pdf'",
+                [],
+            )
+            .unwrap();
+
+        let diff = service.compare_snapshots("job1", ts_a, ts_b, None).unwrap();
+
+        assert_eq!(diff.renamed.len(), 1);
+        assert_eq!(diff.renamed[0].from, "inbox/report.pdf");
+        assert_eq!(diff.renamed[0].to, "archive/2024/report.pdf");
+        assert_eq!(diff.summary.total_renamed, 1);
+
+        let added: Vec<_> = diff.added.iter().map(|e| e.path.as_str()).collect();
+        let deleted: Vec<_> = diff.deleted.iter().map(|e| e.path.as_str()).collect();
+        assert_eq!(added, vec!["notes-moved.txt"]);
+        assert_eq!(deleted, vec!["notes.txt"]);
+        assert_eq!(diff.summary.size_delta, 0);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_compare_snapshots_reports_symlink_retarget() {
+        let (service, temp_dir) = create_test_service();
+        let snap = temp_dir.path().join("snap");
+        for release in ["v1", "v2"] {
+            std::fs::create_dir_all(snap.join("app").join(release)).unwrap();
+            std::fs::write(
+                snap.join("app").join(release).join("config.toml"),
+                "port = 80",
+            )
+            .unwrap();
+        }
+        std::os::unix::fs::symlink("v1", snap.join("app/current")).unwrap();
+        std::os::unix::fs::symlink("v1", snap.join("app/stable")).unwrap();
+
+        let ts_a = 1700000000000_i64;
+        let ts_b = 1700000001000_i64;
+        service
+            .index_snapshot("job1", ts_a, snap.to_str().unwrap())
+            .unwrap();
+
+        // Same path, same size of link text; only where it points changes
+        std::fs::remove_file(snap.join("app/current")).unwrap();
+        std::os::unix::fs::symlink("v2", snap.join("app/current")).unwrap();
+        service
+            .index_snapshot("job1", ts_b, snap.to_str().unwrap())
+            .unwrap();
+
+        let diff = service.compare_snapshots("job1", ts_a, ts_b, None).unwrap();
+        assert_eq!(
+            diff.symlink_retargeted,
+            vec![SymlinkRetarget {
+                path: "app/current".to_string(),
+                target_a: "v1".to_string(),
+                target_b: "v2".to_string(),
+            }]
+        );
+        assert_eq!(diff.summary.total_symlink_retargeted, 1);
+        assert!(diff.added.is_empty() && diff.deleted.is_empty() && diff.modified.is_empty());
+
+        // Scoped out by subtree and ignore globs like any other change
+        let elsewhere = service
+            .compare_snapshots_under("job1", ts_a, ts_b, Some("docs"), &[], None)
+            .unwrap();
+        assert!(elsewhere.symlink_retargeted.is_empty());
+        let ignored = service
+            .compare_snapshots_under("job1", ts_a, ts_b, None, &["app/current".to_string()], None)
+            .unwrap();
+        assert!(ignored.symlink_retargeted.is_empty());
+    }
+
+    #[test]
+    fn test_breadcrumbs_follow_indexed_parent_paths() {
+        let (service, temp_dir) = create_test_service();
+        let snap = temp_dir.path().join("2024-03-01-120000");
+        std::fs::create_dir_all(snap.join("Фото/été 2023/日本")).unwrap();
+        std::fs::write(snap.join("Фото/été 2023/日本/富士.jpg"), "jpg").unwrap();
+
+        let ts = 1709294400000_i64;
+        service
+            .index_snapshot("job1", ts, snap.to_str().unwrap())
+            .unwrap();
+
+        let root = service.resolve_breadcrumbs("job1", ts, "").unwrap();
+        assert_eq!(
+            root,
+            vec![Crumb {
+                name: "2024-03-01-120000".to_string(),
+                parent_path: String::new()
+            }]
+        );
+
+        let crumbs = service
+            .resolve_breadcrumbs("job1", ts, "Фото/été 2023/日本")
+            .unwrap();
+        let pairs: Vec<_> = crumbs
+            .iter()
+            .map(|c| (c.name.as_str(), c.parent_path.as_str()))
+            .collect();
+        assert_eq!(
+            pairs,
+            vec![
+                ("2024-03-01-120000", ""),
+                ("Фото", "Фото"),
+                ("été 2023", "Фото/été 2023"),
+                ("日本", "Фото/été 2023/日本"),
+            ]
+        );
+
+        // Each crumb lists the directory holding the next crumb
+        for pair in crumbs.windows(2) {
+            let listing = service
+                .get_directory_contents("job1", ts, &pair[0].parent_path)
+                .unwrap();
+            assert!(listing.iter().any(|f| f.name == pair[1].name));
+        }
+        let leaf = service
+            .get_directory_contents("job1", ts, &crumbs[3].parent_path)
+            .unwrap();
+        assert_eq!(leaf[0].name, "富士.jpg");
+    }
+
+    #[test]
+    fn test_breadcrumbs_normalize_slashes_and_reject_parent_segments() {
+        let crumbs = breadcrumbs_for("snap", "/a//b/./c/").unwrap();
+        let paths: Vec<_> = crumbs.iter().map(|c| c.parent_path.as_str()).collect();
+        assert_eq!(paths, vec!["", "a", "a/b", "a/b/c"]);
+
+        assert!(breadcrumbs_for("snap", "a/../b").is_err());
+
+        let (service, _temp_dir) = create_test_service();
+        assert!(service.resolve_breadcrumbs("job1", 1, "a").is_err());
+    }
 }
@@ -0,0 +1,194 @@
+//! Index warm-up when a backup drive is connected
+//!
+//! A destination's index lives on the drive, so the first browse after
+//! plugging it in waits on a cold file. When the volume watcher reports a
+//! mount, every job backing up to that volume opens its index and the root
+//! folder of its latest snapshots is cached in memory, so the first listing
+//! the UI asks for is answered without touching the drive. The warm-up stops,
+//! and its cache entries are dropped, when the volume goes away again.
+
+use crate::error::Result;
+use crate::services::cancel_token::{self, CancelRegistry, CancelToken};
+use crate::services::index_service::IndexService;
+use crate::services::manifest_service;
+use crate::services::store::Store;
+use crate::services::volume_watcher::{VolumeEvent, VolumeWatcher};
+use crate::types::job::SyncJob;
+use crate::types::snapshot::FileNode;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+
+/// Snapshots per job warmed up when no preference says otherwise
+pub const DEFAULT_WARMUP_SNAPSHOTS: usize = 3;
+
+static WARMUP_SNAPSHOTS: AtomicUsize = AtomicUsize::new(DEFAULT_WARMUP_SNAPSHOTS);
+
+/// How many of each job's latest snapshots to warm up on mount (0 = off)
+pub fn configure(snapshots: usize) {
+    WARMUP_SNAPSHOTS.store(snapshots, Ordering::SeqCst);
+}
+
+/// Destination, job id and timestamp of a cached root listing
+type RootKey = (String, String, i64);
+
+fn root_cache() -> &'static Mutex<HashMap<RootKey, Vec<FileNode>>> {
+    static ROOTS: OnceLock<Mutex<HashMap<RootKey, Vec<FileNode>>>> = OnceLock::new();
+    ROOTS.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+fn under(dest_path: &str, mount_path: &str) -> bool {
+    Path::new(dest_path).starts_with(mount_path)
+}
+
+/// The root folder of a snapshot: from the warm-up cache when it holds it,
+/// otherwise from `load`
+pub fn root_contents(
+    dest_path: &str,
+    job_id: &str,
+    timestamp: i64,
+    load: impl FnOnce() -> Result<Vec<FileNode>>,
+) -> Result<Vec<FileNode>> {
+    let key = (dest_path.to_string(), job_id.to_string(), timestamp);
+    let cached = root_cache()
+        .lock()
+        .ok()
+        .and_then(|roots| roots.get(&key).cloned());
+    match cached {
+        Some(files) => Ok(files),
+        None => load(),
+    }
+}
+
+/// Drop a snapshot's cached root, once it is deleted or indexed again
+pub fn forget(job_id: &str, timestamp: i64) {
+    if let Ok(mut roots) = root_cache().lock() {
+        roots.retain(|(_, job, ts), _| !(job == job_id && *ts == timestamp));
+    }
+}
+
+/// Drop every cached root of `job_id`
+pub fn forget_job(job_id: &str) {
+    if let Ok(mut roots) = root_cache().lock() {
+        roots.retain(|(_, job, _), _| job != job_id);
+    }
+}
+
+/// Open `dest_path`'s index and cache the root folder of `job_id`'s latest
+/// `count` snapshots. Stops early once `cancel` is cancelled. Returns how
+/// many roots were cached; a destination without an index caches none.
+pub fn warm_destination(
+    dest_path: &str,
+    job_id: &str,
+    count: usize,
+    cancel: &CancelToken,
+) -> Result<usize> {
+    if count == 0 || !manifest_service::get_index_path(dest_path).exists() {
+        return Ok(0);
+    }
+    let index = IndexService::for_destination(dest_path)?;
+    let latest: Vec<i64> = index
+        .list_snapshots(job_id)?
+        .into_iter()
+        .filter(|s| !s.metadata_only)
+        .take(count)
+        .map(|s| s.timestamp)
+        .collect();
+
+    let mut cached = 0;
+    for timestamp in latest {
+        if cancel.cancelled() {
+            break;
+        }
+        let files = index.get_directory_contents(job_id, timestamp, "")?;
+
+        // Checked under the lock so `forget_mount` can't run in between
+        let Ok(mut roots) = root_cache().lock() else {
+            break;
+        };
+        if cancel.cancelled() {
+            break;
+        }
+        roots.insert(
+            (dest_path.to_string(), job_id.to_string(), timestamp),
+            files,
+        );
+        cached += 1;
+    }
+    Ok(cached)
+}
+
+/// Warm up every job in `jobs` whose destination is on `mount_path`, with
+/// the configured snapshot count. Runs as `index-warmup:<mount path>` in
+/// `operations`, and is cancelled by `forget_mount` or `cancel_operation`.
+pub fn warm_mount(operations: &CancelRegistry, mount_path: &str, jobs: &[SyncJob]) -> usize {
+    let count = WARMUP_SNAPSHOTS.load(Ordering::SeqCst);
+    if count == 0 {
+        return 0;
+    }
+
+    let op_id = cancel_token::operation_id(cancel_token::INDEX_WARMUP, mount_path);
+    let cancel = operations.register(&op_id);
+
+    let mut cached = 0;
+    for job in jobs.iter().filter(|job| under(&job.dest_path, mount_path)) {
+        if cancel.cancelled() {
+            break;
+        }
+        match warm_destination(&job.dest_path, &job.id, count, &cancel) {
+            Ok(n) => cached += n,
+            Err(e) => log::debug!("Index warm-up skipped for job {}: {}", job.id, e),
+        }
+    }
+
+    operations.unregister(&op_id, &cancel);
+    cached
+}
+
+/// Stop a running warm-up of `mount_path` and drop everything cached for
+/// destinations on it
+pub fn forget_mount(operations: &CancelRegistry, mount_path: &str) {
+    operations.cancel(&cancel_token::operation_id(
+        cancel_token::INDEX_WARMUP,
+        mount_path,
+    ));
+    if let Ok(mut roots) = root_cache().lock() {
+        roots.retain(|(dest, _, _), _| !under(dest, mount_path));
+    }
+}
+
+/// Follow volume mounts for the life of the app: warm up on mount, cancel
+/// and evict on unmount
+pub async fn watch_volumes(store: Arc<Store>, operations: Arc<CancelRegistry>) {
+    let watcher = VolumeWatcher::new();
+    let mut events = match watcher.start().await {
+        Ok(events) => events,
+        Err(e) => {
+            log::warn!("Index warm-up disabled: {}", e);
+            return;
+        }
+    };
+
+    while let Some(event) = events.recv().await {
+        match event {
+            VolumeEvent::Mounted(mount_path) => {
+                let jobs = match store.load_jobs() {
+                    Ok(jobs) => jobs,
+                    Err(e) => {
+                        log::warn!("Index warm-up skipped: {}", e);
+                        continue;
+                    }
+                };
+                let operations = operations.clone();
+                tokio::task::spawn_blocking(move || {
+                    let cached = warm_mount(&operations, &mount_path, &jobs);
+                    if cached > 0 {
+                        log::info!("Warmed up {} snapshot folders on {}", cached, mount_path);
+                    }
+                });
+            }
+            VolumeEvent::Unmounted(mount_path) => forget_mount(&operations, &mount_path),
+        }
+    }
+}
@@ -0,0 +1,142 @@
+//! Changing a job's id
+//!
+//! Jobs are shown by name but keyed by id: the snapshots in the local and
+//! destination indexes, the destination's manifest and the snapshot cache
+//! all refer to the job by it. Renaming moves all of them to the new id.
+//! Everything that could refuse the rename is checked before anything is
+//! written, and a step that still fails undoes the ones before it, so the
+//! job is never left split between two ids.
+
+use crate::error::{AmberError, Result};
+use crate::services::cache_service;
+use crate::services::index_service::IndexService;
+use crate::services::manifest_service;
+use crate::services::store::Store;
+use crate::types::job::SyncJob;
+use crate::types::manifest::BackupManifest;
+use crate::utils::validation::validate_job_id;
+use std::path::Path;
+
+/// Move the snapshots in `indexes` back to `old_id` after a failed rename
+fn undo_indexes(indexes: &[&IndexService], old_id: &str, new_id: &str) {
+    for index in indexes {
+        if let Err(e) = index.rename_job(new_id, old_id) {
+            log::error!(
+                "[job_rename] Failed to move {} back to job '{}': {}",
+                index.get_db_path().display(),
+                old_id,
+                e
+            );
+        }
+    }
+}
+
+/// Change the id of the job `old_id` to `new_id` in the store, both indexes
+/// and the destination's manifest. Refused if a job called `new_id` exists
+/// or either index already holds snapshots under it.
+pub async fn rename_job_id(
+    store: &Store,
+    local_index: &IndexService,
+    old_id: &str,
+    new_id: &str,
+) -> Result<SyncJob> {
+    validate_job_id(old_id)?;
+    validate_job_id(new_id)?;
+    if old_id == new_id {
+        return Err(AmberError::ValidationError(format!(
+            "Job '{}' already has that id",
+            old_id
+        )));
+    }
+
+    let mut jobs = store.load_jobs()?;
+    if jobs.iter().any(|j| j.id == new_id) {
+        return Err(AmberError::ValidationError(format!(
+            "A job with id '{}' already exists",
+            new_id
+        )));
+    }
+    let position = jobs
+        .iter()
+        .position(|j| j.id == old_id)
+        .ok_or_else(|| AmberError::job_not_found(old_id))?;
+    let dest_path = jobs[position].dest_path.clone();
+
+    // The destination is only touched when it is connected
+    let dest_index = if manifest_service::get_index_path(&dest_path).exists() {
+        Some(IndexService::for_destination(&dest_path)?)
+    } else {
+        None
+    };
+    let manifest = if Path::new(&dest_path).is_dir() {
+        manifest_service::read_manifest(&dest_path)
+            .await
+            .map_err(|e| AmberError::Filesystem(e.to_string()))?
+    } else {
+        None
+    };
+    if let Some(manifest) = manifest.as_ref().filter(|m| m.job_id != old_id) {
+        return Err(AmberError::Job(format!(
+            "The manifest at {} belongs to job '{}', not '{}'",
+            dest_path, manifest.job_id, old_id
+        )));
+    }
+
+    let mut renamed: Vec<&IndexService> = Vec::new();
+    for index in std::iter::once(local_index).chain(dest_index.as_ref()) {
+        match index.rename_job(old_id, new_id) {
+            Ok(snapshots) => {
+                log::info!(
+                    "[job_rename] Moved {} snapshots in {} to job '{}'",
+                    snapshots,
+                    index.get_db_path().display(),
+                    new_id
+                );
+                renamed.push(index);
+            }
+            Err(e) => {
+                undo_indexes(&renamed, old_id, new_id);
+                return Err(e);
+            }
+        }
+    }
+
+    if let Some(manifest) = &manifest {
+        let moved = BackupManifest {
+            job_id: new_id.to_string(),
+            updated_at: chrono::Utc::now().timestamp_millis(),
+            ..manifest.clone()
+        };
+        if let Err(e) = manifest_service::write_manifest(&dest_path, &moved).await {
+            undo_indexes(&renamed, old_id, new_id);
+            return Err(AmberError::Filesystem(e.to_string()));
+        }
+    }
+
+    jobs[position].id = new_id.to_string();
+    if let Err(e) = store.save_jobs(&jobs) {
+        undo_indexes(&renamed, old_id, new_id);
+        if let Some(manifest) = &manifest {
+            if let Err(e) = manifest_service::write_manifest(&dest_path, manifest).await {
+                log::error!(
+                    "[job_rename] Failed to restore the manifest at {}: {}",
+                    dest_path,
+                    e
+                );
+            }
+        }
+        return Err(e);
+    }
+    let job = jobs.swap_remove(position);
+
+    // The rest is rebuilt on demand, so failing here doesn't undo the rename
+    if let Err(e) = store.write_job_to_destination(&job) {
+        log::warn!("Failed to write job config to destination: {}", e);
+    }
+    if let Err(e) = cache_service::delete_snapshot_cache(old_id).await {
+        log::warn!("Failed to delete snapshot cache for job {}: {}", old_id, e);
+    }
+
+    log::info!("[job_rename] Renamed job '{}' to '{}'", old_id, new_id);
+    Ok(job)
+}
@@ -1,3 +1,5 @@
+use chrono::{DateTime, Utc};
+use croner::Cron;
 use std::collections::HashMap;
 use std::sync::Arc;
 use tokio::sync::RwLock;
@@ -5,6 +7,7 @@ use tokio_cron_scheduler::{Job, JobScheduler as TokioScheduler};
 use uuid::Uuid;
 
 use crate::error::{AmberError, Result};
+use crate::services::backup_runner::{self, BackupRunner};
 use crate::types::job::SyncJob;
 
 /// Job scheduler for cron-based backup scheduling
@@ -18,17 +21,36 @@ pub struct JobScheduler {
     app_handle: Arc<RwLock<Option<tauri::AppHandle>>>,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-enum SchedulerRunMode {
-    Rsync,
-    Rclone,
+/// Run `job` on `runner` unless its previous run is still going. Both
+/// backends go through here, so a cloud job is scheduled exactly like a local
+/// one.
+async fn dispatch(
+    runner: &dyn BackupRunner,
+    job: SyncJob,
+    app: Option<tauri::AppHandle>,
+) -> Result<()> {
+    if runner.is_running(&job.id) {
+        return Err(AmberError::JobAlreadyRunning(job.id));
+    }
+    runner.run(job, app).await
+}
+
+/// Whether the scheduler should dispatch `job` at all: the job isn't paused
+/// and has an enabled schedule
+fn is_schedulable(job: &SyncJob) -> bool {
+    job.enabled && job.schedule.as_ref().is_some_and(|s| s.enabled)
 }
 
-fn scheduler_run_mode_for_job(job: &SyncJob) -> SchedulerRunMode {
-    match job.destination_type {
-        Some(crate::types::job::DestinationType::Cloud) => SchedulerRunMode::Rclone,
-        _ => SchedulerRunMode::Rsync,
+/// When `job`'s cron schedule next fires strictly after `after`, in UTC like
+/// the scheduler itself. `None` for paused or unscheduled jobs and for cron
+/// expressions that don't parse.
+pub fn next_run_after(job: &SyncJob, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    if !is_schedulable(job) {
+        return None;
     }
+    let expr = job.schedule.as_ref()?.cron.as_deref()?;
+    let cron = Cron::new(expr).with_seconds_optional().parse().ok()?;
+    cron.find_next_occurrence(&after, false).ok()
 }
 
 impl JobScheduler {
@@ -76,13 +98,9 @@ impl JobScheduler {
         }
 
         // Schedule enabled jobs
-        for job in jobs {
-            if let Some(ref schedule) = job.schedule {
-                if schedule.enabled {
-                    if let Err(e) = self.schedule_job(&job).await {
-                        log::error!("Failed to schedule job '{}': {}", job.name, e);
-                    }
-                }
+        for job in jobs.iter().filter(|job| is_schedulable(job)) {
+            if let Err(e) = self.schedule_job(job).await {
+                log::error!("Failed to schedule job '{}': {}", job.name, e);
             }
         }
 
@@ -102,13 +120,9 @@ impl JobScheduler {
         }
 
         // Re-schedule enabled jobs
-        for job in jobs {
-            if let Some(ref schedule) = job.schedule {
-                if schedule.enabled {
-                    if let Err(e) = self.schedule_job(&job).await {
-                        log::error!("Failed to schedule job '{}': {}", job.name, e);
-                    }
-                }
+        for job in jobs.iter().filter(|job| is_schedulable(job)) {
+            if let Err(e) = self.schedule_job(job).await {
+                log::error!("Failed to schedule job '{}': {}", job.name, e);
             }
         }
 
@@ -122,7 +136,7 @@ impl JobScheduler {
             .as_ref()
             .ok_or_else(|| AmberError::Scheduler("Job has no schedule".into()))?;
 
-        if !schedule.enabled {
+        if !is_schedulable(job) {
             return Ok(());
         }
 
@@ -146,26 +160,10 @@ impl JobScheduler {
             Box::pin(async move {
                 log::info!("Executing scheduled job: {} ({})", job.name, job.id);
 
-                match scheduler_run_mode_for_job(&job) {
-                    SchedulerRunMode::Rsync => {
-                        let app = { app_handle.read().await.clone() };
-                        if let Some(app_handle) = app {
-                            if let Err(e) = crate::commands::rsync::run_rsync(app_handle, job).await
-                            {
-                                log::error!("Scheduled rsync job failed: {}", e);
-                            }
-                        } else {
-                            log::error!(
-                                "Scheduled rsync job '{}' skipped: app handle not initialized",
-                                job.id
-                            );
-                        }
-                    }
-                    SchedulerRunMode::Rclone => {
-                        if let Err(e) = crate::commands::rclone::run_rclone(job).await {
-                            log::error!("Scheduled rclone job failed: {}", e);
-                        }
-                    }
+                let runner = backup_runner::runner_for(&job);
+                let app = { app_handle.read().await.clone() };
+                if let Err(e) = dispatch(runner, job, app).await {
+                    log::error!("Scheduled {} job failed: {}", runner.backend().name(), e);
                 }
             })
         })
@@ -188,6 +186,38 @@ impl JobScheduler {
         Ok(())
     }
 
+    /// Pause or resume one job's schedule. A paused job keeps its schedule but
+    /// is removed from the scheduler; resuming adds it back, so it next fires
+    /// at the first occurrence after now rather than catching up on runs
+    /// missed while paused.
+    pub async fn set_job_enabled(&self, job_id: &str, enabled: bool) -> Result<()> {
+        let job = {
+            let mut registered = self.registered_jobs.write().await;
+            let job = registered
+                .iter_mut()
+                .find(|job| job.id == job_id)
+                .ok_or_else(|| AmberError::job_not_found(job_id))?;
+            job.enabled = enabled;
+            job.clone()
+        };
+
+        self.cancel_job(job_id).await?;
+        if is_schedulable(&job) {
+            self.schedule_job(&job).await?;
+        }
+        log::info!(
+            "Job '{}' {}",
+            job.name,
+            if enabled { "resumed" } else { "paused" }
+        );
+        Ok(())
+    }
+
+    /// Whether the scheduler currently has a cron entry for `job_id`
+    pub async fn is_scheduled(&self, job_id: &str) -> bool {
+        self.job_mappings.read().await.contains_key(job_id)
+    }
+
     /// Cancel a specific job's schedule
     pub async fn cancel_job(&self, job_id: &str) -> Result<()> {
         let uuid = {
@@ -264,24 +294,14 @@ impl JobScheduler {
     fn is_job_due(&self, job: &SyncJob) -> bool {
         // Simple logic: If it has a schedule and is enabled, consider it due
         // A more sophisticated check would compare lastRun against expected schedule
-        if let Some(ref schedule) = job.schedule {
-            if schedule.enabled {
-                return true;
-            }
-        }
-        false
+        is_schedulable(job)
     }
 
     /// Get the next scheduled run time for a job
-    pub async fn get_next_run(&self, job_id: &str) -> Option<chrono::DateTime<chrono::Utc>> {
-        let mappings = self.job_mappings.read().await;
-        if let Some(_uuid) = mappings.get(job_id) {
-            // tokio-cron-scheduler doesn't expose next run time easily
-            // Would need to parse cron expression manually
-            None
-        } else {
-            None
-        }
+    pub async fn get_next_run(&self, job_id: &str) -> Option<DateTime<Utc>> {
+        let registered = self.registered_jobs.read().await;
+        let job = registered.iter().find(|job| job.id == job_id)?;
+        next_run_after(job, Utc::now())
     }
 
     /// Shutdown the scheduler
@@ -309,23 +329,182 @@ impl Default for JobScheduler {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::types::job::{DestinationType, SyncJob};
-
-    #[test]
-    fn selects_rsync_for_local_scheduled_jobs() {
-        let job = SyncJob {
-            destination_type: Some(DestinationType::Local),
+    use crate::services::backup_runner::{Backend, RunProgress, RunTracker};
+    use crate::types::job::{DestinationType, JobSchedule, SyncJob};
+    use chrono::TimeZone;
+    use futures::future::BoxFuture;
+    use std::sync::Mutex;
+
+    fn hourly_job(dest: &std::path::Path, enabled: bool) -> SyncJob {
+        SyncJob {
+            id: "hourly".to_string(),
+            name: "Hourly".to_string(),
+            dest_path: dest.to_string_lossy().to_string(),
+            enabled,
+            schedule: Some(JobSchedule {
+                enabled: true,
+                cron: Some("0 * * * *".to_string()),
+                run_on_mount: Some(true),
+            }),
             ..SyncJob::default()
-        };
-        assert_eq!(scheduler_run_mode_for_job(&job), SchedulerRunMode::Rsync);
+        }
+    }
+
+    /// Records what the scheduler asks of it; a run "spawns" pid 1, reports
+    /// progress and exits
+    struct MockRunner {
+        backend: Backend,
+        tracker: RunTracker,
+        calls: Mutex<Vec<String>>,
+    }
+
+    impl MockRunner {
+        fn new(backend: Backend) -> Self {
+            Self {
+                backend,
+                tracker: RunTracker::new(),
+                calls: Mutex::new(Vec::new()),
+            }
+        }
+
+        fn call(&self, call: String) {
+            self.calls.lock().unwrap().push(call);
+        }
+    }
+
+    impl BackupRunner for MockRunner {
+        fn backend(&self) -> Backend {
+            self.backend
+        }
+
+        fn tracker(&self) -> &RunTracker {
+            &self.tracker
+        }
+
+        fn run(
+            &self,
+            job: SyncJob,
+            _app: Option<tauri::AppHandle>,
+        ) -> BoxFuture<'static, Result<()>> {
+            self.call(format!("run {}", job.id));
+            self.tracker.start(&job.id, 1);
+            self.tracker.record_progress(
+                &job.id,
+                RunProgress {
+                    percentage: 100,
+                    ..RunProgress::default()
+                },
+            );
+            self.call(format!(
+                "progress {:?}",
+                self.progress(&job.id).map(|p| p.percentage)
+            ));
+            self.tracker.finish(&job.id);
+            Box::pin(async { Ok(()) })
+        }
+
+        fn kill(&self, job_id: &str) -> Result<()> {
+            self.call(format!("kill {}", job_id));
+            self.tracker.take_pid(job_id);
+            Ok(())
+        }
+    }
+
+    /// Dispatch `job` twice on a fresh mock of `backend`, the second time while
+    /// an earlier run is still going, and return what the runner saw
+    async fn drive(backend: Backend, job: SyncJob) -> Vec<String> {
+        let runner = MockRunner::new(backend);
+
+        dispatch(&runner, job.clone(), None).await.unwrap();
+        assert!(!runner.is_running(&job.id));
+
+        runner.tracker.start(&job.id, 2);
+        assert!(matches!(
+            dispatch(&runner, job.clone(), None).await,
+            Err(AmberError::JobAlreadyRunning(_))
+        ));
+        runner.kill(&job.id).unwrap();
+        assert!(!runner.is_running(&job.id));
+
+        runner.calls.into_inner().unwrap()
     }
 
-    #[test]
-    fn selects_rclone_for_cloud_scheduled_jobs() {
-        let job = SyncJob {
+    #[tokio::test]
+    async fn dispatch_drives_both_backends_identically() {
+        let dest = tempfile::tempdir().unwrap();
+        let local = hourly_job(dest.path(), true);
+        let cloud = SyncJob {
             destination_type: Some(DestinationType::Cloud),
-            ..SyncJob::default()
+            ..local.clone()
         };
-        assert_eq!(scheduler_run_mode_for_job(&job), SchedulerRunMode::Rclone);
+        assert_eq!(Backend::for_job(&local), Backend::Rsync);
+        assert_eq!(Backend::for_job(&cloud), Backend::Rclone);
+
+        let rsync = drive(Backend::for_job(&local), local).await;
+        let rclone = drive(Backend::for_job(&cloud), cloud).await;
+        assert_eq!(
+            rsync,
+            vec!["run hourly", "progress Some(100)", "kill hourly"]
+        );
+        assert_eq!(rclone, rsync);
+    }
+
+    #[tokio::test]
+    async fn disabled_job_is_never_dispatched() {
+        let dest = tempfile::tempdir().unwrap();
+        let job = hourly_job(dest.path(), false);
+        let scheduler = JobScheduler::new();
+        scheduler.init_with_jobs(vec![job.clone()]).await.unwrap();
+
+        assert!(!scheduler.is_scheduled(&job.id).await);
+        scheduler.schedule_job(&job).await.unwrap();
+        assert!(!scheduler.is_scheduled(&job.id).await);
+        assert!(scheduler
+            .handle_volume_mount(&job.dest_path)
+            .await
+            .is_empty());
+        assert_eq!(scheduler.get_next_run(&job.id).await, None);
+
+        scheduler.shutdown().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn reenabling_resumes_from_next_occurrence() {
+        let dest = tempfile::tempdir().unwrap();
+        let scheduler = JobScheduler::new();
+        scheduler
+            .init_with_jobs(vec![hourly_job(dest.path(), true)])
+            .await
+            .unwrap();
+        assert!(scheduler.is_scheduled("hourly").await);
+
+        scheduler.set_job_enabled("hourly", false).await.unwrap();
+        assert!(!scheduler.is_scheduled("hourly").await);
+
+        scheduler.set_job_enabled("hourly", true).await.unwrap();
+        assert!(scheduler.is_scheduled("hourly").await);
+        assert_eq!(
+            scheduler
+                .handle_volume_mount(&dest.path().to_string_lossy())
+                .await
+                .len(),
+            1
+        );
+        assert!(scheduler.get_next_run("hourly").await.unwrap() > Utc::now());
+
+        // Resumed mid-hour after a long pause: the next run is the coming hour,
+        // not one of the runs skipped while paused
+        let resumed_at = Utc.with_ymd_and_hms(2024, 3, 5, 10, 30, 0).unwrap();
+        assert_eq!(
+            next_run_after(&hourly_job(dest.path(), true), resumed_at),
+            Some(Utc.with_ymd_and_hms(2024, 3, 5, 11, 0, 0).unwrap())
+        );
+        assert_eq!(
+            next_run_after(&hourly_job(dest.path(), false), resumed_at),
+            None
+        );
+
+        assert!(scheduler.set_job_enabled("missing", true).await.is_err());
+        scheduler.shutdown().await.unwrap();
     }
 }
@@ -0,0 +1,348 @@
+//! Checking a job works before relying on it
+//!
+//! Run in the background after a job is saved, when the UI asks for it, or
+//! on demand. Each part of the job gets its own result so the UI can show a
+//! green checkmark or point at what's wrong: the local source is readable
+//! and not empty, the local destination is there, no exclude pattern drops
+//! everything, and `rsync -n` over the top level of the source gets through,
+//! which also proves SSH credentials on either side. The dry run is skipped
+//! once an earlier check has failed, and for jobs with a custom command.
+
+use crate::error::{AmberError, Result};
+use crate::services::exclude_preview;
+use crate::services::rsync_service::{self, RsyncService};
+use crate::services::ssh_check::{self, SshOutput};
+use crate::types::job::{DestinationType, SyncJob};
+use crate::utils::is_ssh_remote;
+use serde::Serialize;
+use std::path::Path;
+
+/// rsync's `--timeout` for the dry run, so a stalled server fails fast
+const DRY_RUN_TIMEOUT_SECS: u32 = 30;
+
+/// ssh's `ConnectTimeout` for the dry run; rsync's `--timeout` only starts
+/// counting once ssh has connected
+const DRY_RUN_CONNECT_TIMEOUT_SECS: u32 = 10;
+
+/// rsync's own exit status when its transport (ssh) failed
+const EXIT_TRANSPORT_ERROR: i32 = 255;
+
+/// A part of the job that is checked
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum JobCheck {
+    Source,
+    Destination,
+    Excludes,
+    DryRun,
+}
+
+/// One check's outcome; `message` is safe to show as is
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CheckResult {
+    pub check: JobCheck,
+    pub ok: bool,
+    pub message: String,
+}
+
+/// Outcome of `validate_job`, checks in the order they ran
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JobValidation {
+    pub job_id: String,
+    pub ok: bool,
+    pub checks: Vec<CheckResult>,
+}
+
+/// rsync arguments for the dry run: the job's own flags, excludes and SSH
+/// settings, but listing only the top level of the source
+pub fn dry_run_args(rsync: &RsyncService, job: &SyncJob, dest: &str) -> Vec<String> {
+    let mut args = rsync.build_rsync_args(job, dest, None);
+    let at = args.len().saturating_sub(2);
+    args.splice(
+        at..at,
+        [
+            "--dry-run".to_string(),
+            "--no-recursive".to_string(),
+            "--dirs".to_string(),
+            format!("--timeout={}", DRY_RUN_TIMEOUT_SECS),
+        ],
+    );
+    rsync_service::insert_ssh_options(
+        &mut args,
+        &format!("-o ConnectTimeout={}", DRY_RUN_CONNECT_TIMEOUT_SECS),
+    );
+    args
+}
+
+/// What a failed dry run means, from rsync's exit status and stderr
+fn dry_run_failure(output: &SshOutput) -> String {
+    if output.exit_code == Some(EXIT_TRANSPORT_ERROR) {
+        // rsync passes ssh's status through; read it the way Test Connection does
+        return ssh_check::diagnose(output).message;
+    }
+    let detail = output
+        .stderr
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty() && !line.starts_with("rsync error:"))
+        .unwrap_or("no error output");
+    match output.exit_code {
+        Some(code) => format!("rsync dry run failed with code {}: {}", code, detail),
+        None => format!("rsync dry run was killed: {}", detail),
+    }
+}
+
+/// Check `job`, running the dry run through `run` with the rsync arguments.
+/// Cloud jobs back up with rclone and can't be checked this way.
+pub fn validate_job<F>(job: &SyncJob, rsync: &RsyncService, run: F) -> Result<JobValidation>
+where
+    F: FnOnce(&[String]) -> std::io::Result<SshOutput>,
+{
+    if job.destination_type == Some(DestinationType::Cloud) {
+        return Err(AmberError::ValidationError(
+            "Cloud jobs back up with rclone; only rsync jobs can be validated".to_string(),
+        ));
+    }
+
+    let mut checks = Vec::new();
+    let mut record = |check: JobCheck, outcome: std::result::Result<String, String>| {
+        let (ok, message) = match outcome {
+            Ok(message) => (true, message),
+            Err(message) => (false, message),
+        };
+        checks.push(CheckResult { check, ok, message });
+        ok
+    };
+
+    let mut ready = true;
+    if !is_ssh_remote(&job.source_path) {
+        ready &= record(
+            JobCheck::Source,
+            rsync_service::check_source_ready(&job.source_path)
+                .map(|_| "The source folder is readable".to_string())
+                .map_err(|e| e.to_string()),
+        );
+    }
+
+    let target = rsync_service::target_base(job);
+    if !is_ssh_remote(&job.dest_path) {
+        let outcome = if Path::new(&job.dest_path).is_dir() {
+            Ok("The destination folder is reachable".to_string())
+        } else {
+            Err(format!(
+                "The destination {} is not reachable; is the drive connected?",
+                job.dest_path
+            ))
+        };
+        ready &= record(JobCheck::Destination, outcome);
+    }
+    if let Err(e) = &target {
+        ready &= record(JobCheck::Destination, Err(e.to_string()));
+    }
+
+    let excludes = exclude_preview::normalize_patterns(&job.config.exclude_patterns);
+    let dangerous: Vec<String> = excludes
+        .iter()
+        .filter_map(|p| {
+            exclude_preview::dangerous_pattern_reason(p).map(|reason| format!("'{}' {}", p, reason))
+        })
+        .collect();
+    ready &= record(
+        JobCheck::Excludes,
+        if dangerous.is_empty() {
+            Ok(format!("{} exclude patterns", excludes.len()))
+        } else {
+            Err(format!("Exclude pattern {}", dangerous.join("; ")))
+        },
+    );
+
+    let custom = job
+        .config
+        .custom_command
+        .as_deref()
+        .is_some_and(|c| !c.trim().is_empty());
+    if let (true, false, Ok(target)) = (ready, custom, &target) {
+        let args = dry_run_args(rsync, job, target.to_str().unwrap_or(""));
+        let outcome = match run(&args) {
+            Ok(output) if output.exit_code == Some(0) => {
+                Ok("rsync can read the source and reach the destination".to_string())
+            }
+            Ok(output) => Err(dry_run_failure(&output)),
+            Err(e) => Err(format!("Failed to run rsync: {}", e)),
+        };
+        record(JobCheck::DryRun, outcome);
+    }
+
+    let validation = JobValidation {
+        job_id: job.id.clone(),
+        ok: checks.iter().all(|c| c.ok),
+        checks,
+    };
+    log::info!(
+        "[job_validation] Job '{}': {}",
+        job.id,
+        if validation.ok { "ok" } else { "failed" }
+    );
+    Ok(validation)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::job::SyncMode;
+    use std::fs;
+
+    /// A local job whose source holds one file
+    fn local_job(root: &Path) -> SyncJob {
+        let source = root.join("source");
+        let dest = root.join("dest");
+        fs::create_dir_all(&source).unwrap();
+        fs::create_dir_all(&dest).unwrap();
+        fs::write(source.join("notes.txt"), "notes").unwrap();
+        SyncJob {
+            id: "validate-job".to_string(),
+            source_path: source.to_string_lossy().to_string(),
+            dest_path: dest.to_string_lossy().to_string(),
+            mode: SyncMode::Mirror,
+            ..SyncJob::default()
+        }
+    }
+
+    fn stub(code: i32, stderr: &str) -> impl FnOnce(&[String]) -> std::io::Result<SshOutput> {
+        let stderr = stderr.to_string();
+        move |_| {
+            Ok(SshOutput {
+                exit_code: Some(code),
+                stdout: String::new(),
+                stderr,
+            })
+        }
+    }
+
+    fn check(validation: &JobValidation, check: JobCheck) -> Option<&CheckResult> {
+        validation.checks.iter().find(|c| c.check == check)
+    }
+
+    #[test]
+    fn test_reachable_job_passes_every_check() {
+        let dir = tempfile::tempdir().unwrap();
+        let job = local_job(dir.path());
+        let mut seen = Vec::new();
+
+        let validation = validate_job(&job, &RsyncService::new(), |args| {
+            seen = args.to_vec();
+            stub(0, "")(args)
+        })
+        .unwrap();
+
+        assert!(validation.ok, "{:?}", validation);
+        let order: Vec<JobCheck> = validation.checks.iter().map(|c| c.check).collect();
+        assert_eq!(
+            order,
+            vec![
+                JobCheck::Source,
+                JobCheck::Destination,
+                JobCheck::Excludes,
+                JobCheck::DryRun
+            ]
+        );
+        assert!(seen.contains(&"--dry-run".to_string()));
+        assert!(seen.contains(&"--no-recursive".to_string()));
+        assert_eq!(seen.last().unwrap(), &format!("{}/source", job.dest_path));
+    }
+
+    #[test]
+    fn test_unreachable_destination_fails_without_a_dry_run() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut job = local_job(dir.path());
+        job.dest_path = dir.path().join("unplugged").to_string_lossy().to_string();
+
+        let validation = validate_job(&job, &RsyncService::new(), |_| {
+            panic!("dry run must not start")
+        })
+        .unwrap();
+
+        assert!(!validation.ok);
+        assert!(check(&validation, JobCheck::Source).unwrap().ok);
+        let dest = check(&validation, JobCheck::Destination).unwrap();
+        assert!(!dest.ok);
+        assert!(dest.message.contains("not reachable"), "{}", dest.message);
+        assert!(check(&validation, JobCheck::DryRun).is_none());
+    }
+
+    #[test]
+    fn test_unreachable_ssh_server_is_reported_from_the_dry_run() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut job = local_job(dir.path());
+        job.dest_path = "backup@nas.local:/volume1/amber".to_string();
+
+        let stderr = "ssh: connect to host nas.local port 22: Connection refused\n\
+                      rsync error: unexplained error (code 255) at io.c(232)";
+        let mut seen = Vec::new();
+        let validation = validate_job(&job, &RsyncService::new(), |args| {
+            seen = args.to_vec();
+            stub(255, stderr)(args)
+        })
+        .unwrap();
+
+        // Bounded both while connecting and once connected
+        let e = seen.iter().position(|a| a == "-e").unwrap();
+        assert!(
+            seen[e + 1].starts_with("ssh -o ConnectTimeout=10"),
+            "{}",
+            seen[e + 1]
+        );
+        assert!(seen.contains(&"--timeout=30".to_string()));
+
+        assert!(!validation.ok);
+        assert!(check(&validation, JobCheck::Destination).is_none());
+        let dry_run = check(&validation, JobCheck::DryRun).unwrap();
+        assert!(!dry_run.ok);
+        let diagnosis = ssh_check::diagnose(&SshOutput {
+            exit_code: Some(255),
+            stdout: String::new(),
+            stderr: stderr.to_string(),
+        });
+        assert_eq!(diagnosis.diagnosis, ssh_check::SshDiagnosis::Unreachable);
+        assert_eq!(dry_run.message, diagnosis.message);
+    }
+
+    #[test]
+    fn test_dangerous_excludes_and_rsync_errors_are_specific() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut job = local_job(dir.path());
+        job.config.exclude_patterns = vec!["*".to_string()];
+        let validation =
+            validate_job(&job, &RsyncService::new(), |_| panic!("no dry run")).unwrap();
+        let excludes = check(&validation, JobCheck::Excludes).unwrap();
+        assert!(!excludes.ok);
+        assert!(excludes.message.contains("'*'"), "{}", excludes.message);
+
+        job.config.exclude_patterns.clear();
+        let validation = validate_job(
+            &job,
+            &RsyncService::new(),
+            stub(
+                23,
+                "rsync: opendir \"/src/private\" failed: Permission denied (13)\n\
+                 rsync error: some files/attrs were not transferred (code 23)",
+            ),
+        )
+        .unwrap();
+        let dry_run = check(&validation, JobCheck::DryRun).unwrap();
+        assert!(!dry_run.ok);
+        assert!(dry_run.message.contains("code 23"), "{}", dry_run.message);
+        assert!(dry_run.message.contains("Permission denied"));
+    }
+
+    #[test]
+    fn test_cloud_jobs_are_not_validated() {
+        let job = SyncJob {
+            destination_type: Some(DestinationType::Cloud),
+            ..SyncJob::default()
+        };
+        assert!(validate_job(&job, &RsyncService::new(), |_| panic!("no dry run")).is_err());
+    }
+}
@@ -0,0 +1,141 @@
+//! Persistent file logging
+//!
+//! Every build writes to `<log dir>/amber.log`, rotated by size, through
+//! tauri_plugin_log; debug builds also echo to stdout. Records can carry
+//! structured fields using the `log` key-value syntax:
+//!
+//! ```ignore
+//! log::info!(job_id = job.id.as_str(), operation = "backup"; "Backup started");
+//! ```
+//!
+//! Fields are appended to the line as `key=value` so support can grep a
+//! release build's log for one job. The level comes from the `logLevel`
+//! preference and can change at runtime via `set_level`.
+
+use log::kv::{Error as KvError, Key, Value, VisitSource};
+use log::{LevelFilter, Record};
+use std::fmt::Arguments;
+use std::path::PathBuf;
+use tauri::plugin::TauriPlugin;
+use tauri::Runtime;
+use tauri_plugin_log::{RotationStrategy, Target, TargetKind};
+
+/// Log file name, without the `.log` extension
+pub const LOG_FILE_NAME: &str = "amber";
+
+/// Rotate the active log once it grows past this size
+pub const MAX_LOG_FILE_BYTES: u128 = 5 * 1024 * 1024;
+
+/// Rotated files kept alongside the active one
+pub const KEEP_LOG_FILES: usize = 5;
+
+/// Level used when the preference is missing or unrecognized
+pub const DEFAULT_LEVEL: LevelFilter = LevelFilter::Info;
+
+/// Parse a `logLevel` preference ("error", "warn", "info", "debug", "trace", "off")
+pub fn parse_level(level: &str) -> LevelFilter {
+    level.trim().parse().unwrap_or(DEFAULT_LEVEL)
+}
+
+/// Change the level at runtime. The sinks accept everything, so this is the
+/// only filter.
+pub fn set_level(level: LevelFilter) {
+    log::set_max_level(level);
+}
+
+struct FieldWriter<'a>(&'a mut String);
+
+impl<'kvs> VisitSource<'kvs> for FieldWriter<'_> {
+    fn visit_pair(&mut self, key: Key<'kvs>, value: Value<'kvs>) -> Result<(), KvError> {
+        let value = value.to_string();
+        if value.is_empty() || value.contains(char::is_whitespace) {
+            self.0.push_str(&format!(" {}={:?}", key, value));
+        } else {
+            self.0.push_str(&format!(" {}={}", key, value));
+        }
+        Ok(())
+    }
+}
+
+/// Render one record as `[date][time][LEVEL][target] message key=value...`
+pub fn format_record(message: &Arguments, record: &Record) -> String {
+    let mut line = format!(
+        "{}[{}][{}] {}",
+        chrono::Local::now().format("[%Y-%m-%d][%H:%M:%S]"),
+        record.level(),
+        record.target(),
+        message
+    );
+    let _ = record.key_values().visit(&mut FieldWriter(&mut line));
+    line
+}
+
+/// Build the log plugin with the file sink (and stdout in debug builds)
+pub fn plugin<R: Runtime>(log_dir: PathBuf) -> TauriPlugin<R> {
+    let mut targets = vec![Target::new(TargetKind::Folder {
+        path: log_dir,
+        file_name: Some(LOG_FILE_NAME.to_string()),
+    })];
+    if cfg!(debug_assertions) {
+        targets.push(Target::new(TargetKind::Stdout));
+    }
+
+    tauri_plugin_log::Builder::default()
+        // Filtering happens through `set_level` so it can change at runtime
+        .level(LevelFilter::Trace)
+        .format(|out, message, record| {
+            out.finish(format_args!("{}", format_record(message, record)))
+        })
+        .max_file_size(MAX_LOG_FILE_BYTES)
+        .rotation_strategy(RotationStrategy::KeepSome(KEEP_LOG_FILES + 1))
+        .targets(targets)
+        .build()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tauri_plugin_log::fern;
+
+    #[test]
+    fn test_parse_level_falls_back_to_info() {
+        assert_eq!(parse_level("debug"), LevelFilter::Debug);
+        assert_eq!(parse_level(" WARN "), LevelFilter::Warn);
+        assert_eq!(parse_level("off"), LevelFilter::Off);
+        assert_eq!(parse_level("chatty"), DEFAULT_LEVEL);
+    }
+
+    #[test]
+    fn test_logged_operation_writes_job_id_field_to_file() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let path = temp.path().join(format!("{}.log", LOG_FILE_NAME));
+
+        // Same formatter as the plugin, writing to a file sink
+        let (_, logger) = fern::Dispatch::new()
+            .format(|out, message, record| {
+                out.finish(format_args!("{}", format_record(message, record)))
+            })
+            .chain(fern::log_file(&path).unwrap())
+            .into_log();
+
+        let fields = [("job_id", "job-42"), ("operation", "backup")];
+        logger.log(
+            &Record::builder()
+                .args(format_args!("Backup started"))
+                .level(log::Level::Info)
+                .target("app_lib::commands::rsync")
+                .key_values(&fields)
+                .build(),
+        );
+        logger.flush();
+
+        let contents = std::fs::read_to_string(&path).unwrap();
+        let line = contents.lines().next().unwrap();
+        assert!(line.contains("[INFO][app_lib::commands::rsync] Backup started"));
+        assert!(
+            line.ends_with(" job_id=job-42 operation=backup"),
+            "{}",
+            line
+        );
+    }
+}
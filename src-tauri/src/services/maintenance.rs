@@ -0,0 +1,123 @@
+//! Compacting the indexes on many destinations at once
+//!
+//! Each destination keeps its own index in `.amber-meta/index.db`, and pruned
+//! snapshots leave free pages and a fragmented search index behind until the
+//! file is vacuumed. `compact_all` runs the full clean-up on every reachable
+//! destination in turn and reports the index size before and after. A
+//! destination that is unplugged, has no index, or is being backed up to is
+//! skipped with a note instead of failing the rest.
+
+use crate::error::Result;
+use crate::services::index_service::IndexService;
+use crate::services::{manifest_service, volume_gate};
+use serde::Serialize;
+use std::path::Path;
+
+/// What compacting one destination's index did
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CompactionResult {
+    pub dest_path: String,
+    /// Index size on disk, WAL included; None when it was skipped
+    pub bytes_before: Option<u64>,
+    pub bytes_after: Option<u64>,
+    /// Why the destination was skipped or failed part way
+    pub note: Option<String>,
+}
+
+impl CompactionResult {
+    fn skipped(dest_path: &str, note: impl Into<String>) -> Self {
+        Self {
+            dest_path: dest_path.to_string(),
+            bytes_before: None,
+            bytes_after: None,
+            note: Some(note.into()),
+        }
+    }
+
+    /// Bytes freed, 0 if the index didn't shrink or was skipped
+    pub fn bytes_saved(&self) -> u64 {
+        match (self.bytes_before, self.bytes_after) {
+            (Some(before), Some(after)) => before.saturating_sub(after),
+            _ => 0,
+        }
+    }
+}
+
+/// Size of the index database on `dest_path` plus its WAL
+fn index_bytes(dest_path: &str) -> u64 {
+    let db = manifest_service::get_index_path(dest_path);
+    let mut wal = db.clone().into_os_string();
+    wal.push("-wal");
+    [db.as_path(), Path::new(&wal)]
+        .iter()
+        .filter_map(|path| std::fs::metadata(path).ok())
+        .map(|meta| meta.len())
+        .sum()
+}
+
+/// Optimize the search index, vacuum, and checkpoint the WAL back into the
+/// database file, so the file on disk ends up as small as it can be
+fn compact_index(dest_path: &str) -> Result<()> {
+    let index = IndexService::for_destination(dest_path)?;
+    index.optimize_fts()?;
+    index.compact()?;
+    index.checkpoint()
+}
+
+/// Compact the index on each of `destinations`, in order. Destinations
+/// listed twice are compacted once.
+pub fn compact_all(destinations: &[String]) -> Vec<CompactionResult> {
+    let mut results: Vec<CompactionResult> = Vec::new();
+    for dest_path in destinations {
+        if results.iter().any(|r| &r.dest_path == dest_path) {
+            continue;
+        }
+        if !Path::new(dest_path).is_dir() {
+            results.push(CompactionResult::skipped(
+                dest_path,
+                "Destination is not reachable; is the drive connected?",
+            ));
+            continue;
+        }
+        if !manifest_service::get_index_path(dest_path).exists() {
+            results.push(CompactionResult::skipped(
+                dest_path,
+                "Destination has no index",
+            ));
+            continue;
+        }
+        if volume_gate::backups_on_volume(dest_path) > 0 {
+            results.push(CompactionResult::skipped(
+                dest_path,
+                "A backup is writing to this drive",
+            ));
+            continue;
+        }
+
+        let bytes_before = index_bytes(dest_path);
+        let note = match compact_index(dest_path) {
+            Ok(()) => None,
+            Err(e) => {
+                log::warn!("[maintenance] Failed to compact {}: {}", dest_path, e);
+                Some(e.to_string())
+            }
+        };
+        let result = CompactionResult {
+            dest_path: dest_path.clone(),
+            bytes_before: Some(bytes_before),
+            bytes_after: Some(index_bytes(dest_path)),
+            note,
+        };
+        if result.note.is_none() {
+            log::info!(
+                "[maintenance] Compacted {}: {} -> {} bytes",
+                dest_path,
+                bytes_before,
+                result.bytes_after.unwrap_or(0)
+            );
+        }
+        results.push(result);
+    }
+    results
+}
@@ -1,19 +1,48 @@
 // Service modules - Business logic
+pub mod age_filter;
+pub mod backup_runner;
 pub mod cache_service;
+pub mod cancel_token;
+pub mod dashboard;
 pub mod data_dir; // Must be first - other services depend on this
+pub mod delete_guard;
+pub mod diagnostics;
+pub mod dir_diff;
+pub mod exclude_preview;
 pub mod file_service;
+pub mod hardlink_probe;
+pub mod index_backfill;
+pub mod index_migrations;
 pub mod index_service;
+pub mod index_warmup;
+pub mod job_rename;
 pub mod job_scheduler;
+pub mod job_validation;
 pub mod keychain_service;
+pub mod logging;
+pub mod maintenance;
 pub mod manifest_service;
 pub mod migration_service;
+pub mod parallel_restore;
+pub mod process_priority;
 pub mod rclone_service;
+pub mod replication;
+pub mod retention;
 pub mod rsync_service;
+pub mod snapshot_commit;
+pub mod snapshot_export;
+pub mod snapshot_open;
 pub mod snapshot_service;
+pub mod source_diff;
+pub mod ssh_askpass;
+pub mod ssh_check;
 pub mod store;
 #[cfg(desktop)]
 pub mod tray_manager;
+pub mod volume_gate;
 pub mod volume_watcher;
+pub mod walk_pool;
+pub mod xattr_check;
 
 // Dev-only modules
 #[cfg(debug_assertions)]
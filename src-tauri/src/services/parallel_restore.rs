@@ -0,0 +1,326 @@
+//! Restoring many small files in parallel
+//!
+//! rsync restores one file at a time, and with tens of thousands of tiny
+//! files the per-file syscalls dominate. With `restoreWorkers` above zero,
+//! `restore_files` copies the selection itself on a pool of that many
+//! threads. Every folder is created first, parents before children, so the
+//! workers only ever write files. Progress is reported for the restore as a
+//! whole, cancelling stops each worker before its next file (what was
+//! already restored stays), and the conflict strategy decides what happens
+//! to files already at the target. As with rsync, symlinks are recreated as
+//! links and fifos, sockets and devices are skipped.
+
+use crate::error::{AmberError, Result};
+use crate::services::cancel_token::CancelToken;
+use crate::services::manifest_service::AMBER_META_DIR;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::SystemTime;
+use walkdir::WalkDir;
+
+/// Restore workers when no preference says otherwise (0 = restore with rsync)
+pub const DEFAULT_RESTORE_WORKERS: usize = 0;
+
+static RESTORE_WORKERS: AtomicUsize = AtomicUsize::new(DEFAULT_RESTORE_WORKERS);
+
+/// Threads `restore_files` copies with (0 = hand the restore to rsync)
+pub fn configure(workers: usize) {
+    RESTORE_WORKERS.store(workers, Ordering::SeqCst);
+}
+
+/// The configured worker count
+pub fn workers() -> usize {
+    RESTORE_WORKERS.load(Ordering::SeqCst)
+}
+
+/// What to do with a file that already exists at the restore target
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ConflictStrategy {
+    /// Replace it with the snapshot's copy
+    #[default]
+    Overwrite,
+    /// Leave it alone
+    Skip,
+    /// Replace it only if the snapshot's copy is newer
+    KeepNewer,
+}
+
+impl ConflictStrategy {
+    /// The rsync flag with the same effect, for restores rsync runs
+    pub fn rsync_flag(self) -> Option<&'static str> {
+        match self {
+            ConflictStrategy::Overwrite => None,
+            ConflictStrategy::Skip => Some("--ignore-existing"),
+            ConflictStrategy::KeepNewer => Some("--update"),
+        }
+    }
+}
+
+/// Progress after each file; skipped files count as done
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RestoreProgress {
+    pub files_done: u64,
+    pub files_total: u64,
+    pub bytes_done: u64,
+    pub bytes_total: u64,
+    pub files_skipped: u64,
+    pub current_path: String,
+}
+
+/// What a finished parallel restore wrote
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RestoreReport {
+    pub files_restored: u64,
+    pub files_skipped: u64,
+    pub bytes_restored: u64,
+    pub dirs_created: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum EntryKind {
+    File,
+    Symlink,
+}
+
+#[derive(Debug)]
+struct PlannedEntry {
+    kind: EntryKind,
+    size: u64,
+}
+
+/// Folders to create (sorted, so parents come first) and the files and
+/// links to copy, all relative to the snapshot root
+#[derive(Debug, Default)]
+struct RestorePlan {
+    dirs: BTreeSet<PathBuf>,
+    entries: BTreeMap<PathBuf, PlannedEntry>,
+}
+
+impl RestorePlan {
+    fn add(&mut self, relative: &Path, metadata: &fs::Metadata) {
+        let file_type = metadata.file_type();
+        if file_type.is_dir() {
+            self.dirs.insert(relative.to_path_buf());
+        } else if file_type.is_symlink() {
+            self.entries.insert(
+                relative.to_path_buf(),
+                PlannedEntry {
+                    kind: EntryKind::Symlink,
+                    size: 0,
+                },
+            );
+        } else if file_type.is_file() {
+            self.entries.insert(
+                relative.to_path_buf(),
+                PlannedEntry {
+                    kind: EntryKind::File,
+                    size: metadata.len(),
+                },
+            );
+        }
+    }
+}
+
+/// Expand the selected paths (relative to `snapshot_root`) into everything
+/// they contain, plus the folders leading to them
+fn plan(snapshot_root: &Path, files: &[impl AsRef<Path>]) -> Result<RestorePlan> {
+    let mut plan = RestorePlan::default();
+    for file in files {
+        let relative = file.as_ref();
+        for ancestor in relative.ancestors().skip(1) {
+            if !ancestor.as_os_str().is_empty() {
+                plan.dirs.insert(ancestor.to_path_buf());
+            }
+        }
+
+        let full = snapshot_root.join(relative);
+        let metadata = fs::symlink_metadata(&full)
+            .map_err(|e| AmberError::fs_error(full.to_string_lossy(), e))?;
+        if !metadata.is_dir() {
+            plan.add(relative, &metadata);
+            continue;
+        }
+
+        let walker = WalkDir::new(&full)
+            .follow_links(false)
+            .into_iter()
+            .filter_entry(|e| e.file_name() != AMBER_META_DIR);
+        for entry in walker {
+            let entry = entry
+                .map_err(|e| AmberError::Filesystem(format!("Failed to walk snapshot: {}", e)))?;
+            let metadata = entry
+                .metadata()
+                .map_err(|e| AmberError::fs_error(entry.path().to_string_lossy(), e))?;
+            let relative = entry
+                .path()
+                .strip_prefix(snapshot_root)
+                .map_err(|_| AmberError::InvalidPath("Entry outside snapshot".to_string()))?;
+            plan.add(relative, &metadata);
+        }
+    }
+    Ok(plan)
+}
+
+fn modified(metadata: &fs::Metadata) -> Option<SystemTime> {
+    metadata.modified().ok()
+}
+
+/// Copy one file or link from `from` to `to`; false when the conflict
+/// strategy left an existing one in place
+fn restore_entry(
+    from: &Path,
+    to: &Path,
+    kind: EntryKind,
+    conflict: ConflictStrategy,
+) -> Result<bool> {
+    let source =
+        fs::symlink_metadata(from).map_err(|e| AmberError::fs_error(from.to_string_lossy(), e))?;
+
+    if let Ok(existing) = fs::symlink_metadata(to) {
+        let keep = match conflict {
+            ConflictStrategy::Overwrite => false,
+            ConflictStrategy::Skip => true,
+            ConflictStrategy::KeepNewer => modified(&existing) >= modified(&source),
+        };
+        if keep {
+            return Ok(false);
+        }
+        if existing.is_dir() {
+            return Err(AmberError::fs_error(
+                to.to_string_lossy(),
+                "a folder is in the way of the restored file",
+            ));
+        }
+        // Never write through a link that happens to sit at the target
+        fs::remove_file(to).map_err(|e| AmberError::fs_error(to.to_string_lossy(), e))?;
+    }
+
+    match kind {
+        EntryKind::File => {
+            let mut reader =
+                File::open(from).map_err(|e| AmberError::fs_error(from.to_string_lossy(), e))?;
+            let mut writer =
+                File::create(to).map_err(|e| AmberError::fs_error(to.to_string_lossy(), e))?;
+            std::io::copy(&mut reader, &mut writer)
+                .map_err(|e| AmberError::fs_error(to.to_string_lossy(), e))?;
+            if let Some(mtime) = modified(&source) {
+                let _ = writer.set_modified(mtime);
+            }
+            // Permissions go last, so a read-only file could still be written
+            fs::set_permissions(to, source.permissions())
+                .map_err(|e| AmberError::fs_error(to.to_string_lossy(), e))?;
+        }
+        EntryKind::Symlink => {
+            #[cfg(unix)]
+            {
+                let target = fs::read_link(from)
+                    .map_err(|e| AmberError::fs_error(from.to_string_lossy(), e))?;
+                std::os::unix::fs::symlink(target, to)
+                    .map_err(|e| AmberError::fs_error(to.to_string_lossy(), e))?;
+            }
+            #[cfg(not(unix))]
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+/// Restore `files` (relative to `snapshot_root`) into `target` with
+/// `workers` threads (0 = one per CPU).
+///
+/// `on_progress` runs after each file, one call at a time; cancelling
+/// `cancel` stops the restore with `AmberError::Cancelled`.
+pub fn restore_parallel(
+    snapshot_root: &Path,
+    files: &[impl AsRef<Path>],
+    target: &Path,
+    workers: usize,
+    conflict: ConflictStrategy,
+    cancel: &CancelToken,
+    on_progress: impl Fn(&RestoreProgress) + Sync,
+) -> Result<RestoreReport> {
+    let plan = plan(snapshot_root, files)?;
+
+    let mut dirs_created = 0;
+    fs::create_dir_all(target).map_err(|e| AmberError::fs_error(target.to_string_lossy(), e))?;
+    for dir in &plan.dirs {
+        let path = target.join(dir);
+        if !path.is_dir() {
+            fs::create_dir_all(&path)
+                .map_err(|e| AmberError::fs_error(path.to_string_lossy(), e))?;
+            dirs_created += 1;
+        }
+    }
+
+    let progress = Mutex::new(RestoreProgress {
+        files_total: plan.entries.len() as u64,
+        bytes_total: plan.entries.values().map(|e| e.size).sum(),
+        ..RestoreProgress::default()
+    });
+    let bytes_restored = AtomicU64::new(0);
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(if workers == 0 {
+            num_cpus::get()
+        } else {
+            workers
+        })
+        .thread_name(|i| format!("amber-restore-{}", i))
+        .build()
+        .map_err(|e| AmberError::Filesystem(format!("Failed to build restore pool: {}", e)))?;
+
+    let entries: Vec<(&PathBuf, &PlannedEntry)> = plan.entries.iter().collect();
+    pool.install(|| {
+        entries.par_iter().try_for_each(|(relative, entry)| {
+            cancel.check()?;
+            let restored = restore_entry(
+                &snapshot_root.join(relative),
+                &target.join(relative),
+                entry.kind,
+                conflict,
+            )?;
+
+            let mut progress = progress
+                .lock()
+                .map_err(|e| AmberError::Filesystem(format!("Restore progress poisoned: {}", e)))?;
+            progress.files_done += 1;
+            progress.bytes_done += entry.size;
+            if restored {
+                bytes_restored.fetch_add(entry.size, Ordering::SeqCst);
+            } else {
+                progress.files_skipped += 1;
+            }
+            progress.current_path = relative.to_string_lossy().to_string();
+            on_progress(&progress);
+            Ok(())
+        })
+    })?;
+
+    // Folder times last, once nothing is written into them any more
+    for dir in plan.dirs.iter().rev() {
+        let from = snapshot_root.join(dir);
+        if let (Some(mtime), Ok(handle)) = (
+            fs::metadata(&from).ok().as_ref().and_then(modified),
+            File::open(target.join(dir)),
+        ) {
+            let _ = handle.set_modified(mtime);
+        }
+    }
+
+    let progress = progress
+        .into_inner()
+        .map_err(|e| AmberError::Filesystem(format!("Restore progress poisoned: {}", e)))?;
+    Ok(RestoreReport {
+        files_restored: progress.files_done - progress.files_skipped,
+        files_skipped: progress.files_skipped,
+        bytes_restored: bytes_restored.into_inner(),
+        dirs_created,
+    })
+}
@@ -0,0 +1,147 @@
+//! Background priority for backup and index work
+//!
+//! With the `backgroundPriority` preference on, rsync runs under `nice` (and
+//! `ionice` on Linux when it is installed) and the directory walk pool's
+//! threads lower their own priority, so a scheduled backup doesn't make the
+//! machine sluggish. Windows has no equivalent wrappers and is left alone.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::OnceLock;
+
+/// Niceness added to rsync and the walk threads
+pub const NICE_LEVEL: i32 = 10;
+
+static BACKGROUND_PRIORITY: AtomicBool = AtomicBool::new(false);
+
+/// Run backups and index walks at background priority
+pub fn configure(background: bool) {
+    BACKGROUND_PRIORITY.store(background, Ordering::SeqCst);
+}
+
+/// Whether background priority is on
+pub fn enabled() -> bool {
+    BACKGROUND_PRIORITY.load(Ordering::SeqCst)
+}
+
+/// `program args` prefixed with `nice`, and with `ionice` in the best-effort
+/// class's lowest I/O priority when `with_ionice`. Both exec the command, so
+/// the child's pid is still the one to signal when cancelling.
+pub fn niced_command(program: &str, args: &[String], with_ionice: bool) -> (String, Vec<String>) {
+    let mut wrapped = Vec::with_capacity(args.len() + 8);
+    if with_ionice {
+        wrapped.extend(["ionice", "-c", "2", "-n", "7"].map(String::from));
+    }
+    wrapped.extend(["nice".to_string(), "-n".to_string(), NICE_LEVEL.to_string()]);
+    wrapped.push(program.to_string());
+    wrapped.extend_from_slice(args);
+
+    let program = wrapped.remove(0);
+    (program, wrapped)
+}
+
+/// The command to spawn for `program args`: niced if background priority is
+/// on and this is a Unix system, unchanged otherwise
+pub fn apply(program: &str, args: &[String]) -> (String, Vec<String>) {
+    if enabled() && cfg!(unix) {
+        niced_command(program, args, ionice_available())
+    } else {
+        (program.to_string(), args.to_vec())
+    }
+}
+
+/// `ionice` ships with util-linux; other systems don't have it
+fn ionice_available() -> bool {
+    static AVAILABLE: OnceLock<bool> = OnceLock::new();
+    *AVAILABLE.get_or_init(|| {
+        cfg!(target_os = "linux")
+            && std::env::var_os("PATH").is_some_and(|paths| {
+                std::env::split_paths(&paths).any(|dir| dir.join("ionice").is_file())
+            })
+    })
+}
+
+/// Lower the calling thread's priority. Only that thread changes: Linux keeps
+/// a nice value per thread, and macOS marks the thread as background, which
+/// also throttles its I/O. Elsewhere this does nothing.
+pub fn lower_current_thread() {
+    #[cfg(target_os = "linux")]
+    // SAFETY: plain syscalls on the calling thread (who = 0)
+    unsafe {
+        let current = libc::getpriority(libc::PRIO_PROCESS, 0);
+        if libc::setpriority(libc::PRIO_PROCESS, 0, (current + NICE_LEVEL).min(19)) != 0 {
+            log::debug!("Could not lower walk thread priority");
+        }
+    }
+
+    #[cfg(target_os = "macos")]
+    // SAFETY: plain syscall on the calling thread (who = 0)
+    unsafe {
+        if libc::setpriority(libc::PRIO_DARWIN_THREAD, 0, libc::PRIO_DARWIN_BG) != 0 {
+            log::debug!("Could not lower walk thread priority");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(parts: &[&str]) -> Vec<String> {
+        parts.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_niced_command_wraps_rsync() {
+        let args = strings(&["-a", "src/", "dest"]);
+
+        let (program, wrapped) = niced_command("rsync", &args, false);
+        assert_eq!(program, "nice");
+        assert_eq!(
+            wrapped,
+            strings(&["-n", "10", "rsync", "-a", "src/", "dest"])
+        );
+
+        let (program, wrapped) = niced_command("rsync", &args, true);
+        assert_eq!(program, "ionice");
+        assert_eq!(
+            wrapped,
+            strings(&["-c", "2", "-n", "7", "nice", "-n", "10", "rsync", "-a", "src/", "dest"])
+        );
+    }
+
+    #[test]
+    fn test_preference_toggles_wrapping() {
+        let args = strings(&["-a"]);
+
+        configure(false);
+        assert_eq!(apply("rsync", &args), ("rsync".to_string(), args.clone()));
+
+        configure(true);
+        let (program, wrapped) = apply("rsync", &args);
+        configure(false);
+        if cfg!(unix) {
+            assert_ne!(program, "rsync");
+            assert!(wrapped.ends_with(&strings(&["nice", "-n", "10", "rsync", "-a"])));
+        } else {
+            assert_eq!(program, "rsync");
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_lowering_affects_only_the_calling_thread() {
+        // SAFETY: reads the calling thread's nice value
+        let nice = || unsafe { libc::getpriority(libc::PRIO_PROCESS, 0) };
+        let before = nice();
+
+        let lowered = std::thread::spawn(move || {
+            lower_current_thread();
+            nice()
+        })
+        .join()
+        .unwrap();
+
+        assert_eq!(lowered, (before + NICE_LEVEL).min(19));
+        assert_eq!(nice(), before);
+    }
+}
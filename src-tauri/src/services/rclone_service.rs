@@ -3,10 +3,14 @@
 //! Handles rclone detection, remote listing, and sync operations.
 
 use crate::error::{AmberError, Result};
+use crate::services::backup_runner::{RunProgress, RunTracker};
+use crate::types::job::CloudConfig;
+use regex::Regex;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::HashSet;
 use std::process::{Child, Command, Stdio};
-use std::sync::Mutex;
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
 
 /// Information about an rclone remote
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -38,14 +42,146 @@ pub struct RcloneProgress {
     pub current_file: Option<String>,
 }
 
+/// Runs of a sync Amber makes when rclone ends with a temporary error, on
+/// top of rclone's own `--retries`
+pub const DEFAULT_SYNC_ATTEMPTS: u32 = 2;
+
+/// What an rclone exit code means (see rclone's "Exit Code" docs)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum RcloneExit {
+    /// 0, or 9: succeeded without transferring anything
+    Success,
+    /// 1: syntax or usage error
+    Usage,
+    /// 2: error not otherwise categorised
+    Uncategorized,
+    /// 3 or 4: directory or file not found
+    NotFound,
+    /// 5: temporary error that more retries might fix
+    Temporary,
+    /// 6: less serious error rclone itself doesn't retry
+    NoRetry,
+    /// 7: fatal error, e.g. account suspended
+    Fatal,
+    /// 8: `--max-transfer` reached
+    TransferLimit,
+    /// 10: `--max-duration` reached
+    DurationLimit,
+    /// No exit code (killed by a signal)
+    Killed,
+    /// A code rclone doesn't document
+    Unknown(i32),
+}
+
+impl RcloneExit {
+    pub fn from_code(code: Option<i32>) -> Self {
+        match code {
+            Some(0 | 9) => Self::Success,
+            Some(1) => Self::Usage,
+            Some(2) => Self::Uncategorized,
+            Some(3 | 4) => Self::NotFound,
+            Some(5) => Self::Temporary,
+            Some(6) => Self::NoRetry,
+            Some(7) => Self::Fatal,
+            Some(8) => Self::TransferLimit,
+            Some(10) => Self::DurationLimit,
+            Some(other) => Self::Unknown(other),
+            None => Self::Killed,
+        }
+    }
+
+    /// Whether running the sync again may succeed
+    pub fn is_retryable(self) -> bool {
+        self == Self::Temporary
+    }
+
+    pub fn describe(self) -> String {
+        match self {
+            Self::Success => "success".to_string(),
+            Self::Usage => "invalid rclone arguments".to_string(),
+            Self::Uncategorized => "rclone reported an error".to_string(),
+            Self::NotFound => "source or destination not found".to_string(),
+            Self::Temporary => "temporary error".to_string(),
+            Self::NoRetry => "error rclone won't retry".to_string(),
+            Self::Fatal => "fatal error (retrying won't help)".to_string(),
+            Self::TransferLimit => "transfer limit reached".to_string(),
+            Self::DurationLimit => "duration limit reached".to_string(),
+            Self::Killed => "rclone was stopped".to_string(),
+            Self::Unknown(code) => format!("rclone exited with code {}", code),
+        }
+    }
+}
+
+/// `rclone sync` arguments for copying `source_path` to the job's remote
+pub fn sync_args(source_path: &str, cloud: &CloudConfig) -> Vec<String> {
+    // Build destination: remote:path
+    let dest = match cloud.remote_path.as_deref() {
+        Some(path) if !path.is_empty() => format!("{}:{}", cloud.remote_name, path),
+        _ => format!("{}:", cloud.remote_name),
+    };
+    // Progress output for parsing, verbose for better logging
+    let mut args: Vec<String> = [
+        "sync",
+        source_path,
+        dest.as_str(),
+        "--progress",
+        "--stats-one-line",
+        "--stats=1s",
+        "-v",
+    ]
+    .map(String::from)
+    .into();
+
+    if let Some(bw) = cloud.bandwidth.as_deref().filter(|bw| !bw.is_empty()) {
+        args.extend(["--bwlimit".to_string(), bw.to_string()]);
+    }
+    if let Some(retries) = cloud.retries {
+        args.push(format!("--retries={}", retries));
+    }
+    if let Some(retries) = cloud.low_level_retries {
+        args.push(format!("--low-level-retries={}", retries));
+    }
+    if let Some(secs) = cloud.timeout_seconds {
+        args.push(format!("--timeout={}s", secs));
+    }
+    // TODO: Implement rclone encryption (crypt remote wrapper), see
+    // https://rclone.org/crypt/ - `cloud.encrypt` is not applied yet
+    args
+}
+
+/// Progress from an rclone stats line (`--stats-one-line`), e.g.
+/// `1.234 MiB / 10.000 MiB, 12%, 1.000 MiB/s, ETA 8s`, possibly behind a log
+/// prefix. None for any other line.
+pub fn parse_stats_line(line: &str) -> Option<RunProgress> {
+    static STATS: OnceLock<Regex> = OnceLock::new();
+    let stats = STATS.get_or_init(|| {
+        Regex::new(
+            r"(\d[\d.]*\s*\w*B)\s*/\s*\d[\d.]*\s*\w*B,\s*(\d{1,3})%,\s*([\d.]+\s*\w*B/s),\s*ETA\s*(\S+)",
+        )
+        .unwrap()
+    });
+    let caps = stats.captures(line)?;
+    Some(RunProgress {
+        transferred: caps[1].to_string(),
+        percentage: caps[2].parse().ok()?,
+        speed: caps[3].to_string(),
+        eta: Some(caps[4].to_string()).filter(|eta| eta != "-"),
+        current_file: None,
+    })
+}
+
 pub struct RcloneService {
-    active_jobs: Mutex<HashMap<String, u32>>,
+    runs: RunTracker,
+    /// Jobs killed since their sync started, so they aren't retried
+    killed: Mutex<HashSet<String>>,
 }
 
 impl RcloneService {
     pub fn new() -> Self {
         Self {
-            active_jobs: Mutex::new(HashMap::new()),
+            runs: RunTracker::new(),
+            killed: Mutex::new(HashSet::new()),
         }
     }
 
@@ -123,100 +259,117 @@ impl RcloneService {
         Ok(remotes)
     }
 
-    /// Build rclone sync command
-    fn build_sync_command(
+    /// Start one rclone sync run for `cloud`
+    pub fn spawn_sync(
         &self,
+        job_id: &str,
         source_path: &str,
-        remote_name: &str,
-        remote_path: Option<&str>,
-        bandwidth: Option<&str>,
-        _encrypt: bool, // TODO: Implement rclone encryption (crypt remote wrapper)
-                        // See: https://rclone.org/crypt/
-                        // Need to create encrypted remote wrapper and pass through here
-                        // Related: TIM-XXX (create ticket for cloud encryption)
-    ) -> Command {
-        let mut cmd = Command::new("rclone");
-
-        cmd.arg("sync");
-        cmd.arg(source_path);
-
-        // Build destination: remote:path
-        let dest = match remote_path {
-            Some(path) if !path.is_empty() => format!("{}:{}", remote_name, path),
-            _ => format!("{}:", remote_name),
-        };
-        cmd.arg(&dest);
-
-        // Progress output for parsing
-        cmd.arg("--progress");
-        cmd.arg("--stats-one-line");
-        cmd.arg("--stats=1s");
-
-        // Verbose for better logging
-        cmd.arg("-v");
-
-        // Bandwidth limit
-        if let Some(bw) = bandwidth {
-            if !bw.is_empty() {
-                cmd.arg("--bwlimit");
-                cmd.arg(bw);
-            }
-        }
+        cloud: &CloudConfig,
+    ) -> Result<Child> {
+        let child = Command::new("rclone")
+            .args(sync_args(source_path, cloud))
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
 
-        cmd.stdout(Stdio::piped());
-        cmd.stderr(Stdio::piped());
+        // Track the process
+        self.runs.start(job_id, child.id());
 
-        cmd
+        Ok(child)
     }
 
-    /// Start an rclone sync job
-    pub fn spawn_sync(
+    /// Run a sync up to `attempts` times, starting another run only after a
+    /// retryable exit and only while the job hasn't been killed. `run` makes
+    /// one attempt (numbered from 1) and returns rclone's exit code; `backoff`
+    /// is waited between attempts.
+    pub fn sync_with_retries<F>(
         &self,
         job_id: &str,
-        source_path: &str,
-        remote_name: &str,
-        remote_path: Option<&str>,
-        bandwidth: Option<&str>,
-        encrypt: bool,
-    ) -> Result<Child> {
-        let mut cmd =
-            self.build_sync_command(source_path, remote_name, remote_path, bandwidth, encrypt);
+        attempts: u32,
+        backoff: Duration,
+        mut run: F,
+    ) -> Result<()>
+    where
+        F: FnMut(u32) -> Result<Option<i32>>,
+    {
+        if let Ok(mut killed) = self.killed.lock() {
+            killed.remove(job_id);
+        }
+        let attempts = attempts.max(1);
 
-        let child = cmd.spawn()?;
+        let mut attempt = 1;
+        loop {
+            let exit = RcloneExit::from_code(run(attempt)?);
+            if exit == RcloneExit::Success {
+                return Ok(());
+            }
 
-        // Track the process
-        if let Ok(mut jobs) = self.active_jobs.lock() {
-            jobs.insert(job_id.to_string(), child.id());
+            let retry = exit.is_retryable() && attempt < attempts && !self.was_killed(job_id);
+            if !retry {
+                return Err(AmberError::Rclone(format!(
+                    "rclone failed after {} attempt{}: {}",
+                    attempt,
+                    if attempt == 1 { "" } else { "s" },
+                    exit.describe()
+                )));
+            }
+
+            log::warn!(
+                "[rclone] {} on attempt {} of {} for job {}, retrying",
+                exit.describe(),
+                attempt,
+                attempts,
+                job_id
+            );
+            std::thread::sleep(backoff);
+            if self.was_killed(job_id) {
+                return Err(AmberError::Cancelled);
+            }
+            attempt += 1;
         }
+    }
 
-        Ok(child)
+    fn was_killed(&self, job_id: &str) -> bool {
+        self.killed
+            .lock()
+            .map(|killed| killed.contains(job_id))
+            .unwrap_or(false)
     }
 
-    /// Kill a running rclone job
+    /// Kill a running rclone job. A sync in `sync_with_retries` won't retry.
     pub fn kill_job(&self, job_id: &str) -> Result<()> {
-        if let Ok(mut jobs) = self.active_jobs.lock() {
-            if let Some(pid) = jobs.remove(job_id) {
-                #[cfg(unix)]
-                {
-                    let _ = Command::new("kill").args(["-9", &pid.to_string()]).status();
-                }
+        if let Ok(mut killed) = self.killed.lock() {
+            killed.insert(job_id.to_string());
+        }
+        if let Some(pid) = self.runs.take_pid(job_id) {
+            #[cfg(unix)]
+            {
+                let _ = Command::new("kill").args(["-9", &pid.to_string()]).status();
+            }
 
-                #[cfg(windows)]
-                {
-                    let _ = Command::new("taskkill")
-                        .args(["/PID", &pid.to_string(), "/F"])
-                        .status();
-                }
+            #[cfg(windows)]
+            {
+                let _ = Command::new("taskkill")
+                    .args(["/PID", &pid.to_string(), "/F"])
+                    .status();
             }
         }
         Ok(())
     }
 
+    /// Check if a sync is running for the job
+    pub fn is_job_running(&self, job_id: &str) -> bool {
+        self.runs.is_running(job_id)
+    }
+
     /// Mark a job as completed (remove from tracking)
     pub fn mark_completed(&self, job_id: &str) {
-        if let Ok(mut jobs) = self.active_jobs.lock() {
-            jobs.remove(job_id);
-        }
+        self.runs.finish(job_id);
+    }
+
+    /// Running syncs and their latest progress
+    pub fn runs(&self) -> &RunTracker {
+        &self.runs
     }
 }
 
@@ -230,6 +383,125 @@ impl Default for RcloneService {
 mod tests {
     use super::*;
 
+    fn cloud(remote_path: Option<&str>) -> CloudConfig {
+        CloudConfig {
+            remote_name: "b2".to_string(),
+            remote_path: remote_path.map(String::from),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_sync_args_pass_retry_and_timeout_settings() {
+        let plain = sync_args("/data", &cloud(None));
+        assert_eq!(&plain[..3], ["sync", "/data", "b2:"]);
+        assert!(!plain.iter().any(|a| a.starts_with("--retries")
+            || a.starts_with("--low-level-retries")
+            || a.starts_with("--timeout")));
+
+        let tuned = CloudConfig {
+            bandwidth: Some("10M".to_string()),
+            retries: Some(5),
+            low_level_retries: Some(20),
+            timeout_seconds: Some(120),
+            ..cloud(Some("backups/laptop"))
+        };
+        let args = sync_args("/data", &tuned);
+        assert_eq!(args[2], "b2:backups/laptop");
+        for flag in ["--retries=5", "--low-level-retries=20", "--timeout=120s"] {
+            assert!(args.contains(&flag.to_string()), "missing {}", flag);
+        }
+        assert!(args.windows(2).any(|w| w == ["--bwlimit", "10M"]));
+    }
+
+    #[test]
+    fn test_exit_codes_are_classified() {
+        assert_eq!(RcloneExit::from_code(Some(0)), RcloneExit::Success);
+        assert_eq!(RcloneExit::from_code(Some(9)), RcloneExit::Success);
+        assert_eq!(RcloneExit::from_code(Some(5)), RcloneExit::Temporary);
+        assert_eq!(RcloneExit::from_code(Some(7)), RcloneExit::Fatal);
+        assert_eq!(RcloneExit::from_code(Some(42)), RcloneExit::Unknown(42));
+        assert_eq!(RcloneExit::from_code(None), RcloneExit::Killed);
+        assert!(RcloneExit::Temporary.is_retryable());
+        for exit in [
+            RcloneExit::Usage,
+            RcloneExit::NotFound,
+            RcloneExit::NoRetry,
+            RcloneExit::Fatal,
+            RcloneExit::Killed,
+        ] {
+            assert!(!exit.is_retryable(), "{:?}", exit);
+        }
+    }
+
+    #[test]
+    fn test_temporary_failures_retry_until_success() {
+        let service = RcloneService::new();
+        let mut codes = vec![Some(5), Some(5), Some(0)].into_iter();
+        let mut runs = Vec::new();
+        service
+            .sync_with_retries("job", 3, Duration::ZERO, |attempt| {
+                runs.push(attempt);
+                Ok(codes.next().unwrap())
+            })
+            .unwrap();
+        assert_eq!(runs, vec![1, 2, 3]);
+
+        // Out of attempts: the last temporary failure is returned
+        let mut runs = 0;
+        let err = service
+            .sync_with_retries("job", 2, Duration::ZERO, |_| {
+                runs += 1;
+                Ok(Some(5))
+            })
+            .unwrap_err();
+        assert_eq!(runs, 2);
+        assert!(err.to_string().contains("after 2 attempts"), "{}", err);
+    }
+
+    #[test]
+    fn test_fatal_failures_and_kills_do_not_retry() {
+        let service = RcloneService::new();
+        for code in [Some(7), Some(1), Some(3), None] {
+            let mut runs = 0;
+            let result = service.sync_with_retries("job", 5, Duration::ZERO, |_| {
+                runs += 1;
+                Ok(code)
+            });
+            assert!(result.is_err());
+            assert_eq!(runs, 1, "exit {:?}", code);
+        }
+
+        // Killed while an attempt was running: its temporary failure isn't retried
+        let mut runs = 0;
+        let result = service.sync_with_retries("job", 5, Duration::ZERO, |_| {
+            runs += 1;
+            service.kill_job("job").unwrap();
+            Ok(Some(5))
+        });
+        assert!(result.is_err());
+        assert_eq!(runs, 1);
+    }
+
+    #[test]
+    fn test_stats_lines_parse_into_progress() {
+        let progress = parse_stats_line(
+            "2024/05/01 10:00:00 INFO  :    1.234 MiB / 10.000 MiB, 12%, 512.000 KiB/s, ETA 17s",
+        )
+        .unwrap();
+        assert_eq!(progress.transferred, "1.234 MiB");
+        assert_eq!(progress.percentage, 12);
+        assert_eq!(progress.speed, "512.000 KiB/s");
+        assert_eq!(progress.eta.as_deref(), Some("17s"));
+
+        let done = parse_stats_line("Transferred: 10 MiB / 10 MiB, 100%, 0 B/s, ETA -").unwrap();
+        assert_eq!(done.percentage, 100);
+        assert_eq!(done.eta, None);
+
+        assert_eq!(parse_stats_line("0 B / 0 B, -, 0 B/s, ETA -"), None);
+        assert_eq!(parse_stats_line("INFO  : docs/a.txt: Copied (new)"), None);
+    }
+
     #[test]
     fn test_check_installation() {
         let service = RcloneService::new();
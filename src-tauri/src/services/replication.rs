@@ -0,0 +1,209 @@
+//! Snapshot replication to a second destination
+//!
+//! Copies one finished snapshot folder to another drive without re-running
+//! the source backup, then gives the copy the same manifest entry and an index
+//! of its own so the second drive is browsable on its own. The folder keeps
+//! its path relative to the destination root, and hardlinks are preserved
+//! inside the snapshot (`-H`) and against the newest snapshot already on the
+//! replica (`--link-dest`).
+
+use crate::error::{AmberError, Result};
+use crate::services::index_service::{IndexService, IndexedSnapshot};
+use crate::services::manifest_service;
+use crate::services::rsync_service::RsyncService;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+/// Where the replica landed and how it was indexed
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReplicatedSnapshot {
+    pub snapshot_path: String,
+    pub indexed: IndexedSnapshot,
+}
+
+/// Find the snapshot folder for `timestamp` on `dest_path`: the indexed root
+/// if the destination has an index, else the manifest's folder name
+pub(crate) fn locate_snapshot(
+    job_id: &str,
+    timestamp: i64,
+    dest_path: &str,
+    folder_name: &str,
+) -> Result<PathBuf> {
+    if manifest_service::get_index_path(dest_path).exists() {
+        let index = IndexService::for_destination(dest_path)?;
+        if let Some(snapshot) = index
+            .list_snapshots(job_id)?
+            .into_iter()
+            .find(|s| s.timestamp == timestamp)
+        {
+            let root = PathBuf::from(snapshot.root_path);
+            if root.is_dir() {
+                return Ok(root);
+            }
+        }
+    }
+
+    let root = Path::new(dest_path).join(folder_name);
+    if root.is_dir() {
+        return Ok(root);
+    }
+
+    Err(AmberError::NotFound(format!(
+        "Snapshot folder for {} not found on {}",
+        timestamp, dest_path
+    )))
+}
+
+/// Copy the snapshot at `timestamp` from `src_dest` to `dst_dest`
+pub async fn replicate_snapshot(
+    job_id: &str,
+    timestamp: i64,
+    src_dest: &str,
+    dst_dest: &str,
+) -> Result<ReplicatedSnapshot> {
+    let manifest = manifest_service::read_manifest(src_dest)
+        .await
+        .map_err(|e| AmberError::Snapshot(format!("Failed to read manifest: {}", e)))?
+        .ok_or_else(|| AmberError::NotFound(format!("No manifest on {}", src_dest)))?;
+    if manifest.job_id != job_id {
+        return Err(AmberError::snapshot_for_job(
+            job_id,
+            format!("{} belongs to job '{}'", src_dest, manifest.job_id),
+        ));
+    }
+    let entry = manifest
+        .snapshots
+        .iter()
+        .find(|s| s.timestamp == timestamp)
+        .cloned()
+        .ok_or_else(|| {
+            AmberError::NotFound(format!("Snapshot {} not found in manifest", timestamp))
+        })?;
+
+    let src_root = Path::new(src_dest)
+        .canonicalize()
+        .map_err(|e| AmberError::InvalidPath(format!("Invalid source destination: {}", e)))?;
+    let dst_root = Path::new(dst_dest)
+        .canonicalize()
+        .map_err(|e| AmberError::InvalidPath(format!("Invalid target destination: {}", e)))?;
+    if src_root == dst_root {
+        return Err(AmberError::ValidationError(
+            "Source and target destinations are the same".to_string(),
+        ));
+    }
+
+    let snapshot_dir = locate_snapshot(job_id, timestamp, src_dest, &entry.folder_name)?
+        .canonicalize()
+        .map_err(|e| AmberError::InvalidPath(format!("Cannot resolve snapshot: {}", e)))?;
+    let relative = snapshot_dir.strip_prefix(&src_root).map_err(|_| {
+        AmberError::PermissionDenied("Snapshot folder is outside source destination".to_string())
+    })?;
+
+    let replica = dst_root.join(relative);
+    let replica_base = replica.parent().unwrap_or(&dst_root).to_path_buf();
+    std::fs::create_dir_all(&replica_base)?;
+
+    // Re-replicating into an existing copy must not link against itself
+    let rsync = RsyncService::new();
+    let link_dest = match rsync.get_latest_backup(&replica_base.to_string_lossy()) {
+        Some(previous) if previous != replica => rsync.link_dest_for(&replica_base, &replica),
+        _ => None,
+    };
+
+    let mut args = vec!["-aH".to_string()];
+    if let Some(link_dest) = link_dest {
+        args.push(format!("--link-dest={}", link_dest.display()));
+    }
+    args.push("--".to_string());
+    args.push(format!("{}/", snapshot_dir.display()));
+    args.push(format!("{}/", replica.display()));
+
+    log::info!(
+        job_id = job_id, operation = "replicate";
+        "Replicating {} to {}", snapshot_dir.display(), replica.display()
+    );
+    let output = tokio::task::spawn_blocking(move || {
+        Command::new("rsync")
+            .args(&args)
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .output()
+    })
+    .await
+    .map_err(|e| AmberError::Rsync(format!("Replication task failed: {}", e)))?
+    .map_err(|e| AmberError::Rsync(format!("Failed to run rsync: {}", e)))?;
+    if !output.status.success() {
+        return Err(AmberError::Rsync(format!(
+            "Replication failed with code {:?}: {}",
+            output.status.code(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+
+    let dst_dest = dst_root.to_string_lossy().to_string();
+    let mut dst_manifest = manifest_service::get_or_create_manifest(
+        &dst_dest,
+        job_id,
+        &manifest.job_name,
+        &manifest.source_path,
+    )
+    .await
+    .map_err(|e| AmberError::Snapshot(format!("Failed to update manifest: {}", e)))?;
+    dst_manifest.remove_snapshot(&entry.id);
+    dst_manifest.add_snapshot(entry);
+    manifest_service::write_manifest(&dst_dest, &dst_manifest)
+        .await
+        .map_err(|e| AmberError::Snapshot(format!("Failed to update manifest: {}", e)))?;
+
+    let snapshot_path = replica.to_string_lossy().to_string();
+    let indexed = {
+        let job_id = job_id.to_string();
+        let snapshot_path = snapshot_path.clone();
+        tokio::task::spawn_blocking(move || {
+            IndexService::for_destination(&dst_dest)?.index_snapshot(
+                &job_id,
+                timestamp,
+                &snapshot_path,
+            )
+        })
+        .await
+        .map_err(|e| AmberError::Index(format!("Replica indexing task failed: {}", e)))??
+    };
+
+    Ok(ReplicatedSnapshot {
+        snapshot_path,
+        indexed,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_locate_snapshot_prefers_indexed_root() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let dest = temp.path().to_str().unwrap();
+        let nested = temp.path().join("Documents").join("2024-01-01-120000");
+        std::fs::create_dir_all(&nested).unwrap();
+        std::fs::write(nested.join("a.txt"), "a").unwrap();
+
+        // No index: the manifest folder name is taken relative to the root
+        assert!(locate_snapshot("job-1", 1704110400000, dest, "2024-01-01-120000").is_err());
+        assert_eq!(
+            locate_snapshot("job-1", 1704110400000, dest, "Documents/2024-01-01-120000").unwrap(),
+            nested
+        );
+
+        IndexService::for_destination(dest)
+            .unwrap()
+            .index_snapshot("job-1", 1704110400000, nested.to_str().unwrap())
+            .unwrap();
+        assert_eq!(
+            locate_snapshot("job-1", 1704110400000, dest, "2024-01-01-120000").unwrap(),
+            nested
+        );
+    }
+}
@@ -0,0 +1,457 @@
+//! Snapshot retention planning
+//!
+//! Decides which snapshots to keep for a grandfather-father-son policy.
+//! Timestamps are milliseconds and are bucketed on UTC calendar boundaries,
+//! matching how snapshot folder names are parsed in `SnapshotService`.
+//! `prune_snapshots` applies a plan to the snapshot folders on a destination.
+
+use crate::error::{AmberError, Result};
+use crate::services::index_backfill::find_snapshot_folders;
+use crate::services::index_service::IndexService;
+use crate::services::manifest_service;
+use crate::services::rsync_service;
+use crate::types::job::SyncJob;
+use crate::types::manifest::ManifestSnapshotStatus;
+use chrono::{DateTime, Datelike, Timelike, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+/// How many snapshots to keep per time bucket
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RetentionPolicy {
+    #[serde(default)]
+    pub keep_hourly: usize,
+    #[serde(default)]
+    pub keep_daily: usize,
+    #[serde(default)]
+    pub keep_weekly: usize,
+    #[serde(default)]
+    pub keep_monthly: usize,
+    /// Always keep the N most recent snapshots, on top of the buckets
+    #[serde(default)]
+    pub keep_last: Option<usize>,
+}
+
+impl RetentionPolicy {
+    /// Whether the policy would keep any snapshot at all
+    pub fn keeps_any(&self) -> bool {
+        self.keep_hourly > 0
+            || self.keep_daily > 0
+            || self.keep_weekly > 0
+            || self.keep_monthly > 0
+            || self.keep_last.is_some_and(|n| n > 0)
+    }
+}
+
+/// Result of applying a policy: both lists are sorted newest first
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RetentionPlan {
+    pub keep: Vec<i64>,
+    pub prune: Vec<i64>,
+}
+
+#[derive(Clone, Copy)]
+enum Bucket {
+    Hour,
+    Day,
+    Week,
+    Month,
+}
+
+impl Bucket {
+    fn key(self, dt: &DateTime<Utc>) -> (i32, u32, u32, u32) {
+        match self {
+            Bucket::Hour => (dt.year(), dt.month(), dt.day(), dt.hour()),
+            Bucket::Day => (dt.year(), dt.month(), dt.day(), 0),
+            Bucket::Week => {
+                let week = dt.iso_week();
+                (week.year(), week.week(), 0, 0)
+            }
+            Bucket::Month => (dt.year(), dt.month(), 0, 0),
+        }
+    }
+}
+
+/// Work out which snapshots survive `policy`.
+///
+/// Protection is additive: a snapshot is kept if it is locked, among the
+/// `keep_last` most recent, or the newest complete snapshot of a retained
+/// bucket. `incomplete` snapshots (failed or partial runs) never fill a
+/// bucket, so they can't push out the complete one sharing their period.
+pub fn plan_retention(
+    timestamps: &[i64],
+    policy: &RetentionPolicy,
+    locked: &[i64],
+    incomplete: &[i64],
+) -> RetentionPlan {
+    let mut sorted: Vec<i64> = timestamps.to_vec();
+    sorted.sort_unstable_by_key(|&ts| std::cmp::Reverse(ts));
+    sorted.dedup();
+
+    let mut keep: HashSet<i64> = locked.iter().copied().collect();
+
+    if let Some(n) = policy.keep_last {
+        keep.extend(sorted.iter().take(n));
+    }
+
+    for (bucket, count) in [
+        (Bucket::Hour, policy.keep_hourly),
+        (Bucket::Day, policy.keep_daily),
+        (Bucket::Week, policy.keep_weekly),
+        (Bucket::Month, policy.keep_monthly),
+    ] {
+        let mut seen = HashSet::new();
+        for &ts in sorted.iter().filter(|ts| !incomplete.contains(ts)) {
+            if seen.len() >= count {
+                break;
+            }
+            let Some(dt) = DateTime::<Utc>::from_timestamp_millis(ts) else {
+                continue;
+            };
+            if seen.insert(bucket.key(&dt)) {
+                keep.insert(ts);
+            }
+        }
+    }
+
+    let (keep, prune) = sorted.into_iter().partition(|ts| keep.contains(ts));
+    RetentionPlan { keep, prune }
+}
+
+/// A snapshot folder `prune_snapshots` removed, or would remove on a dry run
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PrunedSnapshot {
+    pub timestamp: i64,
+    pub folder_name: String,
+    pub path: String,
+}
+
+/// Delete the snapshot folders of `job` on its destination that `policy`
+/// doesn't keep, along with their index rows and manifest entries. With
+/// `dry_run` nothing is deleted and the folders that would be are returned.
+///
+/// Only folders in the job's target base that its manifest records are
+/// considered, timed and classed by their manifest entry; failed and partial
+/// snapshots survive only through `keep_last`. anything else there may belong
+/// to another job sharing the folder and is left alone. Without a manifest
+/// nothing can be attributed to the job, so pruning is refused.
+/// `active_snapshot`, the folder a running backup is writing, is always kept.
+/// A folder that can't be removed is left out of the result and keeps its
+/// index rows.
+pub async fn prune_snapshots(
+    job: &SyncJob,
+    policy: &RetentionPolicy,
+    active_snapshot: Option<PathBuf>,
+    dry_run: bool,
+) -> Result<Vec<PrunedSnapshot>> {
+    if !policy.keeps_any() {
+        return Err(AmberError::ValidationError(
+            "Retention policy keeps no snapshots".to_string(),
+        ));
+    }
+    let job_id = job.id.as_str();
+    let dest_path = job.dest_path.as_str();
+    let manifest = manifest_service::read_manifest(dest_path)
+        .await
+        .map_err(|e| AmberError::Snapshot(format!("Failed to read manifest: {}", e)))?;
+    let Some(manifest) = manifest else {
+        return Err(AmberError::ValidationError(format!(
+            "{} has no manifest, so its snapshots can't be attributed to a job",
+            dest_path
+        )));
+    };
+    if manifest.job_id != job_id {
+        return Err(AmberError::snapshot_for_job(
+            job_id,
+            format!("{} belongs to job '{}'", dest_path, manifest.job_id),
+        ));
+    }
+    let recorded: HashMap<&str, (i64, bool)> = manifest
+        .snapshots
+        .iter()
+        .map(|s| {
+            let complete = s.status == ManifestSnapshotStatus::Complete;
+            (s.folder_name.as_str(), (s.timestamp, complete))
+        })
+        .collect();
+
+    Path::new(dest_path)
+        .canonicalize()
+        .map_err(|e| AmberError::InvalidPath(format!("Invalid destination: {}", e)))?;
+    let Ok(target_base) = rsync_service::target_base(job)?.canonicalize() else {
+        // Nothing has been backed up into this destination yet
+        return Ok(Vec::new());
+    };
+    let active = active_snapshot.and_then(|p| p.canonicalize().ok());
+    let mut folders: Vec<(i64, String, PathBuf)> = Vec::new();
+    let mut locked = Vec::new();
+    let mut incomplete = Vec::new();
+    for path in find_snapshot_folders(&target_base)? {
+        let folder_name = path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let Some(&(timestamp, complete)) = recorded.get(folder_name.as_str()) else {
+            continue;
+        };
+        // Symlinked folders may lead out of the destination; leave them be
+        if path.canonicalize().ok().as_ref() != Some(&path) {
+            continue;
+        }
+        if active.as_ref() == Some(&path) {
+            locked.push(timestamp);
+        }
+        if !complete {
+            incomplete.push(timestamp);
+        }
+        folders.push((timestamp, folder_name, path));
+    }
+
+    let timestamps: Vec<i64> = folders.iter().map(|(ts, _, _)| *ts).collect();
+    let plan = plan_retention(&timestamps, policy, &locked, &incomplete);
+    let prune: HashSet<i64> = plan.prune.into_iter().collect();
+    folders.retain(|(ts, _, _)| prune.contains(ts));
+    folders.sort_by_key(|(ts, _, _)| std::cmp::Reverse(*ts));
+
+    let candidates = folders
+        .into_iter()
+        .map(|(timestamp, folder_name, path)| PrunedSnapshot {
+            timestamp,
+            folder_name,
+            path: path.to_string_lossy().to_string(),
+        });
+    if dry_run {
+        return Ok(candidates.collect());
+    }
+
+    let index = if manifest_service::get_index_path(dest_path).exists() {
+        Some(IndexService::for_destination(dest_path)?)
+    } else {
+        None
+    };
+    let mut pruned = Vec::new();
+    for snapshot in candidates {
+        if let Err(e) = tokio::fs::remove_dir_all(&snapshot.path).await {
+            log::warn!(
+                job_id = job_id, operation = "prune";
+                "Failed to remove snapshot folder {}: {}", snapshot.path, e
+            );
+            continue;
+        }
+        if let Some(index) = &index {
+            if let Err(e) = index.delete_snapshot(job_id, snapshot.timestamp) {
+                log::warn!(
+                    job_id = job_id, operation = "prune";
+                    "Failed to remove snapshot {} from the index: {}", snapshot.timestamp, e
+                );
+            }
+        }
+        pruned.push(snapshot);
+    }
+
+    let ids: Vec<String> = manifest
+        .snapshots
+        .iter()
+        .filter(|s| pruned.iter().any(|p| p.folder_name == s.folder_name))
+        .map(|s| s.id.clone())
+        .collect();
+    if !ids.is_empty() {
+        let mut manifest = manifest;
+        for id in &ids {
+            manifest.remove_snapshot(id);
+        }
+        manifest_service::write_manifest(dest_path, &manifest)
+            .await
+            .map_err(|e| AmberError::Snapshot(format!("Failed to update manifest: {}", e)))?;
+    }
+
+    log::info!(
+        job_id = job_id, operation = "prune";
+        "Pruned {} snapshots on {}", pruned.len(), dest_path
+    );
+    Ok(pruned)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn ts(y: i32, m: u32, d: u32, h: u32) -> i64 {
+        Utc.with_ymd_and_hms(y, m, d, h, 0, 0)
+            .unwrap()
+            .timestamp_millis()
+    }
+
+    #[test]
+    fn test_daily_bucket_keeps_newest_per_day() {
+        let snaps = vec![ts(2024, 1, 1, 9), ts(2024, 1, 1, 18), ts(2024, 1, 2, 9)];
+        let policy = RetentionPolicy {
+            keep_daily: 2,
+            ..Default::default()
+        };
+
+        let plan = plan_retention(&snaps, &policy, &[], &[]);
+        assert_eq!(plan.keep, vec![ts(2024, 1, 2, 9), ts(2024, 1, 1, 18)]);
+        assert_eq!(plan.prune, vec![ts(2024, 1, 1, 9)]);
+    }
+
+    #[test]
+    fn test_keep_last_protects_snapshots_buckets_would_prune() {
+        // Four runs on the same day: a daily bucket alone keeps only the newest
+        let snaps = vec![
+            ts(2024, 1, 1, 6),
+            ts(2024, 1, 1, 12),
+            ts(2024, 1, 1, 18),
+            ts(2024, 1, 1, 23),
+        ];
+        let buckets_only = RetentionPolicy {
+            keep_daily: 1,
+            ..Default::default()
+        };
+        assert_eq!(
+            plan_retention(&snaps, &buckets_only, &[], &[]).keep.len(),
+            1
+        );
+
+        let with_keep_last = RetentionPolicy {
+            keep_last: Some(3),
+            ..buckets_only
+        };
+        let plan = plan_retention(&snaps, &with_keep_last, &[], &[]);
+        assert_eq!(
+            plan.keep,
+            vec![ts(2024, 1, 1, 23), ts(2024, 1, 1, 18), ts(2024, 1, 1, 12)]
+        );
+        assert_eq!(plan.prune, vec![ts(2024, 1, 1, 6)]);
+    }
+
+    #[test]
+    fn test_locked_keep_last_and_buckets_are_additive() {
+        let snaps = vec![
+            ts(2024, 1, 1, 12), // locked
+            ts(2024, 2, 1, 12), // monthly bucket
+            ts(2024, 3, 1, 12),
+            ts(2024, 3, 2, 12), // keep_last
+        ];
+        let policy = RetentionPolicy {
+            keep_monthly: 2,
+            keep_last: Some(1),
+            ..Default::default()
+        };
+
+        let plan = plan_retention(&snaps, &policy, &[ts(2024, 1, 1, 12)], &[]);
+        assert_eq!(
+            plan.keep,
+            vec![ts(2024, 3, 2, 12), ts(2024, 2, 1, 12), ts(2024, 1, 1, 12)]
+        );
+        assert_eq!(plan.prune, vec![ts(2024, 3, 1, 12)]);
+    }
+
+    #[test]
+    fn test_hourly_bucket_keeps_newest_per_hour() {
+        let at = |h: u32, m: u32| {
+            Utc.with_ymd_and_hms(2024, 1, 1, h, m, 0)
+                .unwrap()
+                .timestamp_millis()
+        };
+        let snaps = vec![at(10, 5), at(10, 40), at(11, 10), at(12, 0)];
+        let policy = RetentionPolicy {
+            keep_hourly: 2,
+            ..Default::default()
+        };
+
+        let plan = plan_retention(&snaps, &policy, &[], &[]);
+        assert_eq!(plan.keep, vec![at(12, 0), at(11, 10)]);
+        assert_eq!(plan.prune, vec![at(10, 40), at(10, 5)]);
+    }
+
+    #[test]
+    fn test_weekly_bucket_follows_iso_weeks() {
+        let snaps = vec![
+            ts(2024, 1, 1, 12),   // Monday, week 1
+            ts(2024, 1, 7, 12),   // Sunday, still week 1
+            ts(2024, 1, 8, 12),   // Monday, week 2
+            ts(2024, 12, 30, 12), // Monday, week 1 of 2025
+            ts(2025, 1, 2, 12),
+        ];
+        let policy = RetentionPolicy {
+            keep_weekly: 3,
+            ..Default::default()
+        };
+
+        let plan = plan_retention(&snaps, &policy, &[], &[]);
+        assert_eq!(
+            plan.keep,
+            vec![ts(2025, 1, 2, 12), ts(2024, 1, 8, 12), ts(2024, 1, 7, 12)]
+        );
+        assert_eq!(plan.prune, vec![ts(2024, 12, 30, 12), ts(2024, 1, 1, 12)]);
+    }
+
+    #[test]
+    fn test_grandfather_father_son_over_half_a_year_of_dailies() {
+        // One snapshot a day at noon, 2024-01-01 (Monday) to 2024-06-30 (Sunday)
+        let start = ts(2024, 1, 1, 12);
+        let day = 24 * 60 * 60 * 1000;
+        let snaps: Vec<i64> = (0..182).map(|i| start + i * day).collect();
+        let policy = RetentionPolicy {
+            keep_daily: 7,
+            keep_weekly: 4,
+            keep_monthly: 6,
+            ..Default::default()
+        };
+
+        let plan = plan_retention(&snaps, &policy, &[], &[]);
+        let mut expected: Vec<i64> = (24..=30).map(|d| ts(2024, 6, d, 12)).collect();
+        // Sundays closing the three weeks before the last
+        expected.extend([ts(2024, 6, 23, 12), ts(2024, 6, 16, 12), ts(2024, 6, 9, 12)]);
+        // Month ends
+        expected.extend([
+            ts(2024, 5, 31, 12),
+            ts(2024, 4, 30, 12),
+            ts(2024, 3, 31, 12),
+            ts(2024, 2, 29, 12),
+            ts(2024, 1, 31, 12),
+        ]);
+        expected.sort_unstable_by_key(|&ts| std::cmp::Reverse(ts));
+        expected.dedup();
+        assert_eq!(plan.keep, expected);
+        assert_eq!(plan.prune.len(), 182 - 15);
+    }
+
+    #[test]
+    fn test_incomplete_snapshots_do_not_fill_buckets() {
+        // A failed run later on the same day as a complete one
+        let complete = ts(2024, 1, 1, 9);
+        let failed = ts(2024, 1, 1, 18);
+        let snaps = vec![complete, failed];
+        let policy = RetentionPolicy {
+            keep_daily: 1,
+            ..Default::default()
+        };
+
+        let plan = plan_retention(&snaps, &policy, &[], &[failed]);
+        assert_eq!(plan.keep, vec![complete]);
+        assert_eq!(plan.prune, vec![failed]);
+
+        let with_keep_last = RetentionPolicy {
+            keep_last: Some(1),
+            ..policy
+        };
+        let plan = plan_retention(&snaps, &with_keep_last, &[], &[failed]);
+        assert_eq!(plan.keep, vec![failed, complete]);
+        assert!(plan.prune.is_empty());
+    }
+
+    #[test]
+    fn test_empty_policy_prunes_everything_unprotected() {
+        let snaps = vec![ts(2024, 1, 1, 12), ts(2024, 1, 2, 12)];
+        let plan = plan_retention(&snaps, &RetentionPolicy::default(), &[], &[]);
+        assert!(plan.keep.is_empty());
+        assert_eq!(plan.prune.len(), 2);
+    }
+}
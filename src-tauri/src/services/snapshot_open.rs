@@ -0,0 +1,263 @@
+//! Opening a single file from a snapshot without restoring it
+//!
+//! The file is copied out of the snapshot into its own folder under the app's
+//! cache, readable by the user only, keeping its name so the default app recognises it, and the
+//! copy's path is handed to the opener. Editing the copy never touches the
+//! backup. Every extraction is registered with the time it was made, and
+//! `watch_extractions` removes the ones older than `MAX_EXTRACTION_AGE` once
+//! an hour. Folders left behind by an earlier run are aged by their mtime.
+
+use crate::error::{AmberError, Result};
+use crate::services::data_dir;
+use crate::services::index_service::IndexService;
+use crate::services::manifest_service;
+use crate::services::replication::locate_snapshot;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, SystemTime};
+
+/// How long an extracted copy is kept
+pub const MAX_EXTRACTION_AGE: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// How often `watch_extractions` looks for expired copies
+const CLEANUP_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Extraction folders made by this run, with when they were made
+fn extractions() -> &'static Mutex<HashMap<PathBuf, SystemTime>> {
+    static EXTRACTIONS: OnceLock<Mutex<HashMap<PathBuf, SystemTime>>> = OnceLock::new();
+    EXTRACTIONS.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// Where extracted copies go: the app's cache rather than the shared temp
+/// directory, since they are copies of backed up files
+pub fn extraction_root() -> PathBuf {
+    let data_dir = if data_dir::is_initialized() {
+        data_dir::get().clone()
+    } else {
+        data_dir::default_data_dir()
+    };
+    data_dir.join("cache/opened")
+}
+
+/// Create `dir` (and any missing parents) with mode 0700, and tighten it to
+/// 0700 if it already existed
+fn create_private_dir(dir: &Path) -> std::io::Result<()> {
+    let mut builder = std::fs::DirBuilder::new();
+    builder.recursive(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+        builder.mode(0o700).create(dir)?;
+        std::fs::set_permissions(dir, std::fs::Permissions::from_mode(0o700))?;
+    }
+    #[cfg(not(unix))]
+    builder.create(dir)?;
+    Ok(())
+}
+
+/// Whether `path` is, or lies in, an extraction awaiting cleanup
+pub fn is_registered(path: &Path) -> bool {
+    extractions()
+        .lock()
+        .map(|dirs| dirs.keys().any(|dir| path.starts_with(dir)))
+        .unwrap_or(false)
+}
+
+/// `path` relative to the snapshot root, without empty or `.` components.
+/// `..` is refused.
+fn relative_path(path: &str) -> Result<PathBuf> {
+    let mut relative = PathBuf::new();
+    for component in path.split(['/', '\\']) {
+        match component {
+            "" | "." => continue,
+            ".." => {
+                return Err(AmberError::InvalidPath(format!(
+                    "Path must not contain '..': {}",
+                    path
+                )))
+            }
+            c => relative.push(c),
+        }
+    }
+    if relative.as_os_str().is_empty() {
+        return Err(AmberError::InvalidPath(
+            "A path inside the snapshot is required".to_string(),
+        ));
+    }
+    Ok(relative)
+}
+
+/// Copy `path` (relative to the snapshot root) out of the snapshot at
+/// `timestamp` on `dest_path` into a fresh folder under `extract_root`, and
+/// return the copy's path. The file has to be both in the snapshot's index,
+/// when the snapshot is indexed, and on disk.
+pub async fn open_file_from_snapshot(
+    job_id: &str,
+    timestamp: i64,
+    dest_path: &str,
+    path: &str,
+    extract_root: &Path,
+) -> Result<PathBuf> {
+    let relative = relative_path(path)?;
+
+    let manifest = manifest_service::read_manifest(dest_path)
+        .await
+        .map_err(|e| AmberError::Snapshot(format!("Failed to read manifest: {}", e)))?
+        .ok_or_else(|| AmberError::NotFound(format!("No manifest on {}", dest_path)))?;
+    if manifest.job_id != job_id {
+        return Err(AmberError::snapshot_for_job(
+            job_id,
+            format!("{} belongs to job '{}'", dest_path, manifest.job_id),
+        ));
+    }
+    let entry = manifest
+        .snapshots
+        .iter()
+        .find(|s| s.timestamp == timestamp)
+        .ok_or_else(|| {
+            AmberError::NotFound(format!("Snapshot {} not found in manifest", timestamp))
+        })?;
+
+    let job_id = job_id.to_string();
+    let dest_path = dest_path.to_string();
+    let folder_name = entry.folder_name.clone();
+    let path = path.to_string();
+    let extract_root = extract_root.to_path_buf();
+    tokio::task::spawn_blocking(move || {
+        let snapshot_dir = locate_snapshot(&job_id, timestamp, &dest_path, &folder_name)?
+            .canonicalize()
+            .map_err(|e| AmberError::InvalidPath(format!("Cannot resolve snapshot: {}", e)))?;
+
+        let mut on_disk = relative;
+        if manifest_service::get_index_path(&dest_path).exists() {
+            let index = IndexService::for_destination(&dest_path)?;
+            if index.is_indexed(&job_id, timestamp)? {
+                if !index
+                    .find_path_in_snapshots(&job_id, &path)?
+                    .iter()
+                    .any(|occurrence| occurrence.timestamp == timestamp)
+                {
+                    return Err(AmberError::NotFound(format!(
+                        "{} is not in snapshot {}",
+                        path, timestamp
+                    )));
+                }
+                // The stored name may be escaped or normalized; open the
+                // spelling that is on disk
+                let stored = on_disk
+                    .components()
+                    .map(|c| c.as_os_str().to_string_lossy())
+                    .collect::<Vec<_>>()
+                    .join("/");
+                if let Some(raw) = index.restore_paths_at(&job_id, timestamp, &[stored])?.pop() {
+                    on_disk = raw;
+                }
+            }
+        }
+
+        // Resolved, so a symlink in the snapshot can't lead outside it
+        let source = snapshot_dir.join(&on_disk).canonicalize().map_err(|_| {
+            AmberError::NotFound(format!("{} is missing from the snapshot folder", path))
+        })?;
+        if !source.starts_with(&snapshot_dir) {
+            return Err(AmberError::PermissionDenied(
+                "Path resolves outside the snapshot".to_string(),
+            ));
+        }
+        if !source.is_file() {
+            return Err(AmberError::InvalidPath(format!("{} is not a file", path)));
+        }
+
+        extract_file(&source, &extract_root)
+    })
+    .await
+    .map_err(|e| AmberError::Snapshot(format!("Extraction task failed: {}", e)))?
+}
+
+/// Copy `source` into a new folder under `extract_root`, keeping its name
+/// and mtime, and register the folder for cleanup
+fn extract_file(source: &Path, extract_root: &Path) -> Result<PathBuf> {
+    let name = source
+        .file_name()
+        .ok_or_else(|| AmberError::InvalidPath(format!("No file name: {}", source.display())))?;
+    create_private_dir(extract_root)?;
+    let dir = extract_root.join(uuid::Uuid::new_v4().to_string());
+    create_private_dir(&dir)?;
+    let target = dir.join(name);
+
+    let copied = std::fs::copy(source, &target).and_then(|_| {
+        let modified = std::fs::metadata(source)?.modified()?;
+        std::fs::File::options()
+            .write(true)
+            .open(&target)?
+            .set_modified(modified)
+    });
+    if let Err(e) = copied {
+        let _ = std::fs::remove_dir_all(&dir);
+        return Err(e.into());
+    }
+
+    if let Ok(mut dirs) = extractions().lock() {
+        dirs.insert(dir, SystemTime::now());
+    }
+    Ok(target)
+}
+
+/// Remove the extraction folders under `extract_root` made at least
+/// `max_age` before `now`; returns how many were removed
+pub fn cleanup_extractions(extract_root: &Path, max_age: Duration, now: SystemTime) -> usize {
+    let entries = match std::fs::read_dir(extract_root) {
+        Ok(entries) => entries,
+        Err(_) => return 0,
+    };
+
+    let mut removed = 0;
+    for entry in entries.flatten() {
+        let dir = entry.path();
+        if !entry.file_type().map(|t| t.is_dir()).unwrap_or(false) {
+            continue;
+        }
+        let registered = extractions()
+            .lock()
+            .ok()
+            .and_then(|dirs| dirs.get(&dir).copied());
+        let made = match registered {
+            Some(made) => made,
+            None => match entry.metadata().and_then(|m| m.modified()) {
+                Ok(modified) => modified,
+                Err(_) => continue,
+            },
+        };
+        if now.duration_since(made).unwrap_or_default() < max_age {
+            continue;
+        }
+
+        match std::fs::remove_dir_all(&dir) {
+            Ok(()) => {
+                removed += 1;
+                if let Ok(mut dirs) = extractions().lock() {
+                    dirs.remove(&dir);
+                }
+            }
+            Err(e) => log::warn!("Failed to remove extracted copy {}: {}", dir.display(), e),
+        }
+    }
+    removed
+}
+
+/// Remove expired extractions now and then once every `CLEANUP_INTERVAL`
+pub async fn watch_extractions() {
+    let mut interval = tokio::time::interval(CLEANUP_INTERVAL);
+    loop {
+        interval.tick().await;
+        let removed = tokio::task::spawn_blocking(|| {
+            cleanup_extractions(&extraction_root(), MAX_EXTRACTION_AGE, SystemTime::now())
+        })
+        .await
+        .unwrap_or(0);
+        if removed > 0 {
+            log::info!("Removed {} expired extracted copies", removed);
+        }
+    }
+}
@@ -1,6 +1,6 @@
 use crate::error::Result;
 use crate::services::index_service::IndexService;
-use crate::services::manifest_service;
+use crate::services::{manifest_service, snapshot_commit};
 use crate::types::snapshot::{file_type, FileNode, SnapshotMetadata};
 use base64::engine::general_purpose::URL_SAFE_NO_PAD;
 use base64::Engine;
@@ -71,6 +71,26 @@ impl SnapshotService {
         job_id: &str,
         dest_path: &str,
     ) -> Result<Vec<SnapshotMetadata>> {
+        // Repair index/manifest disagreement left by an interrupted backup.
+        // A running backup's pending entry is not interrupted, so leave the
+        // destination alone until it finishes.
+        let backing_up = crate::commands::rsync::get_rsync_service()
+            .get_backup_info(job_id)
+            .is_some();
+        if backing_up {
+            log::debug!(
+                "[snapshot_service] Skipping reconcile on {} while job {} is backing up",
+                dest_path,
+                job_id
+            );
+        } else if let Err(e) = snapshot_commit::reconcile_pending(dest_path).await {
+            log::warn!(
+                "[snapshot_service] Failed to reconcile pending snapshots on {}: {}",
+                dest_path,
+                e
+            );
+        }
+
         // TIM-191: Check SQLite index first (fastest and most reliable)
         if let Some(snapshots) = self.list_snapshots_from_index(job_id, dest_path) {
             log::debug!(
@@ -141,6 +161,8 @@ impl SnapshotService {
                 status: "Complete".to_string(), // Index only contains complete snapshots
                 duration: None,
                 changes_count: None,
+                changes: None,
+                index_drift: None,
             })
             .collect();
 
@@ -183,6 +205,8 @@ impl SnapshotService {
                     status: format!("{:?}", s.status),
                     duration: s.duration_ms,
                     changes_count: s.changes_count,
+                    changes: s.changes,
+                    index_drift: s.index_drift,
                 }
             })
             .collect();
@@ -254,6 +278,8 @@ impl SnapshotService {
                     status: "Complete".to_string(), // Assume complete for filesystem fallback
                     duration: None,
                     changes_count: None,
+                    changes: None,
+                    index_drift: None,
                 });
             }
         }
@@ -307,12 +333,25 @@ impl SnapshotService {
     }
 
     /// Get the file tree for a snapshot
+    ///
+    /// The SQLite index is authoritative: when `index` has this snapshot the
+    /// tree comes from it and any JSON cache entry is dropped. The JSON cache
+    /// only memoizes filesystem scans of snapshots that were never indexed, so
+    /// the two can't disagree after a re-index.
     pub async fn get_snapshot_tree(
         &self,
+        index: Option<&IndexService>,
         job_id: &str,
         timestamp: i64,
         snapshot_path: &str,
     ) -> Result<Vec<FileNode>> {
+        if let Some(index) = index {
+            if index.is_indexed(job_id, timestamp)? {
+                self.invalidate_cache(job_id, timestamp).await;
+                return index.get_directory_contents(job_id, timestamp, "");
+            }
+        }
+
         let cache_path = self.get_cache_path(job_id, timestamp)?;
 
         if let Ok(data) = tokio::fs::read_to_string(&cache_path).await {
@@ -332,7 +371,18 @@ impl SnapshotService {
         self.index_snapshot(job_id, timestamp, snapshot_path).await
     }
 
-    /// Index a snapshot and cache the result
+    /// Drop the memoized JSON tree/stats for a snapshot.
+    /// Call after (re-)indexing it in SQLite.
+    pub async fn invalidate_cache(&self, job_id: &str, timestamp: i64) {
+        if let Ok(cache_path) = self.get_cache_path(job_id, timestamp) {
+            let _ = tokio::fs::remove_file(&cache_path).await;
+        }
+        if let Some(legacy_path) = self.legacy_cache_path(job_id, timestamp) {
+            let _ = tokio::fs::remove_file(&legacy_path).await;
+        }
+    }
+
+    /// Scan a snapshot from disk and memoize the tree in the JSON cache
     pub async fn index_snapshot(
         &self,
         job_id: &str,
@@ -428,6 +478,7 @@ impl SnapshotService {
                 modified: entry.modified,
                 children: if entry.is_dir { Some(Vec::new()) } else { None },
                 path: entry.path.clone(),
+                link_count: None,
             };
 
             map.insert(entry.path.clone(), node);
@@ -566,6 +617,7 @@ mod tests {
                 modified: 0,
                 children: None,
                 path: "/test/file1.txt".to_string(),
+                link_count: None,
             },
             FileNode {
                 id: "file2".to_string(),
@@ -575,6 +627,7 @@ mod tests {
                 modified: 0,
                 children: None,
                 path: "/test/file2.txt".to_string(),
+                link_count: None,
             },
         ];
 
@@ -593,6 +646,7 @@ mod tests {
             size: 0,
             modified: 0,
             path: "/test/folder1".to_string(),
+            link_count: None,
             children: Some(vec![
                 FileNode {
                     id: "file1".to_string(),
@@ -602,6 +656,7 @@ mod tests {
                     modified: 0,
                     children: None,
                     path: "/test/folder1/file1.txt".to_string(),
+                    link_count: None,
                 },
                 FileNode {
                     id: "subfolder".to_string(),
@@ -610,6 +665,7 @@ mod tests {
                     size: 0,
                     modified: 0,
                     path: "/test/folder1/subfolder".to_string(),
+                    link_count: None,
                     children: Some(vec![FileNode {
                         id: "file2".to_string(),
                         name: "file2.txt".to_string(),
@@ -618,6 +674,7 @@ mod tests {
                         modified: 0,
                         children: None,
                         path: "/test/folder1/subfolder/file2.txt".to_string(),
+                        link_count: None,
                     }]),
                 },
             ]),
@@ -716,6 +773,48 @@ mod tests {
         assert!(entries.iter().any(|e| e.name == "subdir" && e.is_dir));
     }
 
+    #[tokio::test]
+    async fn test_reindex_invalidates_cached_tree() {
+        let (service, temp) = create_test_service();
+        let snap_dir = temp.path().join("2024-01-01-120000");
+        std::fs::create_dir_all(&snap_dir).unwrap();
+        std::fs::write(snap_dir.join("first.txt"), "one").unwrap();
+        let snap = snap_dir.to_str().unwrap();
+        let ts = 1704110400000;
+
+        // Unindexed: filesystem scan is memoized in the JSON cache
+        let tree = service
+            .get_snapshot_tree(None, "job-1", ts, snap)
+            .await
+            .unwrap();
+        assert_eq!(tree.len(), 1);
+        std::fs::write(snap_dir.join("second.txt"), "two").unwrap();
+        let tree = service
+            .get_snapshot_tree(None, "job-1", ts, snap)
+            .await
+            .unwrap();
+        assert_eq!(tree.len(), 1, "memoized tree until re-index");
+
+        // Re-index into SQLite: the index wins and the cache entry is dropped
+        let index = IndexService::new(&temp.path().join("index")).unwrap();
+        index.index_snapshot("job-1", ts, snap).unwrap();
+        let tree = service
+            .get_snapshot_tree(Some(&index), "job-1", ts, snap)
+            .await
+            .unwrap();
+        assert_eq!(tree.len(), 2);
+        assert!(!service.get_cache_path("job-1", ts).unwrap().exists());
+
+        // Once the index entry is gone, the old memoized tree doesn't come back
+        std::fs::write(snap_dir.join("third.txt"), "three").unwrap();
+        index.delete_snapshot("job-1", ts).unwrap();
+        let tree = service
+            .get_snapshot_tree(Some(&index), "job-1", ts, snap)
+            .await
+            .unwrap();
+        assert_eq!(tree.len(), 3);
+    }
+
     // =========================================================================
     // Integration tests for manifest-based snapshot loading (TIM-SIM-001)
     // =========================================================================
@@ -0,0 +1,320 @@
+//! Live diff of a job's source against its latest snapshot
+//!
+//! Scans the source as it is right now (on disk, or over ssh for remote
+//! sources) and compares it with the latest indexed snapshot by path, size
+//! and mtime, so users can see what the next backup would change. Only
+//! regular files are compared, as in snapshot diffs, and the job's exclude
+//! patterns apply as they do for rsync.
+
+use crate::error::{AmberError, Result};
+use crate::services::exclude_preview::ExcludeMatcher;
+use crate::services::index_service::stored_form;
+use crate::services::ssh_check::{self, SshOutput};
+use crate::types::job::SshConfig;
+use crate::utils::parse_ssh_remote;
+use serde::Serialize;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::path::Path;
+use walkdir::WalkDir;
+
+/// A regular file seen on one side of the diff
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScannedFile {
+    /// Relative to the source / snapshot root, `/`-separated
+    pub path: String,
+    pub size: i64,
+    /// Unix SECONDS
+    pub mtime: i64,
+}
+
+/// One file that differs between the snapshot (A) and the live source (B)
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SourceChange {
+    pub path: String,
+    pub size_a: Option<i64>,
+    pub size_b: Option<i64>,
+    /// Unix MILLISECONDS; None on the side the file is missing from
+    pub mtime_a: Option<i64>,
+    pub mtime_b: Option<i64>,
+}
+
+/// Read-only preview of what the next backup would change, comparing the
+/// live source against a job's latest indexed snapshot
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SourceComparison {
+    /// The snapshot the source was compared against
+    pub snapshot_timestamp: i64,
+    /// In the snapshot but gone from the source; `--delete` would remove
+    /// these from a mirror
+    pub removed: Vec<SourceChange>,
+    /// New in the source since the snapshot
+    pub added: Vec<SourceChange>,
+    /// Size or mtime changed since the snapshot
+    pub modified: Vec<SourceChange>,
+    /// Bytes in added and modified files that no backup holds yet
+    pub unprotected_bytes: i64,
+}
+
+/// Regular files under `root` (symlinks not followed, as the index does)
+pub fn scan_local(root: &Path) -> Result<Vec<ScannedFile>> {
+    if !root.is_dir() {
+        return Err(AmberError::InvalidPath(format!(
+            "Source is not a directory: {}",
+            root.display()
+        )));
+    }
+
+    let files = WalkDir::new(root)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_file())
+        .filter_map(|entry| {
+            let metadata = entry.metadata().ok()?;
+            let relative = entry.path().strip_prefix(root).ok()?;
+            let mtime = metadata
+                .modified()
+                .ok()
+                .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+                .map(|d| d.as_secs() as i64)
+                .unwrap_or(0);
+            Some(ScannedFile {
+                path: relative.to_string_lossy().replace('\\', "/"),
+                size: metadata.len() as i64,
+                mtime,
+            })
+        })
+        .collect();
+
+    Ok(files)
+}
+
+/// Remote command listing every regular file under `path` as
+/// `<size> <mtime> <relative path>`, with GNU find or, failing that, BSD stat.
+/// Exits with the connection test's "not found" status if `path` is missing.
+pub fn remote_scan_script(path: &str) -> String {
+    let dir = ssh_check::shell_quote(if path.is_empty() { "." } else { path });
+    format!(
+        "cd -- {dir} 2>/dev/null || exit {missing}; \
+         if find . -maxdepth 0 -printf '' >/dev/null 2>&1; then \
+         find . -type f -printf '%s %T@ %P\\n'; \
+         else find . -type f -exec stat -f '%z %m %N' {{}} +; fi",
+        dir = dir,
+        missing = ssh_check::EXIT_NOT_FOUND,
+    )
+}
+
+/// Parse `remote_scan_script` output. Lines that don't fit are skipped.
+pub fn parse_remote_listing(stdout: &str) -> Vec<ScannedFile> {
+    stdout
+        .lines()
+        .filter_map(|line| {
+            let mut parts = line.splitn(3, ' ');
+            let size = parts.next()?.parse().ok()?;
+            // GNU find prints fractional seconds
+            let mtime = parts.next()?.split('.').next()?.parse().ok()?;
+            let path = parts.next()?;
+            let path = path.strip_prefix("./").unwrap_or(path);
+            (!path.is_empty()).then(|| ScannedFile {
+                path: path.to_string(),
+                size,
+                mtime,
+            })
+        })
+        .collect()
+}
+
+/// List the regular files of a remote source (`[user@]host:path` or
+/// `ssh://...`) over ssh with the job's SSH settings. `run` executes ssh with
+/// the given arguments. find's exit status 1 (some folders unreadable) still
+/// yields what it could list.
+pub fn scan_remote<F>(
+    ssh: &SshConfig,
+    remote: &str,
+    batch_mode: bool,
+    run: F,
+) -> Result<Vec<ScannedFile>>
+where
+    F: FnOnce(&[String]) -> std::io::Result<SshOutput>,
+{
+    let parsed = parse_ssh_remote(remote)
+        .ok_or_else(|| AmberError::ValidationError(format!("Not an SSH source: {}", remote)))?;
+    let args = ssh_check::ssh_args(ssh, &parsed, batch_mode, remote_scan_script(&parsed.path))?;
+    let output = run(&args).map_err(|e| AmberError::Rsync(format!("Failed to run ssh: {}", e)))?;
+
+    match output.exit_code {
+        Some(0) => Ok(parse_remote_listing(&output.stdout)),
+        Some(1) => {
+            log::warn!(
+                "[source_diff] {}: some folders could not be listed",
+                parsed.host
+            );
+            Ok(parse_remote_listing(&output.stdout))
+        }
+        _ => Err(AmberError::Rsync(format!(
+            "Could not scan {}: {}",
+            parsed.host,
+            ssh_check::diagnose(&output).message
+        ))),
+    }
+}
+
+/// `file` with its path in the form the index stores paths in, so a name
+/// spelled NFD on one side and NFC on the other still pairs up
+fn in_stored_form(mut file: ScannedFile) -> ScannedFile {
+    if let Cow::Owned(path) = stored_form(&file.path) {
+        file.path = path;
+    }
+    file
+}
+
+/// Compare the files of the snapshot taken at `snapshot_timestamp` with the
+/// live source. A file counts as modified when its size or mtime changed.
+/// Files `exclude_patterns` match are left out on both sides: rsync neither
+/// copies them nor deletes them from the destination. Each list is sorted by
+/// path.
+pub fn diff_source(
+    snapshot_timestamp: i64,
+    snapshot: Vec<ScannedFile>,
+    source: Vec<ScannedFile>,
+    exclude_patterns: &[String],
+) -> SourceComparison {
+    let excludes = ExcludeMatcher::new(exclude_patterns);
+    let mut backed_up: HashMap<String, ScannedFile> = snapshot
+        .into_iter()
+        .filter(|file| !excludes.excludes_file(&file.path))
+        .map(in_stored_form)
+        .map(|file| (file.path.clone(), file))
+        .collect();
+
+    let mut added = Vec::new();
+    let mut modified = Vec::new();
+    for file in source
+        .into_iter()
+        .filter(|file| !excludes.excludes_file(&file.path))
+        .map(in_stored_form)
+    {
+        match backed_up.remove(&file.path) {
+            None => added.push(SourceChange {
+                path: file.path,
+                size_a: None,
+                size_b: Some(file.size),
+                mtime_a: None,
+                mtime_b: Some(file.mtime * 1000),
+            }),
+            Some(old) if old.size != file.size || old.mtime != file.mtime => {
+                modified.push(SourceChange {
+                    path: file.path,
+                    size_a: Some(old.size),
+                    size_b: Some(file.size),
+                    mtime_a: Some(old.mtime * 1000),
+                    mtime_b: Some(file.mtime * 1000),
+                })
+            }
+            Some(_) => {}
+        }
+    }
+
+    // Whatever the source didn't claim is gone from it
+    let mut removed: Vec<SourceChange> = backed_up
+        .into_values()
+        .map(|old| SourceChange {
+            path: old.path,
+            size_a: Some(old.size),
+            size_b: None,
+            mtime_a: Some(old.mtime * 1000),
+            mtime_b: None,
+        })
+        .collect();
+
+    for list in [&mut added, &mut modified, &mut removed] {
+        list.sort_by(|a, b| a.path.cmp(&b.path));
+    }
+    let unprotected_bytes = added
+        .iter()
+        .chain(&modified)
+        .filter_map(|change| change.size_b)
+        .sum();
+
+    SourceComparison {
+        snapshot_timestamp,
+        removed,
+        added,
+        modified,
+        unprotected_bytes,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(path: &str, size: i64, mtime: i64) -> ScannedFile {
+        ScannedFile {
+            path: path.to_string(),
+            size,
+            mtime,
+        }
+    }
+
+    #[test]
+    fn test_listing_parses_gnu_and_bsd_output() {
+        let gnu = "12 1700000000.5230000000 docs/a b.txt\n0 1700000001.0 empty\ngarbage\n";
+        assert_eq!(
+            parse_remote_listing(gnu),
+            vec![
+                file("docs/a b.txt", 12, 1700000000),
+                file("empty", 0, 1700000001),
+            ]
+        );
+
+        let bsd = "7 1700000002 ./photos/x.jpg\n";
+        assert_eq!(
+            parse_remote_listing(bsd),
+            vec![file("photos/x.jpg", 7, 1700000002)]
+        );
+    }
+
+    #[test]
+    fn test_remote_scan_runs_over_ssh() {
+        let mut seen = Vec::new();
+        let files = scan_remote(
+            &SshConfig::default(),
+            "me@nas:/srv/data",
+            true,
+            |args: &[String]| {
+                seen = args.to_vec();
+                Ok(SshOutput {
+                    exit_code: Some(0),
+                    stdout: "3 1700000000 a.txt\n".to_string(),
+                    stderr: String::new(),
+                })
+            },
+        )
+        .unwrap();
+        assert_eq!(files, vec![file("a.txt", 3, 1700000000)]);
+        assert!(seen.iter().any(|a| a == "me@nas"));
+        assert!(seen.last().unwrap().contains("'/srv/data'"));
+
+        let missing = scan_remote(
+            &SshConfig::default(),
+            "nas:/gone",
+            true,
+            |_: &[String]| {
+                Ok(SshOutput {
+                    exit_code: Some(ssh_check::EXIT_NOT_FOUND),
+                    ..Default::default()
+                })
+            },
+        );
+        assert!(missing.is_err());
+        assert!(
+            scan_remote(&SshConfig::default(), "/local", true, |_: &[String]| {
+                Ok(SshOutput::default())
+            })
+            .is_err()
+        );
+    }
+}
@@ -0,0 +1,201 @@
+//! SSH passphrase handoff via SSH_ASKPASS
+//!
+//! rsync runs ssh without a TTY, so a passphrase-protected identity file would
+//! make the job fail. When the keychain holds a passphrase for the job's key,
+//! the spawned rsync gets `SSH_ASKPASS` pointed at a tiny helper script that
+//! prints the passphrase from the inherited environment. The passphrase is
+//! never placed in argv, so it doesn't show up in `ps` or in our own logs.
+//!
+//! The helper only answers ssh's key passphrase prompt; password and
+//! keyboard-interactive prompts come from the server and get nothing, and
+//! runs that use the helper turn those login methods off altogether.
+
+use crate::error::{AmberError, Result};
+use crate::services::rsync_service;
+use crate::types::job::SyncJob;
+use std::path::{Path, PathBuf};
+
+/// Environment variable the helper reads the passphrase from
+pub const PASSPHRASE_ENV: &str = "AMBER_SSH_PASSPHRASE";
+
+const HELPER_NAME: &str = "ssh-askpass.sh";
+
+const HELPER_SCRIPT: &str = "#!/bin/sh\n\
+# Written by Amber: hands the keychain passphrase to ssh's key prompt only\n\
+case \"$1\" in\n\
+  *\"passphrase for\"*|*\"Enter passphrase\"*) printf '%s\\n' \"$AMBER_SSH_PASSPHRASE\" ;;\n\
+  *) exit 1 ;;\n\
+esac\n";
+
+/// ssh options for runs that go through the helper. With password and
+/// keyboard-interactive logins off, a server can't prompt for anything the
+/// helper might answer.
+pub const ASKPASS_SSH_OPTIONS: &str =
+    "-o PasswordAuthentication=no -o KbdInteractiveAuthentication=no";
+
+/// Write the askpass helper into `dir` (owner-only, executable) and return its path
+pub fn ensure_helper(dir: &Path) -> Result<PathBuf> {
+    std::fs::create_dir_all(dir)?;
+    let path = dir.join(HELPER_NAME);
+
+    if std::fs::read_to_string(&path).ok().as_deref() != Some(HELPER_SCRIPT) {
+        std::fs::write(&path, HELPER_SCRIPT)?;
+    }
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o700))?;
+    }
+
+    Ok(path)
+}
+
+/// Environment that makes ssh ask `helper` for the passphrase instead of a TTY
+pub fn askpass_env(helper: &Path, passphrase: &str) -> Vec<(String, String)> {
+    vec![
+        (
+            "SSH_ASKPASS".to_string(),
+            helper.to_string_lossy().to_string(),
+        ),
+        // OpenSSH >= 8.4: use askpass even without a terminal or DISPLAY
+        ("SSH_ASKPASS_REQUIRE".to_string(), "force".to_string()),
+        (PASSPHRASE_ENV.to_string(), passphrase.to_string()),
+    ]
+}
+
+/// Put `ASKPASS_SSH_OPTIONS` into the ssh command rsync's `-e` runs
+pub fn restrict_rsync_ssh(args: &mut [String]) {
+    rsync_service::insert_ssh_options(args, ASKPASS_SSH_OPTIONS);
+}
+
+/// Askpass environment for `job`, or empty when it has no SSH identity file or
+/// `lookup` (normally the keychain) has no passphrase for it
+pub fn env_for_job<F>(job: &SyncJob, helper_dir: &Path, lookup: F) -> Result<Vec<(String, String)>>
+where
+    F: FnOnce(&str) -> Result<Option<String>>,
+{
+    let Some(identity) = job
+        .ssh_config
+        .as_ref()
+        .filter(|ssh| ssh.enabled)
+        .and_then(|ssh| ssh.identity_file.as_deref())
+        .map(str::trim)
+        .filter(|identity| !identity.is_empty())
+    else {
+        return Ok(Vec::new());
+    };
+
+    match lookup(identity)? {
+        Some(passphrase) => {
+            let helper = ensure_helper(helper_dir).map_err(|e| {
+                AmberError::Rsync(format!("Failed to write SSH askpass helper: {}", e))
+            })?;
+            Ok(askpass_env(&helper, &passphrase))
+        }
+        None => Ok(Vec::new()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::job::SshConfig;
+
+    fn ssh_job(identity: Option<&str>) -> SyncJob {
+        SyncJob {
+            id: "ssh-job".to_string(),
+            source_path: "user@host:/data".to_string(),
+            dest_path: "/dest".to_string(),
+            ssh_config: Some(SshConfig {
+                enabled: true,
+                identity_file: identity.map(str::to_string),
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_helper_prints_passphrase_without_tty() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let env = env_for_job(&ssh_job(Some("/keys/id_ed25519")), temp.path(), |key| {
+            assert_eq!(key, "/keys/id_ed25519");
+            Ok(Some("s3cret phrase".to_string()))
+        })
+        .unwrap();
+
+        let get = |k: &str| {
+            env.iter()
+                .find(|(name, _)| name == k)
+                .map(|(_, v)| v.clone())
+        };
+        assert_eq!(get("SSH_ASKPASS_REQUIRE").as_deref(), Some("force"));
+        assert!(get("DISPLAY").is_none());
+
+        // Run the helper the way ssh would: the prompt as its argument, no
+        // stdin, only the handed-off env
+        let helper = get("SSH_ASKPASS").unwrap();
+        let ask = |prompt: &str| {
+            std::process::Command::new(&helper)
+                .arg(prompt)
+                .env_clear()
+                .envs(env.iter().cloned())
+                .stdin(std::process::Stdio::null())
+                .output()
+                .unwrap()
+        };
+        let output = ask("Enter passphrase for key '/keys/id_ed25519': ");
+        assert!(output.status.success());
+        assert_eq!(String::from_utf8_lossy(&output.stdout), "s3cret phrase\n");
+
+        // Prompts from the server get nothing
+        for prompt in ["user@host's password: ", "Verification code: ", ""] {
+            let output = ask(prompt);
+            assert!(!output.status.success(), "{:?}", prompt);
+            assert!(output.stdout.is_empty(), "{:?}", prompt);
+        }
+
+        // The passphrase lives in the environment, never in the helper itself
+        let script = std::fs::read_to_string(&helper).unwrap();
+        assert!(!script.contains("s3cret"));
+    }
+
+    #[test]
+    fn test_askpass_runs_turn_off_password_logins_first() {
+        let mut args = vec![
+            "-a".to_string(),
+            "-e".to_string(),
+            "ssh -p 2222 -o PasswordAuthentication=yes".to_string(),
+            "src/".to_string(),
+        ];
+        restrict_rsync_ssh(&mut args);
+        assert_eq!(
+            args[2],
+            format!(
+                "ssh {} -p 2222 -o PasswordAuthentication=yes",
+                ASKPASS_SSH_OPTIONS
+            )
+        );
+
+        let mut args = vec!["-e".to_string(), "ssh".to_string()];
+        restrict_rsync_ssh(&mut args);
+        assert_eq!(args[1], format!("ssh {}", ASKPASS_SSH_OPTIONS));
+    }
+
+    #[test]
+    fn test_no_askpass_without_identity_or_passphrase() {
+        let temp = tempfile::TempDir::new().unwrap();
+
+        let env = env_for_job(&ssh_job(None), temp.path(), |_| {
+            panic!("lookup should not run without an identity file")
+        })
+        .unwrap();
+        assert!(env.is_empty());
+
+        let env = env_for_job(&ssh_job(Some("/keys/id_rsa")), temp.path(), |_| Ok(None)).unwrap();
+        assert!(env.is_empty());
+        assert!(!temp.path().join(HELPER_NAME).exists());
+    }
+}
@@ -0,0 +1,417 @@
+//! "Test Connection" for SSH destinations
+//!
+//! Runs a single ssh command with the job's SSH settings that checks the
+//! remote folder exists and accepts a new file, then turns ssh's exit status
+//! and stderr into a diagnosis the UI can act on. The command line and the
+//! environment are never part of the result, so identity paths, custom
+//! options and keychain passphrases don't leak into the UI or the logs.
+
+use crate::error::{AmberError, Result};
+use crate::types::job::SshConfig;
+use crate::utils::validation::{
+    sanitize_ssh_option, validate_file_path, validate_proxy_jump, validate_ssh_port,
+};
+use crate::utils::{parse_ssh_remote, SshRemote};
+use serde::Serialize;
+
+/// Remote script exit status: the folder does not exist
+pub(crate) const EXIT_NOT_FOUND: i32 = 3;
+/// Remote script exit status: the folder exists but can't be entered or written
+const EXIT_NOT_WRITABLE: i32 = 4;
+/// ssh's own exit status for connection and authentication errors
+const EXIT_SSH_ERROR: i32 = 255;
+
+/// Printed by the remote script once the write test passed
+const OK_MARKER: &str = "amber-ssh-ok";
+
+/// Seconds ssh waits for the TCP connection
+const CONNECT_TIMEOUT_SECS: u32 = 10;
+
+/// What the connection test found
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SshDiagnosis {
+    Ok,
+    /// The server rejected every key/password offered
+    AuthFailed,
+    /// Unknown or changed host key
+    HostKey,
+    /// Name lookup, refused connection, timeout or no route
+    Unreachable,
+    PathNotFound,
+    PermissionDenied,
+    /// ssh failed in a way not recognized above
+    Failed,
+}
+
+/// Outcome of `test_destination`; `message` is safe to show as is
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SshTestResult {
+    pub ok: bool,
+    pub diagnosis: SshDiagnosis,
+    pub message: String,
+}
+
+/// What the ssh runner reports back
+#[derive(Debug, Clone, Default)]
+pub struct SshOutput {
+    /// None when ssh was killed by a signal
+    pub exit_code: Option<i32>,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+/// Quote `value` for a POSIX shell on the remote side
+pub(crate) fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', r"'\''"))
+}
+
+/// Remote command: exit 3 if `path` is missing, 4 if it can't be entered or
+/// written, else create and remove a probe file and print the OK marker.
+/// An empty path checks the login directory.
+pub fn remote_check_script(path: &str) -> String {
+    let dir = shell_quote(if path.is_empty() { "." } else { path });
+    format!(
+        "[ -d {dir} ] || exit {missing}; cd -- {dir} 2>/dev/null || exit {denied}; \
+         f=.amber-write-test.$$; touch -- \"$f\" 2>/dev/null || exit {denied}; \
+         rm -f -- \"$f\"; echo {ok}",
+        dir = dir,
+        missing = EXIT_NOT_FOUND,
+        denied = EXIT_NOT_WRITABLE,
+        ok = OK_MARKER,
+    )
+}
+
+/// ssh arguments for checking `remote`, honouring the job's port, identity,
+/// config file, proxy jump, custom options and host key setting. Unlike a
+/// backup, invalid settings are an error here rather than being skipped.
+///
+/// `batch_mode` makes ssh fail instead of prompting; turn it off only when a
+/// keychain passphrase is handed over through SSH_ASKPASS.
+pub fn ssh_test_args(ssh: &SshConfig, remote: &SshRemote, batch_mode: bool) -> Result<Vec<String>> {
+    ssh_args(ssh, remote, batch_mode, remote_check_script(&remote.path))
+}
+
+/// ssh arguments that run `remote_command` on `remote` with the job's SSH
+/// settings, as `ssh_test_args` does for the connection test
+pub fn ssh_args(
+    ssh: &SshConfig,
+    remote: &SshRemote,
+    batch_mode: bool,
+    remote_command: String,
+) -> Result<Vec<String>> {
+    let mut args: Vec<String> = Vec::new();
+    let mut option = |value: String| {
+        args.push("-o".to_string());
+        args.push(value);
+    };
+    if batch_mode {
+        option("BatchMode=yes".to_string());
+    } else {
+        option("PasswordAuthentication=no".to_string());
+        option("KbdInteractiveAuthentication=no".to_string());
+    }
+    option(format!("ConnectTimeout={}", CONNECT_TIMEOUT_SECS));
+    if ssh.disable_host_key_checking == Some(true) {
+        option("StrictHostKeyChecking=no".to_string());
+        option("UserKnownHostsFile=/dev/null".to_string());
+    }
+
+    let set = |value: &Option<String>| value.as_deref().map(str::trim).filter(|v| !v.is_empty());
+
+    match set(&ssh.port) {
+        Some(port) => {
+            args.push("-p".to_string());
+            args.push(validate_ssh_port(port)?.to_string());
+        }
+        None => {
+            if let Some(port) = remote.port {
+                args.push("-p".to_string());
+                args.push(port.to_string());
+            }
+        }
+    }
+    if let Some(identity) = set(&ssh.identity_file) {
+        args.push("-i".to_string());
+        args.push(validate_file_path(identity)?.to_string());
+    }
+    if let Some(config) = set(&ssh.config_file) {
+        args.push("-F".to_string());
+        args.push(validate_file_path(config)?.to_string());
+    }
+    if let Some(proxy) = set(&ssh.proxy_jump) {
+        args.push("-J".to_string());
+        args.push(validate_proxy_jump(proxy)?);
+    }
+    if let Some(custom) = set(&ssh.custom_ssh_options) {
+        args.extend(
+            sanitize_ssh_option(custom)?
+                .split_whitespace()
+                .map(str::to_string),
+        );
+    }
+
+    let host = if remote.host.contains(':') {
+        format!("[{}]", remote.host)
+    } else {
+        remote.host.clone()
+    };
+    args.push(match &remote.user {
+        Some(user) => format!("{}@{}", user, host),
+        None => host,
+    });
+    args.push(remote_command);
+    Ok(args)
+}
+
+/// Last non-empty stderr line that isn't ssh's known-hosts chatter
+fn stderr_summary(stderr: &str) -> Option<&str> {
+    stderr
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with("Warning: Permanently added"))
+        .last()
+}
+
+/// Map what ssh printed and returned to a diagnosis
+pub fn diagnose(output: &SshOutput) -> SshTestResult {
+    let result = |diagnosis: SshDiagnosis, message: String| SshTestResult {
+        ok: diagnosis == SshDiagnosis::Ok,
+        diagnosis,
+        message,
+    };
+    let stderr = output.stderr.to_lowercase();
+    let has = |needles: &[&str]| needles.iter().any(|n| stderr.contains(n));
+    let detail = || {
+        stderr_summary(&output.stderr)
+            .map(|line| format!(" ({})", line))
+            .unwrap_or_default()
+    };
+
+    match output.exit_code {
+        Some(0) if output.stdout.contains(OK_MARKER) => result(
+            SshDiagnosis::Ok,
+            "Connected; the destination folder is writable".to_string(),
+        ),
+        Some(EXIT_NOT_FOUND) => result(
+            SshDiagnosis::PathNotFound,
+            "Connected, but the destination folder does not exist on the server".to_string(),
+        ),
+        Some(EXIT_NOT_WRITABLE) => result(
+            SshDiagnosis::PermissionDenied,
+            "Connected, but this user can't write to the destination folder".to_string(),
+        ),
+        Some(EXIT_SSH_ERROR) => {
+            if has(&[
+                "host key verification failed",
+                "remote host identification has changed",
+                "host key is known",
+                "host key for",
+            ]) {
+                result(
+                    SshDiagnosis::HostKey,
+                    format!(
+                        "The server's host key is unknown or has changed{}",
+                        detail()
+                    ),
+                )
+            } else if has(&[
+                "permission denied (",
+                "too many authentication failures",
+                "no supported authentication methods",
+                "authentication failed",
+            ]) {
+                result(
+                    SshDiagnosis::AuthFailed,
+                    format!("The server rejected the login{}", detail()),
+                )
+            } else if has(&[
+                "could not resolve hostname",
+                "connection refused",
+                "timed out",
+                "no route to host",
+                "network is unreachable",
+                "connection closed by",
+            ]) {
+                result(
+                    SshDiagnosis::Unreachable,
+                    format!("Could not reach the server{}", detail()),
+                )
+            } else {
+                result(SshDiagnosis::Failed, format!("ssh failed{}", detail()))
+            }
+        }
+        Some(code) => result(
+            SshDiagnosis::Failed,
+            format!("The remote check exited with status {}{}", code, detail()),
+        ),
+        None => result(
+            SshDiagnosis::Failed,
+            "ssh was interrupted before it finished".to_string(),
+        ),
+    }
+}
+
+/// Check that `remote` (`[user@]host:path` or `ssh://...`) can be logged into
+/// with `ssh` and that its folder is writable. `run` executes ssh with the
+/// given arguments; a launch failure (e.g. no ssh installed) is an error.
+pub fn test_destination<F>(
+    ssh: &SshConfig,
+    remote: &str,
+    batch_mode: bool,
+    run: F,
+) -> Result<SshTestResult>
+where
+    F: FnOnce(&[String]) -> std::io::Result<SshOutput>,
+{
+    let parsed = parse_ssh_remote(remote).ok_or_else(|| {
+        AmberError::ValidationError(format!("Not an SSH destination: {}", remote))
+    })?;
+    let args = ssh_test_args(ssh, &parsed, batch_mode)?;
+    let output = run(&args).map_err(|e| AmberError::Rsync(format!("Failed to run ssh: {}", e)))?;
+    let result = diagnose(&output);
+    log::info!("[ssh_check] {}: {:?}", parsed.host, result.diagnosis);
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> SshConfig {
+        SshConfig {
+            enabled: true,
+            port: Some("2222".to_string()),
+            identity_file: Some("/keys/id_ed25519".to_string()),
+            proxy_jump: Some("bastion@10.0.0.1".to_string()),
+            ..Default::default()
+        }
+    }
+
+    /// Run `test_destination` against a stub that returns `code`/`stdout`/`stderr`
+    fn run_stubbed(code: Option<i32>, stdout: &str, stderr: &str) -> SshTestResult {
+        test_destination(&config(), "backup@nas:/volume1/amber", true, |_| {
+            Ok(SshOutput {
+                exit_code: code,
+                stdout: stdout.to_string(),
+                stderr: stderr.to_string(),
+            })
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_args_follow_ssh_config() {
+        let mut seen = Vec::new();
+        test_destination(&config(), "backup@nas:/volume1/it's", true, |args| {
+            seen = args.to_vec();
+            Ok(SshOutput::default())
+        })
+        .unwrap();
+
+        let joined = seen.join(" ");
+        assert!(joined.contains("-o BatchMode=yes"));
+        assert!(joined.contains("-p 2222"));
+        assert!(joined.contains("-i /keys/id_ed25519"));
+        assert!(joined.contains("-J bastion@10.0.0.1"));
+        assert_eq!(seen[seen.len() - 2], "backup@nas");
+        assert!(seen[seen.len() - 1].contains(r"'/volume1/it'\''s'"));
+
+        // The ssh:// port is used when the config has none; IPv6 gets brackets
+        let remote = parse_ssh_remote("ssh://root@[fe80::1]:2200/srv").unwrap();
+        let args = ssh_test_args(&SshConfig::default(), &remote, false).unwrap();
+        assert!(args.windows(2).any(|w| w == ["-p", "2200"]));
+        assert!(args.contains(&"root@[fe80::1]".to_string()));
+        assert!(!args.contains(&"BatchMode=yes".to_string()));
+    }
+
+    #[test]
+    fn test_invalid_config_is_rejected_before_running() {
+        let ssh = SshConfig {
+            proxy_jump: Some("evil; rm -rf /".to_string()),
+            ..Default::default()
+        };
+        let result = test_destination(&ssh, "user@host:/data", true, |_| {
+            panic!("ssh should not run with an invalid config")
+        });
+        assert!(matches!(result, Err(AmberError::ValidationError(_))));
+        assert!(matches!(
+            test_destination(&ssh, "/local/path", true, |_| unreachable!()),
+            Err(AmberError::ValidationError(_))
+        ));
+    }
+
+    #[test]
+    fn test_canned_outputs_map_to_diagnoses() {
+        let ok = run_stubbed(Some(0), "amber-ssh-ok\n", "");
+        assert!(ok.ok);
+        assert_eq!(ok.diagnosis, SshDiagnosis::Ok);
+
+        let cases = [
+            (Some(3), "", SshDiagnosis::PathNotFound),
+            (Some(4), "", SshDiagnosis::PermissionDenied),
+            (
+                Some(255),
+                "backup@nas: Permission denied (publickey,password).\r\n",
+                SshDiagnosis::AuthFailed,
+            ),
+            (
+                Some(255),
+                "Received disconnect from 10.0.0.5 port 22:2: Too many authentication failures",
+                SshDiagnosis::AuthFailed,
+            ),
+            (
+                Some(255),
+                "No ED25519 host key is known for nas and you have requested strict checking.\n\
+                 Host key verification failed.\n",
+                SshDiagnosis::HostKey,
+            ),
+            (
+                Some(255),
+                "@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@\n\
+                 @    WARNING: REMOTE HOST IDENTIFICATION HAS CHANGED!     @\n",
+                SshDiagnosis::HostKey,
+            ),
+            (
+                Some(255),
+                "ssh: Could not resolve hostname nas: nodename nor servname provided",
+                SshDiagnosis::Unreachable,
+            ),
+            (
+                Some(255),
+                "ssh: connect to host nas port 2222: Connection timed out",
+                SshDiagnosis::Unreachable,
+            ),
+            (
+                Some(255),
+                "kex_exchange_identification: read: Connection reset by peer",
+                SshDiagnosis::Failed,
+            ),
+            (Some(0), "", SshDiagnosis::Failed),
+            (None, "", SshDiagnosis::Failed),
+        ];
+        for (code, stderr, expected) in cases {
+            let result = run_stubbed(code, "", stderr);
+            assert_eq!(result.diagnosis, expected, "stderr: {}", stderr);
+            assert!(!result.ok);
+        }
+    }
+
+    #[test]
+    fn test_message_never_echoes_the_command() {
+        let result = run_stubbed(
+            Some(255),
+            "",
+            "Warning: Permanently added 'nas' (ED25519) to the list of known hosts.\n\
+             backup@nas: Permission denied (publickey).\n",
+        );
+        assert_eq!(
+            result.message,
+            "The server rejected the login (backup@nas: Permission denied (publickey).)"
+        );
+        for secret in ["/keys/id_ed25519", "bastion", "2222", "amber-write-test"] {
+            assert!(!result.message.contains(secret));
+        }
+    }
+}
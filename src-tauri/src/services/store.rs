@@ -13,6 +13,8 @@ const JOB_CONFIG_FILENAME: &str = "job.json";
 
 pub struct Store {
     data_dir: PathBuf,
+    /// Where preferences live; the data dir unless `with_config_dir` moved them
+    config_dir: PathBuf,
 }
 
 impl Store {
@@ -20,15 +22,38 @@ impl Store {
         let _ = std::fs::create_dir_all(app_data_dir);
         Self {
             data_dir: app_data_dir.to_path_buf(),
+            config_dir: app_data_dir.to_path_buf(),
         }
     }
 
+    /// Keep preferences in `config_dir` instead of the data dir. A
+    /// preferences file an earlier version left in the data dir is moved
+    /// over, unless `config_dir` already has one.
+    pub fn with_config_dir(mut self, config_dir: &Path) -> Self {
+        let legacy = self.prefs_path();
+        self.config_dir = config_dir.to_path_buf();
+        let prefs = self.prefs_path();
+        if legacy != prefs && legacy.exists() && !prefs.exists() {
+            let moved = std::fs::create_dir_all(config_dir)
+                .and_then(|_| std::fs::rename(&legacy, &prefs))
+                .or_else(|_| std::fs::copy(&legacy, &prefs).map(|_| ()));
+            if let Err(e) = moved {
+                log::warn!(
+                    "Failed to move preferences to {}: {}",
+                    config_dir.display(),
+                    e
+                );
+            }
+        }
+        self
+    }
+
     fn jobs_path(&self) -> PathBuf {
         self.data_dir.join(JOBS_FILENAME)
     }
 
     fn prefs_path(&self) -> PathBuf {
-        self.data_dir.join(PREFS_FILENAME)
+        self.config_dir.join(PREFS_FILENAME)
     }
 
     // ===== Jobs =====
@@ -333,6 +358,24 @@ mod tests {
         assert_eq!(entries.len(), 1);
     }
 
+    #[test]
+    fn test_preferences_move_to_config_dir() {
+        let (store, dir) = test_store();
+        let prefs = AppPreferences {
+            max_index_files: 1234,
+            ..AppPreferences::default()
+        };
+        store.save_preferences(&prefs).unwrap();
+        store.save_job(test_job("1")).unwrap();
+
+        let config_dir = dir.path().join("config");
+        let store = Store::new(dir.path()).with_config_dir(&config_dir);
+        assert!(config_dir.join(PREFS_FILENAME).exists());
+        assert!(!dir.path().join(PREFS_FILENAME).exists());
+        assert_eq!(store.load_preferences().unwrap().max_index_files, 1234);
+        assert_eq!(store.load_jobs().unwrap().len(), 1);
+    }
+
     #[test]
     fn test_atomic_write_creates_file() {
         let (store, _dir) = test_store();
@@ -1,4 +1,4 @@
-use crate::commands::rsync::get_rsync_service;
+use crate::services::backup_runner;
 use crate::state::AppState;
 use tauri::image::Image;
 use tauri::menu::{Menu, MenuItem, PredefinedMenuItem};
@@ -9,23 +9,10 @@ use tauri::{AppHandle, Emitter, Manager};
 /// Format: `job::<job_id>` — the handler toggles start/stop based on running state.
 const JOB_PREFIX: &str = "job::";
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-enum JobRunMode {
-    Rsync,
-    Rclone,
-}
-
-fn run_mode_for_job(job: &crate::types::job::SyncJob) -> JobRunMode {
-    match job.destination_type {
-        Some(crate::types::job::DestinationType::Cloud) => JobRunMode::Rclone,
-        _ => JobRunMode::Rsync,
-    }
-}
-
 /// Manages the system tray icon and dynamic menu.
 ///
 /// The menu is rebuilt whenever a job starts, stops, or completes.
-/// Job state is read fresh from `AppState.store` and `RsyncService`
+/// Job state is read fresh from `AppState.store` and the backup runners
 /// on every rebuild — no stale tracking needed.
 pub struct TrayManager {
     app_handle: AppHandle,
@@ -70,9 +57,10 @@ impl TrayManager {
             tray.set_menu(Some(menu))?;
 
             // Update tray icon based on whether any job is running
-            let rsync = get_rsync_service();
             let jobs = self.load_jobs();
-            let running_job = jobs.iter().find(|(id, _)| rsync.is_job_running(id));
+            let running_job = jobs
+                .iter()
+                .find(|(id, _)| backup_runner::running(id).is_some());
 
             if let Some((_id, name)) = running_job {
                 let active_icon =
@@ -93,7 +81,6 @@ impl TrayManager {
     /// Build the full menu, reading jobs from the store and checking running state.
     fn build_menu(&self) -> Result<Menu<tauri::Wry>, Box<dyn std::error::Error>> {
         let app = &self.app_handle;
-        let rsync = get_rsync_service();
 
         let menu = Menu::new(app)?;
 
@@ -119,7 +106,7 @@ impl TrayManager {
             )?)?;
         } else {
             for (id, name) in &jobs {
-                let running = rsync.is_job_running(id);
+                let running = backup_runner::running(id).is_some();
                 let label = if running {
                     format!("\u{25A0} {} (running)", name)
                 } else {
@@ -227,35 +214,20 @@ fn handle_menu_event(app: &AppHandle, id: &str) {
 
 /// Toggle a job: stop it if running, start it if idle.
 fn handle_job_action(app: &AppHandle, job_id: &str) {
-    let rsync = get_rsync_service();
-    let running = rsync.is_job_running(job_id);
-
-    if running {
+    if let Some(runner) = backup_runner::running(job_id) {
         // Stop the running backup
-        let job_id = job_id.to_string();
-        tauri::async_runtime::spawn(async move {
-            if let Err(e) = crate::commands::rsync::kill_rsync(job_id).await {
-                log::error!("Tray: failed to stop backup: {}", e);
-            }
-        });
+        if let Err(e) = runner.kill(job_id) {
+            log::error!("Tray: failed to stop backup: {}", e);
+        }
     } else {
         // Start the backup — load full job from store
         let app_state = app.try_state::<AppState>();
         if let Some(state) = app_state {
             if let Ok(Some(job)) = state.store.get_job(job_id) {
-                let run_mode = run_mode_for_job(&job);
-                let app_clone = app.clone();
+                let runner = backup_runner::runner_for(&job);
+                let run = runner.run(job, Some(app.clone()));
                 tauri::async_runtime::spawn(async move {
-                    let result = match run_mode {
-                        JobRunMode::Rsync => crate::commands::rsync::run_rsync(app_clone, job)
-                            .await
-                            .map_err(|e| e.to_string()),
-                        JobRunMode::Rclone => crate::commands::rclone::run_rclone(job)
-                            .await
-                            .map_err(|e| e.to_string()),
-                    };
-
-                    if let Err(e) = result {
+                    if let Err(e) = run.await {
                         log::error!("Tray: failed to start backup: {}", e);
                     }
                 });
@@ -263,27 +235,3 @@ fn handle_job_action(app: &AppHandle, job_id: &str) {
         }
     }
 }
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::types::job::{DestinationType, SyncJob};
-
-    #[test]
-    fn selects_rsync_for_local_jobs() {
-        let job = SyncJob {
-            destination_type: Some(DestinationType::Local),
-            ..SyncJob::default()
-        };
-        assert_eq!(run_mode_for_job(&job), JobRunMode::Rsync);
-    }
-
-    #[test]
-    fn selects_rclone_for_cloud_jobs() {
-        let job = SyncJob {
-            destination_type: Some(DestinationType::Cloud),
-            ..SyncJob::default()
-        };
-        assert_eq!(run_mode_for_job(&job), JobRunMode::Rclone);
-    }
-}
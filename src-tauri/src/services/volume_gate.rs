@@ -0,0 +1,165 @@
+//! Keeping indexing off a volume while a backup is writing to it
+//!
+//! Indexing one job while another rsyncs to the same external drive makes
+//! both compete for the disk. When serialization is on, index operations wait
+//! until every backup to their volume has finished. Volumes are matched with
+//! `utils::get_volume_info`; paths not on an external volume share the system
+//! disk.
+
+use crate::utils::get_volume_info;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, OnceLock};
+use tokio::sync::Notify;
+
+/// Set from preferences; off means indexing never waits
+static SERIALIZE: AtomicBool = AtomicBool::new(false);
+
+/// Choose whether index operations wait for backups to the same volume
+pub fn configure(serialize: bool) {
+    SERIALIZE.store(serialize, Ordering::SeqCst);
+}
+
+pub fn is_serialized() -> bool {
+    SERIALIZE.load(Ordering::SeqCst)
+}
+
+struct Gate {
+    /// Running backups per volume
+    active: Mutex<HashMap<String, usize>>,
+    /// Woken whenever a backup finishes
+    finished: Notify,
+}
+
+fn gate() -> &'static Gate {
+    static GATE: OnceLock<Gate> = OnceLock::new();
+    GATE.get_or_init(|| Gate {
+        active: Mutex::new(HashMap::new()),
+        finished: Notify::new(),
+    })
+}
+
+fn volume_key(path: &str) -> String {
+    get_volume_info(path).volume_name.unwrap_or_default()
+}
+
+/// Marks a backup as writing to a volume until dropped
+pub struct BackupGuard {
+    volume: String,
+}
+
+impl Drop for BackupGuard {
+    fn drop(&mut self) {
+        if let Ok(mut active) = gate().active.lock() {
+            if let Some(count) = active.get_mut(&self.volume) {
+                *count -= 1;
+                if *count == 0 {
+                    active.remove(&self.volume);
+                }
+            }
+        }
+        gate().finished.notify_waiters();
+    }
+}
+
+/// Record a backup to `dest_path`; it counts as running until the guard drops
+pub fn begin_backup(dest_path: &str) -> BackupGuard {
+    let volume = volume_key(dest_path);
+    if let Ok(mut active) = gate().active.lock() {
+        *active.entry(volume.clone()).or_insert(0) += 1;
+    }
+    BackupGuard { volume }
+}
+
+/// Number of backups currently writing to the volume holding `path`
+pub fn backups_on_volume(path: &str) -> usize {
+    let volume = volume_key(path);
+    gate()
+        .active
+        .lock()
+        .map(|active| active.get(&volume).copied().unwrap_or(0))
+        .unwrap_or(0)
+}
+
+/// Wait until no backup writes to the volume holding `path`. Returns true if
+/// there was one to wait for.
+pub async fn wait_until_no_backups(path: &str) -> bool {
+    let mut deferred = false;
+    loop {
+        // Registered before checking, so a backup finishing in between still
+        // wakes us
+        let finished = gate().finished.notified();
+        if backups_on_volume(path) == 0 {
+            return deferred;
+        }
+        if !deferred {
+            log::info!(
+                "Deferring indexing of {} until backups to its volume finish",
+                path
+            );
+            deferred = true;
+        }
+        finished.await;
+    }
+}
+
+/// Index operations call this before touching `path`: waits out backups to
+/// its volume when serialization is on, otherwise returns at once
+pub async fn defer_while_backing_up(path: &str) -> bool {
+    if !is_serialized() {
+        return false;
+    }
+    wait_until_no_backups(path).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_index_waits_for_backup_on_same_volume() {
+        let backup = begin_backup("/gate-test/backups");
+        assert_eq!(backups_on_volume("/gate-test/other"), 1);
+
+        let ran = Arc::new(AtomicBool::new(false));
+        let ran_flag = ran.clone();
+        let index = tokio::spawn(async move {
+            let deferred = wait_until_no_backups("/gate-test/other").await;
+            ran_flag.store(true, Ordering::SeqCst);
+            deferred
+        });
+
+        // However often the index task is polled, it stays parked
+        for _ in 0..20 {
+            tokio::task::yield_now().await;
+        }
+        assert!(!ran.load(Ordering::SeqCst));
+
+        drop(backup);
+        assert!(index.await.unwrap());
+        assert!(ran.load(Ordering::SeqCst));
+        assert_eq!(backups_on_volume("/gate-test/other"), 0);
+
+        // Nothing running: no wait
+        assert!(!wait_until_no_backups("/gate-test/other").await);
+    }
+
+    #[cfg(any(target_os = "macos", target_os = "linux"))]
+    #[tokio::test]
+    async fn test_index_on_other_volume_is_not_deferred() {
+        #[cfg(target_os = "macos")]
+        let (busy, idle) = ("/Volumes/GateBusy/backups", "/Volumes/GateIdle/backups");
+        #[cfg(target_os = "linux")]
+        let (busy, idle) = ("/mnt/gate-busy/backups", "/mnt/gate-idle/backups");
+
+        let _backup = begin_backup(busy);
+        let deferred = tokio::time::timeout(
+            std::time::Duration::from_secs(5),
+            wait_until_no_backups(idle),
+        )
+        .await
+        .expect("index on an idle volume should not wait");
+        assert!(!deferred);
+    }
+}
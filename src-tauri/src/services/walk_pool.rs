@@ -0,0 +1,199 @@
+//! Shared thread pool for parallel directory walking
+//!
+//! Indexing used to build a fresh rayon pool sized to every CPU on each call,
+//! so concurrent indexes multiplied the thread count. All walks now run on a
+//! single process-wide pool whose size comes from the `indexThreads` preference.
+//! Callers that need a different budget (tests, benchmarks) can build their own
+//! `WalkPool` and hand it to `IndexService::with_walk_pool`.
+
+use crate::error::{AmberError, Result};
+use crate::services::process_priority;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, OnceLock, RwLock};
+
+/// Bounded rayon pool plus a counter of how many workers are busy walking
+pub struct WalkPool {
+    pool: Arc<rayon::ThreadPool>,
+    threads: usize,
+    background: bool,
+    active: AtomicUsize,
+    peak: AtomicUsize,
+}
+
+impl WalkPool {
+    /// Build a pool with `threads` workers (0 = one per logical CPU)
+    pub fn new(threads: usize) -> Result<Self> {
+        Self::with_priority(threads, false)
+    }
+
+    /// Like `new`, but with `background` each worker lowers its own priority
+    /// as it starts
+    pub fn with_priority(threads: usize, background: bool) -> Result<Self> {
+        let threads = resolve_thread_budget(threads);
+        let mut builder = rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .thread_name(|i| format!("amber-walk-{}", i));
+        if background {
+            builder = builder.start_handler(|_| process_priority::lower_current_thread());
+        }
+        let pool = builder
+            .build()
+            .map_err(|e| AmberError::Index(format!("Failed to build walk thread pool: {}", e)))?;
+
+        Ok(Self {
+            pool: Arc::new(pool),
+            threads,
+            background,
+            active: AtomicUsize::new(0),
+            peak: AtomicUsize::new(0),
+        })
+    }
+
+    /// Number of worker threads in this pool
+    pub fn threads(&self) -> usize {
+        self.threads
+    }
+
+    /// Whether the workers run at background priority
+    pub fn is_background(&self) -> bool {
+        self.background
+    }
+
+    /// Highest number of workers seen walking at the same time
+    pub fn peak_threads(&self) -> usize {
+        self.peak.load(Ordering::SeqCst)
+    }
+
+    /// jwalk parallelism that schedules directory reads on this pool
+    pub(crate) fn parallelism(&self) -> jwalk::Parallelism {
+        jwalk::Parallelism::RayonExistingPool {
+            pool: self.pool.clone(),
+            busy_timeout: None,
+        }
+    }
+
+    /// Run `op` on this pool, so rayon work it starts (`par_iter` and the
+    /// like) stays within the thread budget and priority
+    pub(crate) fn install<R: Send>(&self, op: impl FnOnce() -> R + Send) -> R {
+        self.pool.install(op)
+    }
+
+    /// Mark a worker as busy until the returned guard is dropped
+    pub(crate) fn enter(&self) -> ActiveWorker<'_> {
+        let now = self.active.fetch_add(1, Ordering::SeqCst) + 1;
+        self.peak.fetch_max(now, Ordering::SeqCst);
+        ActiveWorker { pool: self }
+    }
+}
+
+/// Guard returned by `WalkPool::enter`
+pub(crate) struct ActiveWorker<'a> {
+    pool: &'a WalkPool,
+}
+
+impl Drop for ActiveWorker<'_> {
+    fn drop(&mut self) {
+        self.pool.active.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+fn resolve_thread_budget(threads: usize) -> usize {
+    if threads == 0 {
+        num_cpus::get()
+    } else {
+        threads
+    }
+}
+
+fn shared_slot() -> &'static RwLock<Option<Arc<WalkPool>>> {
+    static SHARED: OnceLock<RwLock<Option<Arc<WalkPool>>>> = OnceLock::new();
+    SHARED.get_or_init(|| RwLock::new(None))
+}
+
+/// Resize the shared pool or change its priority. Walks already in flight
+/// finish on the old pool.
+pub fn configure(threads: usize, background: bool) -> Result<()> {
+    let wanted = resolve_thread_budget(threads);
+    let mut slot = shared_slot()
+        .write()
+        .map_err(|e| AmberError::Index(format!("Failed to acquire walk pool lock: {}", e)))?;
+
+    if slot
+        .as_ref()
+        .is_some_and(|p| p.threads() == wanted && p.is_background() == background)
+    {
+        return Ok(());
+    }
+
+    *slot = Some(Arc::new(WalkPool::with_priority(wanted, background)?));
+    log::info!(
+        "Directory walk pool sized to {} threads{}",
+        wanted,
+        if background {
+            " at background priority"
+        } else {
+            ""
+        }
+    );
+    Ok(())
+}
+
+/// Get the shared pool, creating it with one thread per CPU if unconfigured
+pub fn shared() -> Result<Arc<WalkPool>> {
+    if let Some(pool) = shared_slot()
+        .read()
+        .map_err(|e| AmberError::Index(format!("Failed to acquire walk pool lock: {}", e)))?
+        .as_ref()
+    {
+        return Ok(pool.clone());
+    }
+
+    let mut slot = shared_slot()
+        .write()
+        .map_err(|e| AmberError::Index(format!("Failed to acquire walk pool lock: {}", e)))?;
+    if let Some(pool) = slot.as_ref() {
+        return Ok(pool.clone());
+    }
+
+    let pool = Arc::new(WalkPool::new(0)?);
+    *slot = Some(pool.clone());
+    Ok(pool)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_zero_threads_uses_cpu_count() {
+        let pool = WalkPool::new(0).unwrap();
+        assert_eq!(pool.threads(), num_cpus::get());
+    }
+
+    #[test]
+    fn test_peak_tracks_concurrent_workers() {
+        let pool = WalkPool::new(2).unwrap();
+        {
+            let _a = pool.enter();
+            let _b = pool.enter();
+            assert_eq!(pool.peak_threads(), 2);
+        }
+        let _c = pool.enter();
+        assert_eq!(pool.peak_threads(), 2);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_background_pool_workers_run_niced() {
+        // SAFETY: reads the calling thread's nice value
+        let nice = || unsafe { libc::getpriority(libc::PRIO_PROCESS, 0) };
+        let expected = (nice() + process_priority::NICE_LEVEL).min(19);
+
+        let pool = WalkPool::with_priority(1, true).unwrap();
+        assert!(pool.is_background());
+        assert_eq!(pool.pool.install(nice), expected);
+
+        let normal = WalkPool::new(1).unwrap();
+        assert_eq!(normal.pool.install(nice), nice());
+    }
+}
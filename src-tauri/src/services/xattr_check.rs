@@ -0,0 +1,91 @@
+//! Extended attribute and ACL support at a backup destination
+//!
+//! A job can ask rsync to copy extended attributes (`-X`) and ACLs (`-A`).
+//! FAT and exFAT drives have nowhere to store either: rsync reports every
+//! file it couldn't set them on and exits with code 23, and the backup holds
+//! the data without the metadata. The destination's filesystem is looked up
+//! in the mount table before the backup so the log says why up front.
+
+use crate::types::job::SyncJob;
+use crate::utils::parse_ssh_remote;
+use crate::utils::platform;
+use std::path::Path;
+
+/// The warning for `job` if it asks for extended attributes or ACLs and its
+/// destination is on `fs_type`, which can't store them
+pub fn warning_for(job: &SyncJob, fs_type: &str) -> Option<String> {
+    let wanted = match (job.config.preserve_xattrs, job.config.preserve_acls) {
+        (false, false) => return None,
+        (true, false) => "extended attributes",
+        (false, true) => "ACLs",
+        (true, true) => "extended attributes or ACLs",
+    };
+    if platform::stores_extended_metadata(fs_type) {
+        return None;
+    }
+    Some(format!(
+        "{} is on {}, which can't store {}; files are backed up without them",
+        job.dest_path, fs_type, wanted
+    ))
+}
+
+/// Pre-flight for a backup: `warning_for` the filesystem of `job`'s local
+/// destination. Remote destinations and unreadable mount tables pass.
+pub fn check_destination(job: &SyncJob) -> Option<String> {
+    if !job.config.preserve_xattrs && !job.config.preserve_acls {
+        return None;
+    }
+    if parse_ssh_remote(&job.dest_path).is_some() {
+        return None;
+    }
+    // A destination that doesn't exist yet is created on its nearest parent
+    let dir = Path::new(&job.dest_path)
+        .ancestors()
+        .find(|dir| dir.is_dir())?;
+    let mount = platform::mount_info(dir)?;
+    warning_for(job, &mount.fs_type)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn job_keeping(xattrs: bool, acls: bool) -> SyncJob {
+        let mut job = SyncJob {
+            dest_path: "/Volumes/USB/Backups".to_string(),
+            ..SyncJob::default()
+        };
+        job.config.preserve_xattrs = xattrs;
+        job.config.preserve_acls = acls;
+        job
+    }
+
+    #[test]
+    fn test_unsupported_destination_warns() {
+        let warning = warning_for(&job_keeping(true, false), "exfat").unwrap();
+        assert!(warning.contains("/Volumes/USB/Backups"), "{}", warning);
+        assert!(warning.contains("can't store extended attributes;"));
+
+        let warning = warning_for(&job_keeping(true, true), "msdos").unwrap();
+        assert!(
+            warning.contains("extended attributes or ACLs"),
+            "{}",
+            warning
+        );
+        assert!(warning_for(&job_keeping(false, true), "vfat")
+            .unwrap()
+            .contains("can't store ACLs"));
+    }
+
+    #[test]
+    fn test_capable_destinations_and_plain_jobs_pass() {
+        assert_eq!(warning_for(&job_keeping(true, true), "apfs"), None);
+        assert_eq!(warning_for(&job_keeping(true, true), "ext4"), None);
+        assert_eq!(warning_for(&job_keeping(false, false), "exfat"), None);
+
+        let mut job = job_keeping(true, true);
+        job.dest_path = "me@nas:/backups".to_string();
+        assert_eq!(check_destination(&job), None);
+        assert_eq!(check_destination(&job_keeping(false, false)), None);
+    }
+}
@@ -4,13 +4,18 @@
 //! Services are initialized once at app startup and shared across all commands.
 
 use crate::security::PathValidator;
+use crate::services::cancel_token::CancelRegistry;
 use crate::services::data_dir;
 use crate::services::file_service::FileService;
-use crate::services::index_service::IndexService;
+use crate::services::index_service::{self, FileFlagThresholds, IndexService};
 use crate::services::job_scheduler::JobScheduler;
 use crate::services::snapshot_service::SnapshotService;
 use crate::services::store::Store;
-use std::path::PathBuf;
+use crate::services::{
+    hardlink_probe, index_warmup, parallel_restore, process_priority, snapshot_commit, volume_gate,
+    walk_pool,
+};
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, RwLock};
 
 /// Application state containing all singleton services
@@ -29,6 +34,8 @@ pub struct AppState {
     pub data_dir: PathBuf,
     /// Path validator for security
     pub path_validator: Arc<RwLock<PathValidator>>,
+    /// Cancel tokens of running long operations
+    pub operations: Arc<CancelRegistry>,
 }
 
 impl AppState {
@@ -55,14 +62,47 @@ impl AppState {
         // Initialize services
         let file_service = Arc::new(FileService::new());
 
+        let store = Arc::new(
+            Store::new(&data_dir_path).with_config_dir(&Self::get_config_dir(&data_dir_path)),
+        );
+        let preferences = store.load_preferences().unwrap_or_default();
+        file_service
+            .set_max_read_bytes(preferences.max_preview_size_mb.saturating_mul(1024 * 1024));
+
+        // Search tokenizer mode must be set before any index is opened
+        index_service::configure_diacritic_folding(preferences.search_fold_diacritics);
+        index_service::configure_normalized_storage(preferences.normalized_index_storage);
+        index_service::configure_unicode_normalization(!preferences.preserve_raw_path_names);
+        index_service::configure_auto_compaction(preferences.compact_after_deletions);
+        index_service::configure_max_index_files(preferences.max_index_files);
+        index_service::configure_flag_thresholds(FileFlagThresholds::from_preferences(
+            preferences.large_file_mb,
+            preferences.stale_after_days,
+        ));
+        volume_gate::configure(preferences.serialize_index_with_backups);
+        snapshot_commit::configure_verification(preferences.verify_index_after_backup);
+        process_priority::configure(preferences.background_priority);
+        hardlink_probe::configure(preferences.require_hardlinks);
+        index_warmup::configure(preferences.warmup_snapshot_count);
+        parallel_restore::configure(preferences.restore_workers);
+
         let index_service = Arc::new(
             IndexService::new(&data_dir_path)
                 .map_err(|e| format!("Failed to initialize index service: {}", e))?,
         );
+        // Destination indexes wait for an explicit rebuild; the local one is ours
+        if let Err(e) = index_service.apply_configured_diacritic_folding() {
+            log::warn!(
+                "Failed to apply diacritic folding to the local index: {}",
+                e
+            );
+        }
 
         let snapshot_service = Arc::new(SnapshotService::new(&data_dir_path));
 
-        let store = Arc::new(Store::new(&data_dir_path));
+        // Size the shared directory walk pool before any indexing starts
+        walk_pool::configure(preferences.index_threads, preferences.background_priority)
+            .map_err(|e| format!("Failed to initialize walk pool: {}", e))?;
         let scheduler = Arc::new(JobScheduler::new());
 
         // Initialize path validator with standard roots
@@ -77,6 +117,7 @@ impl AppState {
             scheduler,
             data_dir: data_dir_path,
             path_validator: Arc::new(RwLock::new(path_validator)),
+            operations: Arc::new(CancelRegistry::new()),
         };
 
         if let Err(e) = app_state.update_job_roots() {
@@ -149,13 +190,17 @@ impl AppState {
             }
         }
 
-        // Production: use standard user data directory
-        if let Some(dir) = dirs::data_dir() {
-            dir.join("amber")
-        } else if let Some(home) = dirs::home_dir() {
-            home.join(".amber")
+        // Production: use standard user data directory (XDG-aware on Linux)
+        data_dir::default_data_dir()
+    }
+
+    /// Get the preferences directory: the platform config dir in production,
+    /// the data directory itself for dev mock-data
+    fn get_config_dir(data_dir_path: &Path) -> PathBuf {
+        if data_dir_path == data_dir::default_data_dir() {
+            data_dir::default_config_dir()
         } else {
-            std::env::temp_dir().join("amber")
+            data_dir_path.to_path_buf()
         }
     }
 }
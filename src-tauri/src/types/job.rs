@@ -1,4 +1,5 @@
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 #[serde(rename_all = "SCREAMING_SNAKE_CASE")]
@@ -17,6 +18,136 @@ pub enum JobStatus {
     Failed,
 }
 
+/// When rsync removes extraneous files on the destination (only used with `delete`)
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum DeleteMode {
+    /// Plain `--delete`: rsync picks the strategy (during, on modern versions)
+    #[default]
+    Auto,
+    /// `--delete-before`: free space first, useful on nearly full destinations
+    Before,
+    /// `--delete-during`
+    During,
+    /// `--delete-after`: only delete once the transfer has succeeded
+    After,
+}
+
+impl DeleteMode {
+    pub fn as_flag(&self) -> &'static str {
+        match self {
+            DeleteMode::Auto => "--delete",
+            DeleteMode::Before => "--delete-before",
+            DeleteMode::During => "--delete-during",
+            DeleteMode::After => "--delete-after",
+        }
+    }
+}
+
+/// How much of the destination a `--delete` run may remove before the backup
+/// is refused as likely misconfigured (say, the wrong drive at the source)
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum DeleteThreshold {
+    /// At most this many files and folders
+    Count(u64),
+    /// At most this percentage of the files and folders in the destination
+    Percent(u8),
+}
+
+impl std::fmt::Display for DeleteThreshold {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DeleteThreshold::Count(count) => write!(f, "{} files", count),
+            DeleteThreshold::Percent(percent) => write!(f, "{}% of its files", percent),
+        }
+    }
+}
+
+/// How much rsync prints while it runs. Huge incremental runs produce a line
+/// per changed file at `Full`; the lower levels trade that detail for less
+/// output to parse.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum RsyncVerbosity {
+    /// Every changed file (`--itemize-changes --progress`, plus `-v` with `verbose`)
+    #[default]
+    Full,
+    /// Whole-run progress and the summary (`--info=progress2,stats2`, or
+    /// `--progress --stats` before rsync 3.1)
+    Progress,
+    /// Summary only (`--info=stats2`, or `--stats` before rsync 3.1)
+    Quiet,
+}
+
+impl RsyncVerbosity {
+    /// Output flags for this level. `info_flags` says whether the rsync
+    /// running takes `--info` (3.1 and later); older ones get the nearest
+    /// classic flags instead.
+    pub fn output_flags(&self, info_flags: bool) -> &'static [&'static str] {
+        match (self, info_flags) {
+            (RsyncVerbosity::Full, _) => &["--itemize-changes", "--stats", "--progress"],
+            (RsyncVerbosity::Progress, true) => &["--info=progress2,stats2"],
+            (RsyncVerbosity::Progress, false) => &["--progress", "--stats"],
+            (RsyncVerbosity::Quiet, true) => &["--info=stats2"],
+            (RsyncVerbosity::Quiet, false) => &["--stats"],
+        }
+    }
+
+    /// Whether rsync prints a line per transferred file at this level
+    pub fn lists_files(&self) -> bool {
+        *self == RsyncVerbosity::Full
+    }
+}
+
+/// A daily window of local time during which a job's transfer rate is capped
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct BandwidthWindow {
+    /// Opening time, "HH:MM"
+    pub start: String,
+    /// Closing time, "HH:MM" (exclusive); earlier than `start` wraps past
+    /// midnight, equal to it covers the whole day
+    pub end: String,
+    /// Passed to `--bwlimit`, in KiB/s
+    pub bwlimit_kbps: u32,
+}
+
+/// Which files to back up by modification time. Both bounds may be set to
+/// keep a window; neither set backs up everything.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct AgeFilter {
+    /// Skip files last modified more than this many days ago
+    #[serde(default)]
+    pub max_age_days: Option<u32>,
+    /// Skip files modified within the last this many days
+    #[serde(default)]
+    pub min_age_days: Option<u32>,
+}
+
+impl AgeFilter {
+    const SECS_PER_DAY: i64 = 24 * 60 * 60;
+
+    /// Whether the filter leaves anything out at all
+    pub fn is_active(&self) -> bool {
+        self.max_age_days.is_some() || self.min_age_days.is_some()
+    }
+
+    /// Whether a file last modified at `mtime` is backed up at `now` (both
+    /// Unix seconds)
+    pub fn keeps(&self, mtime: i64, now: i64) -> bool {
+        let age = now - mtime;
+        let young_enough = self
+            .max_age_days
+            .map_or(true, |days| age <= i64::from(days) * Self::SECS_PER_DAY);
+        let old_enough = self
+            .min_age_days
+            .map_or(true, |days| age >= i64::from(days) * Self::SECS_PER_DAY);
+        young_enough && old_enough
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct RsyncConfig {
@@ -24,7 +155,20 @@ pub struct RsyncConfig {
     pub compress: bool,
     pub archive: bool,
     pub delete: bool,
+    /// Delete strategy when `delete` is enabled
+    #[serde(default)]
+    pub delete_mode: DeleteMode,
+    /// Also delete destination files matching the exclude patterns (needs `delete`)
+    #[serde(default)]
+    pub delete_excluded: bool,
+    /// Refuse a backup whose dry run would delete more than this from the
+    /// destination, unless it is run with the override (needs `delete`)
+    #[serde(default)]
+    pub max_delete: Option<DeleteThreshold>,
     pub verbose: bool,
+    /// Output detail; `verbose` only applies at `Full`
+    #[serde(default)]
+    pub verbosity: RsyncVerbosity,
     pub exclude_patterns: Vec<String>,
     pub link_dest: Option<String>,
     pub custom_flags: String,
@@ -35,6 +179,43 @@ pub struct RsyncConfig {
     /// Stall timeout - kill if no progress for this many seconds (default: 300 = 5 min)
     #[serde(default = "default_stall_timeout")]
     pub stall_timeout_seconds: u64,
+    /// Traverse into filesystems mounted below the source instead of passing
+    /// `--one-file-system`. Off by default: with it on, a source like `/` also
+    /// copies every mounted volume, network share and pseudo-filesystem under
+    /// it, which can multiply backup size.
+    #[serde(default)]
+    pub cross_filesystems: bool,
+    /// Let rsync create missing parent directories of the destination
+    /// (`--mkpath`, rsync 3.2.3+; older versions rely on the pre-created target)
+    #[serde(default)]
+    pub create_dest: bool,
+    /// Copy extended attributes (`-X`): Finder tags, quarantine flags and
+    /// other metadata kept outside the file's contents
+    #[serde(default)]
+    pub preserve_xattrs: bool,
+    /// Copy POSIX ACLs (`-A`), which also preserves permissions
+    #[serde(default)]
+    pub preserve_acls: bool,
+    /// Cap on the transfer rate in KiB/s (`--bwlimit`); None or 0 is unlimited
+    #[serde(default)]
+    pub bwlimit_kbps: Option<u32>,
+    /// Rate limits by time of day, checked when the backup starts; they
+    /// replace `bwlimit_kbps` inside their windows
+    #[serde(default)]
+    pub bandwidth_schedule: Vec<BandwidthWindow>,
+    /// Force an rsync protocol version (`--protocol`) to talk to a much older
+    /// rsync on the other end; None lets the two negotiate
+    #[serde(default)]
+    pub protocol_version: Option<u32>,
+    /// Back up only files whose modification time passes this filter. The
+    /// local source is scanned first and rsync gets the list through
+    /// `--files-from`; SSH sources can't be filtered.
+    #[serde(default)]
+    pub age_filter: Option<AgeFilter>,
+}
+
+fn default_true() -> bool {
+    true
 }
 
 fn default_timeout() -> u64 {
@@ -52,13 +233,25 @@ impl Default for RsyncConfig {
             compress: false,
             archive: true,
             delete: false,
+            delete_mode: DeleteMode::Auto,
+            delete_excluded: false,
+            max_delete: None,
             verbose: true,
+            verbosity: RsyncVerbosity::Full,
             exclude_patterns: vec![],
             link_dest: None,
             custom_flags: String::new(),
             custom_command: None,
             timeout_seconds: default_timeout(),
             stall_timeout_seconds: default_stall_timeout(),
+            cross_filesystems: false,
+            create_dest: false,
+            preserve_xattrs: false,
+            preserve_acls: false,
+            bwlimit_kbps: None,
+            bandwidth_schedule: Vec::new(),
+            protocol_version: None,
+            age_filter: None,
         }
     }
 }
@@ -74,6 +267,18 @@ pub struct SshConfig {
     pub disable_host_key_checking: Option<bool>,
     pub proxy_jump: Option<String>,
     pub custom_ssh_options: Option<String>,
+    /// Compress the transfer (`-z`) even if the job doesn't ask for it;
+    /// None means on, since SSH links are usually slower than the disks
+    #[serde(default)]
+    pub compress: Option<bool>,
+    /// ssh `ServerAliveInterval` in seconds, so a stalled link is noticed
+    /// and dropped connections don't hang the backup (None = 15, 0 = off)
+    #[serde(default)]
+    pub keepalive_interval_secs: Option<u32>,
+    /// ssh `ServerAliveCountMax`: unanswered keepalives before giving up
+    /// (None = 4)
+    #[serde(default)]
+    pub keepalive_count_max: Option<u32>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -91,7 +296,7 @@ pub enum DestinationType {
     Cloud,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct CloudConfig {
     pub remote_name: String,
@@ -100,6 +305,22 @@ pub struct CloudConfig {
     pub encrypt_password_keychain: Option<String>,
     pub bandwidth: Option<String>,
     pub provider: Option<String>,
+    /// rclone `--retries`: times rclone re-runs the whole sync itself
+    /// (None = rclone's default of 3)
+    #[serde(default)]
+    pub retries: Option<u32>,
+    /// rclone `--low-level-retries` for single HTTP operations
+    /// (None = rclone's default of 10)
+    #[serde(default)]
+    pub low_level_retries: Option<u32>,
+    /// rclone `--timeout`: seconds a connection may sit idle
+    /// (None = rclone's default of 5 minutes)
+    #[serde(default)]
+    pub timeout_seconds: Option<u64>,
+    /// Runs Amber makes of a sync that ends with a temporary error
+    /// (None = `rclone_service::DEFAULT_SYNC_ATTEMPTS`)
+    #[serde(default)]
+    pub sync_attempts: Option<u32>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -109,6 +330,12 @@ pub struct SyncJob {
     pub name: String,
     pub source_path: String,
     pub dest_path: String,
+    /// Folder inside `dest_path` the job's backups go to. May use
+    /// `{source_basename}` and `{host}` (the SSH source's host, or this
+    /// machine's name for local sources). None nests under the source's
+    /// basename.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dest_subfolder: Option<String>,
     pub mode: SyncMode,
     pub status: JobStatus,
     pub destination_type: Option<DestinationType>,
@@ -118,6 +345,19 @@ pub struct SyncJob {
     pub ssh_config: Option<SshConfig>,
     pub cloud_config: Option<CloudConfig>,
     pub last_run: Option<i64>,
+    /// Paused jobs keep their schedule and history but are never dispatched
+    /// by the scheduler; manual runs still work
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// Index each snapshot as soon as its backup finishes. When off, the
+    /// snapshot is only recorded in the manifest and indexed when asked for,
+    /// which keeps slow drives free for the next backup.
+    #[serde(default = "default_true")]
+    pub auto_index: bool,
+    /// Extra environment for the rsync process. Only names in
+    /// `validation::ALLOWED_RSYNC_ENV` are accepted; anything else fails the run.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub env: HashMap<String, String>,
     /// DEPRECATED: Snapshots are now stored in manifest.json on the backup drive.
     /// This field is kept for reading old jobs.json files during migration.
     /// It is not serialized when saving jobs.
@@ -132,6 +372,7 @@ impl Default for SyncJob {
             name: String::new(),
             source_path: String::new(),
             dest_path: String::new(),
+            dest_subfolder: None,
             mode: SyncMode::Archive,
             status: JobStatus::Idle,
             destination_type: Some(DestinationType::Local),
@@ -141,6 +382,9 @@ impl Default for SyncJob {
             ssh_config: None,
             cloud_config: None,
             last_run: None,
+            enabled: true,
+            auto_index: true,
+            env: HashMap::new(),
             snapshots: None,
         }
     }
@@ -16,6 +16,42 @@ fn default_accent() -> String {
     "blue".to_string()
 }
 
+fn default_index_threads() -> usize {
+    0
+}
+
+fn default_max_read_mb() -> u64 {
+    25
+}
+
+fn default_compact_after_deletions() -> u64 {
+    crate::services::index_service::DEFAULT_COMPACT_AFTER_DELETIONS
+}
+
+fn default_max_index_files() -> u64 {
+    crate::services::index_service::DEFAULT_MAX_INDEX_FILES
+}
+
+fn default_large_file_mb() -> u64 {
+    crate::services::index_service::DEFAULT_LARGE_FILE_MB
+}
+
+fn default_stale_after_days() -> u64 {
+    crate::services::index_service::DEFAULT_STALE_AFTER_DAYS
+}
+
+fn default_warmup_snapshots() -> usize {
+    crate::services::index_warmup::DEFAULT_WARMUP_SNAPSHOTS
+}
+
+fn default_restore_workers() -> usize {
+    crate::services::parallel_restore::DEFAULT_RESTORE_WORKERS
+}
+
+fn default_log_level() -> String {
+    "info".to_string()
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct AppPreferences {
@@ -29,6 +65,69 @@ pub struct AppPreferences {
     pub theme: String,
     #[serde(default = "default_accent")]
     pub accent_color: String,
+    /// Thread budget shared by all index walks (0 = one per CPU)
+    #[serde(default = "default_index_threads")]
+    pub index_threads: usize,
+    /// Fold diacritics in file search so `cafe` also finds `café`. Turning it
+    /// off makes accented queries exact. The local index switches right away,
+    /// a destination's when its search index is rebuilt.
+    #[serde(default = "default_true")]
+    pub search_fold_diacritics: bool,
+    /// Experimental: lay new indexes out so files unchanged between snapshots
+    /// share one row. Existing indexes keep the layout they were created with.
+    #[serde(default = "default_false")]
+    pub normalized_index_storage: bool,
+    /// Store file names in the index exactly as the filesystem reports them
+    /// instead of NFC-normalized. Keeps paths byte-exact, but then the same
+    /// name indexed on macOS and Linux no longer compares or searches as equal.
+    #[serde(default = "default_false")]
+    pub preserve_raw_path_names: bool,
+    /// Largest file (in MB) the file preview will load into memory
+    #[serde(default = "default_max_read_mb")]
+    pub max_preview_size_mb: u64,
+    /// Hold off indexing while a backup is writing to the same volume, so the
+    /// two don't compete for one drive
+    #[serde(default = "default_false")]
+    pub serialize_index_with_backups: bool,
+    /// After a backup is indexed, recount the snapshot folder and flag the
+    /// snapshot if the index disagrees. Costs one extra walk per backup.
+    #[serde(default = "default_false")]
+    pub verify_index_after_backup: bool,
+    /// Vacuum an index on its next open once this many snapshots have been
+    /// deleted from it since the last vacuum (0 = never)
+    #[serde(default = "default_compact_after_deletions")]
+    pub compact_after_deletions: u64,
+    /// Refuse to index a snapshot holding more files and folders than this,
+    /// e.g. a job accidentally pointed at `/` (0 = no limit)
+    #[serde(default = "default_max_index_files")]
+    pub max_index_files: u64,
+    /// Files at least this many MB are flagged large when indexed
+    #[serde(default = "default_large_file_mb")]
+    pub large_file_mb: u64,
+    /// Files not modified for this many days before their snapshot are
+    /// flagged stale when indexed
+    #[serde(default = "default_stale_after_days")]
+    pub stale_after_days: u64,
+    /// Run rsync under nice/ionice and index walks on lowered-priority
+    /// threads, so backups don't slow the machine down (Unix only)
+    #[serde(default = "default_false")]
+    pub background_priority: bool,
+    /// Fail TimeMachine backups to destinations that can't hardlink (exFAT,
+    /// FAT, some SMB shares) instead of only warning that every snapshot will
+    /// be a full copy
+    #[serde(default = "default_false")]
+    pub require_hardlinks: bool,
+    /// Latest snapshots per job whose root folder is read from the index as
+    /// soon as their backup drive is connected (0 = off)
+    #[serde(default = "default_warmup_snapshots")]
+    pub warmup_snapshot_count: usize,
+    /// Threads that restore selected files by copying them directly, which
+    /// beats rsync's one-at-a-time copy for many small files (0 = use rsync)
+    #[serde(default = "default_restore_workers")]
+    pub restore_workers: usize,
+    /// Minimum level written to the log file ("error" through "trace", or "off")
+    #[serde(default = "default_log_level")]
+    pub log_level: String,
 }
 
 impl Default for AppPreferences {
@@ -39,6 +138,22 @@ impl Default for AppPreferences {
             notifications: true,
             theme: "system".to_string(),
             accent_color: "blue".to_string(),
+            index_threads: 0,
+            search_fold_diacritics: true,
+            normalized_index_storage: false,
+            preserve_raw_path_names: false,
+            max_preview_size_mb: 25,
+            serialize_index_with_backups: false,
+            verify_index_after_backup: false,
+            compact_after_deletions: default_compact_after_deletions(),
+            max_index_files: default_max_index_files(),
+            large_file_mb: default_large_file_mb(),
+            stale_after_days: default_stale_after_days(),
+            background_priority: false,
+            require_hardlinks: false,
+            warmup_snapshot_count: default_warmup_snapshots(),
+            restore_workers: default_restore_workers(),
+            log_level: "info".to_string(),
         }
     }
 }
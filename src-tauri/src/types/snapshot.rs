@@ -1,3 +1,4 @@
+use crate::types::manifest::{IndexDrift, SnapshotChanges};
 use serde::{Deserialize, Serialize};
 
 /// Centralized file type constants - use these everywhere instead of string literals.
@@ -5,6 +6,8 @@ use serde::{Deserialize, Serialize};
 pub mod file_type {
     pub const DIR: &str = "dir";
     pub const FILE: &str = "file";
+    /// Fifo, socket or device; browsable, but skipped on restore
+    pub const SPECIAL: &str = "special";
 
     /// Check if a string represents a directory type
     pub fn is_dir(s: &str) -> bool {
@@ -12,6 +15,66 @@ pub mod file_type {
     }
 }
 
+/// Broad kind of a file, told by its extension, for "only images" style
+/// filters when browsing and searching
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum FileCategory {
+    Images,
+    Documents,
+    Audio,
+    Video,
+    Archives,
+    Code,
+}
+
+impl FileCategory {
+    pub const ALL: [FileCategory; 6] = [
+        FileCategory::Images,
+        FileCategory::Documents,
+        FileCategory::Audio,
+        FileCategory::Video,
+        FileCategory::Archives,
+        FileCategory::Code,
+    ];
+
+    /// Extensions in the category: lowercase, without the dot
+    pub fn extensions(self) -> &'static [&'static str] {
+        match self {
+            FileCategory::Images => &[
+                "jpg", "jpeg", "png", "gif", "bmp", "tif", "tiff", "webp", "heic", "heif", "svg",
+                "ico", "raw", "cr2", "cr3", "nef", "arw", "dng", "orf", "raf", "psd",
+            ],
+            FileCategory::Documents => &[
+                "pdf", "doc", "docx", "odt", "rtf", "txt", "md", "pages", "xls", "xlsx", "ods",
+                "csv", "numbers", "ppt", "pptx", "odp", "key", "epub",
+            ],
+            FileCategory::Audio => &[
+                "mp3", "m4a", "aac", "wav", "flac", "alac", "aiff", "aif", "ogg", "opus", "wma",
+            ],
+            FileCategory::Video => &[
+                "mp4", "m4v", "mov", "avi", "mkv", "webm", "wmv", "flv", "mpg", "mpeg", "3gp",
+            ],
+            FileCategory::Archives => &[
+                "zip", "tar", "gz", "tgz", "bz2", "xz", "7z", "rar", "dmg", "iso",
+            ],
+            FileCategory::Code => &[
+                "rs", "py", "js", "ts", "tsx", "jsx", "c", "h", "cpp", "hpp", "java", "kt",
+                "swift", "go", "rb", "php", "cs", "sh", "html", "css", "json", "toml", "yaml",
+                "yml", "sql",
+            ],
+        }
+    }
+
+    /// The category `extension` (with or without the dot) belongs to
+    pub fn of_extension(extension: &str) -> Option<Self> {
+        let extension = extension.trim_start_matches('.').to_lowercase();
+        Self::ALL
+            .into_iter()
+            .find(|category| category.extensions().contains(&extension.as_str()))
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Snapshot {
@@ -50,6 +113,12 @@ pub struct SnapshotMetadata {
     /// Number of files changed since previous snapshot
     #[serde(skip_serializing_if = "Option::is_none")]
     pub changes_count: Option<u64>,
+    /// Added/modified/deleted breakdown of `changes_count`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub changes: Option<SnapshotChanges>,
+    /// Index/disk file count mismatch found by post-backup verification
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub index_drift: Option<IndexDrift>,
 }
 
 fn default_status() -> String {
@@ -67,6 +136,10 @@ pub struct FileNode {
     pub modified: i64,
     pub children: Option<Vec<FileNode>>,
     pub path: String,
+    /// Paths in the same snapshot sharing this file's inode, when more than
+    /// one (hard links). Only the index's directory listing fills it in.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub link_count: Option<u32>,
 }
 
 impl FileNode {
@@ -85,6 +158,8 @@ impl FileNode {
             name,
             node_type: if is_dir {
                 file_type::DIR
+            } else if file_type == file_type::SPECIAL {
+                file_type::SPECIAL
             } else {
                 file_type::FILE
             }
@@ -93,6 +168,7 @@ impl FileNode {
             modified: mtime_secs * 1000,
             children: if is_dir { Some(Vec::new()) } else { None },
             path,
+            link_count: None,
         }
     }
 }
@@ -11,6 +11,9 @@
 //!
 //! - SQLite `files.path`: ABSOLUTE paths
 //! - SQLite `files.parent_path`: RELATIVE paths (from snapshot root)
+//! - Bytes in a path that aren't valid UTF-8 are stored as `%XX` (see
+//!   `path_text`); the exact bytes go in `raw_paths`, as do those of paths
+//!   whose stored form was NFC-normalized
 //! - SQLite `files.mtime`: Unix SECONDS (converted at API boundary)
 //! - SQLite `snapshots.timestamp`: Unix MILLISECONDS
 //! - Manifest timestamps: Unix MILLISECONDS
@@ -18,27 +21,109 @@
 pub mod platform;
 pub mod validation;
 
-use std::path::Path;
+use std::path::{Component, Path, PathBuf};
 
 // ============================================================================
 // Path Utilities (TIM-122)
 // ============================================================================
 
-/// Check if a path is an SSH remote (user@host:/path format)
-pub fn is_ssh_remote(path: &str) -> bool {
-    !path.starts_with('/') && path.contains('@') && path.contains(':')
+/// An rsync-over-SSH location, split into its parts
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SshRemote {
+    pub user: Option<String>,
+    /// Hostname or IP; IPv6 without the brackets
+    pub host: String,
+    /// Remote path; empty means the login directory
+    pub path: String,
+    /// Only settable in the `ssh://` form
+    pub port: Option<u16>,
 }
 
-/// Extract the local path part from an SSH remote path
-/// Returns `None` if the path is not an SSH remote
+/// Parse `[user@]host:path`, `[user@][v6addr]:path` or
+/// `ssh://[user@]host[:port][/path]`.
 ///
-/// Example: `"user@host:/var/www"` -> `Some("/var/www")`
-pub fn ssh_local_part(path: &str) -> Option<&str> {
-    if is_ssh_remote(path) {
-        path.split_once(':').map(|(_, local)| local)
+/// Follows rsync's rule for the scp form: a colon before any slash makes the
+/// path remote, so `/a:b` and `dir/a:b` are local. Only the first colon after
+/// the host splits, and the path may contain more colons or `@`.
+///
+/// Example: `"git@[::1]:/srv"` -> user `git`, host `::1`, path `/srv`
+pub fn parse_ssh_remote(path: &str) -> Option<SshRemote> {
+    if let Some(url) = path.strip_prefix("ssh://") {
+        return parse_ssh_url(url);
+    }
+
+    let (user, rest) = split_user(path)?;
+    let (host, remote_path) = if let Some(bracketed) = rest.strip_prefix('[') {
+        let (host, after) = bracketed.split_once(']')?;
+        (host, after.strip_prefix(':')?)
     } else {
-        None
+        let (host, remote_path) = rest.split_once(':')?;
+        if host.contains('/') {
+            return None;
+        }
+        (host, remote_path)
+    };
+
+    Some(SshRemote {
+        user,
+        host: valid_host(host)?,
+        path: remote_path.to_string(),
+        port: None,
+    })
+}
+
+fn parse_ssh_url(url: &str) -> Option<SshRemote> {
+    let (authority, remote_path) = match url.find('/') {
+        Some(slash) => url.split_at(slash),
+        None => (url, ""),
+    };
+    let (user, hostport) = split_user(authority)?;
+
+    let (host, port) = if let Some(bracketed) = hostport.strip_prefix('[') {
+        let (host, after) = bracketed.split_once(']')?;
+        match after {
+            "" => (host, None),
+            _ => (host, Some(after.strip_prefix(':')?)),
+        }
+    } else {
+        match hostport.split_once(':') {
+            Some((host, port)) => (host, Some(port)),
+            None => (hostport, None),
+        }
+    };
+    let port = match port {
+        Some(port) => Some(port.parse::<u16>().ok().filter(|p| *p != 0)?),
+        None => None,
+    };
+
+    Some(SshRemote {
+        user,
+        host: valid_host(host)?,
+        path: remote_path.to_string(),
+        port,
+    })
+}
+
+/// Split off `user@` when the `@` comes before any `:` or `/`
+fn split_user(s: &str) -> Option<(Option<String>, &str)> {
+    let boundary = s.find([':', '/']).unwrap_or(s.len());
+    match s[..boundary].split_once('@') {
+        Some(("", _)) => None,
+        Some((user, _)) => Some((Some(user.to_string()), &s[user.len() + 1..])),
+        None => Some((None, s)),
+    }
+}
+
+fn valid_host(host: &str) -> Option<String> {
+    if host.is_empty() || host.contains(|c: char| c.is_whitespace() || c == '/' || c == '@') {
+        return None;
     }
+    Some(host.to_string())
+}
+
+/// Check if a path is an SSH remote (user@host:/path format)
+pub fn is_ssh_remote(path: &str) -> bool {
+    parse_ssh_remote(path).is_some_and(|remote| remote.user.is_some())
 }
 
 /// Make a path relative to a root directory
@@ -51,8 +136,162 @@ pub fn ssh_local_part(path: &str) -> Option<&str> {
 /// ```
 pub fn make_relative(path: &Path, root: &Path) -> String {
     path.strip_prefix(root)
-        .map(|p| p.to_string_lossy().to_string())
-        .unwrap_or_else(|_| path.to_string_lossy().to_string())
+        .map(|p| path_text(p).into_owned())
+        .unwrap_or_else(|_| path_text(path).into_owned())
+}
+
+/// A path as text for the index. Unlike `to_string_lossy`, bytes that aren't
+/// valid UTF-8 (possible in Unix file names) become `%XX` rather than all
+/// turning into U+FFFD, so distinct names stay distinct. A name that gets
+/// escaped has its own `%` written as `%25`, and so does a valid name that
+/// already holds a `%XX`, so "caf%E9" and the Latin-1 bytes "caf\xE9" can't
+/// end up with the same text. Any other valid UTF-8 comes back unchanged.
+/// Each name is escaped on its own, so a path's text is its names' texts
+/// joined by `/`. `path_bytes` keeps what this changed.
+pub fn path_text(path: &Path) -> std::borrow::Cow<'_, str> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::OsStrExt;
+
+        let bytes = path.as_os_str().as_bytes();
+        if !bytes.split(|&b| b == b'/').any(needs_escape) {
+            return path.to_string_lossy();
+        }
+        let names: Vec<String> = bytes
+            .split(|&b| b == b'/')
+            .map(|name| {
+                if needs_escape(name) {
+                    escape_name(name)
+                } else {
+                    String::from_utf8_lossy(name).into_owned()
+                }
+            })
+            .collect();
+        std::borrow::Cow::Owned(names.join("/"))
+    }
+    #[cfg(not(unix))]
+    {
+        path.to_string_lossy()
+    }
+}
+
+/// Whether `path_text` escapes this name: it isn't valid UTF-8, or it holds
+/// something that would read as an escape
+#[cfg(unix)]
+fn needs_escape(name: &[u8]) -> bool {
+    std::str::from_utf8(name).is_err()
+        || name
+            .windows(3)
+            .any(|w| w[0] == b'%' && w[1].is_ascii_hexdigit() && w[2].is_ascii_hexdigit())
+}
+
+#[cfg(unix)]
+fn escape_name(mut bytes: &[u8]) -> String {
+    let mut text = String::with_capacity(bytes.len() + 8);
+    loop {
+        match std::str::from_utf8(bytes) {
+            Ok(valid) => {
+                text.push_str(&valid.replace('%', "%25"));
+                return text;
+            }
+            Err(e) => {
+                let (valid, rest) = bytes.split_at(e.valid_up_to());
+                // Checked by from_utf8 just above
+                let valid = std::str::from_utf8(valid).unwrap_or_default();
+                text.push_str(&valid.replace('%', "%25"));
+                let bad = e.error_len().unwrap_or(rest.len());
+                for byte in &rest[..bad] {
+                    text.push_str(&format!("%{:02X}", byte));
+                }
+                bytes = &rest[bad..];
+            }
+        }
+    }
+}
+
+/// The exact bytes of `path` when `path_text` escaped any of its names;
+/// None otherwise, and always off Unix
+pub fn path_bytes(path: &Path) -> Option<Vec<u8>> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::OsStrExt;
+
+        let bytes = path.as_os_str().as_bytes();
+        bytes
+            .split(|&b| b == b'/')
+            .any(needs_escape)
+            .then(|| bytes.to_vec())
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = path;
+        None
+    }
+}
+
+/// The bytes of `path` as the filesystem has them (UTF-8 off Unix), for
+/// `path_from_bytes` to turn back into the same path
+pub fn os_path_bytes(path: &Path) -> Vec<u8> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::OsStrExt;
+
+        path.as_os_str().as_bytes().to_vec()
+    }
+    #[cfg(not(unix))]
+    {
+        path.to_string_lossy().into_owned().into_bytes()
+    }
+}
+
+/// The path `path_bytes` or `os_path_bytes` returned the bytes of
+pub fn path_from_bytes(bytes: &[u8]) -> PathBuf {
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::OsStrExt;
+
+        PathBuf::from(std::ffi::OsStr::from_bytes(bytes))
+    }
+    #[cfg(not(unix))]
+    {
+        PathBuf::from(String::from_utf8_lossy(bytes).into_owned())
+    }
+}
+
+/// Path that leads from directory `from_dir` to `to`, using `..` as needed
+///
+/// Both paths must be absolute; returns None if they share no root
+/// (e.g. different Windows drives).
+///
+/// # Example
+/// ```ignore
+/// use std::path::{Path, PathBuf};
+/// assert_eq!(
+///     relative_path_between(Path::new("/d/b/new"), Path::new("/d/b/old")),
+///     Some(PathBuf::from("../old"))
+/// );
+/// ```
+pub fn relative_path_between(from_dir: &Path, to: &Path) -> Option<PathBuf> {
+    if !from_dir.is_absolute() || !to.is_absolute() {
+        return None;
+    }
+
+    let from: Vec<Component> = from_dir.components().collect();
+    let target: Vec<Component> = to.components().collect();
+    if from.first() != target.first() {
+        return None;
+    }
+
+    let common = from.iter().zip(&target).take_while(|(a, b)| a == b).count();
+
+    let mut relative = PathBuf::new();
+    for _ in common..from.len() {
+        relative.push("..");
+    }
+    for component in &target[common..] {
+        relative.push(component);
+    }
+    Some(relative)
 }
 
 /// Reconstruct an absolute path from a relative path and root
@@ -103,7 +342,8 @@ pub fn get_machine_id() -> String {
 }
 
 /// Information about a volume/mount point
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct VolumeInfo {
     /// Whether the path is on an external volume
     pub is_external: bool,
@@ -126,6 +366,53 @@ mod tests {
 
     // ========== Path utility tests ==========
 
+    #[test]
+    #[cfg(unix)]
+    fn test_path_text_keeps_non_utf8_bytes_apart() {
+        use std::os::unix::ffi::OsStrExt;
+
+        let latin1 = Path::new(std::ffi::OsStr::from_bytes(b"caf\xe9/r\xe9sum\xe9.txt"));
+        assert_eq!(path_text(latin1), "caf%E9/r%E9sum%E9.txt");
+        let bytes = path_bytes(latin1).unwrap();
+        assert_eq!(path_from_bytes(&bytes), latin1);
+
+        // A different invalid byte gets a different name
+        let other = Path::new(std::ffi::OsStr::from_bytes(b"caf\xe8"));
+        assert_eq!(path_text(other), "caf%E8");
+
+        let utf8 = Path::new("café/100%.txt");
+        assert_eq!(path_text(utf8), "café/100%.txt");
+        assert!(path_bytes(utf8).is_none());
+
+        // A literal escape-like name no longer reads as the Latin-1 one, and
+        // only the names that need it are escaped
+        let literal = Path::new("caf%E9/100%.txt");
+        assert_eq!(path_text(literal), "caf%25E9/100%.txt");
+        assert_eq!(path_bytes(literal).unwrap(), b"caf%E9/100%.txt");
+        let mixed = Path::new(std::ffi::OsStr::from_bytes(b"50%/caf\xe9 100%"));
+        assert_eq!(path_text(mixed), "50%/caf%E9 100%25");
+    }
+
+    #[test]
+    fn test_relative_path_between() {
+        assert_eq!(
+            relative_path_between(Path::new("/d/b/new"), Path::new("/d/b/old")),
+            Some(PathBuf::from("../old"))
+        );
+        assert_eq!(
+            relative_path_between(Path::new("/d/b/new"), Path::new("/x/old")),
+            Some(PathBuf::from("../../../x/old"))
+        );
+        assert_eq!(
+            relative_path_between(Path::new("/d/b"), Path::new("/d/b/sub")),
+            Some(PathBuf::from("sub"))
+        );
+        assert_eq!(
+            relative_path_between(Path::new("rel"), Path::new("/d")),
+            None
+        );
+    }
+
     #[test]
     fn test_is_ssh_remote() {
         assert!(is_ssh_remote("user@host:/path"));
@@ -137,10 +424,67 @@ mod tests {
     }
 
     #[test]
-    fn test_ssh_local_part() {
-        assert_eq!(ssh_local_part("user@host:/var/www"), Some("/var/www"));
-        assert_eq!(ssh_local_part("user@host:/"), Some("/"));
-        assert_eq!(ssh_local_part("/local/path"), None);
+    fn test_ssh_remote_path() {
+        let path = |s: &str| parse_ssh_remote(s).map(|r| r.path);
+        assert_eq!(path("user@host:/var/www").as_deref(), Some("/var/www"));
+        assert_eq!(path("user@host:/").as_deref(), Some("/"));
+        assert_eq!(path("/local/path"), None);
+    }
+
+    #[test]
+    fn test_parse_ssh_remote() {
+        let remote = |user: Option<&str>, host: &str, path: &str, port: Option<u16>| {
+            Some(SshRemote {
+                user: user.map(str::to_string),
+                host: host.to_string(),
+                path: path.to_string(),
+                port,
+            })
+        };
+
+        assert_eq!(
+            parse_ssh_remote("git@[::1]:/srv"),
+            remote(Some("git"), "::1", "/srv", None)
+        );
+        assert_eq!(
+            parse_ssh_remote("host:/p:ath"),
+            remote(None, "host", "/p:ath", None)
+        );
+        assert_eq!(
+            parse_ssh_remote("[fe80::1%en0]:backups"),
+            remote(None, "fe80::1%en0", "backups", None)
+        );
+        assert_eq!(
+            parse_ssh_remote("user@host:/a@b"),
+            remote(Some("user"), "host", "/a@b", None)
+        );
+        // No path: the login directory
+        assert_eq!(
+            parse_ssh_remote("user@host:"),
+            remote(Some("user"), "host", "", None)
+        );
+        assert_eq!(
+            parse_ssh_remote("ssh://backup@[2001:db8::2]:2222/data"),
+            remote(Some("backup"), "2001:db8::2", "/data", Some(2222))
+        );
+        assert_eq!(parse_ssh_remote("ssh://nas"), remote(None, "nas", "", None));
+
+        // Local paths and malformed remotes
+        assert_eq!(parse_ssh_remote("/local/a:b"), None);
+        assert_eq!(parse_ssh_remote("dir/a:b"), None);
+        assert_eq!(parse_ssh_remote("user@host"), None);
+        assert_eq!(parse_ssh_remote("@host:/p"), None);
+        assert_eq!(parse_ssh_remote(":/p"), None);
+        assert_eq!(parse_ssh_remote("git@[::1/srv"), None);
+        assert_eq!(parse_ssh_remote("git@[::1]/srv"), None);
+        assert_eq!(parse_ssh_remote("ssh://host:notaport/p"), None);
+    }
+
+    #[test]
+    fn test_is_ssh_remote_with_ipv6_and_colons() {
+        assert!(is_ssh_remote("git@[::1]:/srv"));
+        assert!(is_ssh_remote("user@host:/p:ath"));
+        assert!(!is_ssh_remote("user@dir/a:b")); // Slash before the colon
     }
 
     #[test]
@@ -3,9 +3,7 @@
 //! macOS: External drives mount under `/Volumes/`
 //! Linux: External drives mount under `/media/$USER/`, `/mnt/`, or `/run/media/$USER/`
 
-#[cfg(target_os = "linux")]
-use std::path::Path;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 /// Returns directories where external volumes are mounted on this platform.
 pub fn mount_root_paths() -> Vec<PathBuf> {
@@ -194,6 +192,136 @@ pub fn get_hardware_uuid() -> Option<String> {
     }
 }
 
+/// The mounted filesystem a path lives on
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MountInfo {
+    pub mount_point: PathBuf,
+    /// As the mount table names it, e.g. "ext4", "apfs", "smbfs", "nfs4"
+    pub fs_type: String,
+}
+
+/// Whether `fs_type` is served over the network (SMB, NFS, AFP, WebDAV,
+/// sshfs). Hardlink and symlink support on these depends on the server.
+pub fn is_network_fs(fs_type: &str) -> bool {
+    matches!(
+        fs_type.to_ascii_lowercase().as_str(),
+        "nfs"
+            | "nfs4"
+            | "cifs"
+            | "smb"
+            | "smb2"
+            | "smb3"
+            | "smbfs"
+            | "afpfs"
+            | "webdav"
+            | "davfs"
+            | "fuse.davfs"
+            | "sshfs"
+            | "fuse.sshfs"
+    )
+}
+
+/// Whether `fs_type` can hold extended attributes and ACLs. FAT, exFAT and
+/// optical media have nowhere to put them; unknown types are assumed to.
+pub fn stores_extended_metadata(fs_type: &str) -> bool {
+    !matches!(
+        fs_type.to_ascii_lowercase().as_str(),
+        "msdos" | "fat" | "vfat" | "fat32" | "exfat" | "cd9660" | "iso9660" | "udf"
+    )
+}
+
+/// The mount `path` is on: the deepest mount point containing it, after
+/// resolving symlinks. None if the mount table can't be read.
+pub fn mount_info(path: &Path) -> Option<MountInfo> {
+    let resolved = std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+
+    #[cfg(target_os = "linux")]
+    {
+        let mounts = std::fs::read_to_string("/proc/self/mounts").ok()?;
+        deepest_mount(parse_proc_mounts(&mounts), &resolved)
+    }
+
+    #[cfg(target_os = "macos")]
+    {
+        let output = std::process::Command::new("mount").output().ok()?;
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        deepest_mount(parse_mount_output(&stdout), &resolved)
+    }
+
+    #[cfg(not(any(target_os = "macos", target_os = "linux")))]
+    {
+        let _ = resolved;
+        None
+    }
+}
+
+/// The entry of `mounts` whose mount point is the longest prefix of `path`
+#[cfg(any(target_os = "macos", target_os = "linux", test))]
+fn deepest_mount(mounts: Vec<MountInfo>, path: &Path) -> Option<MountInfo> {
+    mounts
+        .into_iter()
+        .filter(|mount| path.starts_with(&mount.mount_point))
+        // Later entries shadow earlier ones mounted on the same point
+        .max_by_key(|mount| mount.mount_point.components().count())
+}
+
+/// Entries of `/proc/self/mounts` (`device mount_point fs_type options ...`,
+/// with spaces and tabs in paths escaped as octal)
+#[cfg(any(target_os = "linux", test))]
+fn parse_proc_mounts(contents: &str) -> Vec<MountInfo> {
+    fn unescape(field: &str) -> String {
+        let mut out = String::with_capacity(field.len());
+        let mut rest = field;
+        while let Some(i) = rest.find('\\') {
+            out.push_str(&rest[..i]);
+            let code = rest.get(i + 1..i + 4);
+            match code.and_then(|c| u8::from_str_radix(c, 8).ok()) {
+                Some(byte) => {
+                    out.push(byte as char);
+                    rest = &rest[i + 4..];
+                }
+                None => {
+                    out.push('\\');
+                    rest = &rest[i + 1..];
+                }
+            }
+        }
+        out.push_str(rest);
+        out
+    }
+
+    contents
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let _device = fields.next()?;
+            let mount_point = fields.next()?;
+            let fs_type = fields.next()?;
+            Some(MountInfo {
+                mount_point: PathBuf::from(unescape(mount_point)),
+                fs_type: fs_type.to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Entries of macOS `mount` output (`device on /mount point (fs_type, options)`)
+#[cfg(any(target_os = "macos", test))]
+fn parse_mount_output(stdout: &str) -> Vec<MountInfo> {
+    stdout
+        .lines()
+        .filter_map(|line| {
+            let (_device, rest) = line.split_once(" on ")?;
+            let open = rest.rfind(" (")?;
+            let fs_type = rest[open + 2..].split([',', ')']).next()?.trim();
+            Some(MountInfo {
+                mount_point: PathBuf::from(&rest[..open]),
+                fs_type: fs_type.to_string(),
+            })
+        })
+        .collect()
+}
+
 /// Minimum path component depth required for safe deletion on external volumes.
 /// Prevents deleting entire volumes (e.g., `/Volumes/DriveName` or `/media/user/drive`).
 pub fn min_delete_depth() -> usize {
@@ -252,4 +380,91 @@ mod tests {
         // Just verify it doesn't panic - result depends on platform
         let _uuid = get_hardware_uuid();
     }
+
+    #[test]
+    fn test_network_filesystems_are_classified() {
+        for fs_type in [
+            "nfs",
+            "nfs4",
+            "cifs",
+            "smb3",
+            "smbfs",
+            "afpfs",
+            "fuse.sshfs",
+            "webdav",
+        ] {
+            assert!(
+                is_network_fs(fs_type),
+                "{} is a network filesystem",
+                fs_type
+            );
+        }
+        for fs_type in ["ext4", "btrfs", "apfs", "hfs", "exfat", "msdos", "tmpfs"] {
+            assert!(!is_network_fs(fs_type), "{} is local", fs_type);
+        }
+        assert!(is_network_fs("SMBFS"));
+    }
+
+    #[test]
+    fn test_mount_lookup_from_proc_mounts() {
+        let mounts = parse_proc_mounts(
+            "/dev/sda1 / ext4 rw,relatime 0 0\n\
+             //nas/backups /mnt/nas cifs rw,vers=3.0 0 0\n\
+             nas:/export /mnt/nas/nfs\\040share nfs4 rw 0 0\n\
+             /dev/sdb1 /media/me/Backup\\040Drive exfat rw 0 0\n",
+        );
+
+        let on = |path: &str| deepest_mount(mounts.clone(), Path::new(path)).unwrap();
+        assert_eq!(on("/home/me/docs").mount_point, Path::new("/"));
+        assert_eq!(on("/home/me/docs").fs_type, "ext4");
+
+        let smb = on("/mnt/nas/jobs/laptop");
+        assert_eq!(smb.mount_point, Path::new("/mnt/nas"));
+        assert!(is_network_fs(&smb.fs_type));
+
+        let nfs = on("/mnt/nas/nfs share/x");
+        assert_eq!(nfs.mount_point, Path::new("/mnt/nas/nfs share"));
+        assert_eq!(nfs.fs_type, "nfs4");
+
+        // Component-wise: /mnt/nassy isn't inside /mnt/nas
+        assert_eq!(on("/mnt/nassy").mount_point, Path::new("/"));
+
+        let usb = on("/media/me/Backup Drive/Amber");
+        assert_eq!(usb.fs_type, "exfat");
+        assert!(!is_network_fs(&usb.fs_type));
+    }
+
+    #[test]
+    fn test_mount_lookup_from_macos_mount_output() {
+        let mounts = parse_mount_output(
+            "/dev/disk3s1s1 on / (apfs, sealed, local, read-only, journaled)\n\
+             //me@nas._smb._tcp.local/Backups on /Volumes/Backups (smbfs, nodev, nosuid, mounted by me)\n\
+             /dev/disk4s2 on /Volumes/Time (Capsule) (hfs, local, nodev, nosuid, journaled)\n",
+        );
+
+        let share = deepest_mount(mounts.clone(), Path::new("/Volumes/Backups/amber")).unwrap();
+        assert_eq!(share.mount_point, Path::new("/Volumes/Backups"));
+        assert_eq!(share.fs_type, "smbfs");
+        assert!(is_network_fs(&share.fs_type));
+
+        let disk = deepest_mount(mounts.clone(), Path::new("/Volumes/Time (Capsule)/x")).unwrap();
+        assert_eq!(disk.mount_point, Path::new("/Volumes/Time (Capsule)"));
+        assert_eq!(disk.fs_type, "hfs");
+
+        let root = deepest_mount(mounts, Path::new("/Users/me")).unwrap();
+        assert_eq!(root.fs_type, "apfs");
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_mount_info_resolves_real_paths() {
+        let dir = tempfile::tempdir().unwrap();
+        let info = mount_info(dir.path()).unwrap();
+        assert!(dir
+            .path()
+            .canonicalize()
+            .unwrap()
+            .starts_with(&info.mount_point));
+        assert!(!info.fs_type.is_empty());
+    }
 }
@@ -404,6 +404,104 @@ pub fn sanitize_ssh_option(value: &str) -> Result<String> {
     Ok(value.to_string())
 }
 
+/// Environment variable names a job may set for its rsync process.
+///
+/// Locale/charset settings and rsync's own harmless knobs only. Anything that
+/// changes which program runs or how (`RSYNC_RSH`, `PATH`, `LD_PRELOAD`, ...)
+/// stays out, since a job file could otherwise smuggle in a command. So do
+/// `RSYNC_OLD_ARGS` and `RSYNC_PROTECT_ARGS`: they change how the remote
+/// shell splits the arguments Amber builds, paths included.
+pub const ALLOWED_RSYNC_ENV: &[&str] = &[
+    "LANG",
+    "LANGUAGE",
+    "LC_ALL",
+    "LC_COLLATE",
+    "LC_CTYPE",
+    "LC_MESSAGES",
+    "LC_NUMERIC",
+    "LC_TIME",
+    "TZ",
+    "RSYNC_ICONV",
+    "RSYNC_PARTIAL_DIR",
+];
+
+/// Validates a job's custom rsync environment
+///
+/// # Security
+/// - Names must be in `ALLOWED_RSYNC_ENV` (exact, case-sensitive)
+/// - Values cannot contain NUL or line breaks
+///
+/// Returns the pairs sorted by name, ready for `Command::envs`.
+pub fn validate_rsync_env(
+    env: &std::collections::HashMap<String, String>,
+) -> Result<Vec<(String, String)>> {
+    let mut pairs = Vec::with_capacity(env.len());
+    for (name, value) in env {
+        if !ALLOWED_RSYNC_ENV.contains(&name.as_str()) {
+            return Err(AmberError::ValidationError(format!(
+                "Environment variable '{}' is not allowed for rsync jobs",
+                name
+            )));
+        }
+        if value.contains(['\0', '\n', '\r']) {
+            return Err(AmberError::ValidationError(format!(
+                "Environment variable '{}' contains invalid characters",
+                name
+            )));
+        }
+        pairs.push((name.clone(), value.clone()));
+    }
+    pairs.sort();
+    Ok(pairs)
+}
+
+/// Validates a rendered destination subfolder (see `SyncJob::dest_subfolder`)
+///
+/// # Security
+/// - Must be relative: no leading `/`, drive prefix or UNC root
+/// - Every `/`-separated segment is a plain name: no `.`, `..` or empty segments
+/// - No backslashes, NUL or control characters
+///
+/// # Examples
+/// ```ignore
+/// assert!(validate_dest_subfolder("Documents").is_ok());
+/// assert!(validate_dest_subfolder("laptop/Documents").is_ok());
+/// assert!(validate_dest_subfolder("../elsewhere").is_err());
+/// assert!(validate_dest_subfolder("/etc").is_err());
+/// ```
+pub fn validate_dest_subfolder(subfolder: &str) -> Result<&str> {
+    let invalid = |reason: &str| {
+        Err(AmberError::ValidationError(format!(
+            "Destination subfolder '{}' {}",
+            subfolder, reason
+        )))
+    };
+
+    if subfolder.trim().is_empty() {
+        return invalid("is empty");
+    }
+    if subfolder.chars().any(|c| c == '\\' || c.is_control()) {
+        return invalid("contains invalid characters");
+    }
+    if subfolder
+        .split('/')
+        .any(|segment| segment.is_empty() || segment == "." || segment == "..")
+    {
+        return invalid("must be a relative path without '.' or '..' segments");
+    }
+    // Catches drive prefixes and roots on the platform we run on
+    let path = Path::new(subfolder);
+    if path.is_absolute()
+        || !path
+            .components()
+            .all(|c| matches!(c, std::path::Component::Normal(_)))
+    {
+        return invalid("must stay inside the destination");
+    }
+
+    Ok(subfolder)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -738,4 +836,72 @@ mod tests {
         assert!(validate_job_id("job\\123").is_err());
         assert!(validate_job_id("job\0id").is_err());
     }
+
+    // ========== Rsync Environment Validation Tests ==========
+
+    #[test]
+    fn test_allowed_rsync_env_passes_sorted() {
+        let env = std::collections::HashMap::from([
+            ("LC_ALL".to_string(), "en_US.UTF-8".to_string()),
+            (
+                "RSYNC_PARTIAL_DIR".to_string(),
+                ".rsync-partial".to_string(),
+            ),
+            ("LANG".to_string(), "de_DE.UTF-8".to_string()),
+        ]);
+        let pairs = validate_rsync_env(&env).unwrap();
+        let names: Vec<&str> = pairs.iter().map(|(k, _)| k.as_str()).collect();
+        assert_eq!(names, vec!["LANG", "LC_ALL", "RSYNC_PARTIAL_DIR"]);
+    }
+
+    #[test]
+    fn test_dangerous_rsync_env_rejected() {
+        for name in [
+            "RSYNC_RSH",
+            "PATH",
+            "LD_PRELOAD",
+            "DYLD_INSERT_LIBRARIES",
+            "lang",
+        ] {
+            let env = std::collections::HashMap::from([(name.to_string(), "x".to_string())]);
+            assert!(validate_rsync_env(&env).is_err(), "{} accepted", name);
+        }
+
+        let env = std::collections::HashMap::from([(
+            "LANG".to_string(),
+            "C\nRSYNC_RSH=evil".to_string(),
+        )]);
+        assert!(validate_rsync_env(&env).is_err());
+    }
+
+    #[test]
+    fn test_rsync_argument_quoting_env_rejected() {
+        for name in ["RSYNC_OLD_ARGS", "RSYNC_PROTECT_ARGS"] {
+            let env = std::collections::HashMap::from([(name.to_string(), "1".to_string())]);
+            assert!(validate_rsync_env(&env).is_err(), "{} accepted", name);
+        }
+    }
+
+    #[test]
+    fn test_dest_subfolder_must_stay_inside_destination() {
+        for ok in ["Documents", "laptop/Documents", "nas.local/photos 2024"] {
+            assert_eq!(validate_dest_subfolder(ok).unwrap(), ok);
+        }
+
+        for bad in [
+            "",
+            "  ",
+            "..",
+            "../elsewhere",
+            "laptop/../../etc",
+            "./Documents",
+            "/etc",
+            "laptop//Documents",
+            "Documents/",
+            "..\\elsewhere",
+            "Docs\numents",
+        ] {
+            assert!(validate_dest_subfolder(bad).is_err(), "{:?} accepted", bad);
+        }
+    }
 }
@@ -5,6 +5,7 @@
 
 mod common;
 
+mod replicate_tests;
 mod restore_tests;
 mod rsync_tests;
 mod time_machine_tests;
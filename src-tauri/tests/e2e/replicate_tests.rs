@@ -0,0 +1,136 @@
+//! E2E tests for replicating a snapshot to a second destination
+
+use crate::common::{
+    rsync_available, run_rsync_backup,
+    test_common::{generate, verify, TestBackupEnv},
+};
+use app_lib::services::index_service::IndexService;
+use app_lib::services::{manifest_service, replication};
+use app_lib::types::manifest::{ManifestSnapshot, ManifestSnapshotStatus};
+use std::fs;
+
+fn skip_if_no_rsync() -> bool {
+    if !rsync_available() {
+        eprintln!("Skipping test: rsync not available");
+        return true;
+    }
+    false
+}
+
+#[tokio::test]
+async fn test_replicate_snapshot_copies_files_manifest_and_index() {
+    if skip_if_no_rsync() {
+        return;
+    }
+
+    let env = TestBackupEnv::new().unwrap();
+    generate::simple_backup_structure(&env.source_path).unwrap();
+    generate::file(&env.source_path.join("original.bin"), b"shared bytes").unwrap();
+    #[cfg(unix)]
+    fs::hard_link(
+        env.source_path.join("original.bin"),
+        env.source_path.join("hardlink.bin"),
+    )
+    .unwrap();
+
+    // A finished backup on the first drive: folder, manifest entry and index
+    let job_id = "replicate-job";
+    let ts = 1704110400000_i64;
+    let src_dest = env.dest_path.to_str().unwrap().to_string();
+    let snapshot = env.snapshot_path("source/2024-01-01-120000");
+    fs::create_dir_all(&snapshot).unwrap();
+    run_rsync_backup(&env.source_path, &snapshot).unwrap();
+
+    manifest_service::get_or_create_manifest(&src_dest, job_id, "Replicate", "/src")
+        .await
+        .unwrap();
+    let entry = ManifestSnapshot::from_timestamp(
+        ts,
+        "2024-01-01-120000".to_string(),
+        5,
+        100,
+        ManifestSnapshotStatus::Complete,
+    );
+    manifest_service::add_snapshot_to_manifest(&src_dest, entry)
+        .await
+        .unwrap();
+    let src_indexed = IndexService::for_destination(&src_dest)
+        .unwrap()
+        .index_snapshot(job_id, ts, snapshot.to_str().unwrap())
+        .unwrap();
+
+    let second_drive = env.temp_dir.path().join("second-drive");
+    fs::create_dir_all(&second_drive).unwrap();
+    let dst_dest = second_drive.to_str().unwrap();
+
+    let replicated = replication::replicate_snapshot(job_id, ts, &src_dest, dst_dest)
+        .await
+        .unwrap();
+
+    // Same relative location and identical contents
+    let replica = second_drive.join("source/2024-01-01-120000");
+    assert_eq!(
+        fs::canonicalize(&replicated.snapshot_path).unwrap(),
+        fs::canonicalize(&replica).unwrap()
+    );
+    let diff = verify::compare_directories(&snapshot, &replica).unwrap();
+    assert!(diff.is_identical(), "Replica differs: {:?}", diff);
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        let a = fs::metadata(replica.join("original.bin")).unwrap();
+        let b = fs::metadata(replica.join("hardlink.bin")).unwrap();
+        assert_eq!(a.ino(), b.ino(), "Hardlinks should survive replication");
+    }
+
+    // Manifest entry copied verbatim
+    let dst_manifest = manifest_service::read_manifest(dst_dest)
+        .await
+        .unwrap()
+        .expect("replica destination should have a manifest");
+    assert_eq!(dst_manifest.job_id, job_id);
+    let dst_entry = dst_manifest.get_snapshot(&ts.to_string()).unwrap();
+    assert_eq!(dst_entry.folder_name, "2024-01-01-120000");
+    assert_eq!(dst_entry.timestamp, ts);
+
+    // Index on the second drive matches the original
+    let dst_index = IndexService::for_destination(dst_dest).unwrap();
+    let snapshots = dst_index.list_snapshots(job_id).unwrap();
+    assert_eq!(snapshots.len(), 1);
+    assert_eq!(snapshots[0].timestamp, ts);
+    assert_eq!(snapshots[0].file_count, src_indexed.file_count);
+    assert_eq!(snapshots[0].total_size, src_indexed.total_size);
+
+    // Replicating again replaces rather than duplicates the entry
+    replication::replicate_snapshot(job_id, ts, &src_dest, dst_dest)
+        .await
+        .unwrap();
+    let dst_manifest = manifest_service::read_manifest(dst_dest)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(dst_manifest.snapshots.len(), 1);
+}
+
+#[tokio::test]
+async fn test_replicate_snapshot_rejects_unknown_timestamp() {
+    let env = TestBackupEnv::new().unwrap();
+    let src_dest = env.dest_path.to_str().unwrap();
+    manifest_service::get_or_create_manifest(src_dest, "replicate-job", "Replicate", "/src")
+        .await
+        .unwrap();
+
+    let second_drive = env.temp_dir.path().join("second-drive");
+    fs::create_dir_all(&second_drive).unwrap();
+
+    let result = replication::replicate_snapshot(
+        "replicate-job",
+        42,
+        src_dest,
+        second_drive.to_str().unwrap(),
+    )
+    .await;
+    assert!(result.is_err());
+    assert!(!manifest_service::manifest_exists(second_drive.to_str().unwrap()).await);
+}
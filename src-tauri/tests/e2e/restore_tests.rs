@@ -6,7 +6,11 @@ use crate::common::{
     rsync_available, rsync_restore_dir, rsync_restore_file, run_rsync_backup,
     test_common::{generate, verify, TestBackupEnv},
 };
+use app_lib::commands::snapshots::restore_files_args;
+use app_lib::services::index_service::IndexService;
 use std::fs;
+use std::io::Write;
+use std::process::{Command, Stdio};
 
 fn skip_if_no_rsync() -> bool {
     if !rsync_available() {
@@ -326,3 +330,56 @@ fn test_restore_empty_directory() {
     assert!(env.source_path.join("empty_dir").exists());
     assert!(env.source_path.join("empty_dir").is_dir());
 }
+
+#[test]
+fn test_restore_estimate_matches_restored_files() {
+    if skip_if_no_rsync() {
+        return;
+    }
+
+    let env = TestBackupEnv::new().unwrap();
+    let snapshot = env.snapshot_path("2024-01-01_120000");
+    generate::simple_backup_structure(&snapshot).unwrap();
+    generate::nested_dirs(&snapshot.join("deep"), 2, 3).unwrap();
+
+    let index = IndexService::for_destination(env.dest_path.to_str().unwrap()).unwrap();
+    index
+        .index_snapshot("test-job-id", 1704110400000, snapshot.to_str().unwrap())
+        .unwrap();
+
+    // A directory, a file inside it, a lone file and a nested tree
+    let selection: Vec<String> = ["documents", "documents/readme.txt", "code/main.rs", "deep"]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+    let estimate = index
+        .restore_estimate("test-job-id", 1704110400000, &selection)
+        .unwrap();
+
+    let target = env.temp_dir.path().join("restored");
+    fs::create_dir_all(&target).unwrap();
+    let mut child = Command::new("rsync")
+        .args(restore_files_args(
+            snapshot.to_string_lossy().to_string(),
+            target.to_string_lossy().to_string(),
+        ))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(selection.join("\0").as_bytes())
+        .unwrap();
+    assert!(child.wait().unwrap().success());
+
+    assert_eq!(
+        estimate.file_count,
+        verify::count_files(&target).unwrap() as u64
+    );
+    assert_eq!(estimate.total_bytes, verify::total_size(&target).unwrap());
+    assert!(target.join("deep").is_dir());
+    assert!(!target.join("code/lib.rs").exists());
+}
@@ -6,6 +6,7 @@ use crate::common::{
     rsync_available, run_rsync_backup, run_rsync_incremental,
     test_common::{generate, verify, TestBackupEnv},
 };
+use app_lib::services::exclude_preview;
 use std::fs;
 
 fn skip_if_no_rsync() -> bool {
@@ -299,3 +300,29 @@ fn test_rsync_fails_on_invalid_source() {
     let result = run_rsync_backup(&nonexistent, &env.dest_path);
     assert!(result.is_err(), "rsync should fail with invalid source");
 }
+
+#[test]
+fn test_preview_excludes_matches_pattern_effect() {
+    if skip_if_no_rsync() {
+        return;
+    }
+
+    // simple_backup fixture: code/, config.json, documents/
+    let env = TestBackupEnv::with_fixture("simple_backup").unwrap();
+    let source = env.source_path.to_str().unwrap();
+
+    let preview =
+        exclude_preview::preview_excludes(source, &["*.json".into(), "/code".into()]).unwrap();
+    assert_eq!(preview.total_entries, 3);
+    assert_eq!(preview.excluded, vec!["code", "config.json"]);
+    assert!(preview.warnings.is_empty());
+
+    // Nested-only pattern leaves the top level untouched
+    let preview = exclude_preview::preview_excludes(source, &["notes.md".into()]).unwrap();
+    assert!(preview.excluded.is_empty());
+
+    let preview = exclude_preview::preview_excludes(source, &["*".into()]).unwrap();
+    assert_eq!(preview.excluded.len(), 3);
+    assert_eq!(preview.warnings.len(), 1);
+    assert_eq!(preview.warnings[0].pattern, "*");
+}
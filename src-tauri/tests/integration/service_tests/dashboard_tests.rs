@@ -0,0 +1,167 @@
+//! Integration tests for the storage overview across jobs
+
+use crate::common::test_common::{generate, TestBackupEnv};
+use app_lib::services::dashboard;
+use app_lib::services::index_service::IndexService;
+use app_lib::services::manifest_service;
+use app_lib::types::job::{JobSchedule, SyncJob};
+use chrono::{TimeZone, Utc};
+
+// 2024-01-01-120000, 2024-01-02-120000
+const A_TIMESTAMPS: [i64; 2] = [1704110400000, 1704196800000];
+// 2024-01-03-120000
+const C_TIMESTAMP: i64 = 1704283200000;
+// 2024-01-04-120000
+const B_TIMESTAMP: i64 = 1704369600000;
+// 2024-01-04-180000
+const C_LAST_RUN: i64 = 1704391200000;
+// 2024-01-05-000000
+const NOW: i64 = 1704412800000;
+
+fn scheduled(id: &str, dest_path: &str, cron: &str) -> SyncJob {
+    SyncJob {
+        id: id.to_string(),
+        name: id.to_uppercase(),
+        dest_path: dest_path.to_string(),
+        schedule: Some(JobSchedule {
+            enabled: true,
+            cron: Some(cron.to_string()),
+            run_on_mount: None,
+        }),
+        ..SyncJob::default()
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn test_overview_aggregates_every_job() {
+    let env = TestBackupEnv::new().unwrap();
+    let local = IndexService::new(&env.temp_dir.path().join("app-data")).unwrap();
+
+    // job-a: hourly, two snapshots sharing a hardlinked 100-byte file, the
+    // second adding 10 bytes. Its last backup is well over an hour old.
+    let dest_a = env.dest_path.to_str().unwrap().to_string();
+    let first = env.snapshot_path("a-1");
+    let second = env.snapshot_path("a-2");
+    generate::file(&first.join("shared.bin"), &[b'a'; 100]).unwrap();
+    std::fs::create_dir_all(&second).unwrap();
+    std::fs::hard_link(first.join("shared.bin"), second.join("shared.bin")).unwrap();
+    generate::file(&second.join("new.bin"), &[b'b'; 10]).unwrap();
+    let index_a = IndexService::for_destination(&dest_a).unwrap();
+    index_a
+        .index_snapshot("job-a", A_TIMESTAMPS[0], first.to_str().unwrap())
+        .unwrap();
+    index_a
+        .index_snapshot("job-a", A_TIMESTAMPS[1], second.to_str().unwrap())
+        .unwrap();
+    let job_a = scheduled("job-a", &dest_a, "0 * * * *");
+
+    // job-b: daily at 03:00 on its own drive, backed up the day before
+    let dest_b = env.temp_dir.path().join("dest-b");
+    let snapshot_b = dest_b.join("b-1");
+    generate::file(&snapshot_b.join("file.bin"), &[b'c'; 50]).unwrap();
+    IndexService::for_destination(dest_b.to_str().unwrap())
+        .unwrap()
+        .index_snapshot("job-b", B_TIMESTAMP, snapshot_b.to_str().unwrap())
+        .unwrap();
+    let job_b = scheduled("job-b", dest_b.to_str().unwrap(), "0 3 * * *");
+
+    // job-c: drive unplugged, so it comes from the local index. Paused, and
+    // its last run is later than its one indexed snapshot.
+    let snapshot_c = env.temp_dir.path().join("c-1");
+    generate::file(&snapshot_c.join("file.bin"), &[b'd'; 30]).unwrap();
+    local
+        .index_snapshot("job-c", C_TIMESTAMP, snapshot_c.to_str().unwrap())
+        .unwrap();
+    let unplugged = env.temp_dir.path().join("unplugged");
+    let job_c = SyncJob {
+        enabled: false,
+        last_run: Some(C_LAST_RUN),
+        ..scheduled("job-c", unplugged.to_str().unwrap(), "0 * * * *")
+    };
+
+    let now = Utc.timestamp_millis_opt(NOW).unwrap();
+    let overview = dashboard::overview(&[job_a, job_b, job_c], &local, now);
+
+    assert_eq!(overview.total_jobs, 3);
+    assert_eq!(overview.total_snapshots, 4);
+    assert_eq!(overview.logical_bytes, 100 + (100 + 10) + 50 + 30);
+    assert_eq!(overview.physical_bytes, 100 + 10 + 50 + 30);
+    assert_eq!(overview.last_backup_ms, Some(C_LAST_RUN));
+    assert_eq!(overview.overdue_jobs, vec!["job-a".to_string()]);
+
+    let lines: Vec<_> = overview
+        .jobs
+        .iter()
+        .map(|j| {
+            (
+                j.job_id.as_str(),
+                j.snapshots,
+                j.logical_bytes,
+                j.physical_bytes,
+                j.last_backup_ms,
+                j.overdue,
+            )
+        })
+        .collect();
+    assert_eq!(
+        lines,
+        vec![
+            ("job-a", 2, 210, 110, Some(A_TIMESTAMPS[1]), true),
+            ("job-b", 1, 50, 50, Some(B_TIMESTAMP), false),
+            ("job-c", 1, 30, 30, Some(C_LAST_RUN), false),
+        ]
+    );
+    assert_eq!(overview.jobs[0].name, "JOB-A");
+    assert!(overview.jobs.iter().all(|j| j.error.is_none()));
+}
+
+#[test]
+fn test_unreadable_destination_index_falls_back_to_the_local_index() {
+    let env = TestBackupEnv::new().unwrap();
+    let local = IndexService::new(&env.temp_dir.path().join("app-data")).unwrap();
+
+    let snapshot = env.temp_dir.path().join("local-1");
+    generate::file(&snapshot.join("file.bin"), &[b'e'; 40]).unwrap();
+    local
+        .index_snapshot("job-bad", B_TIMESTAMP, snapshot.to_str().unwrap())
+        .unwrap();
+
+    // job-bad's drive is connected, but its index is garbage
+    let dest_bad = env.temp_dir.path().join("dest-bad");
+    let index_path = manifest_service::get_index_path(dest_bad.to_str().unwrap());
+    generate::file(&index_path, b"this is not an sqlite database").unwrap();
+    let job_bad = scheduled("job-bad", dest_bad.to_str().unwrap(), "0 3 * * *");
+    let job_ok = scheduled("job-ok", "/nowhere", "0 3 * * *");
+
+    let now = Utc.timestamp_millis_opt(NOW).unwrap();
+    let overview = dashboard::overview(&[job_bad, job_ok], &local, now);
+
+    assert_eq!(overview.total_jobs, 2);
+    assert_eq!(overview.total_snapshots, 1);
+    assert_eq!(overview.logical_bytes, 40);
+    let bad = &overview.jobs[0];
+    assert_eq!(bad.job_id, "job-bad");
+    assert_eq!(bad.snapshots, 1);
+    assert_eq!(bad.last_backup_ms, Some(B_TIMESTAMP));
+    assert!(bad.error.is_none());
+    assert_eq!(overview.jobs[1].job_id, "job-ok");
+}
+
+#[test]
+fn test_jobs_that_never_backed_up_are_not_overdue() {
+    let job = scheduled("job-new", "/nowhere", "* * * * *");
+    let now = Utc.timestamp_millis_opt(NOW).unwrap();
+    assert!(!dashboard::is_overdue(&job, None, now));
+    assert!(dashboard::is_overdue(&job, Some(NOW - 5 * 60 * 1000), now));
+
+    let env = TestBackupEnv::new().unwrap();
+    let local = IndexService::new(&env.temp_dir.path().join("app-data")).unwrap();
+    let overview = dashboard::overview(&[job], &local, now);
+    assert_eq!(overview.total_jobs, 1);
+    assert_eq!(overview.total_snapshots, 0);
+    assert_eq!(overview.logical_bytes, 0);
+    assert_eq!(overview.physical_bytes, 0);
+    assert_eq!(overview.last_backup_ms, None);
+    assert!(overview.overdue_jobs.is_empty());
+}
@@ -0,0 +1,58 @@
+//! Integration tests for the support benchmarks
+//!
+//! Runs them against a real destination index, as a user's support bundle would.
+
+use crate::common::test_common::{generate, TestBackupEnv};
+use app_lib::services::diagnostics;
+use app_lib::services::index_service::IndexService;
+use std::fs;
+
+#[test]
+fn test_index_benchmarks_report_sane_timings() {
+    let env = TestBackupEnv::new().unwrap();
+    let dest = env.dest_path.to_str().unwrap();
+
+    let snapshot_path = env.snapshot_path("2024-01-01_120000");
+    fs::create_dir_all(snapshot_path.join("docs")).unwrap();
+    generate::file(&snapshot_path.join("README.md"), b"readme").unwrap();
+    generate::file(&snapshot_path.join("docs/guide.txt"), b"guide").unwrap();
+    IndexService::for_destination(dest)
+        .unwrap()
+        .index_snapshot(
+            "test-job-id",
+            1704110400000,
+            snapshot_path.to_str().unwrap(),
+        )
+        .unwrap();
+
+    let results = diagnostics::run_index_benchmarks(dest).unwrap();
+    let operations: Vec<&str> = results.iter().map(|r| r.operation.as_str()).collect();
+    assert_eq!(
+        operations,
+        vec![
+            "list_snapshots",
+            "directory_contents",
+            "fts_search",
+            "snapshot_stats"
+        ]
+    );
+    for result in &results {
+        assert_eq!(result.iterations, diagnostics::SUPPORT_BENCH_ITERATIONS);
+        assert!(result.min_ms > 0.0, "{}: {:?}", result.operation, result);
+        assert!(result.min_ms <= result.avg_ms, "{:?}", result);
+        assert!(result.avg_ms <= result.max_ms, "{:?}", result);
+        assert!(result.total_ms >= result.max_ms, "{:?}", result);
+    }
+}
+
+#[test]
+fn test_index_benchmarks_need_an_existing_index() {
+    let env = TestBackupEnv::new().unwrap();
+    let dest = env.dest_path.to_str().unwrap();
+
+    assert!(diagnostics::run_index_benchmarks(dest).is_err());
+    assert!(
+        !app_lib::services::manifest_service::get_index_path(dest).exists(),
+        "benchmarks must not create an index"
+    );
+}
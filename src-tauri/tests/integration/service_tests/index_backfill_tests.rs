@@ -0,0 +1,222 @@
+//! Integration tests for indexing every unindexed snapshot on a destination
+
+use crate::common::test_common::{generate, TestBackupEnv};
+use app_lib::error::AmberError;
+use app_lib::services::cancel_token::{self, CancelRegistry, CancelToken};
+use app_lib::services::index_backfill::{self, BackfillProgress, SkippedFolder};
+use app_lib::services::index_service::IndexService;
+use app_lib::services::manifest_service;
+use app_lib::types::job::SyncJob;
+use app_lib::types::manifest::{ManifestSnapshot, ManifestSnapshotStatus};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+const JOB_ID: &str = "backfill-job";
+
+// 2024-01-01-120000 .. 2024-01-05-120000
+const TS_INDEXED: i64 = 1704110400000;
+const TS_RECORDED: i64 = 1704196800000;
+const TS_ORPHAN: i64 = 1704283200000;
+const TS_FAILED: i64 = 1704369600000;
+const TS_ACTIVE: i64 = 1704456000000;
+
+fn snapshot(env: &TestBackupEnv, name: &str) -> PathBuf {
+    let path = env.snapshot_path(&format!("src/{}", name));
+    generate::simple_backup_structure(&path).unwrap();
+    path
+}
+
+fn job(id: &str, env: &TestBackupEnv) -> SyncJob {
+    SyncJob {
+        id: id.to_string(),
+        source_path: "/src".to_string(),
+        dest_path: env.dest_path.to_str().unwrap().to_string(),
+        ..SyncJob::default()
+    }
+}
+
+async fn record(dest: &str, timestamp: i64, name: &str, status: ManifestSnapshotStatus) {
+    let entry = ManifestSnapshot::from_timestamp(timestamp, name.to_string(), 5, 100, status);
+    manifest_service::add_snapshot_to_manifest(dest, entry)
+        .await
+        .unwrap();
+}
+
+/// One folder per case, plus a directory that is not a snapshot at all
+async fn fixture(env: &TestBackupEnv) -> PathBuf {
+    let dest = env.dest_path.to_str().unwrap();
+    manifest_service::get_or_create_manifest(dest, JOB_ID, "Backfill", "/src")
+        .await
+        .unwrap();
+
+    let indexed = snapshot(env, "2024-01-01-120000");
+    record(
+        dest,
+        TS_INDEXED,
+        "2024-01-01-120000",
+        ManifestSnapshotStatus::Complete,
+    )
+    .await;
+    IndexService::for_destination(dest)
+        .unwrap()
+        .index_snapshot(JOB_ID, TS_INDEXED, indexed.to_str().unwrap())
+        .unwrap();
+
+    snapshot(env, "2024-01-02-120000");
+    record(
+        dest,
+        TS_RECORDED,
+        "2024-01-02-120000",
+        ManifestSnapshotStatus::Complete,
+    )
+    .await;
+    snapshot(env, "2024-01-03-120000");
+    snapshot(env, "2024-01-04-120000");
+    record(
+        dest,
+        TS_FAILED,
+        "2024-01-04-120000",
+        ManifestSnapshotStatus::Failed,
+    )
+    .await;
+    generate::file(
+        &env.snapshot_path("src/notes/readme.txt"),
+        b"not a snapshot",
+    )
+    .unwrap();
+
+    snapshot(env, "2024-01-05-120000")
+}
+
+#[tokio::test]
+async fn test_index_all_missing_indexes_only_unindexed_complete_folders() {
+    let env = TestBackupEnv::new().unwrap();
+    let active = fixture(&env).await;
+    let dest = env.dest_path.to_str().unwrap();
+
+    let seen = Arc::new(Mutex::new(Vec::<BackfillProgress>::new()));
+    let sink = seen.clone();
+    let report = index_backfill::index_all_missing(
+        &job(JOB_ID, &env),
+        Some(active),
+        CancelToken::new(),
+        move |p| sink.lock().unwrap().push(p.clone()),
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(report.indexed, vec![TS_RECORDED, TS_ORPHAN]);
+    assert_eq!(report.already_indexed, vec![TS_INDEXED]);
+    assert_eq!(
+        report.skipped,
+        vec![
+            SkippedFolder {
+                folder_name: "2024-01-04-120000".to_string(),
+                reason: "Snapshot is Failed".to_string(),
+            },
+            SkippedFolder {
+                folder_name: "2024-01-05-120000".to_string(),
+                reason: "Backup in progress".to_string(),
+            },
+        ]
+    );
+
+    let index = IndexService::for_destination(dest).unwrap();
+    for ts in [TS_INDEXED, TS_RECORDED, TS_ORPHAN] {
+        assert!(index.is_indexed(JOB_ID, ts).unwrap(), "{} not indexed", ts);
+    }
+    for ts in [TS_FAILED, TS_ACTIVE] {
+        assert!(!index.is_indexed(JOB_ID, ts).unwrap(), "{} indexed", ts);
+    }
+
+    let progress = seen.lock().unwrap().clone();
+    assert_eq!(progress.len(), 2);
+    assert_eq!(progress[0].folders_total, 2);
+    assert_eq!(progress[0].folder_name, "2024-01-02-120000");
+    assert_eq!(progress[1].folders_done, 1);
+
+    // Nothing left to do on a second pass
+    let again =
+        index_backfill::index_all_missing(&job(JOB_ID, &env), None, CancelToken::new(), |_| {})
+            .await
+            .unwrap();
+    assert!(again.indexed.contains(&TS_ACTIVE));
+    assert_eq!(again.indexed.len(), 1);
+    assert_eq!(
+        again.already_indexed,
+        vec![TS_INDEXED, TS_RECORDED, TS_ORPHAN]
+    );
+}
+
+#[tokio::test]
+async fn test_index_all_missing_cancel_keeps_finished_snapshots() {
+    let env = TestBackupEnv::new().unwrap();
+    let active = fixture(&env).await;
+    let dest = env.dest_path.to_str().unwrap();
+
+    // Cancel by operation id as soon as the first folder has been reported
+    let registry = Arc::new(CancelRegistry::new());
+    let op_id = cancel_token::operation_id(cancel_token::INDEX_BACKFILL, dest);
+    let cancel = registry.register(&op_id);
+    let canceller = registry.clone();
+    let cancelled_id = op_id.clone();
+    let result = index_backfill::index_all_missing(
+        &job(JOB_ID, &env),
+        Some(active),
+        cancel.clone(),
+        move |_| assert!(canceller.cancel(&cancelled_id)),
+    )
+    .await;
+    registry.unregister(&op_id, &cancel);
+
+    assert!(matches!(result, Err(AmberError::Cancelled)));
+    assert!(!registry.is_running(&op_id));
+    let index = IndexService::for_destination(dest).unwrap();
+    assert!(index.is_indexed(JOB_ID, TS_RECORDED).unwrap());
+    assert!(!index.is_indexed(JOB_ID, TS_ORPHAN).unwrap());
+}
+
+#[tokio::test]
+async fn test_index_all_missing_rejects_other_job() {
+    let env = TestBackupEnv::new().unwrap();
+    fixture(&env).await;
+
+    let result = index_backfill::index_all_missing(
+        &job("other-job", &env),
+        None,
+        CancelToken::new(),
+        |_| {},
+    )
+    .await;
+    assert!(result.is_err());
+}
+
+#[tokio::test]
+async fn test_index_all_missing_follows_two_level_subfolder_template() {
+    let env = TestBackupEnv::new().unwrap();
+    let dest = env.dest_path.to_str().unwrap();
+    let job = SyncJob {
+        source_path: "backup@nas:/volume1/photos".to_string(),
+        dest_subfolder: Some("{host}/{source_basename}".to_string()),
+        ..job(JOB_ID, &env)
+    };
+    manifest_service::get_or_create_manifest(dest, JOB_ID, "Backfill", &job.source_path)
+        .await
+        .unwrap();
+
+    // Backups land two levels down; a snapshot-named folder one level down
+    // belongs to nobody and must not be picked up
+    let deep = env.snapshot_path("nas/photos/2024-01-02-120000");
+    generate::simple_backup_structure(&deep).unwrap();
+    generate::simple_backup_structure(&env.snapshot_path("nas/2024-01-03-120000")).unwrap();
+
+    let report = index_backfill::index_all_missing(&job, None, CancelToken::new(), |_| {})
+        .await
+        .unwrap();
+    assert_eq!(report.indexed, vec![TS_RECORDED]);
+    assert!(report.skipped.is_empty());
+
+    let index = IndexService::for_destination(dest).unwrap();
+    assert!(index.is_indexed(JOB_ID, TS_RECORDED).unwrap());
+    assert!(!index.is_indexed(JOB_ID, TS_ORPHAN).unwrap());
+}
@@ -4,7 +4,15 @@
 //! These tests call REAL service methods to find actual bugs.
 
 use crate::common::test_common::{generate, TestBackupEnv};
-use app_lib::services::index_service::IndexService;
+use app_lib::error::AmberError;
+use app_lib::services::cancel_token::{self, CancelRegistry, CancelToken};
+use app_lib::services::index_migrations;
+use app_lib::services::index_service::{
+    DeletedFile, DiffCategory, DiffPageRequest, IndexService, IndexStorage, SortBy, SortField,
+};
+use app_lib::services::manifest_service;
+use app_lib::services::source_diff;
+use app_lib::types::snapshot::FileCategory;
 use std::fs;
 
 /// Helper function to create a test IndexService pointing to a temp destination
@@ -271,6 +279,160 @@ fn test_index_special_characters_in_paths() {
     );
 }
 
+#[cfg(unix)]
+#[test]
+fn test_hardlinks_group_by_inode() {
+    let env = TestBackupEnv::new().unwrap();
+    let snapshot_path = env.snapshot_path("2024-01-01_120000");
+
+    // Two linked pairs of different sizes (one across directories), a third
+    // link to the larger one, and an unlinked file
+    generate::file(&snapshot_path.join("big.bin"), &[7u8; 4096]).unwrap();
+    generate::file(&snapshot_path.join("small.txt"), b"small").unwrap();
+    generate::file(&snapshot_path.join("alone.txt"), b"alone").unwrap();
+    fs::create_dir_all(snapshot_path.join("copies")).unwrap();
+    fs::hard_link(
+        snapshot_path.join("big.bin"),
+        snapshot_path.join("copies/big-1.bin"),
+    )
+    .unwrap();
+    fs::hard_link(
+        snapshot_path.join("big.bin"),
+        snapshot_path.join("copies/big-2.bin"),
+    )
+    .unwrap();
+    fs::hard_link(
+        snapshot_path.join("small.txt"),
+        snapshot_path.join("small-link.txt"),
+    )
+    .unwrap();
+
+    let service = create_test_index(env.dest_path.to_str().unwrap());
+    service
+        .index_snapshot(
+            "test-job-id",
+            1704110400000,
+            snapshot_path.to_str().unwrap(),
+        )
+        .unwrap();
+
+    let groups = service
+        .get_hardlink_groups("test-job-id", 1704110400000)
+        .unwrap();
+    assert_eq!(groups.len(), 2);
+    let has_paths = |i: usize, expected: &[&str]| {
+        groups[i].paths.len() == expected.len()
+            && groups[i]
+                .paths
+                .iter()
+                .zip(expected)
+                .all(|(path, name)| path.ends_with(&format!("/{}", name)))
+    };
+    assert_eq!(groups[0].size, 4096);
+    assert!(
+        has_paths(0, &["big.bin", "copies/big-1.bin", "copies/big-2.bin"]),
+        "{:?}",
+        groups[0].paths
+    );
+    assert!(
+        has_paths(1, &["small-link.txt", "small.txt"]),
+        "{:?}",
+        groups[1].paths
+    );
+    assert_ne!(groups[0].inode, groups[1].inode);
+
+    // The listing badges linked files only
+    let listing = service
+        .get_directory_contents("test-job-id", 1704110400000, "")
+        .unwrap();
+    let link_count = |name: &str| listing.iter().find(|f| f.name == name).unwrap().link_count;
+    assert_eq!(link_count("big.bin"), Some(3));
+    assert_eq!(link_count("small.txt"), Some(2));
+    assert_eq!(link_count("alone.txt"), None);
+    assert_eq!(link_count("copies"), None);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_fifo_is_indexed_as_special() {
+    let env = TestBackupEnv::new().unwrap();
+    let snapshot_path = env.snapshot_path("2024-01-01_120000");
+    generate::file(&snapshot_path.join("data.txt"), b"regular").unwrap();
+    let status = std::process::Command::new("mkfifo")
+        .arg(snapshot_path.join("pipe"))
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let service = create_test_index(env.dest_path.to_str().unwrap());
+    let indexed = service
+        .index_snapshot(
+            "test-job-id",
+            1704110400000,
+            snapshot_path.to_str().unwrap(),
+        )
+        .unwrap();
+    // Not counted as a regular file
+    assert_eq!(indexed.file_count, 1);
+
+    let listing = service
+        .get_directory_contents("test-job-id", 1704110400000, "")
+        .unwrap();
+    let pipe = listing.iter().find(|f| f.name == "pipe").unwrap();
+    assert_eq!(pipe.node_type, "special");
+    assert_eq!(pipe.size, 0);
+    let data = listing.iter().find(|f| f.name == "data.txt").unwrap();
+    assert_eq!(data.node_type, "file");
+
+    // Restore skips it, and says so
+    let estimate = service
+        .restore_estimate(
+            "test-job-id",
+            1704110400000,
+            &["pipe".to_string(), "data.txt".to_string()],
+        )
+        .unwrap();
+    assert_eq!(estimate.file_count, 1);
+    assert_eq!(estimate.total_bytes, 7);
+    assert_eq!(estimate.skipped_special, 1);
+}
+
+#[test]
+fn test_index_too_large_aborts_without_a_partial_snapshot() {
+    let env = TestBackupEnv::new().unwrap();
+    let snapshot_path = env.snapshot_path("2024-01-01_120000");
+    for i in 0..5 {
+        generate::file(&snapshot_path.join(format!("file-{}.txt", i)), b"data").unwrap();
+    }
+
+    let service = create_test_index(env.dest_path.to_str().unwrap()).with_max_index_files(3);
+    let result = service.index_snapshot(
+        "test-job-id",
+        1704110400000,
+        snapshot_path.to_str().unwrap(),
+    );
+    match result {
+        Err(AmberError::IndexTooLarge { path, limit }) => {
+            assert_eq!(path, snapshot_path.to_str().unwrap());
+            assert_eq!(limit, 3);
+        }
+        other => panic!("expected IndexTooLarge, got {:?}", other),
+    }
+    assert!(!service.is_indexed("test-job-id", 1704110400000).unwrap());
+    assert!(service.list_snapshots("test-job-id").unwrap().is_empty());
+
+    // Right at the limit the snapshot indexes
+    let service = service.with_max_index_files(5);
+    let indexed = service
+        .index_snapshot(
+            "test-job-id",
+            1704110400000,
+            snapshot_path.to_str().unwrap(),
+        )
+        .unwrap();
+    assert_eq!(indexed.file_count, 5);
+}
+
 #[cfg(unix)]
 #[test]
 fn test_index_symlinks() {
@@ -376,6 +538,260 @@ fn test_index_is_idempotent() {
     );
 }
 
+#[test]
+fn test_cancelled_index_resumes_without_duplicates() {
+    let env = TestBackupEnv::new().unwrap();
+    let snapshot_path = env.snapshot_path("2024-01-01_120000");
+    generate::simple_backup_structure(&snapshot_path).unwrap();
+    for dir in ["a", "b/c", "d"] {
+        for i in 0..15 {
+            generate::file(
+                &snapshot_path.join(dir).join(format!("file_{:02}.txt", i)),
+                format!("{} {}", dir, i).as_bytes(),
+            )
+            .unwrap();
+        }
+    }
+    let path = snapshot_path.to_str().unwrap();
+    let ts = 1704110400000_i64;
+
+    let service = create_test_index(env.dest_path.to_str().unwrap()).with_resume_batch_rows(10);
+
+    // Cancel once two batches are committed
+    let cancel = CancelToken::new();
+    let mut batches = Vec::new();
+    let result = service.index_snapshot_resumable("test-job-id", ts, path, &cancel, |done, _| {
+        batches.push(done);
+        if batches.len() == 2 {
+            cancel.cancel();
+        }
+    });
+    assert!(matches!(result, Err(AmberError::Cancelled)));
+    assert_eq!(batches, vec![10, 20]);
+
+    // The partial snapshot is kept but not reported as indexed
+    assert!(!service.is_indexed("test-job-id", ts).unwrap());
+    assert!(service.list_snapshots("test-job-id").unwrap().is_empty());
+    assert_eq!(row_count(&service, "files"), 20);
+
+    // Restarting picks up after the committed batches
+    let cancel = CancelToken::new();
+    let mut resumed = Vec::new();
+    let indexed = service
+        .index_snapshot_resumable("test-job-id", ts, path, &cancel, |done, total| {
+            resumed.push((done, total))
+        })
+        .unwrap();
+    let (first_done, total) = resumed[0];
+    assert_eq!(first_done, 30);
+    assert_eq!(resumed.last().unwrap(), &(total, total));
+    assert!(service.is_indexed("test-job-id", ts).unwrap());
+
+    // Same rows as indexing it in one go, each exactly once
+    let full_dir = env.temp_dir.path().join("full-index");
+    fs::create_dir_all(&full_dir).unwrap();
+    let full = IndexService::new(&full_dir).unwrap();
+    let expected = full.index_snapshot("test-job-id", ts, path).unwrap();
+    assert_eq!(indexed.file_count, expected.file_count);
+    assert_eq!(indexed.total_size, expected.total_size);
+    assert_eq!(row_count(&service, "files"), total as i64);
+
+    let rows = |service: &IndexService| {
+        let conn = rusqlite::Connection::open(service.get_db_path()).unwrap();
+        let mut stmt = conn
+            .prepare(
+                "SELECT parent_path, name, size, file_type FROM files
+                 ORDER BY parent_path, name",
+            )
+            .unwrap();
+        stmt.query_map([], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, i64>(2)?,
+                row.get::<_, String>(3)?,
+            ))
+        })
+        .unwrap()
+        .map(|r| r.unwrap())
+        .collect::<Vec<_>>()
+    };
+    let resumed_rows = rows(&service);
+    let mut unique = resumed_rows.clone();
+    unique.dedup();
+    assert_eq!(unique.len(), resumed_rows.len());
+    assert_eq!(resumed_rows, rows(&full));
+    assert_eq!(
+        service.list_snapshots("test-job-id").unwrap()[0].file_count,
+        expected.file_count
+    );
+
+    // Running it again on a complete snapshot starts over cleanly
+    service
+        .index_snapshot_resumable("test-job-id", ts, path, &cancel, |_, _| {})
+        .unwrap();
+    assert_eq!(rows(&service), resumed_rows);
+}
+
+#[test]
+fn test_plain_cancellable_index_resumes_a_partial_one() {
+    let env = TestBackupEnv::new().unwrap();
+    let snapshot_path = env.snapshot_path("2024-01-01_120000");
+    for i in 0..25 {
+        generate::file(
+            &snapshot_path.join(format!("file_{:02}.txt", i)),
+            format!("{}", i).as_bytes(),
+        )
+        .unwrap();
+    }
+    let path = snapshot_path.to_str().unwrap();
+    let ts = 1704110400000_i64;
+    let service = create_test_index(env.dest_path.to_str().unwrap()).with_resume_batch_rows(10);
+
+    let cancel = CancelToken::new();
+    let result = service.index_snapshot_resumable("test-job-id", ts, path, &cancel, |_, _| {
+        cancel.cancel();
+    });
+    assert!(matches!(result, Err(AmberError::Cancelled)));
+    assert!(!service.is_indexed("test-job-id", ts).unwrap());
+    assert_eq!(row_count(&service, "files"), 10);
+
+    // What the index command runs without force or hashing
+    let indexed = service
+        .index_snapshot_cancellable("test-job-id", ts, path, false, false, &CancelToken::new())
+        .unwrap();
+    assert_eq!(indexed.file_count, 25);
+    assert!(service.is_indexed("test-job-id", ts).unwrap());
+    assert_eq!(row_count(&service, "files"), 25);
+}
+
+#[test]
+fn test_cancelled_index_keeps_the_previous_index() {
+    let env = TestBackupEnv::new().unwrap();
+    let snapshot_path = env.snapshot_path("2024-01-01_120000");
+    generate::simple_backup_structure(&snapshot_path).unwrap();
+    let path = snapshot_path.to_str().unwrap();
+    let ts = 1704110400000_i64;
+    let service = create_test_index(env.dest_path.to_str().unwrap());
+    let before = service.index_snapshot("test-job-id", ts, path).unwrap();
+    generate::file(&snapshot_path.join("added.txt"), b"new").unwrap();
+
+    let operations = CancelRegistry::new();
+    let op_id = cancel_token::operation_id(cancel_token::INDEX, &format!("test-job-id:{}", ts));
+    assert_eq!(op_id, format!("index:test-job-id:{}", ts));
+    let cancel = operations.register(&op_id);
+    assert!(operations.cancel(&op_id));
+
+    let result = service.index_snapshot_cancellable("test-job-id", ts, path, true, true, &cancel);
+    assert!(matches!(result, Err(AmberError::Cancelled)));
+    let snapshots = service.list_snapshots("test-job-id").unwrap();
+    assert_eq!(snapshots.len(), 1);
+    assert_eq!(snapshots[0].file_count, before.file_count);
+
+    let indexed = service
+        .index_snapshot_cancellable("test-job-id", ts, path, true, false, &CancelToken::new())
+        .unwrap();
+    assert_eq!(indexed.file_count, before.file_count + 1);
+}
+
+#[test]
+fn test_index_refuses_tree_containing_index_dir() {
+    let env = TestBackupEnv::new().unwrap();
+
+    // create_test_index's .index-data sits directly in the destination root
+    generate::file(&env.dest_path.join("docs/a.txt"), b"alpha").unwrap();
+    let service = create_test_index(env.dest_path.to_str().unwrap());
+    let result = service.index_snapshot(
+        "test-job-id",
+        1704110400000,
+        env.dest_path.to_str().unwrap(),
+    );
+    assert!(
+        matches!(result, Err(AmberError::InvalidPath(_))),
+        "Indexing the parent of the open DB's directory must fail, got {:?}",
+        result
+    );
+
+    // Deeper, under an arbitrary (non-reserved) name, it is refused too
+    let custom_dir = env.dest_path.join("nested/db-store");
+    fs::create_dir_all(&custom_dir).unwrap();
+    let custom = IndexService::new(&custom_dir).unwrap();
+    let result = custom.index_snapshot(
+        "test-job-id",
+        1704110400000,
+        env.dest_path.to_str().unwrap(),
+    );
+    assert!(matches!(result, Err(AmberError::InvalidPath(_))));
+    assert!(!service.is_indexed("test-job-id", 1704110400000).unwrap());
+    assert!(!custom.is_indexed("test-job-id", 1704110400000).unwrap());
+
+    // A sibling folder that doesn't contain it indexes as usual
+    let indexed = service
+        .index_snapshot(
+            "test-job-id",
+            1704110400000,
+            env.dest_path.join("docs").to_str().unwrap(),
+        )
+        .unwrap();
+    assert_eq!(indexed.file_count, 1);
+}
+
+#[test]
+fn test_index_refuses_index_dir_as_snapshot_path() {
+    let env = TestBackupEnv::new().unwrap();
+    let service = create_test_index(env.dest_path.to_str().unwrap());
+    let index_dir = env.dest_path.join(".index-data");
+
+    let result = service.index_snapshot("test-job-id", 1704110400000, index_dir.to_str().unwrap());
+    assert!(
+        result.is_err(),
+        "Indexing the open DB's directory must fail"
+    );
+    assert!(!service.is_indexed("test-job-id", 1704110400000).unwrap());
+}
+
+#[test]
+fn test_index_rejects_timestamp_collision_with_other_folder() {
+    let env = TestBackupEnv::new().unwrap();
+    let first = env.snapshot_path("2024-01-01-120000");
+    let second = env.snapshot_path("2024-01-01-120000-copy");
+    fs::create_dir_all(&first).unwrap();
+    fs::create_dir_all(&second).unwrap();
+    generate::file(&first.join("a.txt"), b"first").unwrap();
+    generate::file(&second.join("b.txt"), b"second snapshot").unwrap();
+    generate::file(&second.join("c.txt"), b"more").unwrap();
+
+    let service = create_test_index(env.dest_path.to_str().unwrap());
+    let ts = 1704110400000;
+    service
+        .index_snapshot("test-job-id", ts, first.to_str().unwrap())
+        .unwrap();
+
+    // Re-indexing the same folder (even spelled with a trailing slash) is fine
+    service
+        .index_snapshot("test-job-id", ts, &format!("{}/", first.display()))
+        .unwrap();
+
+    let result = service.index_snapshot("test-job-id", ts, second.to_str().unwrap());
+    assert!(
+        result.is_err(),
+        "A different folder at the same timestamp must be refused"
+    );
+    assert_eq!(
+        service.get_snapshot_stats("test-job-id", ts).unwrap(),
+        (1, 5)
+    );
+
+    let replaced = service
+        .index_snapshot_force_replace("test-job-id", ts, second.to_str().unwrap())
+        .unwrap();
+    assert_eq!(replaced.file_count, 2);
+    assert_eq!(
+        service.get_snapshot_stats("test-job-id", ts).unwrap(),
+        (2, 19)
+    );
+}
+
 // ============================================================================
 // Additional Edge Case Tests
 // ============================================================================
@@ -796,6 +1212,50 @@ fn test_compare_modified_files() {
     assert_eq!(diff.summary.size_delta, expected_delta);
 }
 
+#[test]
+fn test_compare_same_size_edit_needs_hashes() {
+    let env = TestBackupEnv::new().unwrap();
+    let snapshot_path = env.snapshot_path("2024-01-01_120000");
+    let snapshot = snapshot_path.to_str().unwrap();
+    generate::file(&snapshot_path.join("edited.txt"), b"version one").unwrap();
+    generate::file(&snapshot_path.join("grown.txt"), b"short").unwrap();
+    generate::file(&snapshot_path.join("same.txt"), b"untouched").unwrap();
+
+    let service = create_test_index(env.dest_path.to_str().unwrap());
+    let (ts_a, ts_b, ts_c) = (1704110400000_i64, 1704196800000_i64, 1704283200000_i64);
+    service
+        .index_snapshot_hashed("test-job-id", ts_a, snapshot, false)
+        .unwrap();
+
+    // Same length, different bytes
+    generate::file(&snapshot_path.join("edited.txt"), b"version two").unwrap();
+    generate::file(&snapshot_path.join("grown.txt"), b"much longer").unwrap();
+
+    // The fast path has no hashes to compare, so only the size change shows
+    service
+        .index_snapshot("test-job-id", ts_b, snapshot)
+        .unwrap();
+    let diff = service
+        .compare_snapshots("test-job-id", ts_a, ts_b, None)
+        .unwrap();
+    let paths: Vec<&str> = diff.modified.iter().map(|e| e.path.as_str()).collect();
+    assert_eq!(paths, vec!["grown.txt"]);
+    assert_eq!(diff.summary.content_changed, 0);
+
+    service
+        .index_snapshot_hashed("test-job-id", ts_c, snapshot, false)
+        .unwrap();
+    let diff = service
+        .compare_snapshots("test-job-id", ts_a, ts_c, None)
+        .unwrap();
+    let paths: Vec<&str> = diff.modified.iter().map(|e| e.path.as_str()).collect();
+    assert_eq!(paths, vec!["edited.txt", "grown.txt"]);
+    assert_eq!(diff.modified[0].size_a, diff.modified[0].size_b);
+    assert_eq!(diff.summary.total_modified, 2);
+    assert_eq!(diff.summary.content_changed, 1);
+    assert_eq!(diff.summary.size_delta, 6);
+}
+
 #[test]
 fn test_compare_mixed_changes() {
     let env = TestBackupEnv::new().unwrap();
@@ -852,6 +1312,65 @@ fn test_compare_mixed_changes() {
         .any(|f| f.path.contains("modified.txt")));
 }
 
+#[test]
+fn test_list_deleted_between_returns_only_deleted_files() {
+    let env = TestBackupEnv::new().unwrap();
+    let good = env.snapshot_path("2024-01-01_120000");
+    generate::file(&good.join("keep.txt"), b"same").unwrap();
+    generate::file(&good.join("gone.txt"), b"deleted by accident").unwrap();
+    generate::file(&good.join("docs/report.pdf"), b"quarterly numbers").unwrap();
+    generate::file(&good.join("docs/notes.md"), b"short").unwrap();
+    generate::file(&good.join("docs/stays.md"), b"stays").unwrap();
+    let now = env.snapshot_path("2024-01-02_120000");
+    generate::file(&now.join("keep.txt"), b"same").unwrap();
+    generate::file(&now.join("docs/notes.md"), b"much longer now").unwrap();
+    generate::file(&now.join("docs/stays.md"), b"stays").unwrap();
+    generate::file(&now.join("added.txt"), b"newly added").unwrap();
+
+    let service = create_test_index(env.dest_path.to_str().unwrap());
+    let ts_good = 1704110400000_i64;
+    let ts_now = 1704196800000_i64;
+    service
+        .index_snapshot("test-job-id", ts_good, good.to_str().unwrap())
+        .unwrap();
+    service
+        .index_snapshot("test-job-id", ts_now, now.to_str().unwrap())
+        .unwrap();
+
+    let deleted = service
+        .list_deleted_between("test-job-id", ts_good, ts_now)
+        .unwrap();
+    assert_eq!(deleted.snapshot_path, good.to_str().unwrap());
+    assert_eq!(
+        deleted.files,
+        vec![
+            DeletedFile {
+                path: "docs/report.pdf".to_string(),
+                size: 17,
+            },
+            DeletedFile {
+                path: "gone.txt".to_string(),
+                size: 19,
+            },
+        ]
+    );
+    assert_eq!(deleted.total_bytes, 36);
+
+    // The paths are where the files sit in the good snapshot
+    for file in &deleted.files {
+        assert!(std::path::Path::new(&deleted.snapshot_path)
+            .join(&file.path)
+            .is_file());
+    }
+
+    // A snapshot compared with itself has nothing deleted
+    assert!(service
+        .list_deleted_between("test-job-id", ts_now, ts_now)
+        .unwrap()
+        .files
+        .is_empty());
+}
+
 #[test]
 fn test_compare_empty_vs_populated() {
     let env = TestBackupEnv::new().unwrap();
@@ -1047,6 +1566,89 @@ fn test_compare_with_limit() {
     );
 }
 
+#[test]
+fn test_compare_truncation_flags_and_paging() {
+    let env = TestBackupEnv::new().unwrap();
+    let snap_a = env.snapshot_path("2024-01-01_120000");
+    let snap_b = env.snapshot_path("2024-01-02_120000");
+    generate::file(&snap_a.join("gone.txt"), b"bye").unwrap();
+    for i in 0..10 {
+        generate::file(&snap_b.join(format!("docs/file_{:02}.txt", i)), b"new").unwrap();
+    }
+
+    let service = create_test_index(env.dest_path.to_str().unwrap());
+    let (ts_a, ts_b) = (1704110400000_i64, 1704196800000_i64);
+    service
+        .index_snapshot("test-job-id", ts_a, snap_a.to_str().unwrap())
+        .unwrap();
+    service
+        .index_snapshot("test-job-id", ts_b, snap_b.to_str().unwrap())
+        .unwrap();
+
+    let diff = service
+        .compare_snapshots("test-job-id", ts_a, ts_b, Some(3))
+        .unwrap();
+    assert_eq!(diff.added.len(), 3);
+    assert!(diff.truncated.added);
+    assert!(!diff.truncated.deleted);
+    assert!(!diff.truncated.modified);
+    assert_eq!(
+        diff.summary.total_added, 10,
+        "Summary carries the full total"
+    );
+    assert_eq!(diff.summary.total_deleted, 1);
+    assert_eq!(diff.summary.size_delta, 10 * 3 - 3);
+
+    // Page through the added list 4 at a time
+    let mut seen = Vec::new();
+    let mut offset = 0;
+    loop {
+        let page = service
+            .compare_snapshots_page(
+                "test-job-id",
+                ts_a,
+                ts_b,
+                None,
+                &[],
+                DiffPageRequest {
+                    category: DiffCategory::Added,
+                    offset,
+                    limit: 4,
+                },
+            )
+            .unwrap();
+        assert_eq!(page.total, 10);
+        assert_eq!(page.offset, offset);
+        offset += page.entries.len();
+        seen.extend(page.entries.into_iter().map(|e| e.path));
+        if !page.has_more {
+            break;
+        }
+    }
+    let expected: Vec<String> = (0..10).map(|i| format!("docs/file_{:02}.txt", i)).collect();
+    assert_eq!(
+        seen, expected,
+        "Every added file exactly once, in path order"
+    );
+
+    let past_end = service
+        .compare_snapshots_page(
+            "test-job-id",
+            ts_a,
+            ts_b,
+            None,
+            &[],
+            DiffPageRequest {
+                category: DiffCategory::Added,
+                offset: 20,
+                limit: 4,
+            },
+        )
+        .unwrap();
+    assert!(past_end.entries.is_empty());
+    assert!(!past_end.has_more);
+}
+
 #[test]
 fn test_compare_unicode_paths() {
     let env = TestBackupEnv::new().unwrap();
@@ -1176,44 +1778,783 @@ fn test_compare_deeply_nested_changes() {
     );
 }
 
-// ============================================================================
-// SEARCH TESTS AND EDGE CASES
-// ============================================================================
-
 #[test]
-fn test_search_exact_filename() {
+fn test_compare_snapshots_under_subtree() {
     let env = TestBackupEnv::new().unwrap();
 
-    // Create snapshot with distinctly named files
     let snapshot_path = env.snapshot_path("2024-01-01_120000");
-    fs::create_dir_all(&snapshot_path).unwrap();
+    let photos = snapshot_path.join("photos");
+    let nested = photos.join("2024");
+    // Sibling whose name shares the "photos" prefix must not leak into the subtree
+    let lookalike = snapshot_path.join("photos_backup");
+    fs::create_dir_all(&nested).unwrap();
+    fs::create_dir_all(&lookalike).unwrap();
 
-    generate::file(&snapshot_path.join("unique_target.txt"), b"target content").unwrap();
-    generate::file(&snapshot_path.join("other_file.txt"), b"other content").unwrap();
-    generate::file(&snapshot_path.join("another.rs"), b"rust code").unwrap();
+    generate::file(&photos.join("cover.jpg"), b"cover").unwrap();
+    generate::file(&snapshot_path.join("notes.txt"), b"notes").unwrap();
 
     let service = create_test_index(env.dest_path.to_str().unwrap());
+    let ts_a = 1704110400000_i64;
+    let ts_b = 1704196800000_i64;
 
     service
-        .index_snapshot(
-            "test-job-id",
-            1704110400000,
-            snapshot_path.to_str().unwrap(),
-        )
+        .index_snapshot("test-job-id", ts_a, snapshot_path.to_str().unwrap())
         .unwrap();
 
-    // Search for exact filename
-    let results = service
-        .search_files("test-job-id", 1704110400000, "unique_target", 100)
+    // Changes inside the subtree
+    generate::file(&nested.join("beach.jpg"), b"new photo").unwrap();
+    generate::file(&photos.join("cover.jpg"), b"a larger cover image").unwrap();
+    // Changes outside it
+    generate::file(&snapshot_path.join("notes.txt"), b"edited notes, longer").unwrap();
+    generate::file(&lookalike.join("copy.jpg"), b"copy").unwrap();
+
+    service
+        .index_snapshot("test-job-id", ts_b, snapshot_path.to_str().unwrap())
         .unwrap();
 
-    assert_eq!(
-        results.len(),
-        1,
-        "Should find exactly one file with exact name match"
-    );
-    assert_eq!(results[0].name, "unique_target.txt");
-}
+    let diff = service
+        .compare_snapshots_under("test-job-id", ts_a, ts_b, Some("/photos/"), &[], None)
+        .unwrap();
+
+    let added: Vec<_> = diff.added.iter().map(|e| e.path.as_str()).collect();
+    let modified: Vec<_> = diff.modified.iter().map(|e| e.path.as_str()).collect();
+    assert_eq!(added, vec!["photos/2024/beach.jpg"]);
+    assert_eq!(modified, vec!["photos/cover.jpg"]);
+    assert!(diff.deleted.is_empty());
+
+    // Whole-snapshot diff still sees the outside changes
+    let full = service
+        .compare_snapshots("test-job-id", ts_a, ts_b, None)
+        .unwrap();
+    assert_eq!(full.added.len(), 2);
+    assert_eq!(full.modified.len(), 2);
+
+    // Escaping the snapshot root is rejected
+    assert!(service
+        .compare_snapshots_under("test-job-id", ts_a, ts_b, Some("../etc"), &[], None)
+        .is_err());
+}
+
+#[test]
+fn test_size_contributors_rank_the_grown_image_first() {
+    let env = TestBackupEnv::new().unwrap();
+
+    let snapshot_path = env.snapshot_path("2024-01-01_120000");
+    let vms = snapshot_path.join("vms");
+    fs::create_dir_all(&vms).unwrap();
+
+    generate::file(&vms.join("disk.img"), &[0u8; 4096]).unwrap();
+    generate::file(&snapshot_path.join("todo.txt"), b"milk").unwrap();
+    generate::file(&snapshot_path.join("old.txt"), b"gone soon").unwrap();
+
+    let service = create_test_index(env.dest_path.to_str().unwrap());
+    let ts_a = 1704110400000_i64;
+    let ts_b = 1704196800000_i64;
+
+    service
+        .index_snapshot("test-job-id", ts_a, snapshot_path.to_str().unwrap())
+        .unwrap();
+
+    // One big growth among small edits, an addition and a deletion
+    generate::file(&vms.join("disk.img"), &[0u8; 64 * 1024]).unwrap();
+    generate::file(&snapshot_path.join("todo.txt"), b"milk, eggs").unwrap();
+    generate::file(&snapshot_path.join("new.txt"), b"hello").unwrap();
+    fs::remove_file(snapshot_path.join("old.txt")).unwrap();
+
+    service
+        .index_snapshot("test-job-id", ts_b, snapshot_path.to_str().unwrap())
+        .unwrap();
+
+    let top = service
+        .size_contributors("test-job-id", ts_a, ts_b, None, &[], 3)
+        .unwrap();
+    let ranked: Vec<_> = top.iter().map(|e| e.path.as_str()).collect();
+    assert_eq!(ranked, vec!["vms/disk.img", "old.txt", "todo.txt"]);
+    assert_eq!(top[0].size_a, Some(4096));
+    assert_eq!(top[0].size_b, Some(64 * 1024));
+
+    // The glob path ranks the same way
+    let filtered = service
+        .size_contributors("test-job-id", ts_a, ts_b, None, &["*.txt".to_string()], 3)
+        .unwrap();
+    let ranked: Vec<_> = filtered.iter().map(|e| e.path.as_str()).collect();
+    assert_eq!(ranked, vec!["vms/disk.img"]);
+
+    let diff = service
+        .compare_snapshots("test-job-id", ts_a, ts_b, None)
+        .unwrap();
+    assert!(diff.top_contributors.is_empty());
+}
+
+#[test]
+fn test_compare_snapshots_ignore_globs() {
+    let env = TestBackupEnv::new().unwrap();
+
+    let snapshot_path = env.snapshot_path("2024-01-01_120000");
+    let logs = snapshot_path.join("logs");
+    let cache = snapshot_path.join("app/.cache");
+    fs::create_dir_all(&logs).unwrap();
+    fs::create_dir_all(&cache).unwrap();
+
+    generate::file(&logs.join("old.log"), b"old").unwrap();
+    generate::file(&cache.join("blob"), b"cached").unwrap();
+    generate::file(&snapshot_path.join("report.txt"), b"report").unwrap();
+    generate::file(&snapshot_path.join("app/main.rs"), b"fn main() {}").unwrap();
+
+    let service = create_test_index(env.dest_path.to_str().unwrap());
+    let ts_a = 1704110400000_i64;
+    let ts_b = 1704196800000_i64;
+
+    service
+        .index_snapshot("test-job-id", ts_a, snapshot_path.to_str().unwrap())
+        .unwrap();
+
+    // Churn in the noisy folders
+    fs::remove_file(logs.join("old.log")).unwrap();
+    for i in 0..20 {
+        generate::file(&logs.join(format!("run_{:02}.log", i)), b"log line").unwrap();
+    }
+    generate::file(&cache.join("blob"), b"cached and grown").unwrap();
+    // Real changes elsewhere
+    generate::file(&snapshot_path.join("report.txt"), b"report, revised").unwrap();
+    generate::file(&snapshot_path.join("app/lib.rs"), b"pub fn lib() {}").unwrap();
+    fs::remove_file(snapshot_path.join("app/main.rs")).unwrap();
+
+    service
+        .index_snapshot("test-job-id", ts_b, snapshot_path.to_str().unwrap())
+        .unwrap();
+
+    let noisy = service
+        .compare_snapshots("test-job-id", ts_a, ts_b, None)
+        .unwrap();
+    assert_eq!(noisy.summary.total_added, 21);
+
+    let ignore = vec!["logs".to_string(), "**/.cache".to_string()];
+    let diff = service
+        .compare_snapshots_under("test-job-id", ts_a, ts_b, None, &ignore, None)
+        .unwrap();
+
+    let added: Vec<_> = diff.added.iter().map(|e| e.path.as_str()).collect();
+    let deleted: Vec<_> = diff.deleted.iter().map(|e| e.path.as_str()).collect();
+    let modified: Vec<_> = diff.modified.iter().map(|e| e.path.as_str()).collect();
+    assert_eq!(added, vec!["app/lib.rs"]);
+    assert_eq!(deleted, vec!["app/main.rs"]);
+    assert_eq!(modified, vec!["report.txt"]);
+    assert_eq!(diff.summary.total_added, 1);
+    assert_eq!(diff.summary.total_deleted, 1);
+    assert_eq!(diff.summary.total_modified, 1);
+    assert_eq!(
+        diff.summary.size_delta,
+        ("pub fn lib() {}".len() + "report, revised".len()) as i64
+            - ("fn main() {}".len() + "report".len()) as i64
+    );
+
+    // Pages agree with the filtered summary
+    let page = service
+        .compare_snapshots_page(
+            "test-job-id",
+            ts_a,
+            ts_b,
+            None,
+            &ignore,
+            DiffPageRequest {
+                category: DiffCategory::Added,
+                offset: 0,
+                limit: 10,
+            },
+        )
+        .unwrap();
+    assert_eq!(page.total, 1);
+    assert!(!page.has_more);
+
+    // `*` does not cross folders, so this leaves app/.cache/blob in the diff
+    let shallow = service
+        .compare_snapshots_under(
+            "test-job-id",
+            ts_a,
+            ts_b,
+            None,
+            &[
+                "*.log".to_string(),
+                "logs/*".to_string(),
+                "*/blob".to_string(),
+            ],
+            None,
+        )
+        .unwrap();
+    assert_eq!(shallow.summary.total_added, 1);
+    assert_eq!(shallow.summary.total_modified, 2);
+
+    assert!(matches!(
+        service.compare_snapshots_under("test-job-id", ts_a, ts_b, None, &["[".to_string()], None),
+        Err(AmberError::ValidationError(_))
+    ));
+}
+
+#[test]
+fn test_compare_snapshots_each_matches_collected() {
+    let env = TestBackupEnv::new().unwrap();
+
+    let snapshot_path = env.snapshot_path("2024-01-01_120000");
+    for dir in ["docs", "photos/2023", "logs"] {
+        fs::create_dir_all(snapshot_path.join(dir)).unwrap();
+    }
+    for i in 0..40 {
+        generate::file(
+            &snapshot_path.join(format!("docs/doc_{:02}.txt", i)),
+            format!("doc {}", i).as_bytes(),
+        )
+        .unwrap();
+    }
+    generate::file(&snapshot_path.join("photos/2023/beach.jpg"), b"jpeg").unwrap();
+    generate::file(&snapshot_path.join("logs/old.log"), b"log").unwrap();
+
+    let service = create_test_index(env.dest_path.to_str().unwrap());
+    let ts_a = 1704110400000_i64;
+    let ts_b = 1704196800000_i64;
+    service
+        .index_snapshot("test-job-id", ts_a, snapshot_path.to_str().unwrap())
+        .unwrap();
+
+    for i in 0..10 {
+        fs::remove_file(snapshot_path.join(format!("docs/doc_{:02}.txt", i))).unwrap();
+    }
+    for i in 10..25 {
+        generate::file(
+            &snapshot_path.join(format!("docs/doc_{:02}.txt", i)),
+            format!("doc {} revised", i).as_bytes(),
+        )
+        .unwrap();
+    }
+    for i in 0..30 {
+        generate::file(
+            &snapshot_path.join(format!("photos/2023/img_{:02}.jpg", i)),
+            format!("new photo {}", i).as_bytes(),
+        )
+        .unwrap();
+    }
+    generate::file(&snapshot_path.join("logs/new.log"), b"fresh log line").unwrap();
+
+    service
+        .index_snapshot("test-job-id", ts_b, snapshot_path.to_str().unwrap())
+        .unwrap();
+
+    let as_tuples = |entries: &[app_lib::services::index_service::DiffEntry]| {
+        entries
+            .iter()
+            .map(|e| (e.path.clone(), e.size_a, e.size_b))
+            .collect::<Vec<_>>()
+    };
+
+    let cases: [(Option<&str>, Vec<String>); 3] = [
+        (None, vec![]),
+        (None, vec!["logs".to_string()]),
+        (Some("photos"), vec![]),
+    ];
+    for (under, ignore) in cases {
+        let collected = service
+            .compare_snapshots_under("test-job-id", ts_a, ts_b, under, &ignore, None)
+            .unwrap();
+        assert!(collected.renamed.is_empty());
+
+        let mut streamed = [Vec::new(), Vec::new(), Vec::new()];
+        let delivered = service
+            .compare_snapshots_each("test-job-id", ts_a, ts_b, under, &ignore, |category, e| {
+                let list = match category {
+                    DiffCategory::Added => &mut streamed[0],
+                    DiffCategory::Deleted => &mut streamed[1],
+                    DiffCategory::Modified => &mut streamed[2],
+                };
+                list.push((e.path, e.size_a, e.size_b));
+                Ok(())
+            })
+            .unwrap();
+
+        assert_eq!(streamed[0], as_tuples(&collected.added));
+        assert_eq!(streamed[1], as_tuples(&collected.deleted));
+        assert_eq!(streamed[2], as_tuples(&collected.modified));
+        assert_eq!(
+            delivered,
+            (collected.summary.total_added
+                + collected.summary.total_deleted
+                + collected.summary.total_modified) as usize
+        );
+    }
+
+    // Sanity check on the unfiltered diff, then stop a stream part way
+    let full = service
+        .compare_snapshots("test-job-id", ts_a, ts_b, None)
+        .unwrap();
+    assert_eq!(full.added.len(), 31);
+    assert_eq!(full.deleted.len(), 10);
+    assert_eq!(full.modified.len(), 15);
+
+    let mut seen = 0;
+    let err = service.compare_snapshots_each("test-job-id", ts_a, ts_b, None, &[], |_, _| {
+        seen += 1;
+        if seen == 5 {
+            Err(AmberError::Index("stop".into()))
+        } else {
+            Ok(())
+        }
+    });
+    assert!(err.is_err());
+    assert_eq!(seen, 5);
+}
+
+#[test]
+fn test_compare_source_to_latest_snapshot() {
+    let env = TestBackupEnv::new().unwrap();
+    let service = create_test_index(env.dest_path.to_str().unwrap());
+
+    // An older snapshot that must not be the one compared against
+    let old_path = env.snapshot_path("2024-01-01_120000");
+    fs::create_dir_all(&old_path).unwrap();
+    generate::file(&old_path.join("ancient.txt"), b"old").unwrap();
+    service
+        .index_snapshot("test-job-id", 1704110400000, old_path.to_str().unwrap())
+        .unwrap();
+
+    let latest_path = env.snapshot_path("2024-01-02_120000");
+    fs::create_dir_all(latest_path.join("docs")).unwrap();
+    fs::create_dir_all(latest_path.join("photos")).unwrap();
+    generate::file(&latest_path.join("docs/a.txt"), b"alpha").unwrap();
+    generate::file(&latest_path.join("docs/b.txt"), b"bravo").unwrap();
+    generate::file(&latest_path.join("photos/x.jpg"), b"jpeg").unwrap();
+    generate::file(&latest_path.join("old.log"), b"log").unwrap();
+    service
+        .index_snapshot("test-job-id", 1704196800000, latest_path.to_str().unwrap())
+        .unwrap();
+
+    // Source since then: a.txt edited, b.txt, x.jpg and old.log deleted
+    fs::create_dir_all(env.source_path.join("docs")).unwrap();
+    fs::create_dir_all(env.source_path.join("photos")).unwrap();
+    generate::file(&env.source_path.join("docs/a.txt"), b"alpha, edited").unwrap();
+    generate::file(&env.source_path.join("docs/c.txt"), b"charlie").unwrap();
+    generate::file(&env.source_path.join("new.txt"), b"brand new").unwrap();
+
+    let comparison = service
+        .compare_source_to_snapshot("test-job-id", env.source_path.to_str().unwrap(), &[])
+        .unwrap()
+        .expect("job has an indexed snapshot");
+    assert_eq!(comparison.snapshot_timestamp, 1704196800000);
+
+    let removed: Vec<(&str, Option<i64>, Option<i64>)> = comparison
+        .removed
+        .iter()
+        .map(|e| (e.path.as_str(), e.size_a, e.size_b))
+        .collect();
+    assert_eq!(
+        removed,
+        vec![
+            ("docs/b.txt", Some(5), None),
+            ("old.log", Some(3), None),
+            ("photos/x.jpg", Some(4), None),
+        ]
+    );
+    let added: Vec<(&str, Option<i64>, Option<i64>)> = comparison
+        .added
+        .iter()
+        .map(|e| (e.path.as_str(), e.size_a, e.size_b))
+        .collect();
+    assert_eq!(
+        added,
+        vec![("docs/c.txt", None, Some(7)), ("new.txt", None, Some(9))]
+    );
+    let modified: Vec<&str> = comparison
+        .modified
+        .iter()
+        .map(|e| e.path.as_str())
+        .collect();
+    assert_eq!(modified, vec!["docs/a.txt"]);
+
+    // Excluded files are neither copied nor deleted by rsync
+    let excludes = vec![
+        "photos/".to_string(),
+        "*.log".to_string(),
+        "/new.txt".to_string(),
+    ];
+    let comparison = service
+        .compare_source_to_snapshot("test-job-id", env.source_path.to_str().unwrap(), &excludes)
+        .unwrap()
+        .unwrap();
+    let paths = |entries: &[source_diff::SourceChange]| {
+        entries.iter().map(|e| e.path.clone()).collect::<Vec<_>>()
+    };
+    assert_eq!(paths(&comparison.removed), vec!["docs/b.txt"]);
+    assert_eq!(paths(&comparison.added), vec!["docs/c.txt"]);
+
+    // Nothing to compare against for a job without snapshots
+    assert!(service
+        .compare_source_to_snapshot("other-job", env.source_path.to_str().unwrap(), &[])
+        .unwrap()
+        .is_none());
+}
+
+#[test]
+fn test_source_diff_against_latest_snapshot_categories() {
+    let env = TestBackupEnv::new().unwrap();
+    let snapshot_path = env.snapshot_path("2024-01-01_120000");
+    fs::create_dir_all(snapshot_path.join("docs")).unwrap();
+    for name in ["same.txt", "touched.txt", "docs/resized.txt", "gone.txt"] {
+        write_with_mtime(&snapshot_path, name, 1_700_000_000);
+    }
+    let service = create_test_index(env.dest_path.to_str().unwrap());
+    service
+        .index_snapshot(
+            "test-job-id",
+            1704110400000,
+            snapshot_path.to_str().unwrap(),
+        )
+        .unwrap();
+
+    // The source drifts: one file touched, one rewritten, one added, one removed
+    let source = &env.source_path;
+    fs::create_dir_all(source.join("docs")).unwrap();
+    write_with_mtime(source, "same.txt", 1_700_000_000);
+    write_with_mtime(source, "touched.txt", 1_700_000_060);
+    write_with_mtime(source, "docs/resized.txt", 1_700_000_000);
+    fs::write(source.join("docs/resized.txt"), "much longer than before").unwrap();
+    write_with_mtime(source, "new.txt", 1_700_000_120);
+
+    let diff = source_diff::diff_source(
+        1704110400000,
+        service
+            .snapshot_files("test-job-id", 1704110400000)
+            .unwrap(),
+        source_diff::scan_local(source).unwrap(),
+        &[],
+    );
+    let paths = |changes: &[source_diff::SourceChange]| {
+        changes.iter().map(|c| c.path.clone()).collect::<Vec<_>>()
+    };
+    assert_eq!(diff.snapshot_timestamp, 1704110400000);
+    assert_eq!(paths(&diff.added), vec!["new.txt"]);
+    assert_eq!(
+        paths(&diff.modified),
+        vec!["docs/resized.txt", "touched.txt"]
+    );
+    assert_eq!(paths(&diff.removed), vec!["gone.txt"]);
+
+    let touched = &diff.modified[1];
+    assert_eq!(touched.size_a, touched.size_b);
+    assert_eq!(touched.mtime_a, Some(1_700_000_000_000));
+    assert_eq!(touched.mtime_b, Some(1_700_000_060_000));
+    assert_eq!(diff.removed[0].size_b, None);
+    assert_eq!(
+        diff.unprotected_bytes,
+        ("new.txt".len() + "touched.txt".len() + "much longer than before".len()) as i64
+    );
+}
+
+/// Relative paths of a nested listing; directories left unexpanded end in "/…"
+fn subtree_outline(nodes: &[app_lib::types::snapshot::FileNode], prefix: &str) -> Vec<String> {
+    let mut outline = Vec::new();
+    for node in nodes {
+        let path = format!("{}{}", prefix, node.name);
+        match (&node.node_type[..], &node.children) {
+            ("dir", Some(children)) => {
+                outline.push(format!("{}/", path));
+                outline.extend(subtree_outline(children, &format!("{}/", path)));
+            }
+            ("dir", None) => outline.push(format!("{}/…", path)),
+            _ => outline.push(path),
+        }
+    }
+    outline
+}
+
+#[test]
+fn test_subtree_expands_to_the_requested_depth() {
+    let env = TestBackupEnv::new().unwrap();
+    let snapshot_path = env.snapshot_path("2024-01-01_120000");
+    for file in ["top.txt", "a/x.txt", "a/b/y.txt", "a/b/c/z.txt"] {
+        generate::file(&snapshot_path.join(file), b"data").unwrap();
+    }
+
+    let service = create_test_index(env.dest_path.to_str().unwrap());
+    let ts = 1704110400000_i64;
+    service
+        .index_snapshot("test-job-id", ts, snapshot_path.to_str().unwrap())
+        .unwrap();
+    let outline = |parent: &str, depth: usize, max_nodes: Option<usize>| {
+        let subtree = service
+            .get_subtree("test-job-id", ts, parent, depth, max_nodes)
+            .unwrap();
+        let outline = subtree_outline(&subtree.nodes, "");
+        (outline, subtree.node_count, subtree.truncated)
+    };
+
+    // Depth 1 is the plain directory listing
+    let listing: Vec<String> = service
+        .get_directory_contents("test-job-id", ts, "")
+        .unwrap()
+        .into_iter()
+        .map(|n| n.path)
+        .collect();
+    let depth_one = service.get_subtree("test-job-id", ts, "", 1, None).unwrap();
+    assert_eq!(
+        depth_one
+            .nodes
+            .iter()
+            .map(|n| n.path.clone())
+            .collect::<Vec<_>>(),
+        listing
+    );
+    assert_eq!(
+        outline("", 1, None),
+        (vec!["top.txt".to_string(), "a/…".to_string()], 2, false)
+    );
+
+    assert_eq!(
+        outline("", 3, None).0,
+        vec!["top.txt", "a/", "a/x.txt", "a/b/", "a/b/y.txt", "a/b/c/…"]
+    );
+    assert_eq!(
+        outline("", 10, None),
+        (
+            vec![
+                "top.txt".to_string(),
+                "a/".to_string(),
+                "a/x.txt".to_string(),
+                "a/b/".to_string(),
+                "a/b/y.txt".to_string(),
+                "a/b/c/".to_string(),
+                "a/b/c/z.txt".to_string(),
+            ],
+            7,
+            false
+        )
+    );
+    assert_eq!(outline("a/b", 1, None).0, vec!["y.txt", "c/…"]);
+
+    // Three nodes fit the root listing but not "a"'s two children as well
+    assert_eq!(
+        outline("", 3, Some(3)),
+        (vec!["top.txt".to_string(), "a/…".to_string()], 2, true)
+    );
+    assert_eq!(
+        outline("", 3, Some(1)),
+        (vec!["top.txt".to_string()], 1, true)
+    );
+}
+
+#[test]
+fn test_metadata_only_snapshot_compares_but_refuses_restore() {
+    let env = TestBackupEnv::new().unwrap();
+    generate::file(&env.source_path.join("keep.txt"), b"keep").unwrap();
+    generate::file(&env.source_path.join("docs/old.txt"), b"old").unwrap();
+
+    let backup_path = env.snapshot_path("2024-01-01_120000");
+    fs::create_dir_all(&backup_path).unwrap();
+    generate::file(&backup_path.join("keep.txt"), b"keep").unwrap();
+    generate::file(&backup_path.join("docs/old.txt"), b"old").unwrap();
+
+    let service = create_test_index(env.dest_path.to_str().unwrap());
+    let ts_backup = 1704110400000_i64;
+    service
+        .index_snapshot("test-job-id", ts_backup, backup_path.to_str().unwrap())
+        .unwrap();
+
+    // The source moves on; pin it without copying anything
+    fs::remove_file(env.source_path.join("docs/old.txt")).unwrap();
+    generate::file(&env.source_path.join("docs/new.txt"), b"brand new").unwrap();
+    let source = env.source_path.to_str().unwrap();
+    let ts_pin = 1704196800000_i64;
+    let pinned = service
+        .index_metadata_snapshot("test-job-id", ts_pin, source)
+        .unwrap();
+    assert!(pinned.metadata_only);
+    assert_eq!(pinned.file_count, 2);
+    assert!(!backup_path.join("docs/new.txt").exists());
+
+    let listed = service.list_snapshots("test-job-id").unwrap();
+    assert_eq!(
+        listed
+            .iter()
+            .map(|s| (s.timestamp, s.metadata_only))
+            .collect::<Vec<_>>(),
+        vec![(ts_pin, true), (ts_backup, false)]
+    );
+
+    let diff = service
+        .compare_snapshots("test-job-id", ts_backup, ts_pin, None)
+        .unwrap();
+    let paths = |entries: &[app_lib::services::index_service::DiffEntry]| {
+        entries.iter().map(|e| e.path.clone()).collect::<Vec<_>>()
+    };
+    assert_eq!(paths(&diff.added), vec!["docs/new.txt"]);
+    assert_eq!(paths(&diff.deleted), vec!["docs/old.txt"]);
+
+    // Previews against "the latest backup" skip the pin
+    let preview = service
+        .compare_source_to_snapshot("test-job-id", source, &[])
+        .unwrap()
+        .unwrap();
+    assert_eq!(preview.snapshot_timestamp, ts_backup);
+
+    let refused = service
+        .ensure_restorable("test-job-id", ts_pin)
+        .unwrap_err();
+    assert!(matches!(refused, AmberError::Snapshot(_)), "{:?}", refused);
+    assert!(refused.to_string().contains("metadata-only"));
+    assert!(service
+        .restore_estimate("test-job-id", ts_pin, &["keep.txt".to_string()])
+        .is_err());
+    assert!(service
+        .ensure_restorable_root("test-job-id", source)
+        .is_err());
+
+    service.ensure_restorable("test-job-id", ts_backup).unwrap();
+    service
+        .ensure_restorable_root("test-job-id", backup_path.to_str().unwrap())
+        .unwrap();
+    service
+        .restore_estimate("test-job-id", ts_backup, &["keep.txt".to_string()])
+        .unwrap();
+}
+
+#[test]
+fn test_nfd_and_nfc_names_compare_and_search_as_equal() {
+    let env = TestBackupEnv::new().unwrap();
+    // The same name as macOS (decomposed) and Linux (composed) would write it
+    let nfd = "Re\u{301}sume\u{301}.txt";
+    let nfc = "R\u{e9}sum\u{e9}.txt";
+
+    let mac_path = env.snapshot_path("2024-01-01_120000");
+    let linux_path = env.snapshot_path("2024-01-02_120000");
+    fs::create_dir_all(mac_path.join("Caf\u{e9}")).unwrap();
+    fs::create_dir_all(linux_path.join("Cafe\u{301}")).unwrap();
+    generate::file(&mac_path.join(nfd), b"same bytes").unwrap();
+    generate::file(&linux_path.join(nfc), b"same bytes").unwrap();
+    generate::file(&mac_path.join("Caf\u{e9}/menu.txt"), b"menu").unwrap();
+    generate::file(&linux_path.join("Cafe\u{301}/menu.txt"), b"menu").unwrap();
+
+    let service = create_test_index(env.dest_path.to_str().unwrap());
+    let ts_mac = 1704110400000_i64;
+    let ts_linux = 1704196800000_i64;
+    service
+        .index_snapshot("test-job-id", ts_mac, mac_path.to_str().unwrap())
+        .unwrap();
+    service
+        .index_snapshot("test-job-id", ts_linux, linux_path.to_str().unwrap())
+        .unwrap();
+
+    let diff = service
+        .compare_snapshots("test-job-id", ts_mac, ts_linux, None)
+        .unwrap();
+    assert!(diff.added.is_empty(), "added: {:?}", diff.added);
+    assert!(diff.deleted.is_empty(), "deleted: {:?}", diff.deleted);
+
+    // Either spelling of the query finds the file in both snapshots
+    for query in ["Re\u{301}sume\u{301}", "R\u{e9}sum\u{e9}"] {
+        let hits = service.search_files_global(query, None, 10).unwrap();
+        let mut found: Vec<(String, i64)> = hits
+            .into_iter()
+            .map(|h| (h.file.name, h.snapshot_timestamp))
+            .collect();
+        found.sort();
+        assert_eq!(
+            found,
+            vec![(nfc.to_string(), ts_mac), (nfc.to_string(), ts_linux)],
+            "query {:?}",
+            query
+        );
+    }
+}
+
+// ============================================================================
+// SEARCH TESTS AND EDGE CASES
+// ============================================================================
+
+#[test]
+fn test_search_exact_filename() {
+    let env = TestBackupEnv::new().unwrap();
+
+    // Create snapshot with distinctly named files
+    let snapshot_path = env.snapshot_path("2024-01-01_120000");
+    fs::create_dir_all(&snapshot_path).unwrap();
+
+    generate::file(&snapshot_path.join("unique_target.txt"), b"target content").unwrap();
+    generate::file(&snapshot_path.join("other_file.txt"), b"other content").unwrap();
+    generate::file(&snapshot_path.join("another.rs"), b"rust code").unwrap();
+
+    let service = create_test_index(env.dest_path.to_str().unwrap());
+
+    service
+        .index_snapshot(
+            "test-job-id",
+            1704110400000,
+            snapshot_path.to_str().unwrap(),
+        )
+        .unwrap();
+
+    // Search for exact filename
+    let results = service
+        .search_files("test-job-id", 1704110400000, "unique_target", 100)
+        .unwrap();
+
+    assert_eq!(
+        results.len(),
+        1,
+        "Should find exactly one file with exact name match"
+    );
+    assert_eq!(results[0].name, "unique_target.txt");
+}
+
+#[test]
+fn test_rebuild_fts_cancelled_keeps_search_working() {
+    let env = TestBackupEnv::new().unwrap();
+    let snapshot_path = env.snapshot_path("2024-01-01_120000");
+    for i in 0..200 {
+        generate::file(
+            &snapshot_path.join(format!("dir{}/report-{}.txt", i % 10, i)),
+            b"x",
+        )
+        .unwrap();
+    }
+    let service = create_test_index(env.dest_path.to_str().unwrap());
+    service
+        .index_snapshot(
+            "test-job-id",
+            1704110400000,
+            snapshot_path.to_str().unwrap(),
+        )
+        .unwrap();
+    let found = |service: &IndexService| {
+        service
+            .search_files("test-job-id", 1704110400000, "report", 1000)
+            .unwrap()
+            .len()
+    };
+
+    let cancel = CancelToken::new();
+    cancel.cancel();
+    assert!(matches!(
+        service.rebuild_fts(&cancel),
+        Err(AmberError::Cancelled)
+    ));
+    assert_eq!(found(&service), 200);
+
+    // Cancelled from another thread while it may be running: rolled back or
+    // finished, the search index is whole either way
+    let cancel = CancelToken::new();
+    let canceller = {
+        let cancel = cancel.clone();
+        std::thread::spawn(move || cancel.cancel())
+    };
+    let result = service.rebuild_fts(&cancel);
+    canceller.join().unwrap();
+    assert!(
+        matches!(result, Ok(()) | Err(AmberError::Cancelled)),
+        "{:?}",
+        result
+    );
+    assert_eq!(found(&service), 200);
+
+    service.rebuild_fts(&CancelToken::new()).unwrap();
+    assert_eq!(found(&service), 200);
+}
 
 #[test]
 fn test_search_partial_match() {
@@ -1232,37 +2573,152 @@ fn test_search_partial_match() {
     let service = create_test_index(env.dest_path.to_str().unwrap());
 
     service
-        .index_snapshot(
-            "test-job-id",
-            1704110400000,
-            snapshot_path.to_str().unwrap(),
-        )
+        .index_snapshot(
+            "test-job-id",
+            1704110400000,
+            snapshot_path.to_str().unwrap(),
+        )
+        .unwrap();
+
+    // Search for partial match - "config" should match 3 files
+    let results = service
+        .search_files("test-job-id", 1704110400000, "config", 100)
+        .unwrap();
+
+    assert_eq!(
+        results.len(),
+        3,
+        "Should find all files containing 'config'"
+    );
+    for result in &results {
+        assert!(
+            result.name.contains("config"),
+            "Each result should contain 'config': {}",
+            result.name
+        );
+    }
+
+    // Search for ".json" should match 2 files
+    let json_results = service
+        .search_files("test-job-id", 1704110400000, ".json", 100)
+        .unwrap();
+
+    assert_eq!(json_results.len(), 2, "Should find 2 .json files");
+}
+
+#[test]
+fn test_search_files_job_finds_deleted_files() {
+    let env = TestBackupEnv::new().unwrap();
+    let service = create_test_index(env.dest_path.to_str().unwrap());
+
+    // Snapshot A has the report; snapshot B deleted it
+    let snapshot_a = env.snapshot_path("2024-01-01_120000");
+    generate::file(&snapshot_a.join("docs/report.pdf"), b"quarterly").unwrap();
+    generate::file(&snapshot_a.join("docs/report_notes.txt"), b"notes").unwrap();
+    let snapshot_b = env.snapshot_path("2024-01-02_120000");
+    generate::file(&snapshot_b.join("docs/report_notes.txt"), b"notes v2").unwrap();
+    for (ts, path) in [(1704110400000, &snapshot_a), (1704196800000, &snapshot_b)] {
+        service
+            .index_snapshot("test-job-id", ts, path.to_str().unwrap())
+            .unwrap();
+    }
+    service
+        .index_snapshot("other-job", 1704110400000, snapshot_a.to_str().unwrap())
+        .unwrap();
+
+    // The latest snapshot alone no longer has it
+    assert!(service
+        .search_files("test-job-id", 1704196800000, "report.pdf", 100)
+        .unwrap()
+        .is_empty());
+
+    let results = service
+        .search_files_job("test-job-id", "report", 100, false)
+        .unwrap();
+    let found: Vec<(&str, i64)> = results
+        .iter()
+        .map(|r| (r.rel_path.as_str(), r.snapshot_timestamp))
+        .collect();
+    assert_eq!(
+        found,
+        vec![
+            ("docs/report.pdf", 1704110400000),
+            ("docs/report_notes.txt", 1704196800000),
+            ("docs/report_notes.txt", 1704110400000),
+        ]
+    );
+
+    let collapsed = service
+        .search_files_job("test-job-id", "report", 100, true)
+        .unwrap();
+    assert_eq!(collapsed.len(), 2);
+    assert_eq!(collapsed[0].snapshot_timestamps, vec![1704110400000]);
+    assert_eq!(
+        collapsed[1].snapshot_timestamps,
+        vec![1704196800000, 1704110400000]
+    );
+    assert_eq!(collapsed[1].file.size, 8);
+
+    // Collapsed limits count paths, not matches
+    let limited = service
+        .search_files_job("test-job-id", "report", 1, true)
+        .unwrap();
+    assert_eq!(limited.len(), 1);
+    assert_eq!(limited[0].rel_path, "docs/report.pdf");
+}
+
+#[test]
+fn test_find_path_in_snapshots_lists_the_snapshots_holding_it() {
+    let env = TestBackupEnv::new().unwrap();
+    let service = create_test_index(env.dest_path.to_str().unwrap());
+
+    // The budget exists in snapshots 1, 2 and 4, growing; 3 lost it
+    let timestamps = [
+        1704110400000_i64,
+        1704196800000,
+        1704283200000,
+        1704369600000,
+    ];
+    let budgets: [Option<&[u8]>; 4] = [Some(b"v1"), Some(b"v2 longer"), None, Some(b"v4")];
+    for (i, (&ts, budget)) in timestamps.iter().zip(budgets).enumerate() {
+        let snapshot = env.snapshot_path(&format!("snap-{}", i));
+        generate::file(&snapshot.join("finance/notes.txt"), b"notes").unwrap();
+        if let Some(content) = budget {
+            generate::file(&snapshot.join("finance/budget.xlsx"), content).unwrap();
+        }
+        service
+            .index_snapshot("test-job-id", ts, snapshot.to_str().unwrap())
+            .unwrap();
+    }
+    // Same path under another job doesn't count
+    let other = env.snapshot_path("other");
+    generate::file(&other.join("finance/budget.xlsx"), b"theirs").unwrap();
+    service
+        .index_snapshot("other-job", timestamps[0], other.to_str().unwrap())
         .unwrap();
 
-    // Search for partial match - "config" should match 3 files
-    let results = service
-        .search_files("test-job-id", 1704110400000, "config", 100)
+    let found = service
+        .find_path_in_snapshots("test-job-id", "finance/budget.xlsx")
         .unwrap();
-
+    let listed: Vec<(i64, i64)> = found.iter().map(|o| (o.timestamp, o.size)).collect();
     assert_eq!(
-        results.len(),
-        3,
-        "Should find all files containing 'config'"
+        listed,
+        vec![(timestamps[3], 2), (timestamps[1], 9), (timestamps[0], 2)]
     );
-    for result in &results {
-        assert!(
-            result.name.contains("config"),
-            "Each result should contain 'config': {}",
-            result.name
-        );
-    }
-
-    // Search for ".json" should match 2 files
-    let json_results = service
-        .search_files("test-job-id", 1704110400000, ".json", 100)
-        .unwrap();
+    assert!(found.iter().all(|o| o.mtime > 0 && o.mtime % 1000 == 0));
 
-    assert_eq!(json_results.len(), 2, "Should find 2 .json files");
+    // Leading "./" and stray slashes name the same path
+    assert_eq!(
+        service
+            .find_path_in_snapshots("test-job-id", "./finance//budget.xlsx")
+            .unwrap(),
+        found
+    );
+    assert!(service
+        .find_path_in_snapshots("test-job-id", "finance/missing.xlsx")
+        .unwrap()
+        .is_empty());
+    assert!(service.find_path_in_snapshots("test-job-id", "").is_err());
 }
 
 #[test]
@@ -1466,6 +2922,51 @@ fn test_delete_snapshot_from_index() {
     );
 }
 
+#[test]
+fn test_compaction_runs_once_deletions_reach_threshold() {
+    let env = TestBackupEnv::new().unwrap();
+    let snapshot_path = env.snapshot_path("2024-01-01_120000");
+    fs::create_dir_all(&snapshot_path).unwrap();
+    generate::file(&snapshot_path.join("file.txt"), b"content").unwrap();
+
+    let dest = env.dest_path.to_str().unwrap();
+    let service = create_test_index(dest).with_compaction_threshold(3);
+    let timestamps: Vec<i64> = (0..5).map(|i| 1704110400000 + i * 86_400_000).collect();
+    for &ts in &timestamps {
+        service
+            .index_snapshot("test-job-id", ts, snapshot_path.to_str().unwrap())
+            .unwrap();
+    }
+
+    // Below the threshold: counted (and kept across opens), not compacted
+    service
+        .delete_snapshot("test-job-id", timestamps[0])
+        .unwrap();
+    service
+        .delete_snapshot("test-job-id", timestamps[1])
+        .unwrap();
+    service
+        .delete_snapshot("test-job-id", 9999999999999)
+        .unwrap();
+    assert!(!service.compact_if_due().unwrap());
+    assert_eq!(service.compaction_count().unwrap(), 0);
+    drop(service);
+
+    let service = create_test_index(dest).with_compaction_threshold(3);
+    assert_eq!(service.deletions_since_compact().unwrap(), 2);
+    assert!(!service.compact_if_due().unwrap());
+
+    // Crossing it compacts exactly once and resets the counter
+    service
+        .delete_snapshot("test-job-id", timestamps[2])
+        .unwrap();
+    assert!(service.compact_if_due().unwrap());
+    assert!(!service.compact_if_due().unwrap());
+    assert_eq!(service.compaction_count().unwrap(), 1);
+    assert_eq!(service.deletions_since_compact().unwrap(), 0);
+    assert_eq!(service.list_snapshots("test-job-id").unwrap().len(), 2);
+}
+
 #[test]
 fn test_delete_nonexistent_snapshot() {
     let env = TestBackupEnv::new().unwrap();
@@ -1692,3 +3193,673 @@ fn test_multiple_jobs_isolation() {
         "Job-beta files should still be accessible"
     );
 }
+
+// ============================================================================
+// MODIFIED-AFTER FILTER TESTS
+// ============================================================================
+
+/// Write `name` under `dir` with its mtime set to `mtime_secs`
+fn write_with_mtime(dir: &std::path::Path, name: &str, mtime_secs: u64) {
+    let path = dir.join(name);
+    fs::write(&path, name).unwrap();
+    fs::File::options()
+        .write(true)
+        .open(&path)
+        .unwrap()
+        .set_modified(std::time::UNIX_EPOCH + std::time::Duration::from_secs(mtime_secs))
+        .unwrap();
+}
+
+#[test]
+fn test_modified_after_filters_directory_listing_at_boundary() {
+    let env = TestBackupEnv::new().unwrap();
+    let snapshot_path = env.snapshot_path("2024-01-01_120000");
+    fs::create_dir_all(snapshot_path.join("docs")).unwrap();
+    write_with_mtime(&snapshot_path, "before.txt", 1_700_000_000);
+    write_with_mtime(&snapshot_path, "at.txt", 1_700_000_100);
+    write_with_mtime(&snapshot_path, "after.txt", 1_700_000_101);
+
+    let service = create_test_index(env.dest_path.to_str().unwrap());
+    let ts = 1704110400000;
+    service
+        .index_snapshot("test-job-id", ts, snapshot_path.to_str().unwrap())
+        .unwrap();
+
+    let names = |modified_after: Option<i64>| {
+        let contents = service
+            .get_directory_contents_paginated(
+                "test-job-id",
+                ts,
+                "",
+                None,
+                None,
+                modified_after,
+                None,
+            )
+            .unwrap();
+        assert_eq!(contents.total_count, contents.files.len());
+        let mut names: Vec<String> = contents.files.into_iter().map(|f| f.name).collect();
+        names.sort();
+        names
+    };
+
+    assert_eq!(
+        names(None),
+        vec!["after.txt", "at.txt", "before.txt", "docs"]
+    );
+
+    // Exactly at a file's mtime: that file is not newer, the next one is
+    assert_eq!(names(Some(1_700_000_100_000)), vec!["after.txt", "docs"]);
+    // Anywhere inside the preceding second still counts the file as newer
+    assert_eq!(
+        names(Some(1_700_000_099_999)),
+        vec!["after.txt", "at.txt", "docs"]
+    );
+    assert_eq!(names(Some(1_700_000_100_999)), vec!["after.txt", "docs"]);
+
+    // Directories stay so the tree can still be walked
+    assert_eq!(names(Some(1_800_000_000_000)), vec!["docs"]);
+}
+
+#[test]
+fn test_paged_directory_listing_sorts_and_pages_in_sql() {
+    let env = TestBackupEnv::new().unwrap();
+    let snapshot_path = env.snapshot_path("2024-01-01_120000");
+    fs::create_dir_all(snapshot_path.join("b-dir")).unwrap();
+    fs::create_dir_all(snapshot_path.join("y-dir")).unwrap();
+    // Content is the name, so sizes are 5, 7 and 6 bytes
+    write_with_mtime(&snapshot_path, "a.txt", 1_700_000_300);
+    write_with_mtime(&snapshot_path, "ccc.txt", 1_700_000_100);
+    write_with_mtime(&snapshot_path, "xx.txt", 1_700_000_200);
+
+    let service = create_test_index(env.dest_path.to_str().unwrap());
+    let ts = 1704110400000;
+    service
+        .index_snapshot("test-job-id", ts, snapshot_path.to_str().unwrap())
+        .unwrap();
+
+    let page = |sort: SortBy, offset: usize, limit: usize| {
+        let contents = service
+            .get_directory_contents_paged("test-job-id", ts, "", sort, offset, limit)
+            .unwrap();
+        assert_eq!(contents.total_count, 5);
+        let names: Vec<String> = contents.files.into_iter().map(|f| f.name).collect();
+        (names, contents.has_more)
+    };
+
+    // Default: folders first, then by name
+    let by_name = SortBy::default();
+    assert_eq!(
+        page(by_name, 0, 2),
+        (vec!["b-dir".into(), "y-dir".into()], true)
+    );
+    assert_eq!(
+        page(by_name, 2, 2),
+        (vec!["a.txt".into(), "ccc.txt".into()], true)
+    );
+    assert_eq!(page(by_name, 4, 2), (vec!["xx.txt".into()], false));
+    assert_eq!(page(by_name, 6, 2), (vec![], false));
+
+    let name_desc = SortBy {
+        descending: true,
+        ..SortBy::default()
+    };
+    assert_eq!(
+        page(name_desc, 0, 10).0,
+        vec!["y-dir", "b-dir", "xx.txt", "ccc.txt", "a.txt"]
+    );
+
+    let size_desc = SortBy {
+        field: SortField::Size,
+        descending: true,
+        directories_first: true,
+    };
+    assert_eq!(
+        page(size_desc, 0, 10).0,
+        vec!["b-dir", "y-dir", "ccc.txt", "xx.txt", "a.txt"]
+    );
+    assert_eq!(page(size_desc, 3, 1), (vec!["xx.txt".into()], true));
+
+    // Mixed in with the files, the folders (modified just now) sort last
+    let oldest_first = SortBy {
+        field: SortField::Mtime,
+        descending: false,
+        directories_first: false,
+    };
+    assert_eq!(
+        page(oldest_first, 0, 10).0,
+        vec!["ccc.txt", "xx.txt", "a.txt", "b-dir", "y-dir"]
+    );
+}
+
+#[test]
+fn test_modified_after_filters_search_results() {
+    let env = TestBackupEnv::new().unwrap();
+    let snapshot_path = env.snapshot_path("2024-01-01_120000");
+    fs::create_dir_all(&snapshot_path).unwrap();
+    write_with_mtime(&snapshot_path, "report-old.pdf", 1_600_000_000);
+    write_with_mtime(&snapshot_path, "report-new.pdf", 1_700_000_000);
+
+    let service = create_test_index(env.dest_path.to_str().unwrap());
+    let ts = 1704110400000;
+    service
+        .index_snapshot("test-job-id", ts, snapshot_path.to_str().unwrap())
+        .unwrap();
+
+    let all = service
+        .search_files("test-job-id", ts, "report", 100)
+        .unwrap();
+    assert_eq!(all.len(), 2);
+
+    let recent = service
+        .search_files_modified_after(
+            "test-job-id",
+            ts,
+            "report",
+            100,
+            Some(1_650_000_000_000),
+            None,
+        )
+        .unwrap();
+    let names: Vec<_> = recent.iter().map(|f| f.name.as_str()).collect();
+    assert_eq!(names, vec!["report-new.pdf"]);
+}
+
+#[test]
+fn test_type_filter_lists_only_files_of_the_category() {
+    let env = TestBackupEnv::new().unwrap();
+    let snapshot_path = env.snapshot_path("2024-01-01_120000");
+    for name in [
+        "beach.JPG",
+        "diagram.png",
+        "scan.2023.jpeg",
+        "notes.txt",
+        "report.pdf",
+        "song.mp3",
+        "backup.tar.gz",
+        "Makefile",
+        "albums/cover.png",
+    ] {
+        generate::file(&snapshot_path.join(name), name.as_bytes()).unwrap();
+    }
+
+    let service = create_test_index(env.dest_path.to_str().unwrap());
+    let ts = 1704110400000;
+    service
+        .index_snapshot("test-job-id", ts, snapshot_path.to_str().unwrap())
+        .unwrap();
+
+    let listed = |type_filter: Option<FileCategory>| {
+        let contents = service
+            .get_directory_contents_paginated("test-job-id", ts, "", None, None, None, type_filter)
+            .unwrap();
+        assert_eq!(contents.total_count, contents.files.len());
+        let mut names: Vec<String> = contents.files.into_iter().map(|f| f.name).collect();
+        names.sort();
+        names
+    };
+
+    assert_eq!(listed(None).len(), 9);
+    // Folders stay so the tree can still be walked
+    assert_eq!(
+        listed(Some(FileCategory::Images)),
+        vec!["albums", "beach.JPG", "diagram.png", "scan.2023.jpeg"]
+    );
+    assert_eq!(
+        listed(Some(FileCategory::Documents)),
+        vec!["albums", "notes.txt", "report.pdf"]
+    );
+    // Only the last extension counts
+    assert_eq!(
+        listed(Some(FileCategory::Archives)),
+        vec!["albums", "backup.tar.gz"]
+    );
+
+    let found = service
+        .search_files_modified_after(
+            "test-job-id",
+            ts,
+            "a",
+            100,
+            None,
+            Some(FileCategory::Images),
+        )
+        .unwrap();
+    let mut names: Vec<_> = found.iter().map(|f| f.name.as_str()).collect();
+    names.sort();
+    assert_eq!(
+        names,
+        vec!["albums", "beach.JPG", "diagram.png", "scan.2023.jpeg"]
+    );
+    assert!(found
+        .iter()
+        .filter(|f| f.node_type == "file")
+        .all(
+            |f| FileCategory::of_extension(f.name.rsplit('.').next().unwrap())
+                == Some(FileCategory::Images)
+        ));
+}
+
+// ============================================================================
+// Normalized Storage Tests
+// ============================================================================
+
+/// Recreate `src` under `dst` with every file hard-linked, as `--link-dest` does
+fn link_tree(src: &std::path::Path, dst: &std::path::Path) {
+    fs::create_dir_all(dst).unwrap();
+    for entry in fs::read_dir(src).unwrap() {
+        let entry = entry.unwrap();
+        let target = dst.join(entry.file_name());
+        if entry.file_type().unwrap().is_dir() {
+            link_tree(&entry.path(), &target);
+        } else {
+            fs::hard_link(entry.path(), &target).unwrap();
+        }
+    }
+}
+
+fn row_count(service: &IndexService, table: &str) -> i64 {
+    let conn = rusqlite::Connection::open(service.get_db_path()).unwrap();
+    conn.query_row(&format!("SELECT COUNT(*) FROM {}", table), [], |row| {
+        row.get(0)
+    })
+    .unwrap()
+}
+
+fn as_json<T: serde::Serialize>(value: T) -> serde_json::Value {
+    serde_json::to_value(value).unwrap()
+}
+
+/// Three snapshots of a mostly static tree: the second changes one file and
+/// adds one, the third deletes one
+fn build_evolving_tree(env: &TestBackupEnv) -> [std::path::PathBuf; 3] {
+    let first = env.snapshot_path("2024-01-01_120000");
+    generate::simple_backup_structure(&first).unwrap();
+    generate::nested_dirs(&first.join("nested"), 2, 3).unwrap();
+
+    let second = env.snapshot_path("2024-01-02_120000");
+    link_tree(&first, &second);
+    fs::remove_file(second.join("config.json")).unwrap();
+    generate::file(
+        &second.join("config.json"),
+        b"{\"version\": 2, \"extra\": true}",
+    )
+    .unwrap();
+    generate::file(&second.join("documents/report.txt"), b"new report").unwrap();
+
+    let third = env.snapshot_path("2024-01-03_120000");
+    link_tree(&second, &third);
+    fs::remove_file(third.join("documents/notes.md")).unwrap();
+
+    [first, second, third]
+}
+
+/// Index the three snapshots a day apart; recent timestamps keep the stale
+/// flag the same for every snapshot
+fn index_evolving_tree(service: &IndexService, dirs: &[std::path::PathBuf; 3]) -> [i64; 3] {
+    let base = chrono::Utc::now().timestamp_millis() - 3 * 86_400_000;
+    let timestamps = [base, base + 86_400_000, base + 2 * 86_400_000];
+    for (ts, dir) in timestamps.iter().zip(dirs) {
+        service
+            .index_snapshot("test-job-id", *ts, dir.to_str().unwrap())
+            .unwrap();
+    }
+    timestamps
+}
+
+#[test]
+fn test_normalized_storage_matches_denormalized_results() {
+    let env = TestBackupEnv::new().unwrap();
+    let plain_dir = env.temp_dir.path().join("plain-index");
+    let shared_dir = env.temp_dir.path().join("shared-index");
+    fs::create_dir_all(&plain_dir).unwrap();
+    fs::create_dir_all(&shared_dir).unwrap();
+    let plain = IndexService::new_with_storage(&plain_dir, IndexStorage::Denormalized).unwrap();
+    let shared = IndexService::new_with_storage(&shared_dir, IndexStorage::Normalized).unwrap();
+    assert_eq!(plain.storage(), IndexStorage::Denormalized);
+    assert_eq!(shared.storage(), IndexStorage::Normalized);
+
+    let dirs = build_evolving_tree(&env);
+    let ts = index_evolving_tree(&plain, &dirs);
+    for (t, dir) in ts.iter().zip(&dirs) {
+        shared
+            .index_snapshot("test-job-id", *t, dir.to_str().unwrap())
+            .unwrap();
+    }
+
+    for index in [&plain, &shared] {
+        assert_eq!(index.list_snapshots("test-job-id").unwrap().len(), 3);
+    }
+    assert_eq!(
+        as_json(plain.list_snapshots("test-job-id").unwrap()),
+        as_json(shared.list_snapshots("test-job-id").unwrap())
+    );
+
+    for t in ts {
+        for dir in ["", "documents", "nested", "nested/dir_0"] {
+            assert_eq!(
+                as_json(plain.get_directory_contents("test-job-id", t, dir).unwrap()),
+                as_json(
+                    shared
+                        .get_directory_contents("test-job-id", t, dir)
+                        .unwrap()
+                ),
+                "browsing '{}' at {}",
+                dir,
+                t
+            );
+        }
+        assert_eq!(
+            as_json(plain.search_files("test-job-id", t, "file", 100).unwrap()),
+            as_json(shared.search_files("test-job-id", t, "file", 100).unwrap())
+        );
+        assert_eq!(
+            as_json(plain.get_snapshot_stats("test-job-id", t).unwrap()),
+            as_json(shared.get_snapshot_stats("test-job-id", t).unwrap())
+        );
+        assert_eq!(
+            as_json(plain.get_largest_files("test-job-id", t, 10).unwrap()),
+            as_json(shared.get_largest_files("test-job-id", t, 10).unwrap())
+        );
+    }
+
+    for query in ["config", "report", "notes", "file"] {
+        assert_eq!(
+            as_json(
+                plain
+                    .search_files_global(query, Some("test-job-id"), 100)
+                    .unwrap()
+            ),
+            as_json(
+                shared
+                    .search_files_global(query, Some("test-job-id"), 100)
+                    .unwrap()
+            ),
+            "global search for '{}'",
+            query
+        );
+    }
+
+    for (a, b) in [(ts[0], ts[1]), (ts[1], ts[2]), (ts[0], ts[2])] {
+        assert_eq!(
+            as_json(plain.compare_snapshots("test-job-id", a, b, None).unwrap()),
+            as_json(shared.compare_snapshots("test-job-id", a, b, None).unwrap())
+        );
+    }
+
+    // Unchanged files are stored once; only directories (new inodes) and the
+    // two changed files get rows of their own in later snapshots
+    let per_snapshot = row_count(&plain, "files");
+    assert_eq!(row_count(&shared, "files"), per_snapshot);
+    let (entries, dirs) = (35, 9);
+    assert_eq!(per_snapshot, entries + (entries + 1) + entries);
+    assert_eq!(row_count(&shared, "file_rows"), entries + (dirs + 2) + dirs);
+}
+
+#[test]
+fn test_normalized_storage_drops_unshared_rows_with_snapshot() {
+    let env = TestBackupEnv::new().unwrap();
+    let index_dir = env.temp_dir.path().join("shared-index");
+    fs::create_dir_all(&index_dir).unwrap();
+    let shared = IndexService::new_with_storage(&index_dir, IndexStorage::Normalized).unwrap();
+    let ts = index_evolving_tree(&shared, &build_evolving_tree(&env));
+
+    let before = row_count(&shared, "file_rows");
+    shared.delete_snapshot("test-job-id", ts[2]).unwrap();
+    // Rows only the third snapshot used are gone; shared ones stay
+    let after = row_count(&shared, "file_rows");
+    assert!(after < before);
+    assert_eq!(
+        after,
+        row_count(&shared, "(SELECT DISTINCT file_row_id FROM snapshot_files)")
+    );
+    let docs = shared
+        .get_directory_contents("test-job-id", ts[1], "documents")
+        .unwrap();
+    assert!(docs.iter().any(|f| f.name == "notes.md"));
+
+    shared.delete_job_snapshots("test-job-id").unwrap();
+    assert_eq!(row_count(&shared, "file_rows"), 0);
+    assert_eq!(row_count(&shared, "files_fts"), 0);
+    assert!(shared
+        .search_files_global("config", None, 10)
+        .unwrap()
+        .is_empty());
+}
+
+#[test]
+fn test_existing_index_keeps_its_storage() {
+    let env = TestBackupEnv::new().unwrap();
+    let index_dir = env.temp_dir.path().join("index");
+    fs::create_dir_all(&index_dir).unwrap();
+    let snapshot = env.snapshot_path("2024-01-01_120000");
+    generate::simple_backup_structure(&snapshot).unwrap();
+
+    let plain = IndexService::new_with_storage(&index_dir, IndexStorage::Denormalized).unwrap();
+    plain
+        .index_snapshot("test-job-id", 1704110400000, snapshot.to_str().unwrap())
+        .unwrap();
+    drop(plain);
+
+    // A populated index is never converted
+    let reopened = IndexService::new_with_storage(&index_dir, IndexStorage::Normalized).unwrap();
+    assert_eq!(reopened.storage(), IndexStorage::Denormalized);
+    drop(reopened);
+
+    let other_dir = env.temp_dir.path().join("other");
+    fs::create_dir_all(&other_dir).unwrap();
+    drop(IndexService::new_with_storage(&other_dir, IndexStorage::Normalized).unwrap());
+    let reopened = IndexService::new_with_storage(&other_dir, IndexStorage::Denormalized).unwrap();
+    assert_eq!(reopened.storage(), IndexStorage::Normalized);
+}
+
+#[test]
+fn test_older_destination_index_is_upgraded_on_open() {
+    let env = TestBackupEnv::new().unwrap();
+    let dest = env.dest_path.to_str().unwrap();
+    let db_path = manifest_service::get_index_path(dest);
+    fs::create_dir_all(db_path.parent().unwrap()).unwrap();
+
+    // Index written by an Amber that only knew the v1 schema
+    let mut conn = rusqlite::Connection::open(&db_path).unwrap();
+    index_migrations::migrate_up(&mut conn, &index_migrations::migrations(true), 1).unwrap();
+    conn.execute(
+        "INSERT INTO snapshots (job_id, timestamp, root_path, file_count, total_size)
+         VALUES ('test-job-id', 1704110400000, '/old', 5, 100)",
+        [],
+    )
+    .unwrap();
+    drop(conn);
+
+    let service = IndexService::for_destination(dest).unwrap();
+    let conn = rusqlite::Connection::open(&db_path).unwrap();
+    assert_eq!(
+        index_migrations::current_version(&conn).unwrap(),
+        index_migrations::LATEST_VERSION
+    );
+    drop(conn);
+
+    let snapshots = service.list_snapshots("test-job-id").unwrap();
+    assert_eq!(snapshots.len(), 1);
+    assert_eq!(snapshots[0].timestamp, 1704110400000);
+
+    let snapshot = env.snapshot_path("2024-01-02_120000");
+    generate::simple_backup_structure(&snapshot).unwrap();
+    service
+        .index_snapshot("test-job-id", 1704196800000, snapshot.to_str().unwrap())
+        .unwrap();
+    assert_eq!(service.list_snapshots("test-job-id").unwrap().len(), 2);
+}
+
+#[test]
+fn test_newer_destination_index_is_refused_untouched() {
+    let env = TestBackupEnv::new().unwrap();
+    let dest = env.dest_path.to_str().unwrap();
+    let db_path = manifest_service::get_index_path(dest);
+    fs::create_dir_all(db_path.parent().unwrap()).unwrap();
+
+    let conn = rusqlite::Connection::open(&db_path).unwrap();
+    conn.pragma_update(None, "user_version", 99).unwrap();
+    drop(conn);
+
+    let err = IndexService::for_destination(dest).err().unwrap();
+    assert!(matches!(err, AmberError::Index(_)));
+    assert!(err.to_string().contains("v99"), "{}", err);
+
+    let conn = rusqlite::Connection::open(&db_path).unwrap();
+    assert_eq!(index_migrations::current_version(&conn).unwrap(), 99);
+    let tables: i64 = conn
+        .query_row("SELECT COUNT(*) FROM sqlite_master", [], |row| row.get(0))
+        .unwrap();
+    assert_eq!(tables, 0);
+}
+
+/// `files` ids of a snapshot, keyed by path
+fn file_ids(service: &IndexService, timestamp: i64) -> std::collections::HashMap<String, i64> {
+    let conn = service.get_connection_for_stats().unwrap();
+    let mut stmt = conn
+        .prepare(
+            "SELECT f.path, f.id FROM files f JOIN snapshots s ON s.id = f.snapshot_id
+             WHERE s.timestamp = ?",
+        )
+        .unwrap();
+    let ids = stmt
+        .query_map([timestamp], |row| Ok((row.get(0)?, row.get(1)?)))
+        .unwrap()
+        .map(|row| row.unwrap())
+        .collect();
+    ids
+}
+
+#[test]
+fn test_incremental_reindex_writes_only_the_delta() {
+    let timestamp = 1704110400000;
+    for storage in [IndexStorage::Denormalized, IndexStorage::Normalized] {
+        let env = TestBackupEnv::new().unwrap();
+        let index_dir = env.temp_dir.path().join("index");
+        fs::create_dir_all(&index_dir).unwrap();
+        let service = IndexService::new_with_storage(&index_dir, storage).unwrap();
+
+        let snapshot = env.snapshot_path("2024-01-01-120000");
+        fs::create_dir_all(&snapshot).unwrap();
+        for i in 0..20 {
+            write_with_mtime(&snapshot, &format!("file-{:02}.txt", i), 1_700_000_000);
+        }
+        let root = snapshot.to_str().unwrap();
+        service
+            .index_snapshot("test-job-id", timestamp, root)
+            .unwrap();
+        let before = file_ids(&service, timestamp);
+
+        // One touched, one added, one removed
+        write_with_mtime(&snapshot, "file-03.txt", 1_700_000_500);
+        write_with_mtime(&snapshot, "added.txt", 1_700_000_500);
+        fs::remove_file(snapshot.join("file-07.txt")).unwrap();
+
+        let result = service
+            .reindex_snapshot_incremental("test-job-id", timestamp, root)
+            .unwrap();
+        assert_eq!(result.added, 1, "{:?}", storage);
+        assert_eq!(result.updated, 1, "{:?}", storage);
+        assert_eq!(result.removed, 1, "{:?}", storage);
+        assert_eq!(result.unchanged, 18, "{:?}", storage);
+        assert_eq!(result.snapshot.file_count, 20);
+        assert_eq!(
+            service
+                .get_snapshot_stats("test-job-id", timestamp)
+                .unwrap(),
+            (20, 20 * "file-00.txt".len() as i64 - 2)
+        );
+
+        let after = file_ids(&service, timestamp);
+        assert_eq!(after.len(), 20);
+        for (path, id) in &before {
+            if path.ends_with("file-03.txt") || path.ends_with("file-07.txt") {
+                continue;
+            }
+            assert_eq!(after.get(path), Some(id), "{} kept its row", path);
+        }
+        if storage == IndexStorage::Denormalized {
+            let touched = before
+                .iter()
+                .find(|(path, _)| path.ends_with("file-03.txt"))
+                .unwrap();
+            assert_eq!(after.get(touched.0), Some(touched.1), "updated in place");
+        }
+
+        // The FTS index followed the changes
+        let hits = |query: &str| {
+            service
+                .search_files_global(query, Some("test-job-id"), 10)
+                .unwrap()
+        };
+        let touched = hits("\"file-03\"");
+        assert_eq!(touched.len(), 1);
+        assert_eq!(touched[0].file.modified, 1_700_000_500 * 1000);
+        assert_eq!(hits("added").len(), 1);
+        assert!(hits("\"file-07\"").is_empty());
+
+        // Nothing left to do the second time
+        let again = service
+            .reindex_snapshot_incremental("test-job-id", timestamp, root)
+            .unwrap();
+        assert_eq!((again.added, again.updated, again.removed), (0, 0, 0));
+        assert_eq!(again.unchanged, 20);
+        assert_eq!(file_ids(&service, timestamp), after);
+    }
+}
+
+#[test]
+#[ignore] // Timing comparison: cargo test --release -- --ignored --nocapture
+fn bench_incremental_reindex_vs_full() {
+    let env = TestBackupEnv::new().unwrap();
+    let service = create_test_index(env.dest_path.to_str().unwrap());
+    let snapshot = env.snapshot_path("2024-01-01-120000");
+    for dir in 0..80 {
+        let dir_path = snapshot.join(format!("dir-{:02}", dir));
+        fs::create_dir_all(&dir_path).unwrap();
+        for i in 0..1000 {
+            write_with_mtime(&dir_path, &format!("file-{:04}.txt", i), 1_700_000_000);
+        }
+    }
+    let root = snapshot.to_str().unwrap();
+    service
+        .index_snapshot("test-job-id", 1704110400000, root)
+        .unwrap();
+
+    // 2% of the files change
+    for dir in 0..80 {
+        let dir_path = snapshot.join(format!("dir-{:02}", dir));
+        for i in 0..20 {
+            write_with_mtime(&dir_path, &format!("file-{:04}.txt", i), 1_700_000_500);
+        }
+    }
+
+    let start = std::time::Instant::now();
+    service
+        .index_snapshot_force_replace("test-job-id", 1704110400000, root)
+        .unwrap();
+    let full = start.elapsed();
+
+    for dir in 0..80 {
+        let dir_path = snapshot.join(format!("dir-{:02}", dir));
+        for i in 0..20 {
+            write_with_mtime(&dir_path, &format!("file-{:04}.txt", i), 1_700_001_000);
+        }
+    }
+
+    let start = std::time::Instant::now();
+    let result = service
+        .reindex_snapshot_incremental("test-job-id", 1704110400000, root)
+        .unwrap();
+    let incremental = start.elapsed();
+
+    println!(
+        "80k entries, {} changed: full {:?}, incremental {:?} ({:.2}x)",
+        result.updated,
+        full,
+        incremental,
+        full.as_secs_f64() / incremental.as_secs_f64()
+    );
+    assert!(incremental < full);
+}
@@ -0,0 +1,108 @@
+//! Integration tests for warming up a destination's index on mount
+
+use crate::common::test_common::{generate, TestBackupEnv};
+use app_lib::services::cancel_token::{CancelRegistry, CancelToken};
+use app_lib::services::index_service::IndexService;
+use app_lib::services::index_warmup;
+use app_lib::types::job::SyncJob;
+use app_lib::types::snapshot::FileNode;
+
+// 2024-01-01-120000 .. 2024-01-04-120000
+const TIMESTAMPS: [i64; 4] = [1704110400000, 1704196800000, 1704283200000, 1704369600000];
+
+/// Four indexed snapshots of `job_id`, each with its own top-level file
+fn indexed_destination(env: &TestBackupEnv, job_id: &str) -> String {
+    let dest = env.dest_path.to_str().unwrap().to_string();
+    let index = IndexService::for_destination(&dest).unwrap();
+    for (i, &timestamp) in TIMESTAMPS.iter().enumerate() {
+        let path = env.snapshot_path(&format!("snap-{}", i));
+        generate::simple_backup_structure(&path).unwrap();
+        generate::file(&path.join(format!("only-in-{}.txt", i)), b"marker").unwrap();
+        index
+            .index_snapshot(job_id, timestamp, path.to_str().unwrap())
+            .unwrap();
+    }
+    dest
+}
+
+fn names(files: &[FileNode]) -> Vec<String> {
+    let mut names: Vec<String> = files.iter().map(|f| f.name.clone()).collect();
+    names.sort();
+    names
+}
+
+/// The cached root, or None when the cache missed
+fn cached(dest: &str, job_id: &str, timestamp: i64) -> Option<Vec<FileNode>> {
+    let mut missed = false;
+    let files = index_warmup::root_contents(dest, job_id, timestamp, || {
+        missed = true;
+        Ok(Vec::new())
+    })
+    .unwrap();
+    (!missed).then_some(files)
+}
+
+#[test]
+fn test_warmup_populates_the_root_cache_directory_listings_hit() {
+    let env = TestBackupEnv::new().unwrap();
+    let job_id = "warmup-hit-job";
+    let dest = indexed_destination(&env, job_id);
+    let job = SyncJob {
+        id: job_id.to_string(),
+        dest_path: dest.clone(),
+        ..SyncJob::default()
+    };
+    let mount = env.temp_dir.path().to_str().unwrap();
+    let operations = CancelRegistry::new();
+
+    assert!(cached(&dest, job_id, TIMESTAMPS[3]).is_none());
+    assert_eq!(
+        index_warmup::warm_mount(&operations, mount, &[job]),
+        index_warmup::DEFAULT_WARMUP_SNAPSHOTS
+    );
+
+    // The latest snapshots come from the cache and match the index
+    let index = IndexService::for_destination(&dest).unwrap();
+    for &timestamp in &TIMESTAMPS[1..] {
+        let from_cache = cached(&dest, job_id, timestamp).expect("warmed up");
+        let from_index = index.get_directory_contents(job_id, timestamp, "").unwrap();
+        assert_eq!(names(&from_cache), names(&from_index));
+    }
+    assert!(cached(&dest, job_id, TIMESTAMPS[0]).is_none(), "beyond N");
+
+    // Deleting a snapshot drops its root; unplugging the drive drops the rest
+    index.delete_snapshot(job_id, TIMESTAMPS[3]).unwrap();
+    assert!(cached(&dest, job_id, TIMESTAMPS[3]).is_none());
+    assert!(cached(&dest, job_id, TIMESTAMPS[2]).is_some());
+
+    assert!(!operations.is_running(&format!("index-warmup:{}", mount)));
+    index_warmup::forget_mount(&operations, mount);
+    assert!(cached(&dest, job_id, TIMESTAMPS[2]).is_none());
+}
+
+#[test]
+fn test_warmup_stops_when_cancelled_and_skips_other_volumes() {
+    let env = TestBackupEnv::new().unwrap();
+    let job_id = "warmup-cancel-job";
+    let dest = indexed_destination(&env, job_id);
+
+    let cancelled = CancelToken::new();
+    cancelled.cancel();
+    assert_eq!(
+        index_warmup::warm_destination(&dest, job_id, 3, &cancelled).unwrap(),
+        0
+    );
+    assert!(cached(&dest, job_id, TIMESTAMPS[3]).is_none());
+
+    let job = SyncJob {
+        id: job_id.to_string(),
+        dest_path: dest.clone(),
+        ..SyncJob::default()
+    };
+    let operations = CancelRegistry::new();
+    assert_eq!(
+        index_warmup::warm_mount(&operations, "/Volumes/Elsewhere", &[job]),
+        0
+    );
+    assert!(cached(&dest, job_id, TIMESTAMPS[3]).is_none());
+}
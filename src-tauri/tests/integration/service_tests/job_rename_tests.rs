@@ -0,0 +1,123 @@
+//! Integration tests for changing a job's id
+
+use crate::common::test_common::{generate, TestBackupEnv};
+use app_lib::error::AmberError;
+use app_lib::services::index_service::IndexService;
+use app_lib::services::job_rename;
+use app_lib::services::manifest_service::{get_or_create_manifest, read_manifest};
+use app_lib::services::store::Store;
+use app_lib::types::job::SyncJob;
+
+const TIMESTAMP: i64 = 1704110400000;
+
+/// A job called `old-job` with a snapshot in each index and a manifest on
+/// its destination
+async fn renamable_job(env: &TestBackupEnv) -> (Store, IndexService, String) {
+    let app_data = env.temp_dir.path().join("app-data");
+    let store = Store::new(&app_data);
+    let local_index = IndexService::new(&app_data).unwrap();
+    let dest = env.dest_path.to_str().unwrap().to_string();
+
+    let snapshot = env.snapshot_path("2024-01-01-120000");
+    generate::file(&snapshot.join("docs/report.txt"), b"quarterly").unwrap();
+    generate::file(&snapshot.join("notes.txt"), b"notes").unwrap();
+    let dest_index = IndexService::for_destination(&dest).unwrap();
+    for index in [&local_index, &dest_index] {
+        index
+            .index_snapshot("old-job", TIMESTAMP, snapshot.to_str().unwrap())
+            .unwrap();
+    }
+
+    get_or_create_manifest(&dest, "old-job", "Documents", "/source")
+        .await
+        .unwrap();
+    store
+        .save_job(SyncJob {
+            id: "old-job".to_string(),
+            name: "Documents".to_string(),
+            dest_path: dest.clone(),
+            ..SyncJob::default()
+        })
+        .unwrap();
+    (store, local_index, dest)
+}
+
+#[tokio::test]
+async fn test_rename_moves_index_and_manifest_to_the_new_id() {
+    let env = TestBackupEnv::new().unwrap();
+    let (store, local_index, dest) = renamable_job(&env).await;
+
+    let job = job_rename::rename_job_id(&store, &local_index, "old-job", "new-job")
+        .await
+        .unwrap();
+    assert_eq!(job.id, "new-job");
+    assert_eq!(job.name, "Documents");
+    assert!(store.get_job("old-job").unwrap().is_none());
+    assert_eq!(store.get_job("new-job").unwrap().unwrap().dest_path, dest);
+
+    let manifest = read_manifest(&dest).await.unwrap().unwrap();
+    assert_eq!(manifest.job_id, "new-job");
+    let on_drive = Store::read_job_from_destination(&dest).unwrap().unwrap();
+    assert_eq!(on_drive.id, "new-job");
+
+    // Both indexes answer under the new id and have forgotten the old one
+    let dest_index = IndexService::for_destination(&dest).unwrap();
+    for index in [&local_index, &dest_index] {
+        assert!(index.list_snapshots("old-job").unwrap().is_empty());
+        assert!(!index.is_indexed("old-job", TIMESTAMP).unwrap());
+        assert!(index.is_indexed("new-job", TIMESTAMP).unwrap());
+        let listing = index
+            .get_directory_contents("new-job", TIMESTAMP, "")
+            .unwrap();
+        let mut names: Vec<&str> = listing.iter().map(|f| f.name.as_str()).collect();
+        names.sort();
+        assert_eq!(names, vec!["docs", "notes.txt"]);
+        let found = index
+            .search_files("new-job", TIMESTAMP, "report", 10)
+            .unwrap();
+        assert_eq!(found.len(), 1);
+    }
+}
+
+#[tokio::test]
+async fn test_rename_is_refused_without_changing_anything() {
+    let env = TestBackupEnv::new().unwrap();
+    let (store, local_index, dest) = renamable_job(&env).await;
+    store
+        .save_job(SyncJob {
+            id: "taken-job".to_string(),
+            ..SyncJob::default()
+        })
+        .unwrap();
+
+    let result = job_rename::rename_job_id(&store, &local_index, "old-job", "taken-job").await;
+    assert!(
+        matches!(result, Err(AmberError::ValidationError(_))),
+        "{:?}",
+        result
+    );
+    let result = job_rename::rename_job_id(&store, &local_index, "no-such-job", "new-job").await;
+    assert!(matches!(result, Err(AmberError::JobNotFound(_))));
+
+    // Stale snapshots under the new id on the destination: the local index
+    // is renamed first and has to be put back
+    let dest_index = IndexService::for_destination(&dest).unwrap();
+    let stale = env.snapshot_path("stale");
+    generate::file(&stale.join("old.txt"), b"stale").unwrap();
+    dest_index
+        .index_snapshot("new-job", TIMESTAMP, stale.to_str().unwrap())
+        .unwrap();
+    let result = job_rename::rename_job_id(&store, &local_index, "old-job", "new-job").await;
+    assert!(result.is_err());
+
+    assert!(store.get_job("old-job").unwrap().is_some());
+    assert!(store.get_job("new-job").unwrap().is_none());
+    assert_eq!(
+        read_manifest(&dest).await.unwrap().unwrap().job_id,
+        "old-job"
+    );
+    assert!(local_index.is_indexed("old-job", TIMESTAMP).unwrap());
+    assert!(!local_index.is_indexed("new-job", TIMESTAMP).unwrap());
+    assert!(dest_index.is_indexed("old-job", TIMESTAMP).unwrap());
+    assert_eq!(dest_index.list_snapshots("new-job").unwrap().len(), 1);
+}
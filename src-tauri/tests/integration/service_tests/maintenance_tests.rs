@@ -0,0 +1,73 @@
+//! Integration tests for compacting every destination's index
+
+use crate::common::test_common::{generate, TestBackupEnv};
+use app_lib::services::index_service::IndexService;
+use app_lib::services::maintenance;
+use std::path::Path;
+
+const JOB_ID: &str = "maintenance-job";
+
+/// A destination whose index held three snapshots of many files, two of
+/// them since deleted
+fn pruned_destination(root: &Path) -> String {
+    let dest = root.join("dest");
+    let index = IndexService::for_destination(dest.to_str().unwrap()).unwrap();
+    for day in 1..=3 {
+        let snapshot = root.join(format!("snapshots/2024-01-0{}-120000", day));
+        for i in 0..400 {
+            let path = snapshot.join(format!("folder-{}/document-number-{}.txt", i % 20, i));
+            generate::file(&path, b"contents").unwrap();
+        }
+        index
+            .index_snapshot(
+                JOB_ID,
+                1704110400000 + day * 86_400_000,
+                snapshot.to_str().unwrap(),
+            )
+            .unwrap();
+    }
+    for day in 1..=2 {
+        index
+            .delete_snapshot(JOB_ID, 1704110400000 + day * 86_400_000)
+            .unwrap();
+    }
+    dest.to_string_lossy().to_string()
+}
+
+#[test]
+fn test_compact_all_shrinks_every_destination() {
+    let env = TestBackupEnv::new().unwrap();
+    let first = pruned_destination(&env.temp_dir.path().join("first"));
+    let second = pruned_destination(&env.temp_dir.path().join("second"));
+    let unplugged = env
+        .temp_dir
+        .path()
+        .join("unplugged")
+        .to_string_lossy()
+        .to_string();
+
+    let report = maintenance::compact_all(&[first.clone(), unplugged.clone(), second.clone()]);
+    assert_eq!(report.len(), 3);
+
+    for (result, dest) in [(&report[0], &first), (&report[2], &second)] {
+        assert_eq!(&result.dest_path, dest);
+        assert_eq!(result.note, None);
+        assert!(
+            result.bytes_saved() > 0,
+            "{} didn't shrink: {:?}",
+            dest,
+            result
+        );
+
+        // Still a working index afterwards
+        let index = IndexService::for_destination(dest).unwrap();
+        assert_eq!(index.list_snapshots(JOB_ID).unwrap().len(), 1);
+        assert_eq!(index.compaction_count().unwrap(), 1);
+        assert_eq!(index.deletions_since_compact().unwrap(), 0);
+    }
+
+    let skipped = &report[1];
+    assert_eq!(skipped.dest_path, unplugged);
+    assert_eq!((skipped.bytes_before, skipped.bytes_after), (None, None));
+    assert!(skipped.note.as_deref().unwrap().contains("not reachable"));
+}
@@ -7,6 +7,7 @@ use app_lib::services::manifest_service::{
     add_snapshot_to_manifest, get_manifest_path, get_meta_dir, get_or_create_manifest,
     manifest_exists, read_manifest, remove_snapshot_from_manifest, write_manifest, ManifestError,
 };
+use app_lib::types::job::{SshConfig, SyncJob};
 use app_lib::types::manifest::{BackupManifest, ManifestSnapshot, ManifestSnapshotStatus};
 use std::fs;
 
@@ -325,6 +326,65 @@ async fn test_add_snapshot_to_manifest() {
     assert_eq!(reread.snapshots.len(), 1);
 }
 
+#[tokio::test]
+async fn test_snapshots_keep_the_config_they_ran_with() {
+    let env = TestBackupEnv::new().unwrap();
+    let dest_path = env.dest_path.to_str().unwrap();
+    get_or_create_manifest(dest_path, "job-audit", "Audit Test", "/source")
+        .await
+        .unwrap();
+
+    let mut job = SyncJob {
+        id: "job-audit".to_string(),
+        ssh_config: Some(SshConfig {
+            enabled: true,
+            port: Some("2222".to_string()),
+            ..SshConfig::default()
+        }),
+        ..SyncJob::default()
+    };
+    job.config.compress = false;
+    job.config.exclude_patterns = vec!["*.tmp".to_string()];
+    let march = ManifestSnapshot::new(
+        "2024-03-01-120000".to_string(),
+        10,
+        1024,
+        ManifestSnapshotStatus::Complete,
+        Some(1000),
+    )
+    .with_run_config(&job);
+    add_snapshot_to_manifest(dest_path, march).await.unwrap();
+
+    // The job changes after the March backup
+    job.config.compress = true;
+    job.config.exclude_patterns.clear();
+    job.ssh_config = None;
+    let april = ManifestSnapshot::new(
+        "2024-04-01-120000".to_string(),
+        10,
+        1024,
+        ManifestSnapshotStatus::Complete,
+        Some(1000),
+    )
+    .with_run_config(&job);
+    add_snapshot_to_manifest(dest_path, april).await.unwrap();
+
+    let manifest = read_manifest(dest_path).await.unwrap().unwrap();
+    let march = manifest.snapshots[0].run_config.as_ref().unwrap();
+    assert!(!march.rsync.compress);
+    assert_eq!(march.rsync.exclude_patterns, vec!["*.tmp"]);
+    assert_eq!(march.ssh.as_ref().unwrap().port.as_deref(), Some("2222"));
+
+    let april = manifest.snapshots[1].run_config.as_ref().unwrap();
+    assert!(april.rsync.compress);
+    assert!(april.rsync.exclude_patterns.is_empty());
+    assert!(april.ssh.is_none());
+    assert_eq!(
+        serde_json::to_value(&april.rsync).unwrap(),
+        serde_json::to_value(&job.config).unwrap()
+    );
+}
+
 #[tokio::test]
 async fn test_add_multiple_snapshots() {
     let env = TestBackupEnv::new().unwrap();
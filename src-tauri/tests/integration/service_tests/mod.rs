@@ -3,8 +3,19 @@
 //! These tests instantiate actual service instances with temp directories
 //! to verify real behavior, not mocked behavior.
 
+pub mod dashboard_tests;
+pub mod diagnostics_tests;
 pub mod failure_recovery_tests;
+pub mod index_backfill_tests;
 pub mod index_service_tests;
+pub mod index_warmup_tests;
+pub mod job_rename_tests;
+pub mod maintenance_tests;
 pub mod manifest_service_tests;
+pub mod parallel_restore_tests;
+pub mod retention_tests;
 pub mod rsync_service_tests;
+pub mod snapshot_commit_tests;
+pub mod snapshot_export_tests;
+pub mod snapshot_open_tests;
 pub mod snapshot_service_tests;
@@ -0,0 +1,306 @@
+//! Integration tests for restoring many files in parallel
+
+use crate::common::test_common::{generate, verify, TestBackupEnv};
+use app_lib::error::AmberError;
+use app_lib::services::cancel_token::{self, CancelRegistry, CancelToken};
+use app_lib::services::parallel_restore::{self, ConflictStrategy, RestoreProgress};
+use std::path::Path;
+use std::sync::Mutex;
+
+/// `count` tiny files spread over nested folders, plus a link
+fn many_small_files(snapshot: &Path, count: usize) -> u64 {
+    let mut bytes = 0;
+    for i in 0..count {
+        let content = format!("file number {}", i);
+        let path = snapshot.join(format!("data/d{}/e{}/f{}.txt", i % 10, i % 7, i));
+        generate::file(&path, content.as_bytes()).unwrap();
+        bytes += content.len() as u64;
+    }
+    #[cfg(unix)]
+    std::os::unix::fs::symlink("d0", snapshot.join("data/latest")).unwrap();
+    bytes
+}
+
+fn restore(
+    snapshot: &Path,
+    files: &[&str],
+    target: &Path,
+    conflict: ConflictStrategy,
+) -> (
+    app_lib::error::Result<parallel_restore::RestoreReport>,
+    Vec<RestoreProgress>,
+) {
+    let seen = Mutex::new(Vec::new());
+    let files: Vec<String> = files.iter().map(|f| f.to_string()).collect();
+    let result = parallel_restore::restore_parallel(
+        snapshot,
+        &files,
+        target,
+        4,
+        conflict,
+        &CancelToken::new(),
+        |progress| seen.lock().unwrap().push(progress.clone()),
+    );
+    (result, seen.into_inner().unwrap())
+}
+
+#[test]
+fn test_parallel_restore_copies_every_file_with_accurate_progress() {
+    let env = TestBackupEnv::new().unwrap();
+    let snapshot = env.snapshot_path("2024-01-01-120000");
+    let bytes = many_small_files(&snapshot, 2000);
+    let target = env.temp_dir.path().join("restored");
+    let links = if cfg!(unix) { 1 } else { 0 };
+
+    let (report, seen) = restore(&snapshot, &["data"], &target, ConflictStrategy::Overwrite);
+    let report = report.unwrap();
+
+    let diff = verify::compare_directories(&snapshot.join("data"), &target.join("data")).unwrap();
+    assert!(diff.is_identical(), "{:?}", diff);
+    assert_eq!(report.files_restored, 2000 + links);
+    assert_eq!(report.files_skipped, 0);
+    assert_eq!(report.bytes_restored, bytes);
+
+    // One report per file, never going backwards, ending on the totals
+    assert_eq!(seen.len() as u64, 2000 + links);
+    assert!(seen.windows(2).all(|w| w[0].files_done < w[1].files_done));
+    let last = seen.last().unwrap();
+    assert_eq!(last.files_done, last.files_total);
+    assert_eq!(last.files_total, 2000 + links);
+    assert_eq!(last.bytes_done, bytes);
+    assert_eq!(last.bytes_total, bytes);
+}
+
+#[test]
+fn test_parallel_restore_honors_the_conflict_strategy() {
+    let env = TestBackupEnv::new().unwrap();
+    let snapshot = env.snapshot_path("2024-01-01-120000");
+    generate::file(&snapshot.join("docs/a.txt"), b"from snapshot").unwrap();
+    generate::file(&snapshot.join("docs/b.txt"), b"from snapshot").unwrap();
+    let target = env.temp_dir.path().join("restored");
+    generate::file(&target.join("docs/a.txt"), b"edited since").unwrap();
+
+    let (report, seen) = restore(&snapshot, &["docs"], &target, ConflictStrategy::Skip);
+    let report = report.unwrap();
+    assert_eq!((report.files_restored, report.files_skipped), (1, 1));
+    assert!(verify::file_has_content(
+        &target.join("docs/a.txt"),
+        b"edited since"
+    ));
+    assert!(verify::file_has_content(
+        &target.join("docs/b.txt"),
+        b"from snapshot"
+    ));
+    let last = seen.last().unwrap();
+    assert_eq!((last.files_done, last.files_skipped), (2, 1));
+
+    let (report, _) = restore(
+        &snapshot,
+        &["docs/a.txt"],
+        &target,
+        ConflictStrategy::Overwrite,
+    );
+    assert_eq!(report.unwrap().files_restored, 1);
+    assert!(verify::file_has_content(
+        &target.join("docs/a.txt"),
+        b"from snapshot"
+    ));
+}
+
+#[test]
+fn test_parallel_restore_stops_when_cancelled() {
+    let env = TestBackupEnv::new().unwrap();
+    let snapshot = env.snapshot_path("2024-01-01-120000");
+    many_small_files(&snapshot, 50);
+    let target = env.temp_dir.path().join("restored");
+
+    let registry = CancelRegistry::new();
+    let op_id = cancel_token::operation_id(cancel_token::RESTORE, "restore-job");
+    assert!(!registry.cancel(&op_id));
+    let cancel = registry.register(&op_id);
+    assert!(registry.cancel(&op_id));
+
+    let result = parallel_restore::restore_parallel(
+        &snapshot,
+        &["data".to_string()],
+        &target,
+        2,
+        ConflictStrategy::Overwrite,
+        &cancel,
+        |_| panic!("no file may be restored"),
+    );
+    registry.unregister(&op_id, &cancel);
+    assert!(matches!(result, Err(AmberError::Cancelled)));
+    // Folders come first, so they exist even though no file was copied
+    assert!(target.join("data/d0/e0").is_dir());
+    assert_eq!(verify::count_files(&target).unwrap(), 0);
+    assert!(!registry.is_running(&op_id));
+}
+
+#[test]
+#[cfg(unix)]
+fn test_non_utf8_name_round_trips_through_index_and_restore() {
+    use app_lib::services::index_service::IndexService;
+    use std::ffi::OsStr;
+    use std::os::unix::ffi::{OsStrExt, OsStringExt};
+
+    let env = TestBackupEnv::new().unwrap();
+    let snapshot = env.snapshot_path("2024-01-01-120000");
+    let dir = snapshot.join(OsStr::from_bytes(b"caf\xe9"));
+    // Some filesystems (APFS, for one) only take UTF-8 names
+    if std::fs::create_dir_all(&dir).is_err() {
+        return;
+    }
+    generate::file(
+        &dir.join(OsStr::from_bytes(b"r\xe9sum\xe9.txt")),
+        b"latin-1",
+    )
+    .unwrap();
+
+    let index = IndexService::new_in_memory().unwrap();
+    let root = snapshot.to_str().unwrap();
+    index.index_snapshot("job-1", 1704110400000, root).unwrap();
+
+    let listing = index
+        .get_directory_contents("job-1", 1704110400000, "caf%E9")
+        .unwrap();
+    let names: Vec<&str> = listing.iter().map(|f| f.name.as_str()).collect();
+    assert_eq!(names, vec!["r%E9sum%E9.txt"]);
+
+    let paths = index
+        .restore_paths("job-1", root, &["caf%E9/r%E9sum%E9.txt".to_string()])
+        .unwrap();
+    let target = env.temp_dir.path().join("restored");
+    parallel_restore::restore_parallel(
+        &snapshot,
+        &paths,
+        &target,
+        2,
+        ConflictStrategy::Overwrite,
+        &CancelToken::new(),
+        |_| {},
+    )
+    .unwrap();
+
+    let restored_dir = target.join(OsStr::from_bytes(b"caf\xe9"));
+    let restored: Vec<Vec<u8>> = std::fs::read_dir(&restored_dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().into_vec())
+        .collect();
+    assert_eq!(restored, vec![b"r\xe9sum\xe9.txt".to_vec()]);
+    assert_eq!(
+        std::fs::read(restored_dir.join(OsStr::from_bytes(b"r\xe9sum\xe9.txt"))).unwrap(),
+        b"latin-1"
+    );
+}
+
+#[test]
+#[cfg(unix)]
+fn test_escape_like_name_and_non_utf8_name_stay_apart() {
+    use app_lib::services::index_service::IndexService;
+    use std::ffi::OsStr;
+    use std::os::unix::ffi::OsStrExt;
+
+    let env = TestBackupEnv::new().unwrap();
+    let snapshot = env.snapshot_path("2024-01-01-120000");
+    let dir = snapshot.join("names");
+    generate::file(&dir.join("caf%E9"), b"literal").unwrap();
+    // Some filesystems (APFS, for one) only take UTF-8 names
+    if std::fs::write(dir.join(OsStr::from_bytes(b"caf\xe9")), b"latin-1").is_err() {
+        return;
+    }
+
+    let index = IndexService::new_in_memory().unwrap();
+    let root = snapshot.to_str().unwrap();
+    index.index_snapshot("job-1", 1704110400000, root).unwrap();
+
+    let listing = index
+        .get_directory_contents("job-1", 1704110400000, "names")
+        .unwrap();
+    let mut names: Vec<&str> = listing.iter().map(|f| f.name.as_str()).collect();
+    names.sort();
+    assert_eq!(names, vec!["caf%25E9", "caf%E9"]);
+
+    let paths = index
+        .restore_paths(
+            "job-1",
+            root,
+            &["names/caf%25E9".to_string(), "names/caf%E9".to_string()],
+        )
+        .unwrap();
+    let target = env.temp_dir.path().join("restored");
+    parallel_restore::restore_parallel(
+        &snapshot,
+        &paths,
+        &target,
+        2,
+        ConflictStrategy::Overwrite,
+        &CancelToken::new(),
+        |_| {},
+    )
+    .unwrap();
+
+    assert_eq!(
+        std::fs::read(target.join("names/caf%E9")).unwrap(),
+        b"literal"
+    );
+    assert_eq!(
+        std::fs::read(target.join("names").join(OsStr::from_bytes(b"caf\xe9"))).unwrap(),
+        b"latin-1"
+    );
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn test_decomposed_names_restore_on_linux() {
+    use app_lib::services::index_service::IndexService;
+
+    // "café" with a combining acute accent, as macOS writes it; stored NFC
+    let decomposed = "cafe\u{301}";
+    let composed = "caf\u{e9}";
+    let env = TestBackupEnv::new().unwrap();
+    let snapshot = env.snapshot_path("2024-01-01-120000");
+    generate::file(&snapshot.join(decomposed).join("menu.txt"), b"menu").unwrap();
+    generate::file(&snapshot.join(format!("{}.txt", decomposed)), b"note").unwrap();
+
+    let index = IndexService::new_in_memory().unwrap();
+    let root = snapshot.to_str().unwrap();
+    index.index_snapshot("job-1", 1704110400000, root).unwrap();
+    let listing = index
+        .get_directory_contents("job-1", 1704110400000, composed)
+        .unwrap();
+    assert_eq!(listing.len(), 1);
+
+    let requested = vec![
+        format!("{}/menu.txt", composed),
+        format!("{}.txt", composed),
+    ];
+    let paths = index.restore_paths("job-1", root, &requested).unwrap();
+    assert_eq!(
+        index
+            .restore_paths_at("job-1", 1704110400000, &requested)
+            .unwrap(),
+        paths
+    );
+    let target = env.temp_dir.path().join("restored");
+    parallel_restore::restore_parallel(
+        &snapshot,
+        &paths,
+        &target,
+        2,
+        ConflictStrategy::Overwrite,
+        &CancelToken::new(),
+        |_| {},
+    )
+    .unwrap();
+
+    // Restored under the names they had on disk
+    assert_eq!(
+        std::fs::read(target.join(decomposed).join("menu.txt")).unwrap(),
+        b"menu"
+    );
+    assert_eq!(
+        std::fs::read(target.join(format!("{}.txt", decomposed))).unwrap(),
+        b"note"
+    );
+}
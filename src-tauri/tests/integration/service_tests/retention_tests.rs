@@ -0,0 +1,201 @@
+//! Integration tests for pruning snapshots by retention policy
+
+use crate::common::test_common::{generate, TestBackupEnv};
+use app_lib::error::AmberError;
+use app_lib::services::index_service::IndexService;
+use app_lib::services::manifest_service;
+use app_lib::services::retention::{self, RetentionPolicy};
+use app_lib::types::job::SyncJob;
+use app_lib::types::manifest::{ManifestSnapshot, ManifestSnapshotStatus};
+use std::path::PathBuf;
+
+const JOB_ID: &str = "retention-job";
+const DAY: i64 = 24 * 60 * 60 * 1000;
+// 2024-01-01-120000
+const TS_FIRST: i64 = 1704110400000;
+
+/// Four daily snapshots, 2024-01-01 to 2024-01-04, recorded and indexed
+async fn fixture(env: &TestBackupEnv) -> Vec<PathBuf> {
+    let dest = env.dest_path.to_str().unwrap();
+    manifest_service::get_or_create_manifest(dest, JOB_ID, "Retention", "/src")
+        .await
+        .unwrap();
+    let index = IndexService::for_destination(dest).unwrap();
+
+    let mut folders = Vec::new();
+    for day in 0..4 {
+        let name = format!("2024-01-0{}-120000", day + 1);
+        let timestamp = TS_FIRST + day * DAY;
+        let path = env.snapshot_path(&format!("src/{}", name));
+        generate::simple_backup_structure(&path).unwrap();
+        let entry = ManifestSnapshot::from_timestamp(
+            timestamp,
+            name,
+            5,
+            100,
+            ManifestSnapshotStatus::Complete,
+        );
+        manifest_service::add_snapshot_to_manifest(dest, entry)
+            .await
+            .unwrap();
+        index
+            .index_snapshot(JOB_ID, timestamp, path.to_str().unwrap())
+            .unwrap();
+        folders.push(path);
+    }
+    folders
+}
+
+fn job(id: &str, env: &TestBackupEnv) -> SyncJob {
+    SyncJob {
+        id: id.to_string(),
+        source_path: "/src".to_string(),
+        dest_path: env.dest_path.to_str().unwrap().to_string(),
+        ..SyncJob::default()
+    }
+}
+
+fn keep_two_days() -> RetentionPolicy {
+    RetentionPolicy {
+        keep_daily: 2,
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn test_dry_run_lists_without_deleting() {
+    let env = TestBackupEnv::new().unwrap();
+    let folders = fixture(&env).await;
+    let dest = env.dest_path.to_str().unwrap();
+
+    let plan = retention::prune_snapshots(&job(JOB_ID, &env), &keep_two_days(), None, true)
+        .await
+        .unwrap();
+    let timestamps: Vec<i64> = plan.iter().map(|p| p.timestamp).collect();
+    assert_eq!(timestamps, vec![TS_FIRST + DAY, TS_FIRST]);
+    assert_eq!(plan[1].folder_name, "2024-01-01-120000");
+
+    assert!(folders.iter().all(|f| f.is_dir()));
+    let manifest = manifest_service::read_manifest(dest)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(manifest.snapshots.len(), 4);
+    let index = IndexService::for_destination(dest).unwrap();
+    assert!(index.is_indexed(JOB_ID, TS_FIRST).unwrap());
+}
+
+#[tokio::test]
+async fn test_prune_removes_folders_index_rows_and_manifest_entries() {
+    let env = TestBackupEnv::new().unwrap();
+    let folders = fixture(&env).await;
+    let dest = env.dest_path.to_str().unwrap();
+
+    // The oldest is being written by a backup and has to stay
+    let pruned = retention::prune_snapshots(
+        &job(JOB_ID, &env),
+        &keep_two_days(),
+        Some(folders[0].clone()),
+        false,
+    )
+    .await
+    .unwrap();
+    let timestamps: Vec<i64> = pruned.iter().map(|p| p.timestamp).collect();
+    assert_eq!(timestamps, vec![TS_FIRST + DAY]);
+
+    assert!(folders[0].is_dir());
+    assert!(!folders[1].exists());
+    assert!(folders[2].is_dir() && folders[3].is_dir());
+
+    let index = IndexService::for_destination(dest).unwrap();
+    assert!(index.is_indexed(JOB_ID, TS_FIRST).unwrap());
+    assert!(!index.is_indexed(JOB_ID, TS_FIRST + DAY).unwrap());
+    assert_eq!(index.list_snapshots(JOB_ID).unwrap().len(), 3);
+
+    let manifest = manifest_service::read_manifest(dest)
+        .await
+        .unwrap()
+        .unwrap();
+    let left: Vec<i64> = manifest.snapshots.iter().map(|s| s.timestamp).collect();
+    assert_eq!(left.len(), 3);
+    assert!(!left.contains(&(TS_FIRST + DAY)));
+}
+
+#[tokio::test]
+async fn test_policy_keeping_nothing_is_refused() {
+    let env = TestBackupEnv::new().unwrap();
+    let folders = fixture(&env).await;
+
+    let result =
+        retention::prune_snapshots(&job(JOB_ID, &env), &RetentionPolicy::default(), None, false)
+            .await;
+    assert!(matches!(result, Err(AmberError::ValidationError(_))));
+    assert!(folders.iter().all(|f| f.is_dir()));
+
+    let result =
+        retention::prune_snapshots(&job("other-job", &env), &keep_two_days(), None, true).await;
+    assert!(result.is_err());
+}
+
+#[tokio::test]
+async fn test_prune_leaves_folders_the_manifest_does_not_record() {
+    let env = TestBackupEnv::new().unwrap();
+    let folders = fixture(&env).await;
+
+    // A sibling job writing into the same source-named folder, and another
+    // one next to it, both with snapshots older than anything kept
+    let sibling = env.snapshot_path("src/2023-12-30-120000");
+    let neighbour = env.snapshot_path("other/2023-12-30-120000");
+    generate::simple_backup_structure(&sibling).unwrap();
+    generate::simple_backup_structure(&neighbour).unwrap();
+
+    let pruned = retention::prune_snapshots(&job(JOB_ID, &env), &keep_two_days(), None, false)
+        .await
+        .unwrap();
+    let timestamps: Vec<i64> = pruned.iter().map(|p| p.timestamp).collect();
+    assert_eq!(timestamps, vec![TS_FIRST + DAY, TS_FIRST]);
+    assert!(!folders[0].exists() && !folders[1].exists());
+    assert!(sibling.is_dir());
+    assert!(neighbour.is_dir());
+}
+
+#[tokio::test]
+async fn test_failed_snapshot_does_not_displace_complete_one_on_its_day() {
+    let env = TestBackupEnv::new().unwrap();
+    let folders = fixture(&env).await;
+    let dest = env.dest_path.to_str().unwrap();
+
+    // A run later on the newest day that failed part-way
+    let failed_ts = TS_FIRST + 3 * DAY + 6 * 60 * 60 * 1000;
+    let failed = env.snapshot_path("src/2024-01-04-180000");
+    generate::simple_backup_structure(&failed).unwrap();
+    let entry = ManifestSnapshot::from_timestamp(
+        failed_ts,
+        "2024-01-04-180000".to_string(),
+        2,
+        40,
+        ManifestSnapshotStatus::Failed,
+    );
+    manifest_service::add_snapshot_to_manifest(dest, entry)
+        .await
+        .unwrap();
+
+    let plan = retention::prune_snapshots(&job(JOB_ID, &env), &keep_two_days(), None, true)
+        .await
+        .unwrap();
+    let timestamps: Vec<i64> = plan.iter().map(|p| p.timestamp).collect();
+    assert_eq!(timestamps, vec![failed_ts, TS_FIRST + DAY, TS_FIRST]);
+    assert!(folders[3].is_dir());
+}
+
+#[tokio::test]
+async fn test_prune_without_manifest_is_refused() {
+    let env = TestBackupEnv::new().unwrap();
+    let folder = env.snapshot_path("src/2024-01-01-120000");
+    generate::simple_backup_structure(&folder).unwrap();
+
+    let result =
+        retention::prune_snapshots(&job(JOB_ID, &env), &keep_two_days(), None, false).await;
+    assert!(matches!(result, Err(AmberError::ValidationError(_))));
+    assert!(folder.is_dir());
+}
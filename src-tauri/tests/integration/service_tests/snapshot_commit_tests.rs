@@ -0,0 +1,357 @@
+//! Integration tests for committing snapshots to index and manifest
+//!
+//! A crash between the index commit and the manifest write is simulated by
+//! indexing a snapshot as pending and never writing its manifest entry.
+
+use crate::common::test_common::{generate, TestBackupEnv};
+use app_lib::services::index_service::IndexService;
+use app_lib::services::manifest_service;
+use app_lib::services::snapshot_commit::{self, ReconcileReport};
+use app_lib::services::snapshot_service::SnapshotService;
+use app_lib::types::job::SyncJob;
+use app_lib::types::manifest::{
+    IndexDrift, ManifestSnapshot, ManifestSnapshotStatus, SnapshotChanges,
+};
+use std::fs;
+
+const JOB_ID: &str = "commit-job";
+
+async fn create_manifest(dest: &str) {
+    manifest_service::get_or_create_manifest(dest, JOB_ID, "Commit", "/src")
+        .await
+        .unwrap();
+}
+
+fn snapshot_dir(env: &TestBackupEnv, name: &str) -> String {
+    let path = env.snapshot_path(&format!("src/{}", name));
+    generate::simple_backup_structure(&path).unwrap();
+    path.to_string_lossy().to_string()
+}
+
+async fn manifest_timestamps(dest: &str) -> Vec<i64> {
+    let manifest = manifest_service::read_manifest(dest)
+        .await
+        .unwrap()
+        .unwrap();
+    let mut timestamps: Vec<i64> = manifest.snapshots.iter().map(|s| s.timestamp).collect();
+    timestamps.sort();
+    timestamps
+}
+
+#[tokio::test]
+async fn test_commit_snapshot_leaves_nothing_pending() {
+    let env = TestBackupEnv::new().unwrap();
+    let dest = env.dest_path.to_str().unwrap();
+    create_manifest(dest).await;
+    let path = snapshot_dir(&env, "2024-01-01-120000");
+
+    let entry = ManifestSnapshot::from_timestamp(
+        1704110400000,
+        "2024-01-01-120000".to_string(),
+        5,
+        100,
+        ManifestSnapshotStatus::Complete,
+    );
+    let indexed = snapshot_commit::commit_snapshot(dest, JOB_ID, entry, &path)
+        .await
+        .unwrap();
+    assert_eq!(indexed.timestamp, 1704110400000);
+
+    let index = IndexService::for_destination(dest).unwrap();
+    assert!(index.is_indexed(JOB_ID, 1704110400000).unwrap());
+    assert!(index.list_pending_snapshots().unwrap().is_empty());
+    assert_eq!(manifest_timestamps(dest).await, vec![1704110400000]);
+    assert!(snapshot_commit::reconcile_pending(dest)
+        .await
+        .unwrap()
+        .is_empty());
+}
+
+#[tokio::test]
+async fn test_jobs_without_auto_index_record_the_snapshot_unindexed() {
+    let env = TestBackupEnv::new().unwrap();
+    let dest = env.dest_path.to_str().unwrap();
+    create_manifest(dest).await;
+    let path = snapshot_dir(&env, "2024-01-01-120000");
+    let job = SyncJob {
+        id: JOB_ID.to_string(),
+        dest_path: dest.to_string(),
+        auto_index: false,
+        ..SyncJob::default()
+    };
+
+    let entry = ManifestSnapshot::from_timestamp(
+        1704110400000,
+        "2024-01-01-120000".to_string(),
+        5,
+        100,
+        ManifestSnapshotStatus::Complete,
+    );
+    let indexed = snapshot_commit::record_snapshot(&job, entry, &path)
+        .await
+        .unwrap();
+    assert!(indexed.is_none());
+    assert_eq!(manifest_timestamps(dest).await, vec![1704110400000]);
+
+    let index = IndexService::for_destination(dest).unwrap();
+    assert!(!index.is_indexed(JOB_ID, 1704110400000).unwrap());
+    assert!(index.list_snapshots(JOB_ID).unwrap().is_empty());
+    assert!(index.list_pending_snapshots().unwrap().is_empty());
+
+    // Indexed later on request, it browses like any other snapshot
+    let later = index.index_snapshot(JOB_ID, 1704110400000, &path).unwrap();
+    assert!(later.file_count > 0);
+    assert!(index.is_indexed(JOB_ID, 1704110400000).unwrap());
+    assert!(!index
+        .get_directory_contents(JOB_ID, 1704110400000, "")
+        .unwrap()
+        .is_empty());
+    assert_eq!(manifest_timestamps(dest).await, vec![1704110400000]);
+
+    // The default still indexes straight away
+    assert!(SyncJob::default().auto_index);
+}
+
+#[tokio::test]
+async fn test_commit_snapshot_records_changes_since_previous() {
+    let env = TestBackupEnv::new().unwrap();
+    let dest = env.dest_path.to_str().unwrap();
+    create_manifest(dest).await;
+
+    let first = snapshot_dir(&env, "2024-01-01-120000");
+    let entry = |ts: i64, name: &str| {
+        ManifestSnapshot::from_timestamp(
+            ts,
+            name.to_string(),
+            5,
+            100,
+            ManifestSnapshotStatus::Complete,
+        )
+    };
+    snapshot_commit::commit_snapshot(
+        dest,
+        JOB_ID,
+        entry(1704110400000, "2024-01-01-120000"),
+        &first,
+    )
+    .await
+    .unwrap();
+
+    // Second snapshot: two files added, one grown, one removed
+    let second = snapshot_dir(&env, "2024-01-02-120000");
+    let second_root = std::path::Path::new(&second);
+    generate::file(&second_root.join("documents/todo.txt"), b"todo").unwrap();
+    generate::file(&second_root.join("photos/cat.jpg"), b"meow").unwrap();
+    generate::file(
+        &second_root.join("config.json"),
+        b"{\"version\": 2, \"x\": 1}",
+    )
+    .unwrap();
+    fs::remove_file(second_root.join("code/lib.rs")).unwrap();
+    snapshot_commit::commit_snapshot(
+        dest,
+        JOB_ID,
+        entry(1704196800000, "2024-01-02-120000"),
+        &second,
+    )
+    .await
+    .unwrap();
+
+    let manifest = manifest_service::read_manifest(dest)
+        .await
+        .unwrap()
+        .unwrap();
+    let recorded = |ts: i64| {
+        manifest
+            .snapshots
+            .iter()
+            .find(|s| s.timestamp == ts)
+            .unwrap()
+            .clone()
+    };
+
+    // Nothing to compare the first snapshot against
+    let first_entry = recorded(1704110400000);
+    assert_eq!(first_entry.changes, None);
+    assert_eq!(first_entry.changes_count, None);
+
+    let second_entry = recorded(1704196800000);
+    let diff = IndexService::for_destination(dest)
+        .unwrap()
+        .compare_snapshots(JOB_ID, 1704110400000, 1704196800000, None)
+        .unwrap();
+    let expected = SnapshotChanges {
+        added: diff.summary.total_added as u64,
+        modified: diff.summary.total_modified as u64,
+        deleted: diff.summary.total_deleted as u64,
+    };
+    assert_eq!(
+        expected,
+        SnapshotChanges {
+            added: 2,
+            modified: 1,
+            deleted: 1,
+        }
+    );
+    assert_eq!(second_entry.changes, Some(expected));
+    assert_eq!(second_entry.changes_count, Some(4));
+}
+
+#[tokio::test]
+async fn test_verification_flags_index_drift() {
+    let env = TestBackupEnv::new().unwrap();
+    let dest = env.dest_path.to_str().unwrap();
+    create_manifest(dest).await;
+    let path = snapshot_dir(&env, "2024-01-01-120000");
+
+    snapshot_commit::configure_verification(true);
+    let indexed = snapshot_commit::commit_snapshot(
+        dest,
+        JOB_ID,
+        ManifestSnapshot::from_timestamp(
+            1704110400000,
+            "2024-01-01-120000".to_string(),
+            5,
+            100,
+            ManifestSnapshotStatus::Complete,
+        ),
+        &path,
+    )
+    .await
+    .unwrap();
+    snapshot_commit::configure_verification(false);
+
+    // Index and folder agree, so nothing is flagged
+    let manifest = manifest_service::read_manifest(dest)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(manifest.snapshots[0].index_drift, None);
+
+    let indexed_files = indexed.file_count as u64;
+    let root = std::path::Path::new(&path);
+    assert_eq!(
+        snapshot_commit::check_index_drift(indexed_files, root),
+        None
+    );
+
+    // An index count that is off, either way, is reported against the disk
+    assert_eq!(
+        snapshot_commit::check_index_drift(indexed_files + 3, root),
+        Some(IndexDrift {
+            indexed_files: indexed_files + 3,
+            disk_files: indexed_files,
+        })
+    );
+    generate::file(&root.join("documents/late.txt"), b"written after indexing").unwrap();
+    assert_eq!(
+        snapshot_commit::check_index_drift(indexed_files, root),
+        Some(IndexDrift {
+            indexed_files,
+            disk_files: indexed_files + 1,
+        })
+    );
+}
+
+#[tokio::test]
+async fn test_crash_before_manifest_write_is_reconciled() {
+    let env = TestBackupEnv::new().unwrap();
+    let dest = env.dest_path.to_str().unwrap();
+    create_manifest(dest).await;
+
+    // Crashed after the index commit: indexed, pending, not in the manifest
+    let crashed = snapshot_dir(&env, "2024-01-02-120000");
+    let index = IndexService::for_destination(dest).unwrap();
+    let pending = index
+        .index_snapshot_pending(JOB_ID, 1704196800000, &crashed)
+        .unwrap();
+
+    // Crashed after the manifest write but before the flag was cleared
+    let written = snapshot_dir(&env, "2024-01-03-120000");
+    index
+        .index_snapshot_pending(JOB_ID, 1704283200000, &written)
+        .unwrap();
+    manifest_service::add_snapshot_to_manifest(
+        dest,
+        ManifestSnapshot::from_timestamp(
+            1704283200000,
+            "2024-01-03-120000".to_string(),
+            5,
+            100,
+            ManifestSnapshotStatus::Complete,
+        ),
+    )
+    .await
+    .unwrap();
+
+    // Crashed, and the folder was deleted before the next open
+    let removed = snapshot_dir(&env, "2024-01-04-120000");
+    index
+        .index_snapshot_pending(JOB_ID, 1704369600000, &removed)
+        .unwrap();
+    fs::remove_dir_all(&removed).unwrap();
+    drop(index);
+
+    // The inconsistency is detectable
+    let index = IndexService::for_destination(dest).unwrap();
+    assert_eq!(index.list_pending_snapshots().unwrap().len(), 3);
+    assert_eq!(manifest_timestamps(dest).await, vec![1704283200000]);
+
+    let report = snapshot_commit::reconcile_pending(dest).await.unwrap();
+    assert_eq!(
+        report,
+        ReconcileReport {
+            confirmed: vec![1704283200000],
+            restored: vec![1704196800000],
+            dropped: vec![1704369600000],
+        }
+    );
+
+    // Index and manifest agree again
+    assert!(index.list_pending_snapshots().unwrap().is_empty());
+    assert!(!index.is_indexed(JOB_ID, 1704369600000).unwrap());
+    assert_eq!(
+        manifest_timestamps(dest).await,
+        vec![1704196800000, 1704283200000]
+    );
+    let manifest = manifest_service::read_manifest(dest)
+        .await
+        .unwrap()
+        .unwrap();
+    let restored = manifest
+        .snapshots
+        .iter()
+        .find(|s| s.timestamp == 1704196800000)
+        .unwrap();
+    assert_eq!(restored.folder_name, "2024-01-02-120000");
+    assert_eq!(restored.file_count, pending.file_count as u64);
+    assert_eq!(restored.total_size, pending.total_size as u64);
+
+    // Running again is a no-op
+    assert!(snapshot_commit::reconcile_pending(dest)
+        .await
+        .unwrap()
+        .is_empty());
+}
+
+#[tokio::test]
+async fn test_listing_snapshots_reconciles_pending() {
+    let env = TestBackupEnv::new().unwrap();
+    let dest = env.dest_path.to_str().unwrap();
+    create_manifest(dest).await;
+    let crashed = snapshot_dir(&env, "2024-01-02-120000");
+    IndexService::for_destination(dest)
+        .unwrap()
+        .index_snapshot_pending(JOB_ID, 1704196800000, &crashed)
+        .unwrap();
+
+    let service = SnapshotService::new(env.temp_dir.path());
+    let snapshots = service.list_snapshots(JOB_ID, dest).await.unwrap();
+    assert_eq!(snapshots.len(), 1);
+    assert_eq!(manifest_timestamps(dest).await, vec![1704196800000]);
+    assert!(IndexService::for_destination(dest)
+        .unwrap()
+        .list_pending_snapshots()
+        .unwrap()
+        .is_empty());
+}
@@ -0,0 +1,219 @@
+//! Integration tests for snapshot archive export
+//!
+//! Builds a snapshot folder with a manifest entry, exports it as tar and zip,
+//! and extracts the archive again to check the contents round-trip.
+
+use crate::common::test_common::{generate, verify, TestBackupEnv};
+use app_lib::error::AmberError;
+use app_lib::services::cancel_token::CancelToken;
+use app_lib::services::manifest_service;
+use app_lib::services::snapshot_export::{self, ArchiveFormat, ExportProgress};
+use app_lib::types::manifest::{ManifestSnapshot, ManifestSnapshotStatus};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+const JOB_ID: &str = "export-job";
+const TS: i64 = 1704110400000;
+
+/// A snapshot folder on the destination with its manifest entry
+async fn fixture_snapshot(env: &TestBackupEnv) -> PathBuf {
+    let snapshot = env.snapshot_path("2024-01-01-120000");
+    generate::simple_backup_structure(&snapshot).unwrap();
+    generate::file_with_mode(&snapshot.join("bin/run.sh"), b"#!/bin/sh\n", 0o755).unwrap();
+    generate::empty_dir(&snapshot.join("empty")).unwrap();
+    #[cfg(unix)]
+    std::os::unix::fs::symlink("config.json", snapshot.join("config-link.json")).unwrap();
+    // Stray metadata inside the snapshot must not reach the archive
+    generate::file(&snapshot.join(".amber-meta/index.db"), b"not for export").unwrap();
+
+    let dest = env.dest_path.to_str().unwrap();
+    manifest_service::get_or_create_manifest(dest, JOB_ID, "Export", "/src")
+        .await
+        .unwrap();
+    let entry = ManifestSnapshot::from_timestamp(
+        TS,
+        "2024-01-01-120000".to_string(),
+        6,
+        100,
+        ManifestSnapshotStatus::Complete,
+    );
+    manifest_service::add_snapshot_to_manifest(dest, entry)
+        .await
+        .unwrap();
+    snapshot
+}
+
+async fn export(
+    env: &TestBackupEnv,
+    name: &str,
+    format: ArchiveFormat,
+) -> (PathBuf, Vec<ExportProgress>) {
+    let out = env.temp_dir.path().join(name);
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let sink = seen.clone();
+    let exported = snapshot_export::export_snapshot_archive(
+        JOB_ID,
+        TS,
+        env.dest_path.to_str().unwrap(),
+        out.to_str().unwrap(),
+        format,
+        CancelToken::new(),
+        move |p| sink.lock().unwrap().push(p.clone()),
+    )
+    .await
+    .unwrap();
+    assert_eq!(exported.format, format);
+    assert!(out.is_file());
+    let progress = seen.lock().unwrap().clone();
+    (out, progress)
+}
+
+/// The snapshot as it should come back out: everything but `.amber-meta`
+fn expected_tree(snapshot: &std::path::Path, env: &TestBackupEnv) -> PathBuf {
+    let expected = env.temp_dir.path().join("expected");
+    crate::common::test_common::copy_dir_recursive(snapshot, &expected).unwrap();
+    fs::remove_dir_all(expected.join(".amber-meta")).unwrap();
+    expected
+}
+
+#[tokio::test]
+async fn test_export_tar_round_trips_contents() {
+    let env = TestBackupEnv::new().unwrap();
+    let snapshot = fixture_snapshot(&env).await;
+    let (archive, progress) = export(&env, "snapshot.tar", ArchiveFormat::Tar).await;
+
+    let extracted = env.temp_dir.path().join("from-tar");
+    fs::create_dir_all(&extracted).unwrap();
+    let mut tar = tar::Archive::new(fs::File::open(&archive).unwrap());
+    tar.set_preserve_permissions(true);
+    tar.unpack(&extracted).unwrap();
+
+    let expected = expected_tree(&snapshot, &env);
+    let diff = verify::compare_directories(&expected, &extracted).unwrap();
+    assert!(diff.is_identical(), "Archive differs: {:?}", diff);
+    assert!(extracted.join("empty").is_dir());
+    assert!(!extracted.join(".amber-meta").exists());
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = fs::metadata(extracted.join("bin/run.sh"))
+            .unwrap()
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o777, 0o755);
+        let link = extracted.join("config-link.json");
+        assert!(fs::symlink_metadata(&link)
+            .unwrap()
+            .file_type()
+            .is_symlink());
+        assert_eq!(fs::read_link(&link).unwrap(), PathBuf::from("config.json"));
+    }
+
+    let last = progress.last().unwrap();
+    assert_eq!(last.entries_done, last.entries_total);
+    assert_eq!(last.bytes_done, last.bytes_total);
+    assert!(progress
+        .windows(2)
+        .all(|w| w[0].entries_done <= w[1].entries_done));
+}
+
+#[tokio::test]
+async fn test_export_zip_round_trips_contents() {
+    let env = TestBackupEnv::new().unwrap();
+    let snapshot = fixture_snapshot(&env).await;
+    let (archive, progress) = export(&env, "snapshot.zip", ArchiveFormat::Zip).await;
+
+    let extracted = env.temp_dir.path().join("from-zip");
+    fs::create_dir_all(&extracted).unwrap();
+    let mut zip = zip::ZipArchive::new(fs::File::open(&archive).unwrap()).unwrap();
+    assert!(zip.file_names().all(|n| !n.starts_with(".amber-meta")));
+    zip.extract(&extracted).unwrap();
+
+    let expected = expected_tree(&snapshot, &env);
+    let diff = verify::compare_directories(&expected, &extracted).unwrap();
+    assert!(diff.is_identical(), "Archive differs: {:?}", diff);
+    assert!(extracted.join("empty").is_dir());
+
+    let last = progress.last().unwrap();
+    assert_eq!(last.entries_done, last.entries_total);
+}
+
+#[tokio::test]
+async fn test_export_cancelled_removes_partial_archive() {
+    let env = TestBackupEnv::new().unwrap();
+    fixture_snapshot(&env).await;
+    let out = env.temp_dir.path().join("cancelled.tar");
+
+    let cancel = CancelToken::new();
+    let trigger = cancel.clone();
+    let result = snapshot_export::export_snapshot_archive(
+        JOB_ID,
+        TS,
+        env.dest_path.to_str().unwrap(),
+        out.to_str().unwrap(),
+        ArchiveFormat::Tar,
+        cancel,
+        move |_| trigger.cancel(),
+    )
+    .await;
+
+    assert!(matches!(result, Err(AmberError::Cancelled)));
+    assert!(!out.exists());
+}
+
+#[tokio::test]
+async fn test_failed_export_keeps_the_existing_file() {
+    let env = TestBackupEnv::new().unwrap();
+    fixture_snapshot(&env).await;
+    let out = env.temp_dir.path().join("existing.zip");
+    fs::write(&out, b"an older archive").unwrap();
+
+    let cancel = CancelToken::new();
+    let trigger = cancel.clone();
+    let result = snapshot_export::export_snapshot_archive(
+        JOB_ID,
+        TS,
+        env.dest_path.to_str().unwrap(),
+        out.to_str().unwrap(),
+        ArchiveFormat::Zip,
+        cancel,
+        move |_| trigger.cancel(),
+    )
+    .await;
+
+    assert!(matches!(result, Err(AmberError::Cancelled)));
+    assert_eq!(fs::read(&out).unwrap(), b"an older archive");
+    let leftovers: Vec<_> = fs::read_dir(env.temp_dir.path())
+        .unwrap()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_name().to_string_lossy().ends_with(".partial"))
+        .collect();
+    assert!(leftovers.is_empty());
+
+    // A finished export replaces it
+    let (archive, _) = export(&env, "existing.zip", ArchiveFormat::Zip).await;
+    assert!(zip::ZipArchive::new(fs::File::open(&archive).unwrap()).is_ok());
+}
+
+#[tokio::test]
+async fn test_export_rejects_archive_inside_snapshot() {
+    let env = TestBackupEnv::new().unwrap();
+    let snapshot = fixture_snapshot(&env).await;
+    let out = snapshot.join("documents/self.zip");
+
+    let result = snapshot_export::export_snapshot_archive(
+        JOB_ID,
+        TS,
+        env.dest_path.to_str().unwrap(),
+        out.to_str().unwrap(),
+        ArchiveFormat::Zip,
+        CancelToken::new(),
+        |_| {},
+    )
+    .await;
+
+    assert!(matches!(result, Err(AmberError::ValidationError(_))));
+    assert!(!out.exists());
+}
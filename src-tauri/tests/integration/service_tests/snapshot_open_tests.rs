@@ -0,0 +1,160 @@
+//! Integration tests for opening single files from a snapshot
+
+use crate::common::test_common::{generate, TestBackupEnv};
+use app_lib::error::AmberError;
+use app_lib::services::index_service::IndexService;
+use app_lib::services::manifest_service;
+use app_lib::services::snapshot_open;
+use app_lib::types::manifest::{ManifestSnapshot, ManifestSnapshotStatus};
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+
+const JOB_ID: &str = "open-job";
+// 2024-01-01-120000
+const TIMESTAMP: i64 = 1704110400000;
+
+/// One snapshot, recorded and indexed; returns its folder
+async fn fixture(env: &TestBackupEnv) -> PathBuf {
+    let dest = env.dest_path.to_str().unwrap();
+    manifest_service::get_or_create_manifest(dest, JOB_ID, "Open", "/src")
+        .await
+        .unwrap();
+    let path = env.snapshot_path("2024-01-01-120000");
+    generate::simple_backup_structure(&path).unwrap();
+    let entry = ManifestSnapshot::from_timestamp(
+        TIMESTAMP,
+        "2024-01-01-120000".to_string(),
+        5,
+        100,
+        ManifestSnapshotStatus::Complete,
+    );
+    manifest_service::add_snapshot_to_manifest(dest, entry)
+        .await
+        .unwrap();
+    IndexService::for_destination(dest)
+        .unwrap()
+        .index_snapshot(JOB_ID, TIMESTAMP, path.to_str().unwrap())
+        .unwrap();
+    path
+}
+
+#[tokio::test]
+async fn test_copy_is_identical_and_registered_for_cleanup() {
+    let env = TestBackupEnv::new().unwrap();
+    let snapshot = fixture(&env).await;
+    let extract_root = env.temp_dir.path().join("extracted");
+
+    let copy = snapshot_open::open_file_from_snapshot(
+        JOB_ID,
+        TIMESTAMP,
+        env.dest_path.to_str().unwrap(),
+        "/documents/readme.txt",
+        &extract_root,
+    )
+    .await
+    .unwrap();
+
+    assert!(copy.starts_with(&extract_root));
+    assert_eq!(copy.file_name().unwrap(), "readme.txt");
+    assert_eq!(
+        std::fs::read(&copy).unwrap(),
+        std::fs::read(snapshot.join("documents/readme.txt")).unwrap()
+    );
+    assert!(snapshot_open::is_registered(&copy));
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        for dir in [extract_root.as_path(), copy.parent().unwrap()] {
+            let mode = std::fs::metadata(dir).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o700, "{}", dir.display());
+        }
+    }
+
+    // Not expired yet
+    assert_eq!(
+        snapshot_open::cleanup_extractions(
+            &extract_root,
+            Duration::from_secs(3600),
+            SystemTime::now()
+        ),
+        0
+    );
+    assert!(copy.exists());
+
+    let later = SystemTime::now() + Duration::from_secs(2 * 3600);
+    assert_eq!(
+        snapshot_open::cleanup_extractions(&extract_root, Duration::from_secs(3600), later),
+        1
+    );
+    assert!(!copy.exists());
+    assert!(!snapshot_open::is_registered(&copy));
+}
+
+#[tokio::test]
+async fn test_path_must_be_in_index_and_on_disk() {
+    let env = TestBackupEnv::new().unwrap();
+    let snapshot = fixture(&env).await;
+    let dest = env.dest_path.to_str().unwrap();
+    let extract_root = env.temp_dir.path().join("extracted");
+
+    // On disk but never indexed
+    generate::file(&snapshot.join("late.txt"), b"added after indexing").unwrap();
+    let result =
+        snapshot_open::open_file_from_snapshot(JOB_ID, TIMESTAMP, dest, "late.txt", &extract_root)
+            .await;
+    assert!(matches!(result, Err(AmberError::NotFound(_))));
+
+    // Indexed but gone from disk
+    std::fs::remove_file(snapshot.join("config.json")).unwrap();
+    let result = snapshot_open::open_file_from_snapshot(
+        JOB_ID,
+        TIMESTAMP,
+        dest,
+        "config.json",
+        &extract_root,
+    )
+    .await;
+    assert!(matches!(result, Err(AmberError::NotFound(_))));
+
+    let result = snapshot_open::open_file_from_snapshot(
+        JOB_ID,
+        TIMESTAMP,
+        dest,
+        "../outside.txt",
+        &extract_root,
+    )
+    .await;
+    assert!(matches!(result, Err(AmberError::InvalidPath(_))));
+
+    let result =
+        snapshot_open::open_file_from_snapshot(JOB_ID, TIMESTAMP, dest, "documents", &extract_root)
+            .await;
+    assert!(result.is_err());
+    assert!(!extract_root.exists());
+}
+
+#[cfg(target_os = "linux")]
+#[tokio::test]
+async fn test_decomposed_name_opens_on_linux() {
+    let env = TestBackupEnv::new().unwrap();
+    let snapshot = fixture(&env).await;
+    let dest = env.dest_path.to_str().unwrap();
+    // Written decomposed, as macOS does; listed and requested composed
+    generate::file(&snapshot.join("documents/cafe\u{301}.txt"), b"menu").unwrap();
+    IndexService::for_destination(dest)
+        .unwrap()
+        .index_snapshot(JOB_ID, TIMESTAMP, snapshot.to_str().unwrap())
+        .unwrap();
+    let extract_root = env.temp_dir.path().join("extracted");
+
+    let copy = snapshot_open::open_file_from_snapshot(
+        JOB_ID,
+        TIMESTAMP,
+        dest,
+        "/documents/caf\u{e9}.txt",
+        &extract_root,
+    )
+    .await
+    .unwrap();
+    assert_eq!(std::fs::read(&copy).unwrap(), b"menu");
+}
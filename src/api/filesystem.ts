@@ -6,13 +6,20 @@
 import { invoke } from '@tauri-apps/api/core';
 import { open } from '@tauri-apps/plugin-dialog';
 import { desktopDir } from '@tauri-apps/api/path';
-import type { ReadDirEntry, FileNode, VolumeInfo, MountStatus } from '../types';
+import type {
+  DiskStats,
+  ReadDirEntry,
+  ReadDirOptions,
+  FileNode,
+  VolumeInfo,
+  MountStatus,
+} from '../types';
 import { getErrorMessage } from '../types';
 
 // ===== Filesystem =====
 
-export async function readDir(path: string): Promise<ReadDirEntry[]> {
-  return invoke('read_dir', { path });
+export async function readDir(path: string, options?: ReadDirOptions): Promise<ReadDirEntry[]> {
+  return invoke('read_dir', { path, options });
 }
 
 export async function selectDirectory(): Promise<string | null> {
@@ -30,19 +37,26 @@ export async function showItemInFolder(path: string): Promise<void> {
 
 export async function getDiskStats(path: string): Promise<{
   success: boolean;
-  stats?: { total: number; free: number; status: 'AVAILABLE' | 'UNAVAILABLE' };
+  stats?: DiskStats;
   error?: string;
 }> {
   try {
-    const result = await invoke<{ totalBytes: number; availableBytes: number }>('get_volume_info', {
-      path,
-    });
+    const result = await invoke<{
+      totalBytes: number;
+      availableBytes: number;
+      mountPoint: string | null;
+      fsType: string | null;
+      isNetwork: boolean;
+    }>('get_disk_stats', { path });
     return {
       success: true,
       stats: {
         total: result.totalBytes,
         free: result.availableBytes,
         status: 'AVAILABLE',
+        mountPoint: result.mountPoint ?? undefined,
+        fsType: result.fsType ?? undefined,
+        isNetwork: result.isNetwork,
       },
     };
   } catch (e: unknown) {
@@ -50,7 +64,19 @@ export async function getDiskStats(path: string): Promise<{
   }
 }
 
-export async function readFilePreview(filePath: string, maxLines?: number): Promise<string> {
+/**
+ * Whether TimeMachine snapshots at path can share unchanged files via
+ * hardlinks (false on exFAT, FAT and some SMB shares). Warn before picking
+ * TimeMachine mode for a destination that returns false.
+ */
+export async function checkHardlinkSupport(path: string): Promise<boolean> {
+  return invoke('check_hardlink_support', { path });
+}
+
+export async function readFilePreview(
+  filePath: string,
+  maxLines?: number
+): Promise<{ content: string; truncated: boolean }> {
   return invoke('read_file_preview', { filePath, maxLines });
 }
 
@@ -33,8 +33,13 @@ export const api = {
   getJobs: jobs.getJobs,
   getJobsWithStatus: jobs.getJobsWithStatus,
   saveJob: jobs.saveJob,
+  setJobEnabled: jobs.setJobEnabled,
   deleteJob: jobs.deleteJob,
+  renameJobId: jobs.renameJobId,
   deleteJobData: jobs.deleteJobData,
+  previewExcludes: jobs.previewExcludes,
+  testSshDestination: jobs.testSshDestination,
+  validateJob: jobs.validateJob,
   scanForBackups: jobs.scanForBackups,
   findOrphanBackups: jobs.findOrphanBackups,
   importBackupAsJob: jobs.importBackupAsJob,
@@ -46,6 +51,7 @@ export const api = {
   onRsyncProgress: rsync.onRsyncProgress,
   onRsyncComplete: rsync.onRsyncComplete,
   onRsyncStarted: rsync.onRsyncStarted,
+  checkRsync: rsync.checkRsync,
   checkRclone: rsync.checkRclone,
   listRcloneRemotes: rsync.listRcloneRemotes,
   runRclone: rsync.runRclone,
@@ -57,6 +63,7 @@ export const api = {
   openPath: filesystem.openPath,
   showItemInFolder: filesystem.showItemInFolder,
   getDiskStats: filesystem.getDiskStats,
+  checkHardlinkSupport: filesystem.checkHardlinkSupport,
   readFilePreview: filesystem.readFilePreview,
   readFileAsBase64: filesystem.readFileAsBase64,
   getDesktopPath: filesystem.getDesktopPath,
@@ -70,21 +77,37 @@ export const api = {
   listSnapshotsInRange: snapshots.listSnapshotsInRange,
   listSnapshotsInRangeOnDestination: snapshots.listSnapshotsInRangeOnDestination,
   getJobAggregateStats: snapshots.getJobAggregateStats,
+  getJobLogicalFootprint: snapshots.getJobLogicalFootprint,
+  getOverview: snapshots.getOverview,
   getJobAggregateStatsOnDestination: snapshots.getJobAggregateStatsOnDestination,
   getSnapshotDensity: snapshots.getSnapshotDensity,
   getSnapshotDensityOnDestination: snapshots.getSnapshotDensityOnDestination,
   getSnapshotTree: snapshots.getSnapshotTree,
+  restoreEstimate: snapshots.restoreEstimate,
   restoreFiles: snapshots.restoreFiles,
+  cancelRestore: snapshots.cancelRestore,
   restoreSnapshot: snapshots.restoreSnapshot,
   indexSnapshot: snapshots.indexSnapshot,
+  pinSourceState: snapshots.pinSourceState,
   isSnapshotIndexed: snapshots.isSnapshotIndexed,
   getIndexedDirectory: snapshots.getIndexedDirectory,
   getIndexedDirectoryPaginated: snapshots.getIndexedDirectoryPaginated,
+  getIndexedDirectoryPaged: snapshots.getIndexedDirectoryPaged,
+  getSubtree: snapshots.getSubtree,
+  getBreadcrumbs: snapshots.getBreadcrumbs,
   searchSnapshotFiles: snapshots.searchSnapshotFiles,
+  searchFilesJob: snapshots.searchFilesJob,
+  findPathInSnapshots: snapshots.findPathInSnapshots,
+  getHardlinkGroups: snapshots.getHardlinkGroups,
   searchFilesGlobal: snapshots.searchFilesGlobal,
+  getSearchDiacriticFolding: snapshots.getSearchDiacriticFolding,
   getSnapshotStats: snapshots.getSnapshotStats,
   getFileTypeStats: snapshots.getFileTypeStats,
+  getSizeHistogram: snapshots.getSizeHistogram,
+  getExtensionGrowth: snapshots.getExtensionGrowth,
   getLargestFiles: snapshots.getLargestFiles,
+  getRecentFiles: snapshots.getRecentFiles,
+  getFlaggedFiles: snapshots.getFlaggedFiles,
   deleteSnapshotIndex: snapshots.deleteSnapshotIndex,
   deleteJobIndex: snapshots.deleteJobIndex,
   getDestinationIndexPath: snapshots.getDestinationIndexPath,
@@ -98,12 +121,27 @@ export const api = {
   getLargestFilesOnDestination: snapshots.getLargestFilesOnDestination,
   deleteSnapshotFromDestination: snapshots.deleteSnapshotFromDestination,
   compareSnapshots: snapshots.compareSnapshots,
+  compareSnapshotsPage: snapshots.compareSnapshotsPage,
+  listDeletedBetween: snapshots.listDeletedBetween,
+  compareSourceToSnapshot: snapshots.compareSourceToSnapshot,
+  diffDirectories: snapshots.diffDirectories,
+  compareSnapshotsStream: snapshots.compareSnapshotsStream,
   pruneSnapshot: snapshots.pruneSnapshot,
+  pruneSnapshots: snapshots.pruneSnapshots,
+  replicateSnapshot: snapshots.replicateSnapshot,
+  exportSnapshotArchive: snapshots.exportSnapshotArchive,
+  cancelSnapshotExport: snapshots.cancelSnapshotExport,
+  openFileFromSnapshot: snapshots.openFileFromSnapshot,
+  indexAllMissing: snapshots.indexAllMissing,
+  cancelIndexAllMissing: snapshots.cancelIndexAllMissing,
+  compactAllDestinations: snapshots.compactAllDestinations,
+  rebuildSearchIndex: snapshots.rebuildSearchIndex,
 
   // ===== System & Preferences =====
   getPreferences: system.getPreferences,
   setPreferences: system.setPreferences,
   testNotification: system.testNotification,
+  runIndexBenchmarks: system.runIndexBenchmarks,
   isDev: system.isDev,
   devSeedData: system.devSeedData,
   devRunBenchmarks: system.devRunBenchmarks,
@@ -118,6 +156,7 @@ export const api = {
   getAmberMetaPath: system.getAmberMetaPath,
   needsMigration: system.needsMigration,
   runMigration: system.runMigration,
+  cancelOperation: system.cancelOperation,
 
   // ===== Runtime Info =====
   get runtime(): 'tauri' {
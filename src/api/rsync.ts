@@ -21,8 +21,12 @@ export type RsyncStartedCallback = (data: RsyncStartedPayload) => void;
 
 // ===== Rsync Operations =====
 
-export async function runRsync(job: SyncJob): Promise<void> {
-  return invoke('run_rsync', { job });
+/**
+ * Back up a job. A job with `maxDelete` is refused when a dry run would delete
+ * more than that; pass `allowMassDelete` to run it anyway.
+ */
+export async function runRsync(job: SyncJob, allowMassDelete?: boolean): Promise<void> {
+  return invoke('run_rsync', { job, allowMassDelete });
 }
 
 export async function killRsync(jobId: string): Promise<void> {
@@ -66,6 +70,13 @@ export function onRsyncStarted(callback: RsyncStartedCallback): () => void {
   return safeEventListener<RsyncStartedPayload>('rsync-started', callback);
 }
 
+/**
+ * Check that rsync is installed. Rejects with install instructions if it isn't.
+ */
+export async function checkRsync(): Promise<{ installed: boolean; version?: string }> {
+  return invoke('check_rsync');
+}
+
 // ===== Rclone (Cloud Backup) =====
 
 /**
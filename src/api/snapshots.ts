@@ -11,12 +11,34 @@ import type {
   FileNode,
   IndexedDirEntry,
   GlobalSearchResult,
+  JobSearchResult,
+  PathOccurrence,
+  HardlinkGroup,
+  FileFlag,
+  FileCategory,
   FileTypeStats,
+  SizeBucket,
+  ExtensionGrowthPoint,
   LargestFile,
   JobAggregateStats,
+  JobLogicalFootprint,
+  Overview,
   SnapshotDensity,
   DirectoryContents,
+  SortBy,
+  Subtree,
+  Crumb,
+  RestoreEstimate,
+  ConflictStrategy,
   SnapshotDiff,
+  DiffPageRequest,
+  DiffPage,
+  DeletedFiles,
+  CompactionResult,
+  RetentionPolicy,
+  PrunedSnapshot,
+  SourceComparison,
+  DirectoryDiff,
 } from '../types';
 import { getErrorMessage } from '../types';
 
@@ -63,6 +85,20 @@ export async function getJobAggregateStats(jobId: string): Promise<JobAggregateS
   return invoke('get_job_aggregate_stats', { jobId });
 }
 
+/**
+ * Estimate unique content across all snapshots of a job (shared files count once)
+ */
+export async function getJobLogicalFootprint(jobId: string): Promise<JobLogicalFootprint> {
+  return invoke('get_job_logical_footprint', { jobId });
+}
+
+/**
+ * Totals, last backups and overdue schedules across every job
+ */
+export async function getOverview(): Promise<Overview> {
+  return invoke('get_overview');
+}
+
 /**
  * Get aggregate statistics for a job from destination's index
  */
@@ -103,20 +139,44 @@ export async function getSnapshotTree(
   return invoke('get_snapshot_tree', { jobId, timestamp, snapshotPath });
 }
 
+/**
+ * Files and bytes restoring `paths` will write, counted from the index.
+ * Selected directories include everything below them.
+ */
+export async function restoreEstimate(
+  jobId: string,
+  timestamp: number,
+  paths: string[]
+): Promise<RestoreEstimate> {
+  return invoke('restore_estimate', { jobId, timestamp, paths });
+}
+
+/**
+ * Restore selected files and folders. conflict decides what happens to files
+ * already at the target (default 'overwrite'). With the restoreWorkers
+ * preference set, files are copied in parallel and `restore-progress` events
+ * report progress.
+ */
 export async function restoreFiles(
   job: SyncJob,
   snapshotPath: string,
   files: string[],
-  targetPath: string
+  targetPath: string,
+  conflict?: ConflictStrategy
 ): Promise<{ success: boolean; error?: string }> {
   try {
-    await invoke('restore_files', { jobId: job.id, snapshotPath, files, targetPath });
+    await invoke('restore_files', { jobId: job.id, snapshotPath, files, targetPath, conflict });
     return { success: true };
   } catch (e: unknown) {
     return { success: false, error: getErrorMessage(e) };
   }
 }
 
+/** Cancel a running parallel restore; false if none was running */
+export async function cancelRestore(jobId: string): Promise<boolean> {
+  return invoke('cancel_restore', { jobId });
+}
+
 export async function restoreSnapshot(
   job: SyncJob,
   snapshotPath: string,
@@ -135,13 +195,26 @@ export async function restoreSnapshot(
 
 /**
  * Index a snapshot for fast browsing (call after backup completes)
+ * Cancel with cancelOperation(`index:${jobId}:${timestamp}`)
+ * @param forceReplace - replace a different folder already indexed at this timestamp
+ * @param hash - also store content hashes, so same-size edits show as modified (slow)
  */
 export async function indexSnapshot(
   jobId: string,
   timestamp: number,
-  snapshotPath: string
+  snapshotPath: string,
+  forceReplace?: boolean,
+  hash?: boolean
 ): Promise<IndexedSnapshot> {
-  return invoke('index_snapshot', { jobId, timestamp, snapshotPath });
+  return invoke('index_snapshot', { jobId, timestamp, snapshotPath, forceReplace, hash });
+}
+
+/**
+ * Record the job's source listing as a metadata-only snapshot (no backup run)
+ * It can be compared against other snapshots but not restored from.
+ */
+export async function pinSourceState(jobId: string): Promise<IndexedSnapshot> {
+  return invoke('pin_source_state', { jobId });
 }
 
 /**
@@ -163,14 +236,18 @@ export async function getIndexedDirectory(
 }
 
 /**
- * Get directory contents from SQLite index with pagination (for large directories)
+ * Get directory contents from SQLite index with pagination (for large directories).
+ * `modifiedAfter` (ms) hides files not changed after it and `typeFilter` files
+ * outside the category; directories stay listed.
  */
 export async function getIndexedDirectoryPaginated(
   jobId: string,
   timestamp: number,
   parentPath: string,
   limit?: number,
-  offset?: number
+  offset?: number,
+  modifiedAfter?: number,
+  typeFilter?: FileCategory
 ): Promise<DirectoryContents> {
   return invoke('get_indexed_directory_paginated', {
     jobId,
@@ -178,19 +255,113 @@ export async function getIndexedDirectoryPaginated(
     parentPath,
     limit,
     offset,
+    modifiedAfter,
+    typeFilter,
+  });
+}
+
+/**
+ * One page of a directory in the given order, sorted and paged server-side.
+ * `totalCount` covers the whole directory.
+ */
+export async function getIndexedDirectoryPaged(
+  jobId: string,
+  timestamp: number,
+  parentPath: string,
+  sort?: SortBy,
+  offset?: number,
+  limit?: number
+): Promise<DirectoryContents> {
+  return invoke('get_indexed_directory_paged', {
+    jobId,
+    timestamp,
+    parentPath,
+    sort,
+    offset,
+    limit,
   });
 }
 
 /**
- * Search files in a snapshot by pattern
+ * Descendants of a directory down to `depth` levels, nested (depth 1 is the
+ * plain listing). Expansion stops at `maxNodes`, capped server-side.
+ */
+export async function getSubtree(
+  jobId: string,
+  timestamp: number,
+  parentPath: string,
+  depth: number,
+  maxNodes?: number
+): Promise<Subtree> {
+  return invoke('get_subtree', { jobId, timestamp, parentPath, depth, maxNodes });
+}
+
+/**
+ * Breadcrumbs from the snapshot root down to a browsed directory
+ */
+export async function getBreadcrumbs(
+  jobId: string,
+  timestamp: number,
+  parentPath: string
+): Promise<Crumb[]> {
+  return invoke('get_breadcrumbs', { jobId, timestamp, parentPath });
+}
+
+/**
+ * Search files in a snapshot by pattern.
+ * `modifiedAfter` (ms) keeps only files changed after it and `typeFilter` only
+ * files of that category; directories always match.
  */
 export async function searchSnapshotFiles(
   jobId: string,
   timestamp: number,
   pattern: string,
-  limit?: number
+  limit?: number,
+  modifiedAfter?: number,
+  typeFilter?: FileCategory
 ): Promise<FileNode[]> {
-  return invoke('search_snapshot_files', { jobId, timestamp, pattern, limit });
+  return invoke('search_snapshot_files', {
+    jobId,
+    timestamp,
+    pattern,
+    limit,
+    modifiedAfter,
+    typeFilter,
+  });
+}
+
+/**
+ * Groups of files in a snapshot that are hard links of each other, largest first
+ */
+export async function getHardlinkGroups(
+  jobId: string,
+  timestamp: number
+): Promise<HardlinkGroup[]> {
+  return invoke('get_hardlink_groups', { jobId, timestamp });
+}
+
+/**
+ * Search files in every snapshot of one job, so files deleted since are still
+ * found. With `collapse`, each path appears once with all its snapshots.
+ */
+export async function searchFilesJob(
+  jobId: string,
+  pattern: string,
+  limit?: number,
+  collapse?: boolean
+): Promise<JobSearchResult[]> {
+  return invoke('search_files_job', { jobId, pattern, limit, collapse });
+}
+
+/**
+ * Every snapshot of a job that holds `path` (relative to the snapshot root),
+ * newest first, with the size and mtime it had in each
+ */
+export async function findPathInSnapshots(
+  jobId: string,
+  path: string
+): Promise<PathOccurrence[]> {
+  return invoke('find_path_in_snapshots', { jobId, path });
 }
 
 /**
@@ -205,6 +376,14 @@ export async function searchFilesGlobal(
   return invoke('search_files_global', { pattern, jobId, limit });
 }
 
+/**
+ * Whether global search folds diacritics ("cafe" also finds "café") for a
+ * job's index, or the local index when no job is given
+ */
+export async function getSearchDiacriticFolding(jobId?: string): Promise<boolean> {
+  return invoke('get_search_diacritic_folding', { jobId });
+}
+
 /**
  * Get snapshot statistics from index
  */
@@ -230,6 +409,28 @@ export async function getFileTypeStats(
   return invoke('get_file_type_stats', { jobId, timestamp, limit });
 }
 
+/**
+ * Count and size of a snapshot's files per size range. buckets are the bounds
+ * between ranges in bytes; defaults to <1K, 1K–1M, 1M–100M, >=100M
+ */
+export async function getSizeHistogram(
+  jobId: string,
+  timestamp: number,
+  buckets?: number[]
+): Promise<SizeBucket[]> {
+  return invoke('get_size_histogram', { jobId, timestamp, buckets });
+}
+
+/**
+ * Get count and size of one extension across every snapshot, oldest first
+ */
+export async function getExtensionGrowth(
+  jobId: string,
+  extension: string
+): Promise<ExtensionGrowthPoint[]> {
+  return invoke('get_extension_growth', { jobId, extension });
+}
+
 /**
  * Get largest files in a snapshot (for analytics)
  */
@@ -241,6 +442,30 @@ export async function getLargestFiles(
   return invoke('get_largest_files', { jobId, timestamp, limit });
 }
 
+/**
+ * Get the most recently modified files in a snapshot (newest first)
+ */
+export async function getRecentFiles(
+  jobId: string,
+  timestamp: number,
+  limit?: number
+): Promise<FileNode[]> {
+  return invoke('get_recent_files', { jobId, timestamp, limit });
+}
+
+/**
+ * Get files carrying a derived cleanup flag, largest first
+ * Flags are computed at index time; re-index older snapshots to populate them
+ */
+export async function getFlaggedFiles(
+  jobId: string,
+  timestamp: number,
+  flag: FileFlag,
+  limit?: number
+): Promise<FileNode[]> {
+  return invoke('get_flagged_files', { jobId, timestamp, flag, limit });
+}
+
 /**
  * Delete a snapshot from the index
  */
@@ -293,15 +518,25 @@ export async function indexSnapshotOnDestination(
 }
 
 /**
- * Get directory contents from destination's index
+ * Get directory contents from destination's index.
+ * `modifiedAfter` (ms) hides files not changed after it; directories stay listed.
  */
 export async function getDirectoryFromDestination(
   destPath: string,
   jobId: string,
   timestamp: number,
-  parentPath: string
+  parentPath: string,
+  modifiedAfter?: number,
+  typeFilter?: FileCategory
 ): Promise<IndexedDirEntry[]> {
-  return invoke('get_directory_from_destination', { destPath, jobId, timestamp, parentPath });
+  return invoke('get_directory_from_destination', {
+    destPath,
+    jobId,
+    timestamp,
+    parentPath,
+    modifiedAfter,
+    typeFilter,
+  });
 }
 
 /**
@@ -316,16 +551,27 @@ export async function isIndexedOnDestination(
 }
 
 /**
- * Search files in destination's index
+ * Search files in destination's index.
+ * `modifiedAfter` (ms) keeps only files changed after it; directories always match.
  */
 export async function searchFilesOnDestination(
   destPath: string,
   jobId: string,
   timestamp: number,
   pattern: string,
-  limit?: number
+  limit?: number,
+  modifiedAfter?: number,
+  typeFilter?: FileCategory
 ): Promise<IndexedDirEntry[]> {
-  return invoke('search_files_on_destination', { destPath, jobId, timestamp, pattern, limit });
+  return invoke('search_files_on_destination', {
+    destPath,
+    jobId,
+    timestamp,
+    pattern,
+    limit,
+    modifiedAfter,
+    typeFilter,
+  });
 }
 
 /**
@@ -375,14 +621,207 @@ export async function pruneSnapshot(
   return invoke('prune_snapshot', { destPath, jobId, snapshotId, timestamp });
 }
 
+/**
+ * Delete the job's snapshots on destPath that the policy doesn't keep, with
+ * their index rows and manifest entries. With dryRun only lists them.
+ */
+export async function pruneSnapshots(
+  jobId: string,
+  destPath: string,
+  policy: RetentionPolicy,
+  dryRun?: boolean
+): Promise<PrunedSnapshot[]> {
+  return invoke('prune_snapshots', { jobId, destPath, policy, dryRun });
+}
+
+/**
+ * Copy a snapshot to a second destination, with its manifest entry and index
+ */
+export async function replicateSnapshot(
+  jobId: string,
+  timestamp: number,
+  srcDest: string,
+  dstDest: string
+): Promise<{ snapshotPath: string; indexed: IndexedSnapshot }> {
+  return invoke('replicate_snapshot', { jobId, timestamp, srcDest, dstDest });
+}
+
+/**
+ * Package a snapshot into a single tar or zip archive
+ * Progress arrives as `snapshot-export-progress` events
+ */
+export async function exportSnapshotArchive(
+  jobId: string,
+  timestamp: number,
+  destPath: string,
+  outPath: string,
+  format: 'tar' | 'zip'
+): Promise<{ archivePath: string; format: 'tar' | 'zip'; entryCount: number; totalBytes: number }> {
+  return invoke('export_snapshot_archive', { jobId, timestamp, destPath, outPath, format });
+}
+
+/**
+ * Cancel a running snapshot export; resolves false if none was running
+ */
+export async function cancelSnapshotExport(jobId: string, timestamp: number): Promise<boolean> {
+  return invoke('cancel_snapshot_export', { jobId, timestamp });
+}
+
+/**
+ * Copy one file out of a snapshot into a temp folder and return the copy's path,
+ * ready for `openPath`. Copies are removed automatically after a day.
+ */
+export async function openFileFromSnapshot(
+  jobId: string,
+  timestamp: number,
+  path: string
+): Promise<string> {
+  return invoke('open_file_from_snapshot', { jobId, timestamp, path });
+}
+
+/**
+ * Index every snapshot folder on a destination that its index is missing
+ * Progress arrives as `index-backfill-progress` events
+ */
+export async function indexAllMissing(
+  jobId: string,
+  destPath: string
+): Promise<{
+  indexed: number[];
+  alreadyIndexed: number[];
+  skipped: { folderName: string; reason: string }[];
+}> {
+  return invoke('index_all_missing', { jobId, destPath });
+}
+
+/**
+ * Cancel a running indexAllMissing; resolves false if none was running
+ */
+export async function cancelIndexAllMissing(destPath: string): Promise<boolean> {
+  return invoke('cancel_index_all_missing', { destPath });
+}
+
+/**
+ * Re-tokenize every file of the job's index into its search index, switching
+ * it to the searchFoldDiacritics preference if it differs
+ * Cancel with cancelOperation(`fts-rebuild:${jobId}`)
+ */
+export async function rebuildSearchIndex(jobId: string): Promise<void> {
+  return invoke('rebuild_search_index', { jobId });
+}
+
+/**
+ * Compact the index on each destination (every job's destination if omitted)
+ * Unplugged drives and destinations without an index come back with a note
+ */
+export async function compactAllDestinations(
+  destinations?: string[]
+): Promise<CompactionResult[]> {
+  return invoke('compact_all_destinations', { destinations });
+}
+
 /**
  * TIM-221: Compare two snapshots and return file differences
+ * Pass underPath (relative to the snapshot root) to diff a single folder,
+ * and ignoreGlobs (e.g. "logs", "*.tmp") to leave noisy paths out.
+ * topContributors fills `topContributors` with that many files ranked by
+ * absolute size change, to show what drove the size delta
  */
 export async function compareSnapshots(
   jobId: string,
   timestampA: number,
   timestampB: number,
-  limit?: number
+  limit?: number,
+  underPath?: string,
+  ignoreGlobs?: string[],
+  topContributors?: number
 ): Promise<SnapshotDiff> {
-  return invoke('compare_snapshots', { jobId, timestampA, timestampB, limit });
+  return invoke('compare_snapshots', {
+    jobId,
+    timestampA,
+    timestampB,
+    underPath,
+    ignoreGlobs,
+    limit,
+    topContributors,
+  });
+}
+
+/**
+ * Compare two snapshots without collecting the whole diff
+ * Entries arrive as `snapshot-diff-batch` events (see DiffBatch): all added,
+ * then deleted, then modified, each ordered by path. Renames are not paired.
+ * Resolves with the total number of entries once every batch has been sent.
+ */
+export async function compareSnapshotsStream(
+  jobId: string,
+  timestampA: number,
+  timestampB: number,
+  underPath?: string,
+  ignoreGlobs?: string[]
+): Promise<number> {
+  return invoke('compare_snapshots_stream', {
+    jobId,
+    timestampA,
+    timestampB,
+    underPath,
+    ignoreGlobs,
+  });
+}
+
+/**
+ * Preview what the next backup would add, update and, with --delete, remove
+ * Compares the job's source against its latest indexed snapshot by path,
+ * size and mtime, honouring the job's exclude patterns, without running
+ * rsync. SSH sources are listed over ssh. Resolves to null if the job has no
+ * indexed snapshot.
+ */
+export async function compareSourceToSnapshot(jobId: string): Promise<SourceComparison | null> {
+  return invoke('compare_source_to_snapshot', { jobId });
+}
+
+/**
+ * Compare two directories on disk by relative path and size, without the index
+ * For verifying a restore against its snapshot; each list holds at most limit entries.
+ */
+export async function diffDirectories(
+  pathA: string,
+  pathB: string,
+  limit?: number
+): Promise<DirectoryDiff> {
+  return invoke('diff_directories', { pathA, pathB, limit });
+}
+
+/**
+ * Page through one category of a snapshot diff (ordered by path)
+ * Use with SnapshotDiff.truncated to lazy-load the rest of a long list
+ */
+export async function compareSnapshotsPage(
+  jobId: string,
+  timestampA: number,
+  timestampB: number,
+  page: DiffPageRequest,
+  underPath?: string,
+  ignoreGlobs?: string[]
+): Promise<DiffPage> {
+  return invoke('compare_snapshots_page', {
+    jobId,
+    timestampA,
+    timestampB,
+    page,
+    underPath,
+    ignoreGlobs,
+  });
+}
+
+/**
+ * Files deleted between a last-good snapshot and a later one
+ * Pass `snapshotPath` and the file paths straight to restoreFiles
+ */
+export async function listDeletedBetween(
+  jobId: string,
+  timestampGood: number,
+  timestampNow: number
+): Promise<DeletedFiles> {
+  return invoke('list_deleted_between', { jobId, timestampGood, timestampNow });
 }
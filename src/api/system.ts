@@ -30,6 +30,16 @@ export async function testNotification(): Promise<boolean> {
   return invoke('test_notification');
 }
 
+// ===== Diagnostics =====
+
+/**
+ * Benchmark the index on a destination for a support bundle
+ * Unlike devRunBenchmarks this works in release builds, against real data.
+ */
+export async function runIndexBenchmarks(destPath: string): Promise<DevBenchmarkResult[]> {
+  return invoke('run_index_benchmarks', { destPath });
+}
+
 // ===== Utilities =====
 
 export async function isDev(): Promise<boolean> {
@@ -155,3 +165,16 @@ export async function needsMigration(): Promise<boolean> {
 export async function runMigration(): Promise<MigrationReport> {
   return invoke('run_migration');
 }
+
+// ===== Long operations =====
+
+/**
+ * Cancel a running long operation by id, "<kind>:<key>": restore:<jobId>,
+ * index:<jobId>:<timestamp>, index-backfill:<destPath>,
+ * index-warmup:<mountPath>, snapshot-export:<jobId>:<timestamp>,
+ * volume-search:<volumePath> or fts-rebuild:<jobId>
+ * Resolves false if none was running
+ */
+export async function cancelOperation(opId: string): Promise<boolean> {
+  return invoke('cancel_operation', { opId });
+}
@@ -416,7 +416,7 @@ export const DevTools: React.FC<DevToolsProps> = ({ onClose }) => {
             <Caption color="tertiary">
               <strong>Seed Mock Data</strong> creates a dev playground at{' '}
               <Code size="sm" className="bg-layer-3 px-1 py-0.5 rounded">
-                ~/.amber-dev/
+                &lt;app data&gt;/dev-playground/
               </Code>{' '}
               with 1 backup job, ~20,000 files (realistic monorepo), and 3 rsync snapshots with
               churn between each.
@@ -436,7 +436,7 @@ export const DevTools: React.FC<DevToolsProps> = ({ onClose }) => {
               </Code>{' '}
               and won't affect real backups. Clear removes all dev jobs and deletes{' '}
               <Code size="sm" className="bg-layer-3 px-1 py-0.5 rounded">
-                ~/.amber-dev/
+                &lt;app data&gt;/dev-playground/
               </Code>
               .
             </Caption>
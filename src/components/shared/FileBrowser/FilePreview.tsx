@@ -19,6 +19,7 @@ export const FilePreview: React.FC<FilePreviewProps> = ({ filePath, fileName, fi
   const [content, setContent] = useState<string | null>(null);
   const [loading, setLoading] = useState(false);
   const [error, setError] = useState<string | null>(null);
+  const [truncated, setTruncated] = useState(false);
   const [previewType, setPreviewType] = useState<
     'image' | 'text' | 'code' | 'json' | 'unsupported'
   >('unsupported');
@@ -61,6 +62,7 @@ export const FilePreview: React.FC<FilePreviewProps> = ({ filePath, fileName, fi
     const ext = getFileExtension(fileName);
     const type = determinePreviewType(ext);
     setPreviewType(type);
+    setTruncated(false);
 
     if (type === 'unsupported') {
       setContent(null);
@@ -99,8 +101,9 @@ export const FilePreview: React.FC<FilePreviewProps> = ({ filePath, fileName, fi
     setError(null);
 
     try {
-      const fileContent = await api.readFilePreview(filePath, PREVIEW_LINES);
-      setContent(fileContent);
+      const preview = await api.readFilePreview(filePath, PREVIEW_LINES);
+      setContent(preview.content);
+      setTruncated(preview.truncated);
     } catch (err: unknown) {
       setError(getErrorMessage(err) || 'Failed to load preview');
     } finally {
@@ -157,10 +160,10 @@ export const FilePreview: React.FC<FilePreviewProps> = ({ filePath, fileName, fi
                 {content}
               </Code>
             </pre>
-            {fileSize > MAX_TEXT_SIZE && (
+            {truncated && (
               <div className="p-4 border-t border-gray-200 dark:border-gray-700 bg-yellow-50 dark:bg-yellow-900/10">
                 <Caption className="text-yellow-600 dark:text-yellow-500">
-                  ⚠️ Preview truncated - file is larger than {formatBytes(MAX_TEXT_SIZE)}
+                  ⚠️ Preview truncated - only the start of the file is shown
                 </Caption>
               </div>
             )}
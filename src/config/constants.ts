@@ -11,6 +11,8 @@
 export const FILE_TYPE = {
   DIR: 'dir',
   FILE: 'file',
+  /** Fifo, socket or device: listed, but skipped on restore */
+  SPECIAL: 'special',
 } as const;
 
 export type FileType = (typeof FILE_TYPE)[keyof typeof FILE_TYPE];
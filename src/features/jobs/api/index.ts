@@ -4,7 +4,15 @@
  */
 
 import { invoke } from '@tauri-apps/api/core';
-import type { SyncJob, JobWithStatus, DiscoveredBackup } from '@/types';
+import type {
+  SyncJob,
+  JobWithStatus,
+  DiscoveredBackup,
+  ExcludePreview,
+  SshConfig,
+  SshTestResult,
+  JobValidation,
+} from '@/types';
 
 // ===== Job CRUD =====
 
@@ -21,14 +29,42 @@ export async function getJobsWithStatus(): Promise<JobWithStatus[]> {
   return invoke('get_jobs_with_status');
 }
 
-export async function saveJob(job: SyncJob): Promise<void> {
-  return invoke('save_job', { job });
+/**
+ * Save a job; with validate, a dry run starts in the background once it is
+ * saved and its JobValidation arrives as a `job-validation` event
+ */
+export async function saveJob(job: SyncJob, validate?: boolean): Promise<void> {
+  return invoke('save_job', { job, validate });
+}
+
+/**
+ * Check a saved job works: source and destination reachable, excludes sane,
+ * and an rsync dry run over the top level of the source gets through
+ */
+export async function validateJob(jobId: string): Promise<JobValidation> {
+  return invoke('validate_job', { jobId });
+}
+
+/**
+ * Pause or resume a job's schedule; the schedule and history are kept
+ * Resolves with the updated job
+ */
+export async function setJobEnabled(jobId: string, enabled: boolean): Promise<SyncJob> {
+  return invoke('set_job_enabled', { jobId, enabled });
 }
 
 export async function deleteJob(jobId: string): Promise<void> {
   return invoke('delete_job', { jobId });
 }
 
+/**
+ * Change a job's id; its indexed snapshots and manifest follow it
+ * Resolves with the job under its new id
+ */
+export async function renameJobId(oldId: string, newId: string): Promise<SyncJob> {
+  return invoke('rename_job_id', { oldId, newId });
+}
+
 /**
  * Delete backup data from the destination path
  * This permanently removes all snapshots from the backup drive
@@ -37,6 +73,29 @@ export async function deleteJobData(jobId: string, destPath: string): Promise<vo
   return invoke('delete_job_data', { jobId, destPath });
 }
 
+/**
+ * Dry-run exclude patterns against the top level of a source folder
+ * Reports which entries would be skipped and flags patterns like `/` or `*`
+ */
+export async function previewExcludes(
+  sourcePath: string,
+  patterns: string[]
+): Promise<ExcludePreview> {
+  return invoke('preview_excludes', { sourcePath, patterns });
+}
+
+/**
+ * "Test Connection" for an SSH destination before saving the job
+ * Logs in with the job's SSH settings and checks the remote folder is writable;
+ * `diagnosis` tells auth, host key, reachability, path and permission problems apart
+ */
+export async function testSshDestination(
+  sshConfig: SshConfig,
+  remote: string
+): Promise<SshTestResult> {
+  return invoke('test_ssh_destination', { sshConfig, remote });
+}
+
 // ===== Orphan Backup Detection (TIM-118) =====
 
 /**
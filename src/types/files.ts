@@ -14,6 +14,15 @@ export interface FileNode {
   size: number;
   modified: number;
   children?: FileNode[];
+  /** Hard links to this file in the same snapshot, set only when more than one */
+  linkCount?: number;
+}
+
+/** Files in one snapshot that are hard links of each other */
+export interface HardlinkGroup {
+  inode: number;
+  size: number;
+  paths: string[];
 }
 
 /** Directory entry from filesystem commands */
@@ -39,10 +48,27 @@ export interface ReadDirEntry {
   name: string;
   path: string;
   isDirectory: boolean;
+  isSymlink: boolean;
   size: number;
   modified: number;
+  /** Present when the entry's metadata could not be read */
+  error?: string;
+}
+
+/** Server-side sorting/filtering for readDir */
+export interface ReadDirOptions {
+  sortBy?: 'name' | 'size' | 'modified';
+  descending?: boolean;
+  directoriesFirst?: boolean;
+  nameContains?: string;
 }
 
+/** Derived cleanup flag stored per file at index time */
+export type FileFlag = 'large' | 'duplicate' | 'stale';
+
+/** Broad kind of file by extension, for "only images" style filters */
+export type FileCategory = 'images' | 'documents' | 'audio' | 'video' | 'archives' | 'code';
+
 /** TIM-101: File type stats from SQLite index */
 export interface FileTypeStats {
   extension: string;
@@ -50,6 +76,21 @@ export interface FileTypeStats {
   totalSize: number;
 }
 
+/** Files of one size range in a snapshot */
+export interface SizeBucket {
+  minSize: number; // inclusive, bytes
+  maxSize: number | null; // exclusive; null for the last bucket
+  count: number;
+  totalSize: number;
+}
+
+/** Count and size of one extension in one snapshot */
+export interface ExtensionGrowthPoint {
+  timestamp: number;
+  count: number;
+  totalSize: number;
+}
+
 /** TIM-101: Largest file info from SQLite index */
 export interface LargestFile {
   name: string;
@@ -73,3 +114,21 @@ export interface GlobalSearchResult {
   snapshot_timestamp: number;
   rank: number;
 }
+
+/** A match from searching every snapshot of one job */
+export interface JobSearchResult {
+  file: FileNode;
+  /** Path relative to the snapshot root, the same in every snapshot */
+  relPath: string;
+  /** Newest snapshot this match came from */
+  snapshotTimestamp: number;
+  /** Every snapshot holding this path, newest first (more than one only when collapsed) */
+  snapshotTimestamps: number[];
+}
+
+/** A snapshot holding a given path, with the file's size and mtime (ms) in it */
+export interface PathOccurrence {
+  timestamp: number;
+  size: number;
+  mtime: number;
+}
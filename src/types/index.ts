@@ -9,12 +9,24 @@ export {
   DestinationType,
   JobStatus,
   type RsyncConfig,
+  type BandwidthWindow,
+  type AgeFilter,
+  type DeleteThreshold,
   type SshConfig,
   type CloudConfig,
   type JobSchedule,
   type SyncJob,
   type JobMountInfo,
   type JobAggregateStats,
+  type JobLogicalFootprint,
+  type JobOverview,
+  type Overview,
+  type ExcludeWarning,
+  type ExcludePreview,
+  type SshDiagnosis,
+  type SshTestResult,
+  type JobCheck,
+  type JobValidation,
 } from './jobs';
 
 // Snapshots
@@ -24,12 +36,37 @@ export {
   type SnapshotInfo,
   type SnapshotDensity,
   type DirectoryContents,
+  type SortBy,
+  type Subtree,
+  type Crumb,
+  type RestoreEstimate,
+  type ConflictStrategy,
+  type RestoreProgress,
   type ManifestSnapshotStatus,
   type ManifestSnapshot,
   type BackupManifest,
   type DiffEntry,
   type DiffSummary,
+  type RenamedEntry,
+  type SymlinkRetarget,
   type SnapshotDiff,
+  type DiffTruncation,
+  type DiffCategory,
+  type DiffBatch,
+  type DiffPageRequest,
+  type DiffPage,
+  type DeletedFile,
+  type DeletedFiles,
+  type CompactionResult,
+  type RetentionPolicy,
+  type PrunedSnapshot,
+  type SourceComparison,
+  type SourceChange,
+  type DirectoryDiff,
+  type SnapshotChanges,
+  type IndexDrift,
+  type SourceVolume,
+  type RunConfig,
 } from './snapshots';
 
 // Files
@@ -42,9 +79,17 @@ export {
   type DirEntry,
   type IndexedDirEntry,
   type ReadDirEntry,
+  type ReadDirOptions,
+  type FileFlag,
+  type FileCategory,
   type FileTypeStats,
+  type SizeBucket,
+  type ExtensionGrowthPoint,
   type LargestFile,
   type GlobalSearchResult,
+  type JobSearchResult,
+  type PathOccurrence,
+  type HardlinkGroup,
 } from './files';
 
 // System
@@ -62,6 +107,7 @@ export {
   type RsyncProgressData,
   type BackupResult,
   type RsyncLogPayload,
+  type RsyncProgress,
   type RsyncProgressPayload,
   type RsyncCompletePayload,
   type RsyncStartedPayload,
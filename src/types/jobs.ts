@@ -26,11 +26,55 @@ export interface RsyncConfig {
   compress: boolean;
   archive: boolean;
   delete: boolean;
+  /** Delete strategy when delete is on (default AUTO = plain --delete) */
+  deleteMode?: 'AUTO' | 'BEFORE' | 'DURING' | 'AFTER';
+  /** Also remove destination files matching excludePatterns */
+  deleteExcluded?: boolean;
+  /** Refuse a backup whose dry run would delete more than this (needs delete) */
+  maxDelete?: DeleteThreshold;
   verbose: boolean;
+  /** Output detail (default FULL); PROGRESS/QUIET skip the per-file listing on huge runs */
+  verbosity?: 'FULL' | 'PROGRESS' | 'QUIET';
   excludePatterns: string[];
   linkDest?: string;
   customFlags: string;
   customCommand?: string;
+  /** Descend into filesystems mounted inside the source (drops --one-file-system) */
+  crossFilesystems?: boolean;
+  /** Let rsync create missing destination parent folders (--mkpath, rsync 3.2.3+) */
+  createDest?: boolean;
+  /** Copy extended attributes such as Finder tags (-X) */
+  preserveXattrs?: boolean;
+  /** Copy POSIX ACLs (-A) */
+  preserveAcls?: boolean;
+  /** Cap on the transfer rate in KiB/s (--bwlimit); unset or 0 is unlimited */
+  bwlimitKbps?: number;
+  /** Rate limits by local time of day; they replace bwlimitKbps inside their windows */
+  bandwidthSchedule?: BandwidthWindow[];
+  /** Force an rsync protocol version (--protocol) to work with a much older remote rsync */
+  protocolVersion?: number;
+  /** Back up only files in this modification-time window (local sources only) */
+  ageFilter?: AgeFilter;
+}
+
+/** How much of the destination a --delete run may remove: a file count or a percentage */
+export type DeleteThreshold = { count: number } | { percent: number };
+
+/** Modification-time window for a backup; both bounds in days */
+export interface AgeFilter {
+  /** Skip files last modified more than this many days ago */
+  maxAgeDays?: number;
+  /** Skip files modified within the last this many days */
+  minAgeDays?: number;
+}
+
+/** Daily window capping a job's transfer rate; `end` before `start` wraps past midnight */
+export interface BandwidthWindow {
+  /** "HH:MM" */
+  start: string;
+  /** "HH:MM", exclusive */
+  end: string;
+  bwlimitKbps: number;
 }
 
 export interface SshConfig {
@@ -41,6 +85,12 @@ export interface SshConfig {
   disableHostKeyChecking?: boolean;
   proxyJump?: string;
   customSshOptions?: string;
+  /** Compress the transfer even if the job doesn't (unset = on) */
+  compress?: boolean;
+  /** ssh ServerAliveInterval in seconds (unset = 15, 0 = off) */
+  keepaliveIntervalSecs?: number;
+  /** ssh ServerAliveCountMax (unset = 4) */
+  keepaliveCountMax?: number;
 }
 
 export interface CloudConfig {
@@ -50,6 +100,14 @@ export interface CloudConfig {
   encryptPasswordKeychain?: string;
   bandwidth?: string;
   provider?: string;
+  /** rclone --retries (whole-sync retries by rclone; default 3) */
+  retries?: number;
+  /** rclone --low-level-retries (per operation; default 10) */
+  lowLevelRetries?: number;
+  /** rclone --timeout in seconds for idle connections (default 5 minutes) */
+  timeoutSeconds?: number;
+  /** Runs Amber makes of a sync that ends with a temporary error (default 2) */
+  syncAttempts?: number;
 }
 
 export interface JobSchedule {
@@ -63,6 +121,11 @@ export interface SyncJob {
   name: string;
   sourcePath: string;
   destPath: string;
+  /**
+   * Folder inside destPath for this job's backups; may use {source_basename}
+   * and {host}. Defaults to the source folder's name.
+   */
+  destSubfolder?: string;
   mode: SyncMode;
   destinationType: DestinationType;
   scheduleInterval: number | null;
@@ -71,6 +134,12 @@ export interface SyncJob {
   sshConfig?: SshConfig;
   cloudConfig?: CloudConfig;
   lastRun: number | null;
+  /** False while paused: the schedule is kept but never fires (defaults to true) */
+  enabled?: boolean;
+  /** Index each snapshot right after its backup; when false it is indexed on request (defaults to true) */
+  autoIndex?: boolean;
+  /** Extra rsync environment; only locale, TZ and RSYNC_* behaviour variables are accepted */
+  env?: Record<string, string>;
   status: JobStatus;
   snapshots?: Snapshot[];
 }
@@ -90,3 +159,83 @@ export interface JobAggregateStats {
   firstSnapshotMs: number | null;
   lastSnapshotMs: number | null;
 }
+
+/**
+ * Approximate unique content across a job's snapshots: shared (hardlinked)
+ * files count once, so this is far below the naive per-snapshot sum
+ */
+export interface JobLogicalFootprint {
+  uniqueSizeBytes: number;
+  naiveSizeBytes: number;
+  uniqueFiles: number;
+}
+
+/** One job's line in the storage overview */
+export interface JobOverview {
+  jobId: string;
+  name: string;
+  snapshots: number;
+  logicalBytes: number;
+  physicalBytes: number;
+  /** Milliseconds; null if the job has never backed up */
+  lastBackupMs: number | null;
+  /** The schedule fired since the last backup without one being made */
+  overdue: boolean;
+  /** Why no index could be read for this job; its figures are then zero */
+  error: string | null;
+}
+
+/** Totals across every job for the dashboard */
+export interface Overview {
+  totalJobs: number;
+  totalSnapshots: number;
+  logicalBytes: number;
+  physicalBytes: number;
+  lastBackupMs: number | null;
+  overdueJobs: string[];
+  jobs: JobOverview[];
+}
+
+/** A pattern that is almost certainly a mistake (e.g. `/` or `*`) */
+export interface ExcludeWarning {
+  pattern: string;
+  reason: string;
+}
+
+/** What an SSH destination connection test found */
+export type SshDiagnosis =
+  | 'ok'
+  | 'authFailed'
+  | 'hostKey'
+  | 'unreachable'
+  | 'pathNotFound'
+  | 'permissionDenied'
+  | 'failed';
+
+/** Outcome of testSshDestination; message is safe to show as is */
+export interface SshTestResult {
+  ok: boolean;
+  diagnosis: SshDiagnosis;
+  message: string;
+}
+
+/** A part of a job checked by validateJob */
+export type JobCheck = 'source' | 'destination' | 'excludes' | 'dryRun';
+
+/** Outcome of validating a job, checks in the order they ran */
+export interface JobValidation {
+  jobId: string;
+  ok: boolean;
+  checks: {
+    check: JobCheck;
+    ok: boolean;
+    message: string; // safe to show as is
+  }[];
+}
+
+/** Result of dry-running exclude patterns against the top level of a source */
+export interface ExcludePreview {
+  totalEntries: number;
+  excluded: string[]; // top-level entries rsync would skip
+  warnings: ExcludeWarning[];
+}
@@ -29,6 +29,16 @@ export interface RsyncLogPayload {
   message: string;
 }
 
+/** A progress update read into numbers */
+export interface RsyncProgress {
+  bytesDone: number;
+  /** Estimated from bytesDone and percent; null before any progress */
+  totalBytes: number | null;
+  percent: number;
+  rateBytesPerSec: number;
+  etaSeconds: number;
+}
+
 export interface RsyncProgressPayload {
   jobId: string;
   transferred: string;
@@ -36,6 +46,7 @@ export interface RsyncProgressPayload {
   speed: string;
   eta: string;
   currentFile?: string;
+  progress?: RsyncProgress | null;
 }
 
 export interface RsyncCompletePayload {
@@ -2,6 +2,14 @@
  * Snapshot-related type definitions
  */
 import type { FileNode } from './files';
+import type { RsyncConfig, SshConfig } from './jobs';
+
+/** Files changed since the job's previous snapshot */
+export interface SnapshotChanges {
+  added: number;
+  modified: number;
+  deleted: number;
+}
 
 export interface Snapshot {
   id: string;
@@ -9,6 +17,7 @@ export interface Snapshot {
   sizeBytes: number;
   fileCount: number;
   changesCount: number;
+  indexDrift?: IndexDrift;
   status: 'Complete' | 'Partial' | 'Failed';
   duration?: number;
   restored?: boolean;
@@ -25,6 +34,20 @@ export interface IndexedSnapshot {
   rootPath: string;
   fileCount: number;
   totalSize: number;
+  /** Only the source's listing was recorded; nothing can be restored */
+  metadataOnly: boolean;
+}
+
+/** Index/disk file count mismatch found by post-backup verification */
+export interface IndexDrift {
+  indexedFiles: number;
+  diskFiles: number;
+}
+
+/** Volume the backup source was on when a snapshot was taken */
+export interface SourceVolume {
+  isExternal: boolean;
+  volumeName: string | null; // set for external volumes only
 }
 
 /** TIM-110: Snapshot info from manifest */
@@ -34,6 +57,11 @@ export interface SnapshotInfo {
   sizeBytes: number;
   fileCount: number;
   changesCount: number;
+  /** Breakdown of changesCount; absent for the first snapshot */
+  changes?: SnapshotChanges;
+  indexDrift?: IndexDrift;
+  sourceVolume?: SourceVolume;
+  runConfig?: RunConfig;
   status: 'Complete' | 'Partial' | 'Failed';
   duration?: number;
   path?: string;
@@ -53,9 +81,61 @@ export interface DirectoryContents {
   hasMore: boolean;
 }
 
+/** Order of a directory listing; ties go by name, A to Z */
+export interface SortBy {
+  field?: 'name' | 'size' | 'mtime';
+  descending?: boolean;
+  /** Folders ahead of everything else (default true) */
+  directoriesFirst?: boolean;
+}
+
+/**
+ * A directory's descendants a few levels deep. Directories without `children`
+ * weren't expanded (too deep, or over the node limit).
+ */
+export interface Subtree {
+  nodes: FileNode[];
+  nodeCount: number;
+  truncated: boolean;
+}
+
+/** One breadcrumb; `parentPath` lists that directory ("" is the snapshot root) */
+export interface Crumb {
+  name: string;
+  parentPath: string;
+}
+
+/** What restoring a selection will write; directories are not counted */
+export interface RestoreEstimate {
+  fileCount: number;
+  totalBytes: number;
+  /** Fifos, sockets and devices in the selection, which restore skips */
+  skippedSpecial: number;
+}
+
+/** What restore does with a file already at the target */
+export type ConflictStrategy = 'overwrite' | 'skip' | 'keepNewer';
+
+/** Payload of a `restore-progress` event (parallel restores only) */
+export interface RestoreProgress {
+  jobId: string;
+  filesDone: number;
+  filesTotal: number;
+  bytesDone: number;
+  bytesTotal: number;
+  filesSkipped: number; // already at the target and kept per the conflict strategy
+  currentPath: string;
+}
+
 // Manifest types (TIM-114)
 export type ManifestSnapshotStatus = 'Complete' | 'Partial' | 'Failed';
 
+/** Job settings a backup ran with; secrets in free-text fields read `<redacted>` */
+export interface RunConfig {
+  rsync: RsyncConfig;
+  ssh?: SshConfig;
+}
+
 export interface ManifestSnapshot {
   id: string;
   timestamp: number;
@@ -65,6 +145,11 @@ export interface ManifestSnapshot {
   status: ManifestSnapshotStatus;
   durationMs?: number;
   changesCount?: number;
+  changes?: SnapshotChanges;
+  indexDrift?: IndexDrift;
+  sourceVolume?: SourceVolume;
+  /** Absent on snapshots recorded before it was kept */
+  runConfig?: RunConfig;
 }
 
 export interface BackupManifest {
@@ -86,18 +171,144 @@ export interface DiffEntry {
   sizeB: number | null; // size in snapshot B (null if deleted)
 }
 
+/** A file that moved between snapshots (same content hash and size) */
+export interface RenamedEntry {
+  from: string;
+  to: string;
+  size: number;
+}
+
+/** A symlink that points somewhere else in the newer snapshot */
+export interface SymlinkRetarget {
+  path: string;
+  targetA: string;
+  targetB: string;
+}
+
 /** TIM-221: Summary statistics for snapshot diff */
 export interface DiffSummary {
   totalAdded: number;
   totalDeleted: number;
   totalModified: number;
+  /** Same size, different content hash; counted in totalModified too */
+  contentChanged: number;
+  totalRenamed: number;
+  totalSymlinkRetargeted: number;
   sizeDelta: number; // positive = grew, negative = shrunk
 }
 
+/** Which diff lists were cut short by the limit (summary has full totals) */
+export interface DiffTruncation {
+  added: boolean;
+  deleted: boolean;
+  modified: boolean;
+}
+
 /** TIM-221: Complete snapshot diff result */
 export interface SnapshotDiff {
   added: DiffEntry[];
   deleted: DiffEntry[];
   modified: DiffEntry[];
+  renamed: RenamedEntry[]; // only detected when both snapshots carry content hashes
+  symlinkRetargeted: SymlinkRetarget[];
+  truncated: DiffTruncation;
   summary: DiffSummary;
+  topContributors: DiffEntry[]; // largest size changes first; empty unless requested
+}
+
+export type DiffCategory = 'added' | 'deleted' | 'modified';
+
+/** Which slice of a diff category to fetch */
+export interface DiffPageRequest {
+  category: DiffCategory;
+  offset?: number; // defaults to 0
+  limit: number;
+}
+
+/** Payload of a `snapshot-diff-batch` event from compareSnapshotsStream */
+export interface DiffBatch {
+  jobId: string;
+  timestampA: number;
+  timestampB: number;
+  category: DiffCategory;
+  entries: DiffEntry[];
+}
+
+/** A file that differs between the latest snapshot (A) and the live source (B) */
+export interface SourceChange {
+  path: string;
+  sizeA: number | null;
+  sizeB: number | null;
+  mtimeA: number | null; // Unix ms
+  mtimeB: number | null; // Unix ms
+}
+
+/** What the next backup would change, from compareSourceToSnapshot */
+export interface SourceComparison {
+  snapshotTimestamp: number; // latest indexed snapshot compared against
+  removed: SourceChange[]; // in the snapshot, gone from the source
+  added: SourceChange[]; // new in the source
+  modified: SourceChange[]; // size or mtime changed
+  unprotectedBytes: number; // in added and modified files no backup holds yet
+}
+
+/** How directory B differs from directory A on disk, from diffDirectories */
+export interface DirectoryDiff {
+  onlyInA: DiffEntry[]; // sizeA set
+  onlyInB: DiffEntry[]; // sizeB set
+  sizeDiffers: DiffEntry[];
+  totals: {
+    onlyInA: number;
+    onlyInB: number;
+    sizeDiffers: number;
+    matching: number; // same path and size in both
+  };
+  truncated: boolean; // a list hit the limit; totals still count everything
+}
+
+/** One page of a single diff category, for lazy-loading long lists */
+export interface DiffPage {
+  category: DiffCategory;
+  entries: DiffEntry[];
+  offset: number;
+  total: number; // entries in this category across all pages
+  hasMore: boolean;
+}
+
+/** A file of the last-good snapshot that a later one no longer has */
+export interface DeletedFile {
+  path: string; // relative to the snapshot root
+  size: number;
+}
+
+/** Everything deleted between two snapshots, ready to pass to restoreFiles */
+export interface DeletedFiles {
+  snapshotPath: string; // the last-good snapshot's folder
+  files: DeletedFile[];
+  totalBytes: number;
+}
+
+/** What compacting one destination's index did */
+export interface CompactionResult {
+  destPath: string;
+  bytesBefore: number | null; // index size including its WAL; null when skipped
+  bytesAfter: number | null;
+  note: string | null; // why it was skipped or failed
+}
+
+/** How many snapshots a retention policy keeps per time bucket (UTC) */
+export interface RetentionPolicy {
+  keepHourly?: number;
+  keepDaily?: number;
+  keepWeekly?: number;
+  keepMonthly?: number;
+  /** Always keep this many of the most recent snapshots as well */
+  keepLast?: number | null;
+}
+
+/** A snapshot folder pruned, or that would be on a dry run */
+export interface PrunedSnapshot {
+  timestamp: number;
+  folderName: string;
+  path: string;
 }
@@ -8,6 +8,11 @@ export interface DiskStats {
   total: number;
   free: number;
   status: 'AVAILABLE' | 'UNAVAILABLE';
+  /** Mount point of the filesystem holding the path, when it could be resolved */
+  mountPoint?: string;
+  fsType?: string;
+  /** SMB/NFS/AFP share: hardlinks and symlinks depend on the server */
+  isNetwork?: boolean;
 }
 
 /** TIM-47: Volume info for file search palette */
@@ -34,6 +39,36 @@ export interface AppPreferences {
   notifications: boolean;
   theme: string;
   accentColor: string;
+  /** Fold diacritics in file search ("cafe" also finds "café"); off makes accented queries exact */
+  searchFoldDiacritics?: boolean;
+  /** Experimental: new indexes share rows for files unchanged between snapshots */
+  normalizedIndexStorage?: boolean;
+  /** Store names byte-exact instead of NFC-normalized; macOS and Linux spellings then no longer match */
+  preserveRawPathNames?: boolean;
+  /** Largest file (in MB) the file preview will load into memory */
+  maxPreviewSizeMb?: number;
+  /** Hold off indexing while a backup is writing to the same volume */
+  serializeIndexWithBackups?: boolean;
+  /** Recount each new snapshot after indexing and flag index/disk drift */
+  verifyIndexAfterBackup?: boolean;
+  /** Vacuum an index on its next open after this many snapshot deletions (0 = never) */
+  compactAfterDeletions?: number;
+  /** Refuse to index snapshots with more files and folders than this (0 = no limit) */
+  maxIndexFiles?: number;
+  /** Files at least this many MB are flagged large when indexed */
+  largeFileMb?: number;
+  /** Files unmodified for this many days before their snapshot are flagged stale when indexed */
+  staleAfterDays?: number;
+  /** Run rsync under nice/ionice and index walks at lowered priority (Unix only) */
+  backgroundPriority?: boolean;
+  /** Fail TimeMachine backups to destinations without hardlinks instead of warning */
+  requireHardlinks?: boolean;
+  /** Latest snapshots per job whose root folder is preloaded when their drive connects (0 = off) */
+  warmupSnapshotCount?: number;
+  /** Threads that copy restored files directly, faster for many small files (0 = use rsync) */
+  restoreWorkers?: number;
+  /** Minimum level written to the log file ("error" through "trace", or "off") */
+  logLevel?: string;
 }
 
 /** TIM-110: Job with mount status and manifest snapshots */